@@ -0,0 +1,21 @@
+#!/bin/bash
+set -e
+
+# Regenerate the offline query data in .sqlx/ for the sqlx::query! macros.
+# Run this after changing any checked query or the database schema,
+# and commit the results.
+
+cd "$(dirname "$0")/.."
+db_file="$(mktemp -d)/vzdv_schema.sqlite"
+
+echo "Creating schema database"
+cargo run --quiet -p vzdv --example create_schema -- "$db_file"
+
+echo "Recording query data"
+rm -rf .sqlx
+mkdir .sqlx
+touch vzdv-tasks/src/main.rs vzdv-bot/src/main.rs vzdv-import/src/main.rs
+DATABASE_URL="sqlite://$db_file" SQLX_OFFLINE_DIR="$(pwd)/.sqlx" cargo check --workspace --all-targets
+
+rm "$db_file"
+echo "Done"
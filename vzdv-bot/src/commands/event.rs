@@ -0,0 +1,556 @@
+use super::{
+    command_name, component_id, linked_controller, quick_resp, BotCommand, CommandContext,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+use sqlx::{Pool, Sqlite};
+use twilight_interactions::command::{ApplicationCommandData, CommandModel, CreateCommand};
+use twilight_model::{
+    application::interaction::InteractionData,
+    channel::message::{
+        component::{ActionRow, Button, ButtonStyle, SelectMenu, SelectMenuOption},
+        Component, MessageFlags,
+    },
+    gateway::payload::incoming::InteractionCreate,
+    http::interaction::InteractionResponse,
+};
+use twilight_util::builder::{
+    embed::{EmbedBuilder, EmbedFieldBuilder, ImageSource},
+    InteractionResponseDataBuilder,
+};
+use vzdv::{
+    event_posts, permissions,
+    sql::{Controller, EventPosition, EventRegistration},
+};
+
+#[derive(Debug, CommandModel, CreateCommand)]
+#[command(name = "event", desc = "Post event info or positions")]
+struct EventOptions;
+
+/// Custom ID prefix of the position select menu on posted event positions.
+const SIGNUP_PREFIX: &str = "signup,";
+/// Custom ID prefix of the withdraw button on posted event positions.
+const WITHDRAW_PREFIX: &str = "withdraw,";
+/// Number of position choices a registration holds.
+const SIGNUP_CHOICES: u8 = 3;
+/// Discord's limit on the number of options in a select menu.
+const SELECT_MENU_MAX_OPTIONS: usize = 25;
+
+/// Custom ID of the event selection dropdown.
+const EVENT_SELECTION: &str = "event_selection";
+/// Custom ID prefix of the post overview and positions buttons.
+const ACTION_PREFIX: &str = "action_";
+
+/// Post event info and positions, and handle registrations from posted positions.
+///
+/// Posting is for event staff; registering is for all linked controllers.
+pub struct EventCommand;
+
+#[async_trait]
+impl BotCommand for EventCommand {
+    fn create_command(&self) -> ApplicationCommandData {
+        EventOptions::create_command()
+    }
+
+    fn matches(&self, event: &InteractionCreate) -> bool {
+        if command_name(event) == Some("event") {
+            return true;
+        }
+        component_id(event).is_some_and(|id| {
+            id == EVENT_SELECTION
+                || id.starts_with(ACTION_PREFIX)
+                || id.starts_with(SIGNUP_PREFIX)
+                || id.starts_with(WITHDRAW_PREFIX)
+        })
+    }
+
+    async fn handle(&self, ctx: &CommandContext<'_>, event: &InteractionCreate) -> Result<()> {
+        let (interaction, http, config, db) = (&ctx.interaction, ctx.http, ctx.config, ctx.db);
+        let Some(controller) = linked_controller(ctx, event).await? else {
+            return Ok(());
+        };
+        let author_id = event.author_id().unwrap();
+
+        // registration components are for everyone
+        if let Some(InteractionData::MessageComponent(component)) = &event.data {
+            let signup = if let Some(event_id) = component.custom_id.strip_prefix(SIGNUP_PREFIX) {
+                Some((event_id, Some(component.values.as_slice())))
+            } else {
+                component
+                    .custom_id
+                    .strip_prefix(WITHDRAW_PREFIX)
+                    .map(|event_id| (event_id, None))
+            };
+            if let Some((event_id, choices)) = signup {
+                let message = handle_signup(&controller, event_id, choices, db).await?;
+                interaction
+                    .create_response(event.id, &event.token, &quick_resp(&message))
+                    .await?;
+                return Ok(());
+            }
+        }
+
+        // everything else is for event staff
+        if !permissions::has_permission(db, controller.cid, permissions::EVENTS_MANAGE).await? {
+            interaction
+                .create_response(
+                    event.id,
+                    &event.token,
+                    &quick_resp("This command is for event staff"),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        match &event.0.data.as_ref().unwrap() {
+            InteractionData::ApplicationCommand(_app_command) => {
+                info!("Got event command by {author_id}; building dropdown");
+                let now = Utc::now();
+                let events: Vec<vzdv::sql::Event> = sqlx::query_as!(
+                    vzdv::sql::Event,
+                    r#"
+SELECT
+    id AS "id: u32",
+    published AS "published: bool",
+    name,
+    start AS "start: DateTime<Utc>",
+    end AS "end: DateTime<Utc>",
+    description,
+    image_url,
+    partner_facility,
+    external_signup_url
+FROM event
+WHERE end > $1
+"#,
+                    now
+                )
+                .fetch_all(db)
+                .await?;
+                if events.is_empty() {
+                    interaction.create_response(event.id, &event.token, &InteractionResponse {
+                        kind: twilight_model::http::interaction::InteractionResponseType::ChannelMessageWithSource,
+                        data: Some(InteractionResponseDataBuilder::new()
+                            .content("No upcoming events found")
+                            .flags(MessageFlags::EPHEMERAL)
+                            .components(None)
+                            .build()
+                        ),
+                    })
+                    .await?;
+                    return Ok(());
+                }
+                let component = Component::ActionRow(ActionRow {
+                    components: vec![Component::SelectMenu(SelectMenu {
+                        custom_id: String::from(EVENT_SELECTION),
+                        disabled: false,
+                        max_values: Some(1),
+                        min_values: Some(1),
+                        options: events
+                            .iter()
+                            .map(|event| SelectMenuOption {
+                                default: false,
+                                description: None,
+                                emoji: None,
+                                label: event.name.clone(),
+                                value: event.id.to_string(),
+                            })
+                            .collect(),
+                        placeholder: Some(String::from("Select an event")),
+                    })],
+                });
+                debug!("Rendering event selection dropdown");
+                interaction.create_response(event.id, &event.token, &InteractionResponse {
+                    kind: twilight_model::http::interaction::InteractionResponseType::ChannelMessageWithSource,
+                    data: Some(InteractionResponseDataBuilder::new()
+                        .content("Select an event")
+                        .flags(MessageFlags::EPHEMERAL)
+                        .components([component])
+                        .build()
+                    ),
+                })
+                .await?;
+            }
+            InteractionData::MessageComponent(component) => {
+                if component.custom_id == EVENT_SELECTION {
+                    let event_id = match component.values.first() {
+                        Some(id) => id,
+                        None => {
+                            warn!("No event id in dropdown selection");
+                            return Ok(());
+                        }
+                    };
+                    info!("Got event dropdown selection: {event_id}");
+                    let component = Component::ActionRow(ActionRow {
+                        components: Vec::from([
+                            Component::Button(Button {
+                                style: ButtonStyle::Primary,
+                                emoji: None,
+                                label: Some(String::from("Post overview")),
+                                custom_id: Some(format!("action_overview,{event_id}")),
+                                url: None,
+                                disabled: false,
+                            }),
+                            Component::Button(Button {
+                                style: ButtonStyle::Primary,
+                                emoji: None,
+                                label: Some(String::from("Post positions")),
+                                custom_id: Some(format!("action_positions,{event_id}")),
+                                url: None,
+                                disabled: false,
+                            }),
+                        ]),
+                    });
+                    debug!("Rendering action buttons");
+                    interaction.create_response(event.id, &event.token, &InteractionResponse {
+                        kind: twilight_model::http::interaction::InteractionResponseType::UpdateMessage,
+                        data: Some(InteractionResponseDataBuilder::new()
+                            .content("Select an option")
+                            .flags(MessageFlags::EPHEMERAL)
+                            .components([component])
+                            .build()
+                        ),
+                    })
+                    .await?;
+                } else if component.custom_id.starts_with(ACTION_PREFIX) {
+                    let (action, event_id) = {
+                        let mut split = component.custom_id.split(',');
+                        let action = match split.next() {
+                            Some(a) => a,
+                            None => {
+                                warn!("Could not find action in button click");
+                                return Ok(());
+                            }
+                        };
+                        let event_id = match split.next() {
+                            Some(id) => id,
+                            None => {
+                                warn!("Could not find event ID in button click");
+                                return Ok(());
+                            }
+                        };
+                        (action, event_id)
+                    };
+                    info!("Got action {action} for event {event_id} by {author_id}");
+
+                    // let event = let events: Vec<vzdv::sql::Event>
+                    let db_event: Option<vzdv::sql::Event> = sqlx::query_as!(
+                        vzdv::sql::Event,
+                        r#"
+SELECT
+    id AS "id: u32",
+    published AS "published: bool",
+    name,
+    start AS "start: DateTime<Utc>",
+    end AS "end: DateTime<Utc>",
+    description,
+    image_url,
+    partner_facility,
+    external_signup_url
+FROM event
+WHERE id=$1
+"#,
+                        event_id
+                    )
+                    .fetch_optional(db)
+                    .await?;
+                    let db_event = match db_event {
+                        Some(e) => e,
+                        None => {
+                            warn!("Could not find event with id {event_id}");
+                            return Ok(());
+                        }
+                    };
+
+                    let mut components = Vec::new();
+                    let embed = {
+                        let mut embed = EmbedBuilder::new()
+                            .title(db_event.name)
+                            .url(event_posts::event_url(&config.hosted_domain, db_event.id));
+                        if let Some(url) = db_event.image_url {
+                            embed = embed.image(ImageSource::url(url)?);
+                        }
+                        if action == "action_overview" {
+                            embed = embed
+                                .field(
+                                    EmbedFieldBuilder::new(
+                                        "Start",
+                                        format!(
+                                            "<t:{}:f>",
+                                            db_event.start.timestamp_millis() / 1_000
+                                        ),
+                                    )
+                                    .inline(),
+                                )
+                                .field(
+                                    EmbedFieldBuilder::new(
+                                        "End",
+                                        format!(
+                                            "<t:{}:f>",
+                                            db_event.end.timestamp_millis() / 1_000
+                                        ),
+                                    )
+                                    .inline(),
+                                )
+                                .field(EmbedFieldBuilder::new(
+                                    "Description",
+                                    db_event.description.unwrap_or_default(),
+                                ));
+                        } else {
+                            let controllers: Vec<Controller> = sqlx::query_as!(
+                                Controller,
+                                r#"
+SELECT
+    id AS "id: u32",
+    cid AS "cid: u32",
+    first_name,
+    last_name,
+    operating_initials,
+    rating AS "rating!: i8",
+    status AS "status!",
+    discord_id,
+    home_facility AS "home_facility!",
+    is_on_roster AS "is_on_roster!: bool",
+    roles AS "roles!",
+    join_date AS "join_date: DateTime<Utc>",
+    loa_until AS "loa_until: DateTime<Utc>"
+FROM controller
+"#
+                            )
+                            .fetch_all(db)
+                            .await?;
+                            let positions: Vec<EventPosition> = sqlx::query_as!(
+                                EventPosition,
+                                r#"
+SELECT
+    id AS "id: u32",
+    event_id AS "event_id: u32",
+    name,
+    category,
+    cid AS "cid: u32"
+FROM event_position
+WHERE event_id=$1
+"#,
+                                event_id
+                            )
+                            .fetch_all(db)
+                            .await?;
+                            components = signup_components(db_event.id, &positions);
+                            for (position, assignee) in
+                                event_posts::assignments(&positions, &controllers, true)
+                            {
+                                embed = embed
+                                    .field(EmbedFieldBuilder::new(position, assignee).inline());
+                            }
+                            embed = embed.description(format!(
+                                "{}; register for positions below",
+                                event_posts::HEADING
+                            ));
+                        }
+                        embed.validate()?.build()
+                    };
+                    interaction.create_response(event.id, &event.token, &InteractionResponse {
+                        kind: twilight_model::http::interaction::InteractionResponseType::UpdateMessage,
+                        data: Some(InteractionResponseDataBuilder::new().content("Info posted").flags(MessageFlags::EPHEMERAL).components(None).build())
+                    }).await?;
+                    http.create_message(event.channel.as_ref().unwrap().id)
+                        .embeds(&[embed])?
+                        .components(&components)?
+                        .await?;
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// Build the components that let controllers register for the event's positions.
+fn signup_components(event_id: u32, positions: &[EventPosition]) -> Vec<Component> {
+    let mut components = Vec::new();
+    if !positions.is_empty() {
+        let options: Vec<SelectMenuOption> = positions
+            .iter()
+            .take(SELECT_MENU_MAX_OPTIONS)
+            .map(|position| SelectMenuOption {
+                default: false,
+                description: None,
+                emoji: None,
+                label: position.name.clone(),
+                value: position.id.to_string(),
+            })
+            .collect();
+        components.push(Component::ActionRow(ActionRow {
+            components: vec![Component::SelectMenu(SelectMenu {
+                custom_id: format!("{SIGNUP_PREFIX}{event_id}"),
+                disabled: false,
+                max_values: Some(options.len().min(SIGNUP_CHOICES as usize) as u8),
+                min_values: Some(1),
+                options,
+                placeholder: Some(format!("Register: choose up to {SIGNUP_CHOICES} positions")),
+            })],
+        }));
+    }
+    components.push(Component::ActionRow(ActionRow {
+        components: vec![Component::Button(Button {
+            style: ButtonStyle::Secondary,
+            emoji: None,
+            label: Some(String::from("Withdraw registration")),
+            custom_id: Some(format!("{WITHDRAW_PREFIX}{event_id}")),
+            url: None,
+            disabled: false,
+        })],
+    }));
+    components
+}
+
+/// Register the controller for an event with their position choices, or withdraw them.
+///
+/// Choices are stored in the order Discord reports them. Any notes the
+/// controller left on the site are kept.
+async fn handle_signup(
+    controller: &Controller,
+    event_id: &str,
+    choices: Option<&[String]>,
+    db: &Pool<Sqlite>,
+) -> Result<String> {
+    let Ok(event_id) = event_id.parse::<u32>() else {
+        warn!("Could not parse event ID {event_id} in signup");
+        return Ok(String::from("Unknown event"));
+    };
+    let db_event: Option<vzdv::sql::Event> = sqlx::query_as!(
+        vzdv::sql::Event,
+        r#"
+SELECT
+    id AS "id: u32",
+    published AS "published: bool",
+    name,
+    start AS "start: DateTime<Utc>",
+    end AS "end: DateTime<Utc>",
+    description,
+    image_url,
+    partner_facility,
+    external_signup_url
+FROM event
+WHERE id=$1
+"#,
+        event_id
+    )
+    .fetch_optional(db)
+    .await?;
+    let db_event = match db_event {
+        Some(e) if e.published => e,
+        _ => return Ok(String::from("That event isn't available")),
+    };
+    if db_event.end < Utc::now() {
+        return Ok(String::from("That event has already ended"));
+    }
+    let existing: Option<EventRegistration> = sqlx::query_as!(
+        EventRegistration,
+        r#"
+SELECT
+    id AS "id: u32",
+    event_id AS "event_id: u32",
+    cid AS "cid: u32",
+    choice_1 AS "choice_1!: u32",
+    choice_2 AS "choice_2!: u32",
+    choice_3 AS "choice_3!: u32",
+    notes
+FROM event_registration
+WHERE event_id=$1 AND cid=$2
+"#,
+        event_id,
+        controller.cid
+    )
+    .fetch_optional(db)
+    .await?;
+
+    let Some(choices) = choices else {
+        return match existing {
+            Some(existing) => {
+                sqlx::query!("DELETE FROM event_registration WHERE id=$1", existing.id)
+                    .execute(db)
+                    .await?;
+                info!(
+                    "{} removed their registration to event {event_id} from Discord",
+                    controller.cid
+                );
+                Ok(format!(
+                    "Your registration for {} has been withdrawn",
+                    db_event.name
+                ))
+            }
+            None => Ok(format!("You aren't registered for {}", db_event.name)),
+        };
+    };
+
+    let positions: Vec<EventPosition> = sqlx::query_as!(
+        EventPosition,
+        r#"
+SELECT
+    id AS "id: u32",
+    event_id AS "event_id: u32",
+    name,
+    category,
+    cid AS "cid: u32"
+FROM event_position
+WHERE event_id=$1
+"#,
+        event_id
+    )
+    .fetch_all(db)
+    .await?;
+    let mut chosen: Vec<&EventPosition> = Vec::new();
+    for choice in choices.iter().take(SIGNUP_CHOICES as usize) {
+        match positions.iter().find(|p| p.id.to_string() == *choice) {
+            Some(position) => chosen.push(position),
+            None => return Ok(String::from("That position is no longer available")),
+        }
+    }
+    if chosen.is_empty() {
+        return Ok(String::from("Select at least one position"));
+    }
+    let choice_id = |index: usize| chosen.get(index).map(|p| p.id);
+    let (choice_1, choice_2, choice_3) = (choice_id(0), choice_id(1), choice_id(2));
+    let notes = existing.and_then(|e| e.notes).unwrap_or_default();
+    sqlx::query!(
+        "
+INSERT INTO event_registration
+    (event_id, cid, choice_1, choice_2, choice_3, notes)
+VALUES
+    ($1, $2, $3, $4, $5, $6)
+ON CONFLICT DO UPDATE SET
+    choice_1=$3,
+    choice_2=$4,
+    choice_3=$5,
+    notes=$6",
+        event_id,
+        controller.cid,
+        choice_1,
+        choice_2,
+        choice_3,
+        notes
+    )
+    .execute(db)
+    .await?;
+    info!(
+        "{} registered for event {event_id} from Discord: {}",
+        controller.cid,
+        chosen
+            .iter()
+            .map(|p| p.id.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+    Ok(format!(
+        "You're registered for {}: {}",
+        db_event.name,
+        chosen
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    ))
+}
@@ -0,0 +1,108 @@
+use super::{command_name, quick_resp, BotCommand, CommandContext};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use log::info;
+use twilight_interactions::command::{
+    ApplicationCommandData, CommandInputData, CommandModel, CreateCommand,
+};
+use twilight_model::{
+    application::interaction::InteractionData, gateway::payload::incoming::InteractionCreate,
+};
+use vzdv::sql::DiscordLinkCode;
+
+#[derive(Debug, CommandModel, CreateCommand)]
+#[command(
+    name = "link",
+    desc = "Link your Discord account with a code from the website"
+)]
+struct LinkOptions {
+    /// Code from the website's Discord page
+    code: String,
+}
+
+/// Link the user's Discord account to the controller that generated the code.
+///
+/// Available to everyone, as the user isn't linked yet.
+pub struct LinkCommand;
+
+#[async_trait]
+impl BotCommand for LinkCommand {
+    fn create_command(&self) -> ApplicationCommandData {
+        LinkOptions::create_command()
+    }
+
+    fn matches(&self, event: &InteractionCreate) -> bool {
+        command_name(event) == Some("link")
+    }
+
+    async fn handle(&self, ctx: &CommandContext<'_>, event: &InteractionCreate) -> Result<()> {
+        let (interaction, db) = (&ctx.interaction, ctx.db);
+        let Some(InteractionData::ApplicationCommand(command)) = &event.data else {
+            return Ok(());
+        };
+        let command = LinkOptions::from_interaction(CommandInputData::from((**command).clone()))?;
+        let Some(user_id) = event.author_id() else {
+            interaction
+                .create_response(
+                    event.id,
+                    &event.token,
+                    &quick_resp("Discord isn't sharing your user ID"),
+                )
+                .await?;
+            return Ok(());
+        };
+        let code = command.code.trim().to_uppercase();
+        let now = Utc::now();
+        let link_code: Option<DiscordLinkCode> = sqlx::query_as!(
+            DiscordLinkCode,
+            r#"
+SELECT
+    cid AS "cid: u32",
+    code,
+    expires AS "expires: DateTime<Utc>"
+FROM discord_link_code
+WHERE code=$1 AND expires > $2
+"#,
+            code,
+            now
+        )
+        .fetch_optional(db)
+        .await?;
+        let Some(link_code) = link_code else {
+            interaction
+                .create_response(
+                    event.id,
+                    &event.token,
+                    &quick_resp(
+                        "That code is invalid or has expired; get a new one from the website",
+                    ),
+                )
+                .await?;
+            return Ok(());
+        };
+        let discord_id = user_id.get().to_string();
+        sqlx::query!(
+            "UPDATE controller SET discord_id=$2 WHERE cid=$1",
+            link_code.cid,
+            discord_id
+        )
+        .execute(db)
+        .await?;
+        sqlx::query!("DELETE FROM discord_link_code WHERE cid=$1", link_code.cid)
+            .execute(db)
+            .await?;
+        info!(
+            "Set Discord ID for controller {} to {user_id} via link code",
+            link_code.cid
+        );
+        interaction
+            .create_response(
+                event.id,
+                &event.token,
+                &quick_resp("Your Discord account is linked; your roles will update shortly"),
+            )
+            .await?;
+        Ok(())
+    }
+}
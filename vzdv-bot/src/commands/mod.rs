@@ -0,0 +1,176 @@
+//! Slash commands and the message components they post.
+//!
+//! Each command lives in its own module and implements [`BotCommand`].
+//! Add new commands to [`all`] to have them registered with Discord and
+//! sent their interactions.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use log::debug;
+use sqlx::{Pool, Sqlite};
+use std::sync::Arc;
+use twilight_gateway::Event;
+use twilight_http::{client::InteractionClient, Client};
+use twilight_interactions::command::ApplicationCommandData;
+use twilight_model::{
+    application::interaction::InteractionData, channel::message::MessageFlags,
+    gateway::payload::incoming::InteractionCreate, http::interaction::InteractionResponse, id::Id,
+};
+use twilight_util::builder::InteractionResponseDataBuilder;
+use vzdv::{config::Config, sql::Controller};
+
+mod event;
+mod link;
+mod online;
+
+/// What commands need to respond to an interaction.
+pub struct CommandContext<'a> {
+    pub http: &'a Arc<Client>,
+    pub interaction: InteractionClient<'a>,
+    pub config: &'a Arc<Config>,
+    pub db: &'a Pool<Sqlite>,
+}
+
+/// A slash command, along with any message components it posts.
+#[async_trait]
+pub trait BotCommand: Send + Sync {
+    /// Definition of the slash command to register with Discord.
+    fn create_command(&self) -> ApplicationCommandData;
+
+    /// Whether the interaction is for this command, either the slash
+    /// command itself or one of the command's components.
+    fn matches(&self, event: &InteractionCreate) -> bool;
+
+    /// Respond to the interaction.
+    async fn handle(&self, ctx: &CommandContext<'_>, event: &InteractionCreate) -> Result<()>;
+}
+
+/// All of the bot's commands.
+pub fn all() -> Vec<Box<dyn BotCommand>> {
+    vec![
+        Box::new(event::EventCommand),
+        Box::new(link::LinkCommand),
+        Box::new(online::OnlineCommand),
+    ]
+}
+
+/// Name of the slash command, if the interaction is one.
+fn command_name(event: &InteractionCreate) -> Option<&str> {
+    match &event.data {
+        Some(InteractionData::ApplicationCommand(command)) => Some(command.name.as_str()),
+        _ => None,
+    }
+}
+
+/// Custom ID of the message component, if the interaction is from one.
+fn component_id(event: &InteractionCreate) -> Option<&str> {
+    match &event.data {
+        Some(InteractionData::MessageComponent(component)) => Some(component.custom_id.as_str()),
+        _ => None,
+    }
+}
+
+/// Build a simple ephemeral response with a `String` message.
+fn quick_resp(message: &str) -> InteractionResponse {
+    InteractionResponse {
+        kind: twilight_model::http::interaction::InteractionResponseType::ChannelMessageWithSource,
+        data: Some(
+            InteractionResponseDataBuilder::new()
+                .flags(MessageFlags::EPHEMERAL)
+                .content(message)
+                .build(),
+        ),
+    }
+}
+
+/// Look up the controller that created the interaction.
+///
+/// Responds to the user and returns `None` if they can't be identified.
+async fn linked_controller(
+    ctx: &CommandContext<'_>,
+    event: &InteractionCreate,
+) -> Result<Option<Controller>> {
+    let interaction = &ctx.interaction;
+    // author ID check
+    let user_id = match event.author_id() {
+        Some(id) => id,
+        None => {
+            // I don't know when this would be triggered
+            interaction
+                .create_response(
+                    event.id,
+                    &event.token,
+                    &quick_resp("Discord isn't sharing your user ID"),
+                )
+                .await?;
+            return Ok(None);
+        }
+    };
+    // controller lookup
+    let discord_id = user_id.get().to_string();
+    let controller: Option<Controller> = sqlx::query_as!(
+        Controller,
+        r#"
+SELECT
+    id AS "id: u32",
+    cid AS "cid: u32",
+    first_name,
+    last_name,
+    operating_initials,
+    rating AS "rating!: i8",
+    status AS "status!",
+    discord_id,
+    home_facility AS "home_facility!",
+    is_on_roster AS "is_on_roster!: bool",
+    roles AS "roles!",
+    join_date AS "join_date: DateTime<Utc>",
+    loa_until AS "loa_until: DateTime<Utc>"
+FROM controller
+WHERE discord_id=$1
+"#,
+        discord_id
+    )
+    .fetch_optional(ctx.db)
+    .await?;
+    if controller.is_none() {
+        // unknown user
+        interaction
+            .create_response(
+                event.id,
+                &event.token,
+                &quick_resp("You have not linked your Discord to the website"),
+            )
+            .await?;
+    }
+    Ok(controller)
+}
+
+/// Command handler.
+///
+/// Sends interactions to the command they're for.
+pub async fn handler(
+    raw_event: &Event,
+    http: &Arc<Client>,
+    bot_id: u64,
+    config: &Arc<Config>,
+    db: &Pool<Sqlite>,
+) -> Result<()> {
+    let Event::InteractionCreate(event) = raw_event else {
+        // some other type of event; don't care
+        return Ok(());
+    };
+    let ctx = CommandContext {
+        http,
+        interaction: http.interaction(Id::new(bot_id)),
+        config,
+        db,
+    };
+    for command in all() {
+        if command.matches(event) {
+            return command.handle(&ctx, event).await;
+        }
+    }
+    debug!("No command for interaction {}", event.id);
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use super::{command_name, BotCommand, CommandContext};
+use crate::tasks;
+use anyhow::Result;
+use async_trait::async_trait;
+use twilight_interactions::command::{ApplicationCommandData, CommandModel, CreateCommand};
+use twilight_model::{
+    gateway::payload::incoming::InteractionCreate, http::interaction::InteractionResponse,
+};
+use twilight_util::builder::InteractionResponseDataBuilder;
+
+#[derive(Debug, CommandModel, CreateCommand)]
+#[command(
+    name = "online",
+    desc = "Show the facility controllers that are online"
+)]
+struct OnlineOptions;
+
+/// Show the online controllers embed.
+///
+/// Available to everyone, linked or not.
+pub struct OnlineCommand;
+
+#[async_trait]
+impl BotCommand for OnlineCommand {
+    fn create_command(&self) -> ApplicationCommandData {
+        OnlineOptions::create_command()
+    }
+
+    fn matches(&self, event: &InteractionCreate) -> bool {
+        command_name(event) == Some("online")
+    }
+
+    async fn handle(&self, ctx: &CommandContext<'_>, event: &InteractionCreate) -> Result<()> {
+        let embed = tasks::online::latest_embed(ctx.config, ctx.db).await?;
+        ctx.interaction
+            .create_response(
+                event.id,
+                &event.token,
+                &InteractionResponse {
+                    kind: twilight_model::http::interaction::InteractionResponseType::ChannelMessageWithSource,
+                    data: Some(InteractionResponseDataBuilder::new().embeds([embed]).build()),
+                },
+            )
+            .await?;
+        Ok(())
+    }
+}
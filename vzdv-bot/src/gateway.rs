@@ -0,0 +1,162 @@
+//! Gateway connections.
+//!
+//! Each shard runs its own receive loop. The gateway library reconnects and
+//! resumes sessions on its own for recoverable errors; when a shard closes
+//! fatally, it's rebuilt with exponential backoff, resuming its last session
+//! if it had one, instead of the bot going idle.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::{debug, error, info, warn};
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+use tokio::time::sleep;
+use twilight_gateway::{
+    stream, Config as GatewayConfig, ConfigBuilder, ConnectionStatus, Event, Intents, Shard,
+};
+use twilight_http::Client;
+use vzdv::{config::Config, error_reporting};
+
+/// Wait before the first attempt to rebuild a fatally closed shard.
+const BACKOFF_START: Duration = Duration::from_secs(1);
+/// Longest wait between attempts to rebuild a fatally closed shard.
+const BACKOFF_MAX: Duration = Duration::from_secs(60 * 5);
+/// How often to log the shards' connection states.
+const METRICS_INTERVAL: Duration = Duration::from_secs(60 * 5);
+
+/// Connection state of a single shard.
+#[derive(Debug, Default, Clone)]
+struct ShardMetrics {
+    status: String,
+    events: u64,
+    last_event: Option<DateTime<Utc>>,
+    /// Times the shard has been rebuilt after closing fatally.
+    rebuilds: u32,
+    latency: Option<Duration>,
+}
+
+/// Connection state of all shards, by shard number.
+static METRICS: LazyLock<Mutex<BTreeMap<u64, ShardMetrics>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+/// Short name of the shard's connection status.
+fn status_name(status: &ConnectionStatus) -> String {
+    match status {
+        ConnectionStatus::Connected => String::from("connected"),
+        ConnectionStatus::Disconnected {
+            reconnect_attempts, ..
+        } => format!("disconnected ({reconnect_attempts} reconnect attempts)"),
+        ConnectionStatus::FatallyClosed { close_code } => {
+            format!("fatally closed ({close_code:?})")
+        }
+        ConnectionStatus::Identifying => String::from("identifying"),
+        ConnectionStatus::Resuming => String::from("resuming"),
+    }
+}
+
+/// Record the shard's current state.
+fn update_metrics(shard: &Shard, received_event: bool, rebuilt: bool) {
+    let mut metrics = METRICS.lock().unwrap();
+    let entry = metrics.entry(shard.id().number()).or_default();
+    entry.status = status_name(shard.status());
+    entry.latency = shard.latency().average();
+    if received_event {
+        entry.events += 1;
+        entry.last_event = Some(Utc::now());
+    }
+    if rebuilt {
+        entry.rebuilds += 1;
+    }
+}
+
+/// Create the shards, using Discord's recommended count if the config has 0.
+pub async fn create_shards(config: &Config, http: &Client, intents: Intents) -> Result<Vec<Shard>> {
+    let gateway_config = GatewayConfig::new(config.discord.bot_token.clone(), intents);
+    let shards: Vec<Shard> = if config.discord.shards == 0 {
+        stream::create_recommended(http, gateway_config, |_, builder| builder.build())
+            .await?
+            .collect()
+    } else {
+        let total = config.discord.shards;
+        stream::create_range(0..total, total, gateway_config, |_, builder| {
+            builder.build()
+        })
+        .collect()
+    };
+    info!("Created {} gateway shard(s)", shards.len());
+    Ok(shards)
+}
+
+/// Receive events from the shard for as long as the bot runs, passing each to `handler`.
+pub async fn run_shard<F, Fut>(mut shard: Shard, handler: F)
+where
+    F: Fn(Event) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let mut backoff = BACKOFF_START;
+    loop {
+        match shard.next_event().await {
+            Ok(event) => {
+                update_metrics(&shard, true, false);
+                backoff = BACKOFF_START;
+                tokio::spawn(handler(event));
+            }
+            Err(source) => {
+                update_metrics(&shard, false, false);
+                if !source.is_fatal() {
+                    warn!("Error receiving event on shard {}: {source}", shard.id());
+                    continue;
+                }
+                error!(
+                    "Shard {} closed fatally: {source}; rebuilding in {}s",
+                    shard.id(),
+                    backoff.as_secs()
+                );
+                error_reporting::capture_error("bot::gateway", &format!("{source:?}"));
+                sleep(backoff).await;
+                backoff = (backoff * 2).min(BACKOFF_MAX);
+
+                let mut builder = ConfigBuilder::from(shard.config().clone());
+                if let Some(session) = shard.session() {
+                    debug!("Resuming session on shard {}", shard.id());
+                    builder = builder.session(session.clone());
+                }
+                shard = Shard::with_config(shard.id(), builder.build());
+                update_metrics(&shard, false, true);
+            }
+        }
+    }
+}
+
+/// Log the connection state of each shard on an interval.
+pub async fn log_metrics() {
+    loop {
+        sleep(METRICS_INTERVAL).await;
+        let metrics = METRICS.lock().unwrap().clone();
+        for (number, shard) in metrics {
+            let message = format!(
+                "Shard {number}: {}, {} events, last at {}, {} rebuilds, latency {}",
+                shard.status,
+                shard.events,
+                shard
+                    .last_event
+                    .map(|date| date.to_rfc3339())
+                    .unwrap_or_else(|| String::from("never")),
+                shard.rebuilds,
+                shard
+                    .latency
+                    .map(|latency| format!("{}ms", latency.as_millis()))
+                    .unwrap_or_else(|| String::from("unknown"))
+            );
+            if shard.status == "connected" {
+                info!("{message}");
+            } else {
+                warn!("{message}");
+            }
+        }
+    }
+}
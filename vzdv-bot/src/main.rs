@@ -7,16 +7,16 @@
 use anyhow::Result;
 use base64::{engine::general_purpose, Engine as _};
 use clap::Parser;
-use log::{debug, error, info, warn};
+use log::{debug, error, info};
 use sqlx::{Pool, Sqlite};
 use std::{path::PathBuf, sync::Arc};
-use twilight_gateway::{Event, Intents, Shard, ShardId};
+use twilight_gateway::{Event, Intents};
 use twilight_http::Client as HttpClient;
-use twilight_interactions::command::CreateCommand;
 use twilight_model::id::Id;
-use vzdv::{config::Config, general_setup};
+use vzdv::{config::Config, error_reporting, general_setup};
 
 mod commands;
+mod gateway;
 mod tasks;
 
 /// vZDV Discord bot.
@@ -29,6 +29,12 @@ struct Cli {
     #[arg(long)]
     config: Option<PathBuf>,
 
+    /// Merge the config overlay for this environment, like "dev" for "vzdv.dev.toml".
+    ///
+    /// [default: the VZDV_ENV environment variable, if set]
+    #[arg(long)]
+    env: Option<String>,
+
     /// Enable debug logging
     #[arg(short, long)]
     debug: bool,
@@ -54,18 +60,26 @@ fn bot_id_from_token(token: &str) -> u64 {
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
-    let (config, db) = general_setup(cli.debug, "vzdv_bot", cli.config).await;
+    let (config, db) = general_setup(cli.debug, "vzdv_bot", cli.config, cli.env).await;
+    let _error_reporting = error_reporting::init(&config, "vzdv_bot");
     let config = Arc::new(config);
 
     let token = &config.discord.bot_token;
     let bot_id = bot_id_from_token(token);
     let intents = Intents::GUILD_MEMBERS;
-    let mut shard = Shard::new(ShardId::ONE, token.clone(), intents);
     let http = Arc::new(HttpClient::new(token.clone()));
+    let shards = gateway::create_shards(&config, &http, intents)
+        .await
+        .expect("Could not create gateway shards");
     let interaction_client = http.interaction(Id::new(bot_id));
 
     interaction_client
-        .set_global_commands(&[commands::EventCommand::create_command().into()])
+        .set_global_commands(
+            &commands::all()
+                .iter()
+                .map(|command| command.create_command().into())
+                .collect::<Vec<_>>(),
+        )
         .await
         .expect("Could not register commands");
 
@@ -98,26 +112,74 @@ async fn main() {
         });
     };
 
-    info!("Connected to Gateway");
-    loop {
-        let event = match shard.next_event().await {
-            Ok(event) => event,
-            Err(source) => {
-                warn!("Error receiving event: {:?}", source);
-                if source.is_fatal() {
-                    break;
-                }
-                continue;
-            }
-        };
+    {
+        let config = config.clone();
+        let db = db.clone();
         let http = http.clone();
+        tokio::spawn(async move {
+            tasks::welcome::process(config, db, http).await;
+        });
+    };
+
+    {
         let config = config.clone();
-        let db: Pool<Sqlite> = db.clone();
+        let db = db.clone();
+        let http = http.clone();
+        tokio::spawn(async move {
+            tasks::event_channels::process(config, db, http).await;
+        });
+    };
+
+    {
+        let config = config.clone();
+        let db = db.clone();
+        let http = http.clone();
+        tokio::spawn(async move {
+            tasks::next_event::process(config, db, http).await;
+        });
+    };
+
+    {
+        let config = config.clone();
+        let db = db.clone();
+        let http = http.clone();
         tokio::spawn(async move {
-            if let Err(e) = handle_event(event, http, bot_id, &config, &db).await {
-                error!("Error in future: {e}");
-            }
+            tasks::weekly_summary::process(config, db, http).await;
         });
+    };
+
+    {
+        let db = db.clone();
+        let http = http.clone();
+        tokio::spawn(async move {
+            tasks::solo_certs::process(db, http).await;
+        });
+    };
+
+    tokio::spawn(gateway::log_metrics());
+    info!("Connecting to Gateway");
+    let mut shard_handles = Vec::new();
+    for shard in shards {
+        let http = http.clone();
+        let config = config.clone();
+        let db: Pool<Sqlite> = db.clone();
+        shard_handles.push(tokio::spawn(gateway::run_shard(shard, move |event| {
+            let http = http.clone();
+            let config = config.clone();
+            let db = db.clone();
+            let event_kind = format!("{:?}", event.kind());
+            error_reporting::with_context(&[("event", event_kind)], None, async move {
+                if let Err(e) = handle_event(event, http, bot_id, &config, &db).await {
+                    error!("Error in future: {e}");
+                    error_reporting::capture_error("bot::event", &format!("{e:?}"));
+                }
+            })
+        })));
+    }
+    for handle in shard_handles {
+        if let Err(e) = handle.await {
+            error!("Shard task ended: {e}");
+        }
     }
 }
 
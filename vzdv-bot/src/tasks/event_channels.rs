@@ -0,0 +1,153 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use log::{debug, error, info, warn};
+use sqlx::{Pool, Sqlite};
+use std::{sync::Arc, time::Duration};
+use tokio::time::sleep;
+use twilight_http::{error::ErrorType, Client};
+use twilight_model::{channel::ChannelType, id::Id};
+use vzdv::{config::Config, error_reporting, sql::EventChannel};
+
+/// How long before an event starts to create its channels.
+const CREATE_BEFORE_MINUTES: i64 = 30;
+/// How long after an event ends to delete its channels.
+const DELETE_AFTER_MINUTES: i64 = 60;
+
+/// Create channels for events that are about to start.
+async fn create_channels(
+    config: &Arc<Config>,
+    db: &Pool<Sqlite>,
+    http: &Arc<Client>,
+) -> Result<()> {
+    let now = Utc::now();
+    let starts_before = now + ChronoDuration::minutes(CREATE_BEFORE_MINUTES);
+    let to_create: Vec<EventChannel> = sqlx::query_as!(
+        EventChannel,
+        r#"
+SELECT
+    event_channel.id AS "id: u32",
+    event_channel.event_id AS "event_id: u32",
+    event_channel.name,
+    event_channel.voice AS "voice: bool",
+    event_channel.channel_id,
+    event_channel.created_date AS "created_date: DateTime<Utc>",
+    event_channel.deleted_date AS "deleted_date: DateTime<Utc>"
+FROM event_channel JOIN event ON event_channel.event_id=event.id
+WHERE event.published=TRUE AND event.start <= $1 AND event.end > $2
+    AND event_channel.channel_id IS NULL
+"#,
+        starts_before,
+        now
+    )
+    .fetch_all(db)
+    .await?;
+    for event_channel in to_create {
+        let kind = if event_channel.voice {
+            ChannelType::GuildVoice
+        } else {
+            ChannelType::GuildText
+        };
+        let mut request = http
+            .create_guild_channel(Id::new(config.discord.guild_id), &event_channel.name)?
+            .kind(kind);
+        if config.discord.event_channel_category != 0 {
+            request = request.parent_id(Id::new(config.discord.event_channel_category));
+        }
+        let channel = request.await?.model().await?;
+        let channel_id = channel.id.get().to_string();
+        let created = Utc::now();
+        sqlx::query!(
+            "UPDATE event_channel SET channel_id=$2, created_date=$3 WHERE id=$1",
+            event_channel.id,
+            channel_id,
+            created
+        )
+        .execute(db)
+        .await?;
+        info!(
+            "Created channel {} for event {}",
+            event_channel.name, event_channel.event_id
+        );
+    }
+    Ok(())
+}
+
+/// Delete channels for events that are over.
+async fn delete_channels(db: &Pool<Sqlite>, http: &Arc<Client>) -> Result<()> {
+    let ended_before = Utc::now() - ChronoDuration::minutes(DELETE_AFTER_MINUTES);
+    let to_delete: Vec<EventChannel> = sqlx::query_as!(
+        EventChannel,
+        r#"
+SELECT
+    event_channel.id AS "id: u32",
+    event_channel.event_id AS "event_id: u32",
+    event_channel.name,
+    event_channel.voice AS "voice: bool",
+    event_channel.channel_id,
+    event_channel.created_date AS "created_date: DateTime<Utc>",
+    event_channel.deleted_date AS "deleted_date: DateTime<Utc>"
+FROM event_channel JOIN event ON event_channel.event_id=event.id
+WHERE event.end < $1 AND event_channel.channel_id IS NOT NULL
+    AND event_channel.deleted_date IS NULL
+"#,
+        ended_before
+    )
+    .fetch_all(db)
+    .await?;
+    for event_channel in to_delete {
+        let Some(channel_id) = event_channel
+            .channel_id
+            .as_ref()
+            .and_then(|id| id.parse::<u64>().ok())
+        else {
+            warn!(
+                "Could not parse channel ID for event channel {}",
+                event_channel.id
+            );
+            continue;
+        };
+        if let Err(e) = http.delete_channel(Id::new(channel_id)).await {
+            // already deleted by someone in Discord
+            let missing =
+                matches!(e.kind(), ErrorType::Response { status, .. } if status.get() == 404);
+            if !missing {
+                return Err(e.into());
+            }
+            warn!("Event channel {channel_id} was already deleted");
+        }
+        let now = Utc::now();
+        sqlx::query!(
+            "UPDATE event_channel SET deleted_date=$2 WHERE id=$1",
+            event_channel.id,
+            now
+        )
+        .execute(db)
+        .await?;
+        info!(
+            "Deleted channel {} for event {}",
+            event_channel.name, event_channel.event_id
+        );
+    }
+    Ok(())
+}
+
+/// Single loop execution.
+async fn tick(config: &Arc<Config>, db: &Pool<Sqlite>, http: &Arc<Client>) -> Result<()> {
+    create_channels(config, db, http).await?;
+    delete_channels(db, http).await?;
+    Ok(())
+}
+
+// Processing loop.
+pub async fn process(config: Arc<Config>, db: Pool<Sqlite>, http: Arc<Client>) {
+    sleep(Duration::from_secs(30)).await;
+    debug!("Starting event channel processing");
+
+    loop {
+        if let Err(e) = tick(&config, &db, &http).await {
+            error!("Error in event channel processing tick: {e}");
+            error_reporting::capture_error("bot::event_channels", &format!("{e:?}"));
+        }
+        sleep(Duration::from_secs(60)).await; // 1 minute
+    }
+}
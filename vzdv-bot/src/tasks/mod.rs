@@ -1,3 +1,8 @@
+pub mod event_channels;
+pub mod next_event;
 pub mod off_roster;
 pub mod online;
 pub mod roles;
+pub mod solo_certs;
+pub mod weekly_summary;
+pub mod welcome;
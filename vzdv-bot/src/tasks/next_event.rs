@@ -0,0 +1,103 @@
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use log::{debug, error, info};
+use sqlx::{Pool, Sqlite};
+use std::{sync::Arc, time::Duration};
+use tokio::time::sleep;
+use twilight_http::Client;
+use twilight_model::id::Id;
+use vzdv::{config::Config, error_reporting, sql::Event};
+
+/// Format the time until an event starts, to the nearest minute.
+fn countdown(until: TimeDelta) -> String {
+    let days = until.num_days();
+    let hours = until.num_hours() % 24;
+    let minutes = until.num_minutes() % 60;
+    if days > 0 {
+        format!("{days}d {hours}h")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else {
+        format!("{minutes}m")
+    }
+}
+
+/// Build the channel topic for the next published event.
+async fn build_topic(config: &Arc<Config>, db: &Pool<Sqlite>) -> Result<String> {
+    let now = Utc::now();
+    let events: Vec<Event> = sqlx::query_as!(
+        Event,
+        r#"
+SELECT
+    id AS "id: u32",
+    published AS "published: bool",
+    name,
+    start AS "start: DateTime<Utc>",
+    end AS "end: DateTime<Utc>",
+    description,
+    image_url,
+    partner_facility,
+    external_signup_url
+FROM event
+WHERE end > $1 AND published = TRUE
+"#,
+        now
+    )
+    .fetch_all(db)
+    .await?;
+    let Some(event) = events.into_iter().min_by_key(|event| event.start) else {
+        return Ok(String::from("No upcoming events"));
+    };
+    let link = format!("{}events/{}", config.hosted_domain, event.id);
+    if event.start <= now {
+        Ok(format!("{} is happening now! {link}", event.name))
+    } else {
+        Ok(format!(
+            "Next event: {} in {} - {link}",
+            event.name,
+            countdown(event.start - now)
+        ))
+    }
+}
+
+/// Single loop execution.
+///
+/// Returns the topic that the channel now has.
+async fn tick(
+    config: &Arc<Config>,
+    db: &Pool<Sqlite>,
+    http: &Arc<Client>,
+    last_topic: &str,
+) -> Result<String> {
+    let topic = build_topic(config, db).await?;
+    if topic != last_topic {
+        http.update_channel(Id::new(config.discord.next_event_channel))
+            .topic(&topic)?
+            .await?;
+        info!("Updated next event channel topic: {topic}");
+    }
+    Ok(topic)
+}
+
+// Processing loop.
+pub async fn process(config: Arc<Config>, db: Pool<Sqlite>, http: Arc<Client>) {
+    if config.discord.next_event_channel == 0 {
+        debug!("No next event channel configured");
+        return;
+    }
+    sleep(Duration::from_secs(30)).await;
+    debug!("Starting next event processing");
+
+    let mut last_topic = String::new();
+    loop {
+        match tick(&config, &db, &http, &last_topic).await {
+            Ok(topic) => last_topic = topic,
+            Err(e) => {
+                error!("Error in next event processing tick: {e}");
+                error_reporting::capture_error("bot::next_event", &format!("{e:?}"));
+            }
+        }
+        // Discord only allows changing a channel's topic twice every 10 minutes
+        sleep(Duration::from_secs(60 * 10)).await; // 10 minutes
+    }
+}
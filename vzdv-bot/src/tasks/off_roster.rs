@@ -7,19 +7,18 @@ use twilight_http::Client;
 use twilight_model::id::Id;
 use twilight_util::builder::embed::{EmbedBuilder, EmbedFieldBuilder};
 use vatsim_utils::live_api::Vatsim;
-use vzdv::{
-    config::Config,
-    position_in_facility_airspace,
-    sql::{self, Controller},
-};
+use vzdv::{config::Config, error_reporting, position_in_facility_airspace};
 
 /// Single loop execution.
 async fn tick(config: &Arc<Config>, db: &Pool<Sqlite>, http: &Arc<Client>) -> Result<()> {
     let data = Vatsim::new().await?.get_v3_data().await?;
-    let on_roster: Vec<Controller> = sqlx::query_as(sql::GET_ALL_CONTROLLERS_ON_ROSTER)
-        .fetch_all(db)
-        .await?;
-    let on_roster_cids: Vec<_> = on_roster.iter().map(|c| c.cid as u64).collect();
+    let on_roster_cids: Vec<u64> =
+        sqlx::query_scalar!(r#"SELECT cid AS "cid: u32" FROM controller WHERE is_on_roster=TRUE"#)
+            .fetch_all(db)
+            .await?
+            .into_iter()
+            .map(u64::from)
+            .collect();
 
     let mut violations = String::new();
     for online in data.controllers {
@@ -51,13 +50,21 @@ async fn tick(config: &Arc<Config>, db: &Pool<Sqlite>, http: &Arc<Client>) -> Re
 
 // Processing loop.
 pub async fn process(config: Arc<Config>, db: Pool<Sqlite>, http: Arc<Client>) {
+    if !config.discord.tasks.off_roster {
+        debug!("Off-roster controller processing disabled");
+        return;
+    }
     sleep(Duration::from_secs(30)).await;
     debug!("Starting off-roster controller processing");
 
     loop {
         if let Err(e) = tick(&config, &db, &http).await {
             error!("Error in off-roster controller processing tick: {e}");
+            error_reporting::capture_error("bot::off_roster", &format!("{e:?}"));
         }
-        sleep(Duration::from_secs(60 * 5)).await; // 5 minutes
+        sleep(Duration::from_secs(
+            config.discord.tasks.off_roster_interval_seconds,
+        ))
+        .await;
     }
 }
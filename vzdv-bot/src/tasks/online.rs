@@ -2,12 +2,27 @@ use anyhow::Result;
 use chrono::Utc;
 use log::{debug, error};
 use sqlx::{Pool, Sqlite};
-use std::{fmt::Write, sync::Arc, time::Duration};
+use std::{
+    fmt::Write,
+    sync::{Arc, LazyLock, Mutex},
+    time::Duration,
+};
 use tokio::time::sleep;
 use twilight_http::Client;
 use twilight_model::{channel::message::Embed, id::Id};
 use twilight_util::builder::embed::{EmbedBuilder, EmbedFieldBuilder, EmbedFooterBuilder};
-use vzdv::{config::Config, vatsim::get_online_facility_controllers};
+use vzdv::{config::Config, error_reporting, vatsim::get_online_facility_controllers};
+
+/// The most recent online controllers embed, for the `/online` command.
+static LATEST_EMBED: LazyLock<Mutex<Option<Embed>>> = LazyLock::new(|| Mutex::new(None));
+
+/// Get the online controllers embed from the last update, or build one if there isn't one yet.
+pub async fn latest_embed(config: &Arc<Config>, db: &Pool<Sqlite>) -> Result<Embed> {
+    if let Some(embed) = LATEST_EMBED.lock().unwrap().clone() {
+        return Ok(embed);
+    }
+    create_message(config, db).await
+}
 
 async fn create_message(config: &Arc<Config>, db: &Pool<Sqlite>) -> Result<Embed> {
     let data = get_online_facility_controllers(db, config).await?;
@@ -58,16 +73,18 @@ async fn create_message(config: &Arc<Config>, db: &Pool<Sqlite>) -> Result<Embed
 /// Single loop execution.
 async fn tick(config: &Arc<Config>, db: &Pool<Sqlite>, http: &Arc<Client>) -> Result<()> {
     let channel_id = Id::new(config.discord.online_channel);
+    let embed = create_message(config, db).await?;
+    *LATEST_EMBED.lock().unwrap() = Some(embed.clone());
     match config.discord.online_message {
         Some(id) => {
             http.update_message(channel_id, Id::new(id))
-                .embeds(Some(&[create_message(config, db).await?]))?
+                .embeds(Some(&[embed]))?
                 .await?;
         }
         None => {
             let resp = http
                 .create_message(channel_id)
-                .embeds(&[create_message(config, db).await?])?
+                .embeds(&[embed])?
                 .await?
                 .model()
                 .await?;
@@ -79,13 +96,21 @@ async fn tick(config: &Arc<Config>, db: &Pool<Sqlite>, http: &Arc<Client>) -> Re
 
 // Processing loop.
 pub async fn process(config: Arc<Config>, db: Pool<Sqlite>, http: Arc<Client>) {
+    if !config.discord.tasks.online {
+        debug!("Online processing disabled");
+        return;
+    }
     sleep(Duration::from_secs(30)).await;
     debug!("Starting online processing");
 
     loop {
         if let Err(e) = tick(&config, &db, &http).await {
             error!("Error in online processing tick: {e}");
+            error_reporting::capture_error("bot::online", &format!("{e:?}"));
         }
-        sleep(Duration::from_secs(60)).await; // 1 minute
+        sleep(Duration::from_secs(
+            config.discord.tasks.online_interval_seconds,
+        ))
+        .await;
     }
 }
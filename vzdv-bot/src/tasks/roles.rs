@@ -1,25 +1,26 @@
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use log::{debug, error, info};
 use sqlx::{Pool, Sqlite};
 use std::{sync::Arc, time::Duration};
 use tokio::time::sleep;
 use twilight_http::Client;
 use twilight_model::{
+    channel::message::AllowedMentions,
     guild::Member,
     id::{marker::GuildMarker, Id},
 };
-use vzdv::{
-    config::Config,
-    sql::{self, Controller},
-    ControllerRating,
-};
+use vzdv::{config::Config, error_reporting, sql::Controller, ControllerRating};
 
 /// Set the guild member's nickname if needed.
+///
+/// Changes are added to `audit` for the audit channel.
 async fn set_nickname(
     guild_id: Id<GuildMarker>,
     member: &Member,
     controller: &Controller,
     http: &Arc<Client>,
+    audit: &mut Vec<String>,
 ) -> Result<()> {
     let mut name = format!(
         "{} {}.",
@@ -59,12 +60,14 @@ async fn set_nickname(
     if let Some(existing) = &member.nick {
         if existing != &name {
             info!("Updating nick of {} to {name}", member.user.id);
+            audit.push(format!("Nickname: `{existing}` -> `{name}`"));
             // http.update_guild_member(guild_id, member.user.id)
             //     .nick(Some(&name))?
             //     .await?;
         }
     } else {
         info!("Setting nick of {} to {name}", member.user.id);
+        audit.push(format!("Nickname: none -> `{name}`"));
         // http.update_guild_member(guild_id, member.user.id)
         //     .nick(Some(&name))?
         //     .await?;
@@ -74,11 +77,14 @@ async fn set_nickname(
 }
 
 /// Resolve the guild member's roles, adding and removing as necessary.
+///
+/// Changes are added to `audit` for the audit channel.
 async fn resolve_roles(
     guild_id: Id<GuildMarker>,
     member: &Member,
     roles: &[(u64, bool)],
     http: &Arc<Client>,
+    audit: &mut Vec<String>,
 ) -> Result<()> {
     // TODO
 
@@ -90,6 +96,7 @@ async fn resolve_roles(
                 member.nick.as_ref().unwrap_or(&member.user.name),
                 member.user.id.get()
             );
+            audit.push(format!("Role added: <@&{id}>"));
             // http.add_guild_member_role(guild_id, member.user.id, Id::new(id))
             //     .await?;
         } else if !should_have && existing.contains(&id) {
@@ -98,6 +105,7 @@ async fn resolve_roles(
                 member.nick.as_ref().unwrap_or(&member.user.name),
                 member.user.id.get()
             );
+            audit.push(format!("Role removed: <@&{id}>"));
             // http.remove_guild_member_role(guild_id, member.user.id, Id::new(id))
             //     .await?;
         }
@@ -105,6 +113,33 @@ async fn resolve_roles(
     Ok(())
 }
 
+/// Post the changes made to a guild member to the audit channel, if configured.
+///
+/// Failures are only logged, so that they don't interrupt the sync.
+async fn post_audit(config: &Arc<Config>, http: &Arc<Client>, user_id: u64, changes: &[String]) {
+    if config.discord.audit_channel == 0 {
+        return;
+    }
+    let content = format!("<@{user_id}>\n{}", changes.join("\n"));
+    let request = match http
+        .create_message(Id::new(config.discord.audit_channel))
+        .content(&content)
+    {
+        Ok(request) => request,
+        Err(e) => {
+            error!("Could not build audit message for {user_id}: {e}");
+            return;
+        }
+    };
+    // mentions are only for display; don't ping anyone
+    if let Err(e) = request
+        .allowed_mentions(Some(&AllowedMentions::default()))
+        .await
+    {
+        error!("Could not post audit message for {user_id}: {e}");
+    }
+}
+
 /// Determine which roles the guild member should have.
 async fn get_correct_roles(
     config: &Arc<Config>,
@@ -223,18 +258,41 @@ async fn tick(config: &Arc<Config>, db: &Pool<Sqlite>, http: &Arc<Client>) -> Re
             continue;
         }
         debug!("Processing user {}", member.user.id);
-        let controller: Option<Controller> = sqlx::query_as(sql::GET_CONTROLLER_BY_DISCORD_ID)
-            .bind(user_id.to_string())
-            .fetch_optional(db)
-            .await?;
+        let discord_id = user_id.to_string();
+        let controller: Option<Controller> = sqlx::query_as!(
+            Controller,
+            r#"
+SELECT
+    id AS "id: u32",
+    cid AS "cid: u32",
+    first_name,
+    last_name,
+    operating_initials,
+    rating AS "rating!: i8",
+    status AS "status!",
+    discord_id,
+    home_facility AS "home_facility!",
+    is_on_roster AS "is_on_roster!: bool",
+    roles AS "roles!",
+    join_date AS "join_date: DateTime<Utc>",
+    loa_until AS "loa_until: DateTime<Utc>"
+FROM controller
+WHERE discord_id=$1
+"#,
+            discord_id
+        )
+        .fetch_optional(db)
+        .await?;
 
         // roles
         debug!("Determining roles to resolve for {} ({})", nick, user_id);
 
         // determine the roles the guild member should have and update accordingly
+        let mut audit = Vec::new();
         match get_correct_roles(config, member, &controller).await {
             Ok(to_resolve) => {
-                if let Err(e) = resolve_roles(guild_id, member, &to_resolve, http).await {
+                if let Err(e) = resolve_roles(guild_id, member, &to_resolve, http, &mut audit).await
+                {
                     error!("Error resolving roles for {nick} ({user_id}): {e}");
                 }
             }
@@ -244,12 +302,16 @@ async fn tick(config: &Arc<Config>, db: &Pool<Sqlite>, http: &Arc<Client>) -> Re
         }
 
         // nickname
-        if let Some(controller) = controller {
-            if let Err(e) = set_nickname(guild_id, member, &controller, http).await {
+        if let Some(controller) = controller.filter(|_| config.discord.tasks.nicknames) {
+            if let Err(e) = set_nickname(guild_id, member, &controller, http, &mut audit).await {
                 error!("Error setting nickname of {nick} ({user_id}): {e}");
             }
         }
 
+        if !audit.is_empty() {
+            post_audit(config, http, user_id, &audit).await;
+        }
+
         // short wait
         sleep(Duration::from_secs(1)).await;
     }
@@ -260,13 +322,21 @@ async fn tick(config: &Arc<Config>, db: &Pool<Sqlite>, http: &Arc<Client>) -> Re
 
 // Processing loop.
 pub async fn process(config: Arc<Config>, db: Pool<Sqlite>, http: Arc<Client>) {
+    if !config.discord.tasks.roles {
+        debug!("Roles processing disabled");
+        return;
+    }
     sleep(Duration::from_secs(30)).await;
     debug!("Starting roles processing");
 
     loop {
         if let Err(e) = tick(&config, &db, &http).await {
             error!("Error in roles processing tick: {e}");
+            error_reporting::capture_error("bot::roles", &format!("{e:?}"));
         }
-        sleep(Duration::from_secs(60 * 10)).await; // 10 minutes
+        sleep(Duration::from_secs(
+            config.discord.tasks.roles_interval_seconds,
+        ))
+        .await;
     }
 }
@@ -0,0 +1,188 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use log::{debug, error, info, warn};
+use sqlx::{Pool, Sqlite};
+use std::{sync::Arc, time::Duration};
+use tokio::time::sleep;
+use twilight_http::Client;
+use twilight_model::id::Id;
+use vzdv::{
+    error_reporting,
+    sql::{Controller, SoloCert},
+};
+
+/// Days before expiration to send reminders, largest first.
+const REMINDER_DAYS: [u32; 2] = [7, 1];
+
+/// Get the reminder that's due for the solo cert, if any.
+///
+/// Only the most recent reminder is sent, so a cert issued with less than
+/// a day left only gets the last one.
+fn due_reminder(solo_cert: &SoloCert, now: DateTime<Utc>) -> Option<u32> {
+    let remaining = solo_cert.expiration_date - now;
+    let due = REMINDER_DAYS
+        .iter()
+        .copied()
+        .filter(|days| remaining <= ChronoDuration::days(i64::from(*days)))
+        .min()?;
+    match solo_cert.reminder_days {
+        Some(sent) if sent <= due => None,
+        _ => Some(due),
+    }
+}
+
+/// DM the controller, if they've linked their Discord account.
+///
+/// Members can block DMs from the server, which isn't treated as an error.
+async fn send_dm(http: &Arc<Client>, controller: &Controller, content: &str) -> Result<()> {
+    let Some(user_id) = controller
+        .discord_id
+        .as_ref()
+        .and_then(|id| id.parse::<u64>().ok())
+    else {
+        debug!(
+            "{} has no Discord account for a solo cert DM",
+            controller.cid
+        );
+        return Ok(());
+    };
+    let channel = http
+        .create_private_channel(Id::new(user_id))
+        .await?
+        .model()
+        .await?;
+    if let Err(e) = http.create_message(channel.id).content(content)?.await {
+        warn!("Could not send solo cert DM to {}: {e}", controller.cid);
+    }
+    Ok(())
+}
+
+/// Single loop execution.
+async fn tick(db: &Pool<Sqlite>, http: &Arc<Client>) -> Result<()> {
+    let now = Utc::now();
+    let max_days = REMINDER_DAYS.iter().max().copied().unwrap_or_default();
+    let until = now + ChronoDuration::days(i64::from(max_days));
+    let expiring: Vec<SoloCert> = sqlx::query_as!(
+        SoloCert,
+        r#"
+SELECT
+    id AS "id: u32",
+    cid AS "cid: u32",
+    issued_by AS "issued_by: u32",
+    position,
+    reported AS "reported: bool",
+    created_date AS "created_date: DateTime<Utc>",
+    expiration_date AS "expiration_date: DateTime<Utc>",
+    reminder_days AS "reminder_days: u32"
+FROM solo_cert
+WHERE expiration_date > $1 AND expiration_date <= $2
+"#,
+        now,
+        until
+    )
+    .fetch_all(db)
+    .await?;
+    for solo_cert in expiring {
+        let Some(days) = due_reminder(&solo_cert, now) else {
+            continue;
+        };
+        let controller: Option<Controller> = sqlx::query_as!(
+            Controller,
+            r#"
+SELECT
+    id AS "id: u32",
+    cid AS "cid: u32",
+    first_name,
+    last_name,
+    operating_initials,
+    rating AS "rating!: i8",
+    status AS "status!",
+    discord_id,
+    home_facility AS "home_facility!",
+    is_on_roster AS "is_on_roster!: bool",
+    roles AS "roles!",
+    join_date AS "join_date: DateTime<Utc>",
+    loa_until AS "loa_until: DateTime<Utc>"
+FROM controller
+WHERE cid=$1
+"#,
+            solo_cert.cid
+        )
+        .fetch_optional(db)
+        .await?;
+        let mentor: Option<Controller> = sqlx::query_as!(
+            Controller,
+            r#"
+SELECT
+    id AS "id: u32",
+    cid AS "cid: u32",
+    first_name,
+    last_name,
+    operating_initials,
+    rating AS "rating!: i8",
+    status AS "status!",
+    discord_id,
+    home_facility AS "home_facility!",
+    is_on_roster AS "is_on_roster!: bool",
+    roles AS "roles!",
+    join_date AS "join_date: DateTime<Utc>",
+    loa_until AS "loa_until: DateTime<Utc>"
+FROM controller
+WHERE cid=$1
+"#,
+            solo_cert.issued_by
+        )
+        .fetch_optional(db)
+        .await?;
+        let expires = format!("<t:{}:f>", solo_cert.expiration_date.timestamp());
+        if let Some(controller) = &controller {
+            send_dm(
+                http,
+                controller,
+                &format!(
+                    "Your solo certification for {} expires {expires}. \
+                    Talk to your mentor about scheduling your checkout if you haven't already.",
+                    solo_cert.position
+                ),
+            )
+            .await?;
+            if let Some(mentor) = &mentor {
+                send_dm(
+                    http,
+                    mentor,
+                    &format!(
+                        "{} {}'s solo certification for {} expires {expires}.",
+                        controller.first_name, controller.last_name, solo_cert.position
+                    ),
+                )
+                .await?;
+            }
+        }
+        sqlx::query!(
+            "UPDATE solo_cert SET reminder_days=$2 WHERE id=$1",
+            solo_cert.id,
+            days
+        )
+        .execute(db)
+        .await?;
+        info!(
+            "Sent {days} day solo cert reminder for {} on {}",
+            solo_cert.cid, solo_cert.position
+        );
+    }
+    Ok(())
+}
+
+// Processing loop.
+pub async fn process(db: Pool<Sqlite>, http: Arc<Client>) {
+    sleep(Duration::from_secs(30)).await;
+    debug!("Starting solo cert reminder processing");
+
+    loop {
+        if let Err(e) = tick(&db, &http).await {
+            error!("Error in solo cert reminder processing tick: {e}");
+            error_reporting::capture_error("bot::solo_certs", &format!("{e:?}"));
+        }
+        sleep(Duration::from_secs(60 * 30)).await; // 30 minutes
+    }
+}
@@ -0,0 +1,175 @@
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, Timelike, Utc, Weekday};
+use log::{debug, error, info};
+use sqlx::{Pool, Sqlite};
+use std::{fmt::Write, sync::Arc, time::Duration};
+use tokio::time::sleep;
+use twilight_http::Client;
+use twilight_model::{channel::message::Embed, id::Id};
+use twilight_util::builder::embed::{EmbedBuilder, EmbedFieldBuilder};
+use vzdv::{
+    config::Config,
+    error_reporting,
+    sql::{Activity, Event},
+};
+
+/// Setting holding the date of the last summary post.
+const LAST_POSTED_SETTING: &str = "weekly_summary_last_posted";
+/// Day of the week to post the summary.
+const POST_WEEKDAY: Weekday = Weekday::Mon;
+/// Hour (UTC) on that day to post the summary.
+const POST_HOUR: u32 = 15;
+/// How far ahead to list events.
+const EVENTS_DAYS: i64 = 14;
+
+/// Build the summary embed.
+async fn create_message(config: &Arc<Config>, db: &Pool<Sqlite>) -> Result<Embed> {
+    let now = Utc::now();
+    let month = now.format("%Y-%m").to_string();
+    let activity: Vec<Activity> = sqlx::query_as!(
+        Activity,
+        r#"
+SELECT
+    activity.id AS "id: u32",
+    activity.cid AS "cid: u32",
+    controller.first_name,
+    controller.last_name,
+    activity.month,
+    activity.minutes AS "minutes: u32",
+    activity.source
+FROM activity INNER JOIN controller ON activity.cid = controller.cid
+WHERE activity.month=$1 AND activity.source='controlling'
+ORDER BY activity.minutes DESC
+"#,
+        month
+    )
+    .fetch_all(db)
+    .await?;
+    let total_minutes: u32 = activity.iter().map(|a| a.minutes).sum();
+    let top = activity
+        .iter()
+        .take(3)
+        .enumerate()
+        .fold(String::new(), |mut acc, (i, a)| {
+            writeln!(
+                acc,
+                "{}. {} {} - {}h{}m",
+                i + 1,
+                a.first_name,
+                a.last_name,
+                a.minutes / 60,
+                a.minutes % 60
+            )
+            .unwrap();
+            acc
+        });
+
+    let mut events: Vec<Event> = sqlx::query_as!(
+        Event,
+        r#"
+SELECT
+    id AS "id: u32",
+    published AS "published: bool",
+    name,
+    start AS "start: DateTime<Utc>",
+    end AS "end: DateTime<Utc>",
+    description,
+    image_url,
+    partner_facility,
+    external_signup_url
+FROM event
+WHERE end > $1 AND published = TRUE
+"#,
+        now
+    )
+    .fetch_all(db)
+    .await?;
+    events.retain(|event| event.start < now + ChronoDuration::days(EVENTS_DAYS));
+    events.sort_by_key(|event| event.start);
+    let upcoming = events.iter().fold(String::new(), |mut acc, event| {
+        writeln!(
+            acc,
+            "[{}]({}events/{}) - <t:{}:f>",
+            event.name,
+            config.hosted_domain,
+            event.id,
+            event.start.timestamp()
+        )
+        .unwrap();
+        acc
+    });
+
+    let embed = EmbedBuilder::new()
+        .title("Weekly Facility Summary")
+        .field(EmbedFieldBuilder::new(
+            format!("Hours controlled in {}", now.format("%B")),
+            format!("{}h{}m", total_minutes / 60, total_minutes % 60),
+        ))
+        .field(EmbedFieldBuilder::new(
+            "Top controllers",
+            if top.is_empty() {
+                String::from("No activity yet")
+            } else {
+                top
+            },
+        ))
+        .field(EmbedFieldBuilder::new(
+            "Upcoming events",
+            if upcoming.is_empty() {
+                String::from("None scheduled")
+            } else {
+                upcoming
+            },
+        ))
+        .validate()?
+        .build();
+    Ok(embed)
+}
+
+/// Single loop execution.
+async fn tick(config: &Arc<Config>, db: &Pool<Sqlite>, http: &Arc<Client>) -> Result<()> {
+    let now = Utc::now();
+    if now.weekday() != POST_WEEKDAY || now.hour() < POST_HOUR {
+        return Ok(());
+    }
+    let today = now.format("%Y-%m-%d").to_string();
+    let last_posted = sqlx::query_scalar!(
+        "SELECT value FROM setting WHERE name=$1",
+        LAST_POSTED_SETTING
+    )
+    .fetch_optional(db)
+    .await?;
+    if last_posted.is_some_and(|date| date == today) {
+        return Ok(());
+    }
+    http.create_message(Id::new(config.discord.weekly_summary_channel))
+        .embeds(&[create_message(config, db).await?])?
+        .await?;
+    sqlx::query!(
+        "INSERT INTO setting VALUES ($1, $2) ON CONFLICT(name) DO UPDATE SET value=excluded.value",
+        LAST_POSTED_SETTING,
+        today
+    )
+    .execute(db)
+    .await?;
+    info!("Posted weekly summary");
+    Ok(())
+}
+
+// Processing loop.
+pub async fn process(config: Arc<Config>, db: Pool<Sqlite>, http: Arc<Client>) {
+    if config.discord.weekly_summary_channel == 0 {
+        debug!("No weekly summary channel configured");
+        return;
+    }
+    sleep(Duration::from_secs(30)).await;
+    debug!("Starting weekly summary processing");
+
+    loop {
+        if let Err(e) = tick(&config, &db, &http).await {
+            error!("Error in weekly summary processing tick: {e}");
+            error_reporting::capture_error("bot::weekly_summary", &format!("{e:?}"));
+        }
+        sleep(Duration::from_secs(60 * 15)).await; // 15 minutes
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use log::{debug, error, info, warn};
+use sqlx::{Pool, Sqlite};
+use std::{sync::Arc, time::Duration};
+use tokio::time::sleep;
+use twilight_http::Client;
+use twilight_model::id::Id;
+use vzdv::{config::Config, error_reporting, sql::Controller};
+
+/// How long after joining a new controller can link their Discord account and still get a DM.
+const WELCOME_DM_DAYS: i64 = 30;
+
+/// Build the DM content for the controller.
+fn welcome_text(config: &Config, controller: &Controller) -> String {
+    let site = &config.hosted_domain;
+    let mut text = format!(
+        "Welcome to ZDV, {}! Here's how to get started:\n\
+        - Read through the facility's resources: {site}facility/resources\n\
+        - Schedule your training: https://training.zdvartcc.org\n",
+        controller.first_name
+    );
+    if controller.operating_initials.is_none() {
+        text.push_str(&format!(
+            "- Choose your operating initials: {site}user/operating_initials\n"
+        ));
+    }
+    text.push_str("If you have any questions, reach out to the staff here on Discord.");
+    text
+}
+
+/// Single loop execution.
+async fn tick(config: &Arc<Config>, db: &Pool<Sqlite>, http: &Arc<Client>) -> Result<()> {
+    let queued_after = Utc::now() - ChronoDuration::days(WELCOME_DM_DAYS);
+    let pending: Vec<Controller> = sqlx::query_as!(
+        Controller,
+        r#"
+SELECT
+    controller.id AS "id: u32",
+    controller.cid AS "cid: u32",
+    controller.first_name,
+    controller.last_name,
+    controller.operating_initials,
+    controller.rating AS "rating!: i8",
+    controller.status AS "status!",
+    controller.discord_id,
+    controller.home_facility AS "home_facility!",
+    controller.is_on_roster AS "is_on_roster!: bool",
+    controller.roles AS "roles!",
+    controller.join_date AS "join_date: DateTime<Utc>",
+    controller.loa_until AS "loa_until: DateTime<Utc>"
+FROM welcome_message JOIN controller ON welcome_message.cid=controller.cid
+WHERE discord_sent_date IS NULL AND controller.discord_id IS NOT NULL AND queued_date > $1
+"#,
+        queued_after
+    )
+    .fetch_all(db)
+    .await?;
+    for controller in pending {
+        let Some(user_id) = controller
+            .discord_id
+            .as_ref()
+            .and_then(|id| id.parse::<u64>().ok())
+        else {
+            warn!(
+                "Could not parse Discord ID for {} to welcome them",
+                controller.cid
+            );
+            continue;
+        };
+        let channel = http
+            .create_private_channel(Id::new(user_id))
+            .await?
+            .model()
+            .await?;
+        // members can block DMs from the server, which shouldn't be retried
+        if let Err(e) = http
+            .create_message(channel.id)
+            .content(&welcome_text(config, &controller))?
+            .await
+        {
+            warn!("Could not send welcome DM to {}: {e}", controller.cid);
+        } else {
+            info!("Sent welcome DM to {}", controller.cid);
+        }
+        let now = Utc::now();
+        sqlx::query!(
+            "UPDATE welcome_message SET discord_sent_date=$2 WHERE cid=$1",
+            controller.cid,
+            now
+        )
+        .execute(db)
+        .await?;
+    }
+    Ok(())
+}
+
+// Processing loop.
+pub async fn process(config: Arc<Config>, db: Pool<Sqlite>, http: Arc<Client>) {
+    sleep(Duration::from_secs(30)).await;
+    debug!("Starting welcome DM processing");
+
+    loop {
+        if let Err(e) = tick(&config, &db, &http).await {
+            error!("Error in welcome DM processing tick: {e}");
+            error_reporting::capture_error("bot::welcome", &format!("{e:?}"));
+        }
+        sleep(Duration::from_secs(60 * 5)).await; // 5 minutes
+    }
+}
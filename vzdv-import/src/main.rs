@@ -4,14 +4,21 @@
 #![deny(unsafe_code)]
 
 use anyhow::{bail, Result};
+use chrono::{DateTime, NaiveDate, Utc};
 use clap::Parser;
 use log::{debug, error, info, warn};
-use serde::Deserialize;
-use sqlx::{Pool, Sqlite};
-use std::{collections::HashMap, path::PathBuf};
+use serde::{de::DeserializeOwned, Deserialize};
+use sqlx::{Connection, SqliteConnection};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
 use vzdv::{general_setup, ControllerRating, GENERAL_HTTP_CLIENT};
 
 const ROSTER_URL: &str = "https://api.zdvartcc.org/v1/user/all";
+const TRAINING_NOTES_URL: &str = "https://api.zdvartcc.org/v1/training/notes";
+const STAFF_COMMENTS_URL: &str = "https://api.zdvartcc.org/v1/user/comments";
 
 /// vZDV importer to get data from existing site.
 #[derive(Parser)]
@@ -23,9 +30,63 @@ struct Cli {
     #[arg(long)]
     config: Option<PathBuf>,
 
+    /// Merge the config overlay for this environment, like "dev" for "vzdv.dev.toml".
+    ///
+    /// [default: the VZDV_ENV environment variable, if set]
+    #[arg(long)]
+    env: Option<String>,
+
     /// Enable debug logging
     #[arg(short, long)]
     debug: bool,
+
+    /// Report what would change without saving anything
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Only import controllers updated on the ADH site on or after this date (YYYY-MM-DD)
+    #[arg(long)]
+    since: Option<NaiveDate>,
+
+    /// TOML file mapping ADH certification keys or names to the facility's certifications
+    #[arg(long)]
+    cert_mapping: Option<PathBuf>,
+}
+
+/// Translation of ADH certifications to the names in the config's `training.certifications`.
+#[derive(Deserialize, Default)]
+struct CertificationMapping {
+    /// ADH certification key or display name to the new certification name.
+    certifications: HashMap<String, String>,
+}
+
+impl CertificationMapping {
+    /// Load the mapping from disk, making sure every target is a known certification.
+    fn load(path: &Path, known: &[String]) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let mapping: Self = toml::from_str(&text)?;
+        let unknown: Vec<_> = mapping
+            .certifications
+            .values()
+            .filter(|name| !known.contains(name))
+            .collect();
+        if !unknown.is_empty() {
+            bail!("Certification mapping targets unknown certifications: {unknown:?}");
+        }
+        Ok(mapping)
+    }
+
+    /// The new certification name for the ADH certification, if there is one.
+    ///
+    /// A mapping for the key takes priority over one for the display name, and
+    /// display names that are already a known certification map to themselves.
+    fn map(&self, key: &str, display_name: &str, known: &[String]) -> Option<String> {
+        self.certifications
+            .get(key)
+            .or_else(|| self.certifications.get(display_name))
+            .cloned()
+            .or_else(|| known.iter().find(|name| *name == display_name).cloned())
+    }
 }
 
 #[derive(Deserialize)]
@@ -43,91 +104,393 @@ struct AdhController {
     certifications: HashMap<String, AdhCertification>,
     rating: String,
     discord_id: String,
+    /// Not sent for every record; those without it are always imported.
+    #[serde(default)]
+    updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+struct AdhTrainingNote {
+    id: u32,
+    cid: u32,
+    instructor_cid: u32,
+    position: String,
+    session_date: DateTime<Utc>,
+    comments: String,
+}
+
+#[derive(Deserialize)]
+struct AdhStaffComment {
+    cid: u32,
+    author_cid: u32,
+    created_at: DateTime<Utc>,
+    comment: String,
+}
+
+/// What importing a single controller changed.
+#[derive(Default)]
+struct ControllerChanges {
+    new_controller: bool,
+    updated_fields: Vec<&'static str>,
+    certifications_added: Vec<String>,
+    certifications_changed: Vec<String>,
+    certifications_removed: Vec<String>,
+    /// ADH certifications (key and display name) with no mapping.
+    certifications_unmapped: Vec<String>,
+}
+
+impl ControllerChanges {
+    fn is_empty(&self) -> bool {
+        !self.new_controller
+            && self.updated_fields.is_empty()
+            && self.certifications_added.is_empty()
+            && self.certifications_changed.is_empty()
+            && self.certifications_removed.is_empty()
+    }
+
+    /// Single-line description for the log.
+    fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if self.new_controller {
+            parts.push("new controller".to_owned());
+        }
+        if !self.updated_fields.is_empty() {
+            parts.push(format!("updated {}", self.updated_fields.join(", ")));
+        }
+        for (label, certs) in [
+            ("added", &self.certifications_added),
+            ("changed", &self.certifications_changed),
+            ("removed", &self.certifications_removed),
+        ] {
+            if !certs.is_empty() {
+                parts.push(format!("certs {label}: {}", certs.join(", ")));
+            }
+        }
+        parts.join("; ")
+    }
 }
 
-async fn get_adh_data() -> Result<Vec<AdhController>> {
-    let response = GENERAL_HTTP_CLIENT.get(ROSTER_URL).send().await?;
+/// Totals over the whole import.
+#[derive(Default)]
+struct Summary {
+    skipped_by_date: usize,
+    failed: usize,
+    unchanged: usize,
+    new_controllers: usize,
+    updated_controllers: usize,
+    certifications_added: usize,
+    certifications_changed: usize,
+    certifications_removed: usize,
+    training_records_added: usize,
+    staff_notes_added: usize,
+    history_already_imported: usize,
+    history_unknown_controller: usize,
+    /// Unmapped ADH certifications and how many controllers had them.
+    certifications_unmapped: BTreeMap<String, usize>,
+}
+
+impl Summary {
+    fn record(&mut self, changes: &ControllerChanges) {
+        for name in &changes.certifications_unmapped {
+            *self
+                .certifications_unmapped
+                .entry(name.clone())
+                .or_default() += 1;
+        }
+        if changes.is_empty() {
+            self.unchanged += 1;
+            return;
+        }
+        if changes.new_controller {
+            self.new_controllers += 1;
+        } else {
+            self.updated_controllers += 1;
+        }
+        self.certifications_added += changes.certifications_added.len();
+        self.certifications_changed += changes.certifications_changed.len();
+        self.certifications_removed += changes.certifications_removed.len();
+    }
+}
+
+/// Get and parse a list of records from the ADH API.
+async fn get_adh_data<T: DeserializeOwned>(url: &str, name: &str) -> Result<Vec<T>> {
+    let response = GENERAL_HTTP_CLIENT.get(url).send().await?;
     if !response.status().is_success() {
         bail!(
-            "Got status {} from ZDV ADH roster endpoint",
+            "Got status {} from ZDV ADH {name} endpoint",
             response.status().as_u16()
         );
     }
-    let data: Vec<AdhController> = response.json().await?;
-    info!("Got {} controllers from the ZDV roster", data.len());
+    let data: Vec<T> = response.json().await?;
+    info!("Got {} {name} records from ZDV ADH", data.len());
     Ok(data)
 }
 
-async fn update_single(db: &Pool<Sqlite>, controller: &AdhController) -> Result<()> {
+/// Import a single controller, returning what changed.
+async fn update_single(
+    db: &mut SqliteConnection,
+    controller: &AdhController,
+    mapping: &CertificationMapping,
+    known_certs: &[String],
+) -> Result<ControllerChanges> {
     debug!("Updating {}", controller.cid);
+    let mut changes = ControllerChanges::default();
 
     let discord_id: Option<String> = if controller.discord_id.is_empty() {
         None
     } else {
         Some(controller.discord_id.clone())
     };
-    let rows =
-        sqlx::query("UPDATE controller SET operating_initials=$1, discord_id=$2 WHERE cid=$3")
-            .bind(controller.operating_initials.clone())
-            .bind(&discord_id)
-            .bind(controller.cid)
-            .execute(db)
+    let existing = sqlx::query!(
+        "SELECT operating_initials, discord_id FROM controller WHERE cid=$1",
+        controller.cid
+    )
+    .fetch_optional(&mut *db)
+    .await?;
+
+    match existing.map(|row| (row.operating_initials, row.discord_id)) {
+        Some((operating_initials, existing_discord_id)) => {
+            debug!("Existing controller");
+            if operating_initials.as_deref() != Some(controller.operating_initials.as_str()) {
+                changes.updated_fields.push("operating initials");
+            }
+            if existing_discord_id != discord_id {
+                changes.updated_fields.push("Discord ID");
+            }
+            if !changes.updated_fields.is_empty() {
+                sqlx::query!(
+                    "UPDATE controller SET operating_initials=$1, discord_id=$2 WHERE cid=$3",
+                    controller.operating_initials,
+                    discord_id,
+                    controller.cid
+                )
+                .execute(&mut *db)
+                .await?;
+            }
+        }
+        None => {
+            debug!("New controller");
+            changes.new_controller = true;
+            // unknown controller, very likely off-roster
+            let rating = match controller.rating.as_str() {
+                "INA" => ControllerRating::INA,
+                "SUS" => ControllerRating::SUS,
+                "OBS" => ControllerRating::OBS,
+                "S1" => ControllerRating::S1,
+                "S2" => ControllerRating::S2,
+                "S3" => ControllerRating::S3,
+                "C1" => ControllerRating::C1,
+                "C2" => ControllerRating::C2,
+                "C3" => ControllerRating::C3,
+                "I1" => ControllerRating::I1,
+                "I2" => ControllerRating::I2,
+                "I3" => ControllerRating::I3,
+                "SUP" => ControllerRating::SUP,
+                "ADM" => ControllerRating::ADM,
+                _ => {
+                    warn!("Unknown controller rating string: {}", &controller.rating);
+                    ControllerRating::OBS
+                }
+            };
+            let rating = rating.as_id();
+            sqlx::query!(
+                "INSERT INTO controller (id, cid, first_name, last_name, rating, is_on_roster, discord_id) VALUES (NULL, $1, $2, $3, $4, FALSE, $5)",
+                controller.cid,
+                controller.first_name,
+                controller.last_name,
+                rating,
+                discord_id
+            )
+            .execute(&mut *db)
             .await?;
+        }
+    }
 
-    if rows.rows_affected() == 0 {
-        debug!("New controller");
-        // unknown controller, very likely off-roster
-        let sql = "INSERT INTO controller (id, cid, first_name, last_name, rating, is_on_roster, discord_id) VALUES (NULL, $1, $2, $3, $4, FALSE, $5)";
-        let rating = match controller.rating.as_str() {
-            "INA" => ControllerRating::INA,
-            "SUS" => ControllerRating::SUS,
-            "OBS" => ControllerRating::OBS,
-            "S1" => ControllerRating::S1,
-            "S2" => ControllerRating::S2,
-            "S3" => ControllerRating::S3,
-            "C1" => ControllerRating::C1,
-            "C2" => ControllerRating::C2,
-            "C3" => ControllerRating::C3,
-            "I1" => ControllerRating::I1,
-            "I2" => ControllerRating::I2,
-            "I3" => ControllerRating::I3,
-            "SUP" => ControllerRating::SUP,
-            "ADM" => ControllerRating::ADM,
-            _ => {
-                warn!("Unknown controller rating string: {}", &controller.rating);
-                ControllerRating::OBS
+    let existing_certs: BTreeMap<String, String> = sqlx::query!(
+        "SELECT name, value FROM certification WHERE cid=$1",
+        controller.cid
+    )
+    .fetch_all(&mut *db)
+    .await?
+    .into_iter()
+    .map(|row| (row.name, row.value))
+    .collect();
+    // certs the ADH record speaks for, including those it has as "none"; any
+    // others were set on this site and are left alone
+    let mut sources: BTreeMap<String, &str> = BTreeMap::new();
+    let mut new_certs: BTreeMap<String, String> = BTreeMap::new();
+    let mut certifications: Vec<_> = controller.certifications.iter().collect();
+    certifications.sort_by_key(|(key, _)| *key);
+    for (key, certification) in certifications {
+        let Some(name) = mapping.map(key, &certification.display_name, known_certs) else {
+            if certification.value != "none" {
+                changes
+                    .certifications_unmapped
+                    .push(format!("{key} ({})", certification.display_name));
             }
+            continue;
         };
-        sqlx::query(sql)
-            .bind(controller.cid)
-            .bind(&controller.first_name)
-            .bind(&controller.last_name)
-            .bind(rating.as_id())
-            .bind(discord_id)
-            .execute(db)
+        if let Some(first) = sources.get(&name) {
+            warn!(
+                "{}: ADH certifications {first} and {key} both map to {name}; using {first}",
+                controller.cid
+            );
+            continue;
+        }
+        sources.insert(name.clone(), key);
+        if certification.value != "none" {
+            new_certs.insert(name, certification.value.clone());
+        }
+    }
+    let mut changed_names = Vec::new();
+    for name in sources.keys() {
+        let old = existing_certs.get(name);
+        let new = new_certs.get(name);
+        match (old, new) {
+            (None, Some(new)) => changes.certifications_added.push(format!("{name}={new}")),
+            (Some(old), Some(new)) if old != new => changes
+                .certifications_changed
+                .push(format!("{name}={old}->{new}")),
+            (Some(_), None) => changes.certifications_removed.push(name.clone()),
+            _ => continue,
+        }
+        changed_names.push(name);
+    }
+
+    for name in changed_names {
+        let old = existing_certs.get(name);
+        let new = new_certs.get(name);
+        let now = Utc::now();
+        sqlx::query!(
+            "DELETE FROM certification WHERE cid=$1 AND name=$2",
+            controller.cid,
+            name
+        )
+        .execute(&mut *db)
+        .await?;
+        if let Some(value) = new {
+            sqlx::query!(
+                "INSERT INTO certification (id, cid, name, value, changed_on, set_by) VALUES (NULL, $1, $2, $3, $4, 0)",
+                controller.cid,
+                name,
+                value,
+                now
+            )
+            .execute(&mut *db)
             .await?;
-    } else {
-        debug!("Existing controller");
+        }
+        // removed certs go back to "none"
+        let new_value = new.map(String::as_str).unwrap_or("none");
+        sqlx::query!(
+            "INSERT INTO certification_history VALUES (NULL, $1, $2, $3, $4, 0, $5)",
+            controller.cid,
+            name,
+            old,
+            new_value,
+            now
+        )
+        .execute(&mut *db)
+        .await?;
     }
 
-    sqlx::query("DELETE FROM certification WHERE cid=$1")
-        .bind(controller.cid)
-        .execute(db)
+    Ok(changes)
+}
+
+/// CIDs of all controllers in the DB.
+async fn known_controllers(db: &mut SqliteConnection) -> Result<HashSet<u32>> {
+    let cids = sqlx::query_scalar!(r#"SELECT cid AS "cid: u32" FROM controller"#)
+        .fetch_all(&mut *db)
         .await?;
-    for certification in controller.certifications.values() {
-        if certification.value == "none" {
+    Ok(cids.into_iter().collect())
+}
+
+/// Import training notes into the training records, skipping those already imported.
+async fn import_training_notes(
+    db: &mut SqliteConnection,
+    notes: &[AdhTrainingNote],
+    known_cids: &HashSet<u32>,
+    since: Option<DateTime<Utc>>,
+    summary: &mut Summary,
+) -> Result<()> {
+    for note in notes {
+        if since.is_some_and(|since| note.session_date < since) {
             continue;
         }
-        sqlx::query("INSERT INTO certification (id, cid, name, value, changed_on, set_by) VALUES (NULL, $1, $2, $3, $4, $5)")
-            .bind(controller.cid)
-            .bind(&certification.display_name)
-            .bind(&certification.value)
-            .bind(chrono::Utc::now())
-            .bind(0)
-            .execute(db)
-            .await?;
+        if !known_cids.contains(&note.cid) {
+            warn!(
+                "Training note {} is for unknown controller {}",
+                note.id, note.cid
+            );
+            summary.history_unknown_controller += 1;
+            continue;
+        }
+        let result = sqlx::query!(
+            "INSERT INTO training_record (id, cid, instructor_cid, position, date, notes, legacy_id) VALUES (NULL, $1, $2, $3, $4, $5, $6) ON CONFLICT(legacy_id) DO NOTHING",
+            note.cid,
+            note.instructor_cid,
+            note.position,
+            note.session_date,
+            note.comments,
+            note.id
+        )
+        .execute(&mut *db)
+        .await?;
+        if result.rows_affected() == 0 {
+            summary.history_already_imported += 1;
+        } else {
+            summary.training_records_added += 1;
+        }
     }
+    Ok(())
+}
 
+/// Import staff comments as staff notes, skipping those already imported.
+///
+/// Staff notes don't store the legacy ID, so existing notes are matched on their content.
+async fn import_staff_comments(
+    db: &mut SqliteConnection,
+    comments: &[AdhStaffComment],
+    known_cids: &HashSet<u32>,
+    since: Option<DateTime<Utc>>,
+    summary: &mut Summary,
+) -> Result<()> {
+    for comment in comments {
+        if since.is_some_and(|since| comment.created_at < since) {
+            continue;
+        }
+        // staff notes reference the controller table for both the subject and author
+        if !known_cids.contains(&comment.cid) || !known_cids.contains(&comment.author_cid) {
+            warn!(
+                "Staff comment on {} by {} references an unknown controller",
+                comment.cid, comment.author_cid
+            );
+            summary.history_unknown_controller += 1;
+            continue;
+        }
+        let existing = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM staff_note WHERE cid=$1 AND by=$2 AND date=$3 AND comment=$4",
+            comment.cid,
+            comment.author_cid,
+            comment.created_at,
+            comment.comment
+        )
+        .fetch_one(&mut *db)
+        .await?;
+        if existing > 0 {
+            summary.history_already_imported += 1;
+            continue;
+        }
+        sqlx::query!(
+            "INSERT INTO staff_note VALUES (NULL, $1, $2, $3, $4)",
+            comment.cid,
+            comment.author_cid,
+            comment.created_at,
+            comment.comment
+        )
+        .execute(&mut *db)
+        .await?;
+        summary.staff_notes_added += 1;
+    }
     Ok(())
 }
 
@@ -136,10 +499,21 @@ async fn update_single(db: &Pool<Sqlite>, controller: &AdhController) -> Result<
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
-    let (_config, db) = general_setup(cli.debug, "vzdv_import", cli.config).await;
+    let (config, db) = general_setup(cli.debug, "vzdv_import", cli.config, cli.env).await;
+    let known_certs = &config.training.certifications;
+    let mapping = match &cli.cert_mapping {
+        Some(path) => match CertificationMapping::load(path, known_certs) {
+            Ok(m) => m,
+            Err(e) => {
+                error!("Could not load certification mapping: {e}");
+                return;
+            }
+        },
+        None => CertificationMapping::default(),
+    };
 
     info!("Retrieving data");
-    let data = match get_adh_data().await {
+    let data: Vec<AdhController> = match get_adh_data(ROSTER_URL, "roster").await {
         Ok(d) => d,
         Err(e) => {
             error!("Error getting data: {e}");
@@ -147,11 +521,127 @@ async fn main() {
         }
     };
 
+    let since = cli
+        .since
+        .map(|date| date.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    let mut summary = Summary::default();
+    let mut tx = match db.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            error!("Could not start transaction: {e}");
+            return;
+        }
+    };
     for controller in data {
-        if let Err(e) = update_single(&db, &controller).await {
-            error!("Error updating controller {}: {e}", controller.cid);
+        if let (Some(since), Some(updated_at)) = (since, controller.updated_at) {
+            if updated_at < since {
+                summary.skipped_by_date += 1;
+                continue;
+            }
+        }
+        // a savepoint per controller so that one failing partway through doesn't leave it half-updated
+        let mut savepoint = match Connection::begin(&mut *tx).await {
+            Ok(savepoint) => savepoint,
+            Err(e) => {
+                error!("Could not start savepoint for {}: {e}", controller.cid);
+                summary.failed += 1;
+                continue;
+            }
+        };
+        match update_single(&mut savepoint, &controller, &mapping, known_certs).await {
+            Ok(changes) => match savepoint.commit().await {
+                Ok(_) => {
+                    if !changes.is_empty() {
+                        info!("{}: {}", controller.cid, changes.describe());
+                    }
+                    summary.record(&changes);
+                }
+                Err(e) => {
+                    error!("Error saving controller {}: {e}", controller.cid);
+                    summary.failed += 1;
+                }
+            },
+            Err(e) => {
+                error!("Error updating controller {}: {e}", controller.cid);
+                summary.failed += 1;
+                if let Err(e) = savepoint.rollback().await {
+                    // the shared transaction can't be trusted now, so nothing is saved
+                    error!(
+                        "Could not roll back controller {}, aborting the import: {e}",
+                        controller.cid
+                    );
+                    return;
+                }
+            }
         }
     }
 
+    // history is optional; a failure here shouldn't block the roster import
+    match get_adh_data::<AdhTrainingNote>(TRAINING_NOTES_URL, "training note").await {
+        Ok(notes) => match known_controllers(&mut tx).await {
+            Ok(known_cids) => {
+                if let Err(e) =
+                    import_training_notes(&mut tx, &notes, &known_cids, since, &mut summary).await
+                {
+                    error!("Error importing training notes: {e}");
+                }
+            }
+            Err(e) => error!("Error getting known controllers: {e}"),
+        },
+        Err(e) => error!("Error getting training notes: {e}"),
+    }
+    match get_adh_data::<AdhStaffComment>(STAFF_COMMENTS_URL, "staff comment").await {
+        Ok(comments) => match known_controllers(&mut tx).await {
+            Ok(known_cids) => {
+                if let Err(e) =
+                    import_staff_comments(&mut tx, &comments, &known_cids, since, &mut summary)
+                        .await
+                {
+                    error!("Error importing staff comments: {e}");
+                }
+            }
+            Err(e) => error!("Error getting known controllers: {e}"),
+        },
+        Err(e) => error!("Error getting staff comments: {e}"),
+    }
+
+    let result = if cli.dry_run {
+        tx.rollback().await
+    } else {
+        tx.commit().await
+    };
+    if let Err(e) = result {
+        error!("Could not finish transaction: {e}");
+        return;
+    }
+
+    info!(
+        "{}Summary: {} new, {} updated, {} unchanged, {} failed, {} skipped as not updated since --since",
+        if cli.dry_run { "[dry run, nothing saved] " } else { "" },
+        summary.new_controllers,
+        summary.updated_controllers,
+        summary.unchanged,
+        summary.failed,
+        summary.skipped_by_date
+    );
+    info!(
+        "Certifications: {} added, {} changed, {} removed",
+        summary.certifications_added,
+        summary.certifications_changed,
+        summary.certifications_removed
+    );
+    info!(
+        "History: {} training records added, {} staff notes added, {} already imported, {} for unknown controllers",
+        summary.training_records_added,
+        summary.staff_notes_added,
+        summary.history_already_imported,
+        summary.history_unknown_controller
+    );
+    if !summary.certifications_unmapped.is_empty() {
+        warn!("Unmapped certifications were not imported; add them to the --cert-mapping file:");
+        for (name, count) in &summary.certifications_unmapped {
+            warn!("  {name}: {count} controllers");
+        }
+    }
     info!("Complete");
 }
@@ -0,0 +1,176 @@
+//! Blocking abusive users and addresses from submitting to the site.
+
+use crate::shared::{AppError, AppState};
+use axum::extract::{ConnectInfo, Request};
+use chrono::Utc;
+use ipnet::IpNet;
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+use vzdv::sql::{self, Block};
+
+/// Cache key for the unexpired blocks.
+pub const BLOCKLIST_CACHE_KEY: &str = "BLOCKLIST";
+
+/// Get all unexpired blocks, through the cache.
+pub async fn active_blocks(state: &AppState) -> Result<Vec<Block>, AppError> {
+    let blocks = state
+        .cache
+        .get_or_try_insert(BLOCKLIST_CACHE_KEY, Duration::from_secs(60), || async {
+            let blocks: Vec<Block> = sqlx::query_as(sql::GET_ACTIVE_BLOCKS)
+                .bind(Utc::now())
+                .fetch_all(&state.db)
+                .await?;
+            Ok::<_, AppError>(blocks)
+        })
+        .await?;
+    // blocks can expire while cached
+    let now = Utc::now();
+    Ok(blocks
+        .iter()
+        .filter(|block| block.expires.is_none_or(|expires| expires > now))
+        .cloned()
+        .collect())
+}
+
+/// Get the IP address of the client making the request.
+///
+/// Requests from a loopback address are assumed to have come through a
+/// reverse proxy on the same host, so the address that it appended to
+/// the `X-Forwarded-For` header is used instead.
+pub fn client_ip(request: &Request) -> Option<IpAddr> {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()?
+        .0
+        .ip();
+    if peer.is_loopback() {
+        let forwarded = request
+            .headers()
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(|value| value.trim().parse().ok());
+        if let Some(forwarded) = forwarded {
+            return Some(forwarded);
+        }
+    }
+    Some(peer)
+}
+
+/// Validate a block's value from the admin form, returning it in the form it's stored in.
+///
+/// Single IP addresses are stored as ranges of one address.
+pub fn normalize_value(kind: &str, value: &str) -> Option<String> {
+    let value = value.trim();
+    match kind {
+        "ip" => value
+            .parse::<IpNet>()
+            .map(|net| net.trunc())
+            .or_else(|_| value.parse::<IpAddr>().map(IpNet::from))
+            .ok()
+            .map(|net| net.to_string()),
+        "cid" => value.parse::<u32>().ok().map(|cid| cid.to_string()),
+        _ => None,
+    }
+}
+
+/// Find the first block that applies to the IP address or CID.
+pub fn matching_block(blocks: &[Block], ip: Option<IpAddr>, cid: Option<u32>) -> Option<&Block> {
+    blocks.iter().find(|block| match block.kind.as_str() {
+        "ip" => match (ip, block.value.parse::<IpNet>()) {
+            (Some(ip), Ok(net)) => net.contains(&ip),
+            _ => false,
+        },
+        "cid" => cid.is_some_and(|cid| block.value == cid.to_string()),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{client_ip, matching_block, normalize_value};
+    use axum::{
+        body::Body,
+        extract::{ConnectInfo, Request},
+    };
+    use chrono::Utc;
+    use std::net::{IpAddr, SocketAddr};
+    use vzdv::sql::Block;
+
+    fn block(kind: &str, value: &str) -> Block {
+        Block {
+            id: 1,
+            kind: kind.to_owned(),
+            value: value.to_owned(),
+            reason: String::new(),
+            created_by: 1,
+            created_date: Utc::now(),
+            expires: None,
+            hits: 0,
+            last_hit: None,
+        }
+    }
+
+    #[test]
+    fn test_normalize_value() {
+        assert_eq!(
+            normalize_value("ip", "192.0.2.7").as_deref(),
+            Some("192.0.2.7/32")
+        );
+        assert_eq!(
+            normalize_value("ip", "192.0.2.7/24").as_deref(),
+            Some("192.0.2.0/24")
+        );
+        assert_eq!(
+            normalize_value("ip", "2001:db8::1").as_deref(),
+            Some("2001:db8::1/128")
+        );
+        assert_eq!(normalize_value("ip", "nope"), None);
+        assert_eq!(
+            normalize_value("cid", " 1000001 ").as_deref(),
+            Some("1000001")
+        );
+        assert_eq!(normalize_value("cid", "abc"), None);
+        assert_eq!(normalize_value("other", "1"), None);
+    }
+
+    #[test]
+    fn test_matching_block() {
+        let blocks = vec![block("ip", "192.0.2.0/24"), block("cid", "1000001")];
+        let ip: IpAddr = "192.0.2.50".parse().unwrap();
+        let other_ip: IpAddr = "198.51.100.1".parse().unwrap();
+        assert!(matching_block(&blocks, Some(ip), None).is_some());
+        assert!(matching_block(&blocks, Some(other_ip), None).is_none());
+        assert!(matching_block(&blocks, Some(other_ip), Some(1_000_001)).is_some());
+        assert!(matching_block(&blocks, None, Some(1_000_002)).is_none());
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let request = |peer: &str, forwarded: Option<&str>| {
+            let mut builder = Request::get("/");
+            if let Some(forwarded) = forwarded {
+                builder = builder.header("x-forwarded-for", forwarded);
+            }
+            let mut request = builder.body(Body::empty()).unwrap();
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+            request
+        };
+        let direct = request("198.51.100.1:5000", Some("192.0.2.1"));
+        assert_eq!(
+            client_ip(&direct),
+            Some("198.51.100.1".parse::<IpAddr>().unwrap())
+        );
+        let proxied = request("127.0.0.1:5000", Some("203.0.113.9, 192.0.2.1"));
+        assert_eq!(
+            client_ip(&proxied),
+            Some("192.0.2.1".parse::<IpAddr>().unwrap())
+        );
+        let no_connect_info = Request::get("/").body(Body::empty()).unwrap();
+        assert_eq!(client_ip(&no_connect_info), None);
+    }
+}
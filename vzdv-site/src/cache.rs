@@ -0,0 +1,211 @@
+//! Server-side cache of typed values, each with its own time to live.
+//!
+//! Values are stored behind an `Arc`, so handlers can share parsed data
+//! (like METARs and VATSIM datafeed snapshots) instead of re-rendering or
+//! re-parsing strings, and without cloning large structs.
+
+use mini_moka::sync::Cache;
+use serde::Serialize;
+use std::{
+    any::Any,
+    future::Future,
+    io::{self, Write},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+#[derive(Clone)]
+struct CacheEntry {
+    inserted: Instant,
+    ttl: Duration,
+    /// Measures the value for the admin page; see `serialized_size`.
+    size_of: fn(&(dyn Any + Send + Sync)) -> usize,
+    value: Arc<dyn Any + Send + Sync>,
+}
+
+impl CacheEntry {
+    fn is_expired(&self, now: Instant) -> bool {
+        now.duration_since(self.inserted) >= self.ttl
+    }
+}
+
+/// Details of a cached value, for display.
+#[derive(Debug, Serialize)]
+pub struct CacheEntryInfo {
+    pub key: String,
+    pub age_seconds: u64,
+    pub ttl_seconds: u64,
+    pub size: usize,
+}
+
+/// Writer that only counts the bytes written to it.
+struct ByteCounter(usize);
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Approximate size of the value, as its length when serialized to JSON.
+///
+/// Serializing can be slow for large values, like the VATSIM datafeed, so
+/// this is only done when listing the entries rather than on every insert.
+fn serialized_size<T: Serialize + 'static>(value: &(dyn Any + Send + Sync)) -> usize {
+    let Some(value) = value.downcast_ref::<T>() else {
+        return 0;
+    };
+    let mut counter = ByteCounter(0);
+    match serde_json::to_writer(&mut counter, value) {
+        Ok(_) => counter.0,
+        Err(_) => 0,
+    }
+}
+
+/// Cache of values of any type, keyed by name.
+pub struct TypedCache {
+    entries: Cache<String, CacheEntry>,
+}
+
+impl TypedCache {
+    /// Create a cache holding up to `max_capacity` entries.
+    pub fn new(max_capacity: u64) -> Self {
+        Self {
+            entries: Cache::new(max_capacity),
+        }
+    }
+
+    /// Get the value stored under the key.
+    ///
+    /// Returns `None` if there's no value, the value has expired, or the
+    /// value isn't of the requested type.
+    pub fn get<T: Send + Sync + 'static>(&self, key: &str) -> Option<Arc<T>> {
+        let entry = self.entries.get(&key.to_owned())?;
+        if entry.is_expired(Instant::now()) {
+            self.entries.invalidate(&key.to_owned());
+            return None;
+        }
+        entry.value.downcast().ok()
+    }
+
+    /// Store the value under the key for the duration of the TTL.
+    pub fn insert<T: Serialize + Send + Sync + 'static>(
+        &self,
+        key: &str,
+        value: T,
+        ttl: Duration,
+    ) -> Arc<T> {
+        let value = Arc::new(value);
+        self.entries.insert(
+            key.to_owned(),
+            CacheEntry {
+                inserted: Instant::now(),
+                ttl,
+                size_of: serialized_size::<T>,
+                value: value.clone(),
+            },
+        );
+        value
+    }
+
+    /// Get the value stored under the key, or fetch and store it if missing or expired.
+    ///
+    /// Errors from fetching are returned and nothing is stored.
+    pub async fn get_or_try_insert<T, E, F, Fut>(
+        &self,
+        key: &str,
+        ttl: Duration,
+        fetch: F,
+    ) -> Result<Arc<T>, E>
+    where
+        T: Serialize + Send + Sync + 'static,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        if let Some(value) = self.get(key) {
+            return Ok(value);
+        }
+        let value = fetch().await?;
+        Ok(self.insert(key, value, ttl))
+    }
+
+    /// Details of all unexpired entries, sorted by key.
+    ///
+    /// Each value is serialized to measure it, so this is only for the admin page.
+    pub fn entries(&self) -> Vec<CacheEntryInfo> {
+        let now = Instant::now();
+        let mut entries: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|entry| !entry.value().is_expired(now))
+            .map(|entry| CacheEntryInfo {
+                key: entry.key().to_string(),
+                age_seconds: now.duration_since(entry.value().inserted).as_secs(),
+                ttl_seconds: entry.value().ttl.as_secs(),
+                size: (entry.value().size_of)(entry.value().value.as_ref()),
+            })
+            .collect();
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+        entries
+    }
+
+    /// Remove the entry for the key, if present.
+    pub fn invalidate(&self, key: &str) {
+        self.entries.invalidate(&key.to_owned());
+    }
+
+    /// Remove all entries.
+    pub fn invalidate_all(&self) {
+        self.entries.invalidate_all();
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::TypedCache;
+    use std::{sync::Arc, time::Duration};
+
+    #[test]
+    fn test_typed_cache() {
+        let cache = TypedCache::new(10);
+        cache.insert("numbers", vec![1, 2, 3], Duration::from_secs(60));
+        cache.insert("gone", String::from("old"), Duration::ZERO);
+
+        assert_eq!(*cache.get::<Vec<i32>>("numbers").unwrap(), vec![1, 2, 3]);
+        assert!(cache.get::<String>("numbers").is_none());
+        assert!(cache.get::<String>("gone").is_none());
+        assert!(cache.get::<String>("missing").is_none());
+
+        let entries = cache.entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].key, "numbers");
+        assert_eq!(entries[0].ttl_seconds, 60);
+        assert_eq!(entries[0].size, "[1,2,3]".len());
+
+        cache.invalidate("numbers");
+        assert!(cache.get::<Vec<i32>>("numbers").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_or_try_insert() {
+        let cache = TypedCache::new(10);
+        let value: Result<_, ()> = cache
+            .get_or_try_insert("key", Duration::from_secs(60), || async { Ok(1) })
+            .await;
+        assert_eq!(*value.unwrap(), 1);
+        let value: Result<_, ()> = cache
+            .get_or_try_insert("key", Duration::from_secs(60), || async { Ok(2) })
+            .await;
+        assert_eq!(*value.unwrap(), 1);
+        let value: Result<Arc<i32>, &str> = cache
+            .get_or_try_insert("other", Duration::from_secs(60), || async { Err("failed") })
+            .await;
+        assert!(value.is_err());
+        assert!(cache.get::<i32>("other").is_none());
+    }
+}
@@ -2,10 +2,17 @@
 //! _not_ by the bot itself.
 
 use crate::shared::AppError;
+use log::warn;
+use reqwest::{header::RETRY_AFTER, StatusCode};
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 use vzdv::{config::Config, GENERAL_HTTP_CLIENT};
 
+/// Tries at a webhook post that Discord is rate limiting before giving up.
+const WEBHOOK_ATTEMPTS: u32 = 3;
+/// Longest `Retry-After` to wait out between webhook tries.
+const WEBHOOK_MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
+
 // In each of these structs, there are other fields that are returned by their respective
 // API endpoints, but these are the only fields that are actually needed.
 
@@ -16,6 +23,11 @@ pub struct DiscordAccessToken {
     pub access_token: String,
 }
 
+#[derive(Deserialize)]
+struct DiscordChannel {
+    id: String,
+}
+
 #[derive(Deserialize)]
 struct DiscordUserInfo {
     user: DiscordUserInfoUser,
@@ -83,3 +95,83 @@ pub async fn get_token_user_id(access_token: &DiscordAccessToken) -> Result<Stri
     let data: DiscordUserInfo = resp.json().await?;
     Ok(data.user.id)
 }
+
+/// Send a direct message to the user from the bot.
+///
+/// Users can block DMs from server members, so this can fail for reasons out of the site's control.
+pub async fn send_direct_message(
+    config: &Config,
+    user_id: &str,
+    content: &str,
+) -> Result<(), AppError> {
+    let auth = format!("Bot {}", config.discord.bot_token);
+    let resp = GENERAL_HTTP_CLIENT
+        .post("https://discord.com/api/v10/users/@me/channels")
+        .header(reqwest::header::AUTHORIZATION, &auth)
+        .json(&HashMap::from([("recipient_id", user_id)]))
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        return Err(AppError::HttpResponse(
+            "Discord DM channel creation",
+            resp.status().as_u16(),
+        ));
+    }
+    let channel: DiscordChannel = resp.json().await?;
+    let resp = GENERAL_HTTP_CLIENT
+        .post(format!(
+            "https://discord.com/api/v10/channels/{}/messages",
+            channel.id
+        ))
+        .header(reqwest::header::AUTHORIZATION, &auth)
+        .json(&HashMap::from([("content", content)]))
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        return Err(AppError::HttpResponse(
+            "Discord DM send",
+            resp.status().as_u16(),
+        ));
+    }
+    Ok(())
+}
+
+/// Post a message to a Discord webhook.
+///
+/// If Discord rate limits the post, waits as long as its `Retry-After` header
+/// asks and tries again, up to `WEBHOOK_ATTEMPTS` times in total.
+pub async fn post_webhook(
+    url: &str,
+    body: &serde_json::Value,
+    name: &'static str,
+) -> Result<(), AppError> {
+    let mut attempt = 1;
+    loop {
+        // webhook URLs include their token, so keep them out of errors
+        let resp = GENERAL_HTTP_CLIENT
+            .post(url)
+            .json(body)
+            .send()
+            .await
+            .map_err(reqwest::Error::without_url)?;
+        let status = resp.status();
+        if status == StatusCode::TOO_MANY_REQUESTS && attempt < WEBHOOK_ATTEMPTS {
+            let wait = resp
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<f64>().ok())
+                .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+                .map_or(Duration::from_secs(1), Duration::from_secs_f64)
+                .min(WEBHOOK_MAX_RETRY_AFTER);
+            warn!("{name} rate limited; retrying in {wait:?}");
+            tokio::time::sleep(wait).await;
+            attempt += 1;
+            continue;
+        }
+        if !status.is_success() {
+            return Err(AppError::HttpResponse(name, status.as_u16()));
+        }
+        return Ok(());
+    }
+}
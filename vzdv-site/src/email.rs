@@ -1,17 +1,39 @@
 use crate::shared::AppError;
+use chrono::Utc;
 use lettre::message::header::ContentType;
+use lettre::message::{Attachment, MultiPart, SinglePart};
 use lettre::transport::smtp::authentication::Credentials;
 use lettre::{Message, SmtpTransport, Transport};
-use minijinja::{context, Environment};
+use log::{error, info, warn};
+use minijinja::{context, Environment, Value};
 use sqlx::{Pool, Sqlite};
+use uuid::Uuid;
 use vzdv::config::Config;
-use vzdv::sql::{self, Controller};
+use vzdv::sql::{self, Controller, ExitSurvey};
+use vzdv::vatusa;
 
 /// Email templates.
 pub mod templates {
     pub const VISITOR_ACCEPTED: &str = "visitor_accepted";
     pub const VISITOR_DENIED: &str = "visitor_denied";
     pub const VISITOR_REMOVED: &str = "visitor_removed";
+    pub const WELCOME: &str = "welcome";
+    pub const EXIT_SURVEY: &str = "exit_survey";
+    pub const FEEDBACK_FORWARD: &str = "feedback_forward";
+    pub const PURGE_NOTICE: &str = "purge_notice";
+    pub const WAITLIST_PROMOTED: &str = "waitlist_promoted";
+    pub const EVENT_WAITLIST_PROMOTED: &str = "event_waitlist_promoted";
+    pub const TRAINING_FEEDBACK_REQUEST: &str = "training_feedback_request";
+    pub const TRAINING_BOOKED: &str = "training_booked";
+    pub const TRAINING_CANCELLED: &str = "training_cancelled";
+}
+
+/// File attached to an email.
+pub struct MailAttachment {
+    pub filename: String,
+    /// Full MIME type, with any parameters.
+    pub content_type: String,
+    pub body: String,
 }
 
 /// Send an SMTP email to the recipient.
@@ -21,12 +43,64 @@ pub async fn send_mail(
     recipient_name: &str,
     recipient_address: &str,
     template_name: &str,
+) -> Result<(), AppError> {
+    send_mail_with_context(
+        config,
+        db,
+        recipient_name,
+        recipient_address,
+        template_name,
+        context! {},
+    )
+    .await
+}
+
+/// Send an SMTP email to the recipient, with extra values available to the template.
+pub async fn send_mail_with_context(
+    config: &Config,
+    db: &Pool<Sqlite>,
+    recipient_name: &str,
+    recipient_address: &str,
+    template_name: &str,
+    extra: Value,
+) -> Result<(), AppError> {
+    send_mail_with_attachment(
+        config,
+        db,
+        recipient_name,
+        recipient_address,
+        template_name,
+        extra,
+        None,
+    )
+    .await
+}
+
+/// Send an SMTP email to the recipient, with extra values available to the template
+/// and an optional file attached.
+pub async fn send_mail_with_attachment(
+    config: &Config,
+    db: &Pool<Sqlite>,
+    recipient_name: &str,
+    recipient_address: &str,
+    template_name: &str,
+    extra: Value,
+    attachment: Option<MailAttachment>,
 ) -> Result<(), AppError> {
     // template match from config
     let template = match template_name {
         templates::VISITOR_ACCEPTED => &config.email.visitor_accepted_template,
         templates::VISITOR_DENIED => &config.email.visitor_denied_template,
         templates::VISITOR_REMOVED => &config.email.visitor_removed_template,
+        templates::WELCOME => &config.email.welcome_template,
+        templates::EXIT_SURVEY => &config.email.exit_survey_template,
+        templates::FEEDBACK_FORWARD => &config.email.feedback_forward_template,
+        templates::PURGE_NOTICE => &config.email.purge_notice_template,
+        templates::WAITLIST_PROMOTED => &config.email.waitlist_promoted_template,
+        templates::EVENT_WAITLIST_PROMOTED => &config.email.event_waitlist_promoted_template,
+        templates::TRAINING_FEEDBACK_REQUEST => &config.email.training_feedback_request_template,
+        templates::TRAINING_BOOKED => &config.email.training_booked_template,
+        templates::TRAINING_CANCELLED => &config.email.training_cancelled_template,
         _ => {
             return Err(AppError::UnknownEmailTemplate(template_name.to_owned()));
         }
@@ -50,17 +124,27 @@ pub async fn send_mail(
     env.add_template("body", &template.body)?;
     let body = env
         .get_template("body")?
-        .render(context! { recipient_name, atm, datm })?;
+        .render(context! { recipient_name, atm, datm, site => &config.hosted_domain, ..extra })?;
 
     // construct and send email
-    let email = Message::builder()
+    let builder = Message::builder()
         .from(config.email.from.parse().unwrap())
         .reply_to(config.email.reply_to.parse().unwrap())
         .to(recipient_address.parse().unwrap())
-        .subject(template.subject.to_owned())
-        .header(ContentType::TEXT_PLAIN)
-        .body(body)
-        .unwrap();
+        .subject(template.subject.to_owned());
+    let email = match attachment {
+        Some(attachment) => builder
+            .multipart(
+                MultiPart::mixed()
+                    .singlepart(SinglePart::plain(body))
+                    .singlepart(Attachment::new(attachment.filename).body(
+                        attachment.body,
+                        ContentType::parse(&attachment.content_type).unwrap(),
+                    )),
+            )
+            .unwrap(),
+        None => builder.header(ContentType::TEXT_PLAIN).body(body).unwrap(),
+    };
     let creds = Credentials::new(
         config.email.user.to_owned(),
         config.email.password.to_owned(),
@@ -72,3 +156,111 @@ pub async fn send_mail(
     mailer.send(&email)?;
     Ok(())
 }
+
+/// Send the queued welcome emails to controllers new to the roster.
+///
+/// Nothing is sent until the facility has written the welcome template. Addresses
+/// come from VATUSA, since the roster sync doesn't include them.
+pub async fn send_welcome_emails(config: &Config, db: &Pool<Sqlite>) -> Result<(), AppError> {
+    if config.email.welcome_template.body.is_empty() {
+        return Ok(());
+    }
+    let pending: Vec<Controller> = sqlx::query_as(sql::GET_PENDING_WELCOME_EMAILS)
+        .fetch_all(db)
+        .await?;
+    for controller in pending {
+        let info =
+            match vatusa::get_controller_info(controller.cid, Some(&config.vatsim.vatusa_api_key))
+                .await
+            {
+                Ok(info) => info,
+                Err(e) => {
+                    error!(
+                        "Could not get info for {}'s welcome email: {e}",
+                        controller.cid
+                    );
+                    continue;
+                }
+            };
+        match info.email {
+            Some(address) => {
+                let name = format!("{} {}", controller.first_name, controller.last_name);
+                if let Err(e) = send_mail(config, db, &name, &address, templates::WELCOME).await {
+                    error!("Could not send welcome email to {}: {e}", controller.cid);
+                    continue;
+                }
+                info!("Sent welcome email to {}", controller.cid);
+            }
+            None => warn!("No email address to welcome {} at", controller.cid),
+        }
+        sqlx::query(sql::SET_WELCOME_EMAIL_SENT)
+            .bind(controller.cid)
+            .bind(Utc::now())
+            .execute(db)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Send the queued exit surveys to controllers who left the roster.
+///
+/// Each survey gets its own link, so no login is needed to respond. Nothing is
+/// sent until the facility has written the exit survey template.
+pub async fn send_exit_surveys(config: &Config, db: &Pool<Sqlite>) -> Result<(), AppError> {
+    if config.email.exit_survey_template.body.is_empty() {
+        return Ok(());
+    }
+    let pending: Vec<ExitSurvey> = sqlx::query_as(sql::GET_UNSENT_EXIT_SURVEYS)
+        .fetch_all(db)
+        .await?;
+    for survey in pending {
+        let controller: Option<Controller> = sqlx::query_as(sql::GET_CONTROLLER_BY_CID)
+            .bind(survey.cid)
+            .fetch_optional(db)
+            .await?;
+        let info = match vatusa::get_controller_info(
+            survey.cid,
+            Some(&config.vatsim.vatusa_api_key),
+        )
+        .await
+        {
+            Ok(info) => info,
+            Err(e) => {
+                error!("Could not get info for {}'s exit survey: {e}", survey.cid);
+                continue;
+            }
+        };
+        let token = Uuid::new_v4().simple().to_string();
+        match (info.email, controller) {
+            (Some(address), Some(controller)) => {
+                let name = format!("{} {}", controller.first_name, controller.last_name);
+                let survey_link = format!(
+                    "{}/exit_survey/{token}",
+                    config.hosted_domain.trim_end_matches('/')
+                );
+                if let Err(e) = send_mail_with_context(
+                    config,
+                    db,
+                    &name,
+                    &address,
+                    templates::EXIT_SURVEY,
+                    context! { survey_link },
+                )
+                .await
+                {
+                    error!("Could not send exit survey to {}: {e}", survey.cid);
+                    continue;
+                }
+                info!("Sent exit survey {} to {}", survey.id, survey.cid);
+            }
+            _ => warn!("No email address to send {}'s exit survey to", survey.cid),
+        }
+        sqlx::query(sql::SET_EXIT_SURVEY_SENT)
+            .bind(survey.id)
+            .bind(&token)
+            .bind(Utc::now())
+            .execute(db)
+            .await?;
+    }
+    Ok(())
+}
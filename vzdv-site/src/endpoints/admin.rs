@@ -1,57 +1,291 @@
 //! Endpoints for editing and controlling aspects of the site.
 
 use crate::{
+    blocklist, discord,
     email::{self, send_mail},
+    endpoints::BANNERS_CACHE_KEY,
     flashed_messages::{self, MessageLevel},
+    logs::{self, log_file_for, LogFilter, LOG_FILES},
+    metrics::METRICS,
     shared::{
-        is_user_member_of, reject_if_not_in, AppError, AppState, UserInfo, SESSION_USER_INFO_KEY,
+        can_impersonate, has_permission, js_timestamp_to_utc, maintenance_mode, reject_without,
+        snippet_response, AppError, AppState, UserInfo, MAINTENANCE_CACHE_KEY, MAINTENANCE_SETTING,
+        SESSION_USER_INFO_KEY,
     },
 };
 use axum::{
     extract::{DefaultBodyLimit, Multipart, Path, Query, State},
-    response::{Html, IntoResponse, Redirect, Response},
+    http::header,
+    response::{
+        sse::{KeepAlive, Sse},
+        Html, IntoResponse, Redirect, Response,
+    },
     routing::{delete, get, post},
     Form, Router,
 };
-use chrono::Utc;
+use chrono::{DateTime, NaiveDate, Utc};
+use itertools::Itertools;
 use log::{debug, error, info, warn};
 use minijinja::{context, Environment};
 use reqwest::StatusCode;
-use rev_buf_reader::RevBufReader;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::{collections::HashMap, io::BufRead, path::Path as FilePath, sync::Arc};
+use sqlx::{Pool, Sqlite};
+use std::{collections::HashMap, path::Path as FilePath, sync::Arc};
 use tower_sessions::Session;
 use uuid::Uuid;
 use vzdv::{
-    sql::{self, Controller, Feedback, FeedbackForReview, Resource, VisitorRequest},
-    vatusa::{self, add_visiting_controller, get_multiple_controller_info},
-    ControllerRating, PermissionsGroup, GENERAL_HTTP_CLIENT,
+    activity,
+    config::Config,
+    display_name, get_controller_cids_and_names, integrity, permissions,
+    quarterly_report::QuarterlyReport,
+    resources::visibility,
+    sql::{
+        self, Banner, Block, Controller, ControllerPermission, ExitSurvey, Feedback,
+        FeedbackForReview, ImpersonationLogEntry, Loa, NotificationPreference, PurgeCandidate,
+        Resource, ResourceCategory, RolePermission, VisitorRequest,
+    },
+    vatusa::{self, add_visiting_controller, get_multiple_controller_info, RosterStatus},
 };
 
+/// Entry in a feedback item's review history, for display.
+#[derive(Serialize)]
+struct FeedbackHistoryEntry {
+    author: String,
+    /// Set if this entry is the feedback being assigned rather than a comment.
+    assigned_to: Option<String>,
+    comment: String,
+    created_date: DateTime<Utc>,
+}
+
+/// Feedback item with its assignment and review history, for display.
+#[derive(Serialize)]
+struct FeedbackWithHistory {
+    #[serde(flatten)]
+    feedback: FeedbackForReview,
+    assigned_to: Option<String>,
+    history: Vec<FeedbackHistoryEntry>,
+}
+
+/// Feedback ratings that are forwarded to controllers who opted in.
+const FORWARDED_FEEDBACK_RATINGS: [&str; 2] = ["excellent", "good"];
+
+/// Send approved positive feedback to the controller it's for, if they've opted in.
+async fn forward_feedback(state: &Arc<AppState>, feedback: &Feedback) -> Result<(), AppError> {
+    if !FORWARDED_FEEDBACK_RATINGS.contains(&feedback.rating.as_str()) {
+        return Ok(());
+    }
+    let preference: Option<NotificationPreference> =
+        sqlx::query_as(sql::GET_NOTIFICATION_PREFERENCE)
+            .bind(feedback.controller)
+            .fetch_optional(&state.db)
+            .await?;
+    let Some(preference) = preference else {
+        return Ok(());
+    };
+    let Some(controller) = state
+        .repos
+        .controllers
+        .get_by_cid(feedback.controller)
+        .await?
+    else {
+        return Ok(());
+    };
+    if preference.feedback_discord {
+        if let Some(discord_id) = &controller.discord_id {
+            let content = format!(
+                "You've received {} feedback for your time on {}:\n>>> {}",
+                feedback.rating, feedback.position, feedback.comments
+            );
+            discord::send_direct_message(&state.config, discord_id, &content).await?;
+            info!("DM'd feedback {} to {}", feedback.id, controller.cid);
+        }
+    }
+    if preference.feedback_email && !state.config.email.feedback_forward_template.body.is_empty() {
+        let info =
+            vatusa::get_controller_info(controller.cid, Some(&state.config.vatsim.vatusa_api_key))
+                .await?;
+        if let Some(address) = info.email {
+            email::send_mail_with_context(
+                &state.config,
+                &state.db,
+                &format!("{} {}", controller.first_name, controller.last_name),
+                &address,
+                email::templates::FEEDBACK_FORWARD,
+                context! {
+                    position => &feedback.position,
+                    rating => &feedback.rating,
+                    comments => &feedback.comments,
+                },
+            )
+            .await?;
+            info!("Emailed feedback {} to {}", feedback.id, controller.cid);
+        }
+    }
+    Ok(())
+}
+
+/// How long browsers can reuse the pending counts snippet.
+const PENDING_COUNTS_MAX_AGE: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Kind of item waiting on staff, for the dashboard.
+#[derive(Debug, Serialize)]
+struct PendingCount {
+    label: &'static str,
+    link: &'static str,
+    count: u32,
+}
+
+/// Dashboard of widgets for staff members, each refreshed on its own.
+///
+/// Staff members only.
+async fn page_dashboard(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::STAFF).await {
+        return Ok(redirect.into_response());
+    }
+    let template = state.templates.get_template("admin/dashboard")?;
+    let rendered = template.render(context! { user_info })?;
+    Ok(Html(rendered).into_response())
+}
+
+/// Render counts of the items waiting on the user, for what they can manage.
+///
+/// Staff members only.
+async fn snippet_pending_counts(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if !has_permission(&state, &user_info, permissions::STAFF).await {
+        return Ok(StatusCode::FORBIDDEN.into_response());
+    }
+    let cid = user_info.as_ref().unwrap().cid;
+    let mut counts = Vec::new();
+    if has_permission(&state, &user_info, permissions::FEEDBACK_MANAGE).await {
+        counts.push(PendingCount {
+            label: "Feedback to review",
+            link: "/admin/feedback",
+            count: sqlx::query_scalar(sql::COUNT_PENDING_FEEDBACK)
+                .fetch_one(&state.db)
+                .await?,
+        });
+    } else {
+        counts.push(PendingCount {
+            label: "Feedback assigned to you",
+            link: "/admin/feedback",
+            count: sqlx::query_scalar(sql::COUNT_PENDING_FEEDBACK_ASSIGNED_TO)
+                .bind(cid)
+                .fetch_one(&state.db)
+                .await?,
+        });
+    }
+    let by_permission = [
+        (
+            permissions::VISITORS_MANAGE,
+            "Visitor applications",
+            "/admin/visitor_applications",
+            sql::COUNT_PENDING_VISITOR_REQUESTS,
+        ),
+        (
+            permissions::ROSTER_MANAGE,
+            "Purge candidates",
+            "/admin/purge",
+            sql::COUNT_UNDECIDED_PURGE_CANDIDATES,
+        ),
+        (
+            permissions::TRAINING_MANAGE,
+            "Training waitlist",
+            "/training/waitlist",
+            sql::COUNT_TRAINING_WAITLIST,
+        ),
+    ];
+    for (permission, label, link, query) in by_permission {
+        if has_permission(&state, &user_info, permission).await {
+            counts.push(PendingCount {
+                label,
+                link,
+                count: sqlx::query_scalar(query).fetch_one(&state.db).await?,
+            });
+        }
+    }
+
+    let template = state.templates.get_template("admin/pending_counts")?;
+    let rendered = template.render(context! { counts })?;
+    Ok(snippet_response(rendered, PENDING_COUNTS_MAX_AGE, true))
+}
+
 /// Page for managing controller feedback.
 ///
 /// Feedback must be reviewed by staff before being posted to Discord.
+/// Staff who can assign feedback (the ATM, by default) can assign items to
+/// other staff members, and reviewers can discuss items in comments before
+/// action is taken.
 ///
-/// Admin staff members only.
+/// Admin staff members see all feedback; other staff members only see
+/// feedback assigned to them.
 async fn page_feedback(
     State(state): State<Arc<AppState>>,
     session: Session,
 ) -> Result<Response, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if let Some(redirect) = reject_if_not_in(&state, &user_info, PermissionsGroup::Admin).await {
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::STAFF).await {
         return Ok(redirect.into_response());
     }
+    let cid = user_info.as_ref().unwrap().cid;
+    let is_admin = has_permission(&state, &user_info, permissions::FEEDBACK_MANAGE).await;
+    let can_assign = has_permission(&state, &user_info, permissions::FEEDBACK_ASSIGN).await;
+
+    let controllers = state.repos.controllers.get_all().await?;
+    let names: HashMap<u32, String> = controllers
+        .iter()
+        .map(|c| (c.cid, format!("{} {}", c.first_name, c.last_name)))
+        .collect();
+    let name_for = |cid: u32| names.get(&cid).cloned().unwrap_or_else(|| cid.to_string());
+    let staff_cids = permissions::cids_with_permission(&state.db, permissions::STAFF).await?;
+    let staff: Vec<&Controller> = controllers
+        .iter()
+        .filter(|c| staff_cids.contains(&c.cid))
+        .collect();
+
+    let mut comments: HashMap<u32, Vec<FeedbackHistoryEntry>> = HashMap::new();
+    for comment in state.repos.feedback.get_all_comments().await? {
+        comments
+            .entry(comment.feedback_id)
+            .or_default()
+            .push(FeedbackHistoryEntry {
+                author: name_for(comment.cid),
+                assigned_to: comment.assigned_to_cid.map(name_for),
+                comment: comment.comment,
+                created_date: comment.created_date,
+            });
+    }
+    let pending_feedback: Vec<_> = state
+        .repos
+        .feedback
+        .get_for_review()
+        .await?
+        .into_iter()
+        .filter(|feedback| is_admin || feedback.assigned_to_cid == Some(cid))
+        .map(|feedback| FeedbackWithHistory {
+            assigned_to: feedback.assigned_to_cid.map(name_for),
+            history: comments.remove(&feedback.id).unwrap_or_default(),
+            feedback,
+        })
+        .collect();
+
     let template = state.templates.get_template("admin/feedback")?;
-    let pending_feedback: Vec<FeedbackForReview> =
-        sqlx::query_as(sql::GET_PENDING_FEEDBACK_FOR_REVIEW)
-            .fetch_all(&state.db)
-            .await?;
     let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
     let rendered = template.render(context! {
         user_info,
         flashed_messages,
         pending_feedback,
+        is_admin,
+        can_assign,
+        staff,
     })?;
     Ok(Html(rendered).into_response())
 }
@@ -60,108 +294,234 @@ async fn page_feedback(
 struct FeedbackReviewForm {
     id: u32,
     action: String,
+    comment: Option<String>,
+    assignee: Option<u32>,
+}
+
+/// Reviewer actions that resolve a feedback item, taking it out of the queue.
+const FEEDBACK_RESOLUTIONS: [&str; 4] =
+    ["Archive", "Delete", "Post to Discord", "Approve silently"];
+
+/// Take a reviewer action that resolves a feedback item.
+///
+/// Returns how the item was resolved, for the flashed message, or `None`
+/// if the action isn't one that resolves feedback.
+async fn resolve_feedback(
+    state: &Arc<AppState>,
+    reviewer_cid: u32,
+    feedback: &Feedback,
+    action: &str,
+) -> Result<Option<&'static str>, AppError> {
+    let done = match action {
+        "Archive" => {
+            sqlx::query(sql::UPDATE_FEEDBACK_TAKE_ACTION)
+                .bind(reviewer_cid)
+                .bind("archive")
+                .bind(false)
+                .bind(feedback.id)
+                .execute(&state.db)
+                .await?;
+            info!("{reviewer_cid} archived feedback {}", feedback.id);
+            "archived"
+        }
+        "Delete" => {
+            sqlx::query(sql::DELETE_FROM_FEEDBACK)
+                .bind(feedback.id)
+                .execute(&state.db)
+                .await?;
+            info!(
+                "{reviewer_cid} deleted {} feedback {} for {} by {}",
+                feedback.rating, feedback.id, feedback.controller, feedback.submitter_cid
+            );
+            "deleted"
+        }
+        "Post to Discord" | "Approve silently" => {
+            let post_to_discord = action == "Post to Discord";
+            if post_to_discord {
+                let controller: Option<Controller> = state
+                    .repos
+                    .controllers
+                    .get_by_cid(feedback.controller)
+                    .await?;
+                discord::post_webhook(
+                    &state.config.discord.webhooks.feedback,
+                    &json!({
+                        "content": "",
+                        "embeds": [{
+                            "title": "Feedback received",
+                            "fields": [
+                                {
+                                    "name": "Controller",
+                                    "value": controller.map(|c| format!("{} {}", c.first_name, c.last_name)).unwrap_or_default()
+                                },
+                                {
+                                    "name": "Position",
+                                    "value": feedback.position
+                                },
+                                {
+                                    "name": "Rating",
+                                    "value": feedback.rating
+                                },
+                                {
+                                    "name": "Comments",
+                                    "value": feedback.comments
+                                }
+                            ]
+                        }]
+                    }),
+                    "Discord feedback webhook",
+                )
+                .await?;
+                info!(
+                    "{reviewer_cid} submitted feedback {} to Discord",
+                    feedback.id
+                );
+            } else {
+                info!(
+                    "{reviewer_cid} approved feedback {} without posting to Discord",
+                    feedback.id
+                );
+            }
+            if let Err(e) = forward_feedback(state, feedback).await {
+                error!(
+                    "Error forwarding feedback {} to the controller: {e}",
+                    feedback.id
+                );
+            }
+            sqlx::query(sql::UPDATE_FEEDBACK_TAKE_ACTION)
+                .bind(reviewer_cid)
+                .bind("post")
+                .bind(post_to_discord)
+                .bind(feedback.id)
+                .execute(&state.db)
+                .await?;
+            if post_to_discord {
+                "shared"
+            } else {
+                "approved"
+            }
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(done))
 }
 
 /// Handler for staff members taking action on feedback.
 ///
-/// Admin staff members only.
+/// Admin staff members can take any action. Other staff members can only
+/// comment on feedback assigned to them, and only those with the assigning
+/// capability (the ATM, by default) can assign it.
 async fn post_feedback_form_handle(
     State(state): State<Arc<AppState>>,
     session: Session,
     Form(feedback_form): Form<FeedbackReviewForm>,
 ) -> Result<Response, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if let Some(redirect) = reject_if_not_in(&state, &user_info, PermissionsGroup::Admin).await {
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::STAFF).await {
         return Ok(redirect.into_response());
     }
+    let is_admin = has_permission(&state, &user_info, permissions::FEEDBACK_MANAGE).await;
+    let can_assign = has_permission(&state, &user_info, permissions::FEEDBACK_ASSIGN).await;
     let user_info = user_info.unwrap();
-    let db_feedback: Option<Feedback> = sqlx::query_as(sql::GET_FEEDBACK_BY_ID)
-        .bind(feedback_form.id)
-        .fetch_optional(&state.db)
-        .await?;
-    if let Some(feedback) = db_feedback {
-        if feedback_form.action == "Archive" {
-            sqlx::query(sql::UPDATE_FEEDBACK_TAKE_ACTION)
-                .bind(user_info.cid)
-                .bind("archive")
-                .bind(false)
-                .bind(feedback_form.id)
+    let db_feedback: Option<Feedback> = state.repos.feedback.get_by_id(feedback_form.id).await?;
+    if let Some(feedback) = db_feedback.as_ref() {
+        if feedback_form.action == "Comment" {
+            if !is_admin && feedback.assigned_to_cid != Some(user_info.cid) {
+                return Ok(Redirect::to("/").into_response());
+            }
+            let comment = feedback_form.comment.unwrap_or_default();
+            let comment = comment.trim();
+            if comment.is_empty() {
+                flashed_messages::push_flashed_message(
+                    session,
+                    MessageLevel::Error,
+                    "Comment cannot be empty",
+                )
+                .await?;
+            } else {
+                sqlx::query(sql::INSERT_FEEDBACK_COMMENT)
+                    .bind(feedback.id)
+                    .bind(user_info.cid)
+                    .bind(comment)
+                    .bind(None::<u32>)
+                    .bind(Utc::now())
+                    .execute(&state.db)
+                    .await?;
+                info!("{} commented on feedback {}", user_info.cid, feedback.id);
+                flashed_messages::push_flashed_message(
+                    session,
+                    MessageLevel::Success,
+                    "Comment added",
+                )
+                .await?;
+            }
+            return Ok(Redirect::to("/admin/feedback").into_response());
+        }
+        if feedback_form.action == "Assign" {
+            if !can_assign {
+                return Ok(Redirect::to("/").into_response());
+            }
+            let assignee = match feedback_form.assignee {
+                Some(cid) => state.repos.controllers.get_by_cid(cid).await?,
+                None => None,
+            };
+            let assignee = match assignee {
+                Some(c)
+                    if permissions::has_permission(&state.db, c.cid, permissions::STAFF)
+                        .await? =>
+                {
+                    c
+                }
+                _ => {
+                    flashed_messages::push_flashed_message(
+                        session,
+                        MessageLevel::Error,
+                        "Feedback can only be assigned to staff members",
+                    )
+                    .await?;
+                    return Ok(Redirect::to("/admin/feedback").into_response());
+                }
+            };
+            sqlx::query(sql::UPDATE_FEEDBACK_ASSIGNED)
+                .bind(feedback.id)
+                .bind(assignee.cid)
                 .execute(&state.db)
                 .await?;
-            info!("{} archived feedback {}", user_info.cid, feedback.id);
-            flashed_messages::push_flashed_message(
-                session,
-                MessageLevel::Success,
-                "Feedback archived",
-            )
-            .await?;
-        } else if feedback_form.action == "Delete" {
-            sqlx::query(sql::DELETE_FROM_FEEDBACK)
-                .bind(feedback_form.id)
+            sqlx::query(sql::INSERT_FEEDBACK_COMMENT)
+                .bind(feedback.id)
+                .bind(user_info.cid)
+                .bind("")
+                .bind(assignee.cid)
+                .bind(Utc::now())
                 .execute(&state.db)
                 .await?;
             info!(
-                "{} deleted {} feedback {} for {} by {}",
-                user_info.cid,
-                feedback.rating,
-                feedback.id,
-                feedback.controller,
-                feedback.submitter_cid
+                "{} assigned feedback {} to {}",
+                user_info.cid, feedback.id, assignee.cid
             );
             flashed_messages::push_flashed_message(
                 session,
                 MessageLevel::Success,
-                "Feedback deleted",
+                &format!(
+                    "Feedback assigned to {} {}",
+                    assignee.first_name, assignee.last_name
+                ),
             )
             .await?;
-        } else if feedback_form.action == "Post to Discord" {
-            let controller: Option<Controller> = sqlx::query_as(sql::GET_CONTROLLER_BY_CID)
-                .bind(feedback.controller)
-                .fetch_optional(&state.db)
-                .await?;
-            GENERAL_HTTP_CLIENT
-                .post(&state.config.discord.webhooks.feedback)
-                .json(&json!({
-                    "content": "",
-                    "embeds": [{
-                        "title": "Feedback received",
-                        "fields": [
-                            {
-                                "name": "Controller",
-                                "value": controller.map(|c| format!("{} {}", c.first_name, c.last_name)).unwrap_or_default()
-                            },
-                            {
-                                "name": "Position",
-                                "value": feedback.position
-                            },
-                            {
-                                "name": "Rating",
-                                "value": feedback.rating
-                            },
-                            {
-                                "name": "Comments",
-                                "value": feedback.comments
-                            }
-                        ]
-                    }]
-                }))
-                .send()
-                .await?;
-            info!(
-                "{} submitted feedback {} to Discord",
-                user_info.cid, feedback.id
-            );
-            sqlx::query(sql::UPDATE_FEEDBACK_TAKE_ACTION)
-                .bind(user_info.cid)
-                .bind("post")
-                .bind(true)
-                .bind(feedback_form.id)
-                .execute(&state.db)
-                .await?;
+            return Ok(Redirect::to("/admin/feedback").into_response());
+        }
+    }
+    if !is_admin {
+        return Ok(Redirect::to("/").into_response());
+    }
+    if let Some(feedback) = db_feedback {
+        if let Some(done) =
+            resolve_feedback(&state, user_info.cid, &feedback, &feedback_form.action).await?
+        {
             flashed_messages::push_flashed_message(
                 session,
                 MessageLevel::Success,
-                "Feedback shared",
+                &format!("Feedback {done}"),
             )
             .await?;
         }
@@ -173,6 +533,104 @@ async fn post_feedback_form_handle(
     Ok(Redirect::to("/admin/feedback").into_response())
 }
 
+/// Handler for taking the same action on several feedback items at once,
+/// for clearing out the queue after busy events.
+///
+/// The form has the action and an `ids` field for each selected item.
+///
+/// Admin staff members only.
+async fn post_feedback_bulk(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::FEEDBACK_MANAGE).await {
+        return Ok(redirect.into_response());
+    }
+    let user_info = user_info.unwrap();
+    let action = fields
+        .iter()
+        .find(|(key, _)| key == "action")
+        .map(|(_, value)| value.as_str())
+        .unwrap_or_default();
+    let ids: Vec<u32> = fields
+        .iter()
+        .filter(|(key, _)| key == "ids")
+        .filter_map(|(_, value)| value.parse().ok())
+        .collect();
+    if !FEEDBACK_RESOLUTIONS.contains(&action) {
+        flashed_messages::push_flashed_message(session, MessageLevel::Error, "Unknown action")
+            .await?;
+        return Ok(Redirect::to("/admin/feedback").into_response());
+    }
+    if ids.is_empty() {
+        flashed_messages::push_flashed_message(
+            session,
+            MessageLevel::Error,
+            "No feedback selected",
+        )
+        .await?;
+        return Ok(Redirect::to("/admin/feedback").into_response());
+    }
+
+    let mut resolved = Vec::new();
+    let mut failed = None;
+    for id in ids {
+        let Some(feedback) = state.repos.feedback.get_by_id(id).await? else {
+            continue;
+        };
+        // a stale page or a double submit can include items that are already done
+        if feedback.posted_to_discord || feedback.reviewer_action == "post" {
+            continue;
+        }
+        match resolve_feedback(&state, user_info.cid, &feedback, action).await {
+            Ok(Some(done)) => resolved.push(done),
+            Ok(None) => {}
+            Err(e) => {
+                // stop here, leaving the rest in the queue to try again later
+                error!("Error taking bulk action \"{action}\" on feedback {id}: {e}");
+                failed = Some(id);
+                break;
+            }
+        }
+    }
+    if let Some(id) = failed {
+        flashed_messages::push_flashed_message(
+            session.clone(),
+            MessageLevel::Error,
+            &format!(
+                "Stopped at feedback {id}, which couldn't be resolved; it and the rest were left in the queue"
+            ),
+        )
+        .await?;
+    }
+    match resolved.first() {
+        Some(done) => {
+            flashed_messages::push_flashed_message(
+                session,
+                MessageLevel::Success,
+                &format!(
+                    "{} feedback item{} {done}",
+                    resolved.len(),
+                    if resolved.len() == 1 { "" } else { "s" }
+                ),
+            )
+            .await?
+        }
+        None if failed.is_none() => {
+            flashed_messages::push_flashed_message(
+                session,
+                MessageLevel::Error,
+                "No feedback left to resolve",
+            )
+            .await?
+        }
+        None => {}
+    }
+    Ok(Redirect::to("/admin/feedback").into_response())
+}
+
 /// Admin page to manually send emails.
 ///
 /// Admin staff members only.
@@ -181,12 +639,10 @@ async fn page_email_manual_send(
     session: Session,
 ) -> Result<Response, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if let Some(redirect) = reject_if_not_in(&state, &user_info, PermissionsGroup::Admin).await {
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::EMAIL_SEND).await {
         return Ok(redirect.into_response());
     }
-    let all_controllers: Vec<Controller> = sqlx::query_as(sql::GET_ALL_CONTROLLERS)
-        .fetch_all(&state.db)
-        .await?;
+    let all_controllers: Vec<Controller> = state.repos.controllers.get_all().await?;
     let template = state.templates.get_template("admin/manual_email")?;
     let rendered = template.render(context! { user_info, all_controllers })?;
     Ok(Html(rendered).into_response())
@@ -207,12 +663,13 @@ async fn post_email_manual_send(
     Form(manual_email_form): Form<ManualEmailForm>,
 ) -> Result<Response, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if let Some(redirect) = reject_if_not_in(&state, &user_info, PermissionsGroup::Admin).await {
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::EMAIL_SEND).await {
         return Ok(redirect.into_response());
     }
-    let controller: Option<Controller> = sqlx::query_as(sql::GET_CONTROLLER_BY_CID)
-        .bind(manual_email_form.recipient)
-        .fetch_optional(&state.db)
+    let controller: Option<Controller> = state
+        .repos
+        .controllers
+        .get_by_cid(manual_email_form.recipient)
         .await?;
     let controller = match controller {
         Some(c) => c,
@@ -230,8 +687,7 @@ async fn post_email_manual_send(
         manual_email_form.recipient,
         Some(&state.config.vatsim.vatusa_api_key),
     )
-    .await
-    .map_err(|err| AppError::GenericFallback("getting controller info", err))?;
+    .await?;
     let email = match controller_info.email {
         Some(e) => e,
         None => {
@@ -256,10 +712,37 @@ async fn post_email_manual_send(
     Ok(Redirect::to("/admin/email/manual").into_response())
 }
 
+/// Read the viewer's file, filters, and paging from the query string.
+fn parse_log_params(params: &HashMap<String, String>) -> (&'static str, LogFilter, usize, usize) {
+    let file_key = params.get("file").map(String::as_str).unwrap_or("site");
+    let file_key = LOG_FILES
+        .iter()
+        .find(|(key, _)| *key == file_key)
+        .map(|(key, _)| *key)
+        .unwrap_or("site");
+    let filter = LogFilter {
+        level: params.get("level").filter(|l| !l.is_empty()).cloned(),
+        target: params.get("target").cloned(),
+        text: params.get("text").cloned(),
+    };
+    let page = params.get("page").and_then(|p| p.parse().ok()).unwrap_or(0);
+    let line_count: usize = match params.get("lines") {
+        Some(n) => match n.parse() {
+            Ok(n) => n,
+            Err(_) => {
+                warn!("Error parsing 'lines' query param on logs page");
+                100
+            }
+        },
+        None => 100,
+    };
+    (file_key, filter, page, line_count.clamp(1, 1_000))
+}
+
 /// Page for logs.
 ///
-/// Read the last hundred lines from each of the log files
-/// and show them in the page.
+/// Shows a page of lines from one of the log files, newest page first,
+/// optionally filtered by level, target, and text.
 ///
 /// Admin staff members only.
 async fn page_logs(
@@ -268,112 +751,210 @@ async fn page_logs(
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Response, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if let Some(redirect) = reject_if_not_in(&state, &user_info, PermissionsGroup::Admin).await {
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::SITE_ADMIN).await {
         return Ok(redirect.into_response());
     }
-    let line_count: u64 = match params.get("lines") {
-        Some(n) => match n.parse() {
-            Ok(n) => n,
-            Err(_) => {
-                warn!("Error parsing 'lines' query param on logs page");
-                100
-            }
-        },
-        None => 100,
+    let (file_key, filter, page, line_count) = parse_log_params(&params);
+    let file_name = log_file_for(file_key).unwrap_or_default();
+    let (lines, has_older) = match logs::read_page(file_name, &filter, page, line_count) {
+        Ok(result) => result,
+        Err(e) => {
+            error!("Error reading log file {file_name}: {e}");
+            (Vec::new(), false)
+        }
     };
+    let files: Vec<_> = LOG_FILES.iter().map(|(key, _)| *key).collect();
 
-    let file_names = ["vzdv_site.log", "vzdv_tasks.log", "vzdv_bot.log"];
-    let mut logs: HashMap<&str, String> = HashMap::new();
-    for name in file_names {
-        let mut buffer = Vec::new();
-        let file = match std::fs::File::open(name) {
-            Ok(f) => f,
-            Err(e) => {
-                error!("Error reading log file: {e}");
-                logs.insert(name, String::new());
-                continue;
-            }
-        };
-        let reader = RevBufReader::new(file);
-        let mut by_line = reader.lines();
-        for _ in 0..line_count {
-            if let Some(line) = by_line.next() {
-                let line = line.unwrap();
-                buffer.push(line);
-            } else {
-                break;
-            }
+    let template = state.templates.get_template("admin/logs")?;
+    let rendered = template.render(context! {
+        user_info,
+        files,
+        file_key,
+        filter,
+        lines,
+        line_count,
+        page,
+        has_older,
+    })?;
+    Ok(Html(rendered).into_response())
+}
+
+/// Server-sent events of new lines in a log file, for the viewer's live tail.
+///
+/// Takes the same query parameters as the logs page.
+///
+/// Admin staff members only.
+async fn api_logs_tail(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if !has_permission(&state, &user_info, permissions::SITE_ADMIN).await {
+        return Ok(StatusCode::FORBIDDEN.into_response());
+    }
+    let (file_key, filter, _, _) = parse_log_params(&params);
+    let file_name = log_file_for(file_key).unwrap_or_default();
+    match logs::tail(file_name, filter).await {
+        Ok(stream) => Ok(Sse::new(stream)
+            .keep_alive(KeepAlive::default())
+            .into_response()),
+        Err(e) => {
+            error!("Error opening log file {file_name} to tail: {e}");
+            Ok(StatusCode::NOT_FOUND.into_response())
         }
-        buffer.reverse();
-        logs.insert(name, buffer.join("<br>"));
     }
+}
 
-    let template = state.templates.get_template("admin/logs")?;
-    let rendered = template.render(context! { user_info, logs, line_count })?;
+/// Page for request timing and status counts, per route.
+///
+/// Admin staff members only.
+async fn page_stats(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::SITE_ADMIN).await {
+        return Ok(redirect.into_response());
+    }
+    let routes = METRICS.summaries();
+    let template = state.templates.get_template("admin/stats")?;
+    let rendered = template.render(context! { user_info, routes })?;
     Ok(Html(rendered).into_response())
 }
 
-/// Page for managing visitor applications.
+/// Page for inspecting and invalidating the server-side cache.
 ///
 /// Admin staff members only.
-async fn page_visitor_applications(
+async fn page_cache(
     State(state): State<Arc<AppState>>,
     session: Session,
 ) -> Result<Response, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if let Some(redirect) = reject_if_not_in(&state, &user_info, PermissionsGroup::Admin).await {
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::SITE_ADMIN).await {
         return Ok(redirect.into_response());
     }
-    let requests: Vec<VisitorRequest> = sqlx::query_as(sql::GET_ALL_VISITOR_REQUESTS)
-        .fetch_all(&state.db)
-        .await?;
-    let request_cids: Vec<_> = requests.iter().map(|request| request.cid).collect();
-    let controller_info = get_multiple_controller_info(&request_cids).await;
-    let already_visiting = request_cids.iter().fold(HashMap::new(), |mut map, cid| {
-        let info = controller_info.iter().find(|&info| info.cid == *cid);
-        if let Some(info) = info {
-            let already_visiting: Vec<String> = info
-                .visiting_facilities
-                .as_ref()
-                .map(|visiting| {
-                    visiting
-                        .iter()
-                        .map(|visit| visit.facility.to_owned())
-                        .collect()
-                })
-                .unwrap_or_default();
-            map.insert(cid, already_visiting.join(", "));
-        }
-        map
-    });
-
+    let entries = state.cache.entries();
     let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
-    let template = state.templates.get_template("admin/visitor_applications")?;
-    let rendered = template.render(context! {
-        user_info,
-        flashed_messages,
-        requests,
-        already_visiting,
-    })?;
+    let template = state.templates.get_template("admin/cache")?;
+    let rendered = template.render(context! { user_info, flashed_messages, entries })?;
     Ok(Html(rendered).into_response())
 }
 
 #[derive(Deserialize)]
-struct VisitorApplicationActionForm {
+struct CacheActionForm {
     action: String,
+    key: Option<String>,
 }
 
-/// Form submission for managing visitor applications.
+/// Form submission for invalidating one cache entry or flushing all of them.
 ///
 /// Admin staff members only.
-async fn post_visitor_application_action(
+async fn post_cache_action(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Form(cache_form): Form<CacheActionForm>,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::SITE_ADMIN).await {
+        return Ok(redirect.into_response());
+    }
+    let user_info = user_info.unwrap();
+    match (cache_form.action.as_str(), cache_form.key) {
+        ("Invalidate", Some(key)) => {
+            state.cache.invalidate(&key);
+            info!("{} invalidated cache entry {key}", user_info.cid);
+            flashed_messages::push_flashed_message(
+                session,
+                MessageLevel::Success,
+                &format!("Invalidated {key}"),
+            )
+            .await?;
+        }
+        ("Flush all", _) => {
+            state.cache.invalidate_all();
+            info!("{} flushed the cache", user_info.cid);
+            flashed_messages::push_flashed_message(session, MessageLevel::Success, "Cache flushed")
+                .await?;
+        }
+        _ => {
+            warn!(
+                "{} submitted unknown cache action {}",
+                user_info.cid, cache_form.action
+            );
+            flashed_messages::push_flashed_message(session, MessageLevel::Error, "Unknown action")
+                .await?;
+        }
+    }
+    Ok(Redirect::to("/admin/cache").into_response())
+}
+
+/// Page for managing visitor applications.
+///
+/// Admin staff members only.
+async fn page_visitor_applications(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::VISITORS_MANAGE).await {
+        return Ok(redirect.into_response());
+    }
+    let requests: Vec<VisitorRequest> = sqlx::query_as(sql::GET_ALL_VISITOR_REQUESTS)
+        .fetch_all(&state.db)
+        .await?;
+    let request_cids: Vec<_> = requests.iter().map(|request| request.cid).collect();
+    let controller_info = get_multiple_controller_info(&request_cids).await;
+    let already_visiting = request_cids.iter().fold(HashMap::new(), |mut map, cid| {
+        let info = controller_info.iter().find(|&info| info.cid == *cid);
+        if let Some(info) = info {
+            let already_visiting: Vec<String> = info
+                .visiting_facilities
+                .as_ref()
+                .map(|visiting| {
+                    visiting
+                        .iter()
+                        .map(|visit| visit.facility.to_owned())
+                        .collect()
+                })
+                .unwrap_or_default();
+            map.insert(cid, already_visiting.join(", "));
+        }
+        map
+    });
+
+    let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
+    let template = state.templates.get_template("admin/visitor_applications")?;
+    let rendered = template.render(context! {
+        user_info,
+        flashed_messages,
+        requests,
+        already_visiting,
+    })?;
+    Ok(Html(rendered).into_response())
+}
+
+#[derive(Deserialize)]
+struct VisitorApplicationActionForm {
+    action: String,
+    notes: Option<String>,
+}
+
+/// Form submission for managing visitor applications.
+///
+/// Accepting or denying a request emails the applicant and removes the
+/// request. Holding it parks it until staff resume it, and notes are
+/// only for staff.
+///
+/// Admin staff members only.
+async fn post_visitor_application_action(
     State(state): State<Arc<AppState>>,
     session: Session,
     Path(id): Path<u32>,
     Form(action_form): Form<VisitorApplicationActionForm>,
 ) -> Result<Redirect, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if let Some(redirect) = reject_if_not_in(&state, &user_info, PermissionsGroup::Admin).await {
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::VISITORS_MANAGE).await {
         return Ok(redirect);
     }
     let user_info = user_info.unwrap();
@@ -393,10 +974,52 @@ async fn post_visitor_application_action(
             return Ok(Redirect::to("/admin/visitor_applications"));
         }
     };
+    match action_form.action.as_str() {
+        "hold" | "resume" => {
+            let status = if action_form.action == "hold" {
+                "hold"
+            } else {
+                "pending"
+            };
+            sqlx::query(sql::UPDATE_VISITOR_REQUEST_STATUS)
+                .bind(id)
+                .bind(status)
+                .execute(&state.db)
+                .await?;
+            info!(
+                "{} set visitor request {id} for {} to {status}",
+                user_info.cid, request.cid
+            );
+            flashed_messages::push_flashed_message(
+                session,
+                MessageLevel::Success,
+                if status == "hold" {
+                    "Visitor request put on hold"
+                } else {
+                    "Visitor request resumed"
+                },
+            )
+            .await?;
+            return Ok(Redirect::to("/admin/visitor_applications"));
+        }
+        "notes" => {
+            sqlx::query(sql::UPDATE_VISITOR_REQUEST_NOTES)
+                .bind(id)
+                .bind(action_form.notes.unwrap_or_default().trim())
+                .execute(&state.db)
+                .await?;
+            info!(
+                "{} updated notes on visitor request {id} for {}",
+                user_info.cid, request.cid
+            );
+            flashed_messages::push_flashed_message(session, MessageLevel::Success, "Notes saved")
+                .await?;
+            return Ok(Redirect::to("/admin/visitor_applications"));
+        }
+        _ => {}
+    }
     let controller_info =
-        vatusa::get_controller_info(request.cid, Some(&state.config.vatsim.vatusa_api_key))
-            .await
-            .map_err(|err| AppError::GenericFallback("getting controller info", err))?;
+        vatusa::get_controller_info(request.cid, Some(&state.config.vatsim.vatusa_api_key)).await?;
     info!(
         "{} taking action {} on visitor request {id}",
         user_info.cid, action_form.action
@@ -404,9 +1027,7 @@ async fn post_visitor_application_action(
 
     if action_form.action == "accept" {
         // add to roster
-        add_visiting_controller(request.cid, &state.config.vatsim.vatusa_api_key)
-            .await
-            .map_err(|err| AppError::GenericFallback("could not add visitor", err))?;
+        add_visiting_controller(request.cid, &state.config.vatsim.vatusa_api_key).await?;
 
         // inform if possible
         if let Some(email_address) = controller_info.email {
@@ -472,38 +1093,44 @@ async fn post_visitor_application_action(
 
 /// Page for managing the site's resource documents and links.
 ///
-/// Named staff members only.
+/// Staff with the resources capability, like the FE and AFE.
 async fn page_resources(
     State(state): State<Arc<AppState>>,
     session: Session,
 ) -> Result<Response, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if let Some(redirect) =
-        reject_if_not_in(&state, &user_info, PermissionsGroup::NamedPosition).await
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::RESOURCES_MANAGE).await
     {
         return Ok(redirect.into_response());
     }
     let resources: Vec<Resource> = sqlx::query_as(sql::GET_ALL_RESOURCES)
         .fetch_all(&state.db)
         .await?;
-    let categories = &state.config.database.resource_category_ordering;
+    let categories: Vec<ResourceCategory> = sqlx::query_as(sql::GET_RESOURCE_CATEGORIES)
+        .fetch_all(&state.db)
+        .await?;
     let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
     let template = state.templates.get_template("admin/resources")?;
-    let rendered =
-        template.render(context! { user_info, flashed_messages, resources, categories })?;
+    let rendered = template.render(context! {
+        user_info,
+        flashed_messages,
+        resources,
+        categories,
+        visibilities => visibility::ALL,
+    })?;
     Ok(Html(rendered).into_response())
 }
 
 /// API endpoint for deleting a resource.
 ///
-/// Named staff members only.
+/// Staff with the resources capability, like the FE and AFE.
 async fn api_delete_resource(
     State(state): State<Arc<AppState>>,
     session: Session,
     Path(id): Path<u32>,
 ) -> Result<StatusCode, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if !is_user_member_of(&state, &user_info, PermissionsGroup::NamedPosition).await {
+    if !has_permission(&state, &user_info, permissions::RESOURCES_MANAGE).await {
         return Ok(StatusCode::FORBIDDEN);
     }
     let user_info = user_info.unwrap();
@@ -531,14 +1158,15 @@ async fn api_delete_resource(
 
 /// Form submission for creating a new resource.
 ///
-/// Named staff members only.
+/// Staff with the resources capability, like the FE and AFE.
 async fn post_new_resource(
     State(state): State<Arc<AppState>>,
     session: Session,
     mut form: Multipart,
 ) -> Result<Redirect, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if let Some(redirect) = reject_if_not_in(&state, &user_info, PermissionsGroup::Admin).await {
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::RESOURCES_MANAGE).await
+    {
         return Ok(redirect);
     }
     let user_info = user_info.unwrap();
@@ -579,6 +1207,18 @@ async fn post_new_resource(
         }
     }
 
+    let categories: Vec<ResourceCategory> = sqlx::query_as(sql::GET_RESOURCE_CATEGORIES)
+        .fetch_all(&state.db)
+        .await?;
+    if !categories
+        .iter()
+        .any(|category| category.name == resource.category && !category.archived)
+    {
+        flashed_messages::push_flashed_message(session, MessageLevel::Error, "Unknown category")
+            .await?;
+        return Ok(Redirect::to("/admin/resources"));
+    }
+
     // save the constructed struct fields
     sqlx::query(sql::CREATE_NEW_RESOURCE)
         .bind(&resource.category)
@@ -598,104 +1238,2593 @@ async fn post_new_resource(
     Ok(Redirect::to("/admin/resources"))
 }
 
-/// Page for controllers that are not on the roster but have controller DB entries.
+#[derive(Deserialize)]
+struct ResourceCategoryForm {
+    action: String,
+    id: Option<u32>,
+    name: Option<String>,
+    visibility: Option<String>,
+}
+
+/// Form submission for creating, renaming, reordering, archiving, and restricting resource categories.
 ///
-/// Named staff members only.
-async fn page_off_roster_list(
+/// Staff with the resources capability, like the FE and AFE.
+async fn post_resource_category_action(
     State(state): State<Arc<AppState>>,
     session: Session,
-) -> Result<Response, AppError> {
+    Form(category_form): Form<ResourceCategoryForm>,
+) -> Result<Redirect, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if let Some(redirect) = reject_if_not_in(&state, &user_info, PermissionsGroup::SomeStaff).await
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::RESOURCES_MANAGE).await
     {
+        return Ok(redirect);
+    }
+    let user_info = user_info.unwrap();
+    let categories: Vec<ResourceCategory> = sqlx::query_as(sql::GET_RESOURCE_CATEGORIES)
+        .fetch_all(&state.db)
+        .await?;
+    let name = category_form.name.as_deref().unwrap_or_default().trim();
+    let existing = category_form
+        .id
+        .and_then(|id| categories.iter().position(|category| category.id == id));
+
+    let message = match (category_form.action.as_str(), existing) {
+        ("create", _) => {
+            if name.is_empty() || categories.iter().any(|category| category.name == name) {
+                flashed_messages::push_flashed_message(
+                    session,
+                    MessageLevel::Error,
+                    "Categories need a unique name",
+                )
+                .await?;
+                return Ok(Redirect::to("/admin/resources"));
+            }
+            sqlx::query(sql::CREATE_RESOURCE_CATEGORY)
+                .bind(name)
+                .execute(&state.db)
+                .await?;
+            info!("{} created resource category {name}", user_info.cid);
+            "Category created"
+        }
+        ("rename", Some(index)) => {
+            let category = &categories[index];
+            if name.is_empty()
+                || categories
+                    .iter()
+                    .any(|other| other.name == name && other.id != category.id)
+            {
+                flashed_messages::push_flashed_message(
+                    session,
+                    MessageLevel::Error,
+                    "Categories need a unique name",
+                )
+                .await?;
+                return Ok(Redirect::to("/admin/resources"));
+            }
+            let mut tx = state.db.begin().await?;
+            sqlx::query(sql::RENAME_RESOURCE_CATEGORY)
+                .bind(category.id)
+                .bind(name)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(sql::UPDATE_RESOURCES_CATEGORY)
+                .bind(&category.name)
+                .bind(name)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+            info!(
+                "{} renamed resource category {} to {name}",
+                user_info.cid, category.name
+            );
+            "Category renamed"
+        }
+        (action @ ("move_up" | "move_down"), Some(index)) => {
+            let other = if action == "move_up" {
+                index.checked_sub(1)
+            } else {
+                Some(index + 1).filter(|other| *other < categories.len())
+            };
+            if let Some(other) = other {
+                let mut order: Vec<u32> = categories.iter().map(|category| category.id).collect();
+                order.swap(index, other);
+                let mut tx = state.db.begin().await?;
+                for (position, id) in order.iter().enumerate() {
+                    sqlx::query(sql::SET_RESOURCE_CATEGORY_ORDER)
+                        .bind(id)
+                        .bind(position as u32 + 1)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                tx.commit().await?;
+                info!(
+                    "{} moved resource category {} {}",
+                    user_info.cid,
+                    categories[index].name,
+                    if action == "move_up" { "up" } else { "down" }
+                );
+            }
+            "Category moved"
+        }
+        (action @ ("archive" | "unarchive"), Some(index)) => {
+            let archived = action == "archive";
+            sqlx::query(sql::SET_RESOURCE_CATEGORY_ARCHIVED)
+                .bind(categories[index].id)
+                .bind(archived)
+                .execute(&state.db)
+                .await?;
+            info!(
+                "{} {action}d resource category {}",
+                user_info.cid, categories[index].name
+            );
+            if archived {
+                "Category archived"
+            } else {
+                "Category restored"
+            }
+        }
+        ("visibility", Some(index)) => {
+            let category_visibility = category_form.visibility.as_deref().unwrap_or_default();
+            if !visibility::ALL
+                .iter()
+                .any(|(value, _)| *value == category_visibility)
+            {
+                flashed_messages::push_flashed_message(
+                    session,
+                    MessageLevel::Error,
+                    "Unknown visibility",
+                )
+                .await?;
+                return Ok(Redirect::to("/admin/resources"));
+            }
+            sqlx::query(sql::SET_RESOURCE_CATEGORY_VISIBILITY)
+                .bind(categories[index].id)
+                .bind(category_visibility)
+                .execute(&state.db)
+                .await?;
+            info!(
+                "{} set resource category {} visibility to {category_visibility}",
+                user_info.cid, categories[index].name
+            );
+            "Category visibility updated"
+        }
+        _ => {
+            warn!(
+                "{} submitted unknown resource category action {} for {:?}",
+                user_info.cid, category_form.action, category_form.id
+            );
+            flashed_messages::push_flashed_message(session, MessageLevel::Error, "Unknown action")
+                .await?;
+            return Ok(Redirect::to("/admin/resources"));
+        }
+    };
+    flashed_messages::push_flashed_message(session, MessageLevel::Success, message).await?;
+    Ok(Redirect::to("/admin/resources"))
+}
+
+#[derive(Serialize)]
+struct LoaWithName {
+    #[serde(flatten)]
+    loa: Loa,
+    name: String,
+    active: bool,
+}
+
+/// Page for managing controllers' leaves of absence.
+///
+/// Lists current and upcoming LOAs, plus the full LOA history of the
+/// controller in the `cid` query parameter, if present.
+///
+/// Admin staff members only.
+async fn page_loa(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::LOA_MANAGE).await {
         return Ok(redirect.into_response());
     }
-    let controllers: Vec<Controller> = sqlx::query_as(sql::GET_ALL_CONTROLLERS_OFF_ROSTER)
+    let now = Utc::now();
+    let controllers: Vec<Controller> = state.repos.controllers.get_all().await?;
+    let name_of = |cid: u32| {
+        controllers
+            .iter()
+            .find(|c| c.cid == cid)
+            .map(|c| format!("{} {}", c.first_name, c.last_name))
+            .unwrap_or_else(|| format!("{cid}"))
+    };
+    let with_names = |loas: Vec<Loa>| -> Vec<LoaWithName> {
+        loas.into_iter()
+            .map(|loa| LoaWithName {
+                name: name_of(loa.cid),
+                active: loa.start_date <= now && loa.end_date > now,
+                loa,
+            })
+            .collect()
+    };
+
+    let loas: Vec<Loa> = sqlx::query_as(sql::GET_CURRENT_AND_UPCOMING_LOAS)
+        .bind(now)
         .fetch_all(&state.db)
         .await?;
+    let loas = with_names(loas);
+    let (history_cid, history) = match params.get("cid").and_then(|cid| cid.parse::<u32>().ok()) {
+        Some(cid) => {
+            let history: Vec<Loa> = sqlx::query_as(sql::GET_ALL_LOAS_FOR)
+                .bind(cid)
+                .fetch_all(&state.db)
+                .await?;
+            (Some(cid), with_names(history))
+        }
+        None => (None, Vec::new()),
+    };
+    let history_name = history_cid.map(name_of);
+    let mut roster: Vec<(u32, String)> = controllers
+        .iter()
+        .filter(|c| c.is_on_roster)
+        .map(|c| (c.cid, format!("{} {}", c.first_name, c.last_name)))
+        .collect();
+    roster.sort_by(|a, b| a.1.cmp(&b.1));
+
     let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
-    let template = state.templates.get_template("admin/off_roster_list")?;
+    let template = state.templates.get_template("admin/loa")?;
     let rendered = template.render(context! {
-       user_info,
-       controllers,
-       flashed_messages
+        user_info,
+        flashed_messages,
+        loas,
+        roster,
+        history_cid,
+        history_name,
+        history,
     })?;
     Ok(Html(rendered).into_response())
 }
 
-/// This file's routes and templates.
-pub fn router(templates: &mut Environment) -> Router<Arc<AppState>> {
-    templates
-        .add_template(
-            "admin/feedback",
-            include_str!("../../templates/admin/feedback.jinja"),
-        )
-        .unwrap();
-    templates
-        .add_template(
-            "admin/manual_email",
-            include_str!("../../templates/admin/manual_email.jinja"),
-        )
-        .unwrap();
-    templates
-        .add_template(
-            "admin/logs",
-            include_str!("../../templates/admin/logs.jinja"),
-        )
-        .unwrap();
-    templates
-        .add_template(
-            "admin/visitor_applications",
-            include_str!("../../templates/admin/visitor_applications.jinja"),
-        )
-        .unwrap();
-    templates
-        .add_template(
-            "admin/resources",
-            include_str!("../../templates/admin/resources.jinja"),
-        )
-        .unwrap();
-    templates
-        .add_template(
-            "admin/off_roster_list",
-            include_str!("../../templates/admin/off_roster_list.jinja"),
-        )
-        .unwrap();
-    templates.add_filter("nice_date", |date: String| {
-        chrono::DateTime::parse_from_rfc3339(&date)
-            .unwrap()
-            .format("%m/%d/%Y %H:%M:%S")
-            .to_string()
-    });
-    templates.add_filter(
-        "rating_str",
-        |rating: i8| match ControllerRating::try_from(rating) {
-            Ok(r) => r.as_str(),
-            Err(_) => "OBS",
-        },
-    );
+#[derive(Deserialize)]
+struct LoaActionForm {
+    action: String,
+    id: Option<u32>,
+    cid: Option<u32>,
+    start: Option<String>,
+    end: Option<String>,
+    reason: Option<String>,
+}
 
-    Router::new()
-        .route("/admin/feedback", get(page_feedback))
-        .route("/admin/feedback", post(post_feedback_form_handle))
-        .route(
-            "/admin/email/manual",
-            get(page_email_manual_send).post(post_email_manual_send),
-        )
-        .route("/admin/logs", get(page_logs))
-        .route(
-            "/admin/visitor_applications",
-            get(page_visitor_applications),
-        )
-        .route(
-            "/admin/visitor_applications/:id",
-            get(post_visitor_application_action),
-        )
-        .route(
-            "/admin/resources",
-            get(page_resources).post(post_new_resource),
-        )
-        .layer(DefaultBodyLimit::disable()) // no upload limit on this endpoint
-        .route("/admin/resources/:id", delete(api_delete_resource))
-        .route("/admin/off_roster_list", get(page_off_roster_list))
+/// Parse a `date` input, as a timestamp at the start or end of that day (UTC).
+fn parse_loa_date(date: &str, end_of_day: bool) -> Result<DateTime<Utc>, AppError> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")?;
+    let time = if end_of_day {
+        date.and_hms_opt(23, 59, 59)
+    } else {
+        date.and_hms_opt(0, 0, 0)
+    };
+    Ok(time
+        .ok_or(AppError::ChronoOther("building LOA date"))?
+        .and_utc())
+}
+
+/// Form submission for creating, extending, and ending LOAs.
+///
+/// Each change refreshes the controllers' `loa_until`, which exempts them
+/// from the activity requirement.
+///
+/// Admin staff members only.
+async fn post_loa_action(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Form(loa_form): Form<LoaActionForm>,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::LOA_MANAGE).await {
+        return Ok(redirect.into_response());
+    }
+    let user_info = user_info.unwrap();
+    let now = Utc::now();
+
+    let message = match loa_form.action.as_str() {
+        "create" => {
+            let reason = loa_form.reason.as_deref().unwrap_or_default().trim();
+            let (Some(cid), Some(start), Some(end)) = (loa_form.cid, loa_form.start, loa_form.end)
+            else {
+                return Ok(StatusCode::BAD_REQUEST.into_response());
+            };
+            let start = parse_loa_date(&start, false)?;
+            let end = parse_loa_date(&end, true)?;
+            if reason.is_empty() || end <= start || end <= now {
+                flashed_messages::push_flashed_message(
+                    session,
+                    MessageLevel::Error,
+                    "LOAs need a reason and a future end date after their start date",
+                )
+                .await?;
+                return Ok(Redirect::to("/admin/loa").into_response());
+            }
+            sqlx::query(sql::CREATE_LOA)
+                .bind(cid)
+                .bind(start)
+                .bind(end)
+                .bind(reason)
+                .bind(user_info.cid)
+                .bind(now)
+                .execute(&state.db)
+                .await?;
+            info!(
+                "{} created LOA for {cid} from {start} to {end}",
+                user_info.cid
+            );
+            "LOA created"
+        }
+        "extend" | "end" => {
+            let Some(id) = loa_form.id else {
+                return Ok(StatusCode::BAD_REQUEST.into_response());
+            };
+            let loa: Option<Loa> = sqlx::query_as(sql::GET_LOA_BY_ID)
+                .bind(id)
+                .fetch_optional(&state.db)
+                .await?;
+            let Some(loa) = loa else {
+                flashed_messages::push_flashed_message(session, MessageLevel::Error, "Unknown LOA")
+                    .await?;
+                return Ok(Redirect::to("/admin/loa").into_response());
+            };
+            if loa_form.action == "extend" {
+                let end = parse_loa_date(loa_form.end.as_deref().unwrap_or_default(), true)?;
+                if end <= loa.end_date {
+                    flashed_messages::push_flashed_message(
+                        session,
+                        MessageLevel::Error,
+                        "The new end date must be after the current one",
+                    )
+                    .await?;
+                    return Ok(Redirect::to("/admin/loa").into_response());
+                }
+                sqlx::query(sql::UPDATE_LOA_END_DATE)
+                    .bind(id)
+                    .bind(end)
+                    .execute(&state.db)
+                    .await?;
+                info!(
+                    "{} extended LOA {id} for {} to {end}",
+                    user_info.cid, loa.cid
+                );
+                "LOA extended"
+            } else {
+                // ending an upcoming LOA cancels it without it ever starting
+                sqlx::query(sql::END_LOA)
+                    .bind(id)
+                    .bind(now.max(loa.start_date))
+                    .bind(user_info.cid)
+                    .execute(&state.db)
+                    .await?;
+                info!("{} ended LOA {id} for {}", user_info.cid, loa.cid);
+                "LOA ended"
+            }
+        }
+        _ => {
+            warn!(
+                "{} submitted unknown LOA action {}",
+                user_info.cid, loa_form.action
+            );
+            flashed_messages::push_flashed_message(session, MessageLevel::Error, "Unknown action")
+                .await?;
+            return Ok(Redirect::to("/admin/loa").into_response());
+        }
+    };
+
+    sqlx::query(sql::UPDATE_ALL_LOA_UNTIL)
+        .bind(now)
+        .execute(&state.db)
+        .await?;
+    flashed_messages::push_flashed_message(session, MessageLevel::Success, message).await?;
+    Ok(Redirect::to("/admin/loa").into_response())
+}
+
+/// Page for managing the banners shown at the top of every page.
+///
+/// Admin staff members only.
+async fn page_banners(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::SITE_ADMIN).await {
+        return Ok(redirect.into_response());
+    }
+    let banners: Vec<Banner> = sqlx::query_as(sql::GET_ALL_BANNERS)
+        .fetch_all(&state.db)
+        .await?;
+    let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
+    let template = state.templates.get_template("admin/banners")?;
+    let rendered = template.render(context! {
+        user_info,
+        flashed_messages,
+        banners,
+        now => Utc::now(),
+    })?;
+    Ok(Html(rendered).into_response())
+}
+
+#[derive(Deserialize)]
+struct BannerActionForm {
+    action: String,
+    id: Option<u32>,
+    message: Option<String>,
+    level: Option<String>,
+    start: Option<String>,
+    end: Option<String>,
+    timezone: Option<String>,
+}
+
+/// Form submission for creating and removing site banners.
+///
+/// Admin staff members only.
+async fn post_banner_action(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Form(banner_form): Form<BannerActionForm>,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::SITE_ADMIN).await {
+        return Ok(redirect.into_response());
+    }
+    let user_info = user_info.unwrap();
+    match banner_form.action.as_str() {
+        "create" => {
+            let message = banner_form.message.as_deref().unwrap_or_default().trim();
+            let level = banner_form.level.as_deref().unwrap_or_default();
+            let (Some(start), Some(end), Some(timezone)) =
+                (banner_form.start, banner_form.end, banner_form.timezone)
+            else {
+                return Ok(StatusCode::BAD_REQUEST.into_response());
+            };
+            let start = js_timestamp_to_utc(&start, &timezone)?.and_utc();
+            let end = js_timestamp_to_utc(&end, &timezone)?.and_utc();
+            if message.is_empty()
+                || !["info", "warning", "emergency"].contains(&level)
+                || end <= start
+            {
+                flashed_messages::push_flashed_message(
+                    session,
+                    MessageLevel::Error,
+                    "Banners need a message, a level, and an end after their start",
+                )
+                .await?;
+                return Ok(Redirect::to("/admin/banners").into_response());
+            }
+            sqlx::query(sql::CREATE_BANNER)
+                .bind(message)
+                .bind(level)
+                .bind(start)
+                .bind(end)
+                .bind(user_info.cid)
+                .execute(&state.db)
+                .await?;
+            info!(
+                "{} created {level} banner from {start} to {end}: \"{message}\"",
+                user_info.cid
+            );
+            flashed_messages::push_flashed_message(
+                session,
+                MessageLevel::Success,
+                "Banner created",
+            )
+            .await?;
+        }
+        "delete" => {
+            let Some(id) = banner_form.id else {
+                return Ok(StatusCode::BAD_REQUEST.into_response());
+            };
+            sqlx::query(sql::DELETE_BANNER)
+                .bind(id)
+                .execute(&state.db)
+                .await?;
+            info!("{} deleted banner {id}", user_info.cid);
+            flashed_messages::push_flashed_message(
+                session,
+                MessageLevel::Success,
+                "Banner removed",
+            )
+            .await?;
+        }
+        _ => {
+            warn!(
+                "{} submitted unknown banner action {}",
+                user_info.cid, banner_form.action
+            );
+            flashed_messages::push_flashed_message(session, MessageLevel::Error, "Unknown action")
+                .await?;
+            return Ok(Redirect::to("/admin/banners").into_response());
+        }
+    }
+    state.cache.invalidate(BANNERS_CACHE_KEY);
+    Ok(Redirect::to("/admin/banners").into_response())
+}
+
+/// Page for managing blocked IP addresses and users.
+///
+/// Admin staff members only.
+async fn page_blocklist(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::SITE_ADMIN).await {
+        return Ok(redirect.into_response());
+    }
+    let blocks: Vec<Block> = sqlx::query_as(sql::GET_ALL_BLOCKS)
+        .fetch_all(&state.db)
+        .await?;
+    let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
+    let template = state.templates.get_template("admin/blocklist")?;
+    let rendered = template.render(context! {
+        user_info,
+        flashed_messages,
+        blocks,
+        now => Utc::now(),
+    })?;
+    Ok(Html(rendered).into_response())
+}
+
+#[derive(Deserialize)]
+struct BlockActionForm {
+    action: String,
+    id: Option<u32>,
+    kind: Option<String>,
+    value: Option<String>,
+    reason: Option<String>,
+    expires: Option<String>,
+    timezone: Option<String>,
+}
+
+/// Form submission for adding and removing blocks.
+///
+/// Admin staff members only.
+async fn post_blocklist_action(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Form(block_form): Form<BlockActionForm>,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::SITE_ADMIN).await {
+        return Ok(redirect.into_response());
+    }
+    let user_info = user_info.unwrap();
+    match block_form.action.as_str() {
+        "create" => {
+            let kind = block_form.kind.as_deref().unwrap_or_default();
+            let reason = block_form.reason.as_deref().unwrap_or_default().trim();
+            let value =
+                blocklist::normalize_value(kind, block_form.value.as_deref().unwrap_or_default());
+            let (Some(value), false) = (value, reason.is_empty()) else {
+                flashed_messages::push_flashed_message(
+                    session,
+                    MessageLevel::Error,
+                    "Blocks need a reason and a valid IP address, range, or CID",
+                )
+                .await?;
+                return Ok(Redirect::to("/admin/blocklist").into_response());
+            };
+            let expires = match (block_form.expires.as_deref(), block_form.timezone) {
+                (Some(expires), Some(timezone)) if !expires.is_empty() => {
+                    Some(js_timestamp_to_utc(expires, &timezone)?.and_utc())
+                }
+                _ => None,
+            };
+            sqlx::query(sql::CREATE_BLOCK)
+                .bind(kind)
+                .bind(&value)
+                .bind(reason)
+                .bind(user_info.cid)
+                .bind(Utc::now())
+                .bind(expires)
+                .execute(&state.db)
+                .await?;
+            info!(
+                "{} blocked {kind} {value} until {}: \"{reason}\"",
+                user_info.cid,
+                expires.map_or_else(|| "forever".to_owned(), |expires| expires.to_string())
+            );
+            flashed_messages::push_flashed_message(
+                session,
+                MessageLevel::Success,
+                &format!("Blocked {value}"),
+            )
+            .await?;
+        }
+        "delete" => {
+            let Some(id) = block_form.id else {
+                return Ok(StatusCode::BAD_REQUEST.into_response());
+            };
+            sqlx::query(sql::DELETE_BLOCK)
+                .bind(id)
+                .execute(&state.db)
+                .await?;
+            info!("{} removed block {id}", user_info.cid);
+            flashed_messages::push_flashed_message(session, MessageLevel::Success, "Block removed")
+                .await?;
+        }
+        _ => {
+            warn!(
+                "{} submitted unknown blocklist action {}",
+                user_info.cid, block_form.action
+            );
+            flashed_messages::push_flashed_message(session, MessageLevel::Error, "Unknown action")
+                .await?;
+            return Ok(Redirect::to("/admin/blocklist").into_response());
+        }
+    }
+    state.cache.invalidate(blocklist::BLOCKLIST_CACHE_KEY);
+    Ok(Redirect::to("/admin/blocklist").into_response())
+}
+
+/// Page for changing which roles and controllers have which capabilities.
+///
+/// Admin staff members only.
+async fn page_permissions(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) =
+        reject_without(&state, &user_info, permissions::PERMISSIONS_MANAGE).await
+    {
+        return Ok(redirect.into_response());
+    }
+    let role_permissions: Vec<RolePermission> = sqlx::query_as(sql::GET_ROLE_PERMISSIONS)
+        .fetch_all(&state.db)
+        .await?;
+    let mut grants: HashMap<&str, Vec<String>> = permissions::ROLES
+        .iter()
+        .map(|role| (*role, Vec::new()))
+        .collect();
+    for grant in role_permissions {
+        if let Some(held) = grants.get_mut(grant.role.as_str()) {
+            held.push(grant.permission);
+        }
+    }
+    let overrides: Vec<ControllerPermission> = sqlx::query_as(sql::GET_ALL_CONTROLLER_PERMISSIONS)
+        .fetch_all(&state.db)
+        .await?;
+    let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
+    let template = state.templates.get_template("admin/permissions")?;
+    let rendered = template.render(context! {
+        user_info,
+        flashed_messages,
+        roles => permissions::ROLES,
+        capabilities => permissions::ALL,
+        grants,
+        overrides,
+    })?;
+    Ok(Html(rendered).into_response())
+}
+
+#[derive(Deserialize)]
+struct PermissionActionForm {
+    action: String,
+    permission: String,
+    role: Option<String>,
+    cid: Option<u32>,
+}
+
+/// Form submission for granting and revoking capabilities.
+///
+/// Admin staff members only.
+async fn post_permission_action(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Form(permission_form): Form<PermissionActionForm>,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) =
+        reject_without(&state, &user_info, permissions::PERMISSIONS_MANAGE).await
+    {
+        return Ok(redirect.into_response());
+    }
+    let user_info = user_info.unwrap();
+    let permission = permission_form.permission.as_str();
+    if !permissions::ALL.iter().any(|(p, _)| *p == permission) {
+        flashed_messages::push_flashed_message(session, MessageLevel::Error, "Unknown permission")
+            .await?;
+        return Ok(Redirect::to("/admin/permissions").into_response());
+    }
+    let role = permission_form
+        .role
+        .as_deref()
+        .filter(|role| permissions::ROLES.contains(role));
+    match (permission_form.action.as_str(), role, permission_form.cid) {
+        ("grant_role", Some(role), _) => {
+            sqlx::query(sql::GRANT_ROLE_PERMISSION)
+                .bind(role)
+                .bind(permission)
+                .execute(&state.db)
+                .await?;
+            info!("{} granted {permission} to {role}", user_info.cid);
+        }
+        ("revoke_role", Some(role), _) => {
+            sqlx::query(sql::REVOKE_ROLE_PERMISSION)
+                .bind(role)
+                .bind(permission)
+                .execute(&state.db)
+                .await?;
+            info!("{} revoked {permission} from {role}", user_info.cid);
+        }
+        (action @ ("grant_controller" | "deny_controller"), _, Some(cid)) => {
+            let granted = action == "grant_controller";
+            sqlx::query(sql::SET_CONTROLLER_PERMISSION)
+                .bind(cid)
+                .bind(permission)
+                .bind(granted)
+                .execute(&state.db)
+                .await?;
+            info!(
+                "{} {} {permission} for {cid}",
+                user_info.cid,
+                if granted { "granted" } else { "denied" }
+            );
+        }
+        ("remove_controller", _, Some(cid)) => {
+            sqlx::query(sql::DELETE_CONTROLLER_PERMISSION)
+                .bind(cid)
+                .bind(permission)
+                .execute(&state.db)
+                .await?;
+            info!(
+                "{} removed the {permission} override for {cid}",
+                user_info.cid
+            );
+        }
+        _ => {
+            warn!(
+                "{} submitted unknown permission action {}",
+                user_info.cid, permission_form.action
+            );
+            flashed_messages::push_flashed_message(session, MessageLevel::Error, "Unknown action")
+                .await?;
+            return Ok(Redirect::to("/admin/permissions").into_response());
+        }
+    }
+    flashed_messages::push_flashed_message(session, MessageLevel::Success, "Permissions updated")
+        .await?;
+    Ok(Redirect::to("/admin/permissions").into_response())
+}
+
+#[derive(Deserialize)]
+struct ImpersonateForm {
+    cid: u32,
+}
+
+/// Start viewing the site with another controller's permissions.
+///
+/// Nothing can be submitted while doing so; see `middleware::impersonation`.
+///
+/// ATM and WM only.
+async fn post_impersonate_start(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Form(impersonate_form): Form<ImpersonateForm>,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if !can_impersonate(&state, &user_info).await {
+        return Ok(Redirect::to("/").into_response());
+    }
+    let user_info = user_info.unwrap();
+    let target: Option<Controller> = state
+        .repos
+        .controllers
+        .get_by_cid(impersonate_form.cid)
+        .await?;
+    let Some(target_controller) = &target else {
+        flashed_messages::push_flashed_message(
+            session,
+            MessageLevel::Error,
+            "Controller not found",
+        )
+        .await?;
+        return Ok(Redirect::to("/").into_response());
+    };
+    if target_controller.cid == user_info.cid {
+        return Ok(Redirect::to(&format!("/controller/{}", user_info.cid)).into_response());
+    }
+    let mut view_as = UserInfo::new(
+        &state.db,
+        target_controller.cid,
+        target_controller.first_name.clone(),
+        target_controller.last_name.clone(),
+    )
+    .await?;
+    view_as.impersonated_by = Some(user_info.cid);
+    session.insert(SESSION_USER_INFO_KEY, view_as).await?;
+    sqlx::query(sql::START_IMPERSONATION)
+        .bind(user_info.cid)
+        .bind(target_controller.cid)
+        .bind(Utc::now())
+        .execute(&state.db)
+        .await?;
+    info!(
+        "{} started viewing the site as {}",
+        user_info.cid, target_controller.cid
+    );
+    Ok(Redirect::to("/").into_response())
+}
+
+/// Stop viewing the site as another controller, returning to the staff member's own session.
+async fn post_impersonate_stop(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let Some((viewed_cid, staff_cid)) = user_info
+        .as_ref()
+        .and_then(|info| Some((info.cid, info.impersonated_by?)))
+    else {
+        return Ok(Redirect::to("/").into_response());
+    };
+    let staff: Option<Controller> = state.repos.controllers.get_by_cid(staff_cid).await?;
+    let Some(staff_controller) = &staff else {
+        session.delete().await?;
+        return Ok(Redirect::to("/").into_response());
+    };
+    let own = UserInfo::new(
+        &state.db,
+        staff_cid,
+        staff_controller.first_name.clone(),
+        staff_controller.last_name.clone(),
+    )
+    .await?;
+    session.insert(SESSION_USER_INFO_KEY, own).await?;
+    sqlx::query(sql::END_IMPERSONATION)
+        .bind(staff_cid)
+        .bind(viewed_cid)
+        .bind(Utc::now())
+        .execute(&state.db)
+        .await?;
+    info!("{staff_cid} stopped viewing the site as {viewed_cid}");
+    Ok(Redirect::to(&format!("/controller/{viewed_cid}")).into_response())
+}
+
+/// Page for toggling maintenance mode.
+///
+/// Admin staff members only.
+async fn page_maintenance(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::SITE_ADMIN).await {
+        return Ok(redirect.into_response());
+    }
+    let enabled = maintenance_mode(&state).await?;
+    let forced_by_config = state.config.maintenance.enabled;
+    let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
+    let template = state.templates.get_template("admin/maintenance")?;
+    let rendered = template.render(context! {
+        user_info,
+        flashed_messages,
+        enabled,
+        forced_by_config,
+    })?;
+    Ok(Html(rendered).into_response())
+}
+
+#[derive(Deserialize)]
+struct MaintenanceActionForm {
+    action: String,
+}
+
+/// Form submission for turning maintenance mode on or off.
+///
+/// Admin staff members only.
+async fn post_maintenance_action(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Form(maintenance_form): Form<MaintenanceActionForm>,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::SITE_ADMIN).await {
+        return Ok(redirect.into_response());
+    }
+    let user_info = user_info.unwrap();
+    let enable = match maintenance_form.action.as_str() {
+        "enable" => true,
+        "disable" => false,
+        _ => {
+            warn!(
+                "{} submitted unknown maintenance action {}",
+                user_info.cid, maintenance_form.action
+            );
+            flashed_messages::push_flashed_message(session, MessageLevel::Error, "Unknown action")
+                .await?;
+            return Ok(Redirect::to("/admin/maintenance").into_response());
+        }
+    };
+    sqlx::query(sql::SET_SETTING)
+        .bind(MAINTENANCE_SETTING)
+        .bind(if enable { "1" } else { "0" })
+        .execute(&state.db)
+        .await?;
+    state.cache.invalidate(MAINTENANCE_CACHE_KEY);
+    if enable {
+        info!("{} enabled maintenance mode", user_info.cid);
+        flashed_messages::push_flashed_message(
+            session,
+            MessageLevel::Success,
+            "Maintenance mode enabled; only staff can use the site",
+        )
+        .await?;
+    } else {
+        info!("{} disabled maintenance mode", user_info.cid);
+        let message = if state.config.maintenance.enabled {
+            "Maintenance mode toggle disabled, but it's still enabled in the config"
+        } else {
+            "Maintenance mode disabled"
+        };
+        flashed_messages::push_flashed_message(session, MessageLevel::Success, message).await?;
+    }
+    Ok(Redirect::to("/admin/maintenance").into_response())
+}
+
+/// Number of the latest view-as sessions shown on the log page.
+const IMPERSONATIONS_SHOWN: u32 = 200;
+
+/// View-as session for display.
+#[derive(Serialize)]
+struct ImpersonationDisplay {
+    #[serde(flatten)]
+    entry: ImpersonationLogEntry,
+    staff_name: String,
+    viewed_name: String,
+}
+
+/// Page listing when staff members viewed the site as other controllers.
+///
+/// Admin staff members only.
+async fn page_impersonations(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::SITE_ADMIN).await {
+        return Ok(redirect.into_response());
+    }
+    let entries: Vec<ImpersonationLogEntry> = sqlx::query_as(sql::GET_RECENT_IMPERSONATIONS)
+        .bind(IMPERSONATIONS_SHOWN)
+        .fetch_all(&state.db)
+        .await?;
+    let names = get_controller_cids_and_names(&state.db)
+        .await
+        .map_err(|e| AppError::GenericFallback("getting names and CIDs from DB", e))?;
+    let entries: Vec<ImpersonationDisplay> = entries
+        .into_iter()
+        .map(|entry| ImpersonationDisplay {
+            staff_name: display_name(&names, entry.staff_cid),
+            viewed_name: display_name(&names, entry.viewed_cid),
+            entry,
+        })
+        .collect();
+    let template = state.templates.get_template("admin/impersonations")?;
+    let rendered = template.render(context! { user_info, entries })?;
+    Ok(Html(rendered).into_response())
+}
+
+/// Page for the results of the task runner's periodic jobs.
+///
+/// Admin staff members only.
+async fn page_tasks(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::SITE_ADMIN).await {
+        return Ok(redirect.into_response());
+    }
+    let integrity_report = integrity::last_report(&state.db).await?;
+    let integrity_config = &state.config.database.integrity;
+    let template = state.templates.get_template("admin/tasks")?;
+    let rendered = template.render(context! {
+        user_info,
+        integrity_report,
+        integrity_enabled => integrity_config.enabled,
+        integrity_interval => integrity_config.interval_hours.max(1),
+    })?;
+    Ok(Html(rendered).into_response())
+}
+
+#[derive(Serialize)]
+struct ExitSurveyDisplay {
+    name: String,
+    survey: ExitSurvey,
+}
+
+/// Page for reading exit survey responses from controllers who left the roster.
+///
+/// Admin staff members only.
+async fn page_exit_surveys(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::ROSTER_MANAGE).await {
+        return Ok(redirect.into_response());
+    }
+    let surveys: Vec<ExitSurvey> = sqlx::query_as(sql::GET_SUBMITTED_EXIT_SURVEYS)
+        .fetch_all(&state.db)
+        .await?;
+    let names = get_controller_cids_and_names(&state.db)
+        .await
+        .map_err(|e| AppError::GenericFallback("getting names and CIDs from DB", e))?;
+    let mut reason_counts: HashMap<String, u32> = HashMap::new();
+    for survey in &surveys {
+        *reason_counts
+            .entry(survey.reason.clone().unwrap_or_default())
+            .or_default() += 1;
+    }
+    let reason_counts: Vec<(String, u32)> = reason_counts
+        .into_iter()
+        .sorted_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)))
+        .collect();
+    let average_rating = if surveys.is_empty() {
+        None
+    } else {
+        let total: u32 = surveys
+            .iter()
+            .map(|survey| u32::from(survey.rating.unwrap_or_default()))
+            .sum();
+        Some(format!("{:.1}", total as f64 / surveys.len() as f64))
+    };
+    let surveys: Vec<ExitSurveyDisplay> = surveys
+        .into_iter()
+        .map(|survey| ExitSurveyDisplay {
+            name: display_name(&names, survey.cid),
+            survey,
+        })
+        .collect();
+    let template = state.templates.get_template("admin/exit_surveys")?;
+    let rendered = template.render(context! {
+        user_info,
+        surveys,
+        reason_counts,
+        average_rating,
+    })?;
+    Ok(Html(rendered).into_response())
+}
+
+/// Page for controllers that are not on the roster but have controller DB entries.
+///
+/// Named staff members only.
+async fn page_off_roster_list(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::STAFF).await {
+        return Ok(redirect.into_response());
+    }
+    let controllers: Vec<Controller> = state.repos.controllers.get_off_roster().await?;
+    let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
+    let template = state.templates.get_template("admin/off_roster_list")?;
+    let rendered = template.render(context! {
+       user_info,
+       controllers,
+       flashed_messages
+    })?;
+    Ok(Html(rendered).into_response())
+}
+
+/// Purge candidate with the controller's name, for display.
+#[derive(Serialize)]
+struct PurgeCandidateWithName {
+    #[serde(flatten)]
+    candidate: PurgeCandidate,
+    name: String,
+}
+
+/// Page for reviewing the controllers who didn't meet the activity requirement.
+///
+/// Shows the latest compiled quarter, or the one in the `quarter` query parameter.
+///
+/// Admin staff members only.
+async fn page_purge(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::ROSTER_MANAGE).await {
+        return Ok(redirect.into_response());
+    }
+    let quarter = match params.get("quarter") {
+        Some(quarter) => Some(quarter.to_owned()),
+        None => {
+            let latest: (Option<String>,) = sqlx::query_as(sql::GET_LATEST_PURGE_QUARTER)
+                .fetch_one(&state.db)
+                .await?;
+            latest.0
+        }
+    };
+    let candidates: Vec<PurgeCandidate> = match &quarter {
+        Some(quarter) => {
+            sqlx::query_as(sql::GET_PURGE_CANDIDATES)
+                .bind(quarter)
+                .fetch_all(&state.db)
+                .await?
+        }
+        None => Vec::new(),
+    };
+    let names = get_controller_cids_and_names(&state.db)
+        .await
+        .map_err(|e| AppError::GenericFallback("getting names and CIDs from DB", e))?;
+    let candidates: Vec<PurgeCandidateWithName> = candidates
+        .into_iter()
+        .map(|candidate| PurgeCandidateWithName {
+            name: display_name(&names, candidate.cid),
+            candidate,
+        })
+        .collect();
+    let pending = candidates
+        .iter()
+        .filter(|c| {
+            !c.candidate.excluded
+                && c.candidate.removed_date.is_none()
+                && c.candidate.approved_date.is_none()
+        })
+        .count();
+
+    let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
+    let template = state.templates.get_template("admin/purge")?;
+    let rendered = template.render(context! {
+        user_info,
+        flashed_messages,
+        quarter,
+        candidates,
+        pending,
+        required_minutes => activity::REQUIRED_MINUTES,
+    })?;
+    Ok(Html(rendered).into_response())
+}
+
+#[derive(Deserialize)]
+struct PurgeActionForm {
+    action: String,
+    quarter: String,
+    id: Option<u32>,
+}
+
+/// Remove a purge candidate from the VATUSA and local rosters, and send them the notice.
+///
+/// Candidates who have since gone on LOA or otherwise no longer need removing
+/// are skipped instead, with the reason stored on them.
+///
+/// The notice is only sent once the removal has succeeded, and failing to
+/// send it doesn't undo the removal.
+async fn purge_controller(
+    config: &Config,
+    db: &Pool<Sqlite>,
+    candidate: &PurgeCandidate,
+) -> Result<(), AppError> {
+    let removed_by = candidate.approved_by.unwrap_or_default();
+    let quarter = activity::Quarter::from_label(&candidate.quarter)
+        .ok_or(AppError::ChronoOther("parsing the purge quarter"))?;
+    if let Some(skip_reason) =
+        activity::purge_skip_reason(db, candidate.cid, &quarter, Utc::now()).await?
+    {
+        sqlx::query(sql::SET_PURGE_CANDIDATE_SKIPPED)
+            .bind(candidate.id)
+            .bind(&skip_reason)
+            .execute(db)
+            .await?;
+        info!(
+            "Skipped removing {} for inactivity in {}: {skip_reason}",
+            candidate.cid, candidate.quarter
+        );
+        return Ok(());
+    }
+    let api_key = &config.vatsim.vatusa_api_key;
+    let reason = format!("Inactivity in {}", candidate.quarter);
+    let info = vatusa::get_controller_info(candidate.cid, Some(api_key)).await?;
+    match RosterStatus::of(&info) {
+        RosterStatus::Home => {
+            vatusa::remove_home_controller(candidate.cid, &reason, api_key).await?
+        }
+        RosterStatus::Visiting => {
+            vatusa::remove_visiting_controller(candidate.cid, &reason, api_key).await?
+        }
+        // already gone from VATUSA; still clean up the local roster
+        RosterStatus::NotOnRoster => {}
+    }
+    let now = Utc::now();
+    sqlx::query(sql::UPDATE_REMOVED_FROM_ROSTER)
+        .bind(candidate.cid)
+        .execute(db)
+        .await?;
+    sqlx::query(sql::UPDATE_CONTROLLER_STATUS)
+        .bind(candidate.cid)
+        .bind("Removed")
+        .execute(db)
+        .await?;
+    sqlx::query(sql::QUEUE_EXIT_SURVEY)
+        .bind(candidate.cid)
+        .bind(format!("Removed: {reason}"))
+        .bind(now)
+        .execute(db)
+        .await?;
+    sqlx::query(sql::SET_PURGE_CANDIDATE_REMOVED)
+        .bind(candidate.id)
+        .bind(now)
+        .bind(removed_by)
+        .execute(db)
+        .await?;
+    info!(
+        "{removed_by} removed {} from the roster for inactivity in {}",
+        candidate.cid, candidate.quarter
+    );
+
+    if let Some(address) = info.email {
+        if !config.email.purge_notice_template.body.is_empty() {
+            if let Err(e) = email::send_mail_with_context(
+                config,
+                db,
+                &format!("{} {}", info.first_name, info.last_name),
+                &address,
+                email::templates::PURGE_NOTICE,
+                context! {
+                    quarter => &candidate.quarter,
+                    minutes => candidate.minutes,
+                },
+            )
+            .await
+            {
+                warn!("Could not send purge notice to {}: {e}", candidate.cid);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Remove the purge candidates staff have approved.
+///
+/// Failures are stored on the candidate and take it out of the queue, so that
+/// the rest of the purge still goes through; approving again retries them.
+pub async fn remove_approved_purges(config: &Config, db: &Pool<Sqlite>) -> Result<(), AppError> {
+    let candidates: Vec<PurgeCandidate> = sqlx::query_as(sql::GET_APPROVED_PURGE_CANDIDATES)
+        .fetch_all(db)
+        .await?;
+    for candidate in candidates {
+        if let Err(e) = purge_controller(config, db, &candidate).await {
+            error!("Error purging {}: {e}", candidate.cid);
+            sqlx::query(sql::SET_PURGE_CANDIDATE_ERROR)
+                .bind(candidate.id)
+                .bind(e.to_string())
+                .execute(db)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Form submission for excluding candidates from a purge and approving it.
+///
+/// Approving queues every candidate in the quarter that hasn't been excluded
+/// or already removed, and they're removed in the background.
+///
+/// Admin staff members only.
+async fn post_purge_action(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Form(purge_form): Form<PurgeActionForm>,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::ROSTER_MANAGE).await {
+        return Ok(redirect.into_response());
+    }
+    let user_info = user_info.unwrap();
+    let Some(quarter) = activity::Quarter::from_label(&purge_form.quarter) else {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    };
+    let redirect = Redirect::to(&format!("/admin/purge?quarter={}", quarter.label));
+
+    match purge_form.action.as_str() {
+        "exclude" | "include" => {
+            let Some(id) = purge_form.id else {
+                return Ok(StatusCode::BAD_REQUEST.into_response());
+            };
+            sqlx::query(sql::SET_PURGE_CANDIDATE_EXCLUDED)
+                .bind(id)
+                .bind(purge_form.action == "exclude")
+                .execute(&state.db)
+                .await?;
+            info!(
+                "{} set purge candidate {id} to {}",
+                user_info.cid, purge_form.action
+            );
+        }
+        "approve" => {
+            let queued = sqlx::query(sql::APPROVE_PURGE_CANDIDATES)
+                .bind(&quarter.label)
+                .bind(Utc::now())
+                .bind(user_info.cid)
+                .execute(&state.db)
+                .await?
+                .rows_affected();
+            info!(
+                "{} approved the purge of {queued} controllers for {}",
+                user_info.cid, quarter.label
+            );
+            flashed_messages::push_flashed_message(
+                session,
+                MessageLevel::Success,
+                &format!("Queued {queued} controllers for removal"),
+            )
+            .await?;
+        }
+        _ => {
+            warn!(
+                "{} submitted unknown purge action {}",
+                user_info.cid, purge_form.action
+            );
+            flashed_messages::push_flashed_message(session, MessageLevel::Error, "Unknown action")
+                .await?;
+        }
+    }
+    Ok(redirect.into_response())
+}
+
+/// Page listing recent quarters' facility reports.
+///
+/// Roster managers only.
+async fn page_reports(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::ROSTER_MANAGE).await {
+        return Ok(redirect.into_response());
+    }
+    let current = activity::Quarter::containing(Utc::now());
+    let mut quarters = vec![current.label.clone()];
+    let mut quarter = current;
+    for _ in 0..4 {
+        quarter = activity::Quarter::previous(quarter.start);
+        quarters.push(quarter.label.clone());
+    }
+    let template = state.templates.get_template("admin/reports")?;
+    let rendered = template.render(context! { user_info, quarters })?;
+    Ok(Html(rendered).into_response())
+}
+
+/// Download the quarter's facility report as a text document.
+///
+/// Roster managers only.
+async fn get_quarterly_report(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path(label): Path<String>,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::ROSTER_MANAGE).await {
+        return Ok(redirect.into_response());
+    }
+    let Some(quarter) = activity::Quarter::from_label(&label) else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let report = QuarterlyReport::build(&state.db, &quarter, Utc::now()).await?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/plain; charset=utf-8".to_owned()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", report.file_name()),
+            ),
+        ],
+        report.to_document(),
+    )
+        .into_response())
+}
+
+/// This file's routes and templates.
+pub fn router(templates: &mut Environment) -> Router<Arc<AppState>> {
+    templates
+        .add_template(
+            "admin/dashboard",
+            include_str!("../../templates/admin/dashboard.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "admin/pending_counts",
+            include_str!("../../templates/admin/pending_counts.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "admin/feedback",
+            include_str!("../../templates/admin/feedback.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "admin/manual_email",
+            include_str!("../../templates/admin/manual_email.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "admin/logs",
+            include_str!("../../templates/admin/logs.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "admin/stats",
+            include_str!("../../templates/admin/stats.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "admin/cache",
+            include_str!("../../templates/admin/cache.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "admin/visitor_applications",
+            include_str!("../../templates/admin/visitor_applications.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "admin/resources",
+            include_str!("../../templates/admin/resources.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template("admin/loa", include_str!("../../templates/admin/loa.jinja"))
+        .unwrap();
+    templates
+        .add_template(
+            "admin/banners",
+            include_str!("../../templates/admin/banners.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "admin/blocklist",
+            include_str!("../../templates/admin/blocklist.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "admin/permissions",
+            include_str!("../../templates/admin/permissions.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "admin/maintenance",
+            include_str!("../../templates/admin/maintenance.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "admin/tasks",
+            include_str!("../../templates/admin/tasks.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "admin/impersonations",
+            include_str!("../../templates/admin/impersonations.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "admin/exit_surveys",
+            include_str!("../../templates/admin/exit_surveys.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "admin/off_roster_list",
+            include_str!("../../templates/admin/off_roster_list.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "admin/purge",
+            include_str!("../../templates/admin/purge.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "admin/reports",
+            include_str!("../../templates/admin/reports.jinja"),
+        )
+        .unwrap();
+
+    Router::new()
+        .route("/admin", get(page_dashboard))
+        .route("/admin/pending", get(snippet_pending_counts))
+        .route("/admin/feedback", get(page_feedback))
+        .route("/admin/feedback", post(post_feedback_form_handle))
+        .route("/admin/feedback/bulk", post(post_feedback_bulk))
+        .route(
+            "/admin/email/manual",
+            get(page_email_manual_send).post(post_email_manual_send),
+        )
+        .route("/admin/logs", get(page_logs))
+        .route("/admin/logs/tail", get(api_logs_tail))
+        .route("/admin/stats", get(page_stats))
+        .route("/admin/cache", get(page_cache).post(post_cache_action))
+        .route(
+            "/admin/visitor_applications",
+            get(page_visitor_applications),
+        )
+        .route(
+            "/admin/visitor_applications/:id",
+            get(post_visitor_application_action).post(post_visitor_application_action),
+        )
+        .route(
+            "/admin/resources",
+            get(page_resources).post(post_new_resource),
+        )
+        .layer(DefaultBodyLimit::disable()) // no upload limit on this endpoint
+        .route("/admin/resources/:id", delete(api_delete_resource))
+        .route(
+            "/admin/resources/categories",
+            post(post_resource_category_action),
+        )
+        .route("/admin/loa", get(page_loa).post(post_loa_action))
+        .route("/admin/banners", get(page_banners).post(post_banner_action))
+        .route(
+            "/admin/blocklist",
+            get(page_blocklist).post(post_blocklist_action),
+        )
+        .route(
+            "/admin/permissions",
+            get(page_permissions).post(post_permission_action),
+        )
+        .route("/admin/impersonate", post(post_impersonate_start))
+        .route("/admin/impersonate/stop", post(post_impersonate_stop))
+        .route(
+            "/admin/maintenance",
+            get(page_maintenance).post(post_maintenance_action),
+        )
+        .route("/admin/tasks", get(page_tasks))
+        .route("/admin/impersonations", get(page_impersonations))
+        .route("/admin/off_roster_list", get(page_off_roster_list))
+        .route("/admin/exit_surveys", get(page_exit_surveys))
+        .route("/admin/purge", get(page_purge).post(post_purge_action))
+        .route("/admin/reports", get(page_reports))
+        .route("/admin/reports/:quarter", get(get_quarterly_report))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::remove_approved_purges;
+    use crate::test_utils::{test_app, test_app_with, ADMIN_CONTROLLER, HOME_CONTROLLER};
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+        response::IntoResponse,
+        routing::post,
+        Json, Router,
+    };
+    use chrono::Utc;
+    use std::{
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+    use tower::ServiceExt;
+    use vzdv::{
+        permissions,
+        sql::{self, ImpersonationLogEntry, PurgeCandidate, VisitorRequest},
+    };
+
+    #[tokio::test]
+    async fn test_logs_page() {
+        let app = test_app().await;
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let (status, body) = app
+            .get(
+                "/admin/logs?file=tasks&level=WARN&text=roster&page=1",
+                Some(&cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("/admin/logs/tail?file=tasks&"));
+        assert!(body.contains("Page 2"));
+
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, _) = app.get("/admin/logs/tail", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_stats_page() {
+        let app = test_app().await;
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        app.get("/events/1", Some(&cookie)).await;
+        let (status, body) = app.get("/admin/stats", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("GET /events/:id"));
+    }
+
+    #[tokio::test]
+    async fn test_feedback_assignment_and_comments() {
+        let app = test_app().await;
+        sqlx::query(sql::INSERT_FEEDBACK)
+            .bind(HOME_CONTROLLER)
+            .bind("DEN_APP")
+            .bind("good")
+            .bind("Smooth vectors")
+            .bind(Utc::now())
+            .bind(1_234_567)
+            .execute(&app.db)
+            .await
+            .unwrap();
+        sqlx::query(sql::SET_CONTROLLER_ROLES)
+            .bind(HOME_CONTROLLER)
+            .bind("MTR")
+            .execute(&app.db)
+            .await
+            .unwrap();
+
+        // staff only see feedback once it's assigned to them
+        let staff_cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, body) = app.get("/admin/feedback", Some(&staff_cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!body.contains("Smooth vectors"));
+
+        // nor can they assign it without being granted to
+        let id = HOME_CONTROLLER.to_string();
+        app.post_form(
+            "/admin/feedback",
+            &[("id", "1"), ("action", "Assign"), ("assignee", &id)],
+            Some(&staff_cookie),
+        )
+        .await;
+        let feedback = app
+            .state
+            .repos
+            .feedback
+            .get_by_id(1)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(feedback.assigned_to_cid, None);
+
+        let admin_cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let (status, _) = app
+            .post_form(
+                "/admin/feedback",
+                &[("id", "1"), ("action", "Assign"), ("assignee", &id)],
+                Some(&admin_cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+
+        let (_, body) = app.get("/admin/feedback", Some(&staff_cookie)).await;
+        assert!(body.contains("Smooth vectors"));
+        assert!(!body.contains("value=\"Archive\""));
+        app.post_form(
+            "/admin/feedback",
+            &[
+                ("id", "1"),
+                ("action", "Comment"),
+                ("comment", "Agreed, post it"),
+            ],
+            Some(&staff_cookie),
+        )
+        .await;
+        app.post_form(
+            "/admin/feedback",
+            &[("id", "1"), ("action", "Archive")],
+            Some(&staff_cookie),
+        )
+        .await;
+        let feedback = app
+            .state
+            .repos
+            .feedback
+            .get_by_id(1)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(feedback.reviewer_action, "pending");
+        assert_eq!(feedback.assigned_to_cid, Some(HOME_CONTROLLER));
+
+        let (_, body) = app.get("/admin/feedback", Some(&admin_cookie)).await;
+        assert!(body.contains("Admin Controller assigned this to Home Controller"));
+        assert!(body.contains("Agreed, post it"));
+
+        // assigning is its own capability, not tied to the ATM role
+        sqlx::query(sql::SET_CONTROLLER_PERMISSION)
+            .bind(HOME_CONTROLLER)
+            .bind(permissions::FEEDBACK_ASSIGN)
+            .bind(true)
+            .execute(&app.db)
+            .await
+            .unwrap();
+        let admin_id = ADMIN_CONTROLLER.to_string();
+        let (_, body) = app.get("/admin/feedback", Some(&staff_cookie)).await;
+        assert!(body.contains("value=\"Assign\""));
+        app.post_form(
+            "/admin/feedback",
+            &[("id", "1"), ("action", "Assign"), ("assignee", &admin_id)],
+            Some(&staff_cookie),
+        )
+        .await;
+        let feedback = app
+            .state
+            .repos
+            .feedback
+            .get_by_id(1)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(feedback.assigned_to_cid, Some(ADMIN_CONTROLLER));
+    }
+
+    #[tokio::test]
+    async fn test_feedback_bulk_actions() {
+        let app = test_app().await;
+        for comments in ["Smooth vectors", "Great flow", "Spam"] {
+            sqlx::query(sql::INSERT_FEEDBACK)
+                .bind(HOME_CONTROLLER)
+                .bind("DEN_APP")
+                .bind("good")
+                .bind(comments)
+                .bind(Utc::now())
+                .bind(1_234_567)
+                .execute(&app.db)
+                .await
+                .unwrap();
+        }
+
+        // non-admin staff can't take bulk actions
+        let staff_cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, _) = app
+            .post_form(
+                "/admin/feedback/bulk",
+                &[("action", "Delete"), ("ids", "1"), ("ids", "2")],
+                Some(&staff_cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        assert!(app
+            .state
+            .repos
+            .feedback
+            .get_by_id(1)
+            .await
+            .unwrap()
+            .is_some());
+
+        let admin_cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let (_, body) = app.get("/admin/feedback", Some(&admin_cookie)).await;
+        assert!(body.contains("name=\"ids\" value=\"3\""));
+        app.post_form(
+            "/admin/feedback/bulk",
+            &[("action", "Approve silently"), ("ids", "1"), ("ids", "2")],
+            Some(&admin_cookie),
+        )
+        .await;
+        app.post_form(
+            "/admin/feedback/bulk",
+            &[("action", "Delete"), ("ids", "3")],
+            Some(&admin_cookie),
+        )
+        .await;
+        for id in [1, 2] {
+            let feedback = app
+                .state
+                .repos
+                .feedback
+                .get_by_id(id)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(feedback.reviewer_action, "post");
+            assert_eq!(feedback.reviewed_by_cid, ADMIN_CONTROLLER);
+            assert!(!feedback.posted_to_discord);
+        }
+        assert!(app
+            .state
+            .repos
+            .feedback
+            .get_by_id(3)
+            .await
+            .unwrap()
+            .is_none());
+
+        let (_, body) = app.get("/admin/feedback", Some(&admin_cookie)).await;
+        assert!(body.contains("1 feedback item deleted"));
+
+        app.post_form(
+            "/admin/feedback/bulk",
+            &[("action", "Archive")],
+            Some(&admin_cookie),
+        )
+        .await;
+        let (_, body) = app.get("/admin/feedback", Some(&admin_cookie)).await;
+        assert!(body.contains("No feedback selected"));
+    }
+
+    /// Serve a stand-in Discord webhook that rate limits the first post and
+    /// rejects feedback with the comment "Fail", counting the posts it gets.
+    async fn fake_feedback_webhook() -> (String, Arc<AtomicU32>) {
+        let posts = Arc::new(AtomicU32::new(0));
+        let counter = posts.clone();
+        let router = Router::new().route(
+            "/webhook",
+            post(move |Json(body): Json<serde_json::Value>| async move {
+                if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                    return (StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, "0")])
+                        .into_response();
+                }
+                if body["embeds"][0]["fields"][3]["value"] == "Fail" {
+                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                }
+                StatusCode::NO_CONTENT.into_response()
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/webhook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        (url, posts)
+    }
+
+    #[tokio::test]
+    async fn test_feedback_bulk_post_to_discord() {
+        let (url, posts) = fake_feedback_webhook().await;
+        let app = test_app_with(|config| config.discord.webhooks.feedback = url).await;
+        for comments in ["Smooth vectors", "Fail", "Great flow"] {
+            sqlx::query(sql::INSERT_FEEDBACK)
+                .bind(HOME_CONTROLLER)
+                .bind("DEN_APP")
+                .bind("good")
+                .bind(comments)
+                .bind(Utc::now())
+                .bind(1_234_567)
+                .execute(&app.db)
+                .await
+                .unwrap();
+        }
+        let admin_cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let posted = |id: u32| {
+            let app = &app;
+            async move {
+                let feedback = app.state.repos.feedback.get_by_id(id).await.unwrap();
+                let feedback = feedback.unwrap();
+                (feedback.reviewer_action, feedback.posted_to_discord)
+            }
+        };
+
+        // the first item goes through after the rate limit, then the second
+        // fails and the third is left alone
+        app.post_form(
+            "/admin/feedback/bulk",
+            &[
+                ("action", "Post to Discord"),
+                ("ids", "1"),
+                ("ids", "2"),
+                ("ids", "3"),
+            ],
+            Some(&admin_cookie),
+        )
+        .await;
+        assert_eq!(posts.load(Ordering::SeqCst), 3);
+        assert_eq!(posted(1).await, ("post".to_owned(), true));
+        assert_eq!(posted(2).await, ("pending".to_owned(), false));
+        assert_eq!(posted(3).await, ("pending".to_owned(), false));
+        let (_, body) = app.get("/admin/feedback", Some(&admin_cookie)).await;
+        assert!(body.contains("1 feedback item shared"));
+        assert!(body.contains("Stopped at feedback 2"));
+
+        // already posted items aren't posted again
+        app.post_form(
+            "/admin/feedback/bulk",
+            &[("action", "Post to Discord"), ("ids", "1"), ("ids", "3")],
+            Some(&admin_cookie),
+        )
+        .await;
+        assert_eq!(posts.load(Ordering::SeqCst), 4);
+        assert_eq!(posted(3).await, ("post".to_owned(), true));
+        let (_, body) = app.get("/admin/feedback", Some(&admin_cookie)).await;
+        assert!(body.contains("1 feedback item shared"));
+    }
+
+    #[tokio::test]
+    async fn test_visitor_application_hold_and_notes() {
+        let app = test_app().await;
+        sqlx::query(sql::INSERT_INTO_VISITOR_REQ)
+            .bind(1_234_567)
+            .bind("Visiting")
+            .bind("Controller")
+            .bind("ZLC")
+            .bind(5)
+            .bind(Utc::now())
+            .execute(&app.db)
+            .await
+            .unwrap();
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        for form in [
+            [("action", "hold"), ("notes", "")],
+            [("action", "notes"), ("notes", " Waiting on their ATM ")],
+        ] {
+            let (status, _) = app
+                .post_form("/admin/visitor_applications/1", &form, Some(&cookie))
+                .await;
+            assert_eq!(status, StatusCode::SEE_OTHER);
+        }
+        let request: VisitorRequest = sqlx::query_as(sql::GET_VISITOR_REQUEST_BY_ID)
+            .bind(1)
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(request.status, "hold");
+        assert_eq!(request.notes, "Waiting on their ATM");
+
+        app.post_form(
+            "/admin/visitor_applications/1",
+            &[("action", "resume")],
+            Some(&cookie),
+        )
+        .await;
+        let request: VisitorRequest = sqlx::query_as(sql::GET_VISITOR_REQUEST_BY_ID)
+            .bind(1)
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(request.status, "pending");
+    }
+
+    #[tokio::test]
+    async fn test_loa_lifecycle() {
+        let app = test_app().await;
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let today = Utc::now().date_naive();
+        let start = today.to_string();
+        let end = (today + chrono::Days::new(30)).to_string();
+        let cid = HOME_CONTROLLER.to_string();
+        let (status, _) = app
+            .post_form(
+                "/admin/loa",
+                &[
+                    ("action", "create"),
+                    ("cid", &cid),
+                    ("start", &start),
+                    ("end", &end),
+                    ("reason", "Moving house"),
+                ],
+                Some(&cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let controller = app
+            .state
+            .repos
+            .controllers
+            .get_by_cid(HOME_CONTROLLER)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(controller.loa_until.is_some());
+
+        let (status, body) = app.get("/admin/loa?cid=1000001", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Moving house"));
+        assert!(body.contains("History for Home Controller"));
+
+        app.post_form(
+            "/admin/loa",
+            &[("action", "end"), ("id", "1")],
+            Some(&cookie),
+        )
+        .await;
+        let controller = app
+            .state
+            .repos
+            .controllers
+            .get_by_cid(HOME_CONTROLLER)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(controller.loa_until.is_none());
+        let (_, body) = app.get("/admin/loa?cid=1000001", Some(&cookie)).await;
+        assert!(body.contains("Ended early by 1000002"));
+    }
+
+    #[tokio::test]
+    async fn test_purge_review() {
+        let app = test_app().await;
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        sqlx::query(sql::INSERT_PURGE_CANDIDATE)
+            .bind(HOME_CONTROLLER)
+            .bind("2026-Q3")
+            .bind(45)
+            .bind(Utc::now())
+            .execute(&app.db)
+            .await
+            .unwrap();
+
+        let (status, body) = app.get("/admin/purge", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Roster purge for 2026-Q3"));
+        assert!(body.contains("Home Controller"));
+        assert!(body.contains("Approve purge of 1 controllers"));
+
+        let (status, _) = app
+            .post_form(
+                "/admin/purge",
+                &[("action", "exclude"), ("quarter", "2026-Q3"), ("id", "1")],
+                Some(&cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let (_, body) = app.get("/admin/purge?quarter=2026-Q3", Some(&cookie)).await;
+        assert!(body.contains("Excluded"));
+        assert!(!body.contains("Approve purge"));
+
+        for action in ["include", "approve"] {
+            let (status, _) = app
+                .post_form(
+                    "/admin/purge",
+                    &[("action", action), ("quarter", "2026-Q3"), ("id", "1")],
+                    Some(&cookie),
+                )
+                .await;
+            assert_eq!(status, StatusCode::SEE_OTHER);
+        }
+        let (_, body) = app.get("/admin/purge?quarter=2026-Q3", Some(&cookie)).await;
+        assert!(body.contains("Queued 1 controllers for removal"));
+        assert!(body.contains("by 1000002"));
+        assert!(!body.contains("Approve purge"));
+        let approved: Vec<PurgeCandidate> = sqlx::query_as(sql::GET_APPROVED_PURGE_CANDIDATES)
+            .fetch_all(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(approved.len(), 1);
+        assert_eq!(approved[0].approved_by, Some(ADMIN_CONTROLLER));
+        assert!(approved[0].removed_date.is_none());
+
+        let (status, _) = app
+            .post_form(
+                "/admin/purge",
+                &[("action", "approve"), ("quarter", "2026-Q3\r\nX-Test: 1")],
+                Some(&cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_purge_skips_controllers_no_longer_inactive() {
+        let app = test_app().await;
+        sqlx::query("UPDATE controller SET join_date='2024-01-01T00:00:00Z' WHERE cid=$1")
+            .bind(HOME_CONTROLLER)
+            .execute(&app.db)
+            .await
+            .unwrap();
+        sqlx::query(sql::INSERT_PURGE_CANDIDATE)
+            .bind(HOME_CONTROLLER)
+            .bind("2026-Q2")
+            .bind(45)
+            .bind(Utc::now())
+            .execute(&app.db)
+            .await
+            .unwrap();
+        sqlx::query(sql::APPROVE_PURGE_CANDIDATES)
+            .bind("2026-Q2")
+            .bind(Utc::now())
+            .bind(ADMIN_CONTROLLER)
+            .execute(&app.db)
+            .await
+            .unwrap();
+        // late-credited activity brought them over the requirement
+        sqlx::query(
+            "INSERT INTO activity (id, cid, month, minutes) VALUES (NULL, $1, '2026-05', 200)",
+        )
+        .bind(HOME_CONTROLLER)
+        .execute(&app.db)
+        .await
+        .unwrap();
+
+        remove_approved_purges(&app.state.config, &app.db)
+            .await
+            .unwrap();
+        let candidate: PurgeCandidate =
+            sqlx::query_as("SELECT * FROM purge_candidate WHERE cid=$1")
+                .bind(HOME_CONTROLLER)
+                .fetch_one(&app.db)
+                .await
+                .unwrap();
+        assert!(candidate.excluded);
+        assert!(candidate.removed_date.is_none());
+        assert!(candidate.approved_date.is_none());
+        assert!(candidate.error.is_none());
+        assert_eq!(
+            candidate.skip_reason.as_deref(),
+            Some("has 200 minutes in the quarter")
+        );
+        let on_roster: bool =
+            sqlx::query_scalar("SELECT is_on_roster FROM controller WHERE cid=$1")
+                .bind(HOME_CONTROLLER)
+                .fetch_one(&app.db)
+                .await
+                .unwrap();
+        assert!(on_roster);
+    }
+
+    #[tokio::test]
+    async fn test_quarterly_report_download() {
+        let app = test_app().await;
+        let (status, _) = app.get("/admin/reports/2026-Q3", None).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let (status, body) = app.get("/admin/reports", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("(in progress)"));
+
+        let (status, body) = app.get("/admin/reports/2026-Q3", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.starts_with("vZDV Quarterly Facility Report - 2026-Q3"));
+        assert!(body.contains("Controllers on roster: 2"));
+        let (status, _) = app.get("/admin/reports/2026-Q9", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_site_banners() {
+        let app = test_app().await;
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let now = Utc::now();
+        let format = |time: chrono::DateTime<Utc>| time.format("%Y-%m-%dT%H:%M").to_string();
+        for (message, start, end) in [
+            (
+                "Training records unavailable",
+                now - chrono::Duration::hours(1),
+                now + chrono::Duration::hours(1),
+            ),
+            (
+                "Old outage",
+                now - chrono::Duration::hours(3),
+                now - chrono::Duration::hours(2),
+            ),
+        ] {
+            let (status, _) = app
+                .post_form(
+                    "/admin/banners",
+                    &[
+                        ("action", "create"),
+                        ("message", message),
+                        ("level", "warning"),
+                        ("start", &format(start)),
+                        ("end", &format(end)),
+                        ("timezone", "UTC"),
+                    ],
+                    Some(&cookie),
+                )
+                .await;
+            assert_eq!(status, StatusCode::SEE_OTHER);
+        }
+
+        let (status, body) = app.get("/banners", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("alert-warning"));
+        assert!(body.contains("Training records unavailable"));
+        assert!(!body.contains("Old outage"));
+
+        app.post_form(
+            "/admin/banners",
+            &[("action", "delete"), ("id", "1")],
+            Some(&cookie),
+        )
+        .await;
+        let (_, body) = app.get("/banners", None).await;
+        assert!(!body.contains("Training records unavailable"));
+    }
+
+    #[tokio::test]
+    async fn test_blocklist() {
+        let app = test_app().await;
+        let admin_cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let home_cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, _) = app
+            .post_form(
+                "/admin/blocklist",
+                &[
+                    ("action", "create"),
+                    ("kind", "cid"),
+                    ("value", &HOME_CONTROLLER.to_string()),
+                    ("reason", "Spamming feedback"),
+                    ("expires", ""),
+                    ("timezone", "UTC"),
+                ],
+                Some(&admin_cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+
+        let feedback = [
+            ("controller", "1000002"),
+            ("position", "DEN_APP"),
+            ("rating", "good"),
+            ("comments", "Hello"),
+        ];
+        let (status, body) = app
+            .post_form("/feedback", &feedback, Some(&home_cookie))
+            .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(body.contains("blocked from submitting"));
+        let (status, _) = app.get("/feedback", Some(&home_cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (hits,): (u32,) = sqlx::query_as("SELECT hits FROM block WHERE id=1")
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(hits, 1);
+
+        app.post_form(
+            "/admin/blocklist",
+            &[("action", "delete"), ("id", "1")],
+            Some(&admin_cookie),
+        )
+        .await;
+        let (status, _) = app
+            .post_form("/feedback", &feedback, Some(&home_cookie))
+            .await;
+        assert_ne!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_permissions_page() {
+        let app = test_app().await;
+        let admin_cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let home_cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, _) = app.get("/admin/permissions", Some(&home_cookie)).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let (status, body) = app.get("/admin/permissions", Some(&admin_cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("loa.manage"));
+
+        let (status, _) = app.get("/admin/loa", Some(&home_cookie)).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let cid = HOME_CONTROLLER.to_string();
+        let grant = [
+            ("action", "grant_controller"),
+            ("cid", cid.as_str()),
+            ("permission", "loa.manage"),
+        ];
+        app.post_form("/admin/permissions", &grant, Some(&admin_cookie))
+            .await;
+        let (status, _) = app.get("/admin/loa", Some(&home_cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // unknown capabilities aren't stored
+        let grant = [
+            ("action", "grant_controller"),
+            ("cid", cid.as_str()),
+            ("permission", "everything"),
+        ];
+        app.post_form("/admin/permissions", &grant, Some(&admin_cookie))
+            .await;
+        let (count,): (u32,) = sqlx::query_as("SELECT COUNT(*) FROM controller_permission")
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+
+        app.post_form(
+            "/admin/permissions",
+            &[
+                ("action", "revoke_role"),
+                ("role", "ATM"),
+                ("permission", "loa.manage"),
+            ],
+            Some(&admin_cookie),
+        )
+        .await;
+        let (status, _) = app.get("/admin/loa", Some(&admin_cookie)).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+    }
+
+    #[tokio::test]
+    async fn test_impersonation() {
+        let app = test_app().await;
+        let admin_cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let home_cookie = app.login_as(HOME_CONTROLLER, false).await;
+
+        // only the ATM and WM can view as others
+        app.post_form(
+            "/admin/impersonate",
+            &[("cid", &ADMIN_CONTROLLER.to_string())],
+            Some(&home_cookie),
+        )
+        .await;
+        let (_, body) = app.get("/", Some(&home_cookie)).await;
+        assert!(!body.contains("Viewing the site as"));
+
+        let (status, _) = app
+            .post_form(
+                "/admin/impersonate",
+                &[("cid", &HOME_CONTROLLER.to_string())],
+                Some(&admin_cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let (_, body) = app.get("/", Some(&admin_cookie)).await;
+        assert!(body.contains("Viewing the site as"));
+        let (status, _) = app.get("/admin/loa", Some(&admin_cookie)).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let (status, _) = app
+            .post_form(
+                "/admin/banners",
+                &[("action", "delete"), ("id", "1")],
+                Some(&admin_cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let (_, body) = app.get("/", Some(&admin_cookie)).await;
+        assert!(body.contains("Changes can't be made"));
+
+        app.post_form("/admin/impersonate/stop", &[], Some(&admin_cookie))
+            .await;
+        let (status, _) = app.get("/admin/loa", Some(&admin_cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // both the start and the stop are kept for admins to review
+        let log: Vec<ImpersonationLogEntry> = sqlx::query_as(sql::GET_RECENT_IMPERSONATIONS)
+            .bind(10)
+            .fetch_all(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].staff_cid, ADMIN_CONTROLLER);
+        assert_eq!(log[0].viewed_cid, HOME_CONTROLLER);
+        assert!(log[0].ended_date.is_some());
+        let (status, body) = app.get("/admin/impersonations", Some(&admin_cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Admin Controller"));
+        assert!(body.contains("Home Controller"));
+        let (status, _) = app.get("/admin/impersonations", Some(&home_cookie)).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_mode() {
+        let app = test_app().await;
+        let admin_cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let home_cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, _) = app
+            .post_form(
+                "/admin/maintenance",
+                &[("action", "enable")],
+                Some(&admin_cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+
+        let (status, body) = app.get("/feedback", None).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(body.contains("down for maintenance"));
+        let (status, _) = app.get("/feedback", Some(&home_cookie)).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        let (status, _) = app.get("/feedback", Some(&admin_cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = app.get("/banners", None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        app.post_form(
+            "/admin/maintenance",
+            &[("action", "disable")],
+            Some(&admin_cookie),
+        )
+        .await;
+        let (status, _) = app.get("/feedback", None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_tasks_page() {
+        let app = test_app().await;
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let (status, body) = app.get("/admin/tasks", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("No integrity check has run yet"));
+
+        vzdv::integrity::check_database(&app.db, Utc::now())
+            .await
+            .unwrap();
+        let (_, body) = app.get("/admin/tasks", Some(&cookie)).await;
+        assert!(body.contains("No problems found"));
+
+        let report = vzdv::integrity::IntegrityReport {
+            checked: Utc::now(),
+            problems: vec!["row 5 missing from index controller_cid".to_owned()],
+            checkpoint_busy: false,
+            wal_pages: 0,
+        };
+        sqlx::query(sql::SET_SETTING)
+            .bind(vzdv::integrity::INTEGRITY_REPORT_SETTING)
+            .bind(serde_json::to_string(&report).unwrap())
+            .execute(&app.db)
+            .await
+            .unwrap();
+        let (_, body) = app.get("/admin/tasks", Some(&cookie)).await;
+        assert!(body.contains("1 problem(s) found"));
+        assert!(body.contains("row 5 missing from index controller_cid"));
+
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, _) = app.get("/admin/tasks", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+    }
+
+    #[tokio::test]
+    async fn test_cache_page() {
+        let app = test_app().await;
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        app.state
+            .cache
+            .insert("WEATHER_KDEN", "metar", Duration::from_secs(60));
+        app.state
+            .cache
+            .insert("COTM", "controllers", Duration::from_secs(60));
+        let (status, body) = app.get("/admin/cache", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("WEATHER_KDEN"));
+
+        let (status, _) = app
+            .post_form(
+                "/admin/cache",
+                &[("action", "Invalidate"), ("key", "WEATHER_KDEN")],
+                Some(&cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        assert!(app.state.cache.get::<&str>("WEATHER_KDEN").is_none());
+        assert!(app.state.cache.get::<&str>("COTM").is_some());
+
+        app.post_form("/admin/cache", &[("action", "Flush all")], Some(&cookie))
+            .await;
+        assert!(app.state.cache.entries().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resource_categories() {
+        let app = test_app().await;
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        for name in ["SOP", "LOA", "General"] {
+            let (status, _) = app
+                .post_form(
+                    "/admin/resources/categories",
+                    &[("action", "create"), ("name", name)],
+                    Some(&cookie),
+                )
+                .await;
+            assert_eq!(status, StatusCode::SEE_OTHER);
+        }
+        for (name, category) in [
+            ("Ground SOP", "SOP"),
+            ("Cheyenne LOA", "LOA"),
+            ("Welcome", "General"),
+        ] {
+            sqlx::query(sql::CREATE_NEW_RESOURCE)
+                .bind(category)
+                .bind(name)
+                .bind(None::<String>)
+                .bind("https://example.com/")
+                .bind(Utc::now())
+                .execute(&app.db)
+                .await
+                .unwrap();
+        }
+        let post = |action: &'static str, id: &'static str, name: &'static str| {
+            let cookie = cookie.clone();
+            let app = &app;
+            async move {
+                app.post_form(
+                    "/admin/resources/categories",
+                    &[("action", action), ("id", id), ("name", name)],
+                    Some(&cookie),
+                )
+                .await
+            }
+        };
+        // duplicate names aren't allowed
+        post("rename", "2", "SOP").await;
+        post("rename", "1", "Procedures").await;
+        post("move_up", "2", "").await;
+        post("archive", "3", "").await;
+
+        let categories: Vec<sql::ResourceCategory> = sqlx::query_as(sql::GET_RESOURCE_CATEGORIES)
+            .fetch_all(&app.db)
+            .await
+            .unwrap();
+        let names: Vec<_> = categories.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["LOA", "Procedures", "General"]);
+        assert!(categories[2].archived);
+        let category: String =
+            sqlx::query_scalar("SELECT category FROM resource WHERE name='Ground SOP'")
+                .fetch_one(&app.db)
+                .await
+                .unwrap();
+        assert_eq!(category, "Procedures");
+
+        let (_, body) = app.get("/facility/resources", Some(&cookie)).await;
+        let loa = body.find(">LOA<").unwrap();
+        let procedures = body.find(">Procedures<").unwrap();
+        assert!(loa < procedures);
+        assert!(!body.contains(">General<"));
+
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, _) = app
+            .post_form(
+                "/admin/resources/categories",
+                &[("action", "create"), ("name", "Misc")],
+                Some(&cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let count: u32 = sqlx::query_scalar("SELECT COUNT(*) FROM resource_category")
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(count, 3);
+    }
+
+    #[tokio::test]
+    async fn test_dashboard_pending_counts() {
+        let app = test_app().await;
+        for _ in 0..2 {
+            sqlx::query(sql::INSERT_FEEDBACK)
+                .bind(HOME_CONTROLLER)
+                .bind("DEN_APP")
+                .bind("good")
+                .bind("Smooth vectors")
+                .bind(Utc::now())
+                .bind(1_234_567)
+                .execute(&app.db)
+                .await
+                .unwrap();
+        }
+
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, _) = app.get("/admin/pending", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = app.get("/admin", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let (status, body) = app.get("/admin", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("hx-get=\"/admin/pending\""));
+
+        let req = Request::get("/admin/pending")
+            .header(header::COOKIE, &cookie)
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.router.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[header::CACHE_CONTROL], "private, max-age=30");
+        assert_eq!(resp.headers()[header::VARY], "Cookie");
+        let (_, body) = app.get("/admin/pending", Some(&cookie)).await;
+        assert!(body.contains("Feedback to review"));
+        assert!(body.contains("text-bg-primary\">2<"));
+        assert!(body.contains("Training waitlist"));
+
+        // shared widgets are cacheable by anyone, unless they differ by user
+        for (uri, cache_control) in [
+            ("/home/cotm", "public, max-age=60"),
+            ("/events/upcoming", "private, max-age=60"),
+        ] {
+            let req = Request::get(uri).body(Body::empty()).unwrap();
+            let resp = app.router.clone().oneshot(req).await.unwrap();
+            assert_eq!(resp.headers()[header::CACHE_CONTROL], cache_control);
+        }
+    }
 }
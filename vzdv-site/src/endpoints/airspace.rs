@@ -1,25 +1,135 @@
 //! Endpoints for getting information on the airspace.
 
 use crate::{
-    flashed_messages,
-    shared::{AppError, AppState, CacheEntry, UserInfo, SESSION_USER_INFO_KEY},
+    flashed_messages, live,
+    shared::{AppError, AppState, UserInfo, SESSION_USER_INFO_KEY},
 };
 use axum::{
-    extract::State,
-    response::{Html, Redirect},
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Html, IntoResponse, Redirect, Response,
+    },
     routing::{get, post},
     Form, Router,
 };
-use itertools::Itertools;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use futures_util::Stream;
 use log::{info, warn};
 use minijinja::{context, Environment};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::{sync::Arc, time::Instant};
+use std::{convert::Infallible, sync::Arc, time::Duration};
 use thousands::Separable;
 use tower_sessions::Session;
-use vatsim_utils::live_api::Vatsim;
-use vzdv::{aviation::parse_metar, GENERAL_HTTP_CLIENT};
+use vatsim_utils::{
+    live_api::Vatsim,
+    models::{FlightPlan, Pilot, V3ResponseData},
+};
+use vzdv::{
+    aviation::{
+        distance_nm, parse_filed_departure, parse_filed_duration, parse_metar, AirportWeather,
+    },
+    config::{Airport, ConfigStaffingRequestTier},
+    request_id::WithRequestId,
+    GENERAL_HTTP_CLIENT,
+};
+
+/// How long to reuse a VATSIM datafeed snapshot.
+pub const VATSIM_DATA_TTL: Duration = Duration::from_secs(60);
+/// How long to reuse fetched METARs.
+pub const WEATHER_TTL: Duration = Duration::from_secs(60 * 5);
+/// VATSIM datafeed, for the parts `vatsim_utils` doesn't model.
+const VATSIM_DATAFEED_URL: &str = "https://data.vatsim.net/v3/vatsim-data.json";
+/// Ground speed below which a flight is considered to be on the ground, in knots.
+const TAXI_SPEED: i64 = 40;
+/// Distance from an airport within which a flight is considered to be at the field,
+/// in nautical miles.
+const AT_FIELD_DISTANCE: f64 = 5.0;
+/// How soon an arrival has to be expected to be shown as arriving.
+const ARRIVING_WITHIN: chrono::Duration = chrono::Duration::minutes(15);
+
+/// Get the current VATSIM datafeed, shared by everything showing flights.
+pub async fn get_vatsim_data(state: &AppState) -> Result<Arc<V3ResponseData>, AppError> {
+    state
+        .cache
+        .get_or_try_insert("VATSIM_DATA", VATSIM_DATA_TTL, || async {
+            Ok::<_, AppError>(Vatsim::new().await?.get_v3_data().await?)
+        })
+        .await
+}
+
+/// Flight plan filed before the pilot connects.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Prefile {
+    pub cid: u64,
+    pub name: String,
+    pub callsign: String,
+    pub flight_plan: Option<FlightPlan>,
+}
+
+/// Get the prefiled flight plans from the VATSIM datafeed.
+pub async fn get_vatsim_prefiles(state: &AppState) -> Result<Arc<Vec<Prefile>>, AppError> {
+    #[derive(Deserialize)]
+    struct PrefileData {
+        prefiles: Vec<Prefile>,
+    }
+
+    state
+        .cache
+        .get_or_try_insert("VATSIM_PREFILES", VATSIM_DATA_TTL, || async {
+            let resp = GENERAL_HTTP_CLIENT
+                .get(VATSIM_DATAFEED_URL)
+                .with_request_id()
+                .send()
+                .await?;
+            if !resp.status().is_success() {
+                return Err(AppError::HttpResponse(
+                    "VATSIM datafeed",
+                    resp.status().as_u16(),
+                ));
+            }
+            let data: PrefileData = resp.json().await?;
+            Ok(data.prefiles)
+        })
+        .await
+}
+
+/// Get the parsed METARs for the airports.
+///
+/// Airports whose METARs can't be parsed are logged and skipped.
+pub async fn get_weather(
+    state: &AppState,
+    airports: &[&str],
+) -> Result<Arc<Vec<AirportWeather>>, AppError> {
+    let airports = airports.join(",");
+    state
+        .cache
+        .get_or_try_insert(&format!("WEATHER_{airports}"), WEATHER_TTL, || async {
+            let resp = GENERAL_HTTP_CLIENT
+                .get(format!("https://metar.vatsim.net/{airports}"))
+                .with_request_id()
+                .send()
+                .await?;
+            if !resp.status().is_success() {
+                return Err(AppError::HttpResponse("METAR API", resp.status().as_u16()));
+            }
+            let text = resp.text().await?;
+            let weather: Vec<_> = text
+                .split_terminator('\n')
+                .flat_map(|line| {
+                    parse_metar(line).map_err(|e| {
+                        let airport = line.split(' ').next().unwrap_or("Unknown");
+                        warn!("METAR parsing failure for {airport}: {e}");
+                        e
+                    })
+                })
+                .collect();
+            Ok(weather)
+        })
+        .await
+}
 
 /// Table of all the airspace's airports.
 async fn page_airports(
@@ -33,124 +143,403 @@ async fn page_airports(
     Ok(Html(rendered))
 }
 
-/// Table of all airspace-relevant flights.
-async fn page_flights(
-    State(state): State<Arc<AppState>>,
-    session: Session,
-) -> Result<Html<String>, AppError> {
-    #[derive(Serialize, Default)]
-    struct OnlineFlight<'a> {
-        pilot_name: &'a str,
+/// Flight on an airport's arrival or departure board.
+#[derive(Debug, Serialize)]
+struct BoardFlight {
+    callsign: String,
+    pilot_name: String,
+    pilot_cid: u64,
+    /// Where arrivals are coming from, or where departures are going.
+    other_airport: String,
+    aircraft: String,
+    status: &'static str,
+    /// Estimated arrival time for arrivals, or proposed departure time for
+    /// departures that haven't left yet.
+    #[serde(skip)]
+    time: Option<DateTime<Utc>>,
+    /// `time`, formatted like "2330Z".
+    time_display: Option<String>,
+    /// In nautical miles, if the airport's location is configured.
+    distance: Option<u32>,
+}
+
+impl BoardFlight {
+    fn new(
+        callsign: &str,
+        pilot_name: &str,
         pilot_cid: u64,
-        callsign: &'a str,
-        departure: &'a str,
-        arrival: &'a str,
-        altitude: String,
-        speed: String,
+        other_airport: &str,
+        plan: &FlightPlan,
+        (status, time): (&'static str, Option<DateTime<Utc>>),
+        distance: Option<f64>,
+    ) -> Self {
+        Self {
+            callsign: callsign.to_owned(),
+            pilot_name: pilot_name.to_owned(),
+            pilot_cid,
+            other_airport: other_airport.to_owned(),
+            aircraft: plan.aircraft_short.clone(),
+            status,
+            time,
+            time_display: time.map(|time| time.format("%H%MZ").to_string()),
+            distance: distance.map(|distance| distance.round() as u32),
+        }
     }
+}
+
+/// Sort board flights by time, with flights without one last.
+fn sort_board(flights: &mut [BoardFlight]) {
+    flights.sort_by_key(|flight| (flight.time.is_none(), flight.time));
+}
 
-    // cache this endpoint's returned data for 60 seconds
-    let cache_key = "ONLINE_FLIGHTS_FULL";
-    if let Some(cached) = state.cache.get(&cache_key) {
-        let elapsed = Instant::now() - cached.inserted;
-        if elapsed.as_secs() < 60 {
-            return Ok(Html(cached.data));
+/// Current arrivals and departures for the airport from connected pilots and
+/// prefiled flight plans.
+///
+/// Arrival ETAs are from the flight's distance and ground speed when the
+/// airport's location is configured, and from the filed times otherwise.
+fn airport_board(
+    airport: &Airport,
+    pilots: &[Pilot],
+    prefiles: &[Prefile],
+    now: DateTime<Utc>,
+) -> (Vec<BoardFlight>, Vec<BoardFlight>) {
+    let location = airport.latitude.zip(airport.longitude);
+    let filed_arrival = |plan: &FlightPlan| {
+        Some(parse_filed_departure(&plan.deptime, now)? + parse_filed_duration(&plan.enroute_time)?)
+    };
+    let mut arrivals = Vec::new();
+    let mut departures = Vec::new();
+
+    for pilot in pilots {
+        let Some(plan) = &pilot.flight_plan else {
+            continue;
+        };
+        let distance =
+            location.map(|location| distance_nm((pilot.latitude, pilot.longitude), location));
+        let on_ground = pilot.groundspeed < TAXI_SPEED;
+        if plan.arrival == airport.code {
+            let (status, time) = match distance {
+                Some(distance) if on_ground && distance <= AT_FIELD_DISTANCE => ("Arrived", None),
+                _ if on_ground => ("Not departed", filed_arrival(plan)),
+                Some(distance) => {
+                    let hours = distance / pilot.groundspeed as f64;
+                    let eta = now + chrono::Duration::seconds((hours * 3_600.0) as i64);
+                    let status = if eta - now <= ARRIVING_WITHIN {
+                        "Arriving"
+                    } else {
+                        "En route"
+                    };
+                    (status, Some(eta))
+                }
+                None => ("En route", filed_arrival(plan)),
+            };
+            arrivals.push(BoardFlight::new(
+                &pilot.callsign,
+                &pilot.name,
+                pilot.cid,
+                &plan.departure,
+                plan,
+                (status, time),
+                distance,
+            ));
+        }
+        if plan.departure == airport.code {
+            let (status, time) = if !on_ground {
+                ("Departed", None)
+            } else if pilot.groundspeed > 0 {
+                ("Taxiing", parse_filed_departure(&plan.deptime, now))
+            } else {
+                ("Parked", parse_filed_departure(&plan.deptime, now))
+            };
+            departures.push(BoardFlight::new(
+                &pilot.callsign,
+                &pilot.name,
+                pilot.cid,
+                &plan.arrival,
+                plan,
+                (status, time),
+                distance,
+            ));
+        }
+    }
+    for prefile in prefiles {
+        let Some(plan) = &prefile.flight_plan else {
+            continue;
+        };
+        if plan.arrival == airport.code {
+            arrivals.push(BoardFlight::new(
+                &prefile.callsign,
+                &prefile.name,
+                prefile.cid,
+                &plan.departure,
+                plan,
+                ("Prefiled", filed_arrival(plan)),
+                None,
+            ));
+        }
+        if plan.departure == airport.code {
+            departures.push(BoardFlight::new(
+                &prefile.callsign,
+                &prefile.name,
+                prefile.cid,
+                &plan.arrival,
+                plan,
+                ("Prefiled", parse_filed_departure(&plan.deptime, now)),
+                None,
+            ));
         }
-        state.cache.invalidate(&cache_key);
     }
 
-    let artcc_fields: Vec<_> = state
+    sort_board(&mut arrivals);
+    sort_board(&mut departures);
+    (arrivals, departures)
+}
+
+/// Live arrival and departure boards for one of the airspace's airports.
+async fn page_airport(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path(code): Path<String>,
+) -> Result<Response, AppError> {
+    let Some(airport) = state
         .config
         .airports
         .all
         .iter()
-        .map(|airport| &airport.code)
-        .collect();
-    let vatsim_data = Vatsim::new().await?.get_v3_data().await?;
-    let flights: Vec<OnlineFlight> = vatsim_data
-        .pilots
+        .find(|airport| airport.code.eq_ignore_ascii_case(&code))
+    else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let vatsim_data = get_vatsim_data(&state).await?;
+    let prefiles = match get_vatsim_prefiles(&state).await {
+        Ok(prefiles) => prefiles,
+        Err(e) => {
+            warn!("Could not get prefiled flight plans: {e}");
+            Arc::new(Vec::new())
+        }
+    };
+    let (arrivals, departures) = airport_board(airport, &vatsim_data.pilots, &prefiles, Utc::now());
+
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let template = state.templates.get_template("airspace/airport")?;
+    let rendered = template.render(context! { user_info, airport, arrivals, departures })?;
+    Ok(Html(rendered).into_response())
+}
+
+/// Airspace-relevant flight, for the flights page.
+#[derive(Debug, Serialize)]
+struct OnlineFlight {
+    pilot_name: String,
+    pilot_cid: u64,
+    callsign: String,
+    departure: String,
+    arrival: String,
+    altitude: String,
+    speed: String,
+}
+
+/// Flights page query parameters; empty and unknown values are ignored.
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+struct FlightsFilter {
+    /// "departures" or "arrivals"
+    direction: Option<String>,
+    airport: Option<String>,
+    /// "airborne" or "preflight"
+    phase: Option<String>,
+}
+
+impl FlightsFilter {
+    /// Drop empty and unknown values, and uppercase the airport.
+    fn normalized(self) -> Self {
+        Self {
+            direction: self
+                .direction
+                .filter(|direction| direction == "departures" || direction == "arrivals"),
+            airport: self
+                .airport
+                .map(|airport| airport.trim().to_uppercase())
+                .filter(|airport| !airport.is_empty()),
+            phase: self
+                .phase
+                .filter(|phase| phase == "airborne" || phase == "preflight"),
+        }
+    }
+
+    /// Key for caching the flights matching this filter.
+    fn cache_key(&self) -> String {
+        format!(
+            "FLIGHTS_{}_{}_{}",
+            self.direction.as_deref().unwrap_or_default(),
+            self.airport.as_deref().unwrap_or_default(),
+            self.phase.as_deref().unwrap_or_default()
+        )
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Flights to or from the airspace's airports that match the filter.
+///
+/// Flights on the ground count as preflight unless they're at their
+/// arrival airport, which is only known if its location is configured.
+fn filter_flights(
+    pilots: &[Pilot],
+    airports: &[Airport],
+    filter: &FlightsFilter,
+) -> Vec<OnlineFlight> {
+    let airport_matches = |code: &String| match &filter.airport {
+        Some(airport) => code == airport,
+        None => airports.iter().any(|airport| &airport.code == code),
+    };
+    pilots
         .iter()
-        .flat_map(|flight| {
-            if let Some(plan) = &flight.flight_plan {
-                let from = artcc_fields.contains(&&plan.departure);
-                let to = artcc_fields.contains(&&plan.arrival);
-                if from || to {
-                    Some(OnlineFlight {
-                        pilot_name: &flight.name,
-                        pilot_cid: flight.cid,
-                        callsign: &flight.callsign,
-                        departure: &plan.departure,
-                        arrival: &plan.arrival,
-                        altitude: flight.altitude.separate_with_commas(),
-                        speed: flight.groundspeed.separate_with_commas(),
-                    })
-                } else {
-                    None
+        .filter_map(|flight| {
+            let plan = flight.flight_plan.as_ref()?;
+            let from = airport_matches(&plan.departure);
+            let to = airport_matches(&plan.arrival);
+            let direction_matches = match filter.direction.as_deref() {
+                Some("departures") => from,
+                Some("arrivals") => to,
+                _ => from || to,
+            };
+            if !direction_matches {
+                return None;
+            }
+            if let Some(phase) = filter.phase.as_deref() {
+                let on_ground = flight.groundspeed < TAXI_SPEED;
+                let arrived = on_ground
+                    && airports
+                        .iter()
+                        .find(|airport| airport.code == plan.arrival)
+                        .and_then(|airport| airport.latitude.zip(airport.longitude))
+                        .is_some_and(|location| {
+                            distance_nm((flight.latitude, flight.longitude), location)
+                                <= AT_FIELD_DISTANCE
+                        });
+                let phase_matches = match phase {
+                    "airborne" => !on_ground,
+                    _ => on_ground && !arrived,
+                };
+                if !phase_matches {
+                    return None;
                 }
-            } else {
-                None
             }
+            Some(OnlineFlight {
+                pilot_name: flight.name.clone(),
+                pilot_cid: flight.cid,
+                callsign: flight.callsign.clone(),
+                departure: plan.departure.clone(),
+                arrival: plan.arrival.clone(),
+                altitude: flight.altitude.separate_with_commas(),
+                speed: flight.groundspeed.separate_with_commas(),
+            })
         })
-        .collect();
+        .collect()
+}
 
-    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    let template = state.templates.get_template("airspace/flights")?;
-    let rendered = template.render(context! { user_info, flights })?;
+/// Flights table rows for a filter.
+#[derive(Debug, Serialize)]
+struct FlightRows {
+    count: usize,
+    html: String,
+}
+
+/// Render the flights matching the filter as the flights table's rows.
+///
+/// The rows are cached per filter, since they're also pushed to every open
+/// flights page with that filter.
+async fn render_flight_rows(
+    state: &Arc<AppState>,
+    filter: &FlightsFilter,
+) -> Result<Arc<FlightRows>, AppError> {
     state
         .cache
-        .insert(cache_key, CacheEntry::new(rendered.clone()));
+        .get_or_try_insert(
+            &format!("{}_HTML", filter.cache_key()),
+            VATSIM_DATA_TTL,
+            || async {
+                let flights = state
+                    .cache
+                    .get_or_try_insert(&filter.cache_key(), VATSIM_DATA_TTL, || async {
+                        let vatsim_data = get_vatsim_data(state).await?;
+                        Ok::<_, AppError>(filter_flights(
+                            &vatsim_data.pilots,
+                            &state.config.airports.all,
+                            filter,
+                        ))
+                    })
+                    .await?;
+                let template = state.templates.get_template("airspace/flights_rows")?;
+                Ok::<_, AppError>(FlightRows {
+                    count: flights.len(),
+                    html: template.render(context! { flights })?,
+                })
+            },
+        )
+        .await
+}
+
+/// Table of all airspace-relevant flights, filtered by the query parameters.
+async fn page_flights(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Query(filter): Query<FlightsFilter>,
+) -> Result<Html<String>, AppError> {
+    let filter = filter.normalized();
+    let rows = render_flight_rows(&state, &filter).await?;
+
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let template = state.templates.get_template("airspace/flights")?;
+    let rendered = template.render(context! {
+        user_info,
+        rows => rows.html,
+        count => rows.count,
+        is_filtered => !filter.is_empty(),
+        filters => filter,
+        airports => &state.config.airports.all,
+    })?;
     Ok(Html(rendered))
 }
 
+/// Server-sent events of the flights table's rows whenever they change, so the
+/// flights page stays current without reloading.
+///
+/// Takes the same query parameters as the flights page.
+async fn live_flights(
+    State(state): State<Arc<AppState>>,
+    Query(filter): Query<FlightsFilter>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let filter = Arc::new(filter.normalized());
+    Sse::new(live::updates("flights", VATSIM_DATA_TTL, move || {
+        let state = state.clone();
+        let filter = filter.clone();
+        async move {
+            let rows = render_flight_rows(&state, &filter).await?;
+            Ok(json!({ "count": rows.count, "html": rows.html }).to_string())
+        }
+    }))
+    .keep_alive(KeepAlive::default())
+}
+
 /// Larger view of the weather.
 async fn page_weather(
     State(state): State<Arc<AppState>>,
     session: Session,
 ) -> Result<Html<String>, AppError> {
-    // cache this endpoint's returned data for 5 minutes
-    let cache_key = "WEATHER_FULL";
-    if let Some(cached) = state.cache.get(&cache_key) {
-        let elapsed = Instant::now() - cached.inserted;
-        if elapsed.as_secs() < 300 {
-            return Ok(Html(cached.data));
-        }
-        state.cache.invalidate(&cache_key);
-    }
-
-    let resp = GENERAL_HTTP_CLIENT
-        .get(format!(
-            "https://metar.vatsim.net/{}",
-            state
-                .config
-                .airports
-                .all
-                .iter()
-                .map(|airport| &airport.code)
-                .join(",")
-        ))
-        .send()
-        .await?;
-    if !resp.status().is_success() {
-        return Err(AppError::HttpResponse("METAR API", resp.status().as_u16()));
-    }
-    let text = resp.text().await?;
-    let weather: Vec<_> = text
-        .split_terminator('\n')
-        .flat_map(|line| {
-            parse_metar(line).map_err(|e| {
-                let airport = line.split(' ').next().unwrap_or("Unknown");
-                warn!("Metar parsing failure for {airport}: {e}");
-                e
-            })
-        })
+    let airports: Vec<_> = state
+        .config
+        .airports
+        .all
+        .iter()
+        .map(|airport| airport.code.as_str())
         .collect();
+    let weather = get_weather(&state, &airports).await?;
 
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
     let template = state.templates.get_template("airspace/weather")?;
     let rendered = template.render(context! { user_info, weather })?;
-    state
-        .cache
-        .insert(cache_key, CacheEntry::new(rendered.clone()));
     Ok(Html(rendered))
 }
 
@@ -179,6 +568,39 @@ struct StaffingRequestForm {
     comments: String,
 }
 
+/// Parse the staffing request's start time, entered as Zulu in the form's placeholder format.
+fn parse_staffing_request_time(value: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(value.trim(), "%m/%d/%Y %H:%M")
+        .ok()
+        .map(|dt| dt.and_utc())
+}
+
+/// Get the Discord roles to ping for a staffing request.
+///
+/// Requests whose start time can't be parsed are never considered urgent.
+fn staffing_request_pings(
+    tiers: &[ConfigStaffingRequestTier],
+    pilot_count: i16,
+    start: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Vec<u64> {
+    let hours_until = start.map(|start| (start - now).num_hours());
+    let mut roles: Vec<u64> = tiers
+        .iter()
+        .filter(|tier| {
+            let large = i32::from(pilot_count) >= i32::from(tier.min_pilots);
+            let urgent = tier.urgent_within_hours > 0
+                && hours_until.is_some_and(|hours| hours < i64::from(tier.urgent_within_hours));
+            large || urgent
+        })
+        .map(|tier| tier.role)
+        .filter(|role| *role != 0)
+        .collect();
+    roles.sort_unstable();
+    roles.dedup();
+    roles
+}
+
 /// Submit the staffing request form.
 async fn page_staffing_request_post(
     State(state): State<Arc<AppState>>,
@@ -187,10 +609,24 @@ async fn page_staffing_request_post(
 ) -> Result<Redirect, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await.unwrap();
     if let Some(user_info) = user_info {
+        let pings = staffing_request_pings(
+            &state.config.discord.staffing_request_tiers,
+            staffing_request.pilot_count,
+            parse_staffing_request_time(&staffing_request.dt_start),
+            Utc::now(),
+        );
+        let content = pings
+            .iter()
+            .map(|role| format!("<@&{role}>"))
+            .collect::<Vec<_>>()
+            .join(" ");
         let resp = GENERAL_HTTP_CLIENT
             .post(&state.config.discord.webhooks.staffing_request)
             .json(&json!({
-                "content": "",
+                "content": content,
+                "allowed_mentions": {
+                    "roles": pings.iter().map(|role| role.to_string()).collect::<Vec<_>>()
+                },
                 "embeds": [{
                     "title": "New staffing request",
                     "fields": [
@@ -286,17 +722,30 @@ pub fn router(templates: &mut Environment) -> Router<Arc<AppState>> {
             include_str!("../../templates/airspace/staffing_request.jinja"),
         )
         .unwrap();
+    templates
+        .add_template(
+            "airspace/airport",
+            include_str!("../../templates/airspace/airport.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "airspace/flights_rows",
+            include_str!("../../templates/airspace/flights_rows.jinja"),
+        )
+        .unwrap();
     templates
         .add_template(
             "airspace/weather",
             include_str!("../../templates/airspace/weather.jinja"),
         )
         .unwrap();
-    templates.add_filter("format_number", |value: u16| value.separate_with_commas());
 
     Router::new()
         .route("/airspace/airports", get(page_airports))
+        .route("/airspace/airports/:code", get(page_airport))
         .route("/airspace/flights", get(page_flights))
+        .route("/airspace/flights/live", get(live_flights))
         .route("/airspace/weather", get(page_weather))
         .route("/airspace/staffing_request", get(page_staffing_request))
         .route(
@@ -304,3 +753,358 @@ pub fn router(templates: &mut Environment) -> Router<Arc<AppState>> {
             post(page_staffing_request_post),
         )
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::{
+        airport_board, filter_flights, parse_staffing_request_time, staffing_request_pings,
+        BoardFlight, FlightsFilter, OnlineFlight, Prefile, VATSIM_DATA_TTL, WEATHER_TTL,
+    };
+    use crate::test_utils::test_app;
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+    };
+    use chrono::{Duration, TimeZone, Utc};
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+    use vatsim_utils::models::{Pilot, V3ResponseData};
+    use vzdv::{
+        aviation::parse_metar,
+        config::{Airport, ConfigStaffingRequestTier},
+    };
+
+    fn flight_plan(departure: &str, arrival: &str) -> Value {
+        json!({
+            "flight_rules": "I",
+            "aircraft": "B738/L",
+            "aircraft_faa": "B738/L",
+            "aircraft_short": "B738",
+            "departure": departure,
+            "arrival": arrival,
+            "alternate": "",
+            "cruise_tas": "450",
+            "altitude": "35000",
+            "deptime": "1200",
+            "enroute_time": "0230",
+            "fuel_time": "0400",
+            "remarks": "",
+            "route": "DCT",
+            "revision_id": 1,
+            "assigned_transponder": "0000",
+        })
+    }
+
+    fn pilot(callsign: &str, position: (f64, f64), groundspeed: i64, plan: Value) -> Pilot {
+        serde_json::from_value(json!({
+            "cid": 1_234_567,
+            "name": "Pilot",
+            "callsign": callsign,
+            "server": "USA-EAST",
+            "pilot_rating": 0,
+            "military_rating": 0,
+            "latitude": position.0,
+            "longitude": position.1,
+            "altitude": 5_000,
+            "groundspeed": groundspeed,
+            "transponder": "1200",
+            "heading": 0,
+            "qnh_i_hg": 29.92,
+            "qnh_mb": 1013,
+            "flight_plan": plan,
+            "logon_time": "2026-10-17T10:00:00Z",
+            "last_updated": "2026-10-17T11:00:00Z",
+        }))
+        .unwrap()
+    }
+
+    fn vatsim_data(pilots: Vec<Pilot>) -> V3ResponseData {
+        serde_json::from_value(json!({
+            "general": {
+                "version": 3,
+                "reload": 1,
+                "update": "20261017110000",
+                "update_timestamp": "2026-10-17T11:00:00Z",
+                "connected_clients": pilots.len(),
+                "unique_users": pilots.len(),
+            },
+            "pilots": pilots,
+            "controllers": [],
+            "atis": [],
+            "servers": [],
+            "facilities": [],
+            "ratings": [],
+            "pilot_ratings": [],
+            "military_ratings": [],
+        }))
+        .unwrap()
+    }
+
+    fn prefile(callsign: &str, plan: Value) -> Prefile {
+        serde_json::from_value(json!({
+            "cid": 7_654_321,
+            "name": "Prefiler",
+            "callsign": callsign,
+            "flight_plan": plan,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_staffing_request_pings() {
+        let tiers = [
+            ConfigStaffingRequestTier {
+                min_pilots: 1,
+                urgent_within_hours: 0,
+                role: 10,
+            },
+            ConfigStaffingRequestTier {
+                min_pilots: 20,
+                urgent_within_hours: 24,
+                role: 20,
+            },
+        ];
+        let now = Utc::now();
+        let later = Some(now + Duration::days(7));
+        assert_eq!(staffing_request_pings(&tiers, 5, later, now), vec![10]);
+        assert_eq!(staffing_request_pings(&tiers, 25, later, now), vec![10, 20]);
+        assert_eq!(
+            staffing_request_pings(&tiers, 5, Some(now + Duration::hours(3)), now),
+            vec![10, 20]
+        );
+        assert_eq!(staffing_request_pings(&tiers, 5, None, now), vec![10]);
+        assert!(staffing_request_pings(&[], 50, later, now).is_empty());
+
+        assert!(parse_staffing_request_time("01/15/2024 14:00").is_some());
+        assert!(parse_staffing_request_time("tomorrow").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_weather_remarks() {
+        let app = test_app().await;
+        let airports: Vec<_> = app
+            .state
+            .config
+            .airports
+            .all
+            .iter()
+            .map(|airport| airport.code.as_str())
+            .collect();
+        let weather = vec![
+            parse_metar("KDEN 030253Z 22013KT 10SM BKN160 13/M12 A2943 RMK AO2 PK WND 21036/0211 SLP924 RAE30").unwrap(),
+            parse_metar("KAPA 030253Z 22013KT 10SM BKN160 13/M12 A2943 RMK AO2").unwrap(),
+        ];
+        app.state.cache.insert(
+            &format!("WEATHER_{}", airports.join(",")),
+            weather,
+            WEATHER_TTL,
+        );
+
+        let (status, body) = app.get("/airspace/weather", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("#remarks-KDEN"));
+        assert!(!body.contains("#remarks-KAPA"));
+        assert!(body.contains("Sea level pressure: 992.4 hPa"));
+        assert!(body.contains("Peak wind: 210&deg; at 36 kt at 0211Z"));
+        assert!(body.contains("Rain ended at :30"));
+    }
+
+    #[tokio::test]
+    async fn test_airport_board() {
+        let app = test_app().await;
+        let airport = app.state.config.airports.all[0].clone();
+        let now = Utc.with_ymd_and_hms(2026, 10, 17, 11, 0, 0).unwrap();
+        let pilots = [
+            // 60 nm out at 240 knots
+            pilot(
+                "AAL1",
+                (38.8617, -104.6731),
+                240,
+                flight_plan("KDFW", "KDEN"),
+            ),
+            pilot("AAL2", (37.0, -104.6731), 400, flight_plan("KDFW", "KDEN")),
+            pilot(
+                "AAL3",
+                (39.8620, -104.6730),
+                10,
+                flight_plan("KDFW", "KDEN"),
+            ),
+            pilot("AAL4", (32.8998, -97.0403), 0, flight_plan("KDFW", "KDEN")),
+            pilot(
+                "SWA1",
+                (39.8617, -104.6731),
+                15,
+                flight_plan("KDEN", "KLAS"),
+            ),
+            pilot("SWA2", (39.8617, -104.6731), 0, flight_plan("KDEN", "KPHX")),
+            pilot("SWA3", (40.5, -104.6731), 300, flight_plan("KDEN", "KSEA")),
+            pilot("UAL1", (40.0, -100.0), 450, flight_plan("KORD", "KSFO")),
+        ];
+        let prefiles = [prefile("UAL2", flight_plan("KDEN", "KIAH"))];
+
+        let (arrivals, departures) = airport_board(&airport, &pilots, &prefiles, now);
+        let summary = |flights: &[BoardFlight]| -> Vec<(String, &str, Option<String>)> {
+            flights
+                .iter()
+                .map(|f| (f.callsign.clone(), f.status, f.time_display.clone()))
+                .collect()
+        };
+        let time = |hhmm: &str| Some(hhmm.to_owned());
+        assert_eq!(
+            summary(&arrivals),
+            [
+                ("AAL1".to_owned(), "Arriving", time("1115Z")),
+                ("AAL2".to_owned(), "En route", time("1125Z")),
+                ("AAL4".to_owned(), "Not departed", time("1430Z")),
+                ("AAL3".to_owned(), "Arrived", None),
+            ]
+        );
+        assert_eq!(
+            summary(&departures),
+            [
+                ("SWA1".to_owned(), "Taxiing", time("1200Z")),
+                ("SWA2".to_owned(), "Parked", time("1200Z")),
+                ("UAL2".to_owned(), "Prefiled", time("1200Z")),
+                ("SWA3".to_owned(), "Departed", None),
+            ]
+        );
+        assert_eq!(arrivals[0].distance, Some(60));
+        assert_eq!(departures[2].distance, None);
+    }
+
+    #[tokio::test]
+    async fn test_airport_page() {
+        let app = test_app().await;
+        let vatsim_data = vatsim_data(vec![pilot(
+            "AAL1",
+            (38.8617, -104.6731),
+            240,
+            flight_plan("KDFW", "KDEN"),
+        )]);
+        app.state
+            .cache
+            .insert("VATSIM_DATA", vatsim_data, VATSIM_DATA_TTL);
+        app.state.cache.insert(
+            "VATSIM_PREFILES",
+            vec![prefile("UAL2", flight_plan("KDEN", "KIAH"))],
+            VATSIM_DATA_TTL,
+        );
+
+        let (status, body) = app.get("/airspace/airports/kden", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("KDEN &ndash; Denver Intl"));
+        assert!(body.contains("AAL1"));
+        assert!(body.contains("UAL2"));
+        assert!(body.contains("Prefiled"));
+        assert!(body.contains("Distance (nm)"));
+
+        let (status, _) = app.get("/airspace/airports/KXXX", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_filter_flights() {
+        let airports = [Airport {
+            code: "KDEN".to_owned(),
+            latitude: Some(39.8617),
+            longitude: Some(-104.6731),
+            ..Default::default()
+        }];
+        let pilots = [
+            pilot("SWA1", (39.8617, -104.6731), 0, flight_plan("KDEN", "KLAS")),
+            pilot("SWA2", (40.5, -104.6731), 300, flight_plan("KDEN", "KPHX")),
+            pilot(
+                "AAL1",
+                (38.8617, -104.6731),
+                240,
+                flight_plan("KDFW", "KDEN"),
+            ),
+            pilot(
+                "AAL2",
+                (39.8620, -104.6730),
+                10,
+                flight_plan("KDFW", "KDEN"),
+            ),
+            pilot("UAL1", (40.0, -100.0), 450, flight_plan("KORD", "KSFO")),
+        ];
+        let callsigns = |direction: Option<&str>, airport: Option<&str>, phase: Option<&str>| {
+            let filter = FlightsFilter {
+                direction: direction.map(str::to_owned),
+                airport: airport.map(str::to_owned),
+                phase: phase.map(str::to_owned),
+            }
+            .normalized();
+            filter_flights(&pilots, &airports, &filter)
+                .into_iter()
+                .map(|flight| flight.callsign)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            callsigns(None, None, None),
+            ["SWA1", "SWA2", "AAL1", "AAL2"]
+        );
+        assert_eq!(callsigns(Some("departures"), None, None), ["SWA1", "SWA2"]);
+        assert_eq!(callsigns(Some("arrivals"), None, None), ["AAL1", "AAL2"]);
+        assert_eq!(callsigns(None, None, Some("airborne")), ["SWA2", "AAL1"]);
+        // arrived flights aren't preflight
+        assert_eq!(callsigns(None, None, Some("preflight")), ["SWA1"]);
+        assert_eq!(callsigns(None, Some("ksfo"), None), ["UAL1"]);
+        assert_eq!(
+            callsigns(Some("arrivals"), Some("KLAS"), Some("bogus")),
+            ["SWA1"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flights_page_filters() {
+        let app = test_app().await;
+        app.state.cache.insert(
+            "VATSIM_DATA",
+            vatsim_data(vec![
+                pilot("SWA1", (39.8617, -104.6731), 0, flight_plan("KDEN", "KLAS")),
+                pilot(
+                    "AAL1",
+                    (38.8617, -104.6731),
+                    240,
+                    flight_plan("KDFW", "KDEN"),
+                ),
+            ]),
+            VATSIM_DATA_TTL,
+        );
+
+        let (status, body) = app.get("/airspace/flights", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("SWA1") && body.contains("AAL1"));
+
+        let (status, body) = app
+            .get(
+                "/airspace/flights?direction=arrivals&airport=&phase=airborne",
+                None,
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!body.contains("SWA1") && body.contains("AAL1"));
+        assert!(body.contains("<option value=\"arrivals\" selected>"));
+        assert!(app
+            .state
+            .cache
+            .get::<Vec<OnlineFlight>>("FLIGHTS_arrivals__airborne")
+            .is_some());
+
+        let (_, body) = app
+            .get(
+                "/airspace/flights?direction=departures&phase=airborne",
+                None,
+            )
+            .await;
+        assert!(body.contains("No flights match"));
+
+        let req = Request::get("/airspace/flights/live?direction=arrivals")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.router.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], "text/event-stream");
+    }
+}
@@ -0,0 +1,654 @@
+//! JSON API for other services, like the Discord bot and partner facilities.
+//!
+//! Public data needs no authentication. Anything more is only included for
+//! requests with one of the configured API keys, or from a logged-in user with
+//! the matching permission.
+
+use crate::{
+    endpoints::airspace::get_vatsim_data,
+    shared::{has_permission, AppError, AppState, UserInfo, SESSION_USER_INFO_KEY},
+};
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use log::debug;
+use minijinja::{context, Environment};
+use serde::Serialize;
+use serde_json::json;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tower_sessions::Session;
+use vzdv::{
+    config::Config,
+    permissions,
+    sql::{self, Certification, Controller, Event, EventPosition, EventRegistration},
+    vatsim::{online_facility_controllers, OnlineController},
+};
+
+/// How long to reuse the online controllers and ATIS for the overlay.
+const ONLINE_TTL: Duration = Duration::from_secs(30);
+
+/// Name of the configured API key sent with the request, if any.
+pub fn api_client<'a>(state: &'a AppState, headers: &HeaderMap) -> Option<&'a str> {
+    let sent = headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")?;
+    state
+        .config
+        .api
+        .keys
+        .iter()
+        .find(|key| !key.key.is_empty() && constant_time_eq(key.key.as_bytes(), sent.as_bytes()))
+        .map(|key| key.name.as_str())
+}
+
+/// Compare without returning early, so the time taken doesn't hint at the key.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Reject the request unless it has one of the configured API keys.
+fn require_api_client(state: &AppState, headers: &HeaderMap) -> Option<Response> {
+    match api_client(state, headers) {
+        Some(client) => {
+            debug!("API request from {client}");
+            None
+        }
+        None => Some(api_error(
+            StatusCode::UNAUTHORIZED,
+            "A valid API key is required",
+        )),
+    }
+}
+
+/// JSON error body with the status.
+pub fn api_error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+#[derive(Serialize)]
+struct ApiController {
+    cid: u32,
+    name: String,
+    operating_initials: Option<String>,
+}
+
+impl ApiController {
+    fn from(controller: &Controller) -> Self {
+        Self {
+            cid: controller.cid,
+            name: format!("{} {}", controller.first_name, controller.last_name),
+            operating_initials: controller.operating_initials.clone(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ApiEventPosition {
+    id: u32,
+    name: String,
+    category: String,
+    controller: Option<ApiController>,
+}
+
+#[derive(Serialize)]
+struct ApiEventRegistration {
+    controller: ApiController,
+    /// Names of the positions they asked for, in order of preference.
+    choices: Vec<String>,
+    notes: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ApiEvent {
+    id: u32,
+    name: String,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    description: Option<String>,
+    /// Banner, which joint events share with the partner facility.
+    image_url: Option<String>,
+    url: String,
+    partner_facility: Option<String>,
+    external_signup_url: Option<String>,
+    positions: Vec<ApiEventPosition>,
+    /// Only included for API clients and event staff.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    registrations: Option<Vec<ApiEventRegistration>>,
+}
+
+/// Whether the request can see event registrations.
+async fn can_see_registrations(
+    state: &Arc<AppState>,
+    session: &Session,
+    headers: &HeaderMap,
+) -> Result<bool, AppError> {
+    if let Some(client) = api_client(state, headers) {
+        debug!("API request from {client}");
+        return Ok(true);
+    }
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    Ok(has_permission(state, &user_info, permissions::EVENTS_MANAGE).await)
+}
+
+/// Build the JSON form of the events, looking up controllers once for all of them.
+async fn api_events(
+    state: &Arc<AppState>,
+    events: Vec<Event>,
+    with_registrations: bool,
+) -> Result<Vec<ApiEvent>, AppError> {
+    let controllers: HashMap<u32, Controller> = state
+        .repos
+        .controllers
+        .get_all()
+        .await?
+        .into_iter()
+        .map(|c| (c.cid, c))
+        .collect();
+    let controller_for = |cid: u32| match controllers.get(&cid) {
+        Some(controller) => ApiController::from(controller),
+        None => ApiController {
+            cid,
+            name: String::new(),
+            operating_initials: None,
+        },
+    };
+
+    let mut ret = Vec::with_capacity(events.len());
+    for event in events {
+        let positions: Vec<EventPosition> = state.repos.events.get_positions(event.id).await?;
+        let registrations = if with_registrations {
+            let registrations: Vec<EventRegistration> =
+                sqlx::query_as(sql::GET_EVENT_REGISTRATIONS)
+                    .bind(event.id)
+                    .fetch_all(&state.db)
+                    .await?;
+            let position_name = |id: u32| {
+                positions
+                    .iter()
+                    .find(|p| p.id == id)
+                    .map(|p| p.name.clone())
+            };
+            Some(
+                registrations
+                    .into_iter()
+                    .map(|registration| ApiEventRegistration {
+                        controller: controller_for(registration.cid),
+                        choices: [
+                            registration.choice_1,
+                            registration.choice_2,
+                            registration.choice_3,
+                        ]
+                        .into_iter()
+                        .filter_map(position_name)
+                        .collect(),
+                        notes: registration.notes,
+                    })
+                    .collect(),
+            )
+        } else {
+            None
+        };
+        ret.push(ApiEvent {
+            id: event.id,
+            url: format!(
+                "{}/events/{}",
+                state.config.hosted_domain.trim_end_matches('/'),
+                event.id
+            ),
+            name: event.name,
+            start: event.start,
+            end: event.end,
+            description: event.description,
+            image_url: event.image_url,
+            partner_facility: event.partner_facility,
+            external_signup_url: event.external_signup_url,
+            positions: positions
+                .into_iter()
+                .map(|position| ApiEventPosition {
+                    id: position.id,
+                    controller: position.cid.map(controller_for),
+                    name: position.name,
+                    category: position.category,
+                })
+                .collect(),
+            registrations,
+        });
+    }
+    Ok(ret)
+}
+
+/// Published upcoming events, with their positions.
+async fn get_events(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let with_registrations = can_see_registrations(&state, &session, &headers).await?;
+    let events = state.repos.events.get_upcoming(Utc::now(), false).await?;
+    let events = api_events(&state, events, with_registrations).await?;
+    Ok(Json(events).into_response())
+}
+
+/// A single published event, with its positions.
+async fn get_event(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    headers: HeaderMap,
+    Path(id): Path<u32>,
+) -> Result<Response, AppError> {
+    let event = match state.repos.events.get(id).await? {
+        Some(event) if event.published => event,
+        _ => return Ok(api_error(StatusCode::NOT_FOUND, "Event not found")),
+    };
+    let with_registrations = can_see_registrations(&state, &session, &headers).await?;
+    let mut events = api_events(&state, vec![event], with_registrations).await?;
+    Ok(Json(events.remove(0)).into_response())
+}
+
+/// Published upcoming events held jointly with the facility, with our positions.
+///
+/// Lets partner ARTCCs coordinate joint events, like crossfires, by polling
+/// who we have staffing each position.
+async fn get_partner_events(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    headers: HeaderMap,
+    Path(facility): Path<String>,
+) -> Result<Response, AppError> {
+    let events: Vec<Event> = sqlx::query_as(sql::GET_UPCOMING_PARTNER_EVENTS)
+        .bind(Utc::now())
+        .bind(facility.to_uppercase())
+        .fetch_all(&state.db)
+        .await?;
+    let with_registrations = can_see_registrations(&state, &session, &headers).await?;
+    let events = api_events(&state, events, with_registrations).await?;
+    Ok(Json(events).into_response())
+}
+
+#[derive(Serialize)]
+struct ApiAtis {
+    callsign: String,
+    frequency: String,
+    /// Current information letter, if set.
+    code: Option<String>,
+    text: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ApiOnline {
+    controllers: Vec<OnlineController>,
+    atis: Vec<ApiAtis>,
+    updated: DateTime<Utc>,
+}
+
+/// Whether the callsign is an ATIS at one of the facility's airports, like "KDEN_D_ATIS".
+fn is_facility_atis(config: &Config, callsign: &str) -> bool {
+    if !callsign.ends_with("_ATIS") {
+        return false;
+    }
+    let airport = callsign.split('_').next().unwrap_or_default();
+    let airport = airport.strip_prefix('K').unwrap_or(airport);
+    config
+        .stats
+        .position_prefixes
+        .iter()
+        .any(|prefix| prefix == airport)
+}
+
+/// Facility controllers and ATIS currently online, briefly cached.
+async fn online_now(state: &Arc<AppState>) -> Result<Arc<ApiOnline>, AppError> {
+    state
+        .cache
+        .get_or_try_insert("API_ONLINE", ONLINE_TTL, || async {
+            let data = get_vatsim_data(state).await?;
+            let controllers = online_facility_controllers(&state.db, &state.config, &data)
+                .await
+                .into_iter()
+                .sorted_by(|a, b| a.callsign.cmp(&b.callsign))
+                .collect();
+            let atis = data
+                .atis
+                .iter()
+                .filter(|atis| is_facility_atis(&state.config, &atis.callsign))
+                .map(|atis| ApiAtis {
+                    callsign: atis.callsign.clone(),
+                    frequency: atis.frequency.clone(),
+                    code: atis.atis_code.clone(),
+                    text: atis.text_atis.as_ref().map(|lines| lines.join(" ")),
+                })
+                .sorted_by(|a, b| a.callsign.cmp(&b.callsign))
+                .collect();
+            Ok::<_, AppError>(ApiOnline {
+                controllers,
+                atis,
+                updated: Utc::now(),
+            })
+        })
+        .await
+}
+
+/// Facility controllers and ATIS currently online.
+///
+/// CORS is open so that overlays hosted anywhere can poll this.
+async fn get_online(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    let online = online_now(&state).await?;
+    Ok((
+        [(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")],
+        Json(online.as_ref()),
+    )
+        .into_response())
+}
+
+#[derive(Serialize)]
+struct IdsController {
+    #[serde(flatten)]
+    controller: ApiController,
+    rating: i8,
+    /// Certification name to "training", "solo", or "certified".
+    certifications: HashMap<String, String>,
+}
+
+/// Roster controllers with their certifications, for the IDS.
+///
+/// API clients only.
+async fn get_ids_certifications(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    if let Some(rejection) = require_api_client(&state, &headers) {
+        return Ok(rejection);
+    }
+    let controllers = state.repos.controllers.get_on_roster().await?;
+    let certifications: Vec<Certification> = sqlx::query_as(sql::GET_ALL_CERTIFICATIONS)
+        .fetch_all(&state.db)
+        .await?;
+    let mut by_cid: HashMap<u32, HashMap<String, String>> = HashMap::new();
+    for cert in certifications {
+        by_cid
+            .entry(cert.cid)
+            .or_default()
+            .insert(cert.name, cert.value);
+    }
+    let controllers: Vec<IdsController> = controllers
+        .iter()
+        .map(|controller| IdsController {
+            controller: ApiController::from(controller),
+            rating: controller.rating,
+            certifications: by_cid.remove(&controller.cid).unwrap_or_default(),
+        })
+        .collect();
+    Ok(Json(controllers).into_response())
+}
+
+/// Facility ATIS currently online, with their codes and text, for the IDS.
+///
+/// API clients only.
+async fn get_ids_atis(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    if let Some(rejection) = require_api_client(&state, &headers) {
+        return Ok(rejection);
+    }
+    let online = online_now(&state).await?;
+    Ok(Json(&online.atis).into_response())
+}
+
+#[derive(Serialize)]
+struct IdsOnlinePosition<'a> {
+    #[serde(flatten)]
+    online: &'a OnlineController,
+    operating_initials: Option<String>,
+}
+
+#[derive(Serialize)]
+struct IdsEventAssignment {
+    event_id: u32,
+    event_name: String,
+    position: String,
+    controller: ApiController,
+}
+
+/// Who is working which position right now, for the IDS.
+///
+/// Includes both controllers online and the assignments of events in progress.
+///
+/// API clients only.
+async fn get_ids_positions(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    if let Some(rejection) = require_api_client(&state, &headers) {
+        return Ok(rejection);
+    }
+    let online = online_now(&state).await?;
+    let controllers: HashMap<u32, Controller> = state
+        .repos
+        .controllers
+        .get_all()
+        .await?
+        .into_iter()
+        .map(|c| (c.cid, c))
+        .collect();
+    let online_positions: Vec<IdsOnlinePosition> = online
+        .controllers
+        .iter()
+        .map(|online| IdsOnlinePosition {
+            online,
+            operating_initials: controllers
+                .get(&online.cid)
+                .and_then(|c| c.operating_initials.clone()),
+        })
+        .collect();
+
+    let now = Utc::now();
+    let mut event_assignments = Vec::new();
+    for event in state.repos.events.get_upcoming(now, false).await? {
+        if event.start > now {
+            continue;
+        }
+        for position in state.repos.events.get_positions(event.id).await? {
+            let Some(controller) = position.cid.and_then(|cid| controllers.get(&cid)) else {
+                continue;
+            };
+            event_assignments.push(IdsEventAssignment {
+                event_id: event.id,
+                event_name: event.name.clone(),
+                position: position.name,
+                controller: ApiController::from(controller),
+            });
+        }
+    }
+    Ok(Json(json!({
+        "online": online_positions,
+        "event_assignments": event_assignments,
+    }))
+    .into_response())
+}
+
+/// Bare page of online controllers for streaming software to show over video.
+///
+/// Polls the JSON endpoint, so it stays current without reloading.
+async fn page_online_overlay(State(state): State<Arc<AppState>>) -> Result<Html<String>, AppError> {
+    let template = state.templates.get_template("api/online_overlay")?;
+    let rendered = template.render(context! {})?;
+    Ok(Html(rendered))
+}
+
+/// This file's routes and templates.
+pub fn router(templates: &mut Environment) -> Router<Arc<AppState>> {
+    templates
+        .add_template(
+            "api/online_overlay",
+            include_str!("../../templates/api/online_overlay.jinja"),
+        )
+        .unwrap();
+
+    Router::new()
+        .route("/api/v1/events", get(get_events))
+        .route("/api/v1/events/:id", get(get_event))
+        .route("/api/v1/partners/:facility/events", get(get_partner_events))
+        .route("/api/v1/online", get(get_online))
+        .route("/overlay/online", get(page_online_overlay))
+        .route("/api/v1/ids/certifications", get(get_ids_certifications))
+        .route("/api/v1/ids/atis", get(get_ids_atis))
+        .route("/api/v1/ids/positions", get(get_ids_positions))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::is_facility_atis;
+    use crate::test_utils::{test_app, EVENT_ID, HOME_CONTROLLER, TEST_API_KEY};
+    use axum::http::{header, StatusCode};
+    use serde_json::Value;
+    use vzdv::{config::Config, sql};
+
+    #[test]
+    fn test_is_facility_atis() {
+        let mut config = Config::default();
+        config.stats.position_prefixes = vec!["DEN".to_owned(), "COS".to_owned()];
+        assert!(is_facility_atis(&config, "KDEN_ATIS"));
+        assert!(is_facility_atis(&config, "KDEN_D_ATIS"));
+        assert!(!is_facility_atis(&config, "KDEN_APP"));
+        assert!(!is_facility_atis(&config, "KLAX_ATIS"));
+    }
+
+    #[tokio::test]
+    async fn test_events_api() {
+        let app = test_app().await;
+        sqlx::query(sql::INSERT_EVENT_POSITION)
+            .bind(EVENT_ID)
+            .bind("DEN_GND")
+            .bind("Cab")
+            .execute(&app.db)
+            .await
+            .unwrap();
+        sqlx::query(sql::UPSERT_EVENT_REGISTRATION)
+            .bind(EVENT_ID)
+            .bind(HOME_CONTROLLER)
+            .bind(2)
+            .bind(1)
+            .bind(3)
+            .bind("Any")
+            .execute(&app.db)
+            .await
+            .unwrap();
+
+        // anonymous requests don't see registrations
+        let (status, body) = app.get("/api/v1/events", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let events: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(events[0]["name"], "Fixture FNO");
+        assert_eq!(events[0]["positions"].as_array().unwrap().len(), 3);
+        assert!(events[0].get("registrations").is_none());
+
+        let auth = format!("Bearer {TEST_API_KEY}");
+        let (status, body) = app
+            .get_with_headers(
+                &format!("/api/v1/events/{EVENT_ID}"),
+                &[(header::AUTHORIZATION.as_str(), &auth)],
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        let event: Value = serde_json::from_str(&body).unwrap();
+        let registration = &event["registrations"][0];
+        assert_eq!(registration["controller"]["name"], "Home Controller");
+        assert_eq!(registration["choices"][0], "DEN_TWR");
+        assert_eq!(registration["choices"][1], "DEN_APP");
+
+        let (_, body) = app
+            .get_with_headers(
+                &format!("/api/v1/events/{EVENT_ID}"),
+                &[(header::AUTHORIZATION.as_str(), "Bearer wrong")],
+            )
+            .await;
+        let event: Value = serde_json::from_str(&body).unwrap();
+        assert!(event.get("registrations").is_none());
+        let (status, _) = app.get("/api/v1/events/99", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_partner_events_api() {
+        let app = test_app().await;
+        let (_, body) = app.get("/api/v1/partners/ZLC/events", None).await;
+        let events: Value = serde_json::from_str(&body).unwrap();
+        assert!(events.as_array().unwrap().is_empty());
+
+        sqlx::query(
+            "UPDATE event SET partner_facility='ZLC', external_signup_url='https://zlcartcc.org/events' WHERE id=$1",
+        )
+        .bind(EVENT_ID)
+        .execute(&app.db)
+        .await
+        .unwrap();
+        sqlx::query(sql::UPDATE_EVENT_POSITION_CONTROLLER)
+            .bind(1)
+            .bind(HOME_CONTROLLER)
+            .execute(&app.db)
+            .await
+            .unwrap();
+
+        let (status, body) = app.get("/api/v1/partners/zlc/events", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let events: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(events[0]["name"], "Fixture FNO");
+        assert_eq!(events[0]["partner_facility"], "ZLC");
+        assert_eq!(
+            events[0]["external_signup_url"],
+            "https://zlcartcc.org/events"
+        );
+        let positions = events[0]["positions"].as_array().unwrap();
+        let app_position = positions
+            .iter()
+            .find(|position| position["name"] == "DEN_APP")
+            .unwrap();
+        assert_eq!(app_position["controller"]["cid"], HOME_CONTROLLER);
+
+        let (_, body) = app.get("/api/v1/partners/ZAB/events", None).await;
+        let events: Value = serde_json::from_str(&body).unwrap();
+        assert!(events.as_array().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_ids_certifications() {
+        let app = test_app().await;
+        sqlx::query(sql::CREATE_CERTIFICATION)
+            .bind(HOME_CONTROLLER)
+            .bind("GND")
+            .bind("certified")
+            .bind(chrono::Utc::now())
+            .bind(HOME_CONTROLLER)
+            .execute(&app.db)
+            .await
+            .unwrap();
+
+        let (status, _) = app.get("/api/v1/ids/certifications", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let auth = format!("Bearer {TEST_API_KEY}");
+        let (status, body) = app
+            .get_with_headers(
+                "/api/v1/ids/certifications",
+                &[(header::AUTHORIZATION.as_str(), &auth)],
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        let controllers: Value = serde_json::from_str(&body).unwrap();
+        let home = controllers
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|c| c["cid"] == HOME_CONTROLLER)
+            .unwrap();
+        assert_eq!(home["certifications"]["GND"], "certified");
+    }
+}
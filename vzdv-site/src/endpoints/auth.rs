@@ -1,37 +1,68 @@
 //! HTTP endpoints for logging in and out.
 
-use crate::shared::{AppError, AppState, UserInfo, SESSION_USER_INFO_KEY};
+use crate::{
+    endpoints::user,
+    remember_me,
+    shared::{AppError, AppState, UserInfo, SESSION_USER_INFO_KEY},
+};
 use axum::{
     extract::{Query, State},
-    response::{Html, Redirect},
+    http::{header, HeaderMap},
+    response::{Html, IntoResponse, Redirect, Response},
     routing::get,
     Router,
 };
 use log::{debug, info};
 use minijinja::{context, Environment};
+use serde::Deserialize;
 use std::sync::Arc;
 use tower_sessions::Session;
 use vzdv::{
-    controller_can_see,
-    sql::{self, Controller},
+    sql,
     vatsim::{code_to_tokens, get_user_info, oauth_redirect_start, AuthCallback},
 };
 
+/// Key for whether the user asked to stay signed in, kept in the session during the login flow.
+const SESSION_REMEMBER_ME_KEY: &str = "REMEMBER_ME";
+/// Key for the site path to return to, kept in the session during the login flow.
+const SESSION_LOGIN_NEXT_KEY: &str = "LOGIN_NEXT";
+
+#[derive(Debug, Deserialize)]
+struct LoginQuery {
+    #[serde(default)]
+    remember: bool,
+    next: Option<String>,
+}
+
+/// Whether the path is on this site, so logging in can't send the user elsewhere.
+fn is_local_path(path: &str) -> bool {
+    path.starts_with('/') && !path.starts_with("//") && !path.contains('\\')
+}
+
 /// Login page.
 ///
 /// Doesn't actually have a template to render; the user is immediately redirected to
 /// either the homepage if they're already logged in, or the VATSIM OAuth page to start
 /// their login flow.
+///
+/// With `?remember=true`, the user is issued a "keep me signed in" token once logged in.
+/// With `?next=/some/path`, the user is sent there once logged in.
 async fn page_auth_login(
     State(state): State<Arc<AppState>>,
     session: Session,
+    Query(login_query): Query<LoginQuery>,
 ) -> Result<Redirect, AppError> {
     // if already logged in, just redirect to homepage
+    let next = login_query.next.filter(|next| is_local_path(next));
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
     if let Some(user_info) = user_info {
         debug!("Already logged-in user {} hit login page", user_info.cid);
-        return Ok(Redirect::to("/"));
+        return Ok(Redirect::to(next.as_deref().unwrap_or("/")));
     }
+    session
+        .insert(SESSION_REMEMBER_ME_KEY, login_query.remember)
+        .await?;
+    session.insert(SESSION_LOGIN_NEXT_KEY, next).await?;
     let redirect_url = oauth_redirect_start(&state.config);
     Ok(Redirect::to(&redirect_url))
 }
@@ -44,31 +75,20 @@ async fn page_auth_callback(
     query: Query<AuthCallback>,
     State(state): State<Arc<AppState>>,
     session: Session,
-) -> Result<Html<String>, AppError> {
+) -> Result<Response, AppError> {
     let token_data = code_to_tokens(&query.code, &state.config)
         .await
         .map_err(|err| AppError::GenericFallback("getting auth token from code", err))?;
     let session_user_info = get_user_info(&token_data.access_token, &state.config)
         .await
         .map_err(|err| AppError::GenericFallback("getting auth user info", err))?;
-    let db_user_info: Option<Controller> = sqlx::query_as(sql::GET_CONTROLLER_BY_CID)
-        .bind(&session_user_info.data.cid)
-        .fetch_optional(&state.db)
-        .await?;
-    let roles: Vec<_> = match &db_user_info {
-        Some(controller) => controller.roles.split_terminator(',').collect::<Vec<_>>(),
-        None => Vec::new(),
-    };
-
-    let to_session = UserInfo {
-        cid: session_user_info.data.cid.parse()?,
-        first_name: session_user_info.data.personal.name_first,
-        last_name: session_user_info.data.personal.name_last,
-        is_some_staff: !roles.is_empty(),
-        is_training_staff: controller_can_see(&db_user_info, vzdv::PermissionsGroup::TrainingTeam),
-        is_event_staff: controller_can_see(&db_user_info, vzdv::PermissionsGroup::EventsTeam),
-        is_admin: controller_can_see(&db_user_info, vzdv::PermissionsGroup::Admin),
-    };
+    let to_session = UserInfo::new(
+        &state.db,
+        session_user_info.data.cid.parse()?,
+        session_user_info.data.personal.name_first,
+        session_user_info.data.personal.name_last,
+    )
+    .await?;
     session
         .insert(SESSION_USER_INFO_KEY, to_session.clone())
         .await?;
@@ -82,16 +102,43 @@ async fn page_auth_callback(
         .await?;
 
     info!("Completed log in for {}", session_user_info.data.cid);
+    let remember: bool = session
+        .remove(SESSION_REMEMBER_ME_KEY)
+        .await?
+        .unwrap_or_default();
+    let next: Option<String> = session.remove(SESSION_LOGIN_NEXT_KEY).await?.flatten();
+    let choose_ois = user::needs_operating_initials(&state, to_session.cid)
+        .await?
+        .is_some();
     let template = state.templates.get_template("admin/login_complete")?;
-    let rendered = template.render(context! { user_info => to_session })?;
-    Ok(Html(rendered))
+    let rendered = template.render(context! { user_info => to_session, choose_ois, next })?;
+    if remember {
+        let set_cookie = remember_me::issue(&state.db, to_session.cid).await?;
+        info!("Issued remember-me token for {}", to_session.cid);
+        return Ok(([(header::SET_COOKIE, set_cookie)], Html(rendered)).into_response());
+    }
+    Ok(Html(rendered).into_response())
 }
 
 /// Clear session and redirect to homepage.
-async fn page_auth_logout(session: Session) -> Result<Redirect, AppError> {
+///
+/// Also revokes the browser's "keep me signed in" token, if it has one.
+async fn page_auth_logout(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
     // don't need to check if there's something here
     session.delete().await?;
-    Ok(Redirect::to("/"))
+    if let Some((selector, _)) = remember_me::token_from_headers(&headers) {
+        remember_me::forget(&state.db, &selector).await?;
+        return Ok((
+            [(header::SET_COOKIE, remember_me::removal_cookie())],
+            Redirect::to("/"),
+        )
+            .into_response());
+    }
+    Ok(Redirect::to("/").into_response())
 }
 
 /// This file's routes and templates.
@@ -108,3 +155,16 @@ pub fn router(templates: &mut Environment) -> Router<Arc<AppState>> {
         .route("/auth/logout", get(page_auth_logout))
         .route("/auth/callback", get(page_auth_callback))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::is_local_path;
+
+    #[test]
+    fn test_is_local_path() {
+        assert!(is_local_path("/oauth/authorize?client_id=ids"));
+        assert!(!is_local_path("//evil.example.com"));
+        assert!(!is_local_path("/\\evil.example.com"));
+        assert!(!is_local_path("https://evil.example.com"));
+    }
+}
@@ -1,19 +1,24 @@
 //! HTTP endpoints for controller pages.
 
 use crate::{
+    endpoints::training::{
+        promote_from_waitlist, request_session_feedback, session_ics_event,
+        syllabus_by_certification,
+    },
     flashed_messages::{self, MessageLevel},
     shared::{
-        is_user_member_of, js_timestamp_to_utc, reject_if_not_in, AppError, AppState, UserInfo,
-        SESSION_USER_INFO_KEY,
+        can_impersonate, has_permission, js_timestamp_to_utc, reject_without, AppError, AppState,
+        UserInfo, SESSION_USER_INFO_KEY,
     },
 };
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
+    http::header,
     response::{Html, IntoResponse, Redirect, Response},
     routing::{delete, get, post},
     Form, Router,
 };
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Months, NaiveDate, Utc};
 use itertools::Itertools;
 use log::{error, info, warn};
 use minijinja::{context, Environment};
@@ -26,16 +31,30 @@ use std::{
 };
 use tower_sessions::Session;
 use vzdv::{
-    controller_can_see, get_controller_cids_and_names, retrieve_all_in_use_ois,
-    sql::{self, Certification, Controller, Feedback, StaffNote},
+    certifications, display_name, get_controller_cids_and_names,
+    ics::{write_calendar, IcsEvent},
+    permissions, retrieve_all_in_use_ois,
+    sql::{
+        self, Activity, Certification, CertificationHistory, Controller, EventAssignment, Feedback,
+        SoloCert, StaffNote, TrainingSession,
+    },
     vatusa::{
-        get_multiple_controller_names, get_training_records, save_training_record,
-        NewTrainingRecord, TrainingRecord,
+        self, create_solo_cert, delete_solo_cert, get_multiple_controller_names, get_roster_status,
+        get_solo_certs, get_training_records, save_training_record, NewTrainingRecord,
+        RosterStatus, TrainingRecord,
     },
-    ControllerRating, PermissionsGroup, StaffPosition,
+    ControllerRating, StaffPosition,
 };
 
+/// How long past event assignments and training sessions stay in the calendar feed.
+const CALENDAR_PAST_DAYS: i64 = 30;
+
+/// Roles that are mirrored to the controller's VATUSA facility roles.
+const VATUSA_ROLES: [&str; 2] = ["INS", "MTR"];
+
 /// Roles the current user is able to set.
+///
+/// The TA can only set the training staff roles, and the FE and EC their assistants.
 async fn roles_to_set(
     db: &Pool<Sqlite>,
     user_info: &Option<UserInfo>,
@@ -51,16 +70,18 @@ async fn roles_to_set(
     };
     let mut roles_to_set = Vec::new();
     let user_roles: Vec<_> = match &controller {
-        Some(c) => c.roles.split_terminator('\n').collect(),
+        Some(c) => c.roles.split_terminator(',').collect(),
         None => {
             return Ok(HashSet::new());
         }
     };
-    if user_roles.contains(&"FE") {
-        roles_to_set.push(StaffPosition::AFE);
-    } else if user_roles.contains(&"EC") {
-        roles_to_set.push(StaffPosition::AEC);
-    } else if controller_can_see(&controller, PermissionsGroup::Admin) {
+    if permissions::has_permission(
+        db,
+        controller.as_ref().unwrap().cid,
+        permissions::ROLES_MANAGE,
+    )
+    .await?
+    {
         roles_to_set.push(vzdv::StaffPosition::ATM);
         roles_to_set.push(vzdv::StaffPosition::DATM);
         roles_to_set.push(vzdv::StaffPosition::TA);
@@ -72,6 +93,13 @@ async fn roles_to_set(
         roles_to_set.push(vzdv::StaffPosition::AWM);
         roles_to_set.push(vzdv::StaffPosition::INS);
         roles_to_set.push(vzdv::StaffPosition::MTR);
+    } else if user_roles.contains(&"TA") {
+        roles_to_set.push(StaffPosition::INS);
+        roles_to_set.push(StaffPosition::MTR);
+    } else if user_roles.contains(&"FE") {
+        roles_to_set.push(StaffPosition::AFE);
+    } else if user_roles.contains(&"EC") {
+        roles_to_set.push(StaffPosition::AEC);
     }
 
     Ok(roles_to_set
@@ -80,6 +108,82 @@ async fn roles_to_set(
         .collect::<HashSet<String>>())
 }
 
+/// Number of months shown in the controller page's activity chart.
+const ACTIVITY_CHART_MONTHS: u32 = 12;
+
+#[derive(Serialize)]
+struct ActivityChartMonth {
+    label: String,
+    controlling: u32,
+    event: u32,
+    training: u32,
+    total: u32,
+    /// Total of this month and the two before it, as the activity requirement counts.
+    rolling_quarter: u32,
+    /// Bar heights, as percentages of the busiest month.
+    controlling_percent: f32,
+    event_percent: f32,
+    training_percent: f32,
+}
+
+/// Summarize a controller's activity over the last year, oldest month first.
+async fn activity_chart(db: &Pool<Sqlite>, cid: u32) -> Result<Vec<ActivityChartMonth>, AppError> {
+    let now = Utc::now();
+    // include two extra months so the first months' rolling totals are complete
+    let months: Vec<_> = (0..ACTIVITY_CHART_MONTHS + 2)
+        .rev()
+        .filter_map(|offset| now.checked_sub_months(Months::new(offset)))
+        .collect();
+    let activity: Vec<Activity> = sqlx::query_as(sql::GET_ACTIVITY_FOR_SINCE)
+        .bind(cid)
+        .bind(months[0].format("%Y-%m").to_string())
+        .fetch_all(db)
+        .await?;
+    let minutes_in = |month: &str, source: &str| -> u32 {
+        activity
+            .iter()
+            .filter(|a| a.month == month && a.source == source)
+            .map(|a| a.minutes)
+            .sum()
+    };
+    let totals: Vec<_> = months
+        .iter()
+        .map(|month| {
+            let key = month.format("%Y-%m").to_string();
+            (
+                month.format("%b %y").to_string(),
+                minutes_in(&key, "controlling"),
+                minutes_in(&key, "event"),
+                minutes_in(&key, "training"),
+            )
+        })
+        .collect();
+    let busiest = totals
+        .iter()
+        .map(|(_, c, e, t)| c + e + t)
+        .max()
+        .unwrap_or_default()
+        .max(1) as f32;
+    let chart = totals
+        .windows(3)
+        .map(|window| {
+            let (label, controlling, event, training) = &window[2];
+            ActivityChartMonth {
+                label: label.clone(),
+                controlling: *controlling,
+                event: *event,
+                training: *training,
+                total: controlling + event + training,
+                rolling_quarter: window.iter().map(|(_, c, e, t)| c + e + t).sum(),
+                controlling_percent: *controlling as f32 * 100.0 / busiest,
+                event_percent: *event as f32 * 100.0 / busiest,
+                training_percent: *training as f32 * 100.0 / busiest,
+            }
+        })
+        .collect();
+    Ok(chart)
+}
+
 /// Overview page for a user.
 ///
 /// Shows additional information and controls for different staff
@@ -95,6 +199,20 @@ async fn page_controller(
         value: &'a str,
     }
 
+    #[derive(Serialize)]
+    struct CertHistoryDisplay {
+        #[serde(flatten)]
+        entry: CertificationHistory,
+        by: String,
+    }
+
+    #[derive(Serialize)]
+    struct TrainingRecordDisplay {
+        #[serde(flatten)]
+        record: sql::TrainingRecord,
+        instructor: String,
+    }
+
     #[derive(Serialize)]
     struct StaffNoteDisplay {
         id: u32,
@@ -105,10 +223,7 @@ async fn page_controller(
     }
 
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    let controller: Option<Controller> = sqlx::query_as(sql::GET_CONTROLLER_BY_CID)
-        .bind(cid)
-        .fetch_optional(&state.db)
-        .await?;
+    let controller: Option<Controller> = state.repos.controllers.get_by_cid(cid).await?;
     let controller = match controller {
         Some(c) => c,
         None => {
@@ -125,10 +240,7 @@ async fn page_controller(
         .map_err(|err| AppError::GenericFallback("parsing unknown controller rating", err))?
         .as_str();
 
-    let db_certs: Vec<Certification> = sqlx::query_as(sql::GET_ALL_CERTIFICATIONS_FOR)
-        .bind(cid)
-        .fetch_all(&state.db)
-        .await?;
+    let db_certs: Vec<Certification> = state.repos.certifications.get_for(cid).await?;
     let mut certifications: Vec<CertNameValue> =
         Vec::with_capacity(state.config.training.certifications.len());
     let none = String::from("None");
@@ -142,40 +254,106 @@ async fn page_controller(
     }
     let roles: Vec<_> = controller.roles.split_terminator(',').collect();
 
-    let is_admin = is_user_member_of(&state, &user_info, PermissionsGroup::Admin).await;
-    let feedback: Vec<Feedback> = if is_admin {
-        sqlx::query_as(sql::GET_ALL_FEEDBACK_FOR)
-            .bind(cid)
-            .fetch_all(&state.db)
-            .await?
-    } else {
-        Vec::new()
+    let feedback: Vec<Feedback> =
+        if has_permission(&state, &user_info, permissions::FEEDBACK_MANAGE).await {
+            state.repos.feedback.get_for(cid).await?
+        } else {
+            Vec::new()
+        };
+    let staff_notes: Vec<StaffNoteDisplay> =
+        if has_permission(&state, &user_info, permissions::ROSTER_MANAGE).await {
+            let notes: Vec<StaffNote> = sqlx::query_as(sql::GET_STAFF_NOTES_FOR)
+                .bind(cid)
+                .fetch_all(&state.db)
+                .await?;
+            let controllers = get_controller_cids_and_names(&state.db)
+                .await
+                .map_err(|e| AppError::GenericFallback("getting names and CIDs from DB", e))?;
+            notes
+                .iter()
+                .map(|note| StaffNoteDisplay {
+                    id: note.id,
+                    by: controllers
+                        .iter()
+                        .find(|c| *c.0 == note.by)
+                        .map(|c| format!("{} {} ({})", c.1 .0, c.1 .1, c.0))
+                        .unwrap_or_else(|| format!("{}?", note.cid)),
+                    by_cid: note.by,
+                    date: note.date,
+                    comment: note.comment.clone(),
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+    let (solo_certs, syllabus, cert_history) =
+        if has_permission(&state, &user_info, permissions::TRAINING_MANAGE).await {
+            let solo_certs: Vec<SoloCert> = sqlx::query_as(sql::GET_ALL_SOLO_CERTS_FOR)
+                .bind(cid)
+                .fetch_all(&state.db)
+                .await?;
+            let history: Vec<CertificationHistory> =
+                sqlx::query_as(sql::GET_CERTIFICATION_HISTORY_FOR)
+                    .bind(cid)
+                    .fetch_all(&state.db)
+                    .await?;
+            let names = get_controller_cids_and_names(&state.db)
+                .await
+                .map_err(|e| AppError::GenericFallback("getting names and CIDs from DB", e))?;
+            let cert_history: Vec<CertHistoryDisplay> = history
+                .into_iter()
+                .map(|entry| CertHistoryDisplay {
+                    by: display_name(&names, entry.changed_by),
+                    entry,
+                })
+                .collect();
+            (
+                solo_certs,
+                syllabus_by_certification(&state).await?,
+                cert_history,
+            )
+        } else {
+            (Vec::new(), Vec::new(), Vec::new())
+        };
+    // feedback that staff have shared publicly, for the controller's own page
+    let own_feedback: Vec<Feedback> = match &user_info {
+        Some(info) if info.cid == cid => {
+            sqlx::query_as(sql::GET_SHARED_FEEDBACK_FOR)
+                .bind(cid)
+                .fetch_all(&state.db)
+                .await?
+        }
+        _ => Vec::new(),
     };
-    let staff_notes: Vec<StaffNoteDisplay> = if is_admin {
-        let notes: Vec<StaffNote> = sqlx::query_as(sql::GET_STAFF_NOTES_FOR)
-            .bind(cid)
-            .fetch_all(&state.db)
-            .await?;
-        let controllers = get_controller_cids_and_names(&state.db)
-            .await
-            .map_err(|e| AppError::GenericFallback("getting names and CIDs from DB", e))?;
-        notes
-            .iter()
-            .map(|note| StaffNoteDisplay {
-                id: note.id,
-                by: controllers
-                    .iter()
-                    .find(|c| *c.0 == note.by)
-                    .map(|c| format!("{} {} ({})", c.1 .0, c.1 .1, c.0))
-                    .unwrap_or_else(|| format!("{}?", note.cid)),
-                by_cid: note.by,
-                date: note.date,
-                comment: note.comment.clone(),
-            })
-            .collect()
-    } else {
-        Vec::new()
+    // the controller's own training records, from the local copy
+    let own_training_records: Vec<TrainingRecordDisplay> = match &user_info {
+        Some(info) if info.cid == cid => {
+            let records: Vec<sql::TrainingRecord> = sqlx::query_as(sql::GET_TRAINING_RECORDS_FOR)
+                .bind(cid)
+                .fetch_all(&state.db)
+                .await?;
+            let names = get_controller_cids_and_names(&state.db)
+                .await
+                .map_err(|e| AppError::GenericFallback("getting names and CIDs from DB", e))?;
+            records
+                .into_iter()
+                .map(|record| TrainingRecordDisplay {
+                    instructor: display_name(&names, record.instructor_cid),
+                    record,
+                })
+                .collect()
+        }
+        _ => Vec::new(),
     };
+    // activity is visible to staff and to the controller themselves
+    let activity: Vec<ActivityChartMonth> = match &user_info {
+        Some(info) if info.is_some_staff || info.cid == cid => {
+            activity_chart(&state.db, cid).await?
+        }
+        _ => Vec::new(),
+    };
+    let can_view_as = user_info.as_ref().is_some_and(|info| info.cid != cid)
+        && can_impersonate(&state, &user_info).await;
     let settable_roles_set = roles_to_set(&state.db, &user_info).await?;
     let mut settable_roles: Vec<_> = settable_roles_set.iter().collect();
     settable_roles.sort();
@@ -191,6 +369,14 @@ async fn page_controller(
         settable_roles,
         feedback,
         staff_notes,
+        solo_certs,
+        syllabus,
+        cert_history,
+        activity,
+        own_feedback,
+        own_training_records,
+        is_own_page => user_info.as_ref().is_some_and(|info| info.cid == cid),
+        can_view_as,
         flashed_messages
     })?;
     Ok(Html(rendered).into_response())
@@ -205,7 +391,7 @@ async fn api_unlink_discord(
     Path(cid): Path<u32>,
 ) -> Result<Redirect, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if let Some(redirect) = reject_if_not_in(&state, &user_info, PermissionsGroup::Admin).await {
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::ROSTER_MANAGE).await {
         return Ok(redirect);
     }
     sqlx::query(sql::UNSET_CONTROLLER_DISCORD_ID)
@@ -235,7 +421,7 @@ async fn post_change_ois(
     Form(initials_form): Form<ChangeInitialsForm>,
 ) -> Result<Redirect, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if let Some(redirect) = reject_if_not_in(&state, &user_info, PermissionsGroup::Admin).await {
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::ROSTER_MANAGE).await {
         return Ok(redirect);
     }
     let initials = initials_form.initials.to_uppercase();
@@ -279,8 +465,8 @@ async fn post_change_ois(
 /// Form submission to set the controller's certifications.
 ///
 /// Not used to set their network rating; that process is handled
-/// through VATUSA/VATSIM. Also does not handle communicating solo
-/// certs to any other site.
+/// through VATUSA/VATSIM. Solo certs that need to be reported to
+/// VATUSA are issued separately through `post_new_solo_cert`.
 ///
 /// For training staff members.
 async fn post_change_certs(
@@ -290,44 +476,23 @@ async fn post_change_certs(
     Form(certs_form): Form<HashMap<String, String>>,
 ) -> Result<Redirect, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if let Some(redirect) =
-        reject_if_not_in(&state, &user_info, PermissionsGroup::TrainingTeam).await
-    {
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::TRAINING_MANAGE).await {
         return Ok(redirect);
     }
 
     let by_cid = user_info.unwrap().cid;
-    let db_certs: Vec<Certification> = sqlx::query_as(sql::GET_ALL_CERTIFICATIONS_FOR)
-        .bind(cid)
-        .fetch_all(&state.db)
-        .await?;
     for (key, value) in &certs_form {
-        let existing = db_certs.iter().find(|c| &c.name == key);
-        match existing {
-            Some(existing) => {
-                sqlx::query(sql::UPDATE_CERTIFICATION)
-                    .bind(existing.id)
-                    .bind(value)
-                    .bind(Utc::now())
-                    .bind(by_cid)
-                    .execute(&state.db)
-                    .await?;
-                info!("{by_cid} updated cert for {cid} of {key} -> {value}");
-            }
-            None => {
-                sqlx::query(sql::CREATE_CERTIFICATION)
-                    .bind(cid)
-                    .bind(key)
-                    .bind(value)
-                    .bind(Utc::now())
-                    .bind(by_cid)
-                    .execute(&state.db)
-                    .await?;
-                info!("{by_cid} created new cert for {cid} of {key} -> {value}");
-            }
+        if certifications::set_certification(&state.db, cid, key, value, by_cid, Utc::now()).await?
+        {
+            info!("{by_cid} set cert for {cid} of {key} -> {value}");
         }
     }
 
+    // students leaving training may open slots for those waiting
+    for certification in certs_form.keys() {
+        promote_from_waitlist(&state, certification, by_cid).await?;
+    }
+
     flashed_messages::push_flashed_message(session, MessageLevel::Info, "Updated certifications")
         .await?;
     Ok(Redirect::to(&format!("/controller/{cid}")))
@@ -348,8 +513,7 @@ async fn post_new_staff_note(
     Form(note_form): Form<NewNoteForm>,
 ) -> Result<Redirect, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if let Some(redirect) = reject_if_not_in(&state, &user_info, PermissionsGroup::SomeStaff).await
-    {
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::STAFF).await {
         return Ok(redirect);
     }
     let user_info = user_info.unwrap();
@@ -374,7 +538,7 @@ async fn api_delete_staff_note(
     Path((_cid, note_id)): Path<(u32, u32)>,
 ) -> Result<StatusCode, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if reject_if_not_in(&state, &user_info, PermissionsGroup::SomeStaff)
+    if reject_without(&state, &user_info, permissions::STAFF)
         .await
         .is_some()
     {
@@ -408,14 +572,11 @@ async fn snippet_get_training_records(
     use voca_rs::Voca;
 
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if let Some(redirect) =
-        reject_if_not_in(&state, &user_info, PermissionsGroup::TrainingTeam).await
-    {
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::TRAINING_MANAGE).await {
         return Ok(redirect.into_response());
     }
-    let all_training_records = get_training_records(&state.config.vatsim.vatusa_api_key, cid)
-        .await
-        .map_err(|e| AppError::GenericFallback("getting VATUSA training records", e))?;
+    let all_training_records =
+        get_training_records(&state.config.vatsim.vatusa_api_key, cid).await?;
     let training_records: Vec<_> = all_training_records
         .iter()
         .filter(|record| record.facility_id == "ZDV")
@@ -449,6 +610,8 @@ struct NewTrainingRecordForm {
     location: u8,
     notes: String,
     timezone: String,
+    /// ID of the syllabus lesson covered, if any.
+    lesson: Option<String>,
 }
 
 /// Submit a new training note for the controller.
@@ -461,13 +624,16 @@ async fn post_add_training_note(
     Form(record_form): Form<NewTrainingRecordForm>,
 ) -> Result<Redirect, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if let Some(redirect) =
-        reject_if_not_in(&state, &user_info, PermissionsGroup::TrainingTeam).await
-    {
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::TRAINING_MANAGE).await {
         return Ok(redirect);
     }
     let user_info = user_info.unwrap();
     let date = js_timestamp_to_utc(&record_form.date, &record_form.timezone)?;
+    let lesson_id: Option<u32> = record_form
+        .lesson
+        .as_deref()
+        .and_then(|lesson| lesson.parse().ok());
+    let position = record_form.position.clone();
     let new_record = NewTrainingRecord {
         instructor_id: format!("{}", user_info.cid),
         date,
@@ -485,6 +651,16 @@ async fn post_add_training_note(
             )
             .await?;
             info!("{} submitted new training record for {cid}", user_info.cid);
+            if let Some(lesson_id) = lesson_id {
+                sqlx::query(sql::INSERT_TRAINING_LESSON_SESSION)
+                    .bind(lesson_id)
+                    .bind(cid)
+                    .bind(user_info.cid)
+                    .bind(date.and_utc())
+                    .execute(&state.db)
+                    .await?;
+            }
+            request_session_feedback(&state, cid, user_info.cid, &position, date.and_utc()).await?;
         }
         Err(e) => {
             error!("Error saving new training record for {cid}: {e}");
@@ -500,26 +676,316 @@ async fn post_add_training_note(
     Ok(Redirect::to(&format!("/controller/{cid}")))
 }
 
-/// Submit a form to change the controller's roles.
+#[derive(Debug, Deserialize)]
+struct RosterActionForm {
+    action: String,
+    reason: Option<String>,
+    facility: Option<String>,
+}
+
+/// Take a roster action on the controller, updating both VATUSA and the local record.
+///
+/// - "remove" removes them from the facility's VATUSA roster with the given reason
+/// - "transfer" does the same, recording that they transferred to another facility
+/// - "readd" puts a returning member back on the roster, adding them as a visitor
+///   on VATUSA if they aren't already home or visiting
+///
+/// Whether they're a home or visiting controller is determined by asking
+/// VATUSA. The local record is updated right away rather than waiting for
+/// the next roster sync.
 ///
 /// For admin staff members.
-async fn post_set_roles(
+async fn post_roster_action(
     State(state): State<Arc<AppState>>,
     session: Session,
     Path(cid): Path<u32>,
-    Form(roles_form): Form<HashMap<String, String>>,
+    Form(roster_form): Form<RosterActionForm>,
 ) -> Result<Redirect, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if let Some(redirect) = reject_if_not_in(&state, &user_info, PermissionsGroup::SomeStaff).await
-    {
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::ROSTER_MANAGE).await {
+        return Ok(redirect);
+    }
+    let user_info = user_info.unwrap();
+    let redirect = Redirect::to(&format!("/controller/{cid}"));
+    let api_key = &state.config.vatsim.vatusa_api_key;
+
+    if roster_form.action == "readd" {
+        let info = vatusa::get_controller_info(cid, Some(api_key)).await?;
+        let status = RosterStatus::of(&info);
+        if status == RosterStatus::NotOnRoster {
+            if let Err(e) = vatusa::add_visiting_controller(cid, api_key).await {
+                error!("Error re-adding {cid} to the VATUSA roster as a visitor: {e}");
+                flashed_messages::push_flashed_message(
+                    session,
+                    MessageLevel::Error,
+                    "Could not add the controller to the VATUSA roster",
+                )
+                .await?;
+                return Ok(redirect);
+            }
+        }
+        sqlx::query(sql::UPDATE_RETURNED_TO_ROSTER)
+            .bind(cid)
+            .bind(&info.facility)
+            .bind(Utc::now())
+            .execute(&state.db)
+            .await?;
+        info!(
+            "{} re-added {cid} to the roster (was {status:?} on VATUSA)",
+            user_info.cid
+        );
+        flashed_messages::push_flashed_message(
+            session,
+            MessageLevel::Info,
+            "Controller re-added to the roster",
+        )
+        .await?;
+        return Ok(redirect);
+    }
+
+    let (reason, controller_status) = match roster_form.action.as_str() {
+        "remove" => (
+            roster_form.reason.unwrap_or_default().trim().to_owned(),
+            "Removed",
+        ),
+        "transfer" => {
+            let facility = roster_form.facility.unwrap_or_default();
+            let facility = facility.trim().to_uppercase();
+            if facility.is_empty() {
+                flashed_messages::push_flashed_message(
+                    session,
+                    MessageLevel::Error,
+                    "The facility they transferred to is required",
+                )
+                .await?;
+                return Ok(redirect);
+            }
+            (format!("Transferred to {facility}"), "Transferred")
+        }
+        _ => {
+            flashed_messages::push_flashed_message(session, MessageLevel::Error, "Unknown action")
+                .await?;
+            return Ok(redirect);
+        }
+    };
+    if reason.is_empty() {
+        flashed_messages::push_flashed_message(
+            session,
+            MessageLevel::Error,
+            "A reason is required to remove a controller",
+        )
+        .await?;
+        return Ok(redirect);
+    }
+    let status = get_roster_status(cid, api_key).await?;
+    let result = match status {
+        RosterStatus::Home => vatusa::remove_home_controller(cid, &reason, api_key).await,
+        RosterStatus::Visiting => vatusa::remove_visiting_controller(cid, &reason, api_key).await,
+        RosterStatus::NotOnRoster => {
+            flashed_messages::push_flashed_message(
+                session,
+                MessageLevel::Error,
+                "Controller is not on the VATUSA roster",
+            )
+            .await?;
+            return Ok(redirect);
+        }
+    };
+    if let Err(e) = result {
+        error!("Error removing {cid} from the VATUSA roster: {e}");
+        flashed_messages::push_flashed_message(
+            session,
+            MessageLevel::Error,
+            "Could not remove the controller from the VATUSA roster",
+        )
+        .await?;
+        return Ok(redirect);
+    }
+    sqlx::query(sql::UPDATE_REMOVED_FROM_ROSTER)
+        .bind(cid)
+        .execute(&state.db)
+        .await?;
+    sqlx::query(sql::UPDATE_CONTROLLER_STATUS)
+        .bind(cid)
+        .bind(controller_status)
+        .execute(&state.db)
+        .await?;
+    sqlx::query(sql::QUEUE_EXIT_SURVEY)
+        .bind(cid)
+        .bind(format!("{controller_status}: {reason}"))
+        .bind(Utc::now())
+        .execute(&state.db)
+        .await?;
+    info!(
+        "{} removed {cid} from the roster ({status:?}): {reason}",
+        user_info.cid
+    );
+    flashed_messages::push_flashed_message(
+        session,
+        MessageLevel::Info,
+        "Controller removed from the roster",
+    )
+    .await?;
+    Ok(redirect)
+}
+
+/// Whether VATUSA currently lists a solo cert for the controller on the position.
+///
+/// Used to keep the local solo certs in line with VATUSA's when issuing and removing.
+async fn on_vatusa(api_key: &str, cid: u32, position: &str) -> Result<bool, vatusa::VatusaError> {
+    Ok(get_solo_certs(api_key)
+        .await?
+        .iter()
+        .any(|cert| cert.cid == cid && cert.position.eq_ignore_ascii_case(position)))
+}
+
+#[derive(Debug, Deserialize)]
+struct NewSoloCertForm {
+    position: String,
+    expiration: String,
+}
+
+/// Submit a new solo cert for the controller.
+///
+/// The cert is sent to VATUSA first (unless VATUSA already lists it), and only
+/// stored locally if that succeeds.
+///
+/// For training staff members.
+async fn post_new_solo_cert(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path(cid): Path<u32>,
+    Form(solo_form): Form<NewSoloCertForm>,
+) -> Result<Redirect, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::TRAINING_MANAGE).await {
         return Ok(redirect);
     }
-    let roles_can_set = roles_to_set(&state.db, &user_info).await?;
     let user_info = user_info.unwrap();
-    let controller: Option<Controller> = sqlx::query_as(sql::GET_CONTROLLER_BY_CID)
+    let position = solo_form.position.trim().to_uppercase();
+    let expiration = NaiveDate::parse_from_str(&solo_form.expiration, "%Y-%m-%d")?;
+    if position.is_empty() || expiration <= Utc::now().date_naive() {
+        flashed_messages::push_flashed_message(
+            session,
+            MessageLevel::Error,
+            "Solo certs need a position and a future expiration date",
+        )
+        .await?;
+        return Ok(Redirect::to(&format!("/controller/{cid}")));
+    }
+
+    let submitted = match on_vatusa(&state.config.vatsim.vatusa_api_key, cid, &position).await {
+        Ok(true) => {
+            info!("Solo cert for {cid} on {position} is already on VATUSA; storing it locally");
+            Ok(())
+        }
+        Ok(false) => {
+            create_solo_cert(
+                &state.config.vatsim.vatusa_api_key,
+                cid,
+                &position,
+                expiration,
+            )
+            .await
+        }
+        Err(e) => Err(e),
+    };
+    if let Err(e) = submitted {
+        error!("Error submitting solo cert for {cid} on {position} to VATUSA: {e}");
+        flashed_messages::push_flashed_message(
+            session,
+            MessageLevel::Error,
+            "Could not submit the solo cert to VATUSA",
+        )
+        .await?;
+        return Ok(Redirect::to(&format!("/controller/{cid}")));
+    }
+    sqlx::query(sql::CREATE_SOLO_CERT)
         .bind(cid)
+        .bind(user_info.cid)
+        .bind(&position)
+        .bind(true)
+        .bind(Utc::now())
+        .bind(
+            expiration
+                .and_hms_opt(23, 59, 59)
+                .ok_or(AppError::ChronoOther("building solo cert expiration"))?
+                .and_utc(),
+        )
+        .execute(&state.db)
+        .await?;
+    info!(
+        "{} issued solo cert for {cid} on {position} until {expiration}",
+        user_info.cid
+    );
+    flashed_messages::push_flashed_message(session, MessageLevel::Info, "Solo cert issued").await?;
+    Ok(Redirect::to(&format!("/controller/{cid}")))
+}
+
+/// Remove a solo cert from the controller, both locally and on VATUSA.
+///
+/// VATUSA is only asked to remove the cert if it still lists it.
+///
+/// For training staff members.
+async fn api_delete_solo_cert(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path((cid, solo_cert_id)): Path<(u32, u32)>,
+) -> Result<StatusCode, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if !has_permission(&state, &user_info, permissions::TRAINING_MANAGE).await {
+        return Ok(StatusCode::FORBIDDEN);
+    }
+    let user_info = user_info.unwrap();
+    let solo_cert: Option<SoloCert> = sqlx::query_as(sql::GET_SOLO_CERT_BY_ID)
+        .bind(solo_cert_id)
         .fetch_optional(&state.db)
         .await?;
+    let solo_cert = match solo_cert {
+        Some(sc) if sc.cid == cid => sc,
+        _ => return Ok(StatusCode::NOT_FOUND),
+    };
+    if on_vatusa(
+        &state.config.vatsim.vatusa_api_key,
+        cid,
+        &solo_cert.position,
+    )
+    .await?
+    {
+        delete_solo_cert(
+            &state.config.vatsim.vatusa_api_key,
+            cid,
+            &solo_cert.position,
+        )
+        .await?;
+    }
+    sqlx::query(sql::DELETE_SOLO_CERT)
+        .bind(solo_cert_id)
+        .execute(&state.db)
+        .await?;
+    info!(
+        "{} removed solo cert for {cid} on {}",
+        user_info.cid, solo_cert.position
+    );
+    Ok(StatusCode::OK)
+}
+
+/// Submit a form to change the controller's roles.
+///
+/// For staff members; which roles they can change comes from `roles_to_set`.
+async fn post_set_roles(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path(cid): Path<u32>,
+    Form(roles_form): Form<HashMap<String, String>>,
+) -> Result<Redirect, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::STAFF).await {
+        return Ok(redirect);
+    }
+    let roles_can_set = roles_to_set(&state.db, &user_info).await?;
+    let user_info = user_info.unwrap();
+    let controller: Option<Controller> = state.repos.controllers.get_by_cid(cid).await?;
     let controller = match controller {
         Some(c) => c,
         None => {
@@ -568,6 +1034,39 @@ async fn post_set_roles(
         .iter()
         .join(",");
 
+    // training staff roles are also held on VATUSA, so keep them in step
+    let old_roles: HashSet<&str> = controller.roles.split_terminator(',').collect();
+    let new_role_set: HashSet<&str> = new_roles.split_terminator(',').collect();
+    let api_key = &state.config.vatsim.vatusa_api_key;
+    for role in VATUSA_ROLES {
+        let result = match (old_roles.contains(role), new_role_set.contains(role)) {
+            (false, true) => vatusa::add_facility_role(cid, role, api_key).await,
+            (true, false) => vatusa::remove_facility_role(cid, role, api_key).await,
+            _ => continue,
+        };
+        if let Err(e) = result {
+            error!("Error updating {role} role for {cid} on VATUSA: {e}");
+            flashed_messages::push_flashed_message(
+                session,
+                MessageLevel::Error,
+                &format!(
+                    "Could not update the {role} role on VATUSA; the site's roles were not changed"
+                ),
+            )
+            .await?;
+            return Ok(Redirect::to(&format!("/controller/{cid}")));
+        }
+        info!(
+            "{} {} the {role} role for {cid} on VATUSA",
+            user_info.cid,
+            if new_role_set.contains(role) {
+                "added"
+            } else {
+                "removed"
+            }
+        );
+    }
+
     info!(
         "{} is setting roles for {cid} to '{}'; was '{}'",
         user_info.cid, new_roles, controller.roles
@@ -582,6 +1081,81 @@ async fn post_set_roles(
     Ok(Redirect::to(&format!("/controller/{cid}")))
 }
 
+#[derive(Debug, Deserialize)]
+struct CalendarQuery {
+    token: Option<String>,
+}
+
+/// The controller's event position assignments and training sessions, as a calendar feed.
+///
+/// Calendar apps can't log in, so the feed is found with the secret token
+/// from the user's notifications page. Logged-in users can open it directly.
+async fn get_my_calendar(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Query(query): Query<CalendarQuery>,
+) -> Result<Response, AppError> {
+    let cid: Option<u32> = match query.token {
+        Some(token) => {
+            sqlx::query_scalar(sql::GET_CALENDAR_TOKEN_CID)
+                .bind(token)
+                .fetch_optional(&state.db)
+                .await?
+        }
+        None => {
+            let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+            user_info.map(|info| info.cid)
+        }
+    };
+    let Some(cid) = cid else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let now = Utc::now();
+    let assignments: Vec<EventAssignment> = sqlx::query_as(sql::GET_UPCOMING_EVENT_ASSIGNMENTS_FOR)
+        .bind(cid)
+        .bind(now - Duration::days(CALENDAR_PAST_DAYS))
+        .fetch_all(&state.db)
+        .await?;
+    let site = state.config.hosted_domain.trim_end_matches('/');
+    let mut events: Vec<IcsEvent> = assignments
+        .into_iter()
+        .map(|assignment| IcsEvent {
+            uid: format!("event-{}-{cid}@vzdv", assignment.event_id),
+            start: assignment.start,
+            end: assignment.end,
+            summary: format!("{}: {}", assignment.event_name, assignment.position),
+            description: Some(format!(
+                "You're assigned to {} for {}.",
+                assignment.position, assignment.event_name
+            )),
+            url: Some(format!("{site}/events/{}", assignment.event_id)),
+            sequence: 0,
+            cancelled: false,
+        })
+        .collect();
+    let training_sessions: Vec<TrainingSession> =
+        sqlx::query_as(sql::GET_UPCOMING_TRAINING_SESSIONS_FOR)
+            .bind(cid)
+            .bind(now - Duration::days(CALENDAR_PAST_DAYS))
+            .fetch_all(&state.db)
+            .await?;
+    if !training_sessions.is_empty() {
+        let names = get_controller_cids_and_names(&state.db)
+            .await
+            .map_err(|e| AppError::GenericFallback("getting names and CIDs from DB", e))?;
+        events.extend(
+            training_sessions
+                .iter()
+                .map(|session| session_ics_event(session, &names, site)),
+        );
+    }
+    Ok((
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        write_calendar("ZDV assignments", &events, now),
+    )
+        .into_response())
+}
+
 pub fn router(templates: &mut Environment) -> Router<Arc<AppState>> {
     templates
         .add_template(
@@ -595,14 +1169,9 @@ pub fn router(templates: &mut Environment) -> Router<Arc<AppState>> {
             include_str!("../../templates/controller/training_notes.jinja"),
         )
         .unwrap();
-    templates.add_function(
-        "includes",
-        |roles: Vec<String>, role: String| -> Result<bool, minijinja::Error> {
-            Ok(roles.contains(&role))
-        },
-    );
 
     Router::new()
+        .route("/controller/me/calendar.ics", get(get_my_calendar))
         .route("/controller/:cid", get(page_controller))
         .route("/controller/:cid/discord/unlink", post(api_unlink_discord))
         .route("/controller/:cid/ois", post(post_change_ois))
@@ -617,4 +1186,177 @@ pub fn router(templates: &mut Environment) -> Router<Arc<AppState>> {
             get(snippet_get_training_records).post(post_add_training_note),
         )
         .route("/controller/:cid/roles", post(post_set_roles))
+        .route("/controller/:cid/roster", post(post_roster_action))
+        .route("/controller/:cid/solo_cert", post(post_new_solo_cert))
+        .route(
+            "/controller/:cid/solo_cert/:solo_cert_id",
+            delete(api_delete_solo_cert),
+        )
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::roles_to_set;
+    use crate::{
+        shared::UserInfo,
+        test_utils::{test_app, ADMIN_CONTROLLER, HOME_CONTROLLER},
+    };
+    use axum::http::StatusCode;
+    use chrono::Utc;
+    use std::collections::HashSet;
+    use vzdv::sql;
+
+    #[tokio::test]
+    async fn test_ta_role_delegation() {
+        let app = test_app().await;
+        sqlx::query(sql::SET_CONTROLLER_ROLES)
+            .bind(HOME_CONTROLLER)
+            .bind("TA")
+            .execute(&app.db)
+            .await
+            .unwrap();
+        let ta = UserInfo::new(&app.db, HOME_CONTROLLER, String::new(), String::new())
+            .await
+            .unwrap();
+        let can_set = roles_to_set(&app.db, &Some(ta)).await.unwrap();
+        assert_eq!(can_set, HashSet::from(["INS".to_owned(), "MTR".to_owned()]));
+
+        // the TA can't grant or revoke anything else
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, _) = app
+            .post_form(
+                &format!("/controller/{ADMIN_CONTROLLER}/roles"),
+                &[("WM", "on")],
+                Some(&cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let (roles,): (String,) = sqlx::query_as("SELECT roles FROM controller WHERE cid=$1")
+            .bind(ADMIN_CONTROLLER)
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(roles, "ATM");
+    }
+
+    #[tokio::test]
+    async fn test_own_feedback() {
+        let app = test_app().await;
+        for comments in ["Shared comment", "Pending comment"] {
+            sqlx::query(sql::INSERT_FEEDBACK)
+                .bind(HOME_CONTROLLER)
+                .bind("DEN_APP")
+                .bind("excellent")
+                .bind(comments)
+                .bind(Utc::now())
+                .bind(1_234_567)
+                .execute(&app.db)
+                .await
+                .unwrap();
+        }
+        sqlx::query(sql::UPDATE_FEEDBACK_TAKE_ACTION)
+            .bind(ADMIN_CONTROLLER)
+            .bind("post")
+            .bind(true)
+            .bind(1)
+            .execute(&app.db)
+            .await
+            .unwrap();
+        let uri = format!("/controller/{HOME_CONTROLLER}");
+
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, body) = app.get(&uri, Some(&cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("My Feedback"));
+        assert!(body.contains("Shared comment"));
+        assert!(!body.contains("Pending comment"));
+        assert!(!body.contains("1234567"));
+
+        let cookie = app.login_as(ADMIN_CONTROLLER, false).await;
+        let (_, body) = app.get(&uri, Some(&cookie)).await;
+        assert!(!body.contains("Shared comment"));
+    }
+
+    #[tokio::test]
+    async fn test_own_training_records() {
+        let app = test_app().await;
+        sqlx::query(
+            "INSERT INTO training_record (id, cid, instructor_cid, position, date, notes) VALUES (NULL, $1, $2, 'DEN_GND', $3, 'Good phraseology')",
+        )
+        .bind(HOME_CONTROLLER)
+        .bind(ADMIN_CONTROLLER)
+        .bind(Utc::now())
+        .execute(&app.db)
+        .await
+        .unwrap();
+        let uri = format!("/controller/{HOME_CONTROLLER}");
+
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (_, body) = app.get(&uri, Some(&cookie)).await;
+        assert!(body.contains("DEN_GND with Admin Controller"));
+        assert!(body.contains("Good phraseology"));
+
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let (_, body) = app.get(&uri, Some(&cookie)).await;
+        assert!(!body.contains("Good phraseology"));
+    }
+
+    #[tokio::test]
+    async fn test_activity_chart() {
+        let app = test_app().await;
+        let month = Utc::now().format("%Y-%m").to_string();
+        for (minutes, source) in [(90, "controlling"), (30, "event")] {
+            sqlx::query(
+                "INSERT INTO activity (id, cid, month, minutes, source) VALUES (NULL, $1, $2, $3, $4)",
+            )
+            .bind(HOME_CONTROLLER)
+            .bind(&month)
+            .bind(minutes)
+            .bind(source)
+            .execute(&app.db)
+            .await
+            .unwrap();
+        }
+        let uri = format!("/controller/{HOME_CONTROLLER}");
+
+        // the controller themselves
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, body) = app.get(&uri, Some(&cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("2h0m (controlling 1h30m, events 0h30m, training 0m)"));
+
+        // other controllers
+        let cookie = app.login_as(ADMIN_CONTROLLER, false).await;
+        let (_, body) = app.get(&uri, Some(&cookie)).await;
+        assert!(!body.contains("over 3 months"));
+    }
+
+    #[tokio::test]
+    async fn test_cert_history() {
+        let app = test_app().await;
+        let uri = format!("/controller/{HOME_CONTROLLER}/certs");
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        for value in ["training", "training", "solo"] {
+            let (status, _) = app.post_form(&uri, &[("GND", value)], Some(&cookie)).await;
+            assert_eq!(status, StatusCode::SEE_OTHER);
+        }
+        let history: Vec<sql::CertificationHistory> =
+            sqlx::query_as(sql::GET_CERTIFICATION_HISTORY_FOR)
+                .bind(HOME_CONTROLLER)
+                .fetch_all(&app.db)
+                .await
+                .unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].old_value.as_deref(), Some("training"));
+        assert_eq!(history[0].changed_by, ADMIN_CONTROLLER);
+
+        let uri = format!("/controller/{HOME_CONTROLLER}");
+        let (_, body) = app.get(&uri, Some(&cookie)).await;
+        assert!(body.contains("Certification history"));
+        assert!(body.contains("Training <i class=\"bi bi-arrow-right\"></i>"));
+
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (_, body) = app.get(&uri, Some(&cookie)).await;
+        assert!(!body.contains("Certification history"));
+    }
 }
@@ -3,46 +3,45 @@
 //! The CRUD of events themselves is under /admin routes.
 
 use crate::{
-    flashed_messages,
+    discord, email, flashed_messages, post_image,
     shared::{
-        is_user_member_of, js_timestamp_to_utc, reject_if_not_in, AppError, AppState, UserInfo,
-        SESSION_USER_INFO_KEY,
+        has_permission, js_timestamp_to_utc, reject_without, snippet_response, AppError, AppState,
+        UserInfo, SESSION_USER_INFO_KEY,
     },
 };
 use axum::{
     extract::{Path, State},
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{Html, IntoResponse, Redirect, Response},
     routing::{get, post},
     Form, Router,
 };
 use axum_extra::extract::WithRejection;
 use chrono::Utc;
-use log::info;
+use itertools::Itertools;
+use log::{info, warn};
 use minijinja::{context, Environment};
 use serde::{Deserialize, Serialize};
 use sqlx::{Pool, Sqlite};
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
 use tower_sessions::Session;
 use vzdv::{
-    sql::{self, Controller, Event, EventPosition, EventRegistration},
-    ControllerRating, PermissionsGroup,
+    cert_positions, event_posts, permissions,
+    sql::{
+        self, Controller, Event, EventCategoryLimit, EventChannel, EventPosition,
+        EventRegistration, EventWaitlistEntry,
+    },
+    ControllerRating,
 };
 
-/// Get a list of upcoming events optionally with unpublished events.
-async fn query_for_events(db: &Pool<Sqlite>, show_all: bool) -> sqlx::Result<Vec<Event>> {
-    if show_all {
-        sqlx::query_as(sql::GET_ALL_UPCOMING_EVENTS)
-            .bind(Utc::now())
-            .fetch_all(db)
-            .await
-    } else {
-        sqlx::query_as(sql::GET_UPCOMING_EVENTS)
-            .bind(Utc::now())
-            .fetch_all(db)
-            .await
-    }
-}
+/// Categories that event positions are grouped into.
+const POSITION_CATEGORIES: [&str; 3] = ["Enroute", "TRACON", "Local"];
+/// How long browsers can reuse the upcoming events snippet.
+const UPCOMING_EVENTS_MAX_AGE: Duration = Duration::from_secs(60);
 
 /// Render a snippet that lists published upcoming events.
 ///
@@ -51,15 +50,19 @@ async fn query_for_events(db: &Pool<Sqlite>, show_all: bool) -> sqlx::Result<Vec
 async fn snippet_get_upcoming_events(
     State(state): State<Arc<AppState>>,
     session: Session,
-) -> Result<Html<String>, AppError> {
+) -> Result<Response, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    let show_all = is_user_member_of(&state, &user_info, PermissionsGroup::EventsTeam).await;
-    let events = query_for_events(&state.db, show_all).await?;
+    let show_all = has_permission(&state, &user_info, permissions::EVENTS_MANAGE).await;
+    let events = state
+        .repos
+        .events
+        .get_upcoming(Utc::now(), show_all)
+        .await?;
     let template = state
         .templates
         .get_template("events/upcoming_events_snippet")?;
     let rendered = template.render(context! { user_info, events })?;
-    Ok(Html(rendered))
+    Ok(snippet_response(rendered, UPCOMING_EVENTS_MAX_AGE, true))
 }
 
 /// Render a full page of upcoming events.
@@ -70,9 +73,13 @@ async fn get_upcoming_events(
     session: Session,
 ) -> Result<Html<String>, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    let show_all = is_user_member_of(&state, &user_info, PermissionsGroup::EventsTeam).await;
-    let events = query_for_events(&state.db, show_all).await?;
-    let is_event_staff = is_user_member_of(&state, &user_info, PermissionsGroup::EventsTeam).await;
+    let show_all = has_permission(&state, &user_info, permissions::EVENTS_MANAGE).await;
+    let events = state
+        .repos
+        .events
+        .get_upcoming(Utc::now(), show_all)
+        .await?;
+    let is_event_staff = has_permission(&state, &user_info, permissions::EVENTS_MANAGE).await;
     let template = state.templates.get_template("events/upcoming_events")?;
     let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
     let rendered = template.render(context! {
@@ -92,6 +99,37 @@ struct CreateEventForm {
     start: String,
     end: String,
     timezone: String,
+    #[serde(default)]
+    partner_facility: String,
+    #[serde(default)]
+    external_signup_url: String,
+}
+
+/// Check the joint event fields from a form, blank meaning not set.
+///
+/// Returns the facility ID, uppercased, and the external sign-up link.
+fn joint_event_fields(
+    partner_facility: &str,
+    external_signup_url: &str,
+) -> Result<(Option<String>, Option<String>), &'static str> {
+    let partner_facility = partner_facility.trim().to_uppercase();
+    if !partner_facility.is_empty()
+        && (!(3..=4).contains(&partner_facility.len())
+            || !partner_facility.chars().all(|c| c.is_ascii_alphanumeric()))
+    {
+        return Err("The partner facility must be an ID like ZLC");
+    }
+    let external_signup_url = external_signup_url.trim();
+    if !external_signup_url.is_empty()
+        && !external_signup_url.starts_with("https://")
+        && !external_signup_url.starts_with("http://")
+    {
+        return Err("The external sign-up link must be a web address");
+    }
+    Ok((
+        Some(partner_facility).filter(|s| !s.is_empty()),
+        Some(external_signup_url.to_owned()).filter(|s| !s.is_empty()),
+    ))
 }
 
 /// Submit the form to create a new event.
@@ -103,12 +141,27 @@ async fn post_new_event_form(
     WithRejection(Form(create_new_form), _): WithRejection<Form<CreateEventForm>, AppError>,
 ) -> Result<Redirect, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    let is_event_staff = is_user_member_of(&state, &user_info, PermissionsGroup::EventsTeam).await;
+    let is_event_staff = has_permission(&state, &user_info, permissions::EVENTS_MANAGE).await;
     if !is_event_staff {
         return Ok(Redirect::to("/"));
     }
 
     let cid = user_info.unwrap().cid;
+    let (partner_facility, external_signup_url) = match joint_event_fields(
+        &create_new_form.partner_facility,
+        &create_new_form.external_signup_url,
+    ) {
+        Ok(fields) => fields,
+        Err(message) => {
+            flashed_messages::push_flashed_message(
+                session,
+                flashed_messages::MessageLevel::Error,
+                message,
+            )
+            .await?;
+            return Ok(Redirect::to("/events"));
+        }
+    };
     let start = js_timestamp_to_utc(&create_new_form.start, &create_new_form.timezone)?;
     let end = js_timestamp_to_utc(&create_new_form.end, &create_new_form.timezone)?;
     let result = sqlx::query(sql::CREATE_EVENT)
@@ -118,6 +171,8 @@ async fn post_new_event_form(
         .bind(end)
         .bind(create_new_form.description)
         .bind(create_new_form.banner)
+        .bind(partner_facility)
+        .bind(external_signup_url)
         .execute(&state.db)
         .await?;
     info!(
@@ -132,6 +187,59 @@ async fn post_new_event_form(
     )))
 }
 
+/// Number of controllers signed up for positions in each category.
+///
+/// Each registration counts once per category, however many of its choices
+/// are in that category. The registration of `except_cid`, if any, is skipped.
+fn signups_by_category(
+    positions: &[EventPosition],
+    registrations: &[EventRegistration],
+    except_cid: Option<u32>,
+) -> HashMap<String, u32> {
+    let mut counts = HashMap::new();
+    for registration in registrations {
+        if Some(registration.cid) == except_cid {
+            continue;
+        }
+        let categories: HashSet<&String> = [
+            registration.choice_1,
+            registration.choice_2,
+            registration.choice_3,
+        ]
+        .iter()
+        .filter_map(|choice| positions.iter().find(|position| position.id == *choice))
+        .map(|position| &position.category)
+        .collect();
+        for category in categories {
+            *counts.entry(category.clone()).or_default() += 1;
+        }
+    }
+    counts
+}
+
+/// Sign-ups and the limit, if any, for a category of an event's positions.
+#[derive(Serialize)]
+struct CategorySignups {
+    signups: u32,
+    max_signups: Option<u32>,
+}
+
+/// Categories that have positions, all of which are assigned.
+///
+/// Controllers can only join the waitlist for these.
+fn fully_assigned_categories(positions: &[EventPosition]) -> Vec<&str> {
+    POSITION_CATEGORIES
+        .into_iter()
+        .filter(|category| {
+            let mut in_category = positions
+                .iter()
+                .filter(|position| position.category == *category)
+                .peekable();
+            in_category.peek().is_some() && in_category.all(|position| position.cid.is_some())
+        })
+        .collect()
+}
+
 // NOTE: opportunity for some minor speed improvements here by not loading
 // controller records twice for each controller assigned to an event.
 
@@ -142,10 +250,7 @@ async fn page_event(
     Path(id): Path<u32>,
 ) -> Result<Response, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    let event: Option<Event> = sqlx::query_as(sql::GET_EVENT)
-        .bind(id)
-        .fetch_optional(&state.db)
-        .await?;
+    let event: Option<Event> = state.repos.events.get(id).await?;
     let event = match event {
         Some(e) => e,
         None => {
@@ -159,8 +264,7 @@ async fn page_event(
         }
     };
 
-    let not_staff_redirect =
-        reject_if_not_in(&state, &user_info, PermissionsGroup::EventsTeam).await;
+    let not_staff_redirect = reject_without(&state, &user_info, permissions::EVENTS_MANAGE).await;
     if !event.published {
         // only event staff can see unpublished events
         if let Some(redirect) = not_staff_redirect {
@@ -169,24 +273,33 @@ async fn page_event(
     }
 
     let user_controller: Option<Controller> = match &user_info {
-        Some(info) => {
-            sqlx::query_as(sql::GET_CONTROLLER_BY_CID)
-                .bind(info.cid)
-                .fetch_optional(&state.db)
-                .await?
-        }
+        Some(info) => state.repos.controllers.get_by_cid(info.cid).await?,
         None => None,
     };
 
-    let positions_raw: Vec<EventPosition> = sqlx::query_as(sql::GET_EVENT_POSITIONS)
-        .bind(event.id)
-        .fetch_all(&state.db)
-        .await?;
+    let positions_raw: Vec<EventPosition> = state.repos.events.get_positions(event.id).await?;
+    let ineligible_positions: Vec<u32> = match &user_controller {
+        Some(controller) => {
+            let certs = state.repos.certifications.get_for(controller.cid).await?;
+            positions_raw
+                .iter()
+                .filter(|position| {
+                    cert_positions::ineligibility_reason(
+                        &state.config,
+                        controller.rating,
+                        &certs,
+                        &position.name,
+                    )
+                    .is_some()
+                })
+                .map(|position| position.id)
+                .collect()
+        }
+        None => Vec::new(),
+    };
     let positions = event_positions_extra(&positions_raw, &state.db).await?;
     let registrations = event_registrations_extra(event.id, &positions_raw, &state.db).await?;
-    let all_controllers: Vec<Controller> = sqlx::query_as(sql::GET_ALL_CONTROLLERS_ON_ROSTER)
-        .fetch_all(&state.db)
-        .await?;
+    let all_controllers: Vec<Controller> = state.repos.controllers.get_on_roster().await?;
     let all_controllers: Vec<(u32, String)> = all_controllers
         .iter()
         .map(|controller| {
@@ -221,6 +334,98 @@ async fn page_event(
         None
     };
 
+    let channels: Vec<EventChannel> = if not_staff_redirect.is_none() {
+        sqlx::query_as(sql::GET_EVENT_CHANNELS)
+            .bind(id)
+            .fetch_all(&state.db)
+            .await?
+    } else {
+        Vec::new()
+    };
+
+    let all_registrations: Vec<EventRegistration> = sqlx::query_as(sql::GET_EVENT_REGISTRATIONS)
+        .bind(id)
+        .fetch_all(&state.db)
+        .await?;
+    let limits: Vec<EventCategoryLimit> = sqlx::query_as(sql::GET_EVENT_CATEGORY_LIMITS)
+        .bind(id)
+        .fetch_all(&state.db)
+        .await?;
+    let signups = signups_by_category(&positions_raw, &all_registrations, None);
+    let category_signups: HashMap<&str, CategorySignups> = POSITION_CATEGORIES
+        .iter()
+        .map(|category| {
+            (
+                *category,
+                CategorySignups {
+                    signups: signups.get(*category).copied().unwrap_or_default(),
+                    max_signups: limits
+                        .iter()
+                        .find(|limit| limit.category == *category)
+                        .map(|limit| limit.max_signups),
+                },
+            )
+        })
+        .collect();
+    // the controller's own choices don't count against them when changing their registration
+    let own_signups = signups_by_category(
+        &positions_raw,
+        &all_registrations,
+        user_info.as_ref().map(|info| info.cid),
+    );
+    let full_categories: Vec<&str> = limits
+        .iter()
+        .filter(|limit| {
+            own_signups
+                .get(&limit.category)
+                .is_some_and(|count| *count >= limit.max_signups)
+        })
+        .map(|limit| limit.category.as_str())
+        .collect();
+
+    let waitlist: Vec<EventWaitlistEntry> = sqlx::query_as(sql::GET_EVENT_WAITLIST)
+        .bind(id)
+        .fetch_all(&state.db)
+        .await?;
+    let own_waitlist: Vec<&str> = waitlist
+        .iter()
+        .filter(|entry| user_info.as_ref().is_some_and(|info| info.cid == entry.cid))
+        .map(|entry| entry.category.as_str())
+        .collect();
+    let waitlist_categories = fully_assigned_categories(&positions_raw);
+    // only staff see who's waiting
+    let mut waitlists: HashMap<&str, Vec<&str>> = HashMap::new();
+    if not_staff_redirect.is_none() {
+        for entry in &waitlist {
+            let name = all_controllers
+                .iter()
+                .find(|(cid, _)| *cid == entry.cid)
+                .map(|(_, name)| name.as_str())
+                .unwrap_or("???");
+            waitlists
+                .entry(entry.category.as_str())
+                .or_default()
+                .push(name);
+        }
+    }
+
+    let assignments_text = if not_staff_redirect.is_none() {
+        let controllers = state.repos.controllers.get_all().await?;
+        Some(event_posts::assignments_text(
+            &event,
+            &state.config.hosted_domain,
+            &positions_raw,
+            &controllers,
+        ))
+    } else {
+        None
+    };
+
+    let activity_credited: bool = sqlx::query_scalar(sql::GET_EVENT_ACTIVITY_CREDITED)
+        .bind(id)
+        .fetch_one(&state.db)
+        .await?;
+
     let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
     let rendered = template.render(context! {
         user_info,
@@ -233,11 +438,36 @@ async fn page_event(
         is_on_roster => user_controller.map(|c| c.is_on_roster).unwrap_or_default(),
         is_event_staff => not_staff_redirect.is_none(),
         event_not_over =>  Utc::now() < event.end,
+        activity_credited,
+        channels,
+        category_signups,
+        full_categories,
+        ineligible_positions,
+        waitlist_categories,
+        own_waitlist,
+        waitlists,
+        assignments_text,
         flashed_messages,
     })?;
     Ok(Html(rendered).into_response())
 }
 
+/// The controllers with the CIDs, by CID, from a single query.
+async fn controllers_by_cid(
+    cids: impl IntoIterator<Item = u32>,
+    db: &Pool<Sqlite>,
+) -> Result<HashMap<u32, Controller>, AppError> {
+    let cids: Vec<u32> = cids.into_iter().unique().collect();
+    if cids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let controllers: Vec<Controller> = sqlx::query_as(sql::GET_CONTROLLERS_BY_CIDS)
+        .bind(format!("[{}]", cids.iter().join(",")))
+        .fetch_all(db)
+        .await?;
+    Ok(controllers.into_iter().map(|c| (c.cid, c)).collect())
+}
+
 #[derive(Serialize)]
 struct EventPositionDisplay {
     id: u32,
@@ -251,14 +481,11 @@ async fn event_positions_extra(
     positions: &[EventPosition],
     db: &Pool<Sqlite>,
 ) -> Result<Vec<EventPositionDisplay>, AppError> {
+    let controllers = controllers_by_cid(positions.iter().filter_map(|p| p.cid), db).await?;
     let mut ret = Vec::with_capacity(positions.len());
     for position in positions {
         if let Some(pos_cid) = position.cid {
-            let controller: Option<Controller> = sqlx::query_as(sql::GET_CONTROLLER_BY_CID)
-                .bind(pos_cid)
-                .fetch_optional(db)
-                .await?;
-            if let Some(controller) = controller {
+            if let Some(controller) = controllers.get(&pos_cid) {
                 ret.push(EventPositionDisplay {
                     id: position.id,
                     name: position.name.clone(),
@@ -306,6 +533,7 @@ async fn event_registrations_extra(
         .bind(event_id)
         .fetch_all(db)
         .await?;
+    let controllers = controllers_by_cid(registrations.iter().map(|r| r.cid), db).await?;
     let mut ret = Vec::with_capacity(registrations.len());
 
     for registration in &registrations {
@@ -321,11 +549,7 @@ async fn event_registrations_extra(
             .iter()
             .find(|pos| pos.id == registration.choice_3)
             .map(|pos| pos.name.clone());
-        let controller: Option<Controller> = sqlx::query_as(sql::GET_CONTROLLER_BY_CID)
-            .bind(registration.cid)
-            .fetch_optional(db)
-            .await?;
-        let controller = match controller {
+        let controller = match controllers.get(&registration.cid) {
             Some(c) => format!(
                 "{} {} ({}) - {}",
                 c.first_name,
@@ -356,6 +580,75 @@ async fn event_registrations_extra(
     Ok(ret)
 }
 
+/// The event and its positions and controllers, for posting its assignments.
+async fn assignments_for_post(
+    state: &AppState,
+    id: u32,
+) -> Result<Option<(Event, Vec<EventPosition>, Vec<Controller>)>, AppError> {
+    let Some(event) = state.repos.events.get(id).await? else {
+        return Ok(None);
+    };
+    let positions = state.repos.events.get_positions(id).await?;
+    let controllers = state.repos.controllers.get_all().await?;
+    Ok(Some((event, positions, controllers)))
+}
+
+/// The event's position assignments as a Discord message, matching the bot's post.
+///
+/// Event staff only.
+async fn get_assignments_text(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path(id): Path<u32>,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::EVENTS_MANAGE).await {
+        return Ok(redirect.into_response());
+    }
+    let Some((event, positions, controllers)) = assignments_for_post(&state, id).await? else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let text = event_posts::assignments_text(
+        &event,
+        &state.config.hosted_domain,
+        &positions,
+        &controllers,
+    );
+    Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], text).into_response())
+}
+
+/// The event's position assignments as an image, laid out like the bot's post.
+///
+/// Event staff only.
+async fn get_assignments_image(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path(id): Path<u32>,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::EVENTS_MANAGE).await {
+        return Ok(redirect.into_response());
+    }
+    let Some((event, positions, controllers)) = assignments_for_post(&state, id).await? else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    // mentions only work in messages, so the image has names
+    let rows = event_posts::assignments(&positions, &controllers, false);
+    let image = post_image::render_assignments(&event.name, event_posts::HEADING, &rows)
+        .map_err(|e| AppError::GenericFallback("rendering assignments image", e.into()))?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/png".to_owned()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("inline; filename=\"event-{id}-assignments.png\""),
+            ),
+        ],
+        image,
+    )
+        .into_response())
+}
+
 #[derive(Deserialize)]
 struct UpdateEventForm {
     name: String,
@@ -365,6 +658,10 @@ struct UpdateEventForm {
     start: String,
     end: String,
     timezone: String,
+    #[serde(default)]
+    partner_facility: String,
+    #[serde(default)]
+    external_signup_url: String,
 }
 
 /// Submit a form to update an event, and redirect back to the same page.
@@ -377,16 +674,27 @@ async fn post_edit_event_form(
     Form(details_form): Form<UpdateEventForm>,
 ) -> Result<Redirect, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if let Some(redirect) = reject_if_not_in(&state, &user_info, PermissionsGroup::EventsTeam).await
-    {
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::EVENTS_MANAGE).await {
         return Ok(redirect);
     }
 
-    let event: Option<Event> = sqlx::query_as(sql::GET_EVENT)
-        .bind(id)
-        .fetch_optional(&state.db)
-        .await?;
+    let event: Option<Event> = state.repos.events.get(id).await?;
     if event.is_some() {
+        let (partner_facility, external_signup_url) = match joint_event_fields(
+            &details_form.partner_facility,
+            &details_form.external_signup_url,
+        ) {
+            Ok(fields) => fields,
+            Err(message) => {
+                flashed_messages::push_flashed_message(
+                    session,
+                    flashed_messages::MessageLevel::Error,
+                    message,
+                )
+                .await?;
+                return Ok(Redirect::to(&format!("/events/{id}")));
+            }
+        };
         let start = js_timestamp_to_utc(&details_form.start, &details_form.timezone)?;
         let end = js_timestamp_to_utc(&details_form.end, &details_form.timezone)?;
         sqlx::query(sql::UPDATE_EVENT)
@@ -397,6 +705,8 @@ async fn post_edit_event_form(
             .bind(end)
             .bind(details_form.description)
             .bind(details_form.banner)
+            .bind(partner_facility)
+            .bind(external_signup_url)
             .execute(&state.db)
             .await?;
         info!("{} edited event {id}", user_info.unwrap().cid);
@@ -415,14 +725,23 @@ async fn api_delete_event(
     Path(id): Path<u32>,
 ) -> Result<StatusCode, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if !is_user_member_of(&state, &user_info, PermissionsGroup::EventsTeam).await {
+    if !has_permission(&state, &user_info, permissions::EVENTS_MANAGE).await {
         return Ok(StatusCode::FORBIDDEN);
     }
-    let event: Option<Event> = sqlx::query_as(sql::GET_EVENT)
-        .bind(id)
-        .fetch_optional(&state.db)
-        .await?;
+    let event: Option<Event> = state.repos.events.get(id).await?;
     if event.is_some() {
+        sqlx::query(sql::DELETE_EVENT_CHANNELS_FOR)
+            .bind(id)
+            .execute(&state.db)
+            .await?;
+        sqlx::query(sql::DELETE_EVENT_CATEGORY_LIMITS_FOR)
+            .bind(id)
+            .execute(&state.db)
+            .await?;
+        sqlx::query(sql::DELETE_EVENT_WAITLIST_FOR)
+            .bind(id)
+            .execute(&state.db)
+            .await?;
         sqlx::query(sql::DELETE_EVENT)
             .bind(id)
             .execute(&state.db)
@@ -455,10 +774,7 @@ async fn post_register_for_event(
     Path(id): Path<u32>,
     Form(register_data): Form<RegisterForm>,
 ) -> Result<Redirect, AppError> {
-    let event: Option<Event> = sqlx::query_as(sql::GET_EVENT)
-        .bind(id)
-        .fetch_optional(&state.db)
-        .await?;
+    let event: Option<Event> = state.repos.events.get(id).await?;
     if event.is_none() {
         return Ok(Redirect::to("/events"));
     }
@@ -484,6 +800,62 @@ async fn post_register_for_event(
     } else {
         Some(register_data.choice_3)
     };
+    let positions: Vec<EventPosition> = state.repos.events.get_positions(id).await?;
+
+    // controllers can only sign up for positions they can work
+    let controller: Option<Controller> = state.repos.controllers.get_by_cid(cid).await?;
+    let rating = controller.map(|c| c.rating).unwrap_or_default();
+    let certs = state.repos.certifications.get_for(cid).await?;
+    let reasons: Vec<String> = [c_1, c_2, c_3]
+        .iter()
+        .flatten()
+        .filter_map(|choice| positions.iter().find(|position| position.id == *choice))
+        .filter_map(|position| {
+            cert_positions::ineligibility_reason(&state.config, rating, &certs, &position.name)
+        })
+        .collect();
+    if !reasons.is_empty() {
+        flashed_messages::push_flashed_message(
+            session,
+            flashed_messages::MessageLevel::Error,
+            &format!("You can't sign up for that: {}", reasons.join("; ")),
+        )
+        .await?;
+        return Ok(Redirect::to(&format!("/events/{id}")));
+    }
+
+    // don't let the registration push any category over its limit
+    let registrations: Vec<EventRegistration> = sqlx::query_as(sql::GET_EVENT_REGISTRATIONS)
+        .bind(id)
+        .fetch_all(&state.db)
+        .await?;
+    let limits: Vec<EventCategoryLimit> = sqlx::query_as(sql::GET_EVENT_CATEGORY_LIMITS)
+        .bind(id)
+        .fetch_all(&state.db)
+        .await?;
+    let others = signups_by_category(&positions, &registrations, Some(cid));
+    let chosen: HashSet<&String> = [c_1, c_2, c_3]
+        .iter()
+        .flatten()
+        .filter_map(|choice| positions.iter().find(|position| position.id == *choice))
+        .map(|position| &position.category)
+        .collect();
+    if let Some(full) = limits.iter().find(|limit| {
+        chosen.contains(&limit.category)
+            && others.get(&limit.category).copied().unwrap_or_default() >= limit.max_signups
+    }) {
+        flashed_messages::push_flashed_message(
+            session,
+            flashed_messages::MessageLevel::Error,
+            &format!(
+                "{} positions are full ({} sign-ups); choose positions in another category",
+                full.category, full.max_signups
+            ),
+        )
+        .await?;
+        return Ok(Redirect::to(&format!("/events/{id}")));
+    }
+
     // upsert the registration
     sqlx::query(sql::UPSERT_EVENT_REGISTRATION)
         .bind(id)
@@ -532,6 +904,325 @@ async fn api_register_unregister(
     Ok(StatusCode::ACCEPTED)
 }
 
+#[derive(Deserialize)]
+struct CategoryLimitForm {
+    category: String,
+    max_signups: String,
+}
+
+/// Submit a form to set or clear the sign-up limit for a category of the event's positions.
+///
+/// Event staff only.
+async fn post_category_limit(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path(id): Path<u32>,
+    Form(limit_form): Form<CategoryLimitForm>,
+) -> Result<Redirect, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::EVENTS_MANAGE).await {
+        return Ok(redirect);
+    }
+    let event: Option<Event> = state.repos.events.get(id).await?;
+    if event.is_none() || !POSITION_CATEGORIES.contains(&limit_form.category.as_str()) {
+        return Ok(Redirect::to("/"));
+    }
+    let by_cid = user_info.unwrap().cid;
+    // blank or zero removes the limit
+    match limit_form.max_signups.trim().parse::<u32>() {
+        Ok(max_signups) if max_signups > 0 => {
+            sqlx::query(sql::UPSERT_EVENT_CATEGORY_LIMIT)
+                .bind(id)
+                .bind(&limit_form.category)
+                .bind(max_signups)
+                .execute(&state.db)
+                .await?;
+            info!(
+                "{by_cid} limited {} sign-ups for event {id} to {max_signups}",
+                limit_form.category
+            );
+        }
+        _ => {
+            sqlx::query(sql::DELETE_EVENT_CATEGORY_LIMIT)
+                .bind(id)
+                .bind(&limit_form.category)
+                .execute(&state.db)
+                .await?;
+            info!(
+                "{by_cid} removed the {} sign-up limit for event {id}",
+                limit_form.category
+            );
+        }
+    }
+    Ok(Redirect::to(&format!("/events/{id}")))
+}
+
+#[derive(Deserialize)]
+struct WaitlistForm {
+    category: String,
+    action: String,
+}
+
+/// Submit a form to join or leave the waitlist for a category of the event's positions.
+///
+/// Controllers can only join once every position in the category is assigned,
+/// and only if they could work one of them.
+async fn post_waitlist(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path(id): Path<u32>,
+    Form(waitlist_form): Form<WaitlistForm>,
+) -> Result<Redirect, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let cid = match user_info {
+        Some(user_info) => user_info.cid,
+        None => return Ok(Redirect::to(&format!("/events/{id}"))),
+    };
+    let event = match state.repos.events.get(id).await? {
+        Some(event) => event,
+        None => return Ok(Redirect::to("/events")),
+    };
+    let category = waitlist_form.category.as_str();
+    if !POSITION_CATEGORIES.contains(&category) || event.end < Utc::now() {
+        return Ok(Redirect::to(&format!("/events/{id}")));
+    }
+
+    let (level, message) = match waitlist_form.action.as_str() {
+        "join" => {
+            let positions: Vec<EventPosition> = state.repos.events.get_positions(id).await?;
+            let in_category: Vec<&EventPosition> = positions
+                .iter()
+                .filter(|position| position.category == category)
+                .collect();
+            let rating = state
+                .repos
+                .controllers
+                .get_by_cid(cid)
+                .await?
+                .map(|c| c.rating)
+                .unwrap_or_default();
+            let certs = state.repos.certifications.get_for(cid).await?;
+            if !fully_assigned_categories(&positions).contains(&category) {
+                (
+                    flashed_messages::MessageLevel::Error,
+                    format!("There are open {category} positions; register for them instead"),
+                )
+            } else if in_category.iter().any(|position| position.cid == Some(cid)) {
+                (
+                    flashed_messages::MessageLevel::Error,
+                    format!("You're already assigned to a {category} position"),
+                )
+            } else if in_category.iter().all(|position| {
+                cert_positions::ineligibility_reason(&state.config, rating, &certs, &position.name)
+                    .is_some()
+            }) {
+                (
+                    flashed_messages::MessageLevel::Error,
+                    format!("You can't work any of the {category} positions"),
+                )
+            } else {
+                sqlx::query(sql::JOIN_EVENT_WAITLIST)
+                    .bind(id)
+                    .bind(cid)
+                    .bind(category)
+                    .bind(Utc::now())
+                    .execute(&state.db)
+                    .await?;
+                info!("{cid} joined the {category} waitlist for event {id}");
+                (
+                    flashed_messages::MessageLevel::Success,
+                    format!("You're on the waitlist for {category} positions, and will be notified if you're assigned one"),
+                )
+            }
+        }
+        "leave" => {
+            sqlx::query(sql::LEAVE_EVENT_WAITLIST)
+                .bind(id)
+                .bind(cid)
+                .bind(category)
+                .execute(&state.db)
+                .await?;
+            info!("{cid} left the {category} waitlist for event {id}");
+            (
+                flashed_messages::MessageLevel::Info,
+                format!("You've left the waitlist for {category} positions"),
+            )
+        }
+        _ => return Ok(Redirect::to(&format!("/events/{id}"))),
+    };
+    flashed_messages::push_flashed_message(session, level, &message).await?;
+    Ok(Redirect::to(&format!("/events/{id}")))
+}
+
+/// Assign waitlisted controllers to the event's open positions.
+///
+/// Each open position goes to the controller who's been waiting longest for its
+/// category, skipping those already assigned elsewhere in the event or who
+/// can't work the position. Promoted controllers are notified.
+///
+/// Returns the promoted controllers' CIDs and their positions' names.
+async fn fill_from_waitlist(
+    state: &AppState,
+    event: &Event,
+) -> Result<Vec<(u32, String)>, AppError> {
+    if event.end < Utc::now() {
+        return Ok(Vec::new());
+    }
+    let mut positions: Vec<EventPosition> = state.repos.events.get_positions(event.id).await?;
+    positions.sort_by_key(|position| position.id);
+    let mut waiting: Vec<EventWaitlistEntry> = sqlx::query_as(sql::GET_EVENT_WAITLIST)
+        .bind(event.id)
+        .fetch_all(&state.db)
+        .await?;
+    let mut promoted = Vec::new();
+    for index in 0..positions.len() {
+        if positions[index].cid.is_some() {
+            continue;
+        }
+        let mut chosen = None;
+        for (entry_index, entry) in waiting.iter().enumerate() {
+            if entry.category != positions[index].category
+                || positions
+                    .iter()
+                    .any(|position| position.cid == Some(entry.cid))
+            {
+                continue;
+            }
+            let rating = state
+                .repos
+                .controllers
+                .get_by_cid(entry.cid)
+                .await?
+                .map(|c| c.rating)
+                .unwrap_or_default();
+            let certs = state.repos.certifications.get_for(entry.cid).await?;
+            if cert_positions::ineligibility_reason(
+                &state.config,
+                rating,
+                &certs,
+                &positions[index].name,
+            )
+            .is_none()
+            {
+                chosen = Some(entry_index);
+                break;
+            }
+        }
+        let Some(entry_index) = chosen else {
+            continue;
+        };
+        let entry = waiting.remove(entry_index);
+        let mut tx = state.db.begin().await?;
+        sqlx::query(sql::UPDATE_EVENT_POSITION_CONTROLLER)
+            .bind(positions[index].id)
+            .bind(entry.cid)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(sql::SET_EVENT_WAITLIST_ENTRY_PROMOTED)
+            .bind(entry.id)
+            .bind(Utc::now())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        positions[index].cid = Some(entry.cid);
+        info!(
+            "{} assigned to {} for event {} from the waitlist",
+            entry.cid, positions[index].name, event.id
+        );
+        notify_event_waitlist_promotion(state, event, entry.cid, &positions[index].name).await;
+        promoted.push((entry.cid, positions[index].name.clone()));
+    }
+    Ok(promoted)
+}
+
+/// Tell a controller they've been assigned an event position off the waitlist,
+/// by Discord DM if they've linked their account and by email if the template is set.
+async fn notify_event_waitlist_promotion(
+    state: &AppState,
+    event: &Event,
+    cid: u32,
+    position: &str,
+) {
+    let controller = match state.repos.controllers.get_by_cid(cid).await {
+        Ok(Some(controller)) => controller,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Could not get {cid} to notify of event waitlist promotion: {e}");
+            return;
+        }
+    };
+    let start = event.start.format("%Y-%m-%d %H:%M UTC").to_string();
+    if let Some(discord_id) = &controller.discord_id {
+        let content = format!(
+            "A position opened up for {}, and you were next on the waitlist: you're now working {position}, starting {start}.",
+            event.name
+        );
+        if let Err(e) = discord::send_direct_message(&state.config, discord_id, &content).await {
+            warn!("Could not DM {cid} of event waitlist promotion: {e}");
+        }
+    }
+    if state
+        .config
+        .email
+        .event_waitlist_promoted_template
+        .body
+        .is_empty()
+    {
+        return;
+    }
+    let address: Option<String> = match sqlx::query_scalar(sql::GET_CONTROLLER_EMAIL)
+        .bind(cid)
+        .fetch_optional(&state.db)
+        .await
+    {
+        Ok(address) => address.flatten(),
+        Err(e) => {
+            warn!("Could not get {cid}'s email address: {e}");
+            None
+        }
+    };
+    if let Some(address) = address {
+        if let Err(e) = email::send_mail_with_context(
+            &state.config,
+            &state.db,
+            &format!("{} {}", controller.first_name, controller.last_name),
+            &address,
+            email::templates::EVENT_WAITLIST_PROMOTED,
+            context! { event => &event.name, position, start },
+        )
+        .await
+        {
+            warn!("Could not email {cid} of event waitlist promotion: {e}");
+        }
+    }
+}
+
+/// Flash which waitlisted controllers were just assigned, if any.
+async fn flash_promotions(
+    state: &AppState,
+    session: Session,
+    promoted: &[(u32, String)],
+) -> Result<(), AppError> {
+    if promoted.is_empty() {
+        return Ok(());
+    }
+    let mut assignments = Vec::with_capacity(promoted.len());
+    for (cid, position) in promoted {
+        let name = match state.repos.controllers.get_by_cid(*cid).await? {
+            Some(c) => format!("{} {}", c.first_name, c.last_name),
+            None => cid.to_string(),
+        };
+        assignments.push(format!("{name} to {position}"));
+    }
+    flashed_messages::push_flashed_message(
+        session,
+        flashed_messages::MessageLevel::Info,
+        &format!("Assigned from the waitlist: {}", assignments.join(", ")),
+    )
+    .await?;
+    Ok(())
+}
+
 #[derive(Deserialize)]
 struct AddPositionForm {
     name: String,
@@ -546,8 +1237,7 @@ async fn post_add_position(
     Form(new_position_data): Form<AddPositionForm>,
 ) -> Result<Redirect, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if let Some(redirect) = reject_if_not_in(&state, &user_info, PermissionsGroup::EventsTeam).await
-    {
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::EVENTS_MANAGE).await {
         return Ok(redirect);
     }
     if new_position_data.name.is_empty() {
@@ -560,18 +1250,12 @@ async fn post_add_position(
         return Ok(Redirect::to(&format!("/events/{id}")));
     }
 
-    let event: Option<Event> = sqlx::query_as(sql::GET_EVENT)
-        .bind(id)
-        .fetch_optional(&state.db)
-        .await?;
-    if event.is_some() {
+    let event: Option<Event> = state.repos.events.get(id).await?;
+    if let Some(event) = &event {
         let name = new_position_data.name.to_uppercase();
 
         // don't allow position duplicates
-        let existing: Vec<EventPosition> = sqlx::query_as(sql::GET_EVENT_POSITIONS)
-            .bind(id)
-            .fetch_all(&state.db)
-            .await?;
+        let existing: Vec<EventPosition> = state.repos.events.get_positions(id).await?;
         if !existing.iter().any(|position| {
             position.name == name && position.category == new_position_data.category
         }) {
@@ -588,6 +1272,8 @@ async fn post_add_position(
                 .execute(&state.db)
                 .await?;
         }
+        let promoted = fill_from_waitlist(&state, event).await?;
+        flash_promotions(&state, session, &promoted).await?;
         Ok(Redirect::to(&format!("/events/{id}")))
     } else {
         Ok(Redirect::to("/"))
@@ -601,15 +1287,11 @@ async fn post_delete_position(
     Path((id, pos_id)): Path<(u32, u32)>,
 ) -> Result<Redirect, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if let Some(redirect) = reject_if_not_in(&state, &user_info, PermissionsGroup::EventsTeam).await
-    {
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::EVENTS_MANAGE).await {
         return Ok(redirect);
     }
 
-    let event: Option<Event> = sqlx::query_as(sql::GET_EVENT)
-        .bind(id)
-        .fetch_optional(&state.db)
-        .await?;
+    let event: Option<Event> = state.repos.events.get(id).await?;
     if event.is_some() {
         info!(
             "{} removed position {pos_id} from {id}",
@@ -629,9 +1311,13 @@ async fn post_delete_position(
 struct SetPositionForm {
     position_id: u32,
     controller: u32,
+    override_eligibility: Option<String>,
 }
 
 /// Set a controller (or no-one) for a position.
+///
+/// Controllers must be able to work the position, by certification or rating,
+/// unless the staff member overrides it.
 async fn post_set_position(
     State(state): State<Arc<AppState>>,
     session: Session,
@@ -639,38 +1325,224 @@ async fn post_set_position(
     Form(new_position_data): Form<SetPositionForm>,
 ) -> Result<Redirect, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    if let Some(redirect) = reject_if_not_in(&state, &user_info, PermissionsGroup::EventsTeam).await
-    {
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::EVENTS_MANAGE).await {
         return Ok(redirect);
     }
 
-    let event: Option<Event> = sqlx::query_as(sql::GET_EVENT)
-        .bind(id)
-        .fetch_optional(&state.db)
-        .await?;
-    if event.is_some() {
+    let event: Option<Event> = state.repos.events.get(id).await?;
+    if let Some(event) = &event {
         let cid = if new_position_data.controller != 0 {
             Some(new_position_data.controller)
         } else {
             None
         };
+        let overridden = new_position_data.override_eligibility.is_some();
+        if let (Some(cid), false) = (cid, overridden) {
+            let positions = state.repos.events.get_positions(id).await?;
+            if let Some(position) = positions
+                .iter()
+                .find(|position| position.id == new_position_data.position_id)
+            {
+                let rating = state
+                    .repos
+                    .controllers
+                    .get_by_cid(cid)
+                    .await?
+                    .map(|c| c.rating)
+                    .unwrap_or_default();
+                let certs = state.repos.certifications.get_for(cid).await?;
+                if let Some(reason) = cert_positions::ineligibility_reason(
+                    &state.config,
+                    rating,
+                    &certs,
+                    &position.name,
+                ) {
+                    flashed_messages::push_flashed_message(
+                        session,
+                        flashed_messages::MessageLevel::Error,
+                        &format!(
+                            "That controller can't work the position: {reason}. Check the override box to assign them anyway."
+                        ),
+                    )
+                    .await?;
+                    return Ok(Redirect::to(&format!("/events/{id}")));
+                }
+            }
+        }
         sqlx::query(sql::UPDATE_EVENT_POSITION_CONTROLLER)
             .bind(new_position_data.position_id)
             .bind(cid)
             .execute(&state.db)
             .await?;
         info!(
-            "{} updated event {id} position {} to cid {}",
+            "{} updated event {id} position {} to cid {}{}",
             user_info.unwrap().cid,
             new_position_data.position_id,
-            new_position_data.controller
+            new_position_data.controller,
+            if overridden {
+                ", overriding eligibility"
+            } else {
+                ""
+            }
         );
+        // a dropped assignment goes to the next controller waiting for it
+        if cid.is_none() {
+            let promoted = fill_from_waitlist(&state, event).await?;
+            flash_promotions(&state, session, &promoted).await?;
+        }
         Ok(Redirect::to(&format!("/events/{id}")))
     } else {
         Ok(Redirect::to("/"))
     }
 }
 
+#[derive(Deserialize)]
+struct AddChannelForm {
+    name: String,
+    kind: String,
+}
+
+/// Add a Discord channel for the bot to create while the event runs.
+async fn post_add_channel(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path(id): Path<u32>,
+    Form(channel_data): Form<AddChannelForm>,
+) -> Result<Redirect, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::EVENTS_MANAGE).await {
+        return Ok(redirect);
+    }
+    let name = channel_data.name.trim();
+    if name.is_empty() || name.len() > 100 {
+        flashed_messages::push_flashed_message(
+            session,
+            flashed_messages::MessageLevel::Error,
+            "Channel names must be 1 to 100 characters",
+        )
+        .await?;
+        return Ok(Redirect::to(&format!("/events/{id}")));
+    }
+    let event: Option<Event> = state.repos.events.get(id).await?;
+    if event.is_none() {
+        return Ok(Redirect::to("/"));
+    }
+    let voice = channel_data.kind == "voice";
+    sqlx::query(sql::INSERT_EVENT_CHANNEL)
+        .bind(id)
+        .bind(name)
+        .bind(voice)
+        .execute(&state.db)
+        .await?;
+    info!(
+        "{} added {} channel {name} to event {id}",
+        user_info.unwrap().cid,
+        if voice { "voice" } else { "text" }
+    );
+    Ok(Redirect::to(&format!("/events/{id}")))
+}
+
+/// Remove a Discord channel from the event, if the bot hasn't created it yet.
+async fn post_delete_channel(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path((id, channel_id)): Path<(u32, u32)>,
+) -> Result<Redirect, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::EVENTS_MANAGE).await {
+        return Ok(redirect);
+    }
+    let result = sqlx::query(sql::DELETE_EVENT_CHANNEL)
+        .bind(channel_id)
+        .bind(id)
+        .execute(&state.db)
+        .await?;
+    if result.rows_affected() == 0 {
+        flashed_messages::push_flashed_message(
+            session,
+            flashed_messages::MessageLevel::Error,
+            "That channel has already been created",
+        )
+        .await?;
+    } else {
+        info!(
+            "{} removed channel {channel_id} from event {id}",
+            user_info.unwrap().cid
+        );
+    }
+    Ok(Redirect::to(&format!("/events/{id}")))
+}
+
+/// Credit the controllers assigned to the event's positions with the
+/// event's duration as activity, in the month the event started.
+///
+/// Crediting again replaces the earlier credit, so it can be re-run after
+/// fixing position assignments.
+async fn post_credit_activity(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path(id): Path<u32>,
+) -> Result<Redirect, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::EVENTS_MANAGE).await {
+        return Ok(redirect);
+    }
+    let event = match state.repos.events.get(id).await? {
+        Some(e) => e,
+        None => return Ok(Redirect::to("/")),
+    };
+    if Utc::now() < event.end {
+        flashed_messages::push_flashed_message(
+            session,
+            flashed_messages::MessageLevel::Error,
+            "Event hours can be credited once the event is over",
+        )
+        .await?;
+        return Ok(Redirect::to(&format!("/events/{id}")));
+    }
+
+    let positions: Vec<EventPosition> = state.repos.events.get_positions(id).await?;
+    let cids: Vec<u32> = positions
+        .iter()
+        .filter_map(|position| position.cid)
+        .unique()
+        .collect();
+    let minutes = (event.end - event.start).num_minutes().max(0) as u32;
+    let month = event.start.format("%Y-%m").to_string();
+    let mut tx = state.db.begin().await?;
+    sqlx::query(sql::DELETE_EVENT_ACTIVITY)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    for cid in &cids {
+        sqlx::query(sql::INSERT_EVENT_ACTIVITY)
+            .bind(cid)
+            .bind(&month)
+            .bind(minutes)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    info!(
+        "{} credited {minutes} minutes of event {id} activity to {} controllers",
+        user_info.unwrap().cid,
+        cids.len()
+    );
+    flashed_messages::push_flashed_message(
+        session,
+        flashed_messages::MessageLevel::Success,
+        &format!(
+            "Credited {} controllers with {}h{}m",
+            cids.len(),
+            minutes / 60,
+            minutes % 60
+        ),
+    )
+    .await?;
+    Ok(Redirect::to(&format!("/events/{id}")))
+}
+
 /// This file's routes and templates.
 pub fn router(template: &mut Environment) -> Router<Arc<AppState>> {
     template
@@ -707,9 +1579,533 @@ pub fn router(template: &mut Environment) -> Router<Arc<AppState>> {
         .route("/events/:id/register", post(post_register_for_event))
         .route("/events/:id/unregister", post(api_register_unregister))
         .route("/events/:id/add_position", post(post_add_position))
+        .route("/events/:id/category_limit", post(post_category_limit))
+        .route("/events/:id/waitlist", post(post_waitlist))
         .route(
             "/events/:id/delete_position/:pos_id",
             post(post_delete_position),
         )
         .route("/events/:id/set_position", post(post_set_position))
+        .route("/events/:id/credit_activity", post(post_credit_activity))
+        .route("/events/:id/assignments.txt", get(get_assignments_text))
+        .route("/events/:id/assignments.png", get(get_assignments_image))
+        .route("/events/:id/add_channel", post(post_add_channel))
+        .route(
+            "/events/:id/delete_channel/:channel_id",
+            post(post_delete_channel),
+        )
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::joint_event_fields;
+    use crate::test_utils::{test_app, TestApp, ADMIN_CONTROLLER, EVENT_ID, HOME_CONTROLLER};
+    use axum::http::StatusCode;
+    use chrono::{Duration, Utc};
+    use vzdv::sql::{self, EventChannel, EventPosition, EventRegistration, EventWaitlistEntry};
+
+    async fn certify_for_tower(app: &TestApp, cid: u32) {
+        sqlx::query(sql::CREATE_CERTIFICATION)
+            .bind(cid)
+            .bind("TWR")
+            .bind("certified")
+            .bind(Utc::now())
+            .bind(ADMIN_CONTROLLER)
+            .execute(&app.db)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_event_signup() {
+        let app = test_app().await;
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        certify_for_tower(&app, HOME_CONTROLLER).await;
+
+        let (status, body) = app.get(&format!("/events/{EVENT_ID}"), Some(&cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Fixture FNO"));
+
+        let form = [
+            ("choice_1", "1"),
+            ("choice_2", "2"),
+            ("choice_3", "0"),
+            ("notes", "Any position"),
+        ];
+        let (status, _) = app
+            .post_form(
+                &format!("/events/{EVENT_ID}/register"),
+                &form,
+                Some(&cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let registration: EventRegistration = sqlx::query_as(sql::GET_EVENT_REGISTRATION_FOR)
+            .bind(EVENT_ID)
+            .bind(HOME_CONTROLLER)
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(registration.choice_1, 1);
+        assert_eq!(registration.choice_2, 2);
+        assert_eq!(registration.notes.as_deref(), Some("Any position"));
+    }
+
+    #[tokio::test]
+    async fn test_event_page_controller_names() {
+        let app = test_app().await;
+        sqlx::query(sql::UPDATE_EVENT_POSITION_CONTROLLER)
+            .bind(1)
+            .bind(HOME_CONTROLLER)
+            .execute(&app.db)
+            .await
+            .unwrap();
+        sqlx::query(sql::UPDATE_CONTROLLER_OIS)
+            .bind(HOME_CONTROLLER)
+            .bind("HC")
+            .execute(&app.db)
+            .await
+            .unwrap();
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        app.post_form(
+            &format!("/events/{EVENT_ID}/register"),
+            &[
+                ("choice_1", "1"),
+                ("choice_2", "0"),
+                ("choice_3", "0"),
+                ("notes", ""),
+            ],
+            Some(&cookie),
+        )
+        .await;
+
+        let (_, body) = app.get(&format!("/events/{EVENT_ID}"), Some(&cookie)).await;
+        assert!(body.contains("Home Controller (HC)"));
+        assert!(body.contains("Admin Controller (??) - C1"));
+    }
+
+    #[tokio::test]
+    async fn test_category_signup_limit() {
+        let app = test_app().await;
+        let register = |cid: u32, choice_1: &'static str| {
+            let app = &app;
+            async move {
+                let cookie = app.login_as(cid, cid == ADMIN_CONTROLLER).await;
+                app.post_form(
+                    &format!("/events/{EVENT_ID}/register"),
+                    &[
+                        ("choice_1", choice_1),
+                        ("choice_2", "0"),
+                        ("choice_3", "0"),
+                        ("notes", ""),
+                    ],
+                    Some(&cookie),
+                )
+                .await;
+                sqlx::query_as::<_, EventRegistration>(sql::GET_EVENT_REGISTRATION_FOR)
+                    .bind(EVENT_ID)
+                    .bind(cid)
+                    .fetch_optional(&app.db)
+                    .await
+                    .unwrap()
+            }
+        };
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let (status, _) = app
+            .post_form(
+                &format!("/events/{EVENT_ID}/category_limit"),
+                &[("category", "TRACON"), ("max_signups", "1")],
+                Some(&cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+
+        certify_for_tower(&app, HOME_CONTROLLER).await;
+
+        // DEN_APP is the only TRACON position
+        assert!(register(ADMIN_CONTROLLER, "1").await.is_some());
+        assert!(register(HOME_CONTROLLER, "1").await.is_none());
+        assert!(register(HOME_CONTROLLER, "2").await.is_some());
+        // changing an existing registration doesn't count against itself
+        assert!(register(ADMIN_CONTROLLER, "1").await.is_some());
+
+        let (_, body) = app.get(&format!("/events/{EVENT_ID}"), Some(&cookie)).await;
+        assert!(body.contains("1 of 1 sign-ups"));
+        assert!(body.contains("Full"));
+
+        // clearing the limit opens the category back up
+        app.post_form(
+            &format!("/events/{EVENT_ID}/category_limit"),
+            &[("category", "TRACON"), ("max_signups", "")],
+            Some(&cookie),
+        )
+        .await;
+        assert_eq!(register(HOME_CONTROLLER, "1").await.unwrap().choice_1, 1);
+    }
+
+    #[tokio::test]
+    async fn test_assignment_export() {
+        let app = test_app().await;
+        sqlx::query(sql::UPDATE_EVENT_POSITION_CONTROLLER)
+            .bind(1)
+            .bind(HOME_CONTROLLER)
+            .execute(&app.db)
+            .await
+            .unwrap();
+
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, _) = app
+            .get(
+                &format!("/events/{EVENT_ID}/assignments.txt"),
+                Some(&cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let (status, body) = app
+            .get(
+                &format!("/events/{EVENT_ID}/assignments.txt"),
+                Some(&cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.starts_with("**Fixture FNO**\n"));
+        assert!(body.contains("**DEN_APP**: Home Controller\n"));
+        assert!(body.contains("**DEN_TWR**: Unassigned\n"));
+
+        let (_, page) = app.get(&format!("/events/{EVENT_ID}"), Some(&cookie)).await;
+        assert!(page.contains("**DEN_APP**: Home Controller"));
+
+        let (status, body) = app
+            .get(
+                &format!("/events/{EVENT_ID}/assignments.png"),
+                Some(&cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("PNG"));
+        let (status, _) = app.get("/events/99/assignments.png", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_joint_event_fields() {
+        assert_eq!(
+            joint_event_fields(" zlc ", "https://zlcartcc.org/events"),
+            Ok((
+                Some("ZLC".to_owned()),
+                Some("https://zlcartcc.org/events".to_owned())
+            ))
+        );
+        assert_eq!(joint_event_fields("", " "), Ok((None, None)));
+        assert!(joint_event_fields("Salt Lake", "").is_err());
+        assert!(joint_event_fields("ZLC", "javascript:alert(1)").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_event_waitlist() {
+        let app = test_app().await;
+        let staff_cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let waitlist = |action: &'static str| {
+            let app = &app;
+            let cookie = cookie.clone();
+            async move {
+                app.post_form(
+                    &format!("/events/{EVENT_ID}/waitlist"),
+                    &[("category", "TRACON"), ("action", action)],
+                    Some(&cookie),
+                )
+                .await;
+                sqlx::query_as::<_, EventWaitlistEntry>(sql::GET_EVENT_WAITLIST)
+                    .bind(EVENT_ID)
+                    .fetch_all(&app.db)
+                    .await
+                    .unwrap()
+            }
+        };
+        let set_app = |cid: &'static str| {
+            let app = &app;
+            let staff_cookie = staff_cookie.clone();
+            async move {
+                app.post_form(
+                    &format!("/events/{EVENT_ID}/set_position"),
+                    &[("position_id", "1"), ("controller", cid)],
+                    Some(&staff_cookie),
+                )
+                .await;
+                sqlx::query_as::<_, EventPosition>(sql::GET_EVENT_POSITIONS)
+                    .bind(EVENT_ID)
+                    .fetch_all(&app.db)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .find(|position| position.id == 1)
+                    .unwrap()
+                    .cid
+            }
+        };
+
+        // DEN_APP, the only TRACON position, is still open
+        assert!(waitlist("join").await.is_empty());
+
+        assert_eq!(set_app("1000002").await, Some(ADMIN_CONTROLLER));
+        let (_, body) = app.get(&format!("/events/{EVENT_ID}"), Some(&cookie)).await;
+        assert!(body.contains("join the waitlist"));
+        let entries = waitlist("join").await;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].cid, HOME_CONTROLLER);
+        // joining again doesn't add a second entry
+        assert_eq!(waitlist("join").await.len(), 1);
+        assert!(waitlist("leave").await.is_empty());
+        assert_eq!(waitlist("join").await.len(), 1);
+
+        let (_, body) = app
+            .get(&format!("/events/{EVENT_ID}"), Some(&staff_cookie))
+            .await;
+        assert!(body.contains("Waitlist: Home Controller"));
+
+        // dropping the assignment promotes the waiting controller
+        assert_eq!(set_app("0").await, Some(HOME_CONTROLLER));
+        assert!(waitlist("leave").await.is_empty());
+        let promoted: Option<String> =
+            sqlx::query_scalar("SELECT promoted_date FROM event_waitlist WHERE cid=$1")
+                .bind(HOME_CONTROLLER)
+                .fetch_one(&app.db)
+                .await
+                .unwrap();
+        assert!(promoted.is_some());
+
+        // with nobody waiting, the position stays open
+        assert_eq!(set_app("0").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_set_position_requires_certification() {
+        let app = test_app().await;
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let positions: Vec<EventPosition> = sqlx::query_as(sql::GET_EVENT_POSITIONS)
+            .bind(EVENT_ID)
+            .fetch_all(&app.db)
+            .await
+            .unwrap();
+        let tower = positions.iter().find(|p| p.name == "DEN_TWR").unwrap().id;
+        let assigned = || async {
+            let positions: Vec<EventPosition> = sqlx::query_as(sql::GET_EVENT_POSITIONS)
+                .bind(EVENT_ID)
+                .fetch_all(&app.db)
+                .await
+                .unwrap();
+            positions.into_iter().find(|p| p.id == tower).unwrap().cid
+        };
+        let form = [
+            ("position_id", tower.to_string()),
+            ("controller", HOME_CONTROLLER.to_string()),
+        ];
+        let form: Vec<(&str, &str)> = form.iter().map(|(k, v)| (*k, v.as_str())).collect();
+
+        app.post_form(
+            &format!("/events/{EVENT_ID}/set_position"),
+            &form,
+            Some(&cookie),
+        )
+        .await;
+        assert_eq!(assigned().await, None);
+
+        sqlx::query(sql::CREATE_CERTIFICATION)
+            .bind(HOME_CONTROLLER)
+            .bind("TWR")
+            .bind("solo")
+            .bind(Utc::now())
+            .bind(ADMIN_CONTROLLER)
+            .execute(&app.db)
+            .await
+            .unwrap();
+        app.post_form(
+            &format!("/events/{EVENT_ID}/set_position"),
+            &form,
+            Some(&cookie),
+        )
+        .await;
+        assert_eq!(assigned().await, Some(HOME_CONTROLLER));
+
+        let (status, body) = app.get("/facility/positions", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("DEN_GND"));
+        assert!(body.contains(r#"<span class="badge text-bg-info">S</span>"#));
+    }
+
+    #[tokio::test]
+    async fn test_signup_eligibility() {
+        let app = test_app().await;
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let registration = || async {
+            sqlx::query_as::<_, EventRegistration>(sql::GET_EVENT_REGISTRATION_FOR)
+                .bind(EVENT_ID)
+                .bind(HOME_CONTROLLER)
+                .fetch_optional(&app.db)
+                .await
+                .unwrap()
+        };
+        let form = [
+            ("choice_1", "2"),
+            ("choice_2", "0"),
+            ("choice_3", "0"),
+            ("notes", ""),
+        ];
+
+        // DEN_TWR needs the TWR certification
+        let (_, body) = app.get(&format!("/events/{EVENT_ID}"), Some(&cookie)).await;
+        assert!(body.contains("DEN_TWR (not eligible)"));
+        app.post_form(
+            &format!("/events/{EVENT_ID}/register"),
+            &form,
+            Some(&cookie),
+        )
+        .await;
+        assert!(registration().await.is_none());
+
+        // a C1 can work DEN_APP without a certification covering it, but an S2 can't
+        sqlx::query("UPDATE controller SET rating=3 WHERE cid=$1")
+            .bind(HOME_CONTROLLER)
+            .execute(&app.db)
+            .await
+            .unwrap();
+        certify_for_tower(&app, HOME_CONTROLLER).await;
+        app.post_form(
+            &format!("/events/{EVENT_ID}/register"),
+            &[
+                ("choice_1", "2"),
+                ("choice_2", "1"),
+                ("choice_3", "0"),
+                ("notes", ""),
+            ],
+            Some(&cookie),
+        )
+        .await;
+        assert!(registration().await.is_none());
+        app.post_form(
+            &format!("/events/{EVENT_ID}/register"),
+            &form,
+            Some(&cookie),
+        )
+        .await;
+        assert_eq!(registration().await.unwrap().choice_1, 2);
+
+        // staff can assign them to DEN_APP anyway
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let assigned = || async {
+            let positions: Vec<EventPosition> = sqlx::query_as(sql::GET_EVENT_POSITIONS)
+                .bind(EVENT_ID)
+                .fetch_all(&app.db)
+                .await
+                .unwrap();
+            positions.into_iter().find(|p| p.id == 1).unwrap().cid
+        };
+        let controller = HOME_CONTROLLER.to_string();
+        app.post_form(
+            &format!("/events/{EVENT_ID}/set_position"),
+            &[("position_id", "1"), ("controller", &controller)],
+            Some(&cookie),
+        )
+        .await;
+        assert_eq!(assigned().await, None);
+        app.post_form(
+            &format!("/events/{EVENT_ID}/set_position"),
+            &[
+                ("position_id", "1"),
+                ("controller", &controller),
+                ("override_eligibility", "on"),
+            ],
+            Some(&cookie),
+        )
+        .await;
+        assert_eq!(assigned().await, Some(HOME_CONTROLLER));
+    }
+
+    #[tokio::test]
+    async fn test_event_activity_credit() {
+        let app = test_app().await;
+        sqlx::query("UPDATE event SET start=$1, end=$2 WHERE id=$3")
+            .bind(Utc::now() - Duration::hours(3))
+            .bind(Utc::now() - Duration::minutes(30))
+            .bind(EVENT_ID)
+            .execute(&app.db)
+            .await
+            .unwrap();
+        for position_id in [1, 2] {
+            sqlx::query(sql::UPDATE_EVENT_POSITION_CONTROLLER)
+                .bind(position_id)
+                .bind(HOME_CONTROLLER)
+                .execute(&app.db)
+                .await
+                .unwrap();
+        }
+
+        // crediting twice replaces the first credit
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        for _ in 0..2 {
+            let (status, _) = app
+                .post_form(
+                    &format!("/events/{EVENT_ID}/credit_activity"),
+                    &[],
+                    Some(&cookie),
+                )
+                .await;
+            assert_eq!(status, StatusCode::SEE_OTHER);
+        }
+        let credited: Vec<(u32, u32, String)> =
+            sqlx::query_as("SELECT cid, minutes, source FROM activity WHERE event_id=$1")
+                .bind(EVENT_ID)
+                .fetch_all(&app.db)
+                .await
+                .unwrap();
+        assert_eq!(credited, vec![(HOME_CONTROLLER, 150, "event".to_owned())]);
+
+        let (_, body) = app.get("/facility/activity", None).await;
+        assert!(body.contains("events 2h30m"));
+    }
+
+    #[tokio::test]
+    async fn test_event_channels() {
+        let app = test_app().await;
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        app.post_form(
+            &format!("/events/{EVENT_ID}/add_channel"),
+            &[("name", "fno-coordination"), ("kind", "voice")],
+            Some(&cookie),
+        )
+        .await;
+        let channels: Vec<EventChannel> = sqlx::query_as(sql::GET_EVENT_CHANNELS)
+            .bind(EVENT_ID)
+            .fetch_all(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(channels.len(), 1);
+        assert!(channels[0].voice);
+        let (_, body) = app.get(&format!("/events/{EVENT_ID}"), Some(&cookie)).await;
+        assert!(body.contains("fno-coordination"));
+
+        // created channels stay until the bot deletes them
+        sqlx::query(sql::SET_EVENT_CHANNEL_CREATED)
+            .bind(channels[0].id)
+            .bind("123")
+            .bind(Utc::now())
+            .execute(&app.db)
+            .await
+            .unwrap();
+        app.post_form(
+            &format!("/events/{EVENT_ID}/delete_channel/{}", channels[0].id),
+            &[],
+            Some(&cookie),
+        )
+        .await;
+        let remaining: Vec<EventChannel> = sqlx::query_as(sql::GET_EVENT_CHANNELS)
+            .bind(EVENT_ID)
+            .fetch_all(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(remaining.len(), 1);
+    }
 }
@@ -2,11 +2,12 @@
 
 use crate::{
     flashed_messages,
-    shared::{AppError, AppState, UserInfo, SESSION_USER_INFO_KEY},
+    shared::{has_permission, AppError, AppState, UserInfo, SESSION_USER_INFO_KEY},
 };
 use axum::{
-    extract::State,
-    response::{Html, Redirect},
+    extract::{Path, Query, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
     routing::get,
     Form, Router,
 };
@@ -16,15 +17,25 @@ use log::warn;
 use minijinja::{context, Environment};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap},
+    path::Path as FilePath,
     sync::Arc,
 };
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
 use tower_sessions::Session;
 use vzdv::{
+    activity,
     config::Config,
-    determine_staff_positions,
-    sql::{self, Activity, Certification, Controller, Resource, VisitorRequest},
-    vatusa, ControllerRating,
+    determine_staff_positions, permissions,
+    repo::{RosterQuery, RosterSort, ROSTER_PAGE_SIZE},
+    resources::visibility,
+    sql::{
+        self, ActivityMonthSummary, Certification, Controller, Resource, ResourceCategory,
+        StatsEvent, StatsLeaderboardEntry, StatsMonthFeedback, StatsMonthHours, VisitorRequest,
+    },
+    staff_contacts::{self, StaffContact},
+    stats, vatusa, ControllerRating,
 };
 
 #[derive(Debug, Serialize)]
@@ -144,20 +155,55 @@ struct ControllerWithCerts<'a> {
     loa_until: Option<DateTime<Utc>>,
 }
 
-/// View the full roster.
+/// Roster page query parameters; empty values are ignored.
+#[derive(Debug, Default, Deserialize)]
+struct RosterParams {
+    rating: Option<String>,
+    /// "home" or "visiting"
+    membership: Option<String>,
+    cert: Option<String>,
+    sort: Option<String>,
+    /// "asc" or "desc"
+    order: Option<String>,
+    page: Option<u32>,
+}
+
+impl RosterParams {
+    fn to_query(&self) -> RosterQuery {
+        let value = |param: &Option<String>| param.clone().filter(|v| !v.is_empty());
+        RosterQuery {
+            rating: value(&self.rating).and_then(|rating| rating.parse().ok()),
+            home: match self.membership.as_deref() {
+                Some("home") => Some(true),
+                Some("visiting") => Some(false),
+                _ => None,
+            },
+            certification: value(&self.cert),
+            sort: self
+                .sort
+                .as_deref()
+                .and_then(RosterSort::parse)
+                .unwrap_or_default(),
+            descending: self.order.as_deref() == Some("desc"),
+            page: self.page.unwrap_or(1).max(1),
+        }
+    }
+}
+
+/// View the roster, filtered, sorted, and paged by the query parameters.
 async fn page_roster(
     State(state): State<Arc<AppState>>,
     session: Session,
+    Query(params): Query<RosterParams>,
 ) -> Result<Html<String>, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
-    let controllers: Vec<Controller> = sqlx::query_as(sql::GET_ALL_CONTROLLERS_ON_ROSTER)
-        .fetch_all(&state.db)
-        .await?;
-    let certifications: Vec<Certification> = sqlx::query_as(sql::GET_ALL_CERTIFICATIONS)
-        .fetch_all(&state.db)
-        .await?;
+    let query = params.to_query();
+    let roster = state.repos.controllers.get_roster_page(&query).await?;
+    let cids: Vec<u32> = roster.controllers.iter().map(|c| c.cid).collect();
+    let certifications: Vec<Certification> = state.repos.certifications.get_for_many(&cids).await?;
 
-    let controllers_with_certs: Vec<_> = controllers
+    let controllers_with_certs: Vec<_> = roster
+        .controllers
         .iter()
         .map(|controller| {
             let operating_initials = match &controller.operating_initials {
@@ -185,28 +231,147 @@ async fn page_roster(
                 loa_until: controller.loa_until,
             }
         })
-        .sorted_by(|a, b| Ord::cmp(&a.cid, &b.cid))
         .collect();
 
+    // filters as set, to carry over to the sort and page links
+    let filters: BTreeMap<&str, String> = [
+        ("rating", query.rating.map(|r| r.to_string())),
+        (
+            "membership",
+            query
+                .home
+                .map(|home| if home { "home" } else { "visiting" }.to_owned()),
+        ),
+        ("cert", query.certification.clone()),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| value.map(|value| (name, value)))
+    .collect();
+    let ratings: Vec<(String, &str)> = [
+        ControllerRating::OBS,
+        ControllerRating::S1,
+        ControllerRating::S2,
+        ControllerRating::S3,
+        ControllerRating::C1,
+        ControllerRating::C2,
+        ControllerRating::C3,
+        ControllerRating::I1,
+        ControllerRating::I2,
+        ControllerRating::I3,
+        ControllerRating::SUP,
+        ControllerRating::ADM,
+    ]
+    .iter()
+    .map(|rating| (rating.as_id().to_string(), rating.as_str()))
+    .collect();
+    let pages = roster.total.div_ceil(ROSTER_PAGE_SIZE).max(1);
+
     let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
     let template = state.templates.get_template("facility/roster")?;
     let rendered = template.render(context! {
        user_info,
        controllers => controllers_with_certs,
-       flashed_messages
+       flashed_messages,
+       filters,
+       ratings,
+       certifications => &state.config.training.certifications,
+       sort => query.sort.as_str(),
+       order => if query.descending { "desc" } else { "asc" },
+       page => query.page,
+       pages,
+       total => roster.total,
     })?;
     Ok(Html(rendered))
 }
 
+/// Position and the certifications that authorize it, as a matrix column.
+#[derive(Debug, Serialize)]
+struct MatrixPosition {
+    name: String,
+    certifications: Vec<String>,
+}
+
+/// Controller's standing on each matrix position, in column order.
+#[derive(Debug, Serialize)]
+struct MatrixRow {
+    cid: u32,
+    name: String,
+    operating_initials: String,
+    /// "certified", "solo", "training", or empty.
+    statuses: Vec<&'static str>,
+}
+
+/// Who can work which positions, from the controllers' certifications and the
+/// positions each certification authorizes.
+async fn page_positions(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Html<String>, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let mut positions: Vec<MatrixPosition> = Vec::new();
+    for certification in &state.config.training.certifications {
+        let Some(patterns) = state.config.training.positions.get(certification) else {
+            continue;
+        };
+        for pattern in patterns {
+            match positions.iter_mut().find(|p| &p.name == pattern) {
+                Some(existing) => existing.certifications.push(certification.clone()),
+                None => positions.push(MatrixPosition {
+                    name: pattern.clone(),
+                    certifications: vec![certification.clone()],
+                }),
+            }
+        }
+    }
+
+    let controllers: Vec<Controller> = state.repos.controllers.get_on_roster().await?;
+    let certifications: Vec<Certification> = sqlx::query_as(sql::GET_ALL_CERTIFICATIONS)
+        .fetch_all(&state.db)
+        .await?;
+    let rows: Vec<MatrixRow> = controllers
+        .iter()
+        .sorted_by(|a, b| (&a.last_name, &a.first_name).cmp(&(&b.last_name, &b.first_name)))
+        .map(|controller| {
+            let certs: Vec<&Certification> = certifications
+                .iter()
+                .filter(|cert| cert.cid == controller.cid)
+                .collect();
+            let statuses = positions
+                .iter()
+                .map(|position| {
+                    let values: Vec<&str> = certs
+                        .iter()
+                        .filter(|cert| position.certifications.contains(&cert.name))
+                        .map(|cert| cert.value.as_str())
+                        .collect();
+                    // best standing from any authorizing certification
+                    ["certified", "solo", "training"]
+                        .into_iter()
+                        .find(|status| values.contains(status))
+                        .unwrap_or_default()
+                })
+                .collect();
+            MatrixRow {
+                cid: controller.cid,
+                name: format!("{} {}", controller.first_name, controller.last_name),
+                operating_initials: controller.operating_initials.clone().unwrap_or_default(),
+                statuses,
+            }
+        })
+        .collect();
+
+    let template = state.templates.get_template("facility/positions")?;
+    let rendered = template.render(context! { user_info, positions, rows })?;
+    Ok(Html(rendered))
+}
+
 /// View the facility's staff.
 async fn page_staff(
     State(state): State<Arc<AppState>>,
     session: Session,
 ) -> Result<Html<String>, AppError> {
     let mut staff_map = generate_staff_outline(&state.config);
-    let controllers: Vec<Controller> = sqlx::query_as(sql::GET_ALL_CONTROLLERS)
-        .fetch_all(&state.db)
-        .await?;
+    let controllers: Vec<Controller> = state.repos.controllers.get_all().await?;
     for controller in &controllers {
         let roles = determine_staff_positions(controller);
         for role in roles {
@@ -224,26 +389,136 @@ async fn page_staff(
         .collect();
 
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let is_atm = user_info.as_ref().is_some_and(|user_info| {
+        staff_map["ATM"]
+            .controllers
+            .iter()
+            .any(|c| c.cid == user_info.cid)
+    });
     let template = state.templates.get_template("facility/staff")?;
-    let rendered = template.render(context! { user_info, staff })?;
+    let rendered = template.render(context! { user_info, staff, is_atm })?;
     Ok(Html(rendered))
 }
 
+/// Staff positions and their holders as contacts, in the staff page's order.
+///
+/// Returns `None` unless the user is the ATM.
+async fn staff_contacts(
+    state: &Arc<AppState>,
+    user_info: &Option<UserInfo>,
+) -> Result<Option<Vec<StaffContact>>, AppError> {
+    let Some(user_info) = user_info else {
+        return Ok(None);
+    };
+    let controllers: Vec<Controller> = state.repos.controllers.get_all().await?;
+    let is_atm = controllers
+        .iter()
+        .any(|c| c.cid == user_info.cid && determine_staff_positions(c).iter().any(|r| r == "ATM"));
+    if !is_atm {
+        return Ok(None);
+    }
+    let outline = generate_staff_outline(&state.config);
+    let mut contacts = Vec::new();
+    for position in outline.values().sorted_by_key(|position| position.order) {
+        let holders: Vec<_> = controllers
+            .iter()
+            .filter(|c| {
+                determine_staff_positions(c)
+                    .iter()
+                    .any(|r| r == position.short)
+            })
+            .sorted_by_key(|c| c.cid)
+            .collect();
+        if holders.is_empty() && position.email.is_some() {
+            contacts.push(StaffContact {
+                position: position.short.to_owned(),
+                title: position.name.to_owned(),
+                holder: None,
+                email: position.email.clone(),
+            });
+        }
+        for holder in holders {
+            contacts.push(StaffContact {
+                position: position.short.to_owned(),
+                title: position.name.to_owned(),
+                holder: Some((
+                    holder.cid,
+                    holder.first_name.clone(),
+                    holder.last_name.clone(),
+                )),
+                email: position.email.clone(),
+            });
+        }
+    }
+    Ok(Some(contacts))
+}
+
+/// Staff contacts as vCards, for the ATM to import into external contact lists.
+async fn get_staff_contacts_vcard(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let Some(contacts) = staff_contacts(&state, &user_info).await? else {
+        return Ok(Redirect::to("/facility/staff").into_response());
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/vcard; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"vzdv-staff.vcf\"",
+            ),
+        ],
+        staff_contacts::write_vcards(&contacts),
+    )
+        .into_response())
+}
+
+/// Staff contacts as CSV, for the ATM to import into external contact lists.
+async fn get_staff_contacts_csv(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let Some(contacts) = staff_contacts(&state, &user_info).await? else {
+        return Ok(Redirect::to("/facility/staff").into_response());
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"vzdv-staff.csv\"",
+            ),
+        ],
+        staff_contacts::write_csv(&contacts),
+    )
+        .into_response())
+}
+
 /// View all controller's recent (summarized) controlling activity.
 async fn page_activity(
     State(state): State<Arc<AppState>>,
     session: Session,
 ) -> Result<Html<String>, AppError> {
-    #[derive(Debug, Serialize)]
+    #[derive(Debug, Default, Serialize)]
     struct ActivityMonth {
+        /// Total minutes, from all sources.
         value: u32,
+        controlling: u32,
+        event: u32,
+        training: u32,
         position: Option<u8>,
     }
 
-    impl From<u32> for ActivityMonth {
-        fn from(value: u32) -> Self {
+    impl From<&ActivityMonthSummary> for ActivityMonth {
+        fn from(summary: &ActivityMonthSummary) -> Self {
             Self {
-                value,
+                value: summary.controlling + summary.event + summary.training,
+                controlling: summary.controlling,
+                event: summary.event,
+                training: summary.training,
                 position: None,
             }
         }
@@ -257,16 +532,13 @@ async fn page_activity(
         loa_until: Option<DateTime<Utc>>,
         rating: i8,
         months: Vec<ActivityMonth>,
+        /// Totals by source over the months the requirement covers.
+        recent: ActivityMonth,
         violation: bool,
     }
 
     // this could be a join, but oh well
-    let controllers: Vec<Controller> = sqlx::query_as(sql::GET_ALL_CONTROLLERS_ON_ROSTER)
-        .fetch_all(&state.db)
-        .await?;
-    let activity: Vec<Activity> = sqlx::query_as(sql::GET_ALL_ACTIVITY)
-        .fetch_all(&state.db)
-        .await?;
+    let controllers: Vec<Controller> = state.repos.controllers.get_on_roster().await?;
 
     // time ranges
     let now = Utc::now();
@@ -289,26 +561,47 @@ async fn page_activity(
             .format("%Y-%m")
             .to_string(),
     ];
+    let summaries: Vec<ActivityMonthSummary> = sqlx::query_as(sql::GET_ACTIVITY_SUMMARY_SINCE)
+        .bind(&months[4])
+        .fetch_all(&state.db)
+        .await?;
+    let summaries: HashMap<(u32, &str), &ActivityMonthSummary> = summaries
+        .iter()
+        .map(|summary| ((summary.cid, summary.month.as_str()), summary))
+        .collect();
+    let live_minutes = activity::live_minutes_this_month(&state.db, now).await?;
 
     // collect activity into months by controller
     let mut activity_data: Vec<ControllerActivity> = controllers
         .iter()
         .map(|controller| {
-            let this_controller: Vec<_> = activity
+            let mut months: Vec<ActivityMonth> = months
                 .iter()
-                .filter(|a| a.cid == controller.cid)
-                .collect();
-            let months: Vec<ActivityMonth> = (0..=4)
                 .map(|month| {
-                    this_controller
-                        .iter()
-                        .filter(|a| a.month == months[month])
-                        .map(|a| a.minutes)
-                        .sum::<u32>()
-                        .into()
+                    summaries
+                        .get(&(controller.cid, month.as_str()))
+                        .map(|summary| ActivityMonth::from(*summary))
+                        .unwrap_or_default()
                 })
                 .collect();
-            let violation = months.iter().take(3).map(|month| month.value).sum::<u32>() < 180; // 3 hours in a quarter
+            // sessions since the last VATSIM sync
+            if let Some(live) = live_minutes.get(&controller.cid) {
+                months[0].controlling += live;
+                months[0].value += live;
+            }
+            // controllers on an LOA are exempt from the activity requirement
+            let on_loa = controller.loa_until.is_some_and(|until| until > now);
+            let recent = months
+                .iter()
+                .take(3)
+                .fold(ActivityMonth::default(), |acc, month| ActivityMonth {
+                    value: acc.value + month.value,
+                    controlling: acc.controlling + month.controlling,
+                    event: acc.event + month.event,
+                    training: acc.training + month.training,
+                    position: None,
+                });
+            let violation = !on_loa && recent.value < activity::REQUIRED_MINUTES;
 
             ControllerActivity {
                 name: format!("{} {}", controller.first_name, controller.last_name),
@@ -320,6 +613,7 @@ async fn page_activity(
                 loa_until: controller.loa_until,
                 rating: controller.rating,
                 months,
+                recent,
                 violation,
             }
         })
@@ -347,6 +641,91 @@ async fn page_activity(
     Ok(Html(rendered))
 }
 
+/// When the stats rollups were last rebuilt, if ever.
+async fn stats_rollup_date(state: &AppState) -> Result<Option<String>, AppError> {
+    let date: Option<(String,)> = sqlx::query_as(sql::GET_SETTING)
+        .bind(stats::ROLLUP_DATE_SETTING)
+        .fetch_optional(&state.db)
+        .await?;
+    Ok(date.map(|(date,)| date))
+}
+
+/// Facility statistics over the last year, from the nightly rollups.
+///
+/// Feedback counts are only shown to staff.
+async fn page_stats(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Html<String>, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let since = Utc::now().checked_sub_months(Months::new(12)).unwrap();
+    let since_month = since.format("%Y-%m").to_string();
+    let hours: Vec<StatsMonthHours> = sqlx::query_as(sql::GET_STATS_MONTH_HOURS_SINCE)
+        .bind(&since_month)
+        .fetch_all(&state.db)
+        .await?;
+    let events: Vec<StatsEvent> = sqlx::query_as(sql::GET_STATS_EVENTS_SINCE)
+        .bind(since)
+        .fetch_all(&state.db)
+        .await?;
+    let feedback: Vec<StatsMonthFeedback> =
+        if has_permission(&state, &user_info, permissions::STAFF).await {
+            sqlx::query_as(sql::GET_STATS_MONTH_FEEDBACK_SINCE)
+                .bind(&since_month)
+                .fetch_all(&state.db)
+                .await?
+        } else {
+            Vec::new()
+        };
+    let rollup_date = stats_rollup_date(&state).await?;
+    let template = state.templates.get_template("facility/stats")?;
+    let rendered = template.render(context! {
+        user_info,
+        hours,
+        events,
+        feedback,
+        rollup_date,
+    })?;
+    Ok(Html(rendered))
+}
+
+/// Top controllers by controlling time in a month, from the nightly rollups.
+///
+/// Shows the month in the `month` query parameter, or the latest one.
+async fn page_leaderboard(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Html<String>, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let months: Vec<String> = sqlx::query_scalar(sql::GET_STATS_LEADERBOARD_MONTHS)
+        .fetch_all(&state.db)
+        .await?;
+    let month = params
+        .get("month")
+        .cloned()
+        .or_else(|| months.first().cloned());
+    let entries: Vec<StatsLeaderboardEntry> = match &month {
+        Some(month) => {
+            sqlx::query_as(sql::GET_STATS_LEADERBOARD)
+                .bind(month)
+                .fetch_all(&state.db)
+                .await?
+        }
+        None => Vec::new(),
+    };
+    let rollup_date = stats_rollup_date(&state).await?;
+    let template = state.templates.get_template("facility/leaderboard")?;
+    let rendered = template.render(context! {
+        user_info,
+        months,
+        month,
+        entries,
+        rollup_date,
+    })?;
+    Ok(Html(rendered))
+}
+
 /// View files uploaded to the site.
 async fn page_resources(
     State(state): State<Arc<AppState>>,
@@ -360,27 +739,171 @@ async fn page_resources(
         .sorted_by(|a, b| a.name.cmp(&b.name))
         .collect();
 
-    let categories: Vec<_> = resources
-        .iter()
-        .map(|r| &r.category)
-        .collect::<HashSet<_>>()
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+
+    // categories in their set order, skipping archived, empty, and restricted ones
+    let all_categories: Vec<ResourceCategory> = sqlx::query_as(sql::GET_RESOURCE_CATEGORIES)
+        .fetch_all(&state.db)
+        .await?;
+    let mut categories = Vec::new();
+    for category in all_categories {
+        if !category.archived
+            && resources.iter().any(|r| r.category == category.name)
+            && can_view_resources(&state, &user_info, &category.visibility).await?
+        {
+            categories.push(category.name);
+        }
+    }
+    let resources: Vec<_> = resources
         .into_iter()
-        .sorted()
-        .collect();
-    let categories: Vec<_> = state
-        .config
-        .database
-        .resource_category_ordering
-        .iter()
-        .filter(|category| categories.contains(category))
+        .filter(|r| categories.contains(&r.category))
         .collect();
 
-    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
     let template = state.templates.get_template("facility/resources")?;
-    let rendered = template.render(context! { user_info, resources, categories })?;
+    let rendered =
+        template.render(context! { user_info, flashed_messages, resources, categories })?;
     Ok(Html(rendered))
 }
 
+/// The resource, if it exists and the user can see its category.
+async fn get_viewable_resource(
+    state: &Arc<AppState>,
+    user_info: &Option<UserInfo>,
+    id: u32,
+) -> Result<Option<Resource>, AppError> {
+    let resource: Option<Resource> = sqlx::query_as(sql::GET_RESOURCE_BY_ID)
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?;
+    let Some(resource) = resource else {
+        return Ok(None);
+    };
+    let category_visibility: Option<String> =
+        sqlx::query_scalar(sql::GET_RESOURCE_VISIBILITY_BY_ID)
+            .bind(id)
+            .fetch_optional(&state.db)
+            .await?;
+    if let Some(category_visibility) = category_visibility {
+        if !can_view_resources(state, user_info, &category_visibility).await? {
+            return Ok(None);
+        }
+    }
+    Ok(Some(resource))
+}
+
+/// Uploaded file's original name, without the prefix added to keep names unique.
+fn display_file_name(file_name: &str) -> &str {
+    file_name
+        .split_once('_')
+        .map_or(file_name, |(_, original)| original)
+}
+
+/// Page for a single resource, with an inline viewer for PDFs.
+///
+/// Link resources redirect to their destination.
+async fn page_resource(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path(id): Path<u32>,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let Some(resource) = get_viewable_resource(&state, &user_info, id).await? else {
+        flashed_messages::push_flashed_message(
+            session,
+            flashed_messages::MessageLevel::Error,
+            "Resource not found",
+        )
+        .await?;
+        return Ok(Redirect::to("/facility/resources").into_response());
+    };
+    let Some(file_name) = &resource.file_name else {
+        sqlx::query(sql::RECORD_RESOURCE_DOWNLOAD)
+            .bind(id)
+            .bind(Utc::now())
+            .execute(&state.db)
+            .await?;
+        return Ok(
+            Redirect::to(resource.link.as_deref().unwrap_or("/facility/resources")).into_response(),
+        );
+    };
+    let display_name = display_file_name(file_name);
+    let is_pdf = display_name.to_lowercase().ends_with(".pdf");
+    let template = state.templates.get_template("facility/resource")?;
+    let rendered = template.render(context! { user_info, resource, display_name, is_pdf })?;
+    Ok(Html(rendered).into_response())
+}
+
+/// A resource's file, served to be shown in the browser rather than downloaded.
+async fn get_resource_file(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path(id): Path<u32>,
+    request: Request,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let file_name = get_viewable_resource(&state, &user_info, id)
+        .await?
+        .and_then(|resource| resource.file_name);
+    // names come from uploads, so make sure they can't point outside the directory
+    let Some(file_name) = file_name.filter(|name| !name.contains(['/', '\\']) && name != "..")
+    else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let mut response = ServeFile::new(FilePath::new("assets").join(&file_name))
+        .oneshot(request)
+        .await
+        .unwrap_or_else(|e| match e {})
+        .into_response();
+    // partial and not-modified responses are continuations of an earlier view
+    if response.status() == StatusCode::OK {
+        sqlx::query(sql::RECORD_RESOURCE_DOWNLOAD)
+            .bind(id)
+            .bind(Utc::now())
+            .execute(&state.db)
+            .await?;
+    }
+    let disposition = format!(
+        "inline; filename=\"{}\"",
+        display_file_name(&file_name).replace('"', "")
+    );
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&disposition).unwrap_or(HeaderValue::from_static("inline")),
+    );
+    headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    Ok(response)
+}
+
+/// Whether the user can see resources with the category visibility.
+///
+/// Unknown visibility values are treated as the most restricted.
+pub async fn can_view_resources(
+    state: &Arc<AppState>,
+    user_info: &Option<UserInfo>,
+    category_visibility: &str,
+) -> Result<bool, AppError> {
+    let allowed = match category_visibility {
+        visibility::PUBLIC => true,
+        visibility::ROSTER => match user_info {
+            Some(info) => state
+                .repos
+                .controllers
+                .get_by_cid(info.cid)
+                .await?
+                .is_some_and(|controller| controller.is_on_roster),
+            None => false,
+        },
+        visibility::STAFF => has_permission(state, user_info, permissions::STAFF).await,
+        _ => has_permission(state, user_info, permissions::TRAINING_MANAGE).await,
+    };
+    Ok(allowed)
+}
+
 /// Check visitor requirements and submit an application.
 async fn page_visitor_application(
     State(state): State<Arc<AppState>>,
@@ -389,10 +912,8 @@ async fn page_visitor_application(
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
     let controller: Option<Controller> = match user_info {
         Some(ref info) => {
-            let controller: Option<Controller> = sqlx::query_as(sql::GET_CONTROLLER_BY_CID)
-                .bind(info.cid)
-                .fetch_optional(&state.db)
-                .await?;
+            let controller: Option<Controller> =
+                state.repos.controllers.get_by_cid(info.cid).await?;
             controller
         }
         None => None,
@@ -426,7 +947,7 @@ async fn page_visitor_application_form(
         .fetch_optional(&state.db)
         .await?;
     // check rating
-    let controller_info = match vatusa::get_controller_info(user_info.cid, None).await {
+    let controller_info = match vatusa::get_controller_info_cached(user_info.cid).await {
         Ok(info) => Some(info),
         Err(e) => {
             warn!("{e}");
@@ -520,6 +1041,12 @@ pub fn router(templates: &mut Environment) -> Router<Arc<AppState>> {
             include_str!("../../templates/facility/resources.jinja"),
         )
         .unwrap();
+    templates
+        .add_template(
+            "facility/resource",
+            include_str!("../../templates/facility/resource.jinja"),
+        )
+        .unwrap();
     templates
         .add_template(
             "facility/visitor_application",
@@ -532,27 +1059,40 @@ pub fn router(templates: &mut Environment) -> Router<Arc<AppState>> {
             include_str!("../../templates/facility/visitor_application_form.jinja"),
         )
         .unwrap();
-    templates.add_filter("minutes_to_hm", |total_minutes: u32| {
-        let hours = total_minutes / 60;
-        let minutes = total_minutes % 60;
-        if hours > 0 || minutes > 0 {
-            format!("{hours}h{minutes}m")
-        } else {
-            String::new()
-        }
-    });
-    templates.add_filter("simple_date", |date: String| {
-        chrono::DateTime::parse_from_rfc3339(&date)
-            .unwrap()
-            .format("%m/%d/%Y")
-            .to_string()
-    });
+    templates
+        .add_template(
+            "facility/stats",
+            include_str!("../../templates/facility/stats.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "facility/positions",
+            include_str!("../../templates/facility/positions.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "facility/leaderboard",
+            include_str!("../../templates/facility/leaderboard.jinja"),
+        )
+        .unwrap();
 
     Router::new()
         .route("/facility/roster", get(page_roster))
+        .route(
+            "/facility/staff/contacts.vcf",
+            get(get_staff_contacts_vcard),
+        )
+        .route("/facility/staff/contacts.csv", get(get_staff_contacts_csv))
+        .route("/facility/positions", get(page_positions))
         .route("/facility/staff", get(page_staff))
         .route("/facility/activity", get(page_activity))
+        .route("/facility/stats", get(page_stats))
+        .route("/facility/leaderboard", get(page_leaderboard))
         .route("/facility/resources", get(page_resources))
+        .route("/facility/resources/:id", get(page_resource))
+        .route("/facility/resources/:id/file", get(get_resource_file))
         .route(
             "/facility/visitor_application",
             get(page_visitor_application),
@@ -562,3 +1102,172 @@ pub fn router(templates: &mut Environment) -> Router<Arc<AppState>> {
             get(page_visitor_application_form).post(page_visitor_application_form_submit),
         )
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::display_file_name;
+    use crate::test_utils::{test_app, ADMIN_CONTROLLER, HOME_CONTROLLER};
+    use axum::http::StatusCode;
+    use chrono::Utc;
+    use vzdv::sql;
+
+    #[test]
+    fn test_display_file_name() {
+        assert_eq!(
+            display_file_name("0b0e7a8c-3f3e-4b1a-9d2e-1f6f0a8b9c7d_Ground SOP.pdf"),
+            "Ground SOP.pdf"
+        );
+        assert_eq!(display_file_name("checklist.pdf"), "checklist.pdf");
+    }
+
+    #[tokio::test]
+    async fn test_resource_viewer() {
+        let app = test_app().await;
+        for (category, visibility) in [("SOP", "public"), ("OTS", "training_staff")] {
+            sqlx::query(sql::CREATE_RESOURCE_CATEGORY)
+                .bind(category)
+                .execute(&app.db)
+                .await
+                .unwrap();
+            sqlx::query("UPDATE resource_category SET visibility=$2 WHERE name=$1")
+                .bind(category)
+                .bind(visibility)
+                .execute(&app.db)
+                .await
+                .unwrap();
+        }
+        for (category, name, file_name, link) in [
+            ("SOP", "Ground SOP", Some("1_Ground SOP.pdf"), None),
+            ("SOP", "Charts", None, Some("https://example.com/charts")),
+            ("OTS", "OTS checklist", Some("2_checklist.pdf"), None),
+        ] {
+            sqlx::query(sql::CREATE_NEW_RESOURCE)
+                .bind(category)
+                .bind(name)
+                .bind(file_name)
+                .bind(link)
+                .bind(Utc::now())
+                .execute(&app.db)
+                .await
+                .unwrap();
+        }
+
+        let (_, body) = app.get("/facility/resources", None).await;
+        assert!(body.contains("href=\"/facility/resources/1\""));
+        let (status, body) = app.get("/facility/resources/1", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("<iframe src=\"/facility/resources/1/file\""));
+        let (status, _) = app.get("/facility/resources/2", None).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let resource: sql::Resource = sqlx::query_as(sql::GET_RESOURCE_BY_ID)
+            .bind(2)
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(resource.download_count, 1);
+        assert!(resource.last_downloaded.is_some());
+
+        // restricted categories are hidden from those who can't see them
+        let (status, body) = app.get("/facility/resources/3", None).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        assert!(!body.contains("OTS checklist"));
+        let (status, _) = app.get("/facility/resources/3/file", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let (status, body) = app.get("/facility/resources/3", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("OTS checklist"));
+
+        // viewing the page doesn't count as a download
+        let (_, body) = app.get("/admin/resources", Some(&cookie)).await;
+        assert_eq!(body.matches("Never").count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_activity_breakdown() {
+        let app = test_app().await;
+        let month = Utc::now().format("%Y-%m").to_string();
+        sqlx::query(
+            "INSERT INTO activity (id, cid, month, minutes, source) VALUES (NULL, $1, $2, 60, 'training')",
+        )
+        .bind(HOME_CONTROLLER)
+        .bind(&month)
+        .execute(&app.db)
+        .await
+        .unwrap();
+
+        let (_, body) = app.get("/facility/activity", None).await;
+        assert!(body.contains("instructing 1h0m\""));
+        assert!(!body.contains("Potential activity violation"));
+
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let (_, body) = app.get("/facility/activity", Some(&cookie)).await;
+        assert!(body.contains("Potential activity violation: 1h0m in the last 3 months (controlling 0m, events 0m, instructing 1h0m)"));
+    }
+
+    #[tokio::test]
+    async fn test_roster_filters() {
+        let app = test_app().await;
+        sqlx::query("UPDATE controller SET home_facility='ZTL', rating=3 WHERE cid=$1")
+            .bind(ADMIN_CONTROLLER)
+            .execute(&app.db)
+            .await
+            .unwrap();
+        sqlx::query(sql::CREATE_CERTIFICATION)
+            .bind(HOME_CONTROLLER)
+            .bind("GC T1")
+            .bind("certified")
+            .bind(Utc::now())
+            .bind(ADMIN_CONTROLLER)
+            .execute(&app.db)
+            .await
+            .unwrap();
+
+        let (status, body) = app.get("/facility/roster", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("2 controllers"));
+        assert!(body.contains("Home Controller"));
+        assert!(body.contains("Admin Controller"));
+
+        let (_, body) = app
+            .get("/facility/roster?membership=visiting&rating=&cert=", None)
+            .await;
+        assert!(body.contains("1 controller<"));
+        assert!(body.contains("Admin Controller"));
+        assert!(!body.contains("Home Controller"));
+
+        let (_, body) = app.get("/facility/roster?cert=GC+T1&rating=5", None).await;
+        assert!(body.contains("Home Controller"));
+        assert!(!body.contains("Admin Controller"));
+        // filters carry over to the sort links
+        assert!(body.contains("?cert=GC%20T1&rating=5&sort=name&order=asc"));
+
+        let (_, body) = app.get("/facility/roster?rating=1", None).await;
+        assert!(body.contains("No controllers match"));
+    }
+
+    #[tokio::test]
+    async fn test_staff_contacts_export() {
+        let app = test_app().await;
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, _) = app.get("/facility/staff/contacts.csv", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let (_, body) = app.get("/facility/staff", Some(&cookie)).await;
+        assert!(!body.contains("Export staff contacts"));
+
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let (_, body) = app.get("/facility/staff", Some(&cookie)).await;
+        assert!(body.contains("Export staff contacts"));
+        let (status, body) = app.get("/facility/staff/contacts.csv", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.starts_with("Position,Title,CID,First name,Last name,Email\r\n"));
+        assert!(body.contains(&format!(
+            "ATM,Air Traffic Manager,{ADMIN_CONTROLLER},Admin,Controller,atm@"
+        )));
+        // vacant, but the alias is kept
+        assert!(body.contains("\r\nDATM,Deputy Air Traffic Manager,,,,datm@"));
+        let (status, body) = app.get("/facility/staff/contacts.vcf", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("FN:Admin Controller\r\nN:Controller;Admin;;;\r\n"));
+    }
+}
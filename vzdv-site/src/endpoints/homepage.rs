@@ -1,24 +1,35 @@
 //! HTTP endpoints for the homepage.
 
 use crate::{
-    flashed_messages,
-    shared::{AppError, AppState, CacheEntry, UserInfo, SESSION_USER_INFO_KEY},
+    endpoints::airspace::{get_vatsim_data, get_weather, VATSIM_DATA_TTL, WEATHER_TTL},
+    flashed_messages, live,
+    shared::{snippet_response, AppError, AppState, UserInfo, SESSION_USER_INFO_KEY},
+};
+use axum::{
+    extract::State,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Html, Response,
+    },
+    routing::get,
+    Router,
 };
-use axum::{extract::State, response::Html, routing::get, Router};
 use chrono::Utc;
-use log::warn;
+use futures_util::Stream;
+use itertools::Itertools;
 use minijinja::{context, Environment};
 use serde::Serialize;
-use std::{sync::Arc, time::Instant};
+use std::{convert::Infallible, sync::Arc, time::Duration};
 use tower_sessions::Session;
-use vatsim_utils::live_api::Vatsim;
 use vzdv::{
-    aviation::parse_metar,
+    aviation::AirportWeather,
     sql::{self, Activity},
-    vatsim::get_online_facility_controllers,
-    GENERAL_HTTP_CLIENT,
+    vatsim::{online_facility_controllers, OnlineController, POSITION_TYPES},
 };
 
+/// How long to reuse online controllers and the controllers of the month.
+const ONLINE_TTL: Duration = Duration::from_secs(60);
+
 /// Homepage.
 async fn page_home(
     State(state): State<Arc<AppState>>,
@@ -31,75 +42,106 @@ async fn page_home(
     Ok(Html(rendered))
 }
 
-/// Render a list of online controllers.
+/// Render the list of online controllers, grouped by position type.
+///
+/// The rendered list is cached, since it's also pushed to every open homepage.
+async fn render_online_controllers(state: &Arc<AppState>) -> Result<Arc<String>, AppError> {
+    state
+        .cache
+        .get_or_try_insert("ONLINE_CONTROLLERS_HTML", ONLINE_TTL, || async {
+            let online = state
+                .cache
+                .get_or_try_insert("ONLINE_CONTROLLERS", ONLINE_TTL, || async {
+                    let data = get_vatsim_data(state).await?;
+                    Ok::<_, AppError>(
+                        online_facility_controllers(&state.db, &state.config, &data).await,
+                    )
+                })
+                .await?;
+            let groups: Vec<(&str, Vec<&OnlineController>)> = POSITION_TYPES
+                .iter()
+                .map(|position_type| {
+                    let controllers: Vec<_> = online
+                        .iter()
+                        .filter(|controller| controller.position_type == *position_type)
+                        .sorted_by(|a, b| a.callsign.cmp(&b.callsign))
+                        .collect();
+                    (*position_type, controllers)
+                })
+                .filter(|(_, controllers)| !controllers.is_empty())
+                .collect();
+            let template = state
+                .templates
+                .get_template("homepage/online_controllers")?;
+            Ok::<_, AppError>(template.render(context! { groups })?)
+        })
+        .await
+}
+
+/// Render a list of online controllers, grouped by position type.
 async fn snippet_online_controllers(
     State(state): State<Arc<AppState>>,
-) -> Result<Html<String>, AppError> {
-    // cache this endpoint's returned data for 60 seconds
-    let cache_key = "ONLINE_CONTROLLERS";
-    if let Some(cached) = state.cache.get(&cache_key) {
-        let elapsed = Instant::now() - cached.inserted;
-        if elapsed.as_secs() < 60 {
-            return Ok(Html(cached.data));
-        }
-        state.cache.invalidate(&cache_key);
-    }
+) -> Result<Response, AppError> {
+    let rendered = render_online_controllers(&state).await?;
+    Ok(snippet_response(rendered.to_string(), ONLINE_TTL, false))
+}
 
-    let online = get_online_facility_controllers(&state.db, &state.config)
-        .await
-        .map_err(|error| AppError::GenericFallback("getting online controllers", error))?;
-    let template = state
-        .templates
-        .get_template("homepage/online_controllers")?;
-    let rendered = template.render(context! { online })?;
-    state
-        .cache
-        .insert(cache_key, CacheEntry::new(rendered.clone()));
-    Ok(Html(rendered))
+/// Server-sent events of the online controllers list whenever it changes,
+/// so the homepage stays current without reloading.
+async fn live_online_controllers(
+    State(state): State<Arc<AppState>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    Sse::new(live::updates("online_controllers", ONLINE_TTL, move || {
+        let state = state.clone();
+        async move { Ok(render_online_controllers(&state).await?.to_string()) }
+    }))
+    .keep_alive(KeepAlive::default())
 }
 
-async fn snippet_weather(State(state): State<Arc<AppState>>) -> Result<Html<String>, AppError> {
-    // cache this endpoint's returned data for 5 minutes
-    let cache_key = "WEATHER_BRIEF";
-    if let Some(cached) = state.cache.get(&cache_key) {
-        let elapsed = Instant::now() - cached.inserted;
-        if elapsed.as_secs() < 300 {
-            return Ok(Html(cached.data));
-        }
-        state.cache.invalidate(&cache_key);
+/// Render the weather for each of the configured airport groups.
+async fn snippet_weather(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    #[derive(Serialize)]
+    struct WeatherGroupDisplay<'a> {
+        name: &'a str,
+        airports: Vec<&'a AirportWeather>,
     }
 
-    let resp = GENERAL_HTTP_CLIENT
-        .get(format!(
-            "https://metar.vatsim.net/{}",
-            state.config.airports.weather_for.join(",")
-        ))
-        .send()
-        .await?;
-    if !resp.status().is_success() {
-        return Err(AppError::HttpResponse("METAR API", resp.status().as_u16()));
-    }
-    let text = resp.text().await?;
-    let weather: Vec<_> = text
-        .split_terminator('\n')
-        .flat_map(|line| {
-            parse_metar(line).map_err(|e| {
-                let airport = line.split(' ').next().unwrap_or("Unknown");
-                warn!("METAR parsing failure for {airport}: {e}");
-                e
-            })
+    // one fetch (and cache entry) for every group's airports
+    let airports: Vec<_> = state
+        .config
+        .airports
+        .weather_groups
+        .iter()
+        .flat_map(|group| group.airports.iter().map(String::as_str))
+        .unique()
+        .collect();
+    let weather = if airports.is_empty() {
+        Arc::new(Vec::new())
+    } else {
+        get_weather(&state, &airports).await?
+    };
+    let groups: Vec<_> = state
+        .config
+        .airports
+        .weather_groups
+        .iter()
+        .map(|group| WeatherGroupDisplay {
+            name: &group.name,
+            airports: group
+                .airports
+                .iter()
+                .filter_map(|code| weather.iter().find(|w| &w.name == code))
+                .collect(),
         })
+        .filter(|group| !group.airports.is_empty())
         .collect();
 
     let template = state.templates.get_template("homepage/weather")?;
-    let rendered = template.render(context! { weather })?;
-    state
-        .cache
-        .insert(cache_key, CacheEntry::new(rendered.clone()));
-    Ok(Html(rendered))
+    let rendered = template.render(context! { groups })?;
+    Ok(snippet_response(rendered, WEATHER_TTL, false))
 }
 
-async fn snippet_flights(State(state): State<Arc<AppState>>) -> Result<Html<String>, AppError> {
+async fn snippet_flights(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
     #[derive(Serialize, Default)]
     struct OnlineFlights {
         within: u16,
@@ -107,16 +149,6 @@ async fn snippet_flights(State(state): State<Arc<AppState>>) -> Result<Html<Stri
         to: u16,
     }
 
-    // cache this endpoint's returned data for 60 seconds
-    let cache_key = "ONLINE_FLIGHTS_HOMEPAGE";
-    if let Some(cached) = state.cache.get(&cache_key) {
-        let elapsed = Instant::now() - cached.inserted;
-        if elapsed.as_secs() < 60 {
-            return Ok(Html(cached.data));
-        }
-        state.cache.invalidate(&cache_key);
-    }
-
     let artcc_fields: Vec<_> = state
         .config
         .airports
@@ -124,7 +156,7 @@ async fn snippet_flights(State(state): State<Arc<AppState>>) -> Result<Html<Stri
         .iter()
         .map(|airport| &airport.code)
         .collect();
-    let data = Vatsim::new().await?.get_v3_data().await?;
+    let data = get_vatsim_data(&state).await?;
     let flights: OnlineFlights =
         data.pilots
             .iter()
@@ -144,23 +176,10 @@ async fn snippet_flights(State(state): State<Arc<AppState>>) -> Result<Html<Stri
 
     let template = state.templates.get_template("homepage/flights")?;
     let rendered = template.render(context! { flights })?;
-    state
-        .cache
-        .insert(cache_key, CacheEntry::new(rendered.clone()));
-    Ok(Html(rendered))
+    Ok(snippet_response(rendered, VATSIM_DATA_TTL, false))
 }
 
-async fn snippet_cotm(State(state): State<Arc<AppState>>) -> Result<Html<String>, AppError> {
-    // cache this endpoint's returned data for 1 minute
-    let cache_key = "COTM";
-    if let Some(cached) = state.cache.get(&cache_key) {
-        let elapsed = Instant::now() - cached.inserted;
-        if elapsed.as_secs() < 60 {
-            return Ok(Html(cached.data));
-        }
-        state.cache.invalidate(&cache_key);
-    }
-
+async fn snippet_cotm(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
     #[derive(Serialize)]
     struct CotmEntry {
         name: String,
@@ -168,27 +187,30 @@ async fn snippet_cotm(State(state): State<Arc<AppState>>) -> Result<Html<String>
         minutes: u32,
     }
 
-    let this_month = Utc::now().format("%Y-%m").to_string();
-    let activity: Vec<Activity> = sqlx::query_as(sql::GET_ACTIVITY_IN_MONTH)
-        .bind(this_month)
-        .fetch_all(&state.db)
-        .await?;
-    let cotm: Vec<_> = activity
-        .iter()
-        .take(3)
-        .map(|activity| CotmEntry {
-            name: format!("{} {}", activity.first_name, activity.last_name),
-            hours: activity.minutes / 60,
-            minutes: activity.minutes % 60,
+    let cotm = state
+        .cache
+        .get_or_try_insert("COTM", ONLINE_TTL, || async {
+            let this_month = Utc::now().format("%Y-%m").to_string();
+            let activity: Vec<Activity> = sqlx::query_as(sql::GET_ACTIVITY_IN_MONTH)
+                .bind(this_month)
+                .fetch_all(&state.db)
+                .await?;
+            let cotm: Vec<_> = activity
+                .iter()
+                .take(3)
+                .map(|activity| CotmEntry {
+                    name: format!("{} {}", activity.first_name, activity.last_name),
+                    hours: activity.minutes / 60,
+                    minutes: activity.minutes % 60,
+                })
+                .collect();
+            Ok::<_, AppError>(cotm)
         })
-        .collect();
+        .await?;
 
     let template = state.templates.get_template("homepage/cotm")?;
     let rendered = template.render(context! { cotm })?;
-    state
-        .cache
-        .insert(cache_key, CacheEntry::new(rendered.clone()));
-    Ok(Html(rendered))
+    Ok(snippet_response(rendered, ONLINE_TTL, false))
 }
 
 /// This file's routes and templates.
@@ -227,6 +249,10 @@ pub fn router(templates: &mut Environment) -> Router<Arc<AppState>> {
     Router::new()
         .route("/", get(page_home))
         .route("/home/online/controllers", get(snippet_online_controllers))
+        .route(
+            "/home/online/controllers/live",
+            get(live_online_controllers),
+        )
         .route("/home/online/flights", get(snippet_flights))
         .route("/home/weather", get(snippet_weather))
         .route("/home/cotm", get(snippet_cotm))
@@ -1,31 +1,48 @@
 //! HTTP endpoints.
 
 use crate::{
+    endpoints::facility::can_view_resources,
     flashed_messages,
-    shared::{AppError, AppState, UserInfo, SESSION_USER_INFO_KEY},
+    shared::{
+        take_recent_error, AppError, AppState, UserInfo, ERROR_WEBHOOK, SESSION_USER_INFO_KEY,
+    },
 };
 use axum::{
-    extract::State,
-    response::{Html, Redirect},
+    extract::{Path, Request, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
     routing::{get, post},
     Form, Router,
 };
-use log::info;
+use chrono::Utc;
+use log::{info, warn};
 use minijinja::{context, Environment};
 use serde::Deserialize;
-use std::sync::Arc;
-use tower_http::services::ServeDir;
+use std::{
+    path::{Component, Path as FilePath},
+    sync::Arc,
+    time::Duration,
+};
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
 use tower_sessions::Session;
-use vzdv::sql::{self, Controller};
+use vzdv::{
+    error_reporting,
+    sql::{self, Banner, Controller, ExitSurvey},
+};
 
 pub mod admin;
 pub mod airspace;
+pub mod api;
 pub mod auth;
 pub mod controller;
 pub mod events;
 pub mod facility;
 pub mod homepage;
+pub mod oauth;
+pub mod training;
 pub mod user;
+pub mod webhooks;
 
 /// 404 not found page.
 ///
@@ -36,6 +53,78 @@ pub async fn page_404(State(state): State<Arc<AppState>>) -> Result<Html<String>
     Ok(Html(rendered))
 }
 
+/// Cache key for the active site banners.
+pub const BANNERS_CACHE_KEY: &str = "BANNERS";
+
+/// Render the active site banners, for the top of every page.
+async fn snippet_banners(State(state): State<Arc<AppState>>) -> Result<Html<String>, AppError> {
+    let banners = state
+        .cache
+        .get_or_try_insert(BANNERS_CACHE_KEY, Duration::from_secs(60), || async {
+            let banners: Vec<Banner> = sqlx::query_as(sql::GET_ACTIVE_BANNERS)
+                .bind(Utc::now())
+                .fetch_all(&state.db)
+                .await?;
+            Ok::<_, AppError>(banners)
+        })
+        .await?;
+    let template = state.templates.get_template("banners")?;
+    let rendered = template.render(context! { banners })?;
+    Ok(Html(rendered))
+}
+
+/// Serve a file from the assets directory.
+///
+/// Files uploaded for resources are only served to users who can see the
+/// resource's category.
+async fn get_asset(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path(file_name): Path<String>,
+    request: Request,
+) -> Result<Response, AppError> {
+    // the directory is flat, so anything but a plain file name (like "./name"
+    // or "/name") is rejected rather than normalized past the visibility check
+    let mut components = FilePath::new(&file_name).components();
+    let file_name = match (components.next(), components.next()) {
+        (Some(Component::Normal(name)), None) => match name.to_str() {
+            Some(name) => name.to_owned(),
+            None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        },
+        _ => return Ok(StatusCode::NOT_FOUND.into_response()),
+    };
+    let category_visibility: Option<String> =
+        sqlx::query_scalar(sql::GET_RESOURCE_VISIBILITY_BY_FILE_NAME)
+            .bind(&file_name)
+            .fetch_optional(&state.db)
+            .await?;
+    if let Some(category_visibility) = category_visibility {
+        let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+        if !can_view_resources(&state, &user_info, &category_visibility).await? {
+            warn!(
+                "{} denied access to asset {file_name}",
+                user_info.map_or_else(|| "Anonymous user".to_owned(), |info| info.cid.to_string())
+            );
+            return Ok(StatusCode::FORBIDDEN.into_response());
+        }
+    }
+
+    // the file service handles content types and ranges
+    let response = ServeFile::new(FilePath::new("assets").join(&file_name))
+        .oneshot(request)
+        .await
+        .unwrap_or_else(|e| match e {});
+    // partial and not-modified responses are continuations of an earlier download
+    if response.status() == StatusCode::OK {
+        sqlx::query(sql::RECORD_RESOURCE_FILE_DOWNLOAD)
+            .bind(&file_name)
+            .bind(Utc::now())
+            .execute(&state.db)
+            .await?;
+    }
+    Ok(response.into_response())
+}
+
 /// View the feedback form.
 ///
 /// The template handles requiring the user to be logged in.
@@ -45,9 +134,7 @@ async fn page_feedback_form(
 ) -> Result<Html<String>, AppError> {
     let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
     let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
-    let mut all_controllers: Vec<Controller> = sqlx::query_as(sql::GET_ALL_CONTROLLERS_ON_ROSTER)
-        .fetch_all(&state.db)
-        .await?;
+    let mut all_controllers: Vec<Controller> = state.repos.controllers.get_on_roster().await?;
     all_controllers.sort_by_key(|c| format!("{} {}", c.first_name, c.last_name));
     let all_controllers: Vec<(u32, String)> = all_controllers
         .iter()
@@ -109,6 +196,162 @@ async fn page_feedback_form_post(
     Ok(Redirect::to("/feedback"))
 }
 
+#[derive(Debug, Deserialize)]
+struct ErrorReportForm {
+    request_id: String,
+    page: String,
+    notes: String,
+}
+
+/// Most characters of user notes to include in an error report.
+const ERROR_REPORT_NOTES_MAX: usize = 1_000;
+
+/// Report an error from the error page to staff, with the user's notes.
+///
+/// Only errors the site recently showed can be reported, and only once each.
+async fn post_error_report(
+    session: Session,
+    Form(report): Form<ErrorReportForm>,
+) -> Result<Redirect, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let Some(error) = take_recent_error(&report.request_id) else {
+        flashed_messages::push_flashed_message(
+            session,
+            flashed_messages::MessageLevel::Info,
+            "That error has already been reported or is too old to report",
+        )
+        .await?;
+        return Ok(Redirect::to("/"));
+    };
+    let reporter = match &user_info {
+        Some(info) => format!("{} {} ({})", info.first_name, info.last_name, info.cid),
+        None => "an anonymous user".to_owned(),
+    };
+    let notes: String = report
+        .notes
+        .trim()
+        .chars()
+        .take(ERROR_REPORT_NOTES_MAX)
+        .collect();
+    let mut message = format!(
+        "Error reported by {reporter} on `{}`, status {}: {} (request {})",
+        report.page.chars().take(200).collect::<String>(),
+        error.status,
+        error.message,
+        error.request_id
+    );
+    if !notes.is_empty() {
+        message.push_str(&format!("\nNotes: {notes}"));
+    }
+    if let Some(url) = ERROR_WEBHOOK.get() {
+        error_reporting::post_to_webhook(url, &message).await;
+    }
+    info!(
+        "Error for request {} reported by {reporter}",
+        error.request_id
+    );
+    flashed_messages::push_flashed_message(
+        session,
+        flashed_messages::MessageLevel::Success,
+        "Thanks, the error has been reported to staff",
+    )
+    .await?;
+    Ok(Redirect::to("/"))
+}
+
+/// How long exit survey links work after they're sent.
+const EXIT_SURVEY_DAYS: i64 = 30;
+
+/// Most characters kept from exit survey comments.
+const EXIT_SURVEY_COMMENTS_MAX: usize = 5_000;
+
+/// Choices for why a controller left the facility.
+const EXIT_SURVEY_REASONS: [&str; 7] = [
+    "Transferring to another facility",
+    "Taking a break from VATSIM",
+    "Not enough time",
+    "Training",
+    "Activity requirements",
+    "Community",
+    "Other",
+];
+
+/// Get the exit survey for the token, if the link hasn't expired.
+async fn exit_survey_for(state: &AppState, token: &str) -> Result<Option<ExitSurvey>, AppError> {
+    let survey: Option<ExitSurvey> = sqlx::query_as(sql::GET_EXIT_SURVEY_BY_TOKEN)
+        .bind(token)
+        .fetch_optional(&state.db)
+        .await?;
+    Ok(survey.filter(|survey| {
+        survey
+            .email_sent_date
+            .is_some_and(|sent| Utc::now() - sent < chrono::Duration::days(EXIT_SURVEY_DAYS))
+    }))
+}
+
+/// Exit survey for a controller who left the roster.
+///
+/// The link's token identifies the survey, so no login is needed.
+async fn page_exit_survey(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path(token): Path<String>,
+) -> Result<Html<String>, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let survey = exit_survey_for(&state, &token).await?;
+    let template = state.templates.get_template("exit_survey")?;
+    let rendered = template.render(context! {
+        user_info,
+        survey,
+        token,
+        reasons => EXIT_SURVEY_REASONS,
+        comments_max => EXIT_SURVEY_COMMENTS_MAX,
+    })?;
+    Ok(Html(rendered))
+}
+
+#[derive(Debug, Deserialize)]
+struct ExitSurveyForm {
+    rating: u8,
+    reason: String,
+    comments: String,
+}
+
+/// Submit an exit survey; each can only be submitted once.
+async fn post_exit_survey(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+    Form(survey_form): Form<ExitSurveyForm>,
+) -> Result<Redirect, AppError> {
+    let redirect = Redirect::to(&format!("/exit_survey/{token}"));
+    let Some(survey) = exit_survey_for(&state, &token).await? else {
+        return Ok(redirect);
+    };
+    let reason = if EXIT_SURVEY_REASONS.contains(&survey_form.reason.as_str()) {
+        survey_form.reason
+    } else {
+        "Other".to_owned()
+    };
+    let comments: String = survey_form
+        .comments
+        .trim()
+        .chars()
+        .take(EXIT_SURVEY_COMMENTS_MAX)
+        .collect();
+    let result = sqlx::query(sql::SUBMIT_EXIT_SURVEY)
+        .bind(survey.id)
+        .bind(Utc::now())
+        .bind(survey_form.rating.clamp(1, 5))
+        .bind(reason)
+        .bind(comments)
+        .execute(&state.db)
+        .await?;
+    if result.rows_affected() > 0 {
+        info!("{} submitted exit survey {}", survey.cid, survey.id);
+    }
+    Ok(redirect)
+}
+
 /// This file's routes and templates.
 pub fn router(templates: &mut Environment) -> Router<Arc<AppState>> {
     templates
@@ -117,10 +360,267 @@ pub fn router(templates: &mut Environment) -> Router<Arc<AppState>> {
     templates
         .add_template("feedback", include_str!("../../templates/feedback.jinja"))
         .unwrap();
+    templates
+        .add_template("banners", include_str!("../../templates/banners.jinja"))
+        .unwrap();
+    templates
+        .add_template(
+            "maintenance",
+            include_str!("../../templates/maintenance.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template("blocked", include_str!("../../templates/blocked.jinja"))
+        .unwrap();
+    templates
+        .add_template(
+            "exit_survey",
+            include_str!("../../templates/exit_survey.jinja"),
+        )
+        .unwrap();
 
     Router::new()
         .route("/404", get(page_404))
+        .route("/banners", get(snippet_banners))
+        .route("/error/report", post(post_error_report))
+        .route(
+            "/exit_survey/:token",
+            get(page_exit_survey).post(post_exit_survey),
+        )
         .route("/feedback", get(page_feedback_form))
         .route("/feedback", post(page_feedback_form_post))
-        .nest_service("/assets", ServeDir::new("assets"))
+        .route("/assets/*file_name", get(get_asset))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::{
+        shared::AppError,
+        test_utils::{test_app, ADMIN_CONTROLLER, HOME_CONTROLLER},
+    };
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+        response::IntoResponse,
+    };
+    use chrono::Utc;
+    use std::{fs, path::Path as FilePath};
+    use tower::ServiceExt;
+    use uuid::Uuid;
+    use vzdv::{request_id::REQUEST_ID, sql};
+
+    #[tokio::test]
+    async fn test_feedback_submission() {
+        let app = test_app().await;
+        let form = [
+            ("controller", "1000001"),
+            ("position", "DEN_APP"),
+            ("rating", "excellent"),
+            ("comments", "Great service & quick handoffs"),
+        ];
+
+        // anonymous submissions are not stored
+        let (status, _) = app.post_form("/feedback", &form, None).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM feedback")
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(count.0, 0);
+
+        let cookie = app.login_as(1_234_567, false).await;
+        let (status, _) = app.post_form("/feedback", &form, Some(&cookie)).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let feedback = app
+            .state
+            .repos
+            .feedback
+            .get_for(HOME_CONTROLLER)
+            .await
+            .unwrap();
+        assert_eq!(feedback.len(), 1);
+        assert_eq!(feedback[0].submitter_cid, 1_234_567);
+        assert_eq!(feedback[0].comments, "Great service & quick handoffs");
+    }
+
+    #[tokio::test]
+    async fn test_error_report() {
+        let app = test_app().await;
+        let response = REQUEST_ID
+            .scope("0a1b2c3d4e5f".to_owned(), async {
+                AppError::ChronoOther("testing").into_response()
+            })
+            .await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let form = [
+            ("request_id", "0a1b2c3d4e5f"),
+            ("page", "/events/1"),
+            ("notes", "Clicked register"),
+        ];
+        let (status, _) = app.post_form("/error/report", &form, Some(&cookie)).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let (_, body) = app.get("/", Some(&cookie)).await;
+        assert!(body.contains("the error has been reported"));
+
+        // each error can only be reported once
+        app.post_form("/error/report", &form, Some(&cookie)).await;
+        let (_, body) = app.get("/", Some(&cookie)).await;
+        assert!(body.contains("already been reported"));
+    }
+
+    #[tokio::test]
+    async fn test_response_compression() {
+        let app = test_app().await;
+        let req = Request::get("/")
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.router.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[header::CONTENT_ENCODING], "gzip");
+    }
+
+    #[tokio::test]
+    async fn test_exit_survey() {
+        let app = test_app().await;
+        sqlx::query(sql::QUEUE_EXIT_SURVEY)
+            .bind(HOME_CONTROLLER)
+            .bind("Removed: inactivity")
+            .bind(Utc::now())
+            .execute(&app.db)
+            .await
+            .unwrap();
+        sqlx::query(sql::SET_EXIT_SURVEY_SENT)
+            .bind(1)
+            .bind("abc123")
+            .bind(Utc::now())
+            .execute(&app.db)
+            .await
+            .unwrap();
+
+        let (status, body) = app.get("/exit_survey/wrong", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("isn't valid"));
+        let (_, body) = app.get("/exit_survey/abc123", None).await;
+        assert!(body.contains("main reason you left"));
+
+        let form = [
+            ("rating", "4"),
+            ("reason", "Not enough time"),
+            ("comments", "Thanks <3"),
+        ];
+        let (status, _) = app.post_form("/exit_survey/abc123", &form, None).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let (_, body) = app.get("/exit_survey/abc123", None).await;
+        assert!(body.contains("Thank you for your responses"));
+
+        // responses can't be changed after submitting
+        let form = [("rating", "1"), ("reason", "Other"), ("comments", "")];
+        app.post_form("/exit_survey/abc123", &form, None).await;
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let (status, body) = app.get("/admin/exit_surveys", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Thanks &lt;3"));
+        assert!(body.contains("4.0 / 5"));
+    }
+
+    #[tokio::test]
+    async fn test_restricted_resources() {
+        let app = test_app().await;
+        for (category, visibility, file_name) in [
+            ("General", "public", "1_welcome.pdf"),
+            ("OTS", "training_staff", "2_ots_checklist.pdf"),
+        ] {
+            sqlx::query(sql::CREATE_RESOURCE_CATEGORY)
+                .bind(category)
+                .execute(&app.db)
+                .await
+                .unwrap();
+            sqlx::query("UPDATE resource_category SET visibility=$2 WHERE name=$1")
+                .bind(category)
+                .bind(visibility)
+                .execute(&app.db)
+                .await
+                .unwrap();
+            sqlx::query(sql::CREATE_NEW_RESOURCE)
+                .bind(category)
+                .bind(format!("{category} document"))
+                .bind(file_name)
+                .bind(None::<String>)
+                .bind(Utc::now())
+                .execute(&app.db)
+                .await
+                .unwrap();
+        }
+
+        let (_, body) = app.get("/facility/resources", None).await;
+        assert!(body.contains("General document"));
+        assert!(!body.contains("OTS document"));
+        let (status, _) = app.get("/assets/1_welcome.pdf", None).await;
+        assert_ne!(status, StatusCode::FORBIDDEN);
+        let (status, _) = app.get("/assets/2_ots_checklist.pdf", None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, _) = app.get("/assets/2_ots_checklist.pdf", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let (_, body) = app.get("/facility/resources", Some(&cookie)).await;
+        assert!(body.contains("OTS document"));
+        let (status, _) = app.get("/assets/2_ots_checklist.pdf", Some(&cookie)).await;
+        assert_ne!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_restricted_asset_path_variants() {
+        let app = test_app().await;
+        let file_name = format!("{}_restricted.pdf", Uuid::new_v4().simple());
+        sqlx::query(sql::CREATE_RESOURCE_CATEGORY)
+            .bind("OTS")
+            .execute(&app.db)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE resource_category SET visibility='training_staff' WHERE name='OTS'")
+            .execute(&app.db)
+            .await
+            .unwrap();
+        sqlx::query(sql::CREATE_NEW_RESOURCE)
+            .bind("OTS")
+            .bind("OTS document")
+            .bind(&file_name)
+            .bind(None::<String>)
+            .bind(Utc::now())
+            .execute(&app.db)
+            .await
+            .unwrap();
+        // the file has to exist for a bypass to be served
+        fs::create_dir_all("assets").unwrap();
+        let path = FilePath::new("assets").join(&file_name);
+        fs::write(&path, "restricted").unwrap();
+
+        let (status, _) = app.get(&format!("/assets/{file_name}"), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        for variant in [
+            format!("/assets/./{file_name}"),
+            format!("/assets//{file_name}"),
+            format!("/assets/x/../{file_name}"),
+        ] {
+            let (status, body) = app.get(&variant, None).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{variant}");
+            assert!(!body.contains("restricted"));
+        }
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let (status, body) = app
+            .get(&format!("/assets/{file_name}"), Some(&cookie))
+            .await;
+
+        fs::remove_file(&path).unwrap();
+        // only removed if no other files are left in it
+        let _ = fs::remove_dir("assets");
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "restricted");
+    }
 }
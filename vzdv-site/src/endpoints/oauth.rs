@@ -0,0 +1,497 @@
+//! OAuth2 / OpenID Connect provider, so facility tools can sign users in with their vzdv account.
+//!
+//! Only the authorization code flow is supported. Tools are configured in
+//! `oauth_provider.clients`; their ID tokens are signed (HS256) with their secret.
+
+use crate::{
+    endpoints::api::constant_time_eq,
+    shared::{AppError, AppState, UserInfo, SESSION_USER_INFO_KEY},
+    tokens::{hash_secret, new_secret},
+};
+use axum::{
+    extract::{OriginalUri, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Form, Json, Router,
+};
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac};
+use log::{info, warn};
+use minijinja::{context, Environment};
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::sync::Arc;
+use tower_sessions::Session;
+use vzdv::{
+    config::{Config, ConfigOAuthClient},
+    determine_staff_positions,
+    sql::{self, OAuthGrant},
+    ControllerRating,
+};
+
+/// How long a tool has to exchange the code it was sent.
+const CODE_LIFETIME_MINUTES: i64 = 5;
+/// How long access and ID tokens last.
+const TOKEN_LIFETIME_HOURS: i64 = 8;
+/// Scopes a tool can ask for; anything else is dropped.
+const SUPPORTED_SCOPES: [&str; 3] = ["openid", "profile", "email"];
+
+/// Base URL of the site, used as the token issuer.
+fn issuer(config: &Config) -> String {
+    config.hosted_domain.trim_end_matches('/').to_owned()
+}
+
+fn find_client<'a>(config: &'a Config, client_id: &str) -> Option<&'a ConfigOAuthClient> {
+    config
+        .oauth_provider
+        .clients
+        .iter()
+        .find(|client| !client.client_id.is_empty() && client.client_id == client_id)
+}
+
+/// Keep only the supported scopes, in a consistent order.
+fn normalize_scope(scope: &str) -> String {
+    let requested: Vec<&str> = scope.split_whitespace().collect();
+    SUPPORTED_SCOPES
+        .iter()
+        .filter(|scope| requested.contains(scope))
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Send the user back to the tool with the non-empty query parameters added.
+fn redirect_back(redirect_uri: &str, params: &[(&str, &str)]) -> Response {
+    let Ok(mut url) = Url::parse(redirect_uri) else {
+        return (StatusCode::BAD_REQUEST, "Invalid redirect URI").into_response();
+    };
+    url.query_pairs_mut()
+        .extend_pairs(params.iter().filter(|(_, value)| !value.is_empty()));
+    Redirect::to(url.as_str()).into_response()
+}
+
+/// Error body for the token and userinfo endpoints, per RFC 6749.
+fn oauth_error(status: StatusCode, error: &str) -> Response {
+    (status, Json(json!({ "error": error }))).into_response()
+}
+
+/// OpenID Connect discovery document.
+async fn get_openid_configuration(State(state): State<Arc<AppState>>) -> Json<Value> {
+    let issuer = issuer(&state.config);
+    Json(json!({
+        "issuer": issuer,
+        "authorization_endpoint": format!("{issuer}/oauth/authorize"),
+        "token_endpoint": format!("{issuer}/oauth/token"),
+        "userinfo_endpoint": format!("{issuer}/oauth/userinfo"),
+        "response_types_supported": ["code"],
+        "grant_types_supported": ["authorization_code"],
+        "subject_types_supported": ["public"],
+        "id_token_signing_alg_values_supported": ["HS256"],
+        "token_endpoint_auth_methods_supported": ["client_secret_basic", "client_secret_post"],
+        "scopes_supported": SUPPORTED_SCOPES,
+        "claims_supported": [
+            "sub", "name", "given_name", "family_name", "email", "rating", "rating_short",
+            "roles", "operating_initials", "home_facility", "is_on_roster",
+        ],
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+struct AuthorizeRequest {
+    response_type: String,
+    client_id: String,
+    redirect_uri: String,
+    #[serde(default)]
+    scope: String,
+    state: Option<String>,
+    nonce: Option<String>,
+    /// Only set when the user submits the consent form.
+    approve: Option<String>,
+}
+
+/// Check the request is from a configured tool and sending the user to one of its URLs.
+///
+/// Problems here are shown to the user rather than sent to the redirect URI,
+/// since it can't be trusted.
+fn validate_authorize<'a>(
+    config: &'a Config,
+    request: &AuthorizeRequest,
+) -> Result<&'a ConfigOAuthClient, &'static str> {
+    let Some(client) = find_client(config, &request.client_id) else {
+        return Err("Unknown client");
+    };
+    if !client.redirect_uris.contains(&request.redirect_uri) {
+        return Err("Unknown redirect URI");
+    }
+    Ok(client)
+}
+
+/// Ask the user to approve signing in to the tool.
+///
+/// Users not logged in are sent through the site's login first.
+async fn page_authorize(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    OriginalUri(uri): OriginalUri,
+    Query(request): Query<AuthorizeRequest>,
+) -> Result<Response, AppError> {
+    let client = match validate_authorize(&state.config, &request) {
+        Ok(client) => client,
+        Err(problem) => return Ok((StatusCode::BAD_REQUEST, problem).into_response()),
+    };
+    if request.response_type != "code" {
+        return Ok(redirect_back(
+            &request.redirect_uri,
+            &[
+                ("error", "unsupported_response_type"),
+                ("state", request.state.as_deref().unwrap_or_default()),
+            ],
+        ));
+    }
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if user_info.is_none() {
+        let next = uri
+            .path_and_query()
+            .map(|path| path.as_str())
+            .unwrap_or("/oauth/authorize");
+        let mut login = Url::parse("http://localhost/auth/log_in").unwrap();
+        login.query_pairs_mut().append_pair("next", next);
+        return Ok(Redirect::to(&format!(
+            "{}?{}",
+            login.path(),
+            login.query().unwrap_or_default()
+        ))
+        .into_response());
+    }
+    let scope = normalize_scope(&request.scope);
+    let template = state.templates.get_template("oauth/authorize")?;
+    let rendered = template.render(context! {
+        user_info,
+        client_name => &client.name,
+        client_id => &request.client_id,
+        redirect_uri => &request.redirect_uri,
+        scope,
+        shares_email => scope.split(' ').any(|s| s == "email"),
+        state => &request.state,
+        nonce => &request.nonce,
+    })?;
+    Ok(Html(rendered).into_response())
+}
+
+/// Record the user's approval and send them back to the tool with a code.
+async fn post_authorize(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Form(request): Form<AuthorizeRequest>,
+) -> Result<Response, AppError> {
+    let client = match validate_authorize(&state.config, &request) {
+        Ok(client) => client,
+        Err(problem) => return Ok((StatusCode::BAD_REQUEST, problem).into_response()),
+    };
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let Some(user_info) = user_info else {
+        return Ok(Redirect::to("/auth/log_in").into_response());
+    };
+    let tool_state = request.state.as_deref().unwrap_or_default();
+    if request.approve.is_none() {
+        return Ok(redirect_back(
+            &request.redirect_uri,
+            &[("error", "access_denied"), ("state", tool_state)],
+        ));
+    }
+
+    let now = Utc::now();
+    sqlx::query(sql::DELETE_EXPIRED_OAUTH_GRANTS)
+        .bind(now)
+        .execute(&state.db)
+        .await?;
+    let code = new_secret();
+    sqlx::query(sql::CREATE_OAUTH_GRANT)
+        .bind(&client.client_id)
+        .bind(user_info.cid)
+        .bind(normalize_scope(&request.scope))
+        .bind(&request.nonce)
+        .bind(&request.redirect_uri)
+        .bind(hash_secret(&code))
+        .bind(now + Duration::minutes(CODE_LIFETIME_MINUTES))
+        .bind(now)
+        .execute(&state.db)
+        .await?;
+    info!("{} approved signing in to {}", user_info.cid, client.name);
+    Ok(redirect_back(
+        &request.redirect_uri,
+        &[("code", &code), ("state", tool_state)],
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenRequest {
+    grant_type: String,
+    code: String,
+    redirect_uri: String,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+}
+
+/// Client ID and secret from either HTTP Basic auth or the form body.
+fn client_credentials(headers: &HeaderMap, request: &TokenRequest) -> Option<(String, String)> {
+    let basic = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|encoded| STANDARD.decode(encoded).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+        .and_then(|decoded| {
+            let (id, secret) = decoded.split_once(':')?;
+            Some((id.to_owned(), secret.to_owned()))
+        });
+    basic.or_else(|| Some((request.client_id.clone()?, request.client_secret.clone()?)))
+}
+
+/// Sign the claims as a JWT with the client's secret.
+fn sign_id_token(secret: &str, claims: &Value) -> String {
+    let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256","typ":"JWT"}"#);
+    let payload = URL_SAFE_NO_PAD.encode(claims.to_string());
+    let signing_input = format!("{header}.{payload}");
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(signing_input.as_bytes());
+    let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+    format!("{signing_input}.{signature}")
+}
+
+/// Claims about the user, limited to what the grant's scope allows.
+async fn user_claims(state: &AppState, grant: &OAuthGrant) -> Result<Option<Value>, AppError> {
+    let Some(controller) = state.repos.controllers.get_by_cid(grant.cid).await? else {
+        return Ok(None);
+    };
+    let scopes: Vec<&str> = grant.scope.split(' ').collect();
+    let mut claims = json!({ "sub": controller.cid.to_string() });
+    if scopes.contains(&"profile") {
+        claims["name"] = json!(format!(
+            "{} {}",
+            controller.first_name, controller.last_name
+        ));
+        claims["given_name"] = json!(controller.first_name);
+        claims["family_name"] = json!(controller.last_name);
+        claims["rating"] = json!(controller.rating);
+        claims["rating_short"] = json!(ControllerRating::try_from(controller.rating)
+            .map(|rating| rating.as_str())
+            .unwrap_or_default());
+        claims["roles"] = json!(determine_staff_positions(&controller));
+        claims["operating_initials"] = json!(controller.operating_initials);
+        claims["home_facility"] = json!(controller.home_facility);
+        claims["is_on_roster"] = json!(controller.is_on_roster);
+    }
+    if scopes.contains(&"email") {
+        let email: Option<String> = sqlx::query_scalar(sql::GET_CONTROLLER_EMAIL)
+            .bind(controller.cid)
+            .fetch_one(&state.db)
+            .await?;
+        claims["email"] = json!(email);
+    }
+    Ok(Some(claims))
+}
+
+/// Exchange a code for an access token, and an ID token if the "openid" scope was approved.
+async fn post_token(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Form(request): Form<TokenRequest>,
+) -> Result<Response, AppError> {
+    if request.grant_type != "authorization_code" {
+        return Ok(oauth_error(
+            StatusCode::BAD_REQUEST,
+            "unsupported_grant_type",
+        ));
+    }
+    let client = client_credentials(&headers, &request).and_then(|(id, secret)| {
+        find_client(&state.config, &id).filter(|client| {
+            !client.client_secret.is_empty()
+                && constant_time_eq(client.client_secret.as_bytes(), secret.as_bytes())
+        })
+    });
+    let Some(client) = client else {
+        return Ok(oauth_error(StatusCode::UNAUTHORIZED, "invalid_client"));
+    };
+
+    let now = Utc::now();
+    let grant: Option<OAuthGrant> = sqlx::query_as(sql::GET_OAUTH_GRANT_BY_CODE)
+        .bind(hash_secret(&request.code))
+        .bind(now)
+        .fetch_optional(&state.db)
+        .await?;
+    let Some(grant) = grant.filter(|grant| {
+        grant.client_id == client.client_id && grant.redirect_uri == request.redirect_uri
+    }) else {
+        warn!("{} sent an invalid OAuth code", client.name);
+        return Ok(oauth_error(StatusCode::BAD_REQUEST, "invalid_grant"));
+    };
+
+    let access_token = new_secret();
+    let expires = now + Duration::hours(TOKEN_LIFETIME_HOURS);
+    let exchanged = sqlx::query(sql::EXCHANGE_OAUTH_CODE)
+        .bind(grant.id)
+        .bind(hash_secret(&access_token))
+        .bind(expires)
+        .execute(&state.db)
+        .await?;
+    // another request used the code first
+    if exchanged.rows_affected() == 0 {
+        return Ok(oauth_error(StatusCode::BAD_REQUEST, "invalid_grant"));
+    }
+    let Some(mut claims) = user_claims(&state, &grant).await? else {
+        return Ok(oauth_error(StatusCode::BAD_REQUEST, "invalid_grant"));
+    };
+
+    let mut body = json!({
+        "access_token": access_token,
+        "token_type": "Bearer",
+        "expires_in": Duration::hours(TOKEN_LIFETIME_HOURS).num_seconds(),
+        "scope": grant.scope,
+    });
+    if grant.scope.split(' ').any(|scope| scope == "openid") {
+        claims["iss"] = json!(issuer(&state.config));
+        claims["aud"] = json!(client.client_id);
+        claims["iat"] = json!(now.timestamp());
+        claims["exp"] = json!(expires.timestamp());
+        if let Some(nonce) = &grant.nonce {
+            claims["nonce"] = json!(nonce);
+        }
+        body["id_token"] = json!(sign_id_token(&client.client_secret, &claims));
+    }
+    info!("Issued OAuth token for {} to {}", grant.cid, client.name);
+    Ok(([(header::CACHE_CONTROL, "no-store")], Json(body)).into_response())
+}
+
+/// Claims about the user the access token was issued for.
+async fn get_userinfo(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let Some(token) = token else {
+        return Ok(oauth_error(StatusCode::UNAUTHORIZED, "invalid_token"));
+    };
+    let grant: Option<OAuthGrant> = sqlx::query_as(sql::GET_OAUTH_GRANT_BY_TOKEN)
+        .bind(hash_secret(token))
+        .bind(Utc::now())
+        .fetch_optional(&state.db)
+        .await?;
+    let Some(grant) = grant else {
+        return Ok(oauth_error(StatusCode::UNAUTHORIZED, "invalid_token"));
+    };
+    match user_claims(&state, &grant).await? {
+        Some(claims) => Ok(Json(claims).into_response()),
+        None => Ok(oauth_error(StatusCode::UNAUTHORIZED, "invalid_token")),
+    }
+}
+
+pub fn router(templates: &mut Environment) -> Router<Arc<AppState>> {
+    templates
+        .add_template(
+            "oauth/authorize",
+            include_str!("../../templates/oauth/authorize.jinja"),
+        )
+        .unwrap();
+
+    Router::new()
+        .route(
+            "/.well-known/openid-configuration",
+            get(get_openid_configuration),
+        )
+        .route("/oauth/authorize", get(page_authorize).post(post_authorize))
+        .route("/oauth/token", post(post_token))
+        .route("/oauth/userinfo", get(get_userinfo))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize_scope;
+    use crate::test_utils::{test_app, HOME_CONTROLLER, TEST_OAUTH_CLIENT};
+    use axum::http::{header, StatusCode};
+    use reqwest::Url;
+    use serde_json::Value;
+
+    #[test]
+    fn test_normalize_scope() {
+        assert_eq!(normalize_scope("email openid admin"), "openid email");
+        assert_eq!(normalize_scope(""), "");
+    }
+
+    #[tokio::test]
+    async fn test_authorization_code_flow() {
+        let app = test_app().await;
+        let (client_id, client_secret, redirect_uri) = TEST_OAUTH_CLIENT;
+        let authorize = [
+            ("response_type", "code"),
+            ("client_id", client_id),
+            ("redirect_uri", redirect_uri),
+            ("scope", "openid profile"),
+            ("state", "abc"),
+            ("approve", "true"),
+        ];
+
+        // redirect URIs must match the config
+        let mut bad_redirect = authorize;
+        bad_redirect[2].1 = "https://evil.example.com/callback";
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, _) = app
+            .post_form_redirect("/oauth/authorize", &bad_redirect, Some(&cookie))
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, location) = app
+            .post_form_redirect("/oauth/authorize", &authorize, Some(&cookie))
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let location = Url::parse(&location).unwrap();
+        let params: Vec<(String, String)> = location.query_pairs().into_owned().collect();
+        assert!(params.contains(&("state".to_owned(), "abc".to_owned())));
+        let code = params
+            .iter()
+            .find(|(k, _)| k == "code")
+            .map(|(_, v)| v.clone())
+            .unwrap();
+
+        let token_form = [
+            ("grant_type", "authorization_code"),
+            ("code", code.as_str()),
+            ("redirect_uri", redirect_uri),
+            ("client_id", client_id),
+            ("client_secret", client_secret),
+        ];
+        let (status, body) = app.post_form("/oauth/token", &token_form, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let tokens: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(tokens["id_token"].as_str().unwrap().split('.').count(), 3);
+        let access_token = tokens["access_token"].as_str().unwrap();
+
+        // codes are single-use
+        let (status, _) = app.post_form("/oauth/token", &token_form, None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let auth = format!("Bearer {access_token}");
+        let (status, body) = app
+            .get_with_headers(
+                "/oauth/userinfo",
+                &[(header::AUTHORIZATION.as_str(), &auth)],
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        let claims: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(claims["sub"], HOME_CONTROLLER.to_string());
+        assert_eq!(claims["rating_short"], "C1");
+        assert!(claims.get("email").is_none());
+    }
+}
@@ -0,0 +1,1740 @@
+//! HTTP endpoints for the training program.
+
+use crate::{
+    discord,
+    email::{self, MailAttachment},
+    flashed_messages::{self, MessageLevel},
+    shared::{
+        has_permission, js_timestamp_to_utc, reject_without, AppError, AppState, UserInfo,
+        SESSION_USER_INFO_KEY,
+    },
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Form, Router,
+};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
+use log::{info, warn};
+use minijinja::{context, Environment};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
+use tower_sessions::Session;
+use vzdv::{
+    certifications, display_name, get_controller_cids_and_names,
+    ics::{write_calendar, write_invite, IcsEvent},
+    permissions,
+    sql::{
+        self, Certification, CertificationHistory, Controller, TrainingFeedback, TrainingLesson,
+        TrainingLessonSession, TrainingSession, WaitlistEntry,
+    },
+    vatusa, waitlist,
+};
+
+/// Longest training session that can be booked, in minutes.
+const MAX_SESSION_MINUTES: i64 = 8 * 60;
+
+/// How far back the facility training calendar feed goes.
+const CALENDAR_PAST_DAYS: i64 = 30;
+
+/// How long students have to leave feedback on a session.
+const FEEDBACK_REQUEST_DAYS: i64 = 30;
+
+/// Lessons grouped under each configured certification, in order.
+pub async fn syllabus_by_certification(
+    state: &AppState,
+) -> Result<Vec<(String, Vec<TrainingLesson>)>, AppError> {
+    let lessons: Vec<TrainingLesson> = sqlx::query_as(sql::GET_TRAINING_LESSONS)
+        .fetch_all(&state.db)
+        .await?;
+    Ok(state
+        .config
+        .training
+        .certifications
+        .iter()
+        .map(|certification| {
+            (
+                certification.clone(),
+                lessons
+                    .iter()
+                    .filter(|lesson| &lesson.certification == certification)
+                    .cloned()
+                    .collect(),
+            )
+        })
+        .collect())
+}
+
+/// The training syllabus: lessons and milestones for each certification.
+///
+/// For training staff; those who can manage the syllabus can also change it here.
+async fn page_syllabus(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::TRAINING_MANAGE).await {
+        return Ok(redirect.into_response());
+    }
+    let can_edit = has_permission(&state, &user_info, permissions::SYLLABUS_MANAGE).await;
+    let syllabus = syllabus_by_certification(&state).await?;
+    let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
+    let template = state.templates.get_template("training/syllabus")?;
+    let rendered = template.render(context! {
+        user_info,
+        flashed_messages,
+        syllabus,
+        can_edit,
+    })?;
+    Ok(Html(rendered).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct LessonForm {
+    /// Only used when creating a lesson.
+    certification: Option<String>,
+    name: String,
+    objectives: String,
+    milestone: Option<String>,
+}
+
+/// Add a lesson to the end of a certification's syllabus.
+async fn post_new_lesson(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Form(lesson_form): Form<LessonForm>,
+) -> Result<Redirect, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::SYLLABUS_MANAGE).await {
+        return Ok(redirect);
+    }
+    let user_info = user_info.unwrap();
+    let certification = lesson_form.certification.unwrap_or_default();
+    if !state
+        .config
+        .training
+        .certifications
+        .contains(&certification)
+    {
+        flashed_messages::push_flashed_message(
+            session,
+            MessageLevel::Error,
+            "Unknown certification",
+        )
+        .await?;
+        return Ok(Redirect::to("/training/syllabus"));
+    }
+    sqlx::query(sql::CREATE_TRAINING_LESSON)
+        .bind(&certification)
+        .bind(lesson_form.name.trim())
+        .bind(lesson_form.objectives.trim())
+        .bind(lesson_form.milestone.is_some())
+        .bind(user_info.cid)
+        .bind(Utc::now())
+        .execute(&state.db)
+        .await?;
+    info!(
+        "{} added lesson \"{}\" to the {certification} syllabus",
+        user_info.cid, lesson_form.name
+    );
+    flashed_messages::push_flashed_message(session, MessageLevel::Success, "Lesson added").await?;
+    Ok(Redirect::to("/training/syllabus"))
+}
+
+/// Update a lesson's name, objectives, and milestone status.
+async fn post_edit_lesson(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path(id): Path<u32>,
+    Form(lesson_form): Form<LessonForm>,
+) -> Result<Redirect, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::SYLLABUS_MANAGE).await {
+        return Ok(redirect);
+    }
+    let user_info = user_info.unwrap();
+    sqlx::query(sql::UPDATE_TRAINING_LESSON)
+        .bind(id)
+        .bind(lesson_form.name.trim())
+        .bind(lesson_form.objectives.trim())
+        .bind(lesson_form.milestone.is_some())
+        .bind(user_info.cid)
+        .bind(Utc::now())
+        .execute(&state.db)
+        .await?;
+    info!("{} updated lesson #{id}", user_info.cid);
+    flashed_messages::push_flashed_message(session, MessageLevel::Success, "Lesson updated")
+        .await?;
+    Ok(Redirect::to("/training/syllabus"))
+}
+
+#[derive(Debug, Deserialize)]
+struct MoveLessonForm {
+    /// "up" or "down"
+    direction: String,
+}
+
+/// Swap a lesson with the one before or after it in its syllabus.
+async fn post_move_lesson(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path(id): Path<u32>,
+    Form(move_form): Form<MoveLessonForm>,
+) -> Result<Redirect, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::SYLLABUS_MANAGE).await {
+        return Ok(redirect);
+    }
+    let lessons: Vec<TrainingLesson> = sqlx::query_as(sql::GET_TRAINING_LESSONS)
+        .fetch_all(&state.db)
+        .await?;
+    let Some(lesson) = lessons.iter().find(|lesson| lesson.id == id) else {
+        return Ok(Redirect::to("/training/syllabus"));
+    };
+    let mut order: Vec<u32> = lessons
+        .iter()
+        .filter(|other| other.certification == lesson.certification)
+        .map(|other| other.id)
+        .collect();
+    let index = order.iter().position(|other| *other == id).unwrap();
+    match move_form.direction.as_str() {
+        "up" if index > 0 => order.swap(index, index - 1),
+        "down" if index + 1 < order.len() => order.swap(index, index + 1),
+        _ => return Ok(Redirect::to("/training/syllabus")),
+    }
+    // renumber the whole syllabus in case there are gaps or duplicates
+    let mut tx = state.db.begin().await?;
+    for (i, lesson_id) in order.iter().enumerate() {
+        sqlx::query(sql::SET_TRAINING_LESSON_ORDER)
+            .bind(lesson_id)
+            .bind(i as u32 + 1)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(Redirect::to("/training/syllabus"))
+}
+
+/// Delete a lesson, along with the record of which students covered it.
+async fn api_delete_lesson(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path(id): Path<u32>,
+) -> Result<StatusCode, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if reject_without(&state, &user_info, permissions::SYLLABUS_MANAGE)
+        .await
+        .is_some()
+    {
+        return Ok(StatusCode::FORBIDDEN);
+    }
+    sqlx::query(sql::DELETE_TRAINING_LESSON)
+        .bind(id)
+        .execute(&state.db)
+        .await?;
+    info!("{} deleted lesson #{id}", user_info.unwrap().cid);
+    Ok(StatusCode::OK)
+}
+
+/// Single item on a student's training timeline.
+#[derive(Debug, Serialize)]
+struct TimelineEntry {
+    date: DateTime<Utc>,
+    /// "certification", "training", "exam", or "lesson"
+    kind: &'static str,
+    title: String,
+    detail: Option<String>,
+}
+
+/// Syllabus lesson and when the student first covered it, if they have.
+#[derive(Debug, Serialize)]
+struct LessonProgress {
+    lesson: TrainingLesson,
+    covered: Option<DateTime<Utc>>,
+}
+
+/// Student's standing in a single certification's syllabus.
+#[derive(Debug, Serialize)]
+struct CertificationProgress {
+    certification: String,
+    /// "none", "training", "solo", or "certified"
+    value: String,
+    lessons: Vec<LessonProgress>,
+    covered_count: usize,
+}
+
+/// Parse the date-times that VATUSA returns, which may or may not include the time.
+fn parse_vatusa_date(date: &str) -> Option<DateTime<Utc>> {
+    if let Ok(parsed) = NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S") {
+        return Some(parsed.and_utc());
+    }
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .ok()
+        .and_then(|parsed| parsed.and_hms_opt(0, 0, 0))
+        .map(|parsed| parsed.and_utc())
+}
+
+/// Redirect the user to their own training progress page.
+async fn page_own_progress(session: Session) -> Result<Redirect, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    Ok(match user_info {
+        Some(info) => Redirect::to(&format!("/training/progress/{}", info.cid)),
+        None => Redirect::to("/"),
+    })
+}
+
+/// A student's training progress: syllabus completion per certification and a
+/// timeline of their certifications, training sessions, and exams.
+///
+/// For the student themselves and for training staff.
+async fn page_progress(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path(cid): Path<u32>,
+) -> Result<Response, AppError> {
+    use voca_rs::Voca;
+
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if user_info.as_ref().map(|info| info.cid) != Some(cid) {
+        if let Some(redirect) =
+            reject_without(&state, &user_info, permissions::TRAINING_MANAGE).await
+        {
+            return Ok(redirect.into_response());
+        }
+    }
+    let controller: Option<Controller> = sqlx::query_as(sql::GET_CONTROLLER_BY_CID)
+        .bind(cid)
+        .fetch_optional(&state.db)
+        .await?;
+    let controller = match controller {
+        Some(c) => c,
+        None => {
+            flashed_messages::push_flashed_message(
+                session,
+                MessageLevel::Error,
+                "Unknown controller",
+            )
+            .await?;
+            return Ok(Redirect::to("/").into_response());
+        }
+    };
+    let names = get_controller_cids_and_names(&state.db)
+        .await
+        .map_err(|e| AppError::GenericFallback("getting names and CIDs from DB", e))?;
+    let mut timeline = Vec::new();
+
+    let certifications: Vec<Certification> = state.repos.certifications.get_for(cid).await?;
+    let cert_history: Vec<CertificationHistory> =
+        sqlx::query_as(sql::GET_CERTIFICATION_HISTORY_FOR)
+            .bind(cid)
+            .fetch_all(&state.db)
+            .await?;
+    for change in &cert_history {
+        timeline.push(TimelineEntry {
+            date: change.changed_on,
+            kind: "certification",
+            title: match &change.old_value {
+                Some(old) => format!("{}: {old} -> {}", change.name, change.new_value),
+                None => format!("{}: {}", change.name, change.new_value),
+            },
+            detail: Some(format!(
+                "Set by {}",
+                display_name(&names, change.changed_by)
+            )),
+        });
+    }
+
+    let training_records: Vec<sql::TrainingRecord> = sqlx::query_as(sql::GET_TRAINING_RECORDS_FOR)
+        .bind(cid)
+        .fetch_all(&state.db)
+        .await?;
+    for record in &training_records {
+        timeline.push(TimelineEntry {
+            date: record.date,
+            kind: "training",
+            title: format!(
+                "Training on {} with {}",
+                record.position,
+                display_name(&names, record.instructor_cid)
+            ),
+            detail: Some(record.notes.clone()),
+        });
+    }
+
+    // VATUSA may be slow or down; show what's available locally rather than failing
+    let mut vatusa_unavailable = false;
+    let api_key = &state.config.vatsim.vatusa_api_key;
+    if !api_key.is_empty() {
+        match vatusa::get_training_records(api_key, cid).await {
+            Ok(records) => {
+                for record in records
+                    .iter()
+                    .filter(|record| record.facility_id == "ZDV")
+                    // skip records already copied into the local table
+                    .filter(|record| {
+                        !training_records.iter().any(|local| {
+                            local.position == record.position
+                                && Some(local.date) == parse_vatusa_date(&record.session_date)
+                        })
+                    })
+                {
+                    let Some(date) = parse_vatusa_date(&record.session_date) else {
+                        continue;
+                    };
+                    timeline.push(TimelineEntry {
+                        date,
+                        kind: "training",
+                        title: format!(
+                            "Training on {} with {}",
+                            record.position,
+                            display_name(&names, record.instructor_id)
+                        ),
+                        detail: Some(record.notes._strip_tags()),
+                    });
+                }
+            }
+            Err(e) => {
+                warn!("Could not get VATUSA training records for {cid}: {e}");
+                vatusa_unavailable = true;
+            }
+        }
+        match vatusa::get_exam_results(api_key, cid).await {
+            Ok(results) => {
+                for result in &results {
+                    let Some(date) = parse_vatusa_date(&result.date) else {
+                        continue;
+                    };
+                    timeline.push(TimelineEntry {
+                        date,
+                        kind: "exam",
+                        title: format!(
+                            "{} {}",
+                            result.exam_name,
+                            if result.passed { "passed" } else { "failed" }
+                        ),
+                        detail: Some(format!("Score: {}%", result.score)),
+                    });
+                }
+            }
+            Err(e) => {
+                warn!("Could not get VATUSA exam results for {cid}: {e}");
+                vatusa_unavailable = true;
+            }
+        }
+    }
+
+    let sessions: Vec<TrainingLessonSession> =
+        sqlx::query_as(sql::GET_TRAINING_LESSON_SESSIONS_FOR)
+            .bind(cid)
+            .fetch_all(&state.db)
+            .await?;
+    let syllabus = syllabus_by_certification(&state).await?;
+    let lessons_by_id: HashMap<u32, &TrainingLesson> = syllabus
+        .iter()
+        .flat_map(|(_, lessons)| lessons.iter())
+        .map(|lesson| (lesson.id, lesson))
+        .collect();
+    for lesson_session in &sessions {
+        let Some(lesson) = lessons_by_id.get(&lesson_session.lesson_id) else {
+            continue;
+        };
+        timeline.push(TimelineEntry {
+            date: lesson_session.date,
+            kind: "lesson",
+            title: format!(
+                "{} {}: {}",
+                if lesson.milestone {
+                    "Reached milestone"
+                } else {
+                    "Covered lesson"
+                },
+                lesson.certification,
+                lesson.name
+            ),
+            detail: Some(format!(
+                "With {}",
+                display_name(&names, lesson_session.instructor_cid)
+            )),
+        });
+    }
+    timeline.sort_by_key(|entry| std::cmp::Reverse(entry.date));
+
+    let progress: Vec<CertificationProgress> = syllabus
+        .into_iter()
+        .map(|(certification, lessons)| {
+            let lessons: Vec<LessonProgress> = lessons
+                .into_iter()
+                .map(|lesson| {
+                    // sessions are newest-first, so the last match is the first time covered
+                    let covered = sessions
+                        .iter()
+                        .filter(|s| s.lesson_id == lesson.id)
+                        .map(|s| s.date)
+                        .next_back();
+                    LessonProgress { lesson, covered }
+                })
+                .collect();
+            CertificationProgress {
+                value: certifications
+                    .iter()
+                    .find(|c| c.name == certification)
+                    .map(|c| c.value.clone())
+                    .unwrap_or_else(|| String::from("none")),
+                covered_count: lessons.iter().filter(|l| l.covered.is_some()).count(),
+                certification,
+                lessons,
+            }
+        })
+        .collect();
+
+    let template = state.templates.get_template("training/progress")?;
+    let rendered = template.render(context! {
+        user_info,
+        controller,
+        progress,
+        timeline,
+        vatusa_unavailable,
+    })?;
+    Ok(Html(rendered).into_response())
+}
+
+/// Calendar entry for a booked training session.
+///
+/// The UID is shared by the emailed invites and the calendar feeds, so calendar
+/// apps treat them as the same entry.
+pub fn session_ics_event(
+    session: &TrainingSession,
+    names: &HashMap<u32, (String, String)>,
+    site: &str,
+) -> IcsEvent {
+    let mut description = format!(
+        "Student: {}\nMentor: {}",
+        display_name(names, session.cid),
+        display_name(names, session.instructor_cid)
+    );
+    if !session.notes.is_empty() {
+        description.push_str("\n\n");
+        description.push_str(&session.notes);
+    }
+    IcsEvent {
+        uid: format!("training-{}@vzdv", session.id),
+        start: session.start,
+        end: session.end,
+        summary: format!("ZDV training: {}", session.position),
+        description: Some(description),
+        url: Some(format!("{site}/training/sessions")),
+        sequence: session.sequence,
+        cancelled: session.cancelled,
+    }
+}
+
+/// Email the student and mentor a calendar invite for the session, or a
+/// cancellation if it's been cancelled.
+///
+/// Nothing is sent until the facility has written the matching email template.
+async fn send_session_invites(state: &AppState, session: &TrainingSession) -> Result<(), AppError> {
+    let template_name = if session.cancelled {
+        email::templates::TRAINING_CANCELLED
+    } else {
+        email::templates::TRAINING_BOOKED
+    };
+    let template = if session.cancelled {
+        &state.config.email.training_cancelled_template
+    } else {
+        &state.config.email.training_booked_template
+    };
+    if template.body.is_empty() {
+        return Ok(());
+    }
+    let names = get_controller_cids_and_names(&state.db)
+        .await
+        .map_err(|e| AppError::GenericFallback("getting names and CIDs from DB", e))?;
+    let site = state.config.hosted_domain.trim_end_matches('/');
+    let invite = write_invite(
+        "ZDV training",
+        &state.config.email.from,
+        &session_ics_event(session, &names, site),
+        Utc::now(),
+    );
+    let method = if session.cancelled {
+        "CANCEL"
+    } else {
+        "REQUEST"
+    };
+    for cid in [session.cid, session.instructor_cid] {
+        let address: Option<String> = sqlx::query_scalar(sql::GET_CONTROLLER_EMAIL)
+            .bind(cid)
+            .fetch_optional(&state.db)
+            .await?
+            .flatten();
+        let Some(address) = address else {
+            warn!(
+                "No email address to send training session {} to {cid}",
+                session.id
+            );
+            continue;
+        };
+        if let Err(e) = email::send_mail_with_attachment(
+            &state.config,
+            &state.db,
+            &display_name(&names, cid),
+            &address,
+            template_name,
+            context! {
+                student => display_name(&names, session.cid),
+                mentor => display_name(&names, session.instructor_cid),
+                position => &session.position,
+                start => session.start.format("%Y-%m-%d %H:%M UTC").to_string(),
+            },
+            Some(MailAttachment {
+                filename: String::from("invite.ics"),
+                content_type: format!("text/calendar; method={method}; charset=UTF-8"),
+                body: invite.clone(),
+            }),
+        )
+        .await
+        {
+            warn!(
+                "Could not send training session {} invite to {cid}: {e}",
+                session.id
+            );
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct SessionDisplay {
+    session: TrainingSession,
+    student: String,
+    mentor: String,
+    can_cancel: bool,
+}
+
+/// Upcoming training sessions, and a form for training staff to book more.
+///
+/// Training staff see every session; everyone else sees their own.
+async fn page_sessions(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let Some(info) = &user_info else {
+        return Ok(Redirect::to("/").into_response());
+    };
+    let is_training_staff = has_permission(&state, &user_info, permissions::TRAINING_MANAGE).await;
+    let now = Utc::now();
+    let sessions: Vec<TrainingSession> = if is_training_staff {
+        sqlx::query_as(sql::GET_UPCOMING_TRAINING_SESSIONS)
+            .bind(now)
+            .fetch_all(&state.db)
+            .await?
+    } else {
+        sqlx::query_as(sql::GET_UPCOMING_TRAINING_SESSIONS_FOR)
+            .bind(info.cid)
+            .bind(now)
+            .fetch_all(&state.db)
+            .await?
+    };
+    let names = get_controller_cids_and_names(&state.db)
+        .await
+        .map_err(|e| AppError::GenericFallback("getting names and CIDs from DB", e))?;
+    let sessions: Vec<SessionDisplay> = sessions
+        .into_iter()
+        .map(|session| SessionDisplay {
+            student: display_name(&names, session.cid),
+            mentor: display_name(&names, session.instructor_cid),
+            can_cancel: !session.cancelled
+                && (is_training_staff
+                    || session.cid == info.cid
+                    || session.instructor_cid == info.cid),
+            session,
+        })
+        .collect();
+    let (students, feed_url) = if is_training_staff {
+        let controllers: Vec<Controller> = sqlx::query_as(sql::GET_ALL_CONTROLLERS_ON_ROSTER)
+            .fetch_all(&state.db)
+            .await?;
+        let token: Option<String> = sqlx::query_scalar(sql::GET_CALENDAR_TOKEN)
+            .bind(info.cid)
+            .fetch_optional(&state.db)
+            .await?;
+        let feed_url = token.map(|token| {
+            format!(
+                "{}/training/calendar.ics?token={token}",
+                state.config.hosted_domain.trim_end_matches('/')
+            )
+        });
+        (controllers, feed_url)
+    } else {
+        (Vec::new(), None)
+    };
+    let pending_feedback: Vec<TrainingFeedback> =
+        sqlx::query_as(sql::GET_PENDING_TRAINING_FEEDBACK_FOR)
+            .bind(info.cid)
+            .bind(now - Duration::days(FEEDBACK_REQUEST_DAYS))
+            .fetch_all(&state.db)
+            .await?;
+    let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
+    let template = state.templates.get_template("training/sessions")?;
+    let rendered = template.render(context! {
+        user_info,
+        flashed_messages,
+        sessions,
+        pending_feedback,
+        is_training_staff,
+        students,
+        feed_url,
+    })?;
+    Ok(Html(rendered).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct BookSessionForm {
+    cid: u32,
+    position: String,
+    start: String,
+    timezone: String,
+    /// Minutes
+    duration: i64,
+    notes: String,
+}
+
+/// Book a training session with the user as the mentor.
+async fn post_book_session(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Form(book_form): Form<BookSessionForm>,
+) -> Result<Redirect, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) = reject_without(&state, &user_info, permissions::TRAINING_MANAGE).await {
+        return Ok(redirect);
+    }
+    let user_info = user_info.unwrap();
+    let student: Option<Controller> = sqlx::query_as(sql::GET_CONTROLLER_BY_CID)
+        .bind(book_form.cid)
+        .fetch_optional(&state.db)
+        .await?;
+    let problem = if student.is_none() {
+        Some("Unknown student")
+    } else if book_form.position.trim().is_empty() {
+        Some("A position is required")
+    } else if book_form.duration <= 0 || book_form.duration > MAX_SESSION_MINUTES {
+        Some("Sessions must be between 1 minute and 8 hours long")
+    } else {
+        None
+    };
+    if let Some(problem) = problem {
+        flashed_messages::push_flashed_message(session, MessageLevel::Error, problem).await?;
+        return Ok(Redirect::to("/training/sessions"));
+    }
+    let start = js_timestamp_to_utc(&book_form.start, &book_form.timezone)?.and_utc();
+    let end = start + Duration::minutes(book_form.duration);
+    let now = Utc::now();
+    let result = sqlx::query(sql::CREATE_TRAINING_SESSION)
+        .bind(book_form.cid)
+        .bind(user_info.cid)
+        .bind(book_form.position.trim().to_uppercase())
+        .bind(start)
+        .bind(end)
+        .bind(book_form.notes.trim())
+        .bind(now)
+        .execute(&state.db)
+        .await?;
+    let id = result.last_insert_rowid();
+    info!(
+        "{} booked training session {id} with {} on {}",
+        user_info.cid, book_form.cid, book_form.position
+    );
+    let training_session: TrainingSession = sqlx::query_as(sql::GET_TRAINING_SESSION)
+        .bind(id)
+        .fetch_one(&state.db)
+        .await?;
+    send_session_invites(&state, &training_session).await?;
+    flashed_messages::push_flashed_message(session, MessageLevel::Success, "Session booked")
+        .await?;
+    Ok(Redirect::to("/training/sessions"))
+}
+
+/// Cancel a training session, notifying the student and mentor.
+///
+/// For the student, the mentor, and training staff.
+async fn post_cancel_session(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path(id): Path<u32>,
+) -> Result<Redirect, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let Some(info) = &user_info else {
+        return Ok(Redirect::to("/"));
+    };
+    let training_session: Option<TrainingSession> = sqlx::query_as(sql::GET_TRAINING_SESSION)
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?;
+    let Some(training_session) = training_session else {
+        return Ok(Redirect::to("/training/sessions"));
+    };
+    if training_session.cid != info.cid && training_session.instructor_cid != info.cid {
+        if let Some(redirect) =
+            reject_without(&state, &user_info, permissions::TRAINING_MANAGE).await
+        {
+            return Ok(redirect);
+        }
+    }
+    if training_session.cancelled {
+        return Ok(Redirect::to("/training/sessions"));
+    }
+    sqlx::query(sql::CANCEL_TRAINING_SESSION)
+        .bind(id)
+        .bind(Utc::now())
+        .execute(&state.db)
+        .await?;
+    info!("{} cancelled training session {id}", info.cid);
+    let training_session: TrainingSession = sqlx::query_as(sql::GET_TRAINING_SESSION)
+        .bind(id)
+        .fetch_one(&state.db)
+        .await?;
+    send_session_invites(&state, &training_session).await?;
+    flashed_messages::push_flashed_message(session, MessageLevel::Success, "Session cancelled")
+        .await?;
+    Ok(Redirect::to("/training/sessions"))
+}
+
+#[derive(Debug, Deserialize)]
+struct CalendarQuery {
+    token: Option<String>,
+}
+
+/// Shared calendar feed of every training session, for training staff.
+///
+/// Authenticated by the user's personal calendar link token, since calendar
+/// apps can't log in.
+async fn get_training_calendar(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CalendarQuery>,
+) -> Result<Response, AppError> {
+    let cid: Option<u32> = match query.token {
+        Some(token) => {
+            sqlx::query_scalar(sql::GET_CALENDAR_TOKEN_CID)
+                .bind(token)
+                .fetch_optional(&state.db)
+                .await?
+        }
+        None => None,
+    };
+    let Some(cid) = cid else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    if !permissions::has_permission(&state.db, cid, permissions::TRAINING_MANAGE).await? {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+    let now = Utc::now();
+    let sessions: Vec<TrainingSession> = sqlx::query_as(sql::GET_UPCOMING_TRAINING_SESSIONS)
+        .bind(now - Duration::days(CALENDAR_PAST_DAYS))
+        .fetch_all(&state.db)
+        .await?;
+    let names = get_controller_cids_and_names(&state.db)
+        .await
+        .map_err(|e| AppError::GenericFallback("getting names and CIDs from DB", e))?;
+    let site = state.config.hosted_domain.trim_end_matches('/');
+    let events: Vec<IcsEvent> = sessions
+        .iter()
+        .map(|session| session_ics_event(session, &names, site))
+        .collect();
+    Ok((
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        write_calendar("ZDV training", &events, now),
+    )
+        .into_response())
+}
+
+/// Tell a student they've been promoted off a waitlist, by Discord DM if
+/// they've linked their account and by email if the template is set.
+async fn notify_waitlist_promotion(state: &AppState, entry: &WaitlistEntry) {
+    let controller = match state.repos.controllers.get_by_cid(entry.cid).await {
+        Ok(Some(controller)) => controller,
+        Ok(None) => return,
+        Err(e) => {
+            warn!(
+                "Could not get {} to notify of waitlist promotion: {e}",
+                entry.cid
+            );
+            return;
+        }
+    };
+    if let Some(discord_id) = &controller.discord_id {
+        let content = format!(
+            "A training slot has opened for {}, and you're next on the waitlist. A mentor will be in touch to schedule your first session.",
+            entry.certification
+        );
+        if let Err(e) = discord::send_direct_message(&state.config, discord_id, &content).await {
+            warn!("Could not DM {} of waitlist promotion: {e}", entry.cid);
+        }
+    }
+    if state
+        .config
+        .email
+        .waitlist_promoted_template
+        .body
+        .is_empty()
+    {
+        return;
+    }
+    let address: Option<String> = match sqlx::query_scalar(sql::GET_CONTROLLER_EMAIL)
+        .bind(entry.cid)
+        .fetch_optional(&state.db)
+        .await
+    {
+        Ok(address) => address.flatten(),
+        Err(e) => {
+            warn!("Could not get {}'s email address: {e}", entry.cid);
+            None
+        }
+    };
+    if let Some(address) = address {
+        if let Err(e) = email::send_mail_with_context(
+            &state.config,
+            &state.db,
+            &format!("{} {}", controller.first_name, controller.last_name),
+            &address,
+            email::templates::WAITLIST_PROMOTED,
+            context! { certification => &entry.certification },
+        )
+        .await
+        {
+            warn!("Could not email {} of waitlist promotion: {e}", entry.cid);
+        }
+    }
+}
+
+/// Fill any open training slots in the certification from its waitlist.
+///
+/// Promoted students are put in training for the certification, which takes
+/// up the slot, and notified.
+pub async fn promote_from_waitlist(
+    state: &AppState,
+    certification: &str,
+    by_cid: u32,
+) -> Result<(), AppError> {
+    let Some(capacity) = state
+        .config
+        .training
+        .waitlist
+        .capacity
+        .get(certification)
+        .copied()
+    else {
+        return Ok(());
+    };
+    loop {
+        let in_training: u32 = sqlx::query_scalar(sql::COUNT_IN_TRAINING_FOR_CERTIFICATION)
+            .bind(certification)
+            .fetch_one(&state.db)
+            .await?;
+        if in_training >= capacity {
+            return Ok(());
+        }
+        let mut waitlists = waitlist::get_waitlists(&state.db, &state.config, Utc::now()).await?;
+        let Some(entry) = waitlists
+            .remove(certification)
+            .and_then(|entries| entries.into_iter().next())
+        else {
+            return Ok(());
+        };
+        let now = Utc::now();
+        certifications::set_certification(
+            &state.db,
+            entry.cid,
+            certification,
+            "training",
+            by_cid,
+            now,
+        )
+        .await?;
+        sqlx::query(sql::SET_WAITLIST_ENTRY_PROMOTED)
+            .bind(entry.id)
+            .bind(now)
+            .execute(&state.db)
+            .await?;
+        info!(
+            "Promoted {} off the {certification} waitlist into training",
+            entry.cid
+        );
+        notify_waitlist_promotion(state, &entry).await;
+    }
+}
+
+/// Waitlist for a single certification, as shown on the waitlist page.
+#[derive(Debug, Serialize)]
+struct WaitlistDisplay {
+    certification: String,
+    capacity: u32,
+    in_training: u32,
+    /// Full list for training staff; empty for everyone else.
+    entries: Vec<WaitlistEntry>,
+    waiting: usize,
+    /// The user's place in line, from 1, and their entry's ID.
+    own_place: Option<(usize, u32)>,
+    can_join: bool,
+}
+
+/// Waitlists for certifications with limited training slots.
+///
+/// Students can join and leave; training staff see everyone's place in line.
+async fn page_waitlist(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let Some(info) = &user_info else {
+        return Ok(Redirect::to("/").into_response());
+    };
+    let is_training_staff = has_permission(&state, &user_info, permissions::TRAINING_MANAGE).await;
+    let controller = state.repos.controllers.get_by_cid(info.cid).await?;
+    let on_roster = controller.is_some_and(|c| c.is_on_roster);
+    let own_certs: Vec<Certification> = state.repos.certifications.get_for(info.cid).await?;
+    let mut waitlists = waitlist::get_waitlists(&state.db, &state.config, Utc::now()).await?;
+    let mut displays = Vec::new();
+    for certification in &state.config.training.certifications {
+        let Some(capacity) = state.config.training.waitlist.capacity.get(certification) else {
+            continue;
+        };
+        let in_training: u32 = sqlx::query_scalar(sql::COUNT_IN_TRAINING_FOR_CERTIFICATION)
+            .bind(certification)
+            .fetch_one(&state.db)
+            .await?;
+        let entries = waitlists.remove(certification).unwrap_or_default();
+        let own_place = entries
+            .iter()
+            .position(|entry| entry.cid == info.cid)
+            .map(|index| (index + 1, entries[index].id));
+        displays.push(WaitlistDisplay {
+            certification: certification.clone(),
+            capacity: *capacity,
+            in_training,
+            waiting: entries.len(),
+            can_join: on_roster
+                && own_place.is_none()
+                && !own_certs
+                    .iter()
+                    .any(|c| &c.name == certification && c.value != "none"),
+            own_place,
+            entries: if is_training_staff {
+                entries
+            } else {
+                Vec::new()
+            },
+        });
+    }
+    let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
+    let template = state.templates.get_template("training/waitlist")?;
+    let rendered = template.render(context! {
+        user_info,
+        flashed_messages,
+        waitlists => displays,
+        is_training_staff,
+    })?;
+    Ok(Html(rendered).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct JoinWaitlistForm {
+    certification: String,
+}
+
+/// Join a certification's waitlist.
+///
+/// For controllers on the roster who don't already hold or train for the certification.
+async fn post_join_waitlist(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Form(join_form): Form<JoinWaitlistForm>,
+) -> Result<Redirect, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let Some(info) = user_info else {
+        return Ok(Redirect::to("/"));
+    };
+    let certification = join_form.certification;
+    let controller = state.repos.controllers.get_by_cid(info.cid).await?;
+    let own_certs: Vec<Certification> = state.repos.certifications.get_for(info.cid).await?;
+    let problem = if !controller.is_some_and(|c| c.is_on_roster) {
+        Some("Only controllers on the roster can join a waitlist")
+    } else if !state
+        .config
+        .training
+        .waitlist
+        .capacity
+        .contains_key(&certification)
+    {
+        Some("That certification has no waitlist")
+    } else if own_certs
+        .iter()
+        .any(|c| c.name == certification && c.value != "none")
+    {
+        Some("You're already training for or certified on that")
+    } else {
+        None
+    };
+    if let Some(problem) = problem {
+        flashed_messages::push_flashed_message(session, MessageLevel::Error, problem).await?;
+        return Ok(Redirect::to("/training/waitlist"));
+    }
+    let result = sqlx::query(sql::JOIN_WAITLIST)
+        .bind(info.cid)
+        .bind(&certification)
+        .bind(Utc::now())
+        .execute(&state.db)
+        .await;
+    if let Err(sqlx::Error::Database(e)) = &result {
+        if e.is_unique_violation() {
+            flashed_messages::push_flashed_message(
+                session,
+                MessageLevel::Error,
+                "You're already on that waitlist",
+            )
+            .await?;
+            return Ok(Redirect::to("/training/waitlist"));
+        }
+    }
+    result?;
+    info!("{} joined the {certification} waitlist", info.cid);
+    // there may already be an open slot
+    promote_from_waitlist(&state, &certification, info.cid).await?;
+    flashed_messages::push_flashed_message(session, MessageLevel::Success, "Joined the waitlist")
+        .await?;
+    Ok(Redirect::to("/training/waitlist"))
+}
+
+/// Take an entry off a waitlist.
+///
+/// For the student on it and training staff.
+async fn post_remove_waitlist_entry(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path(id): Path<u32>,
+) -> Result<Redirect, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let Some(info) = &user_info else {
+        return Ok(Redirect::to("/"));
+    };
+    let cid: Option<u32> = sqlx::query_scalar(sql::GET_WAITLIST_ENTRY)
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?;
+    let Some(cid) = cid else {
+        return Ok(Redirect::to("/training/waitlist"));
+    };
+    if cid != info.cid {
+        if let Some(redirect) =
+            reject_without(&state, &user_info, permissions::TRAINING_MANAGE).await
+        {
+            return Ok(redirect);
+        }
+    }
+    sqlx::query(sql::DELETE_WAITLIST_ENTRY)
+        .bind(id)
+        .execute(&state.db)
+        .await?;
+    info!("{} removed {cid}'s waitlist entry {id}", info.cid);
+    flashed_messages::push_flashed_message(session, MessageLevel::Success, "Removed from waitlist")
+        .await?;
+    Ok(Redirect::to("/training/waitlist"))
+}
+
+/// Ask the student to rate a training session the mentor just filed a record for,
+/// by Discord DM if they've linked their account and by email if the template is set.
+pub async fn request_session_feedback(
+    state: &AppState,
+    cid: u32,
+    instructor_cid: u32,
+    position: &str,
+    session_date: DateTime<Utc>,
+) -> Result<(), AppError> {
+    let result = sqlx::query(sql::CREATE_TRAINING_FEEDBACK_REQUEST)
+        .bind(cid)
+        .bind(instructor_cid)
+        .bind(position)
+        .bind(session_date)
+        .bind(Utc::now())
+        .execute(&state.db)
+        .await?;
+    let link = format!(
+        "{}/training/feedback/{}",
+        state.config.hosted_domain.trim_end_matches('/'),
+        result.last_insert_rowid()
+    );
+    let Some(controller) = state.repos.controllers.get_by_cid(cid).await? else {
+        return Ok(());
+    };
+    let mentor = state
+        .repos
+        .controllers
+        .get_by_cid(instructor_cid)
+        .await?
+        .map(|mentor| format!("{} {}", mentor.first_name, mentor.last_name))
+        .unwrap_or_else(|| instructor_cid.to_string());
+    if let Some(discord_id) = &controller.discord_id {
+        let content = format!(
+            "How was your training session on {position} with {mentor}? Let the training staff know: {link}"
+        );
+        if let Err(e) = discord::send_direct_message(&state.config, discord_id, &content).await {
+            warn!("Could not DM {cid} for training session feedback: {e}");
+        }
+    }
+    if !state
+        .config
+        .email
+        .training_feedback_request_template
+        .body
+        .is_empty()
+    {
+        let address: Option<String> = sqlx::query_scalar(sql::GET_CONTROLLER_EMAIL)
+            .bind(cid)
+            .fetch_optional(&state.db)
+            .await?
+            .flatten();
+        if let Some(address) = address {
+            if let Err(e) = email::send_mail_with_context(
+                &state.config,
+                &state.db,
+                &format!("{} {}", controller.first_name, controller.last_name),
+                &address,
+                email::templates::TRAINING_FEEDBACK_REQUEST,
+                context! { mentor, position, link },
+            )
+            .await
+            {
+                warn!("Could not email {cid} for training session feedback: {e}");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Form for the student to rate a training session.
+async fn page_session_feedback(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path(id): Path<u32>,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let Some(info) = &user_info else {
+        return Ok(
+            Redirect::to(&format!("/auth/log_in?next=/training/feedback/{id}")).into_response(),
+        );
+    };
+    let feedback: Option<TrainingFeedback> = sqlx::query_as(sql::GET_TRAINING_FEEDBACK)
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?;
+    let Some(feedback) = feedback.filter(|feedback| feedback.cid == info.cid) else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let mentor = state
+        .repos
+        .controllers
+        .get_by_cid(feedback.instructor_cid)
+        .await?
+        .map(|mentor| format!("{} {}", mentor.first_name, mentor.last_name))
+        .unwrap_or_else(|| feedback.instructor_cid.to_string());
+    let expired = feedback.requested_date + Duration::days(FEEDBACK_REQUEST_DAYS) < Utc::now();
+    let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
+    let template = state.templates.get_template("training/session_feedback")?;
+    let rendered = template.render(context! {
+        user_info,
+        flashed_messages,
+        feedback,
+        mentor,
+        expired,
+    })?;
+    Ok(Html(rendered).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct SessionFeedbackForm {
+    rating: u8,
+    comments: String,
+}
+
+/// Save the student's rating of a training session.
+async fn post_session_feedback(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Path(id): Path<u32>,
+    Form(feedback_form): Form<SessionFeedbackForm>,
+) -> Result<Redirect, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let Some(info) = &user_info else {
+        return Ok(Redirect::to("/"));
+    };
+    let feedback: Option<TrainingFeedback> = sqlx::query_as(sql::GET_TRAINING_FEEDBACK)
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?;
+    let Some(feedback) = feedback.filter(|feedback| feedback.cid == info.cid) else {
+        return Ok(Redirect::to("/"));
+    };
+    let redirect = Redirect::to(&format!("/training/feedback/{id}"));
+    if !(1..=5).contains(&feedback_form.rating) {
+        flashed_messages::push_flashed_message(
+            session,
+            MessageLevel::Error,
+            "Pick a rating from 1 to 5",
+        )
+        .await?;
+        return Ok(redirect);
+    }
+    if feedback.requested_date + Duration::days(FEEDBACK_REQUEST_DAYS) < Utc::now() {
+        return Ok(redirect);
+    }
+    sqlx::query(sql::SUBMIT_TRAINING_FEEDBACK)
+        .bind(id)
+        .bind(feedback_form.rating)
+        .bind(feedback_form.comments.trim())
+        .bind(Utc::now())
+        .execute(&state.db)
+        .await?;
+    info!("{} left feedback on training session {id}", info.cid);
+    flashed_messages::push_flashed_message(
+        session,
+        MessageLevel::Success,
+        "Thanks for your feedback",
+    )
+    .await?;
+    Ok(redirect)
+}
+
+/// Students' feedback on a single mentor's sessions.
+#[derive(Debug, Serialize)]
+struct MentorFeedbackSummary {
+    cid: u32,
+    name: String,
+    count: usize,
+    average: f64,
+    /// Count of each rating, from 1 to 5.
+    distribution: [usize; 5],
+    feedback: Vec<TrainingFeedback>,
+}
+
+/// Summarize the feedback by mentor, best-rated first.
+fn summarize_feedback(
+    feedback: Vec<TrainingFeedback>,
+    names: &HashMap<u32, (String, String)>,
+) -> Vec<MentorFeedbackSummary> {
+    let mut by_mentor: HashMap<u32, Vec<TrainingFeedback>> = HashMap::new();
+    for item in feedback {
+        by_mentor.entry(item.instructor_cid).or_default().push(item);
+    }
+    let mut summaries: Vec<MentorFeedbackSummary> = by_mentor
+        .into_iter()
+        .map(|(cid, feedback)| {
+            let mut distribution = [0; 5];
+            let mut total = 0;
+            for rating in feedback.iter().filter_map(|item| item.rating) {
+                distribution[(rating.clamp(1, 5) - 1) as usize] += 1;
+                total += rating as u32;
+            }
+            MentorFeedbackSummary {
+                cid,
+                name: display_name(names, cid),
+                count: feedback.len(),
+                average: (total as f64 / feedback.len() as f64 * 10.0).round() / 10.0,
+                distribution,
+                feedback,
+            }
+        })
+        .collect();
+    summaries.sort_by(|a, b| b.average.total_cmp(&a.average).then(a.name.cmp(&b.name)));
+    summaries
+}
+
+/// Students' feedback on their sessions, summarized by mentor.
+///
+/// Students' names aren't shown so that they can be candid.
+async fn page_feedback_summary(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    if let Some(redirect) =
+        reject_without(&state, &user_info, permissions::TRAINING_FEEDBACK_VIEW).await
+    {
+        return Ok(redirect.into_response());
+    }
+    let feedback: Vec<TrainingFeedback> = sqlx::query_as(sql::GET_SUBMITTED_TRAINING_FEEDBACK)
+        .fetch_all(&state.db)
+        .await?;
+    let names = get_controller_cids_and_names(&state.db)
+        .await
+        .map_err(|e| AppError::GenericFallback("getting names and CIDs from DB", e))?;
+    let summaries = summarize_feedback(feedback, &names);
+    let template = state.templates.get_template("training/feedback_summary")?;
+    let rendered = template.render(context! { user_info, summaries })?;
+    Ok(Html(rendered).into_response())
+}
+
+pub fn router(templates: &mut Environment) -> Router<Arc<AppState>> {
+    templates
+        .add_template(
+            "training/syllabus",
+            include_str!("../../templates/training/syllabus.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "training/progress",
+            include_str!("../../templates/training/progress.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "training/sessions",
+            include_str!("../../templates/training/sessions.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "training/waitlist",
+            include_str!("../../templates/training/waitlist.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "training/session_feedback",
+            include_str!("../../templates/training/session_feedback.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "training/feedback_summary",
+            include_str!("../../templates/training/feedback_summary.jinja"),
+        )
+        .unwrap();
+
+    Router::new()
+        .route(
+            "/training/syllabus",
+            get(page_syllabus).post(post_new_lesson),
+        )
+        .route(
+            "/training/syllabus/:id",
+            post(post_edit_lesson).delete(api_delete_lesson),
+        )
+        .route("/training/syllabus/:id/move", post(post_move_lesson))
+        .route("/training/progress", get(page_own_progress))
+        .route("/training/progress/:cid", get(page_progress))
+        .route(
+            "/training/sessions",
+            get(page_sessions).post(post_book_session),
+        )
+        .route("/training/sessions/:id/cancel", post(post_cancel_session))
+        .route("/training/calendar.ics", get(get_training_calendar))
+        .route(
+            "/training/waitlist",
+            get(page_waitlist).post(post_join_waitlist),
+        )
+        .route(
+            "/training/waitlist/:id/remove",
+            post(post_remove_waitlist_entry),
+        )
+        .route("/training/feedback", get(page_feedback_summary))
+        .route(
+            "/training/feedback/:id",
+            get(page_session_feedback).post(post_session_feedback),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_vatusa_date, request_session_feedback};
+    use crate::test_utils::{test_app, ADMIN_CONTROLLER, HOME_CONTROLLER};
+    use axum::http::StatusCode;
+    use chrono::{TimeZone, Utc};
+    use vzdv::{
+        permissions,
+        sql::{self, TrainingLesson, TrainingSession},
+    };
+
+    #[test]
+    fn test_parse_vatusa_date() {
+        assert_eq!(
+            parse_vatusa_date("2024-06-01 18:30:00"),
+            Some(Utc.with_ymd_and_hms(2024, 6, 1, 18, 30, 0).unwrap())
+        );
+        assert_eq!(
+            parse_vatusa_date("2024-06-01"),
+            Some(Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap())
+        );
+        assert_eq!(parse_vatusa_date("yesterday"), None);
+    }
+
+    #[tokio::test]
+    async fn test_syllabus() {
+        let app = test_app().await;
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, _) = app
+            .post_form(
+                "/training/syllabus",
+                &[("certification", "GND"), ("name", "x"), ("objectives", "")],
+                Some(&cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let lessons: Vec<TrainingLesson> = sqlx::query_as(sql::GET_TRAINING_LESSONS)
+            .fetch_all(&app.db)
+            .await
+            .unwrap();
+        assert!(lessons.is_empty());
+
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        for name in ["Clearances", "Taxi", "Ready for OTS"] {
+            app.post_form(
+                "/training/syllabus",
+                &[
+                    ("certification", "GND"),
+                    ("name", name),
+                    ("objectives", "Things"),
+                ],
+                Some(&cookie),
+            )
+            .await;
+        }
+        app.post_form(
+            "/training/syllabus/2/move",
+            &[("direction", "up")],
+            Some(&cookie),
+        )
+        .await;
+        let lessons: Vec<TrainingLesson> = sqlx::query_as(sql::GET_TRAINING_LESSONS)
+            .fetch_all(&app.db)
+            .await
+            .unwrap();
+        let names: Vec<&str> = lessons.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, vec!["Taxi", "Clearances", "Ready for OTS"]);
+
+        let (status, body) = app.get("/training/syllabus", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Ready for OTS"));
+    }
+
+    #[tokio::test]
+    async fn test_progress() {
+        let app = test_app().await;
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        app.post_form(
+            "/training/syllabus",
+            &[
+                ("certification", "GND"),
+                ("name", "Clearances"),
+                ("objectives", ""),
+                ("milestone", "on"),
+            ],
+            Some(&cookie),
+        )
+        .await;
+        sqlx::query(sql::INSERT_TRAINING_LESSON_SESSION)
+            .bind(1)
+            .bind(HOME_CONTROLLER)
+            .bind(ADMIN_CONTROLLER)
+            .bind(Utc::now())
+            .execute(&app.db)
+            .await
+            .unwrap();
+
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, body) = app
+            .get(
+                &format!("/training/progress/{HOME_CONTROLLER}"),
+                Some(&cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Reached milestone GND: Clearances"));
+        assert!(body.contains("1 of 1"));
+
+        let (status, _) = app
+            .get(
+                &format!("/training/progress/{ADMIN_CONTROLLER}"),
+                Some(&cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+    }
+
+    #[tokio::test]
+    async fn test_sessions() {
+        let app = test_app().await;
+        let cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let (status, _) = app
+            .post_form(
+                "/training/sessions",
+                &[
+                    ("cid", &HOME_CONTROLLER.to_string()),
+                    ("position", "den_gnd"),
+                    ("start", "2099-01-01T18:00"),
+                    ("timezone", "America/Denver"),
+                    ("duration", "90"),
+                    ("notes", "Bring questions"),
+                ],
+                Some(&cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let session: TrainingSession = sqlx::query_as(sql::GET_TRAINING_SESSION)
+            .bind(1)
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(session.position, "DEN_GND");
+        assert_eq!(
+            session.start,
+            Utc.with_ymd_and_hms(2099, 1, 2, 1, 0, 0).unwrap()
+        );
+        assert_eq!(session.end - session.start, chrono::Duration::minutes(90));
+
+        app.post_form("/user/calendar_token", &[], Some(&cookie))
+            .await;
+        let token: String = sqlx::query_scalar(sql::GET_CALENDAR_TOKEN)
+            .bind(ADMIN_CONTROLLER)
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+
+        let student_cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, body) = app.get("/training/sessions", Some(&student_cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Bring questions"));
+        app.post_form("/training/sessions/1/cancel", &[], Some(&student_cookie))
+            .await;
+
+        let (status, body) = app
+            .get(&format!("/training/calendar.ics?token={token}"), None)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("UID:training-1@vzdv"));
+        assert!(body.contains("SEQUENCE:1"));
+        assert!(body.contains("STATUS:CANCELLED"));
+
+        app.post_form("/user/calendar_token", &[], Some(&student_cookie))
+            .await;
+        let token: String = sqlx::query_scalar(sql::GET_CALENDAR_TOKEN)
+            .bind(HOME_CONTROLLER)
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+        let (status, _) = app
+            .get(&format!("/training/calendar.ics?token={token}"), None)
+            .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (_, body) = app
+            .get(&format!("/controller/me/calendar.ics?token={token}"), None)
+            .await;
+        assert!(body.contains("UID:training-1@vzdv"));
+    }
+
+    #[tokio::test]
+    async fn test_waitlist() {
+        let app = test_app().await;
+        let in_training = |cid: u32| {
+            let db = app.db.clone();
+            async move {
+                sqlx::query_scalar::<_, String>(
+                    "SELECT value FROM certification WHERE cid=$1 AND name='GND'",
+                )
+                .bind(cid)
+                .fetch_optional(&db)
+                .await
+                .unwrap()
+                .as_deref()
+                    == Some("training")
+            }
+        };
+
+        // a slot is open, so the first student goes straight into training
+        let home_cookie = app.login_as(HOME_CONTROLLER, false).await;
+        app.post_form(
+            "/training/waitlist",
+            &[("certification", "GND")],
+            Some(&home_cookie),
+        )
+        .await;
+        assert!(in_training(HOME_CONTROLLER).await);
+
+        let admin_cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        app.post_form(
+            "/training/waitlist",
+            &[("certification", "GND")],
+            Some(&admin_cookie),
+        )
+        .await;
+        assert!(!in_training(ADMIN_CONTROLLER).await);
+        let (status, body) = app.get("/training/waitlist", Some(&admin_cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("1 of 1 training slots in use, 1 waiting"));
+        assert!(body.contains("number 1 in line"));
+
+        // the first student certifying opens the slot back up
+        app.post_form(
+            &format!("/controller/{HOME_CONTROLLER}/certs"),
+            &[("GND", "certified")],
+            Some(&admin_cookie),
+        )
+        .await;
+        assert!(in_training(ADMIN_CONTROLLER).await);
+        let (_, body) = app.get("/training/waitlist", Some(&home_cookie)).await;
+        assert!(body.contains("1 of 1 training slots in use, 0 waiting"));
+    }
+
+    #[tokio::test]
+    async fn test_session_feedback() {
+        let app = test_app().await;
+        request_session_feedback(
+            &app.state,
+            HOME_CONTROLLER,
+            ADMIN_CONTROLLER,
+            "DEN_GND",
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+
+        let admin_cookie = app.login_as(ADMIN_CONTROLLER, true).await;
+        let (status, _) = app.get("/training/feedback/1", Some(&admin_cookie)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, body) = app.get("/training/sessions", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("/training/feedback/1"));
+        let (status, _) = app
+            .post_form(
+                "/training/feedback/1",
+                &[("rating", "4"), ("comments", "Very helpful")],
+                Some(&cookie),
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let (_, body) = app.get("/training/feedback/1", Some(&cookie)).await;
+        assert!(body.contains("you rated this session 4 out of 5"));
+        let (status, _) = app.get("/training/feedback", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+
+        // only the TA has this by default
+        let (status, _) = app.get("/training/feedback", Some(&admin_cookie)).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        sqlx::query(sql::SET_CONTROLLER_PERMISSION)
+            .bind(ADMIN_CONTROLLER)
+            .bind(permissions::TRAINING_FEEDBACK_VIEW)
+            .bind(true)
+            .execute(&app.db)
+            .await
+            .unwrap();
+        let (status, body) = app.get("/training/feedback", Some(&admin_cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Admin Controller"));
+        assert!(body.contains("Very helpful"));
+    }
+}
@@ -7,18 +7,30 @@ use crate::{
 use axum::{
     extract::{Query, State},
     response::{Html, IntoResponse, Redirect, Response},
-    routing::get,
-    Router,
+    routing::{get, post},
+    Form, Router,
 };
+use chrono::{Duration, Utc};
 use log::{debug, info, warn};
 use minijinja::{context, Environment};
+use serde::Deserialize;
 use std::{collections::HashMap, sync::Arc};
 use tower_sessions::Session;
+use uuid::Uuid;
 use vzdv::{
-    sql::{self, Controller},
+    retrieve_all_in_use_ois,
+    sql::{self, Controller, DiscordLinkCode, NotificationPreference},
+    suggest_operating_initials,
     vatusa::{self, TrainingRecord},
+    OPERATING_INITIALS_CHOICE_DAYS,
 };
 
+/// How long a Discord link code can be used for.
+const DISCORD_LINK_CODE_MINUTES: i64 = 15;
+
+/// How many OIs to offer new controllers.
+const OPERATING_INITIALS_SUGGESTIONS: usize = 8;
+
 /// Retrieve and show the user their training records from VATUSA.
 async fn page_training_notes(
     State(state): State<Arc<AppState>>,
@@ -32,9 +44,7 @@ async fn page_training_notes(
         None => return Ok(Redirect::to("/").into_response()),
     };
     let all_training_records =
-        vatusa::get_training_records(&state.config.vatsim.vatusa_api_key, user_info.cid)
-            .await
-            .map_err(|e| AppError::GenericFallback("getting VATUSA training records", e))?;
+        vatusa::get_training_records(&state.config.vatsim.vatusa_api_key, user_info.cid).await?;
     let training_records: Vec<_> = all_training_records
         .iter()
         .filter(|record| record.facility_id == "ZDV")
@@ -67,6 +77,11 @@ async fn page_discord(
         .bind(user_info.cid)
         .fetch_one(&state.db)
         .await?;
+    let link_code: Option<DiscordLinkCode> = sqlx::query_as(sql::GET_DISCORD_LINK_CODE_FOR)
+        .bind(user_info.cid)
+        .bind(Utc::now())
+        .fetch_optional(&state.db)
+        .await?;
     let template = state.templates.get_template("user/discord")?;
     let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
     let rendered: String = template.render(context! {
@@ -74,11 +89,39 @@ async fn page_discord(
         oauth_link => discord::get_oauth_link(&state.config),
         join_link => &state.config.discord.join_link,
         discord_id => controller.discord_id,
+        link_code,
         flashed_messages
     })?;
     Ok(Html(rendered).into_response())
 }
 
+/// Generate a code for linking a Discord account with the bot's `/link` command.
+///
+/// This is a fallback for users who can't complete the OAuth flow.
+async fn post_discord_code(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Redirect, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let Some(user_info) = user_info else {
+        return Ok(Redirect::to("/"));
+    };
+    let now = Utc::now();
+    sqlx::query(sql::DELETE_EXPIRED_DISCORD_LINK_CODES)
+        .bind(now)
+        .execute(&state.db)
+        .await?;
+    let code = Uuid::new_v4().simple().to_string()[..8].to_uppercase();
+    sqlx::query(sql::SET_DISCORD_LINK_CODE)
+        .bind(user_info.cid)
+        .bind(&code)
+        .bind(now + Duration::minutes(DISCORD_LINK_CODE_MINUTES))
+        .execute(&state.db)
+        .await?;
+    info!("Generated Discord link code for {}", user_info.cid);
+    Ok(Redirect::to("/user/discord"))
+}
+
 /// Navigation from the Discord OAuth flow.
 async fn page_discord_callback(
     State(state): State<Arc<AppState>>,
@@ -133,6 +176,208 @@ async fn page_discord_callback(
     Ok(Redirect::to("/user/discord"))
 }
 
+/// Get the controller if they're on the roster and still need to choose their OIs.
+pub async fn needs_operating_initials(
+    state: &AppState,
+    cid: u32,
+) -> Result<Option<Controller>, AppError> {
+    let controller: Option<Controller> = sqlx::query_as(sql::GET_CONTROLLER_BY_CID)
+        .bind(cid)
+        .fetch_optional(&state.db)
+        .await?;
+    Ok(controller.filter(|c| c.is_on_roster && c.operating_initials.is_none()))
+}
+
+/// Get OIs for the controller to choose from.
+async fn operating_initials_choices(
+    state: &AppState,
+    controller: &Controller,
+) -> Result<Vec<String>, AppError> {
+    let in_use = retrieve_all_in_use_ois(&state.db)
+        .await
+        .map_err(|err| AppError::GenericFallback("accessing DB to get existing OIs", err))?;
+    Ok(suggest_operating_initials(
+        &in_use,
+        &controller.first_name,
+        &controller.last_name,
+        OPERATING_INITIALS_SUGGESTIONS,
+    ))
+}
+
+/// Onboarding step for new controllers to choose their operating initials.
+///
+/// Controllers who don't choose are assigned OIs by the roster task.
+async fn page_operating_initials(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let Some(user_info) = user_info else {
+        return Ok(Redirect::to("/").into_response());
+    };
+    let Some(controller) = needs_operating_initials(&state, user_info.cid).await? else {
+        return Ok(Redirect::to("/").into_response());
+    };
+    let suggestions = operating_initials_choices(&state, &controller).await?;
+    let deadline = controller
+        .join_date
+        .map(|joined| joined + Duration::days(OPERATING_INITIALS_CHOICE_DAYS));
+    let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
+    let template = state.templates.get_template("user/operating_initials")?;
+    let rendered = template.render(context! {
+        user_info,
+        flashed_messages,
+        suggestions,
+        deadline,
+    })?;
+    Ok(Html(rendered).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct OperatingInitialsForm {
+    initials: String,
+}
+
+/// Form submission for a new controller choosing their OIs.
+async fn post_operating_initials(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Form(initials_form): Form<OperatingInitialsForm>,
+) -> Result<Redirect, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let Some(user_info) = user_info else {
+        return Ok(Redirect::to("/"));
+    };
+    let Some(controller) = needs_operating_initials(&state, user_info.cid).await? else {
+        return Ok(Redirect::to("/"));
+    };
+    // only accept what's offered, which also confirms the OIs are still free
+    let suggestions = operating_initials_choices(&state, &controller).await?;
+    if !suggestions.contains(&initials_form.initials) {
+        flashed_messages::push_flashed_message(
+            session,
+            flashed_messages::MessageLevel::Error,
+            "Those OIs aren't available; please choose again",
+        )
+        .await?;
+        return Ok(Redirect::to("/user/operating_initials"));
+    }
+    sqlx::query(sql::UPDATE_CONTROLLER_OIS)
+        .bind(user_info.cid)
+        .bind(&initials_form.initials)
+        .execute(&state.db)
+        .await?;
+    info!(
+        "{} chose {} as their OIs",
+        user_info.cid, initials_form.initials
+    );
+    flashed_messages::push_flashed_message(
+        session,
+        flashed_messages::MessageLevel::Success,
+        &format!("Your OIs are {}", initials_form.initials),
+    )
+    .await?;
+    Ok(Redirect::to(&format!("/controller/{}", user_info.cid)))
+}
+
+/// Page for the user's notification preferences.
+async fn page_notifications(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let Some(user_info) = user_info else {
+        return Ok(Redirect::to("/").into_response());
+    };
+    let preference: Option<NotificationPreference> =
+        sqlx::query_as(sql::GET_NOTIFICATION_PREFERENCE)
+            .bind(user_info.cid)
+            .fetch_optional(&state.db)
+            .await?;
+    let preference = preference.unwrap_or_default();
+    let controller: Option<Controller> = state.repos.controllers.get_by_cid(user_info.cid).await?;
+    let discord_linked = controller.is_some_and(|c| c.discord_id.is_some());
+    let calendar_token: Option<String> = sqlx::query_scalar(sql::GET_CALENDAR_TOKEN)
+        .bind(user_info.cid)
+        .fetch_optional(&state.db)
+        .await?;
+    let calendar_url = calendar_token.map(|token| {
+        format!(
+            "{}/controller/me/calendar.ics?token={token}",
+            state.config.hosted_domain.trim_end_matches('/')
+        )
+    });
+    let flashed_messages = flashed_messages::drain_flashed_messages(session).await?;
+    let template = state.templates.get_template("user/notifications")?;
+    let rendered = template.render(context! {
+        user_info,
+        flashed_messages,
+        preference,
+        discord_linked,
+        calendar_url,
+    })?;
+    Ok(Html(rendered).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct NotificationsForm {
+    feedback_email: Option<String>,
+    feedback_discord: Option<String>,
+}
+
+/// Form submission for the user's notification preferences.
+async fn post_notifications(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+    Form(notifications_form): Form<NotificationsForm>,
+) -> Result<Redirect, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let Some(user_info) = user_info else {
+        return Ok(Redirect::to("/"));
+    };
+    sqlx::query(sql::SET_NOTIFICATION_PREFERENCE)
+        .bind(user_info.cid)
+        .bind(notifications_form.feedback_email.is_some())
+        .bind(notifications_form.feedback_discord.is_some())
+        .execute(&state.db)
+        .await?;
+    debug!("{} updated their notification preferences", user_info.cid);
+    flashed_messages::push_flashed_message(
+        session,
+        flashed_messages::MessageLevel::Success,
+        "Preferences saved",
+    )
+    .await?;
+    Ok(Redirect::to("/user/notifications"))
+}
+
+/// Create or replace the user's calendar feed link.
+///
+/// Replacing it stops anyone with the old link from seeing the feed.
+async fn post_calendar_token(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Redirect, AppError> {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await?;
+    let Some(user_info) = user_info else {
+        return Ok(Redirect::to("/"));
+    };
+    sqlx::query(sql::UPSERT_CALENDAR_TOKEN)
+        .bind(user_info.cid)
+        .bind(Uuid::new_v4().simple().to_string())
+        .bind(Utc::now())
+        .execute(&state.db)
+        .await?;
+    info!("{} created a new calendar feed link", user_info.cid);
+    flashed_messages::push_flashed_message(
+        session,
+        flashed_messages::MessageLevel::Success,
+        "New calendar link created",
+    )
+    .await?;
+    Ok(Redirect::to("/user/notifications"))
+}
+
 pub fn router(templates: &mut Environment) -> Router<Arc<AppState>> {
     templates
         .add_template(
@@ -146,9 +391,161 @@ pub fn router(templates: &mut Environment) -> Router<Arc<AppState>> {
             include_str!("../../templates/user/discord.jinja"),
         )
         .unwrap();
+    templates
+        .add_template(
+            "user/operating_initials",
+            include_str!("../../templates/user/operating_initials.jinja"),
+        )
+        .unwrap();
+    templates
+        .add_template(
+            "user/notifications",
+            include_str!("../../templates/user/notifications.jinja"),
+        )
+        .unwrap();
 
     Router::new()
         .route("/user/training_notes", get(page_training_notes))
         .route("/user/discord", get(page_discord))
         .route("/user/discord/callback", get(page_discord_callback))
+        .route("/user/discord/code", post(post_discord_code))
+        .route(
+            "/user/operating_initials",
+            get(page_operating_initials).post(post_operating_initials),
+        )
+        .route(
+            "/user/notifications",
+            get(page_notifications).post(post_notifications),
+        )
+        .route("/user/calendar_token", post(post_calendar_token))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::test_utils::{test_app, HOME_CONTROLLER};
+    use axum::http::StatusCode;
+
+    #[tokio::test]
+    async fn test_choose_operating_initials() {
+        let app = test_app().await;
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, body) = app.get("/user/operating_initials", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("value=\"HC\""));
+
+        // only suggested OIs can be chosen
+        app.post_form(
+            "/user/operating_initials",
+            &[("initials", "ZZ")],
+            Some(&cookie),
+        )
+        .await;
+        let (ois,): (Option<String>,) =
+            sqlx::query_as("SELECT operating_initials FROM controller WHERE cid=$1")
+                .bind(HOME_CONTROLLER)
+                .fetch_one(&app.db)
+                .await
+                .unwrap();
+        assert!(ois.is_none());
+
+        app.post_form(
+            "/user/operating_initials",
+            &[("initials", "HC")],
+            Some(&cookie),
+        )
+        .await;
+        let (ois,): (Option<String>,) =
+            sqlx::query_as("SELECT operating_initials FROM controller WHERE cid=$1")
+                .bind(HOME_CONTROLLER)
+                .fetch_one(&app.db)
+                .await
+                .unwrap();
+        assert_eq!(ois.as_deref(), Some("HC"));
+        let (status, _) = app.get("/user/operating_initials", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+    }
+
+    #[tokio::test]
+    async fn test_notification_preferences() {
+        let app = test_app().await;
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, body) = app.get("/user/notifications", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!body.contains("name=\"feedback_email\" checked"));
+
+        app.post_form(
+            "/user/notifications",
+            &[("feedback_email", "")],
+            Some(&cookie),
+        )
+        .await;
+        let (email, discord): (bool, bool) = sqlx::query_as(
+            "SELECT feedback_email, feedback_discord FROM notification_preference WHERE cid=$1",
+        )
+        .bind(HOME_CONTROLLER)
+        .fetch_one(&app.db)
+        .await
+        .unwrap();
+        assert!(email);
+        assert!(!discord);
+
+        app.post_form("/user/notifications", &[], Some(&cookie))
+            .await;
+        let (email,): (bool,) =
+            sqlx::query_as("SELECT feedback_email FROM notification_preference WHERE cid=$1")
+                .bind(HOME_CONTROLLER)
+                .fetch_one(&app.db)
+                .await
+                .unwrap();
+        assert!(!email);
+    }
+
+    #[tokio::test]
+    async fn test_calendar_feed() {
+        let app = test_app().await;
+        sqlx::query("UPDATE event_position SET cid=$1 WHERE name='DEN_APP'")
+            .bind(HOME_CONTROLLER)
+            .execute(&app.db)
+            .await
+            .unwrap();
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        app.post_form("/user/calendar_token", &[], Some(&cookie))
+            .await;
+        let token: String = sqlx::query_scalar("SELECT token FROM calendar_token WHERE cid=$1")
+            .bind(HOME_CONTROLLER)
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+
+        let (status, _) = app
+            .get("/controller/me/calendar.ics?token=wrong", None)
+            .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, body) = app
+            .get(&format!("/controller/me/calendar.ics?token={token}"), None)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("SUMMARY:Fixture FNO: DEN_APP"));
+        assert!(!body.contains("DEN_TWR"));
+    }
+
+    #[tokio::test]
+    async fn test_discord_link_code() {
+        let app = test_app().await;
+        let cookie = app.login_as(HOME_CONTROLLER, false).await;
+        let (status, body) = app.get("/user/discord", Some(&cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!body.contains("/link "));
+
+        app.post_form("/user/discord/code", &[], Some(&cookie))
+            .await;
+        let (code,): (String,) = sqlx::query_as("SELECT code FROM discord_link_code WHERE cid=$1")
+            .bind(HOME_CONTROLLER)
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(code.len(), 8);
+        let (_, body) = app.get("/user/discord", Some(&cookie)).await;
+        assert!(body.contains(&format!("/link {code}")));
+    }
 }
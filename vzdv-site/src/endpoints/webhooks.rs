@@ -0,0 +1,163 @@
+//! Callbacks from other services.
+
+use crate::{
+    endpoints::api::api_error,
+    shared::{AppError, AppState},
+};
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use log::{info, warn};
+use minijinja::Environment;
+use serde::Deserialize;
+use serde_json::json;
+use sha2::Sha256;
+use std::sync::Arc;
+use vzdv::sql;
+
+/// Header VATUSA sends the body's signature in.
+const VATUSA_SIGNATURE_HEADER: &str = "X-VATUSA-Signature";
+
+/// Roster or transfer change VATUSA is telling the facility about.
+#[derive(Debug, Deserialize)]
+struct VatusaWebhook {
+    cid: u32,
+    /// Like "roster.add", "roster.remove", or "transfer.accepted".
+    #[serde(default)]
+    event: String,
+}
+
+/// Check the hex HMAC-SHA256 signature of the body, which may be prefixed with "sha256=".
+fn valid_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let signature = signature.strip_prefix("sha256=").unwrap_or(signature);
+    let Some(signature) = decode_hex(signature) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Queue a re-sync of the controller VATUSA says changed.
+///
+/// The task runner picks it up within a minute, rather than waiting for
+/// the next full roster sync.
+async fn post_vatusa_webhook(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, AppError> {
+    let secret = &state.config.vatsim.vatusa_webhook_secret;
+    if secret.is_empty() {
+        return Ok(api_error(
+            StatusCode::NOT_FOUND,
+            "VATUSA webhooks are not configured",
+        ));
+    }
+    let signature = headers
+        .get(VATUSA_SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if !valid_signature(secret, &body, signature) {
+        warn!("Rejected VATUSA webhook with an invalid signature");
+        return Ok(api_error(StatusCode::UNAUTHORIZED, "Invalid signature"));
+    }
+    let Ok(webhook) = serde_json::from_slice::<VatusaWebhook>(&body) else {
+        return Ok(api_error(StatusCode::BAD_REQUEST, "Unrecognized payload"));
+    };
+    sqlx::query(sql::QUEUE_ROSTER_REFRESH)
+        .bind(webhook.cid)
+        .bind(&webhook.event)
+        .bind(Utc::now())
+        .execute(&state.db)
+        .await?;
+    info!(
+        "VATUSA webhook \"{}\" queued a roster refresh for {}",
+        webhook.event, webhook.cid
+    );
+    Ok((StatusCode::ACCEPTED, Json(json!({ "queued": webhook.cid }))).into_response())
+}
+
+pub fn router(_templates: &mut Environment) -> Router<Arc<AppState>> {
+    Router::new().route("/webhooks/vatusa", post(post_vatusa_webhook))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{valid_signature, VATUSA_SIGNATURE_HEADER};
+    use crate::test_utils::{test_app, HOME_CONTROLLER, TEST_WEBHOOK_SECRET};
+    use axum::http::StatusCode;
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+    use vzdv::sql::{self, RosterRefresh};
+
+    fn sign(body: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(TEST_WEBHOOK_SECRET.as_bytes()).unwrap();
+        mac.update(body.as_bytes());
+        format!("sha256={:x}", mac.finalize().into_bytes())
+    }
+
+    #[test]
+    fn test_valid_signature() {
+        let body = r#"{"cid":1}"#;
+        assert!(valid_signature(
+            TEST_WEBHOOK_SECRET,
+            body.as_bytes(),
+            &sign(body)
+        ));
+        assert!(!valid_signature("other", body.as_bytes(), &sign(body)));
+        assert!(!valid_signature(TEST_WEBHOOK_SECRET, body.as_bytes(), "zz"));
+        assert!(!valid_signature(TEST_WEBHOOK_SECRET, body.as_bytes(), ""));
+    }
+
+    #[tokio::test]
+    async fn test_vatusa_webhook() {
+        let app = test_app().await;
+        let body = format!(r#"{{"cid":{HOME_CONTROLLER},"event":"roster.remove"}}"#);
+
+        let (status, _) = app
+            .post_with_headers(
+                "/webhooks/vatusa",
+                &body,
+                &[(VATUSA_SIGNATURE_HEADER, "sha256=00")],
+            )
+            .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let signature = sign(&body);
+        let (status, _) = app
+            .post_with_headers(
+                "/webhooks/vatusa",
+                &body,
+                &[(VATUSA_SIGNATURE_HEADER, &signature)],
+            )
+            .await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let queued: Vec<RosterRefresh> = sqlx::query_as(sql::GET_QUEUED_ROSTER_REFRESHES)
+            .fetch_all(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(queued.len(), 1);
+        assert_eq!(queued[0].cid, HOME_CONTROLLER);
+        assert_eq!(queued[0].reason, "roster.remove");
+    }
+}
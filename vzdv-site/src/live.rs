@@ -0,0 +1,77 @@
+//! Pushing periodically re-rendered page sections to browsers as server-sent events.
+//!
+//! The sections are rendered through the cache, so each refresh cycle renders
+//! a section once no matter how many browsers are connected.
+
+use crate::shared::AppError;
+use axum::response::sse::Event;
+use futures_util::{stream, Stream, StreamExt};
+use log::warn;
+use std::{convert::Infallible, future::Future, time::Duration};
+use tokio::time;
+
+/// Stream the output of `render` every interval, skipping outputs that are the
+/// same as the last one sent.
+///
+/// Errors from rendering are logged and the interval is skipped.
+pub fn changes<F, Fut>(interval: Duration, render: F) -> impl Stream<Item = String>
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<String, AppError>> + Send,
+{
+    stream::unfold((render, None), move |(render, last)| async move {
+        let mut last: Option<String> = last;
+        loop {
+            time::sleep(interval).await;
+            match render().await {
+                Ok(output) if last.as_ref() != Some(&output) => {
+                    last = Some(output.clone());
+                    return Some((output, (render, last)));
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Error rendering live update: {e}"),
+            }
+        }
+    })
+}
+
+/// Stream the changed outputs of `render` as server-sent events with the name.
+pub fn updates<F, Fut>(
+    name: &'static str,
+    interval: Duration,
+    render: F,
+) -> impl Stream<Item = Result<Event, Infallible>>
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<String, AppError>> + Send,
+{
+    changes(interval, render).map(move |output| Ok(Event::default().event(name).data(output)))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::changes;
+    use futures_util::StreamExt;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    #[tokio::test]
+    async fn test_changes() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let outputs = ["a", "a", "b", "b", "a"];
+        let stream = changes(Duration::from_millis(1), move || {
+            let calls = calls.clone();
+            async move {
+                let call = calls.fetch_add(1, Ordering::SeqCst);
+                Ok(outputs[call.min(outputs.len() - 1)].to_owned())
+            }
+        });
+        let sent: Vec<String> = stream.take(3).collect().await;
+        assert_eq!(sent, ["a", "b", "a"]);
+    }
+}
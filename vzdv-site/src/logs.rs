@@ -0,0 +1,248 @@
+//! Reading and filtering the binaries' log files for the admin log viewer.
+
+use axum::response::sse::Event;
+use futures_util::{stream, Stream};
+use rev_buf_reader::RevBufReader;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    convert::Infallible,
+    fs::File,
+    io::{self, BufRead, SeekFrom},
+    time::Duration,
+};
+use tokio::{
+    fs::File as AsyncFile,
+    io::{AsyncReadExt, AsyncSeekExt},
+    time,
+};
+
+/// Log file for each binary, by the key used in the viewer.
+pub const LOG_FILES: [(&str, &str); 3] = [
+    ("site", "vzdv_site.log"),
+    ("tasks", "vzdv_tasks.log"),
+    ("bot", "vzdv_bot.log"),
+];
+
+/// How often the live tail checks the file for new lines.
+const TAIL_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Get the log file name for the binary key, if known.
+pub fn log_file_for(key: &str) -> Option<&'static str> {
+    LOG_FILES
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, file)| *file)
+}
+
+/// A single line from a log file.
+///
+/// Lines that aren't in the standard format (like the continuation of a
+/// multi-line message) have only the message set.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct LogLine {
+    pub timestamp: String,
+    pub level: String,
+    pub target: String,
+    /// ID of the site request being handled when the line was written.
+    pub request_id: String,
+    pub message: String,
+}
+
+impl LogLine {
+    /// Parse a line written by the logger set up in `vzdv::general_setup`.
+    pub fn parse(line: &str) -> Self {
+        let parsed = line.strip_prefix('[').and_then(|rest| {
+            let (header, message) = rest.split_once("] ")?;
+            let mut parts = header.splitn(4, ' ');
+            Some(Self {
+                timestamp: parts.next()?.to_owned(),
+                level: parts.next()?.to_owned(),
+                target: parts.next()?.to_owned(),
+                request_id: parts.next().unwrap_or_default().to_owned(),
+                message: message.to_owned(),
+            })
+        });
+        parsed.unwrap_or_else(|| Self {
+            timestamp: String::new(),
+            level: String::new(),
+            target: String::new(),
+            request_id: String::new(),
+            message: line.to_owned(),
+        })
+    }
+}
+
+/// Filters from the viewer's query string.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct LogFilter {
+    /// Minimum level to show; all levels if not set.
+    pub level: Option<String>,
+    /// Text the line's target must contain.
+    pub target: Option<String>,
+    /// Text the line's message must contain, ignoring case, or its request ID.
+    pub text: Option<String>,
+}
+
+/// Severity order of the levels, most severe first.
+fn level_rank(level: &str) -> Option<u8> {
+    match level {
+        "ERROR" => Some(1),
+        "WARN" => Some(2),
+        "INFO" => Some(3),
+        "DEBUG" => Some(4),
+        "TRACE" => Some(5),
+        _ => None,
+    }
+}
+
+impl LogFilter {
+    /// Whether the line passes all the set filters.
+    pub fn matches(&self, line: &LogLine) -> bool {
+        if let Some(min) = self.level.as_deref().and_then(level_rank) {
+            match level_rank(&line.level) {
+                Some(rank) if rank <= min => {}
+                _ => return false,
+            }
+        }
+        if let Some(target) = self.target.as_deref().filter(|t| !t.is_empty()) {
+            if !line.target.contains(target) {
+                return false;
+            }
+        }
+        if let Some(text) = self.text.as_deref().filter(|t| !t.is_empty()) {
+            if line.request_id != text
+                && !line.message.to_lowercase().contains(&text.to_lowercase())
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Read a page of matching lines, counting backwards from the end of the file.
+///
+/// Page 0 is the newest lines. Returns the lines oldest first, and whether
+/// there are older matching lines.
+pub fn read_page(
+    file_name: &str,
+    filter: &LogFilter,
+    page: usize,
+    per_page: usize,
+) -> io::Result<(Vec<LogLine>, bool)> {
+    let file = File::open(file_name)?;
+    let mut matching = RevBufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .map(|line| LogLine::parse(&line))
+        .filter(|line| filter.matches(line))
+        .skip(page * per_page);
+    let mut lines: Vec<_> = matching.by_ref().take(per_page).collect();
+    let has_more = matching.next().is_some();
+    lines.reverse();
+    Ok((lines, has_more))
+}
+
+struct TailState {
+    file: AsyncFile,
+    position: u64,
+    partial: String,
+    pending: VecDeque<LogLine>,
+    filter: LogFilter,
+}
+
+/// Stream lines matching the filter as they're written to the file.
+///
+/// Starts at the current end of the file. If the file shrinks, it's
+/// assumed to have been replaced and is read again from the start.
+pub async fn tail(
+    file_name: &str,
+    filter: LogFilter,
+) -> io::Result<impl Stream<Item = Result<Event, Infallible>>> {
+    let mut file = AsyncFile::open(file_name).await?;
+    let position = file.seek(SeekFrom::End(0)).await?;
+    let state = TailState {
+        file,
+        position,
+        partial: String::new(),
+        pending: VecDeque::new(),
+        filter,
+    };
+    Ok(stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(line) = state.pending.pop_front() {
+                let event = Event::default()
+                    .json_data(&line)
+                    .unwrap_or_else(|_| Event::default());
+                return Some((Ok(event), state));
+            }
+            time::sleep(TAIL_POLL_INTERVAL).await;
+            let length = match state.file.metadata().await {
+                Ok(m) => m.len(),
+                Err(_) => return None,
+            };
+            if length < state.position {
+                state.position = 0;
+                state.partial.clear();
+                if state.file.seek(SeekFrom::Start(0)).await.is_err() {
+                    return None;
+                }
+            }
+            let mut buffer = String::new();
+            match state.file.read_to_string(&mut buffer).await {
+                Ok(read) => state.position += read as u64,
+                Err(_) => return None,
+            }
+            state.partial.push_str(&buffer);
+            while let Some(index) = state.partial.find('\n') {
+                let raw: String = state.partial.drain(..=index).collect();
+                let line = LogLine::parse(raw.trim_end());
+                if state.filter.matches(&line) {
+                    state.pending.push_back(line);
+                }
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{LogFilter, LogLine};
+
+    #[test]
+    fn test_parse_and_filter() {
+        let line = LogLine::parse("[2024-08-01T12:00:00Z WARN vzdv_site::endpoints] Something odd");
+        assert_eq!(line.level, "WARN");
+        assert_eq!(line.target, "vzdv_site::endpoints");
+        assert_eq!(line.request_id, "");
+        assert_eq!(line.message, "Something odd");
+
+        let with_id = LogLine::parse(
+            "[2024-08-01T12:00:00Z ERROR vzdv_site::shared 3fa9c0d2e1b7] Unhandled error",
+        );
+        assert_eq!(with_id.target, "vzdv_site::shared");
+        assert_eq!(with_id.request_id, "3fa9c0d2e1b7");
+        let by_id = LogFilter {
+            text: Some("3fa9c0d2e1b7".to_owned()),
+            ..Default::default()
+        };
+        assert!(by_id.matches(&with_id));
+        assert!(!by_id.matches(&line));
+
+        let continuation = LogLine::parse("    at some/file.rs:12");
+        assert_eq!(continuation.level, "");
+        assert_eq!(continuation.message, "    at some/file.rs:12");
+
+        let filter = LogFilter {
+            level: Some("WARN".to_owned()),
+            target: Some("endpoints".to_owned()),
+            text: Some("ODD".to_owned()),
+        };
+        assert!(filter.matches(&line));
+        assert!(!filter.matches(&continuation));
+        let info = LogLine::parse("[2024-08-01T12:00:00Z INFO vzdv_site::endpoints] Something odd");
+        assert!(!filter.matches(&info));
+        assert!(LogFilter::default().matches(&continuation));
+    }
+}
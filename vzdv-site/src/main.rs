@@ -3,32 +3,59 @@
 #![deny(clippy::all)]
 #![deny(unsafe_code)]
 
-use axum::{middleware as axum_middleware, Router};
+use axum::{middleware as axum_middleware, Extension, Router};
+use axum_server::{tls_rustls::RustlsConfig, Handle};
+use cache::TypedCache;
 use clap::Parser;
 use log::{debug, error, info, warn};
-use mini_moka::sync::Cache;
 use minijinja::Environment;
-use shared::{AppError, AppState, ERROR_WEBHOOK};
+use shared::{AppState, ERROR_WEBHOOK};
 use std::{
     fs,
+    net::SocketAddr,
     path::{Path, PathBuf},
     process,
     sync::Arc,
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 use tokio::signal;
 use tower::ServiceBuilder;
-use tower_http::timeout::TimeoutLayer;
+use tower_http::{
+    compression::{
+        predicate::{NotForContentType, Predicate},
+        CompressionLayer, DefaultPredicate,
+    },
+    timeout::TimeoutLayer,
+};
 use tower_sessions::SessionManagerLayer;
 use tower_sessions_sqlx_store::SqliteStore;
-use vzdv::general_setup;
+use vzdv::{config::ConfigTls, error_reporting, general_setup, repo::Repos};
 
+mod blocklist;
+mod cache;
 mod discord;
 mod email;
 mod endpoints;
 mod flashed_messages;
+mod live;
+mod logs;
+mod metrics;
 mod middleware;
+mod post_image;
+mod remember_me;
 mod shared;
+mod templating;
+#[cfg(test)]
+mod test_utils;
+mod tokens;
+
+/// How often to send queued welcome emails and exit surveys.
+const QUEUED_EMAIL_INTERVAL: Duration = Duration::from_secs(60 * 5);
+/// How often to remove the controllers in approved purges.
+const APPROVED_PURGE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often to check the TLS certificate and key files for changes.
+const TLS_RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 5);
 
 /// vZDV website.
 #[derive(Parser)]
@@ -40,6 +67,12 @@ struct Cli {
     #[arg(long)]
     config: Option<PathBuf>,
 
+    /// Merge the config overlay for this environment, like "dev" for "vzdv.dev.toml".
+    ///
+    /// [default: the VZDV_ENV environment variable, if set]
+    #[arg(long)]
+    env: Option<String>,
+
     /// Enable debug logging
     #[arg(short, long)]
     debug: bool,
@@ -53,12 +86,14 @@ struct Cli {
     port: u16,
 }
 
-/// Load all template files into the binary via the stdlib `include_str!`
-/// macro and supply to the minijinja environment.
-fn load_templates() -> Result<Environment<'static>, AppError> {
-    let mut env = Environment::new();
-    env.add_template("_layout", include_str!("../templates/_layout.jinja"))?;
-    Ok(env)
+/// Which responses to compress.
+///
+/// The defaults skip images, event streams, and tiny bodies; PDFs and
+/// archives from the resources are already compressed.
+fn compression_predicate() -> impl Predicate {
+    DefaultPredicate::new()
+        .and(NotForContentType::const_new("application/pdf"))
+        .and(NotForContentType::const_new("application/zip"))
 }
 
 /// Create all the endpoints and insert middleware.
@@ -69,18 +104,29 @@ fn load_router(
     Router::new()
         .merge(endpoints::router(env))
         .merge(endpoints::admin::router(env))
+        .merge(endpoints::api::router(env))
         .merge(endpoints::airspace::router(env))
         .merge(endpoints::auth::router(env))
         .merge(endpoints::controller::router(env))
         .merge(endpoints::events::router(env))
         .merge(endpoints::facility::router(env))
         .merge(endpoints::homepage::router(env))
+        .merge(endpoints::oauth::router(env))
+        .merge(endpoints::training::router(env))
         .merge(endpoints::user::router(env))
+        .merge(endpoints::webhooks::router(env))
         .layer(
             ServiceBuilder::new()
+                .layer(axum_middleware::from_fn(middleware::request_id))
+                .layer(CompressionLayer::new().compress_when(compression_predicate()))
                 .layer(TimeoutLayer::new(Duration::from_secs(30)))
                 .layer(axum_middleware::from_fn(middleware::logging))
-                .layer(sessions_layer),
+                .layer(sessions_layer)
+                .layer(axum_middleware::from_fn(middleware::remember_me))
+                .layer(axum_middleware::from_fn(middleware::error_context))
+                .layer(axum_middleware::from_fn(middleware::impersonation))
+                .layer(axum_middleware::from_fn(middleware::maintenance))
+                .layer(axum_middleware::from_fn(middleware::blocklist)),
         )
         .fallback(endpoints::page_404)
 }
@@ -112,15 +158,58 @@ async fn shutdown_signal() {
     }
 }
 
+/// Last modified times of the TLS certificate and key files.
+fn tls_files_modified(tls: &ConfigTls) -> Option<(SystemTime, SystemTime)> {
+    let cert = fs::metadata(&tls.cert_file)
+        .and_then(|m| m.modified())
+        .ok()?;
+    let key = fs::metadata(&tls.key_file)
+        .and_then(|m| m.modified())
+        .ok()?;
+    Some((cert, key))
+}
+
+/// Reload the TLS certificate and key whenever the files change, like on renewal.
+async fn reload_tls_on_change(tls: ConfigTls, rustls_config: RustlsConfig) {
+    let mut last_modified = tls_files_modified(&tls);
+    loop {
+        tokio::time::sleep(TLS_RELOAD_CHECK_INTERVAL).await;
+        let modified = tls_files_modified(&tls);
+        if modified.is_none() || modified == last_modified {
+            continue;
+        }
+        match rustls_config
+            .reload_from_pem_file(&tls.cert_file, &tls.key_file)
+            .await
+        {
+            Ok(_) => {
+                info!("Reloaded the TLS certificate and key");
+                last_modified = modified;
+            }
+            Err(e) => error!("Could not reload the TLS certificate and key: {e}"),
+        }
+    }
+}
+
 /// Entrypoint.
 #[allow(clippy::needless_return)] // https://github.com/rust-lang/rust-clippy/issues/13458
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
-    let (config, db) = general_setup(cli.debug, "vzdv_site", cli.config).await;
+    let (config, db) = general_setup(cli.debug, "vzdv_site", cli.config, cli.env).await;
+    let _error_reporting = error_reporting::init(&config, "vzdv_site");
     ERROR_WEBHOOK
         .set(config.discord.webhooks.errors.clone())
         .expect("Could not set global error webhook");
+    {
+        let url = config.discord.webhooks.errors.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                error_reporting::flush_webhook_summaries(&url).await;
+            }
+        });
+    }
 
     let sessions = SqliteStore::new(db.clone());
     if let Err(e) = sessions.migrate().await {
@@ -130,7 +219,7 @@ async fn main() {
     // "lax" seems to be needed for the Discord OAuth login, but is there a concern about security?
     let session_layer =
         SessionManagerLayer::new(sessions).with_same_site(tower_sessions::cookie::SameSite::Lax);
-    let mut templates = match load_templates() {
+    let mut templates = match templating::environment() {
         Ok(t) => t,
         Err(e) => {
             error!("Could not load the first templates: {e}");
@@ -141,13 +230,44 @@ async fn main() {
 
     debug!("Setting up app");
     let router = load_router(session_layer, &mut templates);
+    let tls = config.tls.clone();
     let app_state = Arc::new(AppState {
         config,
         db: db.clone(),
+        repos: Repos::sqlite(&db),
         templates,
-        cache: Cache::new(10),
+        cache: TypedCache::new(10),
     });
-    let app = router.with_state(app_state);
+    {
+        let app_state = app_state.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(QUEUED_EMAIL_INTERVAL).await;
+                if let Err(e) = email::send_welcome_emails(&app_state.config, &app_state.db).await {
+                    error!("Error sending welcome emails: {e}");
+                }
+                if let Err(e) = email::send_exit_surveys(&app_state.config, &app_state.db).await {
+                    error!("Error sending exit surveys: {e}");
+                }
+            }
+        });
+    }
+    {
+        let app_state = app_state.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(APPROVED_PURGE_INTERVAL).await;
+                if let Err(e) =
+                    endpoints::admin::remove_approved_purges(&app_state.config, &app_state.db).await
+                {
+                    error!("Error removing approved purges: {e}");
+                }
+            }
+        });
+    }
+    let app = router
+        .with_state(app_state.clone())
+        .layer(Extension(app_state));
     let assets_dir = Path::new("./assets");
     if !assets_dir.exists() {
         if let Err(e) = fs::create_dir(assets_dir) {
@@ -159,13 +279,58 @@ async fn main() {
     debug!("Set up");
 
     let host_and_port = format!("{}:{}", cli.host, cli.port);
-    info!("Listening on http://{host_and_port}/");
-    let listener = tokio::net::TcpListener::bind(&host_and_port)
-        .await
-        .expect("Could not bind the HTTP listener");
-    axum::serve(listener, app)
+    if tls.cert_file.is_empty() {
+        info!("Listening on http://{host_and_port}/");
+        let listener = tokio::net::TcpListener::bind(&host_and_port)
+            .await
+            .expect("Could not bind the HTTP listener");
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
         .with_graceful_shutdown(shutdown_signal())
         .await
         .expect("Could not serve the app");
+    } else {
+        // axum-server is built without a default provider, so rustls needs one picked
+        // before it can load anything; only fails if one is already installed
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let rustls_config = match RustlsConfig::from_pem_file(&tls.cert_file, &tls.key_file).await {
+            Ok(c) => c,
+            Err(e) => {
+                error!("Could not load the TLS certificate and key: {e}");
+                process::exit(1);
+            }
+        };
+        tokio::spawn(reload_tls_on_change(tls, rustls_config.clone()));
+        // resolved like the plain HTTP listener does, so hostnames work too
+        let addr = match tokio::net::lookup_host(&host_and_port).await {
+            Ok(mut addrs) => match addrs.next() {
+                Some(addr) => addr,
+                None => {
+                    error!("No addresses found for {host_and_port}");
+                    process::exit(1);
+                }
+            },
+            Err(e) => {
+                error!("Could not resolve {host_and_port}: {e}");
+                process::exit(1);
+            }
+        };
+        let handle = Handle::new();
+        {
+            let handle = handle.clone();
+            tokio::spawn(async move {
+                shutdown_signal().await;
+                handle.graceful_shutdown(Some(Duration::from_secs(10)));
+            });
+        }
+        info!("Listening on https://{host_and_port}/");
+        axum_server::bind_rustls(addr, rustls_config)
+            .handle(handle)
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+            .await
+            .expect("Could not serve the app");
+    }
     db.close().await;
 }
@@ -0,0 +1,147 @@
+//! In-memory request metrics, per route.
+//!
+//! Recorded by the logging middleware and shown on the admin stats page.
+//! Counts reset when the site restarts.
+
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+/// Upper bounds of the latency histogram buckets, in milliseconds.
+/// Anything slower goes in a final overflow bucket.
+const BUCKET_BOUNDS_MS: [u64; 9] = [10, 25, 50, 100, 250, 500, 1_000, 2_500, 5_000];
+
+/// Metrics for the whole site.
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+#[derive(Default)]
+struct RouteMetrics {
+    /// Count of requests in each bucket; one more than the bounds for the overflow.
+    buckets: [u64; BUCKET_BOUNDS_MS.len() + 1],
+    /// Count of responses by status class, 1xx through 5xx.
+    status_classes: [u64; 5],
+    total_ms: u64,
+    max_ms: u64,
+}
+
+impl RouteMetrics {
+    fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// Estimate the percentile as the upper bound of the bucket it falls in.
+    ///
+    /// Returns `None` if it falls in the overflow bucket.
+    fn percentile_ms(&self, percentile: f64) -> Option<u64> {
+        let target = (self.count() as f64 * percentile).ceil() as u64;
+        let mut seen = 0;
+        for (index, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                return BUCKET_BOUNDS_MS.get(index).copied();
+            }
+        }
+        None
+    }
+}
+
+/// Summary of a route's metrics for display.
+#[derive(Debug, Serialize)]
+pub struct RouteSummary {
+    pub route: String,
+    pub count: u64,
+    pub client_errors: u64,
+    pub server_errors: u64,
+    /// Percent of requests that returned a 5xx status.
+    pub error_rate: f64,
+    pub average_ms: u64,
+    /// `None` if slower than the largest bucket.
+    pub p50_ms: Option<u64>,
+    pub p95_ms: Option<u64>,
+    pub max_ms: u64,
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    routes: Mutex<HashMap<String, RouteMetrics>>,
+}
+
+impl Metrics {
+    /// Record a handled request.
+    pub fn record(&self, route: &str, status: u16, elapsed: Duration) {
+        let ms = elapsed.as_millis() as u64;
+        let mut routes = self.routes.lock().unwrap();
+        let metrics = routes.entry(route.to_owned()).or_default();
+        let bucket = BUCKET_BOUNDS_MS
+            .iter()
+            .position(|bound| ms <= *bound)
+            .unwrap_or(BUCKET_BOUNDS_MS.len());
+        metrics.buckets[bucket] += 1;
+        if let Some(class) = (status / 100).checked_sub(1) {
+            if let Some(count) = metrics.status_classes.get_mut(class as usize) {
+                *count += 1;
+            }
+        }
+        metrics.total_ms += ms;
+        metrics.max_ms = metrics.max_ms.max(ms);
+    }
+
+    /// Summaries of all routes, slowest on average first.
+    pub fn summaries(&self) -> Vec<RouteSummary> {
+        let routes = self.routes.lock().unwrap();
+        let mut summaries: Vec<_> = routes
+            .iter()
+            .map(|(route, metrics)| {
+                let count = metrics.count();
+                RouteSummary {
+                    route: route.clone(),
+                    count,
+                    client_errors: metrics.status_classes[3],
+                    server_errors: metrics.status_classes[4],
+                    error_rate: metrics.status_classes[4] as f64 * 100.0 / count.max(1) as f64,
+                    average_ms: metrics.total_ms / count.max(1),
+                    p50_ms: metrics.percentile_ms(0.5),
+                    p95_ms: metrics.percentile_ms(0.95),
+                    max_ms: metrics.max_ms,
+                }
+            })
+            .collect();
+        summaries.sort_by_key(|summary| std::cmp::Reverse(summary.average_ms));
+        summaries
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::Metrics;
+    use std::time::Duration;
+
+    #[test]
+    fn test_route_summaries() {
+        let metrics = Metrics::default();
+        for ms in [5, 8, 20, 40, 90] {
+            metrics.record("GET /", 200, Duration::from_millis(ms));
+        }
+        metrics.record("GET /roster", 200, Duration::from_millis(700));
+        metrics.record("GET /roster", 500, Duration::from_millis(9_000));
+        metrics.record("GET /roster", 404, Duration::from_millis(1));
+
+        let summaries = metrics.summaries();
+        assert_eq!(summaries[0].route, "GET /roster");
+        assert_eq!(summaries[0].count, 3);
+        assert_eq!(summaries[0].server_errors, 1);
+        assert_eq!(summaries[0].client_errors, 1);
+        assert_eq!(summaries[0].p50_ms, Some(1_000));
+        assert_eq!(summaries[0].p95_ms, None);
+        assert_eq!(summaries[0].max_ms, 9_000);
+
+        assert_eq!(summaries[1].route, "GET /");
+        assert_eq!(summaries[1].average_ms, 32);
+        assert_eq!(summaries[1].p50_ms, Some(25));
+        assert_eq!(summaries[1].p95_ms, Some(100));
+        assert_eq!(summaries[1].error_rate, 0.0);
+    }
+}
@@ -1,22 +1,78 @@
 //! App middleware functions.
 
-use axum::{extract::Request, middleware::Next, response::Response};
-use log::{debug, warn};
-use std::{collections::HashSet, sync::LazyLock};
+use crate::{
+    blocklist::{active_blocks, client_ip, matching_block},
+    flashed_messages::{self, MessageLevel},
+    metrics::METRICS,
+    remember_me,
+    shared::{
+        has_permission, maintenance_mode, AppError, AppState, UserInfo, SESSION_USER_INFO_KEY,
+    },
+};
+use axum::{
+    extract::{MatchedPath, Request},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{Html, IntoResponse, Redirect, Response},
+    Extension,
+};
+use chrono::Utc;
+use log::{debug, error, info, warn};
+use minijinja::context;
+use std::{
+    collections::HashSet,
+    sync::{Arc, LazyLock},
+    time::Instant,
+};
+use tower_sessions::Session;
+use uuid::Uuid;
+use vzdv::{
+    error_reporting, permissions,
+    request_id::{self, REQUEST_ID, REQUEST_ID_HEADER},
+    sql,
+};
 
 static IGNORE_PATHS: LazyLock<HashSet<&str>> = LazyLock::new(|| HashSet::from(["/favicon.ico"]));
 
+/// Path prefixes that stay reachable in maintenance mode, so that staff
+/// can still log in and pages can still load their styles.
+const MAINTENANCE_ALLOWED_PREFIXES: &[&str] = &["/auth/", "/assets/", "/banners", "/webhooks/"];
+
+/// Assign the request an ID, available to everything handling it, and
+/// return it in a response header.
+pub async fn request_id(request: Request, next: Next) -> Response {
+    let id = Uuid::new_v4().simple().to_string()[..12].to_owned();
+    let mut response = REQUEST_ID.scope(id.clone(), next.run(request)).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
 /// Simple logging middleware.
 ///
 /// Logs the method, path, and response code to debug
 /// if processing returned a successful code, and to
-/// warn otherwise.
+/// warn otherwise. Also records the route's timing and
+/// status in the metrics.
 pub async fn logging(request: Request, next: Next) -> Response {
     let uri = request.uri().clone();
     let path = uri.path();
     if !IGNORE_PATHS.contains(path) {
         let method = request.method().clone();
+        // group by the route template, so that path parameters don't each get their own entry
+        let route = request
+            .extensions()
+            .get::<MatchedPath>()
+            .map(|matched| matched.as_str().to_owned())
+            .unwrap_or_else(|| "(unmatched)".to_owned());
+        let started = Instant::now();
         let response = next.run(request).await;
+        METRICS.record(
+            &format!("{method} {route}"),
+            response.status().as_u16(),
+            started.elapsed(),
+        );
         let s = format!("{} {} {}", method, path, response.status().as_u16());
         if response.status().is_success() || response.status().is_redirection() {
             debug!("{s}");
@@ -28,3 +84,168 @@ pub async fn logging(request: Request, next: Next) -> Response {
         next.run(request).await
     }
 }
+
+/// Attach the endpoint and logged-in user to any errors reported while
+/// handling the request.
+pub async fn error_context(session: Session, request: Request, next: Next) -> Response {
+    let endpoint = format!("{} {}", request.method(), request.uri().path());
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await.ok().flatten();
+    let mut tags = vec![("endpoint", endpoint)];
+    if let Some(id) = request_id::current() {
+        tags.push(("request_id", id));
+    }
+    error_reporting::with_context(&tags, user_info.map(|info| info.cid), next.run(request)).await
+}
+
+/// Log users back in from their "keep me signed in" token if their session is gone.
+///
+/// Needs the app state as a request extension.
+pub async fn remember_me(
+    Extension(state): Extension<Arc<AppState>>,
+    session: Session,
+    request: Request,
+    next: Next,
+) -> Response {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await.ok().flatten();
+    if user_info.is_some() {
+        return next.run(request).await;
+    }
+    let Some((selector, validator)) = remember_me::token_from_headers(request.headers()) else {
+        return next.run(request).await;
+    };
+    let set_cookie =
+        match remember_me::restore_session(&state, &session, &selector, &validator).await {
+            Ok(set_cookie) => set_cookie,
+            Err(e) => return e.into_response(),
+        };
+    let mut response = next.run(request).await;
+    if let Some(Ok(value)) = set_cookie.as_deref().map(HeaderValue::from_str) {
+        response.headers_mut().append(header::SET_COOKIE, value);
+    }
+    response
+}
+
+/// Keep staff viewing the site as another user from changing anything as them.
+pub async fn impersonation(session: Session, request: Request, next: Next) -> Response {
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await.ok().flatten();
+    let Some(impersonated_by) = user_info.as_ref().and_then(|info| info.impersonated_by) else {
+        return next.run(request).await;
+    };
+    if matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) || request.uri().path() == "/admin/impersonate/stop"
+    {
+        return next.run(request).await;
+    }
+    info!(
+        "{impersonated_by} (viewing as {}) was stopped from {} {}",
+        user_info.map(|info| info.cid).unwrap_or_default(),
+        request.method(),
+        request.uri().path()
+    );
+    if let Err(e) = flashed_messages::push_flashed_message(
+        session,
+        MessageLevel::Error,
+        "Changes can't be made while viewing the site as another user",
+    )
+    .await
+    {
+        return e.into_response();
+    }
+    Redirect::to("/").into_response()
+}
+
+/// Show the maintenance page to non-staff users while the site is in maintenance mode.
+///
+/// Needs the app state as a request extension, since it runs before the
+/// router's state is available to extractors.
+pub async fn maintenance(
+    Extension(state): Extension<Arc<AppState>>,
+    session: Session,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path();
+    if MAINTENANCE_ALLOWED_PREFIXES
+        .iter()
+        .any(|prefix| path.starts_with(prefix))
+    {
+        return next.run(request).await;
+    }
+    match maintenance_mode(&state).await {
+        Ok(false) => return next.run(request).await,
+        Ok(true) => {}
+        Err(e) => return e.into_response(),
+    }
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await.ok().flatten();
+    if has_permission(&state, &user_info, permissions::STAFF).await {
+        return next.run(request).await;
+    }
+    match render_page(&state, "maintenance", &user_info) {
+        Ok(page) => (StatusCode::SERVICE_UNAVAILABLE, page).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Render one of the pages returned directly by middleware.
+fn render_page(
+    state: &AppState,
+    name: &str,
+    user_info: &Option<UserInfo>,
+) -> Result<Html<String>, AppError> {
+    let template = state.templates.get_template(name)?;
+    let rendered = template.render(context! { user_info })?;
+    Ok(Html(rendered))
+}
+
+/// Reject submissions from blocked IP addresses and users.
+///
+/// Only requests that submit something are checked, so blocked users
+/// can still read the site. Needs the app state as a request extension.
+pub async fn blocklist(
+    Extension(state): Extension<Arc<AppState>>,
+    session: Session,
+    request: Request,
+    next: Next,
+) -> Response {
+    if matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) {
+        return next.run(request).await;
+    }
+    let blocks = match active_blocks(&state).await {
+        Ok(blocks) => blocks,
+        Err(e) => return e.into_response(),
+    };
+    if blocks.is_empty() {
+        return next.run(request).await;
+    }
+    let ip = client_ip(&request);
+    let user_info: Option<UserInfo> = session.get(SESSION_USER_INFO_KEY).await.ok().flatten();
+    let cid = user_info.as_ref().map(|info| info.cid);
+    let Some(block) = matching_block(&blocks, ip, cid) else {
+        return next.run(request).await;
+    };
+    warn!(
+        "Block {} rejected {} {} from IP {} (CID {})",
+        block.id,
+        request.method(),
+        request.uri().path(),
+        ip.map(|ip| ip.to_string()).unwrap_or_default(),
+        cid.unwrap_or_default()
+    );
+    if let Err(e) = sqlx::query(sql::RECORD_BLOCK_HIT)
+        .bind(block.id)
+        .bind(Utc::now())
+        .execute(&state.db)
+        .await
+    {
+        error!("Could not record hit on block {}: {e}", block.id);
+    }
+    match render_page(&state, "blocked", &user_info) {
+        Ok(page) => (StatusCode::FORBIDDEN, page).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
@@ -0,0 +1,213 @@
+//! Render event position assignments as a PNG, for staff to post to Discord.
+//!
+//! Laid out like the bot's embed, in Discord's dark theme colors, with the
+//! Noto Sans fonts built in so the output doesn't depend on the server's fonts.
+
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
+use std::sync::LazyLock;
+
+static REGULAR: LazyLock<FontRef<'static>> =
+    LazyLock::new(|| FontRef::try_from_slice(notosans::REGULAR_TTF).expect("bundled font"));
+static BOLD: LazyLock<FontRef<'static>> =
+    LazyLock::new(|| FontRef::try_from_slice(notosans::BOLD_TTF).expect("bundled font"));
+
+const BACKGROUND: [u8; 3] = [0x2b, 0x2d, 0x31];
+/// Left edge of Discord embeds.
+const STRIPE: [u8; 3] = [0x1e, 0x1f, 0x22];
+const TEXT: [u8; 3] = [0xf2, 0xf3, 0xf5];
+const SECONDARY: [u8; 3] = [0xb5, 0xba, 0xc1];
+
+const PADDING: u32 = 24;
+const STRIPE_WIDTH: u32 = 4;
+const TITLE_SIZE: f32 = 28.0;
+const TEXT_SIZE: f32 = 20.0;
+const LINE_GAP: u32 = 10;
+/// Space between the position and assignee columns.
+const COLUMN_GAP: u32 = 24;
+const MIN_WIDTH: u32 = 480;
+
+/// RGB image being drawn on.
+struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32) -> Self {
+        let pixels = BACKGROUND
+            .iter()
+            .copied()
+            .cycle()
+            .take((width * height * 3) as usize)
+            .collect();
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Blend the color over the pixel by the coverage, from 0 to 1.
+    fn blend(&mut self, x: u32, y: u32, color: [u8; 3], coverage: f32) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let index = ((y * self.width + x) * 3) as usize;
+        let coverage = coverage.clamp(0.0, 1.0);
+        for (channel, value) in color.iter().enumerate() {
+            let existing = self.pixels[index + channel] as f32;
+            self.pixels[index + channel] =
+                (existing + (*value as f32 - existing) * coverage).round() as u8;
+        }
+    }
+
+    fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: [u8; 3]) {
+        for y in y..(y + height).min(self.height) {
+            for x in x..(x + width).min(self.width) {
+                self.blend(x, y, color, 1.0);
+            }
+        }
+    }
+
+    /// Draw the text with its top-left corner at the point.
+    fn draw_text(&mut self, font: &FontRef, size: f32, x: u32, y: u32, text: &str, color: [u8; 3]) {
+        let scaled = font.as_scaled(PxScale::from(size));
+        let baseline = y as f32 + scaled.ascent();
+        let mut caret = x as f32;
+        let mut previous = None;
+        for c in text.chars() {
+            let id = scaled.glyph_id(c);
+            if let Some(previous) = previous {
+                caret += scaled.kern(previous, id);
+            }
+            let glyph = id.with_scale_and_position(size, ab_glyph::point(caret, baseline));
+            caret += scaled.h_advance(id);
+            previous = Some(id);
+            if let Some(outline) = font.outline_glyph(glyph) {
+                let bounds = outline.px_bounds();
+                outline.draw(|gx, gy, coverage| {
+                    let px = bounds.min.x + gx as f32;
+                    let py = bounds.min.y + gy as f32;
+                    if px >= 0.0 && py >= 0.0 {
+                        self.blend(px as u32, py as u32, color, coverage);
+                    }
+                });
+            }
+        }
+    }
+
+    fn encode_png(&self) -> Result<Vec<u8>, png::EncodingError> {
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.pixels)?;
+        Ok(out)
+    }
+}
+
+/// Width of the text in pixels.
+fn text_width(font: &FontRef, size: f32, text: &str) -> u32 {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            width += scaled.kern(previous, id);
+        }
+        width += scaled.h_advance(id);
+        previous = Some(id);
+    }
+    width.ceil() as u32
+}
+
+fn line_height(font: &FontRef, size: f32) -> u32 {
+    font.as_scaled(PxScale::from(size)).height().ceil() as u32
+}
+
+/// Render the title, a line under it, and the rows of positions and who's working them.
+pub fn render_assignments(
+    title: &str,
+    subtitle: &str,
+    rows: &[(String, String)],
+) -> Result<Vec<u8>, png::EncodingError> {
+    let (regular, bold) = (&*REGULAR, &*BOLD);
+    let title_height = line_height(bold, TITLE_SIZE);
+    let text_height = line_height(regular, TEXT_SIZE);
+    let position_width = rows
+        .iter()
+        .map(|(position, _)| text_width(bold, TEXT_SIZE, position))
+        .max()
+        .unwrap_or_default();
+    let assignee_width = rows
+        .iter()
+        .map(|(_, assignee)| text_width(regular, TEXT_SIZE, assignee))
+        .max()
+        .unwrap_or_default();
+    let left = STRIPE_WIDTH + PADDING;
+    let content_width = [
+        text_width(bold, TITLE_SIZE, title),
+        text_width(regular, TEXT_SIZE, subtitle),
+        position_width + COLUMN_GAP + assignee_width,
+    ]
+    .into_iter()
+    .max()
+    .unwrap_or_default();
+    let width = (left + content_width + PADDING).max(MIN_WIDTH);
+    let height = PADDING * 2
+        + title_height
+        + LINE_GAP
+        + text_height
+        + (text_height + LINE_GAP) * rows.len() as u32
+        + LINE_GAP;
+
+    let mut canvas = Canvas::new(width, height);
+    canvas.fill_rect(0, 0, STRIPE_WIDTH, height, STRIPE);
+    let mut y = PADDING;
+    canvas.draw_text(bold, TITLE_SIZE, left, y, title, TEXT);
+    y += title_height + LINE_GAP;
+    canvas.draw_text(regular, TEXT_SIZE, left, y, subtitle, SECONDARY);
+    y += text_height + LINE_GAP * 2;
+    for (position, assignee) in rows {
+        canvas.draw_text(bold, TEXT_SIZE, left, y, position, TEXT);
+        canvas.draw_text(
+            regular,
+            TEXT_SIZE,
+            left + position_width + COLUMN_GAP,
+            y,
+            assignee,
+            TEXT,
+        );
+        y += text_height + LINE_GAP;
+    }
+    canvas.encode_png()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{render_assignments, BACKGROUND, MIN_WIDTH};
+
+    #[test]
+    fn test_render_assignments() {
+        let rows = vec![
+            ("DEN_APP".to_owned(), "Home Controller".to_owned()),
+            ("DEN_TWR".to_owned(), "Unassigned".to_owned()),
+        ];
+        let image = render_assignments("Fixture FNO", "Position assignments", &rows).unwrap();
+
+        let decoder = png::Decoder::new(image.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert!(info.width >= MIN_WIDTH);
+        assert!(info.height > 100);
+        // some text was drawn
+        assert!(pixels
+            .chunks(3)
+            .filter(|pixel| *pixel != BACKGROUND)
+            .nth(1_000)
+            .is_some());
+    }
+}
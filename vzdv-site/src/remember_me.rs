@@ -0,0 +1,252 @@
+//! "Keep me signed in" tokens, which restore a user's session after
+//! their browser has dropped the session cookie.
+//!
+//! The token cookie holds a selector, used to find the token, and a secret
+//! validator, of which only a hash is stored. The validator is replaced
+//! every time the token restores a session. The one it replaced is still
+//! accepted for a short while, since the browser can send several requests
+//! with it at once; if an old validator is presented after that, the token
+//! was copied, and all of the user's tokens are revoked.
+
+use crate::{
+    shared::{AppError, AppState, UserInfo, SESSION_USER_INFO_KEY},
+    tokens::{hash_secret, new_secret},
+};
+use axum::http::{header, HeaderMap};
+use chrono::{Duration, Utc};
+use log::{info, warn};
+use sqlx::SqlitePool;
+use tower_sessions::{
+    cookie::{time, Cookie, SameSite},
+    Session,
+};
+use vzdv::sql::{self, Controller, RememberToken};
+
+/// Name of the token cookie.
+pub const REMEMBER_ME_COOKIE: &str = "vzdv_remember";
+/// How long a token lasts since it was last used.
+const REMEMBER_ME_DAYS: i64 = 30;
+/// How long the replaced validator is still accepted after a rotation.
+const ROTATION_GRACE_SECONDS: i64 = 60;
+
+fn token_cookie(selector: &str, validator: &str) -> String {
+    Cookie::build((REMEMBER_ME_COOKIE, format!("{selector}:{validator}")))
+        .path("/")
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::days(REMEMBER_ME_DAYS))
+        .build()
+        .to_string()
+}
+
+/// `Set-Cookie` header value that removes the token cookie.
+pub fn removal_cookie() -> String {
+    Cookie::build((REMEMBER_ME_COOKIE, ""))
+        .path("/")
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::ZERO)
+        .build()
+        .to_string()
+}
+
+/// Get the selector and validator from the request's token cookie, if it has one.
+pub fn token_from_headers(headers: &HeaderMap) -> Option<(String, String)> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(Cookie::split_parse)
+        .filter_map(Result::ok)
+        .find(|cookie| cookie.name() == REMEMBER_ME_COOKIE)
+        .and_then(|cookie| {
+            let (selector, validator) = cookie.value().split_once(':')?;
+            Some((selector.to_owned(), validator.to_owned()))
+        })
+}
+
+/// Create a new token for the controller, returning the `Set-Cookie` header value for it.
+pub async fn issue(db: &SqlitePool, cid: u32) -> Result<String, AppError> {
+    let now = Utc::now();
+    sqlx::query(sql::DELETE_EXPIRED_REMEMBER_TOKENS)
+        .bind(now)
+        .execute(db)
+        .await?;
+    let selector = new_secret();
+    let validator = new_secret();
+    sqlx::query(sql::CREATE_REMEMBER_TOKEN)
+        .bind(&selector)
+        .bind(hash_secret(&validator))
+        .bind(cid)
+        .bind(now)
+        .bind(now + Duration::days(REMEMBER_ME_DAYS))
+        .execute(db)
+        .await?;
+    Ok(token_cookie(&selector, &validator))
+}
+
+/// Remove the token with the selector, like when the user logs out.
+pub async fn forget(db: &SqlitePool, selector: &str) -> Result<(), AppError> {
+    sqlx::query(sql::DELETE_REMEMBER_TOKEN)
+        .bind(selector)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// Log the user back in from their token, rotating its validator.
+///
+/// Returns the `Set-Cookie` header value to send back, if any: the rotated
+/// token if the session was restored, or the cookie's removal if the token
+/// wasn't valid. Nothing is sent back when the session was restored but
+/// another request rotated the token, as that request's response has the
+/// new cookie.
+pub async fn restore_session(
+    state: &AppState,
+    session: &Session,
+    selector: &str,
+    validator: &str,
+) -> Result<Option<String>, AppError> {
+    let token: Option<RememberToken> = sqlx::query_as(sql::GET_REMEMBER_TOKEN)
+        .bind(selector)
+        .fetch_optional(&state.db)
+        .await?;
+    let Some(token) = token else {
+        return Ok(Some(removal_cookie()));
+    };
+    let now = Utc::now();
+    if token.expires < now {
+        forget(&state.db, selector).await?;
+        return Ok(Some(removal_cookie()));
+    }
+    let validator_hash = hash_secret(validator);
+    let is_current = token.validator_hash == validator_hash;
+    let is_just_replaced = token.previous_validator_hash.as_deref()
+        == Some(validator_hash.as_str())
+        && token
+            .rotated_date
+            .is_some_and(|rotated| now - rotated <= Duration::seconds(ROTATION_GRACE_SECONDS));
+    if !is_current && !is_just_replaced {
+        warn!(
+            "Remember-me token for {} presented with an old validator; revoking all of their tokens",
+            token.cid
+        );
+        sqlx::query(sql::DELETE_REMEMBER_TOKENS_FOR)
+            .bind(token.cid)
+            .execute(&state.db)
+            .await?;
+        return Ok(Some(removal_cookie()));
+    }
+    let controller: Option<Controller> = sqlx::query_as(sql::GET_CONTROLLER_BY_CID)
+        .bind(token.cid)
+        .fetch_optional(&state.db)
+        .await?;
+    let Some(db_controller) = &controller else {
+        forget(&state.db, selector).await?;
+        return Ok(Some(removal_cookie()));
+    };
+    let user_info = UserInfo::new(
+        &state.db,
+        token.cid,
+        db_controller.first_name.clone(),
+        db_controller.last_name.clone(),
+    )
+    .await?;
+    session.insert(SESSION_USER_INFO_KEY, user_info).await?;
+    info!("Restored session for {} from a remembered login", token.cid);
+    if !is_current {
+        return Ok(None);
+    }
+
+    let validator = new_secret();
+    let rotated = sqlx::query(sql::ROTATE_REMEMBER_TOKEN)
+        .bind(selector)
+        .bind(hash_secret(&validator))
+        .bind(now + Duration::days(REMEMBER_ME_DAYS))
+        .bind(now)
+        .bind(&validator_hash)
+        .execute(&state.db)
+        .await?
+        .rows_affected();
+    if rotated == 0 {
+        // another request with the same cookie got here first
+        return Ok(None);
+    }
+    Ok(Some(token_cookie(selector, &validator)))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{issue, REMEMBER_ME_COOKIE};
+    use crate::test_utils::{test_app, HOME_CONTROLLER};
+    use axum::{
+        body::{to_bytes, Body},
+        http::{header, Request, StatusCode},
+    };
+    use chrono::{Duration, Utc};
+    use tower::ServiceExt;
+
+    /// Request a page with only the token cookie, returning the status, new token cookie, and body.
+    async fn get_with_token(
+        router: &axum::Router,
+        token_cookie: &str,
+    ) -> (StatusCode, Option<String>, String) {
+        let request = Request::get("/feedback")
+            .header(header::COOKIE, token_cookie)
+            .body(Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let new_token = response
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .find(|value| value.starts_with(REMEMBER_ME_COOKIE))
+            .map(|value| value.split(';').next().unwrap().to_owned());
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (
+            status,
+            new_token,
+            String::from_utf8_lossy(&body).to_string(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_remember_me() {
+        let app = test_app().await;
+        let set_cookie = issue(&app.db, HOME_CONTROLLER).await.unwrap();
+        let token = set_cookie.split(';').next().unwrap().to_owned();
+
+        let (status, rotated, body) = get_with_token(&app.router, &token).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Log out"));
+        let rotated = rotated.unwrap();
+        assert_ne!(rotated, token);
+
+        // the browser's other requests sent with the old validator still work for a bit
+        let (status, not_rotated, body) = get_with_token(&app.router, &token).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Log out"));
+        assert!(not_rotated.is_none());
+
+        // but presenting it after that revokes the token
+        sqlx::query("UPDATE remember_token SET rotated_date=$1")
+            .bind(Utc::now() - Duration::minutes(5))
+            .execute(&app.db)
+            .await
+            .unwrap();
+        let (_, removed, body) = get_with_token(&app.router, &token).await;
+        assert!(!body.contains("Log out"));
+        assert_eq!(removed.as_deref(), Some("vzdv_remember="));
+        let (_, _, body) = get_with_token(&app.router, &rotated).await;
+        assert!(!body.contains("Log out"));
+        let (count,): (u32,) = sqlx::query_as("SELECT COUNT(*) FROM remember_token")
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+}
@@ -1,25 +1,21 @@
 //! Structs and data to be shared across multiple parts of the site.
 
+use crate::{cache::TypedCache, templating};
 use axum::extract::rejection::FormRejection;
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{Html, IntoResponse, Redirect, Response},
 };
 use chrono::{NaiveDateTime, TimeZone};
 use log::{error, info};
-use mini_moka::sync::Cache;
 use minijinja::{context, Environment};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
-use std::sync::OnceLock;
-use std::{sync::Arc, time::Instant};
+use std::collections::VecDeque;
+use std::sync::{Arc, LazyLock, Mutex, OnceLock};
+use std::time::Duration;
 use tower_sessions_sqlx_store::sqlx::SqlitePool;
-use vzdv::GENERAL_HTTP_CLIENT;
 use vzdv::{
-    config::Config,
-    controller_can_see,
-    sql::{self, Controller},
-    PermissionsGroup,
+    config::Config, error_reporting, permissions, repo::Repos, request_id, sql, vatusa::VatusaError,
 };
 
 /// Discord webhook for reporting errors.
@@ -28,6 +24,42 @@ use vzdv::{
 /// otherwise have access to the loaded config struct.
 pub static ERROR_WEBHOOK: OnceLock<String> = OnceLock::new();
 
+/// How many recent errors to keep for users to report.
+const RECENT_ERRORS_MAX: usize = 100;
+
+/// An error shown to a user, kept so that they can report it.
+#[derive(Debug, Clone)]
+pub struct RecentError {
+    pub request_id: String,
+    pub status: u16,
+    pub message: String,
+}
+
+/// Errors recently shown on the error page, newest last.
+///
+/// Reports from the error page are matched to these by request ID, so that
+/// only the server's own description of the error is sent to staff.
+static RECENT_ERRORS: LazyLock<Mutex<VecDeque<RecentError>>> =
+    LazyLock::new(|| Mutex::new(VecDeque::with_capacity(RECENT_ERRORS_MAX)));
+
+fn record_recent_error(error: RecentError) {
+    let mut errors = RECENT_ERRORS.lock().unwrap();
+    if errors.len() >= RECENT_ERRORS_MAX {
+        errors.pop_front();
+    }
+    errors.push_back(error);
+}
+
+/// Remove and return the recent error with the request ID, so each error
+/// can only be reported once.
+pub fn take_recent_error(request_id: &str) -> Option<RecentError> {
+    let mut errors = RECENT_ERRORS.lock().unwrap();
+    let index = errors
+        .iter()
+        .position(|error| error.request_id == request_id)?;
+    errors.remove(index)
+}
+
 /// Error handling for all possible issues.
 #[derive(Debug, thiserror::Error)]
 pub enum AppError {
@@ -44,6 +76,8 @@ pub enum AppError {
     #[error(transparent)]
     VatsimApi(#[from] vatsim_utils::errors::VatsimUtilError),
     #[error(transparent)]
+    VatusaApi(#[from] VatusaError),
+    #[error(transparent)]
     ChronoParse(#[from] chrono::ParseError),
     #[error(transparent)]
     ChronoTimezone(#[from] chrono_tz::ParseError),
@@ -76,6 +110,12 @@ impl AppError {
             Self::HttpCall(_) => "Issue sending HTTP call",
             Self::HttpResponse(_, _) => "Issue processing HTTP response",
             Self::VatsimApi(_) => "Issue accessing VATSIM APIs",
+            Self::VatusaApi(e) => match e {
+                VatusaError::Unauthorized(_) => "Issue authenticating with VATUSA",
+                VatusaError::NotFound(_) => "Could not find that on VATUSA",
+                VatusaError::RateLimited(_) => "VATUSA is busy; please try again shortly",
+                _ => "Issue accessing VATUSA APIs",
+            },
             Self::ChronoParse(_) => "Issue processing time data",
             Self::ChronoTimezone(_) => "Issue processing timezone data",
             Self::ChronoOther(_) => "Issue processing time",
@@ -93,12 +133,12 @@ impl AppError {
 
 /// Try to construct the error page.
 fn try_build_error_page(error: AppError) -> Result<String, AppError> {
-    let mut env = Environment::new();
-    env.add_template("_layout", include_str!("../templates/_layout.jinja"))?;
+    let mut env = templating::environment()?;
     env.add_template("_error", include_str!("../templates/_error.jinja"))?;
     let template = env.get_template("_error")?;
     let rendered = template.render(context! {
         error => error.friendly_message(),
+        request_id => request_id::current(),
         no_links => true,
     })?;
     Ok(rendered)
@@ -117,20 +157,28 @@ impl IntoResponse for AppError {
             },
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         };
+        if status.is_server_error() {
+            error_reporting::capture_error("site", &error_msg);
+        }
 
         // report errors to Discord webhook
+        let request_id = request_id::current().unwrap_or_default();
+        if !request_id.is_empty() {
+            record_recent_error(RecentError {
+                request_id: request_id.clone(),
+                status: status.as_u16(),
+                message: error_msg.clone(),
+            });
+        }
         tokio::spawn(async move {
             if let Some(url) = ERROR_WEBHOOK.get() {
-                let res = GENERAL_HTTP_CLIENT
-                    .post(url)
-                    .json(&json!({
-                        "content": format!("Error occurred, returning status {status}: {error_msg}")
-                    }))
-                    .send()
-                    .await;
-                if let Err(e) = res {
-                    error!("Could not send error to Discord webhook: {e}");
-                }
+                error_reporting::post_to_webhook(
+                    url,
+                    &format!(
+                        "Error occurred, returning status {status}: {error_msg} (request {request_id})"
+                    ),
+                )
+                .await;
             }
         });
 
@@ -143,33 +191,18 @@ impl IntoResponse for AppError {
     }
 }
 
-/// Data wrapper for items in the server-side cache.
-#[derive(Clone)]
-pub struct CacheEntry {
-    pub inserted: Instant,
-    pub data: String,
-}
-
-impl CacheEntry {
-    /// Wrap the data with a timestamp.
-    pub fn new(data: String) -> Self {
-        Self {
-            inserted: Instant::now(),
-            data,
-        }
-    }
-}
-
 /// App's state, available in all handlers via an extractor.
 pub struct AppState {
     /// App config
     pub config: Config,
     /// Access to the DB
     pub db: SqlitePool,
+    /// Data access through the repository traits
+    pub repos: Repos,
     /// Loaded templates
     pub templates: Environment<'static>,
-    /// Server-side cache for heavier-compute rendered templates
-    pub cache: Cache<&'static str, CacheEntry>,
+    /// Server-side cache for data from slower sources
+    pub cache: TypedCache,
 }
 
 /// Key for user info CRUD in session.
@@ -177,6 +210,11 @@ pub const SESSION_USER_INFO_KEY: &str = "USER_INFO";
 /// Key for flashed messages CRUD in session.
 pub const SESSION_FLASHED_MESSAGES_KEY: &str = "FLASHED_MESSAGES";
 
+/// Name of the `setting` row holding the admin maintenance mode toggle.
+pub const MAINTENANCE_SETTING: &str = "maintenance_mode";
+/// Cache key for the admin maintenance mode toggle.
+pub const MAINTENANCE_CACHE_KEY: &str = "MAINTENANCE_MODE";
+
 /// Data stored in the user's session.
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct UserInfo {
@@ -187,60 +225,137 @@ pub struct UserInfo {
     pub is_some_staff: bool,
     pub is_training_staff: bool,
     pub is_event_staff: bool,
-    pub is_admin: bool,
+    /// Capabilities from `vzdv::permissions`, as of logging in, for showing links and buttons.
+    ///
+    /// Endpoints check the DB for the user's current permissions instead.
+    #[serde(default)]
+    pub permissions: Vec<String>,
+
+    /// CID of the ATM or WM viewing the site as this user, if any.
+    #[serde(default)]
+    pub impersonated_by: Option<u32>,
 }
 
-/// Returns a response to redirect to the homepage for non-staff users.
+impl UserInfo {
+    /// Build the session data for a controller, with their current permissions.
+    pub async fn new(
+        db: &SqlitePool,
+        cid: u32,
+        first_name: String,
+        last_name: String,
+    ) -> Result<Self, AppError> {
+        let held = permissions::permissions_for(db, cid).await?;
+        let mut held_list: Vec<String> = held.iter().cloned().collect();
+        held_list.sort();
+        Ok(Self {
+            cid,
+            first_name,
+            last_name,
+            is_some_staff: held.contains(permissions::STAFF),
+            is_training_staff: held.contains(permissions::TRAINING_MANAGE),
+            is_event_staff: held.contains(permissions::EVENTS_MANAGE),
+            permissions: held_list,
+            impersonated_by: None,
+        })
+    }
+}
+
+/// Returns a response to redirect to the homepage for users without the capability.
 ///
-/// This function checks the database to ensure that the staff member is
-/// still actually a staff member at the time of making the request.
+/// This function checks the database to ensure that the user still
+/// has the capability at the time of making the request.
 ///
-/// So long as the permissions being checked against aren't `PermissionsGroup::Anon`,
-/// it's safe to assume that `user_info` is `Some<UserInfo>`.
-pub async fn reject_if_not_in(
+/// If this returns `None`, it's safe to assume that `user_info` is `Some<UserInfo>`.
+pub async fn reject_without(
     state: &Arc<AppState>,
     user_info: &Option<UserInfo>,
-    permissions: PermissionsGroup,
+    permission: &str,
 ) -> Option<Redirect> {
-    if is_user_member_of(state, user_info, permissions).await {
+    if has_permission(state, user_info, permission).await {
         None
     } else {
         info!(
-            "Rejected access for {} to a resource",
+            "Rejected access for {} to a resource needing {permission}",
             user_info.as_ref().map(|ui| ui.cid).unwrap_or_default()
         );
         Some(Redirect::to("/"))
     }
 }
 
-/// Return whether the user is a member of the corresponding staff group.
+/// Return whether the user has the capability from `vzdv::permissions`.
 ///
-/// This function checks the database to ensure that the staff member is
-/// still actually a staff member at the time of making the request.
-///
-/// So long as the permissions being checked against aren't `PermissionsGroup::Anon`,
-/// it's safe to assume that `user_info` is `Some<UserInfo>`.
-pub async fn is_user_member_of(
+/// This function checks the database to ensure that the user still
+/// has the capability at the time of making the request.
+pub async fn has_permission(
     state: &Arc<AppState>,
     user_info: &Option<UserInfo>,
-    permissions: PermissionsGroup,
+    permission: &str,
 ) -> bool {
-    if user_info.is_none() {
+    let Some(user_info) = user_info else {
         return false;
-    }
-    let user_info = user_info.as_ref().unwrap();
-    let controller: Option<Controller> = match sqlx::query_as(sql::GET_CONTROLLER_BY_CID)
-        .bind(user_info.cid)
-        .fetch_optional(&state.db)
-        .await
-    {
-        Ok(c) => c,
+    };
+    match permissions::has_permission(&state.db, user_info.cid, permission).await {
+        Ok(has) => has,
         Err(e) => {
-            error!("Unknown controller with CID {}: {e}", user_info.cid);
-            return false;
+            error!("Could not check permissions for {}: {e}", user_info.cid);
+            false
         }
-    };
-    controller_can_see(&controller, permissions)
+    }
+}
+
+/// Return whether the user is allowed to view the site as other users.
+///
+/// Staff already viewing as someone else can't start again from there.
+pub async fn can_impersonate(state: &Arc<AppState>, user_info: &Option<UserInfo>) -> bool {
+    if user_info
+        .as_ref()
+        .is_some_and(|info| info.impersonated_by.is_some())
+    {
+        return false;
+    }
+    has_permission(state, user_info, permissions::IMPERSONATE).await
+}
+
+/// Return whether the site is in maintenance mode, either from the config
+/// or from the toggle on the admin page.
+pub async fn maintenance_mode(state: &AppState) -> Result<bool, AppError> {
+    if state.config.maintenance.enabled {
+        return Ok(true);
+    }
+    let enabled = state
+        .cache
+        .get_or_try_insert(MAINTENANCE_CACHE_KEY, Duration::from_secs(60), || async {
+            let value: Option<(String,)> = sqlx::query_as(sql::GET_SETTING)
+                .bind(MAINTENANCE_SETTING)
+                .fetch_optional(&state.db)
+                .await?;
+            Ok::<_, AppError>(value.is_some_and(|(value,)| value == "1"))
+        })
+        .await?;
+    Ok(*enabled)
+}
+
+/// Wrap a rendered snippet for HTMX polling, with cache headers for how long
+/// its content can be reused.
+///
+/// Snippets that differ by user are marked private so shared caches don't
+/// serve them to other users.
+pub fn snippet_response(html: String, max_age: Duration, per_user: bool) -> Response {
+    let visibility = if per_user { "private" } else { "public" };
+    let mut response = (
+        [(
+            header::CACHE_CONTROL,
+            format!("{visibility}, max-age={}", max_age.as_secs()),
+        )],
+        Html(html),
+    )
+        .into_response();
+    if per_user {
+        response
+            .headers_mut()
+            .insert(header::VARY, HeaderValue::from_static("Cookie"));
+    }
+    response
 }
 
 /// Convert an HTML `datetime-local` input and JS timezone name to a UTC timestamp.
@@ -0,0 +1,187 @@
+//! The template environment every page is rendered with.
+//!
+//! Filters, functions, and globals are registered here rather than by the
+//! endpoint modules, so templates can use them regardless of which module's
+//! router added the template.
+
+use crate::shared::AppError;
+use minijinja::{Environment, Value};
+use serde::Serialize;
+use thousands::Separable;
+use vzdv::ControllerRating;
+
+/// Shown in the navbar and page titles.
+pub const FACILITY_NAME: &str = "vZDV";
+
+/// A link in one of the navbar's dropdown menus.
+#[derive(Debug, Serialize)]
+pub struct NavLink {
+    pub label: &'static str,
+    pub link: &'static str,
+}
+
+/// A navbar dropdown menu, shown to everyone.
+#[derive(Debug, Serialize)]
+pub struct NavMenu {
+    pub label: &'static str,
+    pub links: &'static [NavLink],
+    /// Other sites, opened in a new tab below a divider.
+    pub external: &'static [NavLink],
+}
+
+/// The navbar's public dropdown menus, in order.
+pub const NAV_MENUS: &[NavMenu] = &[
+    NavMenu {
+        label: "Airspace",
+        links: &[
+            NavLink {
+                label: "Airports",
+                link: "/airspace/airports",
+            },
+            NavLink {
+                label: "Flights",
+                link: "/airspace/flights",
+            },
+            NavLink {
+                label: "Weather",
+                link: "/airspace/weather",
+            },
+            NavLink {
+                label: "Staffing Request",
+                link: "/airspace/staffing_request",
+            },
+        ],
+        external: &[
+            NavLink {
+                label: "SimBrief",
+                link: "https://www.simbrief.com",
+            },
+            NavLink {
+                label: "SkyVector",
+                link: "https://skyvector.com/",
+            },
+            NavLink {
+                label: "IFR Routing",
+                link: "https://www.flightaware.com/statistics/ifr-route/",
+            },
+            NavLink {
+                label: "VATSIM Radar",
+                link: "https://vatsim-radar.com/",
+            },
+        ],
+    },
+    NavMenu {
+        label: "Facility",
+        links: &[
+            NavLink {
+                label: "Staff",
+                link: "/facility/staff",
+            },
+            NavLink {
+                label: "Roster",
+                link: "/facility/roster",
+            },
+            NavLink {
+                label: "Positions",
+                link: "/facility/positions",
+            },
+            NavLink {
+                label: "Activity",
+                link: "/facility/activity",
+            },
+            NavLink {
+                label: "Stats",
+                link: "/facility/stats",
+            },
+            NavLink {
+                label: "Leaderboard",
+                link: "/facility/leaderboard",
+            },
+            NavLink {
+                label: "Resources",
+                link: "/facility/resources",
+            },
+            NavLink {
+                label: "Visitor Application",
+                link: "/facility/visitor_application",
+            },
+        ],
+        external: &[],
+    },
+];
+
+/// Minutes like "1h30m", or nothing for zero.
+fn minutes_to_hm(total_minutes: u32) -> String {
+    let hours = total_minutes / 60;
+    let minutes = total_minutes % 60;
+    if hours > 0 || minutes > 0 {
+        format!("{hours}h{minutes}m")
+    } else {
+        String::new()
+    }
+}
+
+/// Reformat an RFC 3339 timestamp, leaving anything else as-is.
+fn format_timestamp(date: &str, format: &str) -> String {
+    match chrono::DateTime::parse_from_rfc3339(date) {
+        Ok(date) => date.format(format).to_string(),
+        Err(_) => date.to_owned(),
+    }
+}
+
+fn rating_str(rating: i8) -> &'static str {
+    match ControllerRating::try_from(rating) {
+        Ok(r) => r.as_str(),
+        Err(_) => "OBS",
+    }
+}
+
+/// Create the environment with the layout, filters, functions, and globals.
+///
+/// Endpoint modules add their own templates to this in their `router` functions.
+pub fn environment() -> Result<Environment<'static>, AppError> {
+    let mut env = Environment::new();
+    env.add_template("_layout", include_str!("../templates/_layout.jinja"))?;
+
+    env.add_filter("format_number", |value: u16| value.separate_with_commas());
+    env.add_filter("minutes_to_hm", minutes_to_hm);
+    env.add_filter("simple_date", |date: String| {
+        format_timestamp(&date, "%m/%d/%Y")
+    });
+    env.add_filter("nice_date", |date: String| {
+        format_timestamp(&date, "%m/%d/%Y %H:%M:%S")
+    });
+    env.add_filter("rating_str", rating_str);
+    env.add_function("includes", |roles: Vec<String>, role: String| {
+        roles.contains(&role)
+    });
+
+    env.add_global("facility_name", FACILITY_NAME);
+    env.add_global("nav_menus", Value::from_serialize(NAV_MENUS));
+    Ok(env)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::environment;
+    use minijinja::context;
+
+    #[test]
+    fn test_environment() {
+        let mut env = environment().unwrap();
+        env.add_template(
+            "test",
+            "{{ 90|minutes_to_hm }} {{ 0|minutes_to_hm }}|{{ date|simple_date }}|{{ date|nice_date }}|{{ 'bad'|simple_date }}|{{ 5|rating_str }}|{{ 12345|format_number }}|{{ includes(['ATM'], 'ATM') }}|{{ facility_name }}|{{ nav_menus|map(attribute='label')|join(',') }}",
+        )
+        .unwrap();
+        let rendered = env
+            .get_template("test")
+            .unwrap()
+            .render(context! { date => "2026-10-17T18:30:05Z" })
+            .unwrap();
+        assert_eq!(
+            rendered,
+            "1h30m |10/17/2026|10/17/2026 18:30:05|bad|C1|12,345|true|vZDV|Airspace,Facility"
+        );
+    }
+}
@@ -0,0 +1,308 @@
+//! Helpers for driving the site's routes in tests.
+//!
+//! Each `TestApp` gets its own in-memory database with the full schema
+//! and a small set of fixture data.
+
+use crate::{
+    cache::TypedCache,
+    load_router,
+    shared::{AppState, UserInfo, SESSION_USER_INFO_KEY},
+    templating,
+};
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Request, StatusCode},
+    Extension, Router,
+};
+use chrono::{Duration, Utc};
+use sqlx::{sqlite::SqlitePoolOptions, Executor, SqlitePool};
+use std::{collections::HashMap, sync::Arc};
+use tower::ServiceExt;
+use tower_sessions::{
+    cookie::time::OffsetDateTime,
+    session::{Id, Record},
+    SessionManagerLayer, SessionStore,
+};
+use tower_sessions_sqlx_store::SqliteStore;
+use vzdv::{
+    config::{Airport, Config, ConfigApiKey, ConfigOAuthClient},
+    db::run_migrations,
+    permissions,
+    repo::Repos,
+    sql,
+};
+
+/// CID of the fixture home controller.
+pub const HOME_CONTROLLER: u32 = 1_000_001;
+/// CID of the fixture admin (ATM) controller.
+pub const ADMIN_CONTROLLER: u32 = 1_000_002;
+/// ID of the fixture published, upcoming event.
+pub const EVENT_ID: u32 = 1;
+/// Key in the test config's API keys.
+pub const TEST_API_KEY: &str = "test-api-key";
+/// VATUSA webhook secret set in the test app's config.
+pub const TEST_WEBHOOK_SECRET: &str = "test-webhook-secret";
+/// OAuth client in the test app's config.
+pub const TEST_OAUTH_CLIENT: (&str, &str, &str) = (
+    "test-tool",
+    "test-tool-secret",
+    "https://tool.example.com/callback",
+);
+
+pub struct TestApp {
+    pub router: Router,
+    pub state: Arc<AppState>,
+    pub db: SqlitePool,
+    sessions: SqliteStore,
+}
+
+/// Build the app around a fresh in-memory database with fixtures loaded.
+pub async fn test_app() -> TestApp {
+    test_app_with(|_| {}).await
+}
+
+/// Build the test app, letting the test adjust its config first.
+pub async fn test_app_with(configure: impl FnOnce(&mut Config)) -> TestApp {
+    // a single connection, since each in-memory connection is its own DB
+    let db = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    db.execute(sql::CREATE_TABLES).await.unwrap();
+    run_migrations(&db).await.unwrap();
+    seed_fixtures(&db).await;
+
+    let sessions = SqliteStore::new(db.clone());
+    sessions.migrate().await.unwrap();
+    let mut templates = templating::environment().unwrap();
+    let router = load_router(SessionManagerLayer::new(sessions.clone()), &mut templates);
+    let mut config = Config::default();
+    config.training.certifications = vec!["GND".to_owned(), "TWR".to_owned()];
+    config.training.positions = HashMap::from([
+        ("GND".to_owned(), vec!["DEN_GND".to_owned()]),
+        ("TWR".to_owned(), vec!["DEN_TWR".to_owned()]),
+    ]);
+    config.training.waitlist.capacity = HashMap::from([("GND".to_owned(), 1)]);
+    config.api.keys = vec![ConfigApiKey {
+        name: "test".to_owned(),
+        key: TEST_API_KEY.to_owned(),
+    }];
+    config.vatsim.vatusa_webhook_secret = TEST_WEBHOOK_SECRET.to_owned();
+    config.airports.all = vec![Airport {
+        code: "KDEN".to_owned(),
+        name: "Denver Intl".to_owned(),
+        location: "Denver, CO".to_owned(),
+        towered: true,
+        class: "B".to_owned(),
+        latitude: Some(39.8617),
+        longitude: Some(-104.6731),
+    }];
+    config.oauth_provider.clients = vec![ConfigOAuthClient {
+        name: "Test tool".to_owned(),
+        client_id: TEST_OAUTH_CLIENT.0.to_owned(),
+        client_secret: TEST_OAUTH_CLIENT.1.to_owned(),
+        redirect_uris: vec![TEST_OAUTH_CLIENT.2.to_owned()],
+    }];
+    configure(&mut config);
+    let state = Arc::new(AppState {
+        config,
+        db: db.clone(),
+        repos: Repos::sqlite(&db),
+        templates,
+        cache: TypedCache::new(10),
+    });
+    TestApp {
+        router: router
+            .with_state(state.clone())
+            .layer(Extension(state.clone())),
+        state,
+        db,
+        sessions,
+    }
+}
+
+/// Insert the fixture controllers, event, and event positions.
+async fn seed_fixtures(db: &SqlitePool) {
+    for (cid, first, last, roles) in [
+        (HOME_CONTROLLER, "Home", "Controller", ""),
+        (ADMIN_CONTROLLER, "Admin", "Controller", "ATM"),
+    ] {
+        sqlx::query(
+            "INSERT INTO controller (cid, first_name, last_name, rating, status, home_facility, is_on_roster, roles) VALUES ($1, $2, $3, 5, 'Active', 'ZDV', TRUE, $4)",
+        )
+        .bind(cid)
+        .bind(first)
+        .bind(last)
+        .bind(roles)
+        .execute(db)
+        .await
+        .unwrap();
+    }
+    sqlx::query(sql::CREATE_EVENT)
+        .bind(ADMIN_CONTROLLER)
+        .bind("Fixture FNO")
+        .bind(Utc::now() + Duration::days(7))
+        .bind(Utc::now() + Duration::days(7) + Duration::hours(3))
+        .bind("An event")
+        .bind(None::<String>)
+        .bind(None::<String>)
+        .bind(None::<String>)
+        .execute(db)
+        .await
+        .unwrap();
+    db.execute("UPDATE event SET published=TRUE").await.unwrap();
+    for (name, category) in [("DEN_APP", "TRACON"), ("DEN_TWR", "Cab")] {
+        sqlx::query(sql::INSERT_EVENT_POSITION)
+            .bind(EVENT_ID)
+            .bind(name)
+            .bind(category)
+            .execute(db)
+            .await
+            .unwrap();
+    }
+}
+
+impl TestApp {
+    /// Store a session logged in as the controller, returning the cookie header value.
+    pub async fn login_as(&self, cid: u32, is_admin: bool) -> String {
+        let user_info = UserInfo {
+            cid,
+            first_name: "Test".to_owned(),
+            last_name: "User".to_owned(),
+            is_some_staff: is_admin,
+            is_training_staff: is_admin,
+            is_event_staff: is_admin,
+            permissions: if is_admin {
+                permissions::ALL
+                    .iter()
+                    .map(|(p, _)| (*p).to_owned())
+                    .collect()
+            } else {
+                Vec::new()
+            },
+            impersonated_by: None,
+        };
+        let mut record = Record {
+            id: Id::default(),
+            data: HashMap::from([(
+                SESSION_USER_INFO_KEY.to_owned(),
+                serde_json::to_value(user_info).unwrap(),
+            )]),
+            expiry_date: OffsetDateTime::now_utc()
+                + tower_sessions::cookie::time::Duration::hours(1),
+        };
+        self.sessions.create(&mut record).await.unwrap();
+        format!("id={}", record.id)
+    }
+
+    /// Send a GET request, returning the status and body.
+    pub async fn get(&self, uri: &str, cookie: Option<&str>) -> (StatusCode, String) {
+        let mut req = Request::get(uri);
+        if let Some(cookie) = cookie {
+            req = req.header(header::COOKIE, cookie);
+        }
+        self.send(req.body(Body::empty()).unwrap()).await
+    }
+
+    /// Send a GET request with extra headers, returning the status and body.
+    pub async fn get_with_headers(
+        &self,
+        uri: &str,
+        headers: &[(&str, &str)],
+    ) -> (StatusCode, String) {
+        let mut req = Request::get(uri);
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        self.send(req.body(Body::empty()).unwrap()).await
+    }
+
+    /// Send a POST request with a URL-encoded form body, returning the status and body.
+    pub async fn post_form(
+        &self,
+        uri: &str,
+        form: &[(&str, &str)],
+        cookie: Option<&str>,
+    ) -> (StatusCode, String) {
+        let body = form
+            .iter()
+            .map(|(k, v)| format!("{k}={}", urlencode(v)))
+            .collect::<Vec<_>>()
+            .join("&");
+        let mut req =
+            Request::post(uri).header(header::CONTENT_TYPE, "application/x-www-form-urlencoded");
+        if let Some(cookie) = cookie {
+            req = req.header(header::COOKIE, cookie);
+        }
+        self.send(req.body(Body::from(body)).unwrap()).await
+    }
+
+    /// Send a POST request with a URL-encoded form body, returning the status and `Location` header.
+    pub async fn post_form_redirect(
+        &self,
+        uri: &str,
+        form: &[(&str, &str)],
+        cookie: Option<&str>,
+    ) -> (StatusCode, String) {
+        let body = form
+            .iter()
+            .map(|(k, v)| format!("{k}={}", urlencode(v)))
+            .collect::<Vec<_>>()
+            .join("&");
+        let mut req =
+            Request::post(uri).header(header::CONTENT_TYPE, "application/x-www-form-urlencoded");
+        if let Some(cookie) = cookie {
+            req = req.header(header::COOKIE, cookie);
+        }
+        let resp = self
+            .router
+            .clone()
+            .oneshot(req.body(Body::from(body)).unwrap())
+            .await
+            .unwrap();
+        let location = resp
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_owned();
+        (resp.status(), location)
+    }
+
+    /// Send a POST request with a raw body and extra headers, returning the status and body.
+    pub async fn post_with_headers(
+        &self,
+        uri: &str,
+        body: &str,
+        headers: &[(&str, &str)],
+    ) -> (StatusCode, String) {
+        let mut req = Request::post(uri);
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        self.send(req.body(Body::from(body.to_owned())).unwrap())
+            .await
+    }
+
+    async fn send(&self, req: Request<Body>) -> (StatusCode, String) {
+        let resp = self.router.clone().oneshot(req).await.unwrap();
+        let status = resp.status();
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8_lossy(&body).to_string())
+    }
+}
+
+/// Minimal form value encoding; enough for test data.
+fn urlencode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b' ' => "+".to_owned(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
@@ -0,0 +1,31 @@
+//! Random secrets handed to clients, of which only a hash is stored.
+
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+/// A new random secret, like a token or code.
+pub fn new_secret() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+/// Hash of the secret for storing and looking it up.
+///
+/// The secrets are random, so a fast unsalted hash is enough.
+pub fn hash_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{hash_secret, new_secret};
+
+    #[test]
+    fn test_secrets() {
+        let secret = new_secret();
+        assert_eq!(secret.len(), 32);
+        assert_ne!(secret, new_secret());
+        assert_eq!(hash_secret(&secret), hash_secret(&secret));
+        assert_ne!(hash_secret(&secret), secret);
+        assert_eq!(hash_secret(&secret).len(), 64);
+    }
+}
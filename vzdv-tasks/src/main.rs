@@ -4,25 +4,57 @@
 #![deny(unsafe_code)]
 
 use anyhow::{Context, Result};
-use chrono::{DateTime, Months};
-use clap::Parser;
-use log::{debug, error, info};
-use sqlx::{sqlite::SqliteRow, Row, SqlitePool};
+use chrono::{DateTime, Months, Utc};
+use clap::{Parser, Subcommand};
+use log::{debug, error, info, warn};
+use sqlx::SqlitePool;
 use std::{
     collections::{HashMap, HashSet},
-    path::PathBuf,
+    path::{Path, PathBuf},
     time::Duration,
 };
 use tokio::time;
-use vatsim_utils::rest_api;
+use vatsim_utils::{live_api::Vatsim, rest_api};
 use vzdv::{
+    activity::{
+        clear_synced_sessions, compile_purge_candidates, credit_training_sessions,
+        record_datafeed_sessions, start_of_month, HistorySession, Quarter,
+    },
+    cleanup::remove_stale_data,
     config::Config,
-    general_setup, generate_operating_initials_for, position_in_facility_airspace,
-    retrieve_all_in_use_ois,
-    sql::{self, Controller},
-    vatusa::{get_roster, MembershipType, RosterMember},
+    error_reporting, general_setup, generate_operating_initials_for, integrity,
+    position_in_facility_airspace, retrieve_all_in_use_ois,
+    sql::RosterRefresh,
+    stats,
+    vatusa::{
+        get_controller_info, get_roster, MembershipType, RosterMember, RosterStatus, VatusaError,
+    },
+    GENERAL_HTTP_CLIENT, OPERATING_INITIALS_CHOICE_DAYS,
 };
 
+mod seed;
+
+/// File name prefix for database backups, used to find old ones to remove.
+const BACKUP_FILE_PREFIX: &str = "vzdv_backup_";
+
+/// Controllers who joined longer ago than this aren't sent welcome messages.
+const WELCOME_MAX_AGE_DAYS: i64 = 30;
+
+/// How many months of controlling activity to keep, for the controller page's chart.
+const ACTIVITY_HISTORY_MONTHS: u32 = 12;
+
+/// How often to check for controllers queued for a roster refresh by VATUSA webhooks.
+const ROSTER_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often to record controllers in the VATSIM datafeed.
+const SESSION_TRACKING_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Hour (UTC) to rebuild the stats rollups at; the middle of the night in Denver.
+const STATS_ROLLUP_HOUR: u32 = 9;
+
+/// Setting storing the last quarter that purge candidates were compiled for.
+const PURGE_QUARTER_SETTING: &str = "purge_candidates_quarter";
+
 /// vZDV task runner.
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -33,9 +65,28 @@ struct Cli {
     #[arg(long)]
     config: Option<PathBuf>,
 
+    /// Merge the config overlay for this environment, like "dev" for "vzdv.dev.toml".
+    ///
+    /// [default: the VZDV_ENV environment variable, if set]
+    #[arg(long)]
+    env: Option<String>,
+
     /// Enable debug logging
     #[arg(short, long)]
     debug: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Fill the database with fake data for local development, then exit.
+    Seed {
+        /// Delete any existing data and seed anyway
+        #[arg(long)]
+        force: bool,
+    },
 }
 
 /// Update a single controller's stored data.
@@ -58,21 +109,25 @@ async fn update_controller_record(db: &SqlitePool, controller: &RosterMember) ->
         .filter(|role| role != "INS")
         .collect();
 
-    let controller_record: Option<Controller> = sqlx::query_as(sql::GET_CONTROLLER_BY_CID)
-        .bind(controller.cid)
-        .fetch_optional(db)
-        .await?;
+    let existing_roles: Option<Option<String>> =
+        sqlx::query_scalar!("SELECT roles FROM controller WHERE cid=$1", controller.cid)
+            .fetch_optional(db)
+            .await?;
 
     // merge any new roles with any existing roles
     let roles = if roles.is_empty() {
         roles
     } else {
-        match &controller_record {
-            Some(cr) => {
+        match &existing_roles {
+            Some(existing) => {
                 let mut all_roles = HashSet::new();
-                cr.roles.split(',').for_each(|r| {
-                    all_roles.insert(r);
-                });
+                existing
+                    .as_deref()
+                    .unwrap_or_default()
+                    .split(',')
+                    .for_each(|r| {
+                        all_roles.insert(r);
+                    });
                 roles.iter().for_each(|r| {
                     all_roles.insert(r);
                 });
@@ -82,36 +137,92 @@ async fn update_controller_record(db: &SqlitePool, controller: &RosterMember) ->
         }
     };
 
+    let was_on_roster: Option<bool> = sqlx::query_scalar!(
+        r#"SELECT is_on_roster AS "is_on_roster!: bool" FROM controller WHERE cid=$1"#,
+        controller.cid
+    )
+    .fetch_optional(db)
+    .await?;
+
     let facility_join = DateTime::parse_from_rfc3339(&controller.facility_join)?;
-    // update main record
-    sqlx::query(sql::UPSERT_USER_TASK)
-        .bind(controller.cid)
-        .bind(&controller.first_name)
-        .bind(&controller.last_name)
-        .bind(&controller.email)
-        .bind(controller.rating)
-        .bind(&controller.facility)
-        // controller will be on the roster since that's what the VATSIM API is showing
-        .bind(true)
-        .bind(facility_join)
-        .bind(roles.join(","))
-        .execute(db)
-        .await?;
-    // for controllers new to the ARTCC, also set their default OIs
-    if controller_record.is_none() {
+    let roles = roles.join(",");
+    // update main record; controller will be on the roster since that's what the VATSIM API is showing
+    sqlx::query!(
+        "
+INSERT INTO controller
+    (id, cid, first_name, last_name, email, rating, home_facility, is_on_roster, join_date, roles)
+VALUES
+    (NULL, $1, $2, $3, $4, $5, $6, TRUE, $7, $8)
+ON CONFLICT(cid) DO UPDATE SET
+    first_name=excluded.first_name,
+    last_name=excluded.last_name,
+    email=excluded.email,
+    rating=excluded.rating,
+    home_facility=excluded.home_facility,
+    is_on_roster=excluded.is_on_roster,
+    join_date=excluded.join_date,
+    roles=excluded.roles
+WHERE
+    cid=excluded.cid
+",
+        controller.cid,
+        controller.first_name,
+        controller.last_name,
+        controller.email,
+        controller.rating,
+        controller.facility,
+        facility_join,
+        roles
+    )
+    .execute(db)
+    .await?;
+    // new controllers get a few days to choose their own OIs on the site before being assigned some
+    let stored_ois: Option<String> = sqlx::query_scalar!(
+        "SELECT operating_initials FROM controller WHERE cid=$1",
+        controller.cid
+    )
+    .fetch_one(db)
+    .await?;
+    let choice_ended = Utc::now() - facility_join.to_utc()
+        >= chrono::Duration::days(OPERATING_INITIALS_CHOICE_DAYS);
+    if stored_ois.is_none() && choice_ended {
         let in_use = retrieve_all_in_use_ois(db).await?;
         let new_ois = generate_operating_initials_for(
             &in_use,
             &controller.first_name,
             &controller.last_name,
         )?;
-        sqlx::query(sql::UPDATE_CONTROLLER_OIS)
-            .bind(controller.cid)
-            .bind(&new_ois)
-            .execute(db)
-            .await?;
+        sqlx::query!(
+            "UPDATE controller SET operating_initials=$2 WHERE cid=$1",
+            controller.cid,
+            new_ois
+        )
+        .execute(db)
+        .await?;
+        info!(
+            "{} {} ({}) didn't choose OIs; assigned {new_ois}",
+            &controller.first_name, &controller.last_name, controller.cid
+        );
+    }
+    // welcome controllers who recently joined; the site and bot send the messages
+    let recently_joined =
+        Utc::now() - facility_join.to_utc() < chrono::Duration::days(WELCOME_MAX_AGE_DAYS);
+    if was_on_roster != Some(true) && recently_joined {
+        let now = Utc::now();
+        let queued = sqlx::query!(
+            "INSERT OR IGNORE INTO welcome_message VALUES ($1, $2, NULL, NULL)",
+            controller.cid,
+            now
+        )
+        .execute(db)
+        .await?;
+        if queued.rows_affected() > 0 {
+            info!("Queued welcome messages for {}", controller.cid);
+        }
+    }
+    if existing_roles.is_none() {
         info!(
-            "{} {} ({}) added to DB with OIs {new_ois}",
+            "{} {} ({}) added to DB",
             &controller.first_name, &controller.last_name, controller.cid
         );
     } else {
@@ -123,13 +234,105 @@ async fn update_controller_record(db: &SqlitePool, controller: &RosterMember) ->
     Ok(())
 }
 
+/// Update the controller's record to show they're not on the roster.
+///
+/// Controllers that were on it are sent an exit survey.
+async fn mark_off_roster(db: &SqlitePool, cid: u32, was_on_roster: bool) {
+    if was_on_roster {
+        info!("Controller {cid} left the roster; queueing exit survey");
+        let now = Utc::now();
+        if let Err(e) = sqlx::query!(
+            "INSERT INTO exit_survey (id, cid, departure, created_date) VALUES (NULL, $1, $2, $3)",
+            cid,
+            "Left the VATUSA roster",
+            now
+        )
+        .execute(db)
+        .await
+        {
+            error!("Error queueing exit survey for {cid}: {e}");
+        }
+    }
+    if let Err(e) = sqlx::query!(
+        "UPDATE controller SET is_on_roster=0, home_facility='', join_date=NULL, operating_initials=NULL WHERE cid=$1",
+        cid
+    )
+    .execute(db)
+    .await
+    {
+        error!("Error updating controller {cid} to show off-roster: {e}")
+    }
+}
+
+/// Re-sync controllers that VATUSA webhooks reported changes for.
+async fn refresh_queued_controllers(config: &Config, db: &SqlitePool) -> Result<()> {
+    let queued: Vec<RosterRefresh> = sqlx::query_as!(
+        RosterRefresh,
+        r#"SELECT cid AS "cid: u32", reason, queued_date AS "queued_date: DateTime<Utc>" FROM roster_refresh ORDER BY queued_date"#
+    )
+    .fetch_all(db)
+    .await?;
+    for refresh in queued {
+        debug!(
+            "Refreshing {} after VATUSA \"{}\"",
+            refresh.cid, refresh.reason
+        );
+        let member =
+            match get_controller_info(refresh.cid, Some(&config.vatsim.vatusa_api_key)).await {
+                Ok(info) => Some(info),
+                Err(VatusaError::NotFound(_)) => None,
+                Err(e) => {
+                    // left queued to try again next time
+                    warn!("Could not get VATUSA info for {}: {e}", refresh.cid);
+                    continue;
+                }
+            };
+        match member {
+            Some(member) if RosterStatus::of(&member) != RosterStatus::NotOnRoster => {
+                // like the full refresh, one bad record doesn't hold up the rest of the queue
+                if let Err(e) = update_controller_record(db, &member).await {
+                    error!("Error updating controller {} in DB: {e}", refresh.cid);
+                }
+            }
+            _ => {
+                let was_on_roster: Option<bool> = sqlx::query_scalar!(
+                    r#"SELECT is_on_roster AS "is_on_roster!: bool" FROM controller WHERE cid=$1"#,
+                    refresh.cid
+                )
+                .fetch_optional(db)
+                .await?;
+                if was_on_roster.is_some() {
+                    mark_off_roster(db, refresh.cid, was_on_roster == Some(true)).await;
+                }
+            }
+        }
+        // a newer webhook for the same controller replaces the queue date, keeping it queued
+        sqlx::query!(
+            "DELETE FROM roster_refresh WHERE cid=$1 AND queued_date=$2",
+            refresh.cid,
+            refresh.queued_date
+        )
+        .execute(db)
+        .await?;
+    }
+    Ok(())
+}
+
 /// Update the stored roster with fresh data from VATUSA.
 async fn update_roster(db: &SqlitePool) -> Result<()> {
     /*
      * Don't use a transaction here; instead, attempt to update every controller's
      * data. Don't error-out unless VATSIM doesn't give any data.
      */
-    let roster_data = get_roster("ZDV", MembershipType::Both).await?;
+    let roster_data = match get_roster("ZDV", MembershipType::Both).await {
+        Ok(data) => data,
+        Err(e) if e.is_retryable() => {
+            warn!("Retrying roster fetch in 1 minute after error: {e}");
+            time::sleep(Duration::from_secs(60)).await;
+            get_roster("ZDV", MembershipType::Both).await?
+        }
+        Err(e) => return Err(e.into()),
+    };
     debug!("Got roster response");
     for controller in &roster_data {
         if let Err(e) = update_controller_record(db, controller).await {
@@ -142,23 +345,34 @@ async fn update_roster(db: &SqlitePool) -> Result<()> {
         .iter()
         .map(|controller| controller.cid)
         .collect();
-    let db_controllers: Vec<SqliteRow> = sqlx::query(sql::GET_ALL_CONTROLLER_CIDS)
-        .fetch_all(db)
-        .await?;
-    for row in db_controllers {
-        let cid: u32 = row.try_get("cid")?;
+    let db_controllers: Vec<u32> =
+        sqlx::query_scalar!(r#"SELECT cid AS "cid: u32" FROM controller"#)
+            .fetch_all(db)
+            .await?;
+    let was_on_roster: Vec<u32> =
+        sqlx::query_scalar!(r#"SELECT cid AS "cid: u32" FROM controller WHERE is_on_roster=TRUE"#)
+            .fetch_all(db)
+            .await?;
+    for cid in db_controllers {
         if !current_controllers.contains(&cid) {
             debug!("Controller {cid} is not on the roster");
-            if let Err(e) = sqlx::query(sql::UPDATE_REMOVED_FROM_ROSTER)
-                .bind(cid)
-                .execute(db)
-                .await
-            {
-                error!("Error updating controller {cid} to show off-roster: {e}")
-            }
+            mark_off_roster(db, cid, was_on_roster.contains(&cid)).await;
         }
     }
 
+    // LOAs can start and end between staff changes, so refresh who's on one
+    debug!("Updating LOA status");
+    let now = Utc::now();
+    sqlx::query!(
+        "UPDATE controller SET loa_until=(
+            SELECT MAX(end_date) FROM loa
+            WHERE loa.cid=controller.cid AND start_date <= $1 AND end_date > $1
+        )",
+        now
+    )
+    .execute(db)
+    .await?;
+
     Ok(())
 }
 
@@ -168,22 +382,35 @@ async fn update_roster(db: &SqlitePool) -> Result<()> {
 async fn update_single_activity(
     config: &Config,
     db: &SqlitePool,
-    five_months_ago: &str,
+    since: &str,
     cid: u32,
 ) -> Result<()> {
     /*
-     * Get the last 5 months of the controller's activity.
+     * Get the last year of the controller's activity.
      *
-     * I'm not (currently) worried about pagination as even the facility's most
-     * active controllers don't have enough sessions in this time range to go over
-     * the endpoint's single-page response limit.
+     * A year is enough for the most active controllers to go over the
+     * endpoint's single-page response limit, so follow the pages.
      */
-    let sessions = rest_api::get_atc_sessions(cid as u64, None, None, Some(five_months_ago), None)
-        .await
-        .with_context(|| format!("Processing CID {cid}"))?;
+    let fetched_at = Utc::now();
+    let mut sessions = Vec::new();
+    let mut page = 1;
+    loop {
+        let response = rest_api::get_atc_sessions(cid as u64, Some(page), None, Some(since), None)
+            .await
+            .with_context(|| format!("Processing CID {cid}"))?;
+        sessions.extend(response.results);
+        if response.next.is_none() {
+            break;
+        }
+        page += 1;
+    }
+    let history: Vec<HistorySession> = sessions
+        .iter()
+        .filter_map(HistorySession::from_entry)
+        .collect();
     // group the controller's activity by month
     let mut seconds_map: HashMap<String, f32> = HashMap::new();
-    for session in sessions.results {
+    for session in sessions {
         // filter to only sessions in the facility
         if !position_in_facility_airspace(config, &session.callsign) {
             continue;
@@ -199,22 +426,30 @@ async fn update_single_activity(
 
     // transaction for the ~6 queries
     let mut tx = db.begin().await?;
-    // clear the controller's existing records in prep for replacement
-    sqlx::query(sql::DELETE_ACTIVITY_FOR_CID)
-        .bind(cid)
-        .execute(&mut *tx)
+    // clear the controller's existing records in prep for replacement, keeping credited time
+    sqlx::query!(
+        "DELETE FROM activity WHERE cid=$1 AND source='controlling'",
+        cid
+    )
+    .execute(&mut *tx)
+    .await
+    .with_context(|| format!("Processing CID {cid}"))?;
+    // datafeed sessions that are in VATSIM's data now
+    clear_synced_sessions(&mut tx, cid, &history, fetched_at)
         .await
         .with_context(|| format!("Processing CID {cid}"))?;
     // for each relevant month, store their total controlled minutes in the DB
     for (month, seconds) in seconds_map {
         let minutes = (seconds / 60.0).round() as u32;
-        sqlx::query(sql::INSERT_INTO_ACTIVITY)
-            .bind(cid)
-            .bind(month)
-            .bind(minutes)
-            .execute(&mut *tx)
-            .await
-            .with_context(|| format!("Processing CID {cid}"))?;
+        sqlx::query!(
+            "INSERT INTO activity (id, cid, month, minutes) VALUES (NULL, $1, $2, $3)",
+            cid,
+            month,
+            minutes
+        )
+        .execute(&mut *tx)
+        .await
+        .with_context(|| format!("Processing CID {cid}"))?;
     }
     // commit the controller's changes
     tx.commit().await?;
@@ -224,28 +459,202 @@ async fn update_single_activity(
 
 /// Update all controllers' stored activity data with data from VATSIM.
 ///
-/// For each controller in the DB, their activity data will be cleared,
-/// and then (for on-roster controllers) fetched and stored in the DB as
-/// part of a transaction.
+/// For each controller in the DB, their controlling activity data will be
+/// cleared (credited event and training time is kept), and then (for
+/// on-roster controllers) fetched and stored in the DB as part of a transaction.
+/// Instructors are then credited with their finished training sessions.
 async fn update_activity(config: &Config, db: &SqlitePool) -> Result<()> {
-    // prep cids for on-roster controllers and a 5-month-ago timestamp that the API recognizes
-    let controllers = sqlx::query(sql::GET_ALL_ROSTER_CONTROLLER_CIDS)
-        .fetch_all(db)
-        .await?;
-    let five_months_ago = chrono::Utc::now()
-        .checked_sub_months(Months::new(5))
+    // prep cids for on-roster controllers and a start timestamp that the API recognizes
+    let controllers: Vec<u32> =
+        sqlx::query_scalar!(r#"SELECT cid AS "cid: u32" FROM controller WHERE is_on_roster=TRUE"#)
+            .fetch_all(db)
+            .await?;
+    let since = chrono::Utc::now()
+        .checked_sub_months(Months::new(ACTIVITY_HISTORY_MONTHS))
         .unwrap()
         .format("%Y-%m-%d")
         .to_string();
-    for row in controllers {
-        let cid: u32 = row.try_get("cid")?;
+    for cid in controllers {
         debug!("Getting activity for {cid}");
-        if let Err(e) = update_single_activity(config, db, &five_months_ago, cid).await {
+        if let Err(e) = update_single_activity(config, db, &since, cid).await {
             error!("Error updating activity for {cid}: {e}");
         }
         // wait a second to be nice to the VATSIM API
         time::sleep(Duration::from_secs(1)).await;
     }
+    // sessions from past months no longer count, including those of off-roster controllers
+    let month_start = start_of_month(Utc::now());
+    sqlx::query!(
+        "DELETE FROM controller_session WHERE last_seen < $1",
+        month_start
+    )
+    .execute(db)
+    .await?;
+    let credited = credit_training_sessions(db, Utc::now()).await?;
+    debug!("Credited {credited} training sessions");
+    Ok(())
+}
+
+/// Record the facility's controllers in the VATSIM datafeed, so that activity
+/// between syncs with VATSIM's session history is available.
+async fn track_controller_sessions(config: &Config, db: &SqlitePool) -> Result<()> {
+    let data = Vatsim::new().await?.get_v3_data().await?;
+    let recorded = record_datafeed_sessions(db, config, &data, Utc::now()).await?;
+    debug!("Recorded {recorded} controller sessions");
+    Ok(())
+}
+
+/// Compile the last quarter's purge candidates, once per quarter.
+///
+/// Waits a day into the new quarter so that the activity sync has picked up
+/// the end of the last one. Staff review the candidates on the site.
+async fn update_purge_candidates(db: &SqlitePool) -> Result<()> {
+    let now = Utc::now();
+    let quarter = Quarter::previous(now);
+    if now - quarter.end < chrono::Duration::days(1) {
+        return Ok(());
+    }
+    let compiled: Option<String> = sqlx::query_scalar!(
+        "SELECT value FROM setting WHERE name=$1",
+        PURGE_QUARTER_SETTING
+    )
+    .fetch_optional(db)
+    .await?;
+    if compiled.is_some_and(|label| label == quarter.label) {
+        debug!("Purge candidates already compiled for {}", quarter.label);
+        return Ok(());
+    }
+    // instruction time counts toward the requirement
+    credit_training_sessions(db, now).await?;
+    let added = compile_purge_candidates(db, &quarter, now).await?;
+    sqlx::query!(
+        "INSERT INTO setting VALUES ($1, $2) ON CONFLICT(name) DO UPDATE SET value=excluded.value",
+        PURGE_QUARTER_SETTING,
+        quarter.label
+    )
+    .execute(db)
+    .await?;
+    info!(
+        "Compiled {} purge candidates for {}",
+        added.len(),
+        quarter.label
+    );
+    Ok(())
+}
+
+/// How long until the next nightly stats rollup.
+fn time_until_stats_rollup(now: DateTime<Utc>) -> Duration {
+    let today = now
+        .date_naive()
+        .and_hms_opt(STATS_ROLLUP_HOUR, 0, 0)
+        .unwrap()
+        .and_utc();
+    let next = if today > now {
+        today
+    } else {
+        today + chrono::Duration::days(1)
+    };
+    (next - now).to_std().unwrap_or_default()
+}
+
+/// Remove data the site no longer needs, logging what was removed.
+async fn clean_up(config: &Config, db: &SqlitePool) -> Result<()> {
+    let report = remove_stale_data(
+        db,
+        &config.database.cleanup,
+        Path::new("./assets"),
+        Utc::now(),
+    )
+    .await?;
+    if report.is_empty() {
+        debug!("Nothing to clean up");
+    } else {
+        info!("Cleaned up {report}");
+    }
+    Ok(())
+}
+
+/// Write a copy of the database to the backups directory, remove old
+/// backups past the retention count, and upload the new file if configured.
+async fn backup_database(config: &Config, db: &SqlitePool) -> Result<()> {
+    let backups = &config.database.backups;
+    let directory = Path::new(&backups.directory);
+    std::fs::create_dir_all(directory).context("creating backups directory")?;
+    let file_name = format!(
+        "{BACKUP_FILE_PREFIX}{}.sqlite",
+        Utc::now().format("%Y%m%d_%H%M%S")
+    );
+    let path = directory.join(&file_name);
+
+    // VACUUM INTO uses SQLite's own consistent read, so the site and bot can keep writing
+    let path_str = path.to_string_lossy().to_string();
+    sqlx::query!("VACUUM INTO $1", path_str)
+        .execute(db)
+        .await
+        .context("writing backup")?;
+    info!("Database backed up to {}", path.display());
+
+    // rotate; the timestamp in the name sorts oldest-first
+    let mut existing: Vec<PathBuf> = std::fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(BACKUP_FILE_PREFIX))
+        })
+        .collect();
+    existing.sort();
+    let to_remove = existing.len().saturating_sub(backups.retain.max(1));
+    for old in existing.iter().take(to_remove) {
+        debug!("Removing old backup {}", old.display());
+        std::fs::remove_file(old).with_context(|| format!("removing {}", old.display()))?;
+    }
+
+    if !backups.upload_url.is_empty() {
+        let data = tokio::fs::read(&path).await?;
+        let mut req = GENERAL_HTTP_CLIENT
+            .put(format!(
+                "{}/{file_name}",
+                backups.upload_url.trim_end_matches('/')
+            ))
+            .body(data);
+        if !backups.upload_authorization.is_empty() {
+            req = req.header("Authorization", &backups.upload_authorization);
+        }
+        let resp = req.send().await.context("uploading backup")?;
+        if !resp.status().is_success() {
+            anyhow::bail!("Got status {} uploading backup", resp.status().as_u16());
+        }
+        info!("Uploaded backup {file_name}");
+    }
+    Ok(())
+}
+
+/// Check the database for corruption and checkpoint the WAL, reporting any problems.
+async fn check_database_integrity(config: &Config, db: &SqlitePool) -> Result<()> {
+    let report = integrity::check_database(db, Utc::now())
+        .await
+        .context("checking database integrity")?;
+    if report.checkpoint_busy {
+        warn!("WAL checkpoint was blocked by another connection");
+    }
+    if report.is_ok() {
+        info!(
+            "Database integrity check passed; checkpointed {} WAL pages",
+            report.wal_pages.max(0)
+        );
+        return Ok(());
+    }
+    // the first few are enough to go on; the rest are on the admin tasks page
+    let message = format!(
+        "Database integrity check found {} problem(s):\n{}",
+        report.problems.len(),
+        report.problems[..report.problems.len().min(10)].join("\n")
+    );
+    error!("{message}");
+    error_reporting::capture_error("tasks::integrity", &message);
+    error_reporting::post_to_webhook(&config.discord.webhooks.errors, &message).await;
     Ok(())
 }
 
@@ -254,7 +663,16 @@ async fn update_activity(config: &Config, db: &SqlitePool) -> Result<()> {
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
-    let (config, db) = general_setup(cli.debug, "vzdv_tasks", cli.config).await;
+    let (config, db) = general_setup(cli.debug, "vzdv_tasks", cli.config, cli.env).await;
+    let _error_reporting = error_reporting::init(&config, "vzdv_tasks");
+
+    if let Some(Command::Seed { force }) = cli.command {
+        if let Err(e) = seed::seed(&config, &db, force).await {
+            error!("Error seeding database: {e}");
+        }
+        db.close().await;
+        return;
+    }
 
     info!("Starting tasks");
     let roster_handle = {
@@ -270,6 +688,7 @@ async fn main() {
                     }
                     Err(e) => {
                         error!("Error updating roster: {e}");
+                        error_reporting::capture_error("tasks::roster", &format!("{e:?}"));
                     }
                 }
                 debug!("Waiting 4 hours for next roster sync");
@@ -278,6 +697,20 @@ async fn main() {
         })
     };
 
+    let roster_refresh_handle = {
+        let config = config.clone();
+        let db = db.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = refresh_queued_controllers(&config, &db).await {
+                    error!("Error refreshing queued controllers: {e}");
+                    error_reporting::capture_error("tasks::roster_refresh", &format!("{e:?}"));
+                }
+                time::sleep(ROSTER_REFRESH_INTERVAL).await;
+            }
+        })
+    };
+
     let activity_handle = {
         let config = config.clone();
         let db = db.clone();
@@ -292,6 +725,7 @@ async fn main() {
                     }
                     Err(e) => {
                         error!("Error updating activity: {e}");
+                        error_reporting::capture_error("tasks::activity", &format!("{e:?}"));
                     }
                 }
                 debug!("Waiting 12 hours for next activity sync");
@@ -300,8 +734,147 @@ async fn main() {
         })
     };
 
+    let sessions_handle = {
+        let config = config.clone();
+        let db = db.clone();
+        tokio::spawn(async move {
+            debug!("Waiting 30 seconds before starting session tracking");
+            time::sleep(time::Duration::from_secs(30)).await;
+            loop {
+                if let Err(e) = track_controller_sessions(&config, &db).await {
+                    error!("Error tracking controller sessions: {e}");
+                    error_reporting::capture_error("tasks::sessions", &format!("{e:?}"));
+                }
+                time::sleep(SESSION_TRACKING_INTERVAL).await;
+            }
+        })
+    };
+
+    let purge_handle = {
+        let db = db.clone();
+        tokio::spawn(async move {
+            debug!("Waiting 5 minutes before checking purge candidates");
+            time::sleep(time::Duration::from_secs(60 * 5)).await;
+            loop {
+                if let Err(e) = update_purge_candidates(&db).await {
+                    error!("Error compiling purge candidates: {e}");
+                    error_reporting::capture_error("tasks::purge", &format!("{e:?}"));
+                }
+                debug!("Waiting 12 hours for next purge candidates check");
+                time::sleep(time::Duration::from_secs(60 * 60 * 12)).await;
+            }
+        })
+    };
+
+    let backup_handle = {
+        let config = config.clone();
+        let db = db.clone();
+        tokio::spawn(async move {
+            if config.database.backups.directory.is_empty() {
+                info!("No backups directory set; not backing up the database");
+                return;
+            }
+            let interval = config.database.backups.interval_hours.max(1);
+            loop {
+                info!("Backing up database");
+                match backup_database(&config, &db).await {
+                    Ok(_) => {
+                        info!("Database backup successful");
+                    }
+                    Err(e) => {
+                        error!("Error backing up database: {e:?}");
+                        error_reporting::capture_error("tasks::backup", &format!("{e:?}"));
+                        error_reporting::post_to_webhook(
+                            &config.discord.webhooks.errors,
+                            &format!("Database backup failed: {e:?}"),
+                        )
+                        .await;
+                    }
+                }
+                debug!("Waiting {interval} hours for next database backup");
+                time::sleep(time::Duration::from_secs(60 * 60 * interval)).await;
+            }
+        })
+    };
+
+    let stats_handle = {
+        let db = db.clone();
+        tokio::spawn(async move {
+            // build once on startup so the pages have data before the first night
+            loop {
+                info!("Rebuilding stats rollups");
+                if let Err(e) = stats::rebuild_rollups(&db, Utc::now()).await {
+                    error!("Error rebuilding stats rollups: {e}");
+                    error_reporting::capture_error("tasks::stats", &format!("{e:?}"));
+                }
+                let wait = time_until_stats_rollup(Utc::now());
+                debug!(
+                    "Waiting {} minutes for next stats rollup",
+                    wait.as_secs() / 60
+                );
+                time::sleep(wait).await;
+            }
+        })
+    };
+
+    let cleanup_handle = {
+        let config = config.clone();
+        let db = db.clone();
+        tokio::spawn(async move {
+            if !config.database.cleanup.enabled {
+                info!("Cleanup is disabled");
+                return;
+            }
+            debug!("Waiting 10 minutes before starting cleanup");
+            time::sleep(time::Duration::from_secs(60 * 10)).await;
+            let interval = config.database.cleanup.interval_hours.max(1);
+            loop {
+                if let Err(e) = clean_up(&config, &db).await {
+                    error!("Error cleaning up stale data: {e:?}");
+                    error_reporting::capture_error("tasks::cleanup", &format!("{e:?}"));
+                }
+                debug!("Waiting {interval} hours for next cleanup");
+                time::sleep(time::Duration::from_secs(60 * 60 * interval)).await;
+            }
+        })
+    };
+
+    let integrity_handle = {
+        let config = config.clone();
+        let db = db.clone();
+        tokio::spawn(async move {
+            if !config.database.integrity.enabled {
+                info!("Database integrity checks are disabled");
+                return;
+            }
+            debug!("Waiting 15 minutes before the first integrity check");
+            time::sleep(time::Duration::from_secs(60 * 15)).await;
+            let interval = config.database.integrity.interval_hours.max(1);
+            loop {
+                if let Err(e) = check_database_integrity(&config, &db).await {
+                    error!("Error checking database integrity: {e:?}");
+                    error_reporting::capture_error("tasks::integrity", &format!("{e:?}"));
+                    error_reporting::post_to_webhook(
+                        &config.discord.webhooks.errors,
+                        &format!("Database integrity check failed: {e:?}"),
+                    )
+                    .await;
+                }
+                debug!("Waiting {interval} hours for next integrity check");
+                time::sleep(time::Duration::from_secs(60 * 60 * interval)).await;
+            }
+        })
+    };
+
     roster_handle.await.unwrap();
+    roster_refresh_handle.await.unwrap();
     activity_handle.await.unwrap();
+    sessions_handle.await.unwrap();
+    purge_handle.await.unwrap();
+    backup_handle.await.unwrap();
+    cleanup_handle.await.unwrap();
+    stats_handle.await.unwrap();
+    integrity_handle.await.unwrap();
 
     db.close().await;
 }
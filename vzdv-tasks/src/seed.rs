@@ -0,0 +1,337 @@
+//! Fake data for local development.
+
+use anyhow::{bail, Result};
+use chrono::{Duration, Months, Utc};
+use log::{info, warn};
+use sqlx::SqlitePool;
+use vzdv::{config::Config, ControllerRating};
+
+const FIRST_NAMES: &[&str] = &[
+    "Alex", "Jordan", "Taylor", "Morgan", "Casey", "Riley", "Jamie", "Avery", "Quinn", "Rowan",
+    "Skyler", "Dakota",
+];
+const LAST_NAMES: &[&str] = &[
+    "Baker", "Chen", "Diaz", "Evans", "Fischer", "Garcia", "Hughes", "Ito", "Jensen", "Kowalski",
+    "Lopez", "Miller", "Novak",
+];
+const RATINGS: &[ControllerRating] = &[
+    ControllerRating::OBS,
+    ControllerRating::S1,
+    ControllerRating::S2,
+    ControllerRating::S3,
+    ControllerRating::C1,
+    ControllerRating::C1,
+    ControllerRating::C3,
+    ControllerRating::I1,
+];
+const POSITIONS: &[&str] = &[
+    "DEN_GND", "DEN_TWR", "DEN_APP", "DEN_CTR", "COS_TWR", "ASE_TWR",
+];
+const RESOURCE_CATEGORIES: &[&str] = &["General", "SOP", "LOA", "Misc"];
+/// Event positions and their categories.
+const EVENT_POSITIONS: &[(&str, &str)] = &[
+    ("DEN_CTR", "Enroute"),
+    ("DEN_APP", "TRACON"),
+    ("DEN_TWR", "Local"),
+    ("DEN_GND", "Local"),
+];
+/// CIDs of fake controllers start here, to not collide with real ones.
+const FIRST_CID: u32 = 9_900_000;
+const CONTROLLER_COUNT: u32 = 30;
+/// Tables emptied by a forced seed, ordered so foreign keys aren't violated.
+const CLEARED_TABLES: &[&str] = &[
+    "event_waitlist",
+    "event_registration",
+    "event_position",
+    "event_category_limit",
+    "event_channel",
+    "event",
+    "solo_cert",
+    "training_record",
+    "loa",
+    "staff_note",
+    "activity",
+    "certification_history",
+    "certification",
+    "feedback_comment",
+    "feedback",
+    "resource",
+    "resource_category",
+    "banner",
+    "visitor_request",
+    "remember_token",
+    "controller_permission",
+    "welcome_message",
+    "exit_survey",
+    "notification_preference",
+    "discord_link_code",
+    "purge_candidate",
+    "oauth_grant",
+    "calendar_token",
+    "training_lesson_session",
+    "training_feedback",
+    "training_session",
+    "training_waitlist",
+    "impersonation_log",
+    "controller",
+];
+
+/// Fill an empty database with fake controllers, activity, events, feedback, and resources.
+///
+/// With `force`, all existing data in those tables is deleted first.
+///
+/// Values are derived from each controller's index rather than randomly, so
+/// re-seeding produces the same data.
+pub async fn seed(config: &Config, db: &SqlitePool, force: bool) -> Result<()> {
+    let existing = sqlx::query_scalar!("SELECT COUNT(*) FROM controller")
+        .fetch_one(db)
+        .await?;
+    if existing > 0 && !force {
+        bail!("Database already has {existing} controllers; pass --force to replace them");
+    }
+
+    let now = Utc::now();
+    let mut tx = db.begin().await?;
+    if existing > 0 {
+        warn!("Deleting existing data before seeding");
+        for table in CLEARED_TABLES {
+            // table names can't be bound, so this is the one query left unchecked
+            sqlx::query(&format!("DELETE FROM {table}"))
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+    for i in 0..CONTROLLER_COUNT {
+        let cid = FIRST_CID + i;
+        let index = i as usize;
+        let first_name = FIRST_NAMES[index % FIRST_NAMES.len()];
+        let last_name = LAST_NAMES[(index * 7) % LAST_NAMES.len()];
+        let rating = RATINGS[index % RATINGS.len()].as_id();
+        let is_on_roster = i % 10 != 9;
+        let home_facility = if i % 5 == 4 { "ZLC" } else { "ZDV" };
+        let roles = match i {
+            0 => "ATM",
+            1 => "DATM",
+            2 => "TA",
+            3 | 4 => "MTR",
+            _ => "",
+        };
+        let ois = format!(
+            "{}{}",
+            first_name.chars().next().unwrap(),
+            (b'A' + (i % 26) as u8) as char
+        );
+        let join_date = now - Duration::days(30 * (i as i64 + 1));
+        sqlx::query!(
+            "INSERT INTO controller (id, cid, first_name, last_name, email, operating_initials, rating, status, home_facility, is_on_roster, roles, join_date) VALUES (NULL, $1, $2, $3, NULL, $4, $5, 'Active', $6, $7, $8, $9)",
+            cid,
+            first_name,
+            last_name,
+            ois,
+            rating,
+            home_facility,
+            is_on_roster,
+            roles,
+            join_date
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for (cert_index, cert) in config.training.certifications.iter().enumerate() {
+            let value = match (index + cert_index) % 4 {
+                0 => "certified",
+                1 => "training",
+                2 => "solo",
+                _ => continue,
+            };
+            sqlx::query!(
+                "INSERT INTO certification (id, cid, name, value, changed_on, set_by) VALUES (NULL, $1, $2, $3, $4, $5)",
+                cid,
+                cert,
+                value,
+                now,
+                FIRST_CID
+            )
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query!(
+                "INSERT INTO certification_history VALUES (NULL, $1, $2, $3, $4, $5, $6)",
+                cid,
+                cert,
+                None::<String>,
+                value,
+                FIRST_CID,
+                now
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        if is_on_roster {
+            for month_offset in 0..12 {
+                let month = (now - Months::new(month_offset))
+                    .format("%Y-%m")
+                    .to_string();
+                let minutes = ((i * 37 + month_offset * 53) % 600) as i64;
+                sqlx::query!(
+                    "INSERT INTO activity (id, cid, month, minutes) VALUES (NULL, $1, $2, $3)",
+                    cid,
+                    month,
+                    minutes
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+    }
+
+    for i in 0..10u32 {
+        let controller = FIRST_CID + (i * 3) % CONTROLLER_COUNT;
+        let position = POSITIONS[i as usize % POSITIONS.len()];
+        let rating = ["excellent", "good", "fair", "poor"][i as usize % 4];
+        let created = now - Duration::days(i as i64 * 2);
+        let action = if i < 5 { "pending" } else { "archive" };
+        sqlx::query!(
+            "INSERT INTO feedback (id, controller, position, rating, comments, created_date, submitter_cid, reviewer_action) VALUES (NULL, $1, $2, $3, $4, $5, $6, $7)",
+            controller,
+            position,
+            rating,
+            "Smooth handoffs and clear instructions.",
+            created,
+            1_234_567,
+            action
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for (i, name) in [
+        "Friday Night Ops",
+        "Rocky Mountain Fly-In",
+        "Mile High Madness",
+    ]
+    .iter()
+    .enumerate()
+    {
+        let start = now + Duration::days(7 * (i as i64 + 1));
+        let end = start + Duration::hours(3);
+        let published = i < 2;
+        let event_id = sqlx::query!(
+            "INSERT INTO event (id, created_by, published, name, start, end, description, image_url) VALUES (NULL, $1, $2, $3, $4, $5, $6, NULL)",
+            FIRST_CID,
+            published,
+            name,
+            start,
+            end,
+            "Come fly into Denver!"
+        )
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        for (position, category) in EVENT_POSITIONS {
+            sqlx::query!(
+                "INSERT INTO event_position (id, event_id, name, category, cid) VALUES (NULL, $1, $2, $3, NULL)",
+                event_id,
+                position,
+                category
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    for (i, category) in RESOURCE_CATEGORIES.iter().enumerate() {
+        sqlx::query!(
+            "INSERT INTO resource_category VALUES (NULL, $1, (SELECT COALESCE(MAX(sort_order), 0) + 1 FROM resource_category), FALSE, 'public')",
+            category
+        )
+        .execute(&mut *tx)
+        .await?;
+        let name = format!("{category} document {}", i + 1);
+        sqlx::query!(
+            "INSERT INTO resource (id, category, name, file_name, link, updated) VALUES (NULL, $1, $2, NULL, $3, $4)",
+            category,
+            name,
+            "https://example.com/",
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    info!("Seeded {CONTROLLER_COUNT} controllers with activity, events, feedback, and resources");
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{seed, CLEARED_TABLES};
+    use sqlx::{sqlite::SqlitePoolOptions, Executor, SqlitePool};
+    use vzdv::{config::Config, db::run_migrations, sql};
+
+    async fn migrated_db() -> SqlitePool {
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        db.execute(sql::CREATE_TABLES).await.unwrap();
+        run_migrations(&db).await.unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_cleared_tables_order() {
+        let db = migrated_db().await;
+        let tables: Vec<(String,)> =
+            sqlx::query_as("SELECT name FROM sqlite_master WHERE type='table'")
+                .fetch_all(&db)
+                .await
+                .unwrap();
+        for (table,) in tables {
+            let parents: Vec<(String,)> =
+                sqlx::query_as("SELECT DISTINCT \"table\" FROM pragma_foreign_key_list($1)")
+                    .bind(&table)
+                    .fetch_all(&db)
+                    .await
+                    .unwrap();
+            for (parent,) in parents {
+                let Some(parent_index) = CLEARED_TABLES.iter().position(|t| *t == parent) else {
+                    continue;
+                };
+                // anything referencing a cleared table has to be cleared before it
+                let index = CLEARED_TABLES.iter().position(|t| *t == table);
+                assert!(
+                    index.is_some_and(|index| index < parent_index),
+                    "{table} references {parent} but isn't cleared before it"
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forced_seed_twice() {
+        let db = migrated_db().await;
+        let mut config = Config::default();
+        config.training.certifications = vec!["GND".to_owned(), "TWR".to_owned()];
+
+        seed(&config, &db, false).await.unwrap();
+        assert!(seed(&config, &db, false).await.is_err());
+        seed(&config, &db, true).await.unwrap();
+        // rows the seed doesn't create still have to be cleared before their controllers
+        db.execute(
+            "INSERT INTO remember_token VALUES (NULL, 'selector', 'hash', 9900000, '', '', NULL, NULL, NULL);
+            INSERT INTO controller_permission VALUES (9900001, 'staff', TRUE);
+            INSERT INTO notification_preference (cid) VALUES (9900002);",
+        )
+        .await
+        .unwrap();
+        seed(&config, &db, true).await.unwrap();
+        let (controllers,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM controller")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(controllers, 30);
+    }
+}
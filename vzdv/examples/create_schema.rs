@@ -0,0 +1,17 @@
+//! Create an empty database with the current schema at the supplied path.
+//!
+//! Used by `ops/prepare_queries.sh` to give the `sqlx` query macros a
+//! database to check against.
+
+use vzdv::{config::Config, db::load_db};
+
+#[tokio::main]
+async fn main() {
+    let path = std::env::args()
+        .nth(1)
+        .expect("Supply a path for the new database file");
+    let mut config = Config::default();
+    config.database.file = path;
+    let db = load_db(&config).await.expect("Could not create database");
+    db.close().await;
+}
@@ -0,0 +1,633 @@
+//! Activity requirement, live activity, and the quarterly roster purge.
+//!
+//! Controllers need [`REQUIRED_MINUTES`] of activity each quarter. After a
+//! quarter ends, the controllers who fell short are stored as purge
+//! candidates for staff to review on the site before anyone is removed.
+//!
+//! Stored controlling activity comes from VATSIM's session history, which is
+//! only synced every few hours. Sessions seen in the datafeed since then are
+//! tracked separately so that the current month can include them.
+//!
+//! Mentors and instructors are also credited with the time they spend
+//! instructing in booked training sessions, which counts toward the requirement.
+
+use crate::{
+    config::Config,
+    position_in_facility_airspace,
+    sql::{self, Controller, ControllerSession, TrainingSession},
+    vatsim::parse_vatsim_timestamp,
+};
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveDateTime, Utc};
+use sqlx::{SqliteConnection, SqlitePool};
+use std::collections::HashMap;
+use vatsim_utils::models::{AtcSessionEntry, V3ResponseData};
+
+/// Minutes of activity, from all sources, needed in a quarter.
+pub const REQUIRED_MINUTES: u32 = 180;
+
+/// How far apart a datafeed session and VATSIM's record of it can be and still match.
+const SESSION_MATCH_TOLERANCE_MINUTES: i64 = 5;
+/// How long a datafeed session is kept if VATSIM's history never matches it.
+const SESSION_RETENTION_HOURS: i64 = 24;
+
+/// A calendar quarter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Quarter {
+    /// Like "2026-Q3".
+    pub label: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Activity months in the quarter, like "2026-07".
+    pub months: [String; 3],
+}
+
+impl Quarter {
+    /// The quarter that contains the timestamp.
+    pub fn containing(date: DateTime<Utc>) -> Self {
+        let first_month = (date.month0() / 3) * 3 + 1;
+        let start = NaiveDate::from_ymd_opt(date.year(), first_month, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_utc();
+        let month = |offset: u32| {
+            start
+                .checked_add_months(Months::new(offset))
+                .unwrap()
+                .format("%Y-%m")
+                .to_string()
+        };
+        Self {
+            label: format!("{}-Q{}", date.year(), first_month / 3 + 1),
+            start,
+            end: start.checked_add_months(Months::new(3)).unwrap(),
+            months: [month(0), month(1), month(2)],
+        }
+    }
+
+    /// The last full quarter before the timestamp.
+    pub fn previous(date: DateTime<Utc>) -> Self {
+        Self::containing(Self::containing(date).start - chrono::Duration::days(1))
+    }
+
+    /// Parse a label like "2026-Q3".
+    pub fn from_label(label: &str) -> Option<Self> {
+        let (year, number) = label.split_once("-Q")?;
+        let year: i32 = year.parse().ok()?;
+        let number: u32 = number.parse().ok()?;
+        if !(1..=4).contains(&number) {
+            return None;
+        }
+        let start = NaiveDate::from_ymd_opt(year, (number - 1) * 3 + 1, 1)?
+            .and_hms_opt(0, 0, 0)?
+            .and_utc();
+        Some(Self::containing(start))
+    }
+}
+
+/// Start of the month that contains the timestamp.
+pub fn start_of_month(date: DateTime<Utc>) -> DateTime<Utc> {
+    NaiveDate::from_ymd_opt(date.year(), date.month(), 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+        .and_utc()
+}
+
+/// Store the facility controllers in the datafeed as sessions seen at `now`.
+///
+/// Returns how many sessions were stored or updated.
+pub async fn record_datafeed_sessions(
+    db: &SqlitePool,
+    config: &Config,
+    data: &V3ResponseData,
+    now: DateTime<Utc>,
+) -> anyhow::Result<usize> {
+    let mut recorded = 0;
+    for controller in data
+        .controllers
+        .iter()
+        .filter(|controller| position_in_facility_airspace(config, &controller.callsign))
+    {
+        let logon_time = parse_vatsim_timestamp(&controller.logon_time)?;
+        sqlx::query(sql::UPSERT_CONTROLLER_SESSION)
+            .bind(controller.cid as u32)
+            .bind(&controller.callsign)
+            .bind(logon_time)
+            .bind(now)
+            .execute(db)
+            .await?;
+        recorded += 1;
+    }
+    Ok(recorded)
+}
+
+/// A controller's session from VATSIM's history.
+#[derive(Debug, Clone)]
+pub struct HistorySession {
+    pub callsign: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl HistorySession {
+    /// From the session history API's entry, if its times can be read.
+    pub fn from_entry(entry: &AtcSessionEntry) -> Option<Self> {
+        // the API's timestamps vary in fractional seconds and zone suffix
+        let parse = |stamp: &str| {
+            NaiveDateTime::parse_from_str(stamp.get(..19)?, "%Y-%m-%dT%H:%M:%S")
+                .ok()
+                .map(|time| time.and_utc())
+        };
+        Some(Self {
+            callsign: entry.callsign.clone(),
+            start: parse(&entry.start)?,
+            end: parse(&entry.end)?,
+        })
+    }
+
+    /// Whether this is the datafeed session, through when it was last seen.
+    fn covers(&self, session: &ControllerSession) -> bool {
+        let tolerance = Duration::minutes(SESSION_MATCH_TOLERANCE_MINUTES);
+        self.callsign == session.callsign
+            && (self.start - session.logon_time).abs() <= tolerance
+            && self.end >= session.last_seen - tolerance
+    }
+}
+
+/// Remove the controller's datafeed sessions that their fetched VATSIM
+/// history now includes, so that they aren't counted twice.
+///
+/// Sessions the history doesn't have yet are kept for
+/// [`live_minutes_this_month`], unless they're old enough that VATSIM
+/// should have had them by now. Returns how many sessions were removed.
+pub async fn clear_synced_sessions(
+    db: &mut SqliteConnection,
+    cid: u32,
+    history: &[HistorySession],
+    now: DateTime<Utc>,
+) -> Result<usize, sqlx::Error> {
+    let sessions: Vec<ControllerSession> = sqlx::query_as(sql::GET_CONTROLLER_SESSIONS_FOR)
+        .bind(cid)
+        .fetch_all(&mut *db)
+        .await?;
+    let retention_cutoff = now - Duration::hours(SESSION_RETENTION_HOURS);
+    let mut removed = 0;
+    for session in sessions {
+        if session.last_seen >= retention_cutoff
+            && !history.iter().any(|entry| entry.covers(&session))
+        {
+            continue;
+        }
+        sqlx::query(sql::DELETE_CONTROLLER_SESSION)
+            .bind(session.id)
+            .execute(&mut *db)
+            .await?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// Minutes per controller from datafeed sessions in the current month that
+/// the VATSIM sync hasn't picked up yet.
+///
+/// Sessions that started last month only count from the start of this one.
+pub async fn live_minutes_this_month(
+    db: &SqlitePool,
+    now: DateTime<Utc>,
+) -> Result<HashMap<u32, u32>, sqlx::Error> {
+    let month_start = start_of_month(now);
+    let sessions: Vec<ControllerSession> = sqlx::query_as(sql::GET_CONTROLLER_SESSIONS_SEEN_SINCE)
+        .bind(month_start)
+        .fetch_all(db)
+        .await?;
+    let mut minutes: HashMap<u32, u32> = HashMap::new();
+    for session in sessions {
+        let start = session.logon_time.max(month_start);
+        *minutes.entry(session.cid).or_default() +=
+            (session.last_seen - start).num_minutes().max(0) as u32;
+    }
+    Ok(minutes)
+}
+
+/// Credit instructors with the time of their training sessions that have ended.
+///
+/// Each session is credited once, in the month it started. Time credited for
+/// sessions that were cancelled afterwards is removed. Returns how many
+/// sessions were newly credited.
+pub async fn credit_training_sessions(
+    db: &SqlitePool,
+    now: DateTime<Utc>,
+) -> Result<usize, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    sqlx::query(sql::DELETE_CANCELLED_TRAINING_ACTIVITY)
+        .execute(&mut *tx)
+        .await?;
+    let sessions: Vec<TrainingSession> = sqlx::query_as(sql::GET_UNCREDITED_TRAINING_SESSIONS)
+        .bind(now)
+        .fetch_all(&mut *tx)
+        .await?;
+    for session in &sessions {
+        sqlx::query(sql::INSERT_TRAINING_ACTIVITY)
+            .bind(session.instructor_cid)
+            .bind(session.start.format("%Y-%m").to_string())
+            .bind((session.end - session.start).num_minutes().max(0) as u32)
+            .bind(session.id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(sessions.len())
+}
+
+/// Why the controller doesn't need to meet the activity requirement for the quarter, if so.
+///
+/// Controllers on an LOA are exempt, as are those who joined after the quarter
+/// started and so didn't have all of it to meet the requirement.
+pub fn exemption(
+    controller: &Controller,
+    quarter: &Quarter,
+    now: DateTime<Utc>,
+) -> Option<&'static str> {
+    if controller.loa_until.is_some_and(|until| until > now) {
+        return Some("on LOA");
+    }
+    // unknown join dates are treated as new, to be safe
+    if controller
+        .join_date
+        .is_none_or(|joined| joined > quarter.start)
+    {
+        return Some("joined during the quarter");
+    }
+    None
+}
+
+/// Store the controllers who didn't meet the activity requirement in the quarter.
+///
+/// Controllers with an [`exemption`] are skipped. Returns the CIDs of
+/// newly-stored candidates; running this again for the same quarter adds no
+/// duplicates.
+pub async fn compile_purge_candidates(
+    db: &SqlitePool,
+    quarter: &Quarter,
+    now: DateTime<Utc>,
+) -> Result<Vec<u32>, sqlx::Error> {
+    let controllers: Vec<Controller> = sqlx::query_as(sql::GET_ALL_CONTROLLERS_ON_ROSTER)
+        .fetch_all(db)
+        .await?;
+    let totals: HashMap<u32, u32> = sqlx::query_as(sql::GET_ACTIVITY_TOTALS_BETWEEN)
+        .bind(&quarter.months[0])
+        .bind(&quarter.months[2])
+        .fetch_all(db)
+        .await?
+        .into_iter()
+        .collect();
+    let mut added = Vec::new();
+    for controller in controllers {
+        if exemption(&controller, quarter, now).is_some() {
+            continue;
+        }
+        let minutes = totals.get(&controller.cid).copied().unwrap_or_default();
+        if minutes >= REQUIRED_MINUTES {
+            continue;
+        }
+        let result = sqlx::query(sql::INSERT_PURGE_CANDIDATE)
+            .bind(controller.cid)
+            .bind(&quarter.label)
+            .bind(minutes)
+            .bind(now)
+            .execute(db)
+            .await?;
+        if result.rows_affected() > 0 {
+            added.push(controller.cid);
+        }
+    }
+    Ok(added)
+}
+
+/// Why a purge candidate no longer needs removing for the quarter, if so.
+///
+/// Purges are approved some time after the candidates are compiled, so this
+/// checks the controller against the same [`exemption`] and activity
+/// requirement again, right before removing them.
+pub async fn purge_skip_reason(
+    db: &SqlitePool,
+    cid: u32,
+    quarter: &Quarter,
+    now: DateTime<Utc>,
+) -> Result<Option<String>, sqlx::Error> {
+    let controller: Option<Controller> = sqlx::query_as(sql::GET_CONTROLLER_BY_CID)
+        .bind(cid)
+        .fetch_optional(db)
+        .await?;
+    if let Some(reason) = controller
+        .as_ref()
+        .and_then(|controller| exemption(controller, quarter, now))
+    {
+        return Ok(Some(reason.to_owned()));
+    }
+    let minutes: u32 = sqlx::query_scalar(sql::GET_ACTIVITY_TOTAL_BETWEEN_FOR)
+        .bind(cid)
+        .bind(&quarter.months[0])
+        .bind(&quarter.months[2])
+        .fetch_one(db)
+        .await?;
+    if minutes >= REQUIRED_MINUTES {
+        return Ok(Some(format!("has {minutes} minutes in the quarter")));
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{
+        clear_synced_sessions, compile_purge_candidates, credit_training_sessions,
+        live_minutes_this_month, purge_skip_reason, HistorySession, Quarter,
+    };
+    use crate::{db::run_migrations, sql};
+    use chrono::{TimeZone, Utc};
+    use sqlx::{sqlite::SqlitePoolOptions, Executor};
+
+    #[test]
+    fn test_quarters() {
+        let date = Utc.with_ymd_and_hms(2026, 10, 16, 12, 0, 0).unwrap();
+        let current = Quarter::containing(date);
+        assert_eq!(current.label, "2026-Q4");
+        assert_eq!(
+            current.end,
+            Utc.with_ymd_and_hms(2027, 1, 1, 0, 0, 0).unwrap()
+        );
+
+        let previous = Quarter::previous(date);
+        assert_eq!(previous.label, "2026-Q3");
+        assert_eq!(previous.months, ["2026-07", "2026-08", "2026-09"]);
+        assert_eq!(
+            previous.start,
+            Utc.with_ymd_and_hms(2026, 7, 1, 0, 0, 0).unwrap()
+        );
+
+        let january = Utc.with_ymd_and_hms(2027, 1, 2, 0, 0, 0).unwrap();
+        assert_eq!(Quarter::previous(january).label, "2026-Q4");
+
+        assert_eq!(Quarter::from_label("2026-Q3"), Some(previous));
+        assert_eq!(Quarter::from_label("2026-Q5"), None);
+        assert_eq!(Quarter::from_label("2026"), None);
+    }
+
+    #[tokio::test]
+    async fn test_compile_purge_candidates() {
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        db.execute(sql::CREATE_TABLES).await.unwrap();
+        run_migrations(&db).await.unwrap();
+        let now = Utc.with_ymd_and_hms(2026, 10, 2, 0, 0, 0).unwrap();
+        let long_ago = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let recently = Utc.with_ymd_and_hms(2026, 8, 1, 0, 0, 0).unwrap();
+        let loa_end = Utc.with_ymd_and_hms(2026, 11, 1, 0, 0, 0).unwrap();
+        // active, inactive, inactive but on LOA, inactive but new, off-roster
+        for (cid, joined, loa_until, on_roster) in [
+            (1, Some(long_ago), None, true),
+            (2, Some(long_ago), None, true),
+            (3, Some(long_ago), Some(loa_end), true),
+            (4, Some(recently), None, true),
+            (5, None, None, false),
+        ] {
+            sqlx::query("INSERT INTO controller (cid, first_name, last_name, rating, is_on_roster, join_date, loa_until) VALUES ($1, '', '', 1, $2, $3, $4)")
+                .bind(cid)
+                .bind(on_roster)
+                .bind(joined)
+                .bind(loa_until)
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+        for (cid, month, minutes) in [(1, "2026-08", 200), (2, "2026-09", 60), (2, "2026-10", 500)]
+        {
+            sqlx::query("INSERT INTO activity (id, cid, month, minutes) VALUES (NULL, $1, $2, $3)")
+                .bind(cid)
+                .bind(month)
+                .bind(minutes)
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+
+        let quarter = Quarter::previous(now);
+        let added = compile_purge_candidates(&db, &quarter, now).await.unwrap();
+        assert_eq!(added, vec![2]);
+        let minutes: u32 = sqlx::query_scalar("SELECT minutes FROM purge_candidate WHERE cid=2")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(minutes, 60);
+
+        let added = compile_purge_candidates(&db, &quarter, now).await.unwrap();
+        assert!(added.is_empty());
+
+        // re-checked when the approved purge runs
+        assert_eq!(
+            purge_skip_reason(&db, 2, &quarter, now).await.unwrap(),
+            None
+        );
+        sqlx::query(
+            "INSERT INTO activity (id, cid, month, minutes) VALUES (NULL, 2, '2026-07', 150)",
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        assert_eq!(
+            purge_skip_reason(&db, 2, &quarter, now).await.unwrap(),
+            Some("has 210 minutes in the quarter".to_owned())
+        );
+        sqlx::query("UPDATE controller SET loa_until=$1 WHERE cid=2")
+            .bind(loa_end)
+            .execute(&db)
+            .await
+            .unwrap();
+        assert_eq!(
+            purge_skip_reason(&db, 2, &quarter, now).await.unwrap(),
+            Some("on LOA".to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_live_minutes_this_month() {
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        db.execute(sql::CREATE_TABLES).await.unwrap();
+        run_migrations(&db).await.unwrap();
+        let now = Utc.with_ymd_and_hms(2026, 10, 16, 12, 0, 0).unwrap();
+        // ongoing, ongoing since last month, and last month only
+        for (cid, logon, last_seen) in [
+            (1, Utc.with_ymd_and_hms(2026, 10, 16, 10, 30, 0), now),
+            (
+                2,
+                Utc.with_ymd_and_hms(2026, 9, 30, 23, 0, 0),
+                Utc.with_ymd_and_hms(2026, 10, 1, 1, 0, 0).unwrap(),
+            ),
+            (
+                3,
+                Utc.with_ymd_and_hms(2026, 9, 20, 10, 0, 0),
+                Utc.with_ymd_and_hms(2026, 9, 20, 12, 0, 0).unwrap(),
+            ),
+        ] {
+            sqlx::query(sql::UPSERT_CONTROLLER_SESSION)
+                .bind(cid)
+                .bind("DEN_APP")
+                .bind(logon.unwrap())
+                .bind(last_seen)
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+
+        let minutes = live_minutes_this_month(&db, now).await.unwrap();
+        assert_eq!(minutes.get(&1), Some(&90));
+        assert_eq!(minutes.get(&2), Some(&60));
+        assert_eq!(minutes.get(&3), None);
+    }
+
+    #[tokio::test]
+    async fn test_clear_synced_sessions() {
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        db.execute(sql::CREATE_TABLES).await.unwrap();
+        run_migrations(&db).await.unwrap();
+        let now = Utc.with_ymd_and_hms(2026, 10, 16, 12, 0, 0).unwrap();
+        let at = |hour, minute| Utc.with_ymd_and_hms(2026, 10, 16, hour, minute, 0).unwrap();
+        // synced, ended but not synced yet, ongoing, and long gone
+        for (callsign, logon, last_seen) in [
+            ("DEN_APP", at(8, 0), at(9, 30)),
+            ("DEN_CTR", at(10, 0), at(11, 0)),
+            ("DEN_TWR", at(11, 0), at(11, 59)),
+            (
+                "DEN_GND",
+                Utc.with_ymd_and_hms(2026, 10, 14, 8, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2026, 10, 14, 9, 0, 0).unwrap(),
+            ),
+        ] {
+            sqlx::query(sql::UPSERT_CONTROLLER_SESSION)
+                .bind(1)
+                .bind(callsign)
+                .bind(logon)
+                .bind(last_seen)
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+        let history = vec![
+            HistorySession {
+                callsign: "DEN_APP".to_owned(),
+                start: at(8, 1),
+                end: at(9, 31),
+            },
+            // only up to partway through the session
+            HistorySession {
+                callsign: "DEN_TWR".to_owned(),
+                start: at(11, 0),
+                end: at(11, 20),
+            },
+        ];
+
+        let mut conn = db.acquire().await.unwrap();
+        let removed = clear_synced_sessions(&mut conn, 1, &history, now)
+            .await
+            .unwrap();
+        assert_eq!(removed, 2);
+        let remaining: Vec<String> =
+            sqlx::query_scalar("SELECT callsign FROM controller_session ORDER BY callsign")
+                .fetch_all(&mut *conn)
+                .await
+                .unwrap();
+        assert_eq!(remaining, vec!["DEN_CTR", "DEN_TWR"]);
+    }
+
+    #[tokio::test]
+    async fn test_credit_training_sessions() {
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        db.execute(sql::CREATE_TABLES).await.unwrap();
+        run_migrations(&db).await.unwrap();
+        let now = Utc.with_ymd_and_hms(2026, 10, 2, 0, 0, 0).unwrap();
+        let long_ago = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        for cid in [1, 2] {
+            sqlx::query("INSERT INTO controller (cid, first_name, last_name, rating, is_on_roster, join_date) VALUES ($1, '', '', 5, TRUE, $2)")
+                .bind(cid)
+                .bind(long_ago)
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+        // instructor 1 teaching student 2: done, done but cancelled, and upcoming
+        for (start, minutes, cancelled) in [
+            (
+                Utc.with_ymd_and_hms(2026, 9, 30, 23, 0, 0).unwrap(),
+                120,
+                false,
+            ),
+            (
+                Utc.with_ymd_and_hms(2026, 9, 10, 18, 0, 0).unwrap(),
+                90,
+                true,
+            ),
+            (
+                Utc.with_ymd_and_hms(2026, 10, 3, 18, 0, 0).unwrap(),
+                60,
+                false,
+            ),
+        ] {
+            sqlx::query("INSERT INTO training_session (id, cid, instructor_cid, position, start, end, notes, cancelled, created_date, updated_date) VALUES (NULL, 2, 1, 'DEN_APP', $1, $2, '', $3, $1, $1)")
+                .bind(start)
+                .bind(start + chrono::Duration::minutes(minutes))
+                .bind(cancelled)
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(credit_training_sessions(&db, now).await.unwrap(), 1);
+        assert_eq!(credit_training_sessions(&db, now).await.unwrap(), 0);
+        let credited: Vec<(u32, String, u32)> =
+            sqlx::query_as("SELECT cid, month, minutes FROM activity WHERE source='training'")
+                .fetch_all(&db)
+                .await
+                .unwrap();
+        assert_eq!(credited, vec![(1, "2026-09".to_owned(), 120)]);
+
+        // counts toward the instructor's requirement, but not the student's
+        sqlx::query(
+            "INSERT INTO activity (id, cid, month, minutes) VALUES (NULL, 1, '2026-08', 60)",
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        let quarter = Quarter::previous(now);
+        let added = compile_purge_candidates(&db, &quarter, now).await.unwrap();
+        assert_eq!(added, vec![2]);
+
+        // cancelled after the fact
+        sqlx::query("UPDATE training_session SET cancelled=TRUE")
+            .execute(&db)
+            .await
+            .unwrap();
+        credit_training_sessions(&db, now).await.unwrap();
+        let remaining: u32 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM activity WHERE source='training'")
+                .fetch_one(&db)
+                .await
+                .unwrap();
+        assert_eq!(remaining, 0);
+    }
+}
@@ -1,9 +1,13 @@
 use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, NaiveTime, Utc};
 use serde::Serialize;
 
+/// Mean radius of the Earth in nautical miles.
+const EARTH_RADIUS_NM: f64 = 3_440.065;
+
 /// Derived weather conditions.
 #[allow(clippy::upper_case_acronyms)]
-#[derive(Serialize, Debug, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
 pub enum WeatherConditions {
     VFR,
     MVFR,
@@ -12,18 +16,251 @@ pub enum WeatherConditions {
 }
 
 /// Parsed weather information for an airport.
-#[derive(Serialize)]
-pub struct AirportWeather<'a> {
-    pub name: &'a str,
+#[derive(Serialize, Clone)]
+pub struct AirportWeather {
+    pub name: String,
     pub conditions: WeatherConditions,
     pub visibility: u16,
     pub ceiling: u16,
-    pub raw: &'a str,
+    pub remarks: MetarRemarks,
+    pub raw: String,
+}
+
+/// Commonly-used groups decoded from a METAR's remarks.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct MetarRemarks {
+    /// In hectopascals.
+    pub sea_level_pressure: Option<f32>,
+    pub peak_wind: Option<PeakWind>,
+    pub precipitation: Vec<PrecipitationChange>,
+    pub variable_visibility: Option<VariableVisibility>,
+}
+
+/// Highest wind speed since the last METAR.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PeakWind {
+    pub direction: u16,
+    /// In knots.
+    pub speed: u16,
+    /// Like ":11" for minutes past the hour or "0211Z".
+    pub time: String,
+}
+
+/// Precipitation or thunderstorm beginning or ending since the last METAR.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PrecipitationChange {
+    /// Like "FZRA".
+    pub code: String,
+    /// Like "freezing rain".
+    pub description: String,
+    pub began: bool,
+    /// Like ":11" for minutes past the hour or "0211Z".
+    pub time: String,
+}
+
+/// Prevailing visibility varying between two values, in statute miles.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct VariableVisibility {
+    /// Like "1 1/2".
+    pub minimum: String,
+    pub maximum: String,
+}
+
+/// Weather descriptors and phenomena that appear in precipitation remarks.
+const WEATHER_CODES: [(&str, &str); 17] = [
+    ("MI", "shallow"),
+    ("PR", "partial"),
+    ("BC", "patchy"),
+    ("DR", "low drifting"),
+    ("BL", "blowing"),
+    ("FZ", "freezing"),
+    ("SH", "showers"),
+    ("TS", "thunderstorm"),
+    ("DZ", "drizzle"),
+    ("RA", "rain"),
+    ("SN", "snow"),
+    ("SG", "snow grains"),
+    ("IC", "ice crystals"),
+    ("PL", "ice pellets"),
+    ("GR", "hail"),
+    ("GS", "small hail"),
+    ("UP", "unknown precipitation"),
+];
+
+/// Format a remark time of either minutes past the hour or hours and minutes.
+fn remark_time(digits: &str) -> String {
+    if digits.len() == 2 {
+        format!(":{digits}")
+    } else {
+        format!("{digits}Z")
+    }
+}
+
+/// Describe a weather code like "SHRA" in words.
+fn describe_weather(code: &str) -> String {
+    let words: Vec<&str> = code
+        .as_bytes()
+        .chunks(2)
+        .filter_map(|chunk| {
+            WEATHER_CODES
+                .iter()
+                .find(|(c, _)| c.as_bytes() == chunk)
+                .map(|(_, word)| *word)
+        })
+        .collect();
+    match words.as_slice() {
+        ["showers", rest @ ..] if !rest.is_empty() => format!("{} showers", rest.join(" ")),
+        ["thunderstorm", rest @ ..] if !rest.is_empty() => {
+            format!("thunderstorm with {}", rest.join(" "))
+        }
+        _ => words.join(" "),
+    }
+}
+
+/// Parse a precipitation remark like "RAB15E30" or "RAE0455SNB0455".
+///
+/// Returns `None` if the group isn't one.
+fn parse_precipitation(group: &str) -> Option<Vec<PrecipitationChange>> {
+    let bytes = group.as_bytes();
+    let mut changes = Vec::new();
+    let mut code = String::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let next_is_digit = bytes.get(i + 1).is_some_and(u8::is_ascii_digit);
+        if (bytes[i] == b'B' || bytes[i] == b'E') && next_is_digit && !code.is_empty() {
+            let digits = bytes[i + 1..]
+                .iter()
+                .take_while(|b| b.is_ascii_digit())
+                .count();
+            if digits != 2 && digits != 4 {
+                return None;
+            }
+            changes.push(PrecipitationChange {
+                description: describe_weather(&code),
+                code: code.clone(),
+                began: bytes[i] == b'B',
+                time: remark_time(&group[i + 1..i + 1 + digits]),
+            });
+            i += 1 + digits;
+        } else {
+            let chunk = bytes.get(i..i + 2)?;
+            if !WEATHER_CODES.iter().any(|(c, _)| c.as_bytes() == chunk) {
+                return None;
+            }
+            // a new weather code after a time starts the next phenomenon
+            if i > 0 && bytes[i - 1].is_ascii_digit() {
+                code.clear();
+            }
+            code.push_str(&group[i..i + 2]);
+            i += 2;
+        }
+    }
+    if changes.is_empty() {
+        None
+    } else {
+        Some(changes)
+    }
+}
+
+/// Decode the supported groups from the METAR's remarks, ignoring the rest.
+fn parse_remarks(parts: &[&str]) -> MetarRemarks {
+    let mut remarks = MetarRemarks::default();
+    let mut i = 0;
+    while i < parts.len() {
+        let part = parts[i];
+        if let Some(value) = part.strip_prefix("SLP") {
+            if let (3, Ok(tenths)) = (value.len(), value.parse::<u16>()) {
+                let base = if tenths < 500 { 1_000.0 } else { 900.0 };
+                remarks.sea_level_pressure = Some(base + f32::from(tenths) / 10.0);
+            }
+        } else if part == "PK" && parts.get(i + 1) == Some(&"WND") {
+            if let Some((wind, time)) = parts.get(i + 2).and_then(|p| p.split_once('/')) {
+                if let (Ok(direction), Ok(speed), true) = (
+                    wind.get(..3).unwrap_or_default().parse(),
+                    wind.get(3..).unwrap_or_default().parse(),
+                    (time.len() == 2 || time.len() == 4)
+                        && time.chars().all(|c| c.is_ascii_digit()),
+                ) {
+                    remarks.peak_wind = Some(PeakWind {
+                        direction,
+                        speed,
+                        time: remark_time(time),
+                    });
+                }
+                i += 2;
+            }
+        } else if part == "VIS" {
+            let mut value = parts.get(i + 1).copied().unwrap_or_default().to_owned();
+            let mut consumed = 1;
+            // whole miles are a separate group from the fraction, as in "VIS 1 1/2V3"
+            if !value.contains('V') && value.chars().all(|c| c.is_ascii_digit()) {
+                if let Some(next) = parts.get(i + 2) {
+                    value = format!("{value} {next}");
+                    consumed = 2;
+                }
+            }
+            if let Some((minimum, maximum)) = value.split_once('V') {
+                let is_distance = |s: &str| {
+                    !s.is_empty()
+                        && s.chars()
+                            .all(|c| c.is_ascii_digit() || c == '/' || c == ' ')
+                };
+                if is_distance(minimum) && is_distance(maximum) {
+                    remarks.variable_visibility = Some(VariableVisibility {
+                        minimum: minimum.to_owned(),
+                        maximum: maximum.to_owned(),
+                    });
+                    i += consumed;
+                }
+            }
+        } else if let Some(changes) = parse_precipitation(part) {
+            remarks.precipitation.extend(changes);
+        }
+        i += 1;
+    }
+    remarks
+}
+
+/// Great-circle distance in nautical miles between two latitude and longitude pairs.
+pub fn distance_nm(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat1, lat2) = (from.0.to_radians(), to.0.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (to.1 - from.1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_NM * a.sqrt().asin()
+}
+
+/// Parse a flight plan duration like "0145" for an hour and 45 minutes.
+pub fn parse_filed_duration(hhmm: &str) -> Option<Duration> {
+    if hhmm.len() != 4 || !hhmm.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i64 = hhmm[..2].parse().ok()?;
+    let minutes: i64 = hhmm[2..].parse().ok()?;
+    Some(Duration::hours(hours) + Duration::minutes(minutes))
+}
+
+/// Parse a flight plan's proposed departure time like "2330" into the nearest
+/// such time to now, since plans don't include a date.
+pub fn parse_filed_departure(hhmm: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let time = NaiveTime::parse_from_str(hhmm, "%H%M").ok()?;
+    let today = now.date_naive().and_time(time).and_utc();
+    [today - Duration::days(1), today, today + Duration::days(1)]
+        .into_iter()
+        .min_by_key(|time| (*time - now).num_seconds().abs())
 }
 
 /// Parse a METAR into a struct of data.
 pub fn parse_metar(line: &str) -> Result<AirportWeather> {
-    let parts: Vec<_> = line.split(' ').collect();
+    let mut parts: Vec<_> = line.split(' ').collect();
+    let remarks = match parts.iter().position(|part| *part == "RMK") {
+        Some(index) => {
+            let remarks = parse_remarks(&parts[index + 1..]);
+            parts.truncate(index);
+            remarks
+        }
+        None => MetarRemarks::default(),
+    };
     let airport = parts.first().ok_or_else(|| anyhow!("Blank metar?"))?;
     let mut ceiling = 3_456;
     for part in &parts {
@@ -63,17 +300,22 @@ pub fn parse_metar(line: &str) -> Result<AirportWeather> {
     };
 
     Ok(AirportWeather {
-        name: airport,
+        name: airport.to_string(),
         conditions,
         visibility,
         ceiling,
-        raw: line,
+        remarks,
+        raw: line.to_owned(),
     })
 }
 
 #[cfg(test)]
 pub mod tests {
-    use super::{parse_metar, WeatherConditions};
+    use super::{
+        distance_nm, parse_filed_departure, parse_filed_duration, parse_metar, parse_remarks,
+        MetarRemarks, PeakWind, PrecipitationChange, VariableVisibility, WeatherConditions,
+    };
+    use chrono::{Duration, TimeZone, Utc};
 
     #[test]
     fn test_parse_metar() {
@@ -90,4 +332,95 @@ pub mod tests {
         let ret = parse_metar("KDEN 1/2SM OVC001").unwrap();
         assert_eq!(ret.conditions, WeatherConditions::LIFR);
     }
+
+    #[test]
+    fn test_parse_remarks() {
+        let ret = parse_metar("KDEN 030253Z 22013KT 10SM SCT100 BKN160 13/M12 A2943 RMK AO2 PK WND 21036/0211 SLP924 T01331117 58005").unwrap();
+        assert_eq!(
+            ret.remarks,
+            MetarRemarks {
+                sea_level_pressure: Some(992.4),
+                peak_wind: Some(PeakWind {
+                    direction: 210,
+                    speed: 36,
+                    time: "0211Z".to_owned(),
+                }),
+                precipitation: Vec::new(),
+                variable_visibility: None,
+            }
+        );
+
+        let ret = parse_remarks(
+            &"AO2 VIS 1 1/2V3 FZRAB15E30SNB30 TSB05 SLP045 SLPNO"
+                .split(' ')
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(ret.sea_level_pressure, Some(1_004.5));
+        assert_eq!(
+            ret.variable_visibility,
+            Some(VariableVisibility {
+                minimum: "1 1/2".to_owned(),
+                maximum: "3".to_owned(),
+            })
+        );
+        assert_eq!(
+            ret.precipitation,
+            vec![
+                PrecipitationChange {
+                    code: "FZRA".to_owned(),
+                    description: "freezing rain".to_owned(),
+                    began: true,
+                    time: ":15".to_owned(),
+                },
+                PrecipitationChange {
+                    code: "FZRA".to_owned(),
+                    description: "freezing rain".to_owned(),
+                    began: false,
+                    time: ":30".to_owned(),
+                },
+                PrecipitationChange {
+                    code: "SN".to_owned(),
+                    description: "snow".to_owned(),
+                    began: true,
+                    time: ":30".to_owned(),
+                },
+                PrecipitationChange {
+                    code: "TS".to_owned(),
+                    description: "thunderstorm".to_owned(),
+                    began: true,
+                    time: ":05".to_owned(),
+                },
+            ]
+        );
+
+        // groups that only look like remarks are skipped
+        let ret = parse_remarks(&"AO2 VIS 2 RWY11 BINOVC".split(' ').collect::<Vec<_>>());
+        assert_eq!(ret, MetarRemarks::default());
+    }
+
+    #[test]
+    fn test_distance_nm() {
+        // KDEN to KCOS
+        let distance = distance_nm((39.8617, -104.6731), (38.8058, -104.7008));
+        assert!((distance - 63.4).abs() < 0.5, "{distance}");
+        assert_eq!(distance_nm((39.8617, -104.6731), (39.8617, -104.6731)), 0.0);
+    }
+
+    #[test]
+    fn test_filed_times() {
+        assert_eq!(parse_filed_duration("0145"), Some(Duration::minutes(105)));
+        assert_eq!(parse_filed_duration(""), None);
+        assert_eq!(parse_filed_duration("1:45"), None);
+
+        let now = Utc.with_ymd_and_hms(2026, 10, 17, 23, 30, 0).unwrap();
+        assert_eq!(
+            parse_filed_departure("2345", now),
+            Some(Utc.with_ymd_and_hms(2026, 10, 17, 23, 45, 0).unwrap())
+        );
+        assert_eq!(
+            parse_filed_departure("0015", now),
+            Some(Utc.with_ymd_and_hms(2026, 10, 18, 0, 15, 0).unwrap())
+        );
+        assert_eq!(parse_filed_departure("2500", now), None);
+    }
 }
@@ -0,0 +1,196 @@
+//! Which positions each certification authorizes controllers to work.
+//!
+//! The mapping is set in the config's `training.positions`, from certification
+//! name to position names. Position names can use `*` to match any characters,
+//! like "DEN_*_APP" for the split approach sectors.
+//!
+//! Positions that no certification covers fall back to the network's minimum
+//! rating for the position's type.
+
+use crate::{config::Config, sql::Certification, ControllerRating};
+
+/// Certification values that allow the controller to work the positions.
+pub const AUTHORIZING_VALUES: &[&str] = &["solo", "certified"];
+
+/// Whether the position name matches the pattern, ignoring case.
+pub fn pattern_matches(pattern: &str, position: &str) -> bool {
+    let pattern = pattern.to_uppercase();
+    let position = position.to_uppercase();
+    let mut parts = pattern.split('*');
+    // there's always a first part, even if empty
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = position.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // no wildcard
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Certifications that authorize the position, in config order.
+pub fn certifications_for<'a>(config: &'a Config, position: &str) -> Vec<&'a str> {
+    config
+        .training
+        .certifications
+        .iter()
+        .filter(|certification| {
+            config
+                .training
+                .positions
+                .get(*certification)
+                .is_some_and(|patterns| {
+                    patterns
+                        .iter()
+                        .any(|pattern| pattern_matches(pattern, position))
+                })
+        })
+        .map(|certification| certification.as_str())
+        .collect()
+}
+
+/// Whether the controller's certifications authorize them to work the position.
+///
+/// Returns `None` if no certification covers the position, so anyone can work it.
+pub fn is_authorized(config: &Config, certs: &[Certification], position: &str) -> Option<bool> {
+    let required = certifications_for(config, position);
+    if required.is_empty() {
+        return None;
+    }
+    Some(certs.iter().any(|cert| {
+        required.contains(&cert.name.as_str()) && AUTHORIZING_VALUES.contains(&cert.value.as_str())
+    }))
+}
+
+/// Lowest network rating that can work the position, by its type suffix.
+pub fn minimum_rating(position: &str) -> Option<ControllerRating> {
+    let suffix = position.rsplit('_').next().unwrap_or_default();
+    match suffix.to_uppercase().as_str() {
+        "DEL" | "GND" => Some(ControllerRating::S1),
+        "TWR" => Some(ControllerRating::S2),
+        "APP" | "DEP" => Some(ControllerRating::S3),
+        "CTR" | "FSS" => Some(ControllerRating::C1),
+        _ => None,
+    }
+}
+
+/// Why the controller can't work the position, if they can't.
+///
+/// A certification that covers the position decides, so solo certs allow
+/// working above the controller's rating; otherwise the rating must be high enough.
+pub fn ineligibility_reason(
+    config: &Config,
+    rating: i8,
+    certs: &[Certification],
+    position: &str,
+) -> Option<String> {
+    match is_authorized(config, certs, position) {
+        Some(true) => None,
+        Some(false) => Some(format!(
+            "{position} needs the {} certification",
+            certifications_for(config, position).join(" or ")
+        )),
+        None => minimum_rating(position)
+            .filter(|minimum| rating < minimum.as_id())
+            .map(|minimum| format!("{position} needs a rating of at least {}", minimum.as_str())),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{certifications_for, ineligibility_reason, is_authorized, pattern_matches};
+    use crate::{config::Config, sql::Certification};
+    use chrono::Utc;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_pattern_matches() {
+        assert!(pattern_matches("DEN_GND", "DEN_GND"));
+        assert!(pattern_matches("den_gnd", "DEN_GND"));
+        assert!(!pattern_matches("DEN_GND", "DEN_1_GND"));
+        assert!(pattern_matches("DEN_*_GND", "DEN_1_GND"));
+        assert!(!pattern_matches("DEN_*_GND", "DEN_GND"));
+        assert!(pattern_matches("DEN_*", "DEN_APP"));
+        assert!(pattern_matches("*_CTR", "DEN_CTR"));
+        assert!(pattern_matches("DEN*APP*", "DEN_N_APP_1"));
+        assert!(!pattern_matches("DEN_*_APP", "COS_N_APP"));
+    }
+
+    #[test]
+    fn test_is_authorized() {
+        let mut config = Config::default();
+        config.training.certifications = vec!["GND".to_owned(), "TWR".to_owned()];
+        config.training.positions = HashMap::from([
+            (
+                "GND".to_owned(),
+                vec!["DEN_GND".to_owned(), "DEN_RMP".to_owned()],
+            ),
+            (
+                "TWR".to_owned(),
+                vec!["DEN_TWR".to_owned(), "DEN_*_TWR".to_owned()],
+            ),
+        ]);
+        let cert = |name: &str, value: &str| Certification {
+            id: 0,
+            cid: 1,
+            name: name.to_owned(),
+            value: value.to_owned(),
+            changed_on: Utc::now(),
+            set_by: 0,
+        };
+
+        assert_eq!(certifications_for(&config, "DEN_E_TWR"), vec!["TWR"]);
+        assert_eq!(is_authorized(&config, &[], "DEN_APP"), None);
+        assert_eq!(
+            is_authorized(&config, &[cert("GND", "certified")], "DEN_RMP"),
+            Some(true)
+        );
+        assert_eq!(
+            is_authorized(&config, &[cert("TWR", "solo")], "DEN_TWR"),
+            Some(true)
+        );
+        assert_eq!(
+            is_authorized(&config, &[cert("TWR", "training")], "DEN_TWR"),
+            Some(false)
+        );
+        assert_eq!(
+            is_authorized(&config, &[cert("GND", "certified")], "DEN_TWR"),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn test_ineligibility_reason() {
+        let mut config = Config::default();
+        config.training.certifications = vec!["TWR".to_owned()];
+        config.training.positions = HashMap::from([("TWR".to_owned(), vec!["DEN_TWR".to_owned()])]);
+        let solo = Certification {
+            id: 0,
+            cid: 1,
+            name: "TWR".to_owned(),
+            value: "solo".to_owned(),
+            changed_on: Utc::now(),
+            set_by: 0,
+        };
+
+        assert_eq!(
+            ineligibility_reason(&config, 5, &[], "DEN_TWR").as_deref(),
+            Some("DEN_TWR needs the TWR certification")
+        );
+        assert_eq!(ineligibility_reason(&config, 2, &[solo], "DEN_TWR"), None);
+        assert_eq!(
+            ineligibility_reason(&config, 3, &[], "DEN_APP").as_deref(),
+            Some("DEN_APP needs a rating of at least S3")
+        );
+        assert_eq!(ineligibility_reason(&config, 4, &[], "DEN_N_APP"), None);
+        assert_eq!(ineligibility_reason(&config, 1, &[], "DEN_SUP"), None);
+    }
+}
@@ -0,0 +1,117 @@
+//! Setting controllers' certifications.
+//!
+//! The `certification` table holds each controller's current values, and every
+//! change is also recorded in `certification_history` so staff can see how a
+//! student progressed.
+
+use crate::sql::{self, Certification};
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+
+/// Set the controller's certification, recording the change in its history.
+///
+/// Returns whether the value changed; setting the same value again does nothing.
+pub async fn set_certification(
+    db: &SqlitePool,
+    cid: u32,
+    name: &str,
+    value: &str,
+    by_cid: u32,
+    now: DateTime<Utc>,
+) -> sqlx::Result<bool> {
+    let mut tx = db.begin().await?;
+    let current: Vec<Certification> = sqlx::query_as(sql::GET_ALL_CERTIFICATIONS_FOR)
+        .bind(cid)
+        .fetch_all(&mut *tx)
+        .await?;
+    let existing = current.into_iter().find(|cert| cert.name == name);
+    match &existing {
+        Some(existing) if existing.value == value => return Ok(false),
+        Some(existing) => {
+            sqlx::query(sql::UPDATE_CERTIFICATION)
+                .bind(existing.id)
+                .bind(value)
+                .bind(now)
+                .bind(by_cid)
+                .execute(&mut *tx)
+                .await?;
+        }
+        None => {
+            sqlx::query(sql::CREATE_CERTIFICATION)
+                .bind(cid)
+                .bind(name)
+                .bind(value)
+                .bind(now)
+                .bind(by_cid)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+    sqlx::query(sql::INSERT_CERTIFICATION_HISTORY)
+        .bind(cid)
+        .bind(name)
+        .bind(existing.map(|cert| cert.value))
+        .bind(value)
+        .bind(by_cid)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::set_certification;
+    use crate::{
+        db::run_migrations,
+        sql::{self, CertificationHistory},
+    };
+    use chrono::{Duration, TimeZone, Utc};
+    use sqlx::{sqlite::SqlitePoolOptions, Executor};
+
+    #[tokio::test]
+    async fn test_set_certification() {
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        db.execute(sql::CREATE_TABLES).await.unwrap();
+        run_migrations(&db).await.unwrap();
+        sqlx::query("INSERT INTO controller (cid, first_name, last_name, rating, is_on_roster) VALUES (1, '', '', 3, TRUE)")
+            .execute(&db)
+            .await
+            .unwrap();
+        let now = Utc.with_ymd_and_hms(2026, 10, 1, 0, 0, 0).unwrap();
+
+        assert!(set_certification(&db, 1, "GND", "training", 2, now)
+            .await
+            .unwrap());
+        assert!(!set_certification(&db, 1, "GND", "training", 3, now)
+            .await
+            .unwrap());
+        assert!(
+            set_certification(&db, 1, "GND", "solo", 2, now + Duration::days(7))
+                .await
+                .unwrap()
+        );
+
+        let value: String =
+            sqlx::query_scalar("SELECT value FROM certification WHERE cid=1 AND name='GND'")
+                .fetch_one(&db)
+                .await
+                .unwrap();
+        assert_eq!(value, "solo");
+        let history: Vec<CertificationHistory> = sqlx::query_as(sql::GET_CERTIFICATION_HISTORY_FOR)
+            .bind(1)
+            .fetch_all(&db)
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].old_value.as_deref(), Some("training"));
+        assert_eq!(history[0].new_value, "solo");
+        assert_eq!(history[1].old_value, None);
+        assert_eq!(history[1].changed_by, 2);
+    }
+}
@@ -0,0 +1,222 @@
+//! Periodic removal of data the site no longer needs.
+
+use crate::{
+    config::ConfigDatabaseCleanup,
+    sql::{self, Event},
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use sqlx::SqlitePool;
+use std::{collections::HashSet, fmt, path::Path, time::SystemTime};
+
+/// What a cleanup run removed.
+#[derive(Debug, Default)]
+pub struct CleanupReport {
+    pub sessions: u64,
+    pub remember_tokens: u64,
+    /// Names of the removed events.
+    pub events: Vec<String>,
+    /// Positions, registrations, channels, signup limits, and waitlists of events that no longer exist.
+    pub orphaned_event_rows: u64,
+    /// Names of the removed asset files.
+    pub assets: Vec<String>,
+}
+
+impl CleanupReport {
+    pub fn is_empty(&self) -> bool {
+        self.sessions == 0
+            && self.remember_tokens == 0
+            && self.events.is_empty()
+            && self.orphaned_event_rows == 0
+            && self.assets.is_empty()
+    }
+}
+
+impl fmt::Display for CleanupReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} expired sessions, {} expired remember tokens, {} orphaned event rows",
+            self.sessions, self.remember_tokens, self.orphaned_event_rows
+        )?;
+        if !self.events.is_empty() {
+            write!(f, "; events: {}", self.events.join(", "))?;
+        }
+        if !self.assets.is_empty() {
+            write!(f, "; files: {}", self.assets.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether the file name is from a resource upload, like "<uuid>_<original name>".
+///
+/// Anything else in the assets directory was put there by hand and is left alone.
+fn is_uploaded_file(name: &str) -> bool {
+    let Some((prefix, rest)) = name.split_at_checked(36) else {
+        return false;
+    };
+    rest.starts_with('_')
+        && prefix.char_indices().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
+/// Remove expired sessions, old events nobody signed up for, rows left behind by
+/// deleted events, and uploaded files that no resource uses.
+pub async fn remove_stale_data(
+    db: &SqlitePool,
+    config: &ConfigDatabaseCleanup,
+    assets_dir: &Path,
+    now: DateTime<Utc>,
+) -> Result<CleanupReport> {
+    let mut report = CleanupReport::default();
+
+    let (sessions_table,): (bool,) = sqlx::query_as(sql::SESSIONS_TABLE_EXISTS)
+        .fetch_one(db)
+        .await?;
+    if sessions_table {
+        report.sessions = sqlx::query(sql::DELETE_EXPIRED_SESSIONS)
+            .execute(db)
+            .await?
+            .rows_affected();
+    }
+    report.remember_tokens = sqlx::query(sql::DELETE_EXPIRED_REMEMBER_TOKENS)
+        .bind(now)
+        .execute(db)
+        .await?
+        .rows_affected();
+
+    let events: Vec<Event> = sqlx::query_as(sql::GET_STALE_EVENTS)
+        .bind(now - Duration::days(config.event_days.into()))
+        .fetch_all(db)
+        .await?;
+    for event in events {
+        let mut tx = db.begin().await?;
+        for statement in [
+            sql::DELETE_EVENT_CHANNELS_FOR,
+            sql::DELETE_EVENT_CATEGORY_LIMITS_FOR,
+            sql::DELETE_EVENT_WAITLIST_FOR,
+            sql::DELETE_EVENT_POSITIONS_FOR,
+            sql::DELETE_EVENT,
+        ] {
+            sqlx::query(statement)
+                .bind(event.id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        report.events.push(event.name);
+    }
+
+    // registrations reference positions, so they go first
+    for statement in [
+        sql::DELETE_ORPHANED_EVENT_REGISTRATIONS,
+        sql::DELETE_ORPHANED_EVENT_POSITIONS,
+        sql::DELETE_ORPHANED_EVENT_CHANNELS,
+        sql::DELETE_ORPHANED_EVENT_CATEGORY_LIMITS,
+        sql::DELETE_ORPHANED_EVENT_WAITLIST,
+    ] {
+        report.orphaned_event_rows += sqlx::query(statement).execute(db).await?.rows_affected();
+    }
+
+    if assets_dir.exists() {
+        let in_use: HashSet<String> = sqlx::query_scalar(sql::GET_RESOURCE_FILE_NAMES)
+            .fetch_all(db)
+            .await?
+            .into_iter()
+            .collect();
+        let cutoff = SystemTime::from(now - Duration::days(config.asset_days.into()));
+        for entry in std::fs::read_dir(assets_dir)
+            .with_context(|| format!("reading {}", assets_dir.display()))?
+        {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !is_uploaded_file(&name) || in_use.contains(&name) {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            if !metadata.is_file() || metadata.modified()? > cutoff {
+                continue;
+            }
+            std::fs::remove_file(entry.path())
+                .with_context(|| format!("removing {}", entry.path().display()))?;
+            report.assets.push(name);
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{is_uploaded_file, remove_stale_data};
+    use crate::{config::ConfigDatabaseCleanup, db::run_migrations, sql};
+    use chrono::{Duration, Utc};
+    use sqlx::{sqlite::SqlitePoolOptions, Executor};
+
+    #[test]
+    fn test_is_uploaded_file() {
+        assert!(is_uploaded_file(
+            "0b5ed5a4-3f0e-4b6a-9a4e-2b8f7d1c6e90_SOP.pdf"
+        ));
+        assert!(!is_uploaded_file("favicon.ico"));
+        assert!(!is_uploaded_file(
+            "not-a-uuid-at-all-but-36-characters_SOP.pdf"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_remove_stale_data() {
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        db.execute(sql::CREATE_TABLES).await.unwrap();
+        run_migrations(&db).await.unwrap();
+        let now = Utc::now();
+        sqlx::query("INSERT INTO controller (cid, first_name, last_name, rating, is_on_roster) VALUES (1, '', '', 1, TRUE)")
+            .execute(&db)
+            .await
+            .unwrap();
+        // old and empty, old with a signup, recent and empty
+        for (id, name, days_ago) in [(1, "Empty", 400), (2, "Popular", 400), (3, "Recent", 10)] {
+            sqlx::query("INSERT INTO event (id, created_by, published, name, start, end) VALUES ($1, 1, TRUE, $2, $3, $3)")
+                .bind(id)
+                .bind(name)
+                .bind(now - Duration::days(days_ago))
+                .execute(&db)
+                .await
+                .unwrap();
+            sqlx::query("INSERT INTO event_position VALUES (NULL, $1, 'DEN_APP', 'TRACON', NULL)")
+                .bind(id)
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+        sqlx::query("INSERT INTO event_registration (event_id, cid, choice_1) VALUES (2, 1, 2)")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let config = ConfigDatabaseCleanup {
+            enabled: true,
+            interval_hours: 24,
+            event_days: 365,
+            asset_days: 30,
+        };
+        let assets = std::env::temp_dir().join("vzdv_cleanup_test_assets");
+        let report = remove_stale_data(&db, &config, &assets, now).await.unwrap();
+        assert_eq!(report.events, vec!["Empty".to_string()]);
+        let remaining: Vec<u32> = sqlx::query_scalar("SELECT event_id FROM event_position")
+            .fetch_all(&db)
+            .await
+            .unwrap();
+        assert_eq!(remaining, vec![2, 3]);
+
+        let report = remove_stale_data(&db, &config, &assets, now).await.unwrap();
+        assert!(report.is_empty());
+    }
+}
@@ -1,10 +1,40 @@
 use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
-use std::{fs, path::Path};
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+use toml::{Table, Value};
 
 /// Default place to look for the config file.
 pub const DEFAULT_CONFIG_FILE_NAME: &str = "vzdv.toml";
 
+/// Environment variable naming the config overlay to use when the binary isn't
+/// given one, like "dev" for "vzdv.dev.toml".
+pub const ENVIRONMENT_ENV_VAR: &str = "VZDV_ENV";
+
+/// Secrets file looked for next to the config file, merged over it if present.
+pub const SECRETS_FILE_NAME: &str = "vzdv.secrets.toml";
+
+/// Environment variable with a path to the secrets file, to use instead of the default.
+pub const SECRETS_FILE_ENV_VAR: &str = "VZDV_SECRETS_FILE";
+
+/// Config keys that can also be set from the environment, which takes
+/// precedence over both files. The variable for `["email", "password"]`
+/// is `VZDV_EMAIL_PASSWORD`.
+pub const SECRET_KEYS: &[&[&str]] = &[
+    &["vatsim", "oauth_client_secret"],
+    &["vatsim", "vatusa_api_key"],
+    &["vatsim", "vatusa_webhook_secret"],
+    &["discord", "bot_token"],
+    &["discord", "auth", "client_secret"],
+    &["email", "user"],
+    &["email", "password"],
+    &["database", "backups", "upload_authorization"],
+    &["error_reporting", "dsn"],
+];
+
 /// App configuration.
 #[derive(Debug, Clone, Deserialize, Default)]
 pub struct Config {
@@ -17,12 +47,55 @@ pub struct Config {
     pub stats: ConfigStats,
     pub discord: ConfigDiscord,
     pub email: ConfigEmail,
+    pub error_reporting: ConfigErrorReporting,
+    pub tls: ConfigTls,
+    pub maintenance: ConfigMaintenance,
+    pub api: ConfigApi,
+    pub oauth_provider: ConfigOAuthProvider,
 }
 
 #[derive(Debug, Clone, Deserialize, Default)]
 pub struct ConfigDatabase {
     pub file: String,
-    pub resource_category_ordering: Vec<String>,
+    /// Connections each binary keeps open; 0 for the default of 10.
+    pub pool_size: u32,
+    /// How long a connection waits on another process's write lock before
+    /// giving up with "database is locked"; 0 for the default of 5.
+    pub busy_timeout_seconds: u64,
+    /// Page cache per connection; 0 to leave SQLite's default of about 2 MiB.
+    pub cache_size_kib: u32,
+    pub backups: ConfigDatabaseBackups,
+    pub cleanup: ConfigDatabaseCleanup,
+    pub integrity: ConfigDatabaseIntegrity,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ConfigDatabaseBackups {
+    /// Directory to write backups to; leave empty to disable backups.
+    pub directory: String,
+    pub interval_hours: u64,
+    /// Number of backups to keep in the directory.
+    pub retain: usize,
+    /// Optional URL prefix to PUT each backup file to, like an object storage bucket.
+    pub upload_url: String,
+    /// Optional "Authorization" header value for the upload.
+    pub upload_authorization: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ConfigDatabaseCleanup {
+    pub enabled: bool,
+    pub interval_hours: u64,
+    /// Events that ended more than this many days ago with nobody signed up are removed.
+    pub event_days: u32,
+    /// Uploaded files no resource uses are removed once they're this many days old.
+    pub asset_days: u32,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ConfigDatabaseIntegrity {
+    pub enabled: bool,
+    pub interval_hours: u64,
 }
 
 #[derive(Debug, Clone, Deserialize, Default)]
@@ -37,17 +110,39 @@ pub struct ConfigVatsim {
     pub oauth_client_secret: String,
     pub oauth_client_callback_url: String,
     pub vatusa_api_key: String,
+    /// Shared secret VATUSA signs webhook bodies with; webhooks are refused if empty.
+    pub vatusa_webhook_secret: String,
 }
 
 #[derive(Debug, Clone, Deserialize, Default)]
 pub struct ConfigTraining {
     pub certifications: Vec<String>,
+    /// Positions each certification authorizes, from `cert_positions`.
+    pub positions: HashMap<String, Vec<String>>,
+    pub waitlist: ConfigTrainingWaitlist,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ConfigTrainingWaitlist {
+    /// Students that can be in training at once, per certification; those not
+    /// listed have no waitlist.
+    pub capacity: HashMap<String, u32>,
+    /// Order to compare waiting students by, from `waitlist::priority`; ties go
+    /// to whoever joined the waitlist first.
+    pub priority: Vec<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Default)]
 pub struct ConfigAirports {
     pub all: Vec<Airport>,
-    pub weather_for: Vec<String>,
+    /// Groups of airports to show the weather for on the homepage, in order.
+    pub weather_groups: Vec<WeatherGroup>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct WeatherGroup {
+    pub name: String,
+    pub airports: Vec<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
@@ -57,6 +152,9 @@ pub struct Airport {
     pub location: String,
     pub towered: bool,
     pub class: String,
+    /// Used for live arrival ETAs; filed times are used without it.
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
 }
 
 #[derive(Debug, Clone, Deserialize, Default)]
@@ -74,9 +172,26 @@ pub struct ConfigDiscord {
     pub online_channel: u64,
     pub online_message: Option<u64>,
     pub off_roster_channel: u64,
+    pub event_channel_category: u64,
+    pub next_event_channel: u64,
+    pub weekly_summary_channel: u64,
+    pub audit_channel: u64,
     pub webhooks: ConfigDiscordWebhooks,
     pub roles: ConfigDiscordRoles,
+    pub tasks: ConfigDiscordTasks,
     pub owner_id: u64,
+    pub shards: u64,
+    pub staffing_request_tiers: Vec<ConfigStaffingRequestTier>,
+}
+
+/// Role to ping for staffing requests that are large or soon enough.
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ConfigStaffingRequestTier {
+    /// Requests with at least this many pilots ping the role.
+    pub min_pilots: u16,
+    /// Requests starting within this many hours ping the role regardless of size; 0 to ignore.
+    pub urgent_within_hours: u32,
+    pub role: u64,
 }
 
 #[derive(Debug, Clone, Deserialize, Default)]
@@ -94,6 +209,19 @@ pub struct ConfigDiscordWebhooks {
     pub errors: String,
 }
 
+/// Switches and intervals for the bot's background tasks.
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ConfigDiscordTasks {
+    pub online: bool,
+    pub online_interval_seconds: u64,
+    pub roles: bool,
+    pub roles_interval_seconds: u64,
+    /// Set nicknames as part of the roles sync.
+    pub nicknames: bool,
+    pub off_roster: bool,
+    pub off_roster_interval_seconds: u64,
+}
+
 #[derive(Debug, Clone, Deserialize, Default)]
 pub struct ConfigDiscordRoles {
     // status
@@ -144,16 +272,269 @@ pub struct ConfigEmail {
     pub visitor_accepted_template: ConfigEmailTemplate,
     pub visitor_denied_template: ConfigEmailTemplate,
     pub visitor_removed_template: ConfigEmailTemplate,
+    /// Sent to controllers when they're added to the roster.
+    pub welcome_template: ConfigEmailTemplate,
+    /// Sent to controllers after they leave the roster; `survey_link` is available.
+    pub exit_survey_template: ConfigEmailTemplate,
+    /// Sent to controllers who opted in when staff approve positive feedback for them;
+    /// `position`, `rating`, and `comments` are available.
+    pub feedback_forward_template: ConfigEmailTemplate,
+    /// Sent to controllers removed for inactivity when staff approve a purge;
+    /// `quarter` and `minutes` are available.
+    pub purge_notice_template: ConfigEmailTemplate,
+    /// Sent to students promoted off a training waitlist; `certification` is available.
+    pub waitlist_promoted_template: ConfigEmailTemplate,
+    /// Sent to controllers assigned to an event position off its waitlist;
+    /// `event`, `position`, and `start` are available.
+    pub event_waitlist_promoted_template: ConfigEmailTemplate,
+    /// Sent to students after a mentor files a training record, asking them to rate the
+    /// session; `mentor`, `position`, and `link` are available.
+    pub training_feedback_request_template: ConfigEmailTemplate,
+    /// Sent to the student and mentor, with a calendar invite, when a training session
+    /// is booked; `student`, `mentor`, `position`, and `start` are available.
+    pub training_booked_template: ConfigEmailTemplate,
+    /// Sent to the student and mentor, with a calendar cancellation, when a training
+    /// session is cancelled; the same values as the booked template are available.
+    pub training_cancelled_template: ConfigEmailTemplate,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ConfigErrorReporting {
+    /// Sentry-compatible DSN; reporting is disabled if empty.
+    pub dsn: String,
+    pub environment: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ConfigTls {
+    /// PEM certificate chain; the site serves plain HTTP if empty.
+    pub cert_file: String,
+    /// PEM private key.
+    pub key_file: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ConfigMaintenance {
+    /// Show the maintenance page to non-staff users regardless of the admin toggle.
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ConfigApi {
+    /// Keys for other services to send as `Authorization: Bearer <key>`.
+    pub keys: Vec<ConfigApiKey>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ConfigApiKey {
+    /// Who the key was issued to, for logging.
+    pub name: String,
+    pub key: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ConfigOAuthProvider {
+    /// Facility tools allowed to sign users in with their vzdv account.
+    pub clients: Vec<ConfigOAuthClient>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ConfigOAuthClient {
+    /// Shown to the user when asked to approve the sign in.
+    pub name: String,
+    pub client_id: String,
+    /// Also the key the client's ID tokens are signed with.
+    pub client_secret: String,
+    /// Exact URLs the user can be sent back to.
+    pub redirect_uris: Vec<String>,
+}
+
+/// Recursively copy the values in `overlay` over those in `base`.
+fn merge_tables(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base_table)), Value::Table(overlay_table)) => {
+                merge_tables(base_table, overlay_table);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Set the `SECRET_KEYS` that `lookup` has a value for.
+fn apply_secret_env(table: &mut Table, lookup: impl Fn(&str) -> Option<String>) {
+    for path in SECRET_KEYS {
+        let var = format!("VZDV_{}", path.join("_").to_uppercase());
+        let Some(value) = lookup(&var) else {
+            continue;
+        };
+        let (key, parents) = path.split_last().unwrap();
+        let mut current = &mut *table;
+        for parent in parents {
+            let entry = current
+                .entry(parent.to_string())
+                .or_insert_with(|| Value::Table(Table::new()));
+            if !entry.is_table() {
+                *entry = Value::Table(Table::new());
+            }
+            current = entry.as_table_mut().unwrap();
+        }
+        current.insert(key.to_string(), Value::String(value));
+    }
+}
+
+/// The overlay file for the environment, next to the config file: "vzdv.dev.toml"
+/// for the "dev" environment of "vzdv.toml".
+pub fn overlay_path(path: &Path, environment: &str) -> Result<PathBuf> {
+    if environment.is_empty()
+        || !environment
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        bail!("Invalid config environment \"{environment}\"");
+    }
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    Ok(path.with_file_name(format!("{stem}.{environment}.toml")))
 }
 
 impl Config {
     /// Read the TOML file at the given path and load into the app's configuration file.
-    pub fn load_from_disk(path: &Path) -> Result<Self> {
+    ///
+    /// If an environment is given, or set in `VZDV_ENV`, its overlay file is
+    /// merged over the file. Values from the secrets file and the environment
+    /// variables are merged over those, so neither file needs them.
+    pub fn load_from_disk(path: &Path, environment: Option<&str>) -> Result<Self> {
         if !Path::new(path).exists() {
             bail!("Config file \"{}\" not found", path.display());
         }
         let text = fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&text)?;
-        Ok(config)
+        let environment = match environment {
+            Some(environment) => Some(environment.to_owned()),
+            None => env::var(ENVIRONMENT_ENV_VAR).ok().filter(|e| !e.is_empty()),
+        };
+        let overlay = match environment {
+            Some(environment) => {
+                let overlay_path = overlay_path(path, &environment)?;
+                if !overlay_path.exists() {
+                    bail!(
+                        "Config overlay \"{}\" for environment \"{environment}\" not found",
+                        overlay_path.display()
+                    );
+                }
+                Some(fs::read_to_string(overlay_path)?)
+            }
+            None => None,
+        };
+        let secrets_path = match env::var(SECRETS_FILE_ENV_VAR) {
+            Ok(secrets_path) => {
+                let secrets_path = Path::new(&secrets_path).to_owned();
+                if !secrets_path.exists() {
+                    bail!("Secrets file \"{}\" not found", secrets_path.display());
+                }
+                Some(secrets_path)
+            }
+            Err(_) => Some(path.with_file_name(SECRETS_FILE_NAME)).filter(|p| p.exists()),
+        };
+        let secrets = match secrets_path {
+            Some(secrets_path) => Some(fs::read_to_string(secrets_path)?),
+            None => None,
+        };
+        Self::from_sources(&text, overlay.as_deref(), secrets.as_deref(), |var| {
+            env::var(var).ok()
+        })
+    }
+
+    /// Parse the config from the main file's contents, the overlay's contents,
+    /// the secrets file's contents, and the environment, in increasing precedence.
+    fn from_sources(
+        text: &str,
+        overlay: Option<&str>,
+        secrets: Option<&str>,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self> {
+        let mut table: Table = toml::from_str(text)?;
+        if let Some(overlay) = overlay {
+            merge_tables(&mut table, toml::from_str(overlay)?);
+        }
+        if let Some(secrets) = secrets {
+            merge_tables(&mut table, toml::from_str(secrets)?);
+        }
+        apply_secret_env(&mut table, lookup);
+        Ok(table.try_into()?)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{overlay_path, Config};
+    use std::path::Path;
+
+    #[test]
+    fn test_overlay() {
+        assert_eq!(
+            overlay_path(Path::new("/etc/vzdv/vzdv.toml"), "dev").unwrap(),
+            Path::new("/etc/vzdv/vzdv.dev.toml")
+        );
+        assert!(overlay_path(Path::new("vzdv.toml"), "../prod").is_err());
+        assert!(overlay_path(Path::new("vzdv.toml"), "").is_err());
+
+        let text = include_str!("../../vzdv.example.toml");
+        let overlay = "
+[database]
+file = \"./scratch.sqlite\"
+
+[discord.webhooks]
+feedback = \"https://discord.com/api/webhooks/test\"
+";
+        let secrets = "[database]\nfile = \"./secret.sqlite\"";
+        let config = Config::from_sources(text, Some(overlay), None, |_| None).unwrap();
+        assert_eq!(config.database.file, "./scratch.sqlite");
+        assert_eq!(
+            config.discord.webhooks.feedback,
+            "https://discord.com/api/webhooks/test"
+        );
+        assert_eq!(config.database.backups.retain, 7);
+        // secrets still win over the overlay
+        let config = Config::from_sources(text, Some(overlay), Some(secrets), |_| None).unwrap();
+        assert_eq!(config.database.file, "./secret.sqlite");
+    }
+
+    #[test]
+    fn test_from_sources() {
+        let text = include_str!("../../vzdv.example.toml");
+        let secrets = "
+[vatsim]
+vatusa_api_key = \"from the file\"
+
+[email]
+password = \"file password\"
+";
+        let config = Config::from_sources(text, None, Some(secrets), |var| match var {
+            "VZDV_EMAIL_PASSWORD" => Some("env password".to_owned()),
+            "VZDV_DISCORD_AUTH_CLIENT_SECRET" => Some("discord secret".to_owned()),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(config.vatsim.vatusa_api_key, "from the file");
+        assert_eq!(config.email.password, "env password");
+        assert_eq!(config.discord.auth.client_secret, "discord secret");
+        // the rest of the tables are left alone
+        assert_eq!(config.vatsim.oauth_client_id, "225");
+        assert_eq!(config.discord.auth.client_id, "");
+
+        // secrets can be left out of the main file entirely
+        let text = text.replace("bot_token = \"\"\n", "");
+        assert!(Config::from_sources(&text, None, None, |_| None).is_err());
+        let config =
+            Config::from_sources(&text, None, Some("[discord]\nbot_token = \"abc\""), |_| {
+                None
+            })
+            .unwrap();
+        assert_eq!(config.discord.bot_token, "abc");
     }
 }
@@ -1,28 +1,203 @@
-use crate::{config::Config, sql};
+use crate::{
+    config::{Config, ConfigDatabase},
+    sql,
+};
 use anyhow::Result;
-use log::warn;
+use log::{info, warn};
 use sqlx::{
-    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqliteSynchronous},
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous},
     Executor, SqlitePool,
 };
-use std::path::Path;
+use std::{path::Path, time::Duration};
 
-/// Connect to the SQLite file at the destination, if it exists. If it does
-/// not, a new file is created and statements to create tables are executed.
-pub async fn load_db(config: &Config) -> Result<SqlitePool> {
-    let options = SqliteConnectOptions::new()
-        .filename(&config.database.file)
+/// Connection options for the file, with the tuning from the config applied.
+fn connect_options(config: &ConfigDatabase) -> SqliteConnectOptions {
+    let mut options = SqliteConnectOptions::new()
+        .filename(&config.file)
         .journal_mode(SqliteJournalMode::Wal)
         .synchronous(SqliteSynchronous::Normal)
         .foreign_keys(true);
+    if config.busy_timeout_seconds > 0 {
+        options = options.busy_timeout(Duration::from_secs(config.busy_timeout_seconds));
+    }
+    if config.cache_size_kib > 0 {
+        // negative values are in KiB rather than pages
+        options = options.pragma("cache_size", format!("-{}", config.cache_size_kib));
+    }
+    options
+}
+
+fn pool_options(config: &ConfigDatabase) -> SqlitePoolOptions {
+    let options = SqlitePoolOptions::new();
+    if config.pool_size > 0 {
+        options.max_connections(config.pool_size)
+    } else {
+        options
+    }
+}
+
+/// Connect to the SQLite file at the destination, if it exists. If it does
+/// not, a new file is created and statements to create tables are executed.
+///
+/// Any pending migrations are then applied.
+pub async fn load_db(config: &Config) -> Result<SqlitePool> {
+    let options = connect_options(&config.database);
+    let pool_options = pool_options(&config.database);
     let pool = if !Path::new(&config.database.file).exists() {
         warn!("Creating new database file");
         let options = options.create_if_missing(true);
-        let pool = SqlitePool::connect_with(options).await?;
+        let pool = pool_options.connect_with(options).await?;
         pool.execute(sql::CREATE_TABLES).await?;
         pool
     } else {
-        SqlitePool::connect_with(options).await?
+        pool_options.connect_with(options).await?
     };
+    run_migrations(&pool).await?;
     Ok(pool)
 }
+
+/// Apply any migrations from `sql::MIGRATIONS` that the DB hasn't seen yet.
+///
+/// Each migration is applied in its own transaction along with the bump
+/// to the `user_version` pragma, so a failure leaves the DB at the last
+/// successful migration.
+pub async fn run_migrations(pool: &SqlitePool) -> Result<()> {
+    let (version,): (i64,) = sqlx::query_as("PRAGMA user_version")
+        .fetch_one(pool)
+        .await?;
+    for (index, migration) in sql::MIGRATIONS
+        .iter()
+        .enumerate()
+        .skip(version.max(0) as usize)
+    {
+        let new_version = index + 1;
+        info!("Applying database migration {new_version}");
+        let mut tx = pool.begin().await?;
+        tx.execute(*migration).await?;
+        tx.execute(format!("PRAGMA user_version = {new_version}").as_str())
+            .await?;
+        tx.commit().await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{load_db, run_migrations};
+    use crate::{config::Config, sql};
+    use itertools::Itertools;
+    use sqlx::{sqlite::SqlitePoolOptions, Executor};
+
+    #[tokio::test]
+    async fn test_run_migrations() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        pool.execute(sql::CREATE_TABLES).await.unwrap();
+
+        run_migrations(&pool).await.unwrap();
+        // running again is a no-op
+        run_migrations(&pool).await.unwrap();
+
+        let (version,): (i64,) = sqlx::query_as("PRAGMA user_version")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(version as usize, sql::MIGRATIONS.len());
+    }
+
+    #[tokio::test]
+    async fn test_load_db_tuning() {
+        let file = std::env::temp_dir().join("vzdv_load_db_tuning_test.sqlite");
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", file.display()));
+        }
+        let mut config = Config::default();
+        config.database.file = file.display().to_string();
+        config.database.pool_size = 3;
+        config.database.busy_timeout_seconds = 30;
+        config.database.cache_size_kib = 8192;
+
+        let pool = load_db(&config).await.unwrap();
+        assert_eq!(pool.options().get_max_connections(), 3);
+        let (busy_timeout,): (i64,) = sqlx::query_as("PRAGMA busy_timeout")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(busy_timeout, 30_000);
+        let (cache_size,): (i64,) = sqlx::query_as("PRAGMA cache_size")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(cache_size, -8192);
+        pool.close().await;
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[tokio::test]
+    async fn test_activity_month_summary() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        pool.execute(sql::CREATE_TABLES).await.unwrap();
+        // activity from before the summary table is backfilled
+        pool.execute(sql::MIGRATIONS[..37].join("").as_str())
+            .await
+            .unwrap();
+        pool.execute("PRAGMA user_version = 37").await.unwrap();
+        pool.execute("INSERT INTO controller (cid, first_name, last_name, rating, is_on_roster) VALUES (1, '', '', 5, TRUE), (2, '', '', 5, TRUE)")
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO activity (id, cid, month, minutes) VALUES (NULL, 1, '2026-09', 90)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        run_migrations(&pool).await.unwrap();
+
+        for (cid, month, minutes, source) in [
+            (1, "2026-09", 30, "controlling"),
+            (1, "2026-09", 120, "event"),
+            (1, "2026-10", 60, "training"),
+            (2, "2026-10", 45, "controlling"),
+        ] {
+            sqlx::query("INSERT INTO activity (id, cid, month, minutes, source) VALUES (NULL, $1, $2, $3, $4)")
+                .bind(cid)
+                .bind(month)
+                .bind(minutes)
+                .bind(source)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        sqlx::query("UPDATE activity SET minutes=50 WHERE cid=2")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM activity WHERE source='training'")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let summaries: Vec<sql::ActivityMonthSummary> =
+            sqlx::query_as(sql::GET_ACTIVITY_SUMMARY_SINCE)
+                .bind("2026-01")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        let totals: Vec<_> = summaries
+            .iter()
+            .map(|s| (s.cid, s.month.as_str(), s.controlling, s.event, s.training))
+            .sorted()
+            .collect();
+        assert_eq!(
+            totals,
+            [(1, "2026-09", 120, 120, 0), (2, "2026-10", 50, 0, 0)]
+        );
+    }
+}
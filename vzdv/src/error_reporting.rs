@@ -0,0 +1,254 @@
+//! Reporting errors to the Discord errors webhook and, optionally, to a
+//! Sentry-compatible error tracker.
+//!
+//! When no DSN is configured, the tracker isn't initialized and capturing
+//! errors does nothing. Webhook posts are throttled so that repeats of the
+//! same error don't flood the channel.
+
+use crate::{config::Config, GENERAL_HTTP_CLIENT};
+use log::{error, info};
+use sentry::{ClientInitGuard, ClientOptions, Hub, Level, SentryFutureExt, User};
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+/// How long repeats of an error are held back after it's posted to the webhook.
+const WEBHOOK_SUPPRESSION_WINDOW: Duration = Duration::from_secs(60 * 10);
+
+/// Throttle shared by everything in the process posting to the errors webhook.
+static WEBHOOK_THROTTLE: LazyLock<WebhookThrottle> =
+    LazyLock::new(|| WebhookThrottle::new(WEBHOOK_SUPPRESSION_WINDOW));
+
+/// Set up the error tracker client, if configured.
+///
+/// The returned guard must be held for the life of the program, as
+/// dropping it flushes and disables the client.
+pub fn init(config: &Config, binary_name: &str) -> Option<ClientInitGuard> {
+    if config.error_reporting.dsn.is_empty() {
+        return None;
+    }
+    let guard = sentry::init((
+        config.error_reporting.dsn.as_str(),
+        ClientOptions {
+            release: sentry::release_name!(),
+            environment: Some(config.error_reporting.environment.clone().into()),
+            server_name: Some(binary_name.to_owned().into()),
+            ..Default::default()
+        },
+    ));
+    info!("Error reporting enabled");
+    Some(guard)
+}
+
+/// Report an error message, tagged with where it came from.
+///
+/// Any context set by `with_context` around the caller is included.
+pub fn capture_error(source: &str, message: &str) {
+    sentry::with_scope(
+        |scope| scope.set_tag("source", source),
+        || sentry::capture_message(message, Level::Error),
+    );
+}
+
+/// Wrap the future so that any errors captured within it have extra context attached.
+pub fn with_context<F: Future>(
+    tags: &[(&str, String)],
+    cid: Option<u32>,
+    future: F,
+) -> impl Future<Output = F::Output> {
+    let hub = Arc::new(Hub::new_from_top(Hub::current()));
+    hub.configure_scope(|scope| {
+        for (key, value) in tags {
+            scope.set_tag(key, value);
+        }
+        if let Some(cid) = cid {
+            scope.set_user(Some(User {
+                id: Some(cid.to_string()),
+                ..Default::default()
+            }));
+        }
+    });
+    future.bind_hub(hub)
+}
+
+/// Group messages that only differ in numbers and IDs, like CIDs and request IDs.
+fn fingerprint(message: &str) -> String {
+    message
+        .split_whitespace()
+        .map(|word| {
+            if word.chars().any(|c| c.is_ascii_digit()) {
+                "#"
+            } else {
+                word
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+struct ThrottleEntry {
+    /// Last time a message with this fingerprint was let through.
+    sent: Option<Instant>,
+    /// Most recent message with this fingerprint that was held back.
+    last_message: String,
+    suppressed: u32,
+}
+
+/// Suppresses repeats of the same error within a window, keeping count of them.
+pub struct WebhookThrottle {
+    window: Duration,
+    entries: Mutex<HashMap<String, ThrottleEntry>>,
+}
+
+impl WebhookThrottle {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record the message, returning what to post, if anything.
+    ///
+    /// The first occurrence is let through; repeats within the window are
+    /// held back and counted. The next one after the window notes how many
+    /// were held back.
+    pub fn check(&self, message: &str, now: Instant) -> Option<String> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries
+            .entry(fingerprint(message))
+            .or_insert_with(|| ThrottleEntry {
+                sent: None,
+                last_message: String::new(),
+                suppressed: 0,
+            });
+        if entry
+            .sent
+            .is_some_and(|sent| now.duration_since(sent) < self.window)
+        {
+            entry.suppressed += 1;
+            entry.last_message = message.to_owned();
+            return None;
+        }
+        let to_send = if entry.suppressed > 0 {
+            format!(
+                "{message}\n(seen {} more times since last reported)",
+                entry.suppressed
+            )
+        } else {
+            message.to_owned()
+        };
+        entry.sent = Some(now);
+        entry.suppressed = 0;
+        entry.last_message.clear();
+        Some(to_send)
+    }
+
+    /// Summaries for errors whose window has passed with repeats held back.
+    ///
+    /// Entries that have gone a full window without activity are dropped.
+    pub fn drain_expired(&self, now: Instant) -> Vec<String> {
+        let mut entries = self.entries.lock().unwrap();
+        let mut summaries = Vec::new();
+        entries.retain(|_, entry| {
+            if entry
+                .sent
+                .is_some_and(|sent| now.duration_since(sent) < self.window)
+            {
+                return true;
+            }
+            if entry.suppressed > 0 {
+                summaries.push(format!(
+                    "{}\n(seen {} times in the last {} minutes)",
+                    entry.last_message,
+                    entry.suppressed,
+                    self.window.as_secs() / 60
+                ));
+            }
+            false
+        });
+        summaries
+    }
+}
+
+/// Post the message to the Discord errors webhook, unless it's a recent repeat.
+pub async fn post_to_webhook(url: &str, message: &str) {
+    if url.is_empty() {
+        return;
+    }
+    if let Some(content) = WEBHOOK_THROTTLE.check(message, Instant::now()) {
+        send_webhook(url, &content).await;
+    }
+}
+
+/// Post summaries of any repeated errors that were held back.
+///
+/// Call periodically so that held-back repeats are reported even if the
+/// error doesn't happen again.
+pub async fn flush_webhook_summaries(url: &str) {
+    if url.is_empty() {
+        return;
+    }
+    for summary in WEBHOOK_THROTTLE.drain_expired(Instant::now()) {
+        send_webhook(url, &summary).await;
+    }
+}
+
+async fn send_webhook(url: &str, content: &str) {
+    let res = GENERAL_HTTP_CLIENT
+        .post(url)
+        .json(&json!({ "content": content }))
+        .send()
+        .await;
+    if let Err(e) = res {
+        error!("Could not send error to Discord webhook: {e}");
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::WebhookThrottle;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_webhook_throttle() {
+        let throttle = WebhookThrottle::new(Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert_eq!(
+            throttle.check("Error for 1234", start).as_deref(),
+            Some("Error for 1234")
+        );
+        assert!(throttle.check("Error for 5678", start).is_none());
+        assert!(throttle
+            .check("Error for 9", start + Duration::from_secs(30))
+            .is_none());
+        assert!(throttle.check("Other error", start).is_some());
+
+        let later = start + Duration::from_secs(61);
+        assert_eq!(
+            throttle.drain_expired(later),
+            vec!["Error for 9\n(seen 2 times in the last 1 minutes)".to_owned()]
+        );
+        assert!(throttle.drain_expired(later).is_empty());
+        assert!(throttle.check("Error for 1", later).is_some());
+        assert!(throttle.check("Error for 2", later).is_none());
+        assert_eq!(
+            throttle
+                .check("Error for 3", later + Duration::from_secs(60))
+                .as_deref(),
+            Some("Error for 3\n(seen 1 more times since last reported)")
+        );
+
+        assert!(throttle
+            .check("Failed (request 3fa9c0d2e1b7)", start)
+            .is_some());
+        assert!(throttle
+            .check("Failed (request 91b0aa7c44d2)", start)
+            .is_none());
+    }
+}
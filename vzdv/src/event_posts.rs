@@ -0,0 +1,137 @@
+//! Event position assignments as they're posted to Discord.
+//!
+//! The bot posts them as an embed, and event staff can copy them from the
+//! site as text or an image to post themselves; both use these lines so the
+//! posts look the same.
+
+use crate::sql::{Controller, Event, EventPosition};
+
+/// Shown for positions nobody's assigned to.
+pub const UNASSIGNED: &str = "Unassigned";
+/// Shown for positions assigned to a controller who isn't in the database.
+pub const UNKNOWN: &str = "Unknown";
+/// Heading above the assignments.
+pub const HEADING: &str = "Position assignments";
+
+/// Link to the event's page on the site.
+pub fn event_url(hosted_domain: &str, event_id: u32) -> String {
+    format!("{}/events/{event_id}", hosted_domain.trim_end_matches('/'))
+}
+
+/// Who's working the position.
+///
+/// With `mention`, controllers who've linked their Discord account are
+/// mentioned instead of named, which only works in Discord messages.
+pub fn assignee(position: &EventPosition, controllers: &[Controller], mention: bool) -> String {
+    let Some(cid) = position.cid else {
+        return String::from(UNASSIGNED);
+    };
+    match controllers.iter().find(|c| c.cid == cid) {
+        Some(c) => match (&c.discord_id, mention) {
+            (Some(discord_id), true) => format!("<@{discord_id}>"),
+            _ => format!("{} {}", c.first_name, c.last_name),
+        },
+        None => String::from(UNKNOWN),
+    }
+}
+
+/// Each position's name and who's working it, in the positions' order.
+pub fn assignments(
+    positions: &[EventPosition],
+    controllers: &[Controller],
+    mention: bool,
+) -> Vec<(String, String)> {
+    positions
+        .iter()
+        .map(|position| {
+            (
+                position.name.clone(),
+                assignee(position, controllers, mention),
+            )
+        })
+        .collect()
+}
+
+/// The assignments as a Discord message, using markdown and mentions.
+pub fn assignments_text(
+    event: &Event,
+    hosted_domain: &str,
+    positions: &[EventPosition],
+    controllers: &[Controller],
+) -> String {
+    let mut text = format!(
+        "**{}**\n<{}>\n{HEADING}\n",
+        event.name,
+        event_url(hosted_domain, event.id)
+    );
+    for (position, assignee) in assignments(positions, controllers, true) {
+        text.push_str(&format!("**{position}**: {assignee}\n"));
+    }
+    text
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{assignee, assignments_text};
+    use crate::sql::{Controller, Event, EventPosition};
+    use chrono::Utc;
+
+    fn position(id: u32, name: &str, cid: Option<u32>) -> EventPosition {
+        EventPosition {
+            id,
+            event_id: 1,
+            name: name.to_owned(),
+            category: String::from("TRACON"),
+            cid,
+        }
+    }
+
+    #[test]
+    fn test_assignments_text() {
+        let mut linked = Controller {
+            cid: 1,
+            first_name: String::from("Linked"),
+            last_name: String::from("Controller"),
+            discord_id: Some(String::from("1234")),
+            ..Default::default()
+        };
+        let unlinked = Controller {
+            cid: 2,
+            first_name: String::from("Unlinked"),
+            last_name: String::from("Controller"),
+            ..Default::default()
+        };
+        let event = Event {
+            id: 5,
+            published: true,
+            name: String::from("FNO"),
+            start: Utc::now(),
+            end: Utc::now(),
+            description: None,
+            image_url: None,
+            partner_facility: None,
+            external_signup_url: None,
+        };
+        let positions = vec![
+            position(1, "DEN_APP", Some(1)),
+            position(2, "DEN_TWR", Some(2)),
+            position(3, "DEN_GND", None),
+            position(4, "DEN_CTR", Some(3)),
+        ];
+        let controllers = vec![linked.clone(), unlinked];
+
+        assert_eq!(
+            assignments_text(&event, "https://example.com/", &positions, &controllers),
+            "**FNO**\n<https://example.com/events/5>\nPosition assignments\n**DEN_APP**: <@1234>\n**DEN_TWR**: Unlinked Controller\n**DEN_GND**: Unassigned\n**DEN_CTR**: Unknown\n"
+        );
+        assert_eq!(
+            assignee(&positions[0], &controllers, false),
+            "Linked Controller"
+        );
+        linked.discord_id = None;
+        assert_eq!(
+            assignee(&positions[0], &[linked], true),
+            "Linked Controller"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! Writing iCalendar (RFC 5545) feeds.
+
+use chrono::{DateTime, Utc};
+
+/// Timestamp format for UTC date-times.
+const ICS_TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Lines longer than this many bytes are folded.
+const MAX_LINE_BYTES: usize = 75;
+
+/// Single entry in a calendar.
+#[derive(Debug, Clone)]
+pub struct IcsEvent {
+    /// Must be stable across requests so calendar apps update the entry rather than duplicate it.
+    pub uid: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub summary: String,
+    pub description: Option<String>,
+    pub url: Option<String>,
+    /// Revision of the entry; must increase with each change sent as an invite.
+    pub sequence: u32,
+    pub cancelled: bool,
+}
+
+/// Escape text property values.
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Fold the content line, without splitting characters, and terminate it.
+pub(crate) fn push_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > MAX_LINE_BYTES {
+            out.push_str("\r\n ");
+            // the leading space counts toward the next line
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+/// Write the entries as a calendar with the name.
+pub fn write_calendar(name: &str, events: &[IcsEvent], now: DateTime<Utc>) -> String {
+    write(name, None, None, events, now)
+}
+
+/// Write a single entry as an emailed invite (iTIP) from the organizer's address.
+///
+/// Cancelled entries are sent as cancellations, which remove them from the
+/// recipient's calendar.
+pub fn write_invite(name: &str, organizer: &str, event: &IcsEvent, now: DateTime<Utc>) -> String {
+    let method = if event.cancelled { "CANCEL" } else { "REQUEST" };
+    write(
+        name,
+        Some(method),
+        Some(organizer),
+        std::slice::from_ref(event),
+        now,
+    )
+}
+
+fn write(
+    name: &str,
+    method: Option<&str>,
+    organizer: Option<&str>,
+    events: &[IcsEvent],
+    now: DateTime<Utc>,
+) -> String {
+    let mut out = String::new();
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, "PRODID:-//vZDV//vzdv//EN");
+    push_line(&mut out, "CALSCALE:GREGORIAN");
+    if let Some(method) = method {
+        push_line(&mut out, &format!("METHOD:{method}"));
+    }
+    push_line(&mut out, &format!("X-WR-CALNAME:{}", escape(name)));
+    let stamp = now.format(ICS_TIME_FORMAT);
+    for event in events {
+        push_line(&mut out, "BEGIN:VEVENT");
+        push_line(&mut out, &format!("UID:{}", escape(&event.uid)));
+        push_line(&mut out, &format!("DTSTAMP:{stamp}"));
+        push_line(&mut out, &format!("SEQUENCE:{}", event.sequence));
+        if event.cancelled {
+            push_line(&mut out, "STATUS:CANCELLED");
+        }
+        if let Some(organizer) = organizer {
+            push_line(&mut out, &format!("ORGANIZER:mailto:{organizer}"));
+        }
+        push_line(
+            &mut out,
+            &format!("DTSTART:{}", event.start.format(ICS_TIME_FORMAT)),
+        );
+        push_line(
+            &mut out,
+            &format!("DTEND:{}", event.end.format(ICS_TIME_FORMAT)),
+        );
+        push_line(&mut out, &format!("SUMMARY:{}", escape(&event.summary)));
+        if let Some(description) = &event.description {
+            push_line(&mut out, &format!("DESCRIPTION:{}", escape(description)));
+        }
+        if let Some(url) = &event.url {
+            push_line(&mut out, &format!("URL:{url}"));
+        }
+        push_line(&mut out, "END:VEVENT");
+    }
+    push_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{escape, push_line, write_calendar, write_invite, IcsEvent};
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("DEN_APP, DEN_TWR; a\\b\nc"),
+            "DEN_APP\\, DEN_TWR\\; a\\\\b\\nc"
+        );
+    }
+
+    #[test]
+    fn test_push_line_folds() {
+        let mut out = String::new();
+        push_line(&mut out, &"a".repeat(100));
+        let lines: Vec<&str> = out.trim_end().split("\r\n").collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), 75);
+        assert_eq!(lines[1], format!(" {}", "a".repeat(25)));
+    }
+
+    #[test]
+    fn test_write_calendar() {
+        let start = Utc.with_ymd_and_hms(2024, 8, 2, 0, 0, 0).unwrap();
+        let calendar = write_calendar(
+            "Assignments",
+            &[IcsEvent {
+                uid: "event-1@vzdv".to_owned(),
+                start,
+                end: start + chrono::Duration::hours(3),
+                summary: "FNO: DEN_APP".to_owned(),
+                description: None,
+                url: Some("https://example.com/events/1".to_owned()),
+                sequence: 0,
+                cancelled: false,
+            }],
+            start,
+        );
+        assert!(calendar.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(calendar.contains("DTSTART:20240802T000000Z\r\n"));
+        assert!(calendar.contains("DTEND:20240802T030000Z\r\n"));
+        assert!(calendar.ends_with("END:VCALENDAR\r\n"));
+    }
+
+    #[test]
+    fn test_write_invite() {
+        let start = Utc.with_ymd_and_hms(2024, 8, 2, 0, 0, 0).unwrap();
+        let mut event = IcsEvent {
+            uid: "training-1@vzdv".to_owned(),
+            start,
+            end: start + chrono::Duration::hours(1),
+            summary: "Training: DEN_GND".to_owned(),
+            description: None,
+            url: None,
+            sequence: 0,
+            cancelled: false,
+        };
+        let invite = write_invite("Training", "training@example.com", &event, start);
+        assert!(invite.contains("METHOD:REQUEST\r\n"));
+        assert!(invite.contains("ORGANIZER:mailto:training@example.com\r\n"));
+        assert!(!invite.contains("STATUS:CANCELLED"));
+
+        event.cancelled = true;
+        event.sequence = 1;
+        let invite = write_invite("Training", "training@example.com", &event, start);
+        assert!(invite.contains("METHOD:CANCEL\r\n"));
+        assert!(invite.contains("SEQUENCE:1\r\n"));
+        assert!(invite.contains("STATUS:CANCELLED\r\n"));
+    }
+}
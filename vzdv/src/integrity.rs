@@ -0,0 +1,93 @@
+//! Periodic database integrity checks and WAL checkpoints.
+//!
+//! The task runner records each check's result in a setting so that the
+//! admin tasks page can show it.
+
+use crate::sql;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+/// Setting storing the JSON of the last `IntegrityReport`.
+pub const INTEGRITY_REPORT_SETTING: &str = "integrity_report";
+
+/// Result of one integrity check and checkpoint.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct IntegrityReport {
+    pub checked: DateTime<Utc>,
+    /// Problems SQLite found; empty if the database is intact.
+    pub problems: Vec<String>,
+    /// The checkpoint couldn't finish because another connection was using the WAL.
+    pub checkpoint_busy: bool,
+    /// Pages that were in the WAL when it was checkpointed.
+    pub wal_pages: i64,
+}
+
+impl IntegrityReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Check the database for corruption and checkpoint the WAL into the main file,
+/// recording the report in the settings.
+pub async fn check_database(
+    db: &SqlitePool,
+    now: DateTime<Utc>,
+) -> Result<IntegrityReport, sqlx::Error> {
+    let problems: Vec<(String,)> = sqlx::query_as(sql::INTEGRITY_CHECK).fetch_all(db).await?;
+    let problems: Vec<String> = problems
+        .into_iter()
+        .map(|(problem,)| problem)
+        .filter(|problem| problem != "ok")
+        .collect();
+    let (busy, wal_pages, _checkpointed): (i64, i64, i64) =
+        sqlx::query_as(sql::WAL_CHECKPOINT).fetch_one(db).await?;
+    let report = IntegrityReport {
+        checked: now,
+        problems,
+        checkpoint_busy: busy != 0,
+        wal_pages,
+    };
+    sqlx::query(sql::SET_SETTING)
+        .bind(INTEGRITY_REPORT_SETTING)
+        .bind(serde_json::to_string(&report).unwrap_or_default())
+        .execute(db)
+        .await?;
+    Ok(report)
+}
+
+/// The report from the last check, if there's been one.
+pub async fn last_report(db: &SqlitePool) -> Result<Option<IntegrityReport>, sqlx::Error> {
+    let value: Option<(String,)> = sqlx::query_as(sql::GET_SETTING)
+        .bind(INTEGRITY_REPORT_SETTING)
+        .fetch_optional(db)
+        .await?;
+    Ok(value.and_then(|(value,)| serde_json::from_str(&value).ok()))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{check_database, last_report};
+    use crate::{db::run_migrations, sql};
+    use chrono::{TimeZone, Utc};
+    use sqlx::{sqlite::SqlitePoolOptions, Executor};
+
+    #[tokio::test]
+    async fn test_check_database() {
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        db.execute(sql::CREATE_TABLES).await.unwrap();
+        run_migrations(&db).await.unwrap();
+        assert!(last_report(&db).await.unwrap().is_none());
+
+        let now = Utc.with_ymd_and_hms(2026, 10, 17, 4, 0, 0).unwrap();
+        let report = check_database(&db, now).await.unwrap();
+        assert!(report.is_ok());
+        assert!(!report.checkpoint_busy);
+        assert_eq!(last_report(&db).await.unwrap(), Some(report));
+    }
+}
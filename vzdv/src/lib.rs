@@ -10,7 +10,7 @@ use fern::{
     colors::{Color, ColoredLevelConfig},
     Dispatch,
 };
-use log::{debug, error};
+use log::{debug, error, info};
 use reqwest::ClientBuilder;
 use sql::Controller;
 use sqlx::{sqlite::SqliteRow, Pool, Row, Sqlite};
@@ -21,16 +21,35 @@ use std::{
     time::SystemTime,
 };
 
+pub mod activity;
 pub mod aviation;
+pub mod cert_positions;
+pub mod certifications;
+pub mod cleanup;
 pub mod config;
 pub mod db;
+pub mod error_reporting;
+pub mod event_posts;
+pub mod ics;
+pub mod integrity;
+pub mod permissions;
+pub mod quarterly_report;
+pub mod repo;
+pub mod request_id;
+pub mod resources;
 pub mod sql;
+pub mod staff_contacts;
+pub mod stats;
 pub mod vatsim;
 pub mod vatusa;
+pub mod waitlist;
 
 // I don't know what this is, but there's a SUP in ZDV that has this rating.
 const IGNORE_MISSING_STAFF_POSITIONS_FOR: [&str; 1] = ["FACCBT"];
 
+/// Days new controllers have to choose their own OIs before they're assigned some.
+pub const OPERATING_INITIALS_CHOICE_DAYS: i64 = 7;
+
 /// HTTP client for making external requests.
 ///
 /// Include an HTTP user agent of the project's repo for contact.
@@ -77,6 +96,14 @@ pub async fn get_controller_cids_and_names(
     Ok(cid_name_map)
 }
 
+/// The controller's full name from `get_controller_cids_and_names`, or their CID if they're not known.
+pub fn display_name(names: &HashMap<u32, (String, String)>, cid: u32) -> String {
+    names
+        .get(&cid)
+        .map(|(first, last)| format!("{first} {last}"))
+        .unwrap_or_else(|| cid.to_string())
+}
+
 /// Determine the staff position of the controller.
 ///
 /// VATUSA does not differentiate between the official staff position (say, FE)
@@ -244,86 +271,11 @@ impl From<&str> for StaffPosition {
     }
 }
 
-#[derive(Debug, PartialEq)]
-pub enum PermissionsGroup {
-    /// Literally anyone.
-    Anon,
-    /// Has a session.
-    LoggedIn,
-    /// Has a role.
-    SomeStaff,
-    /// Has a Jr or Sr position.
-    NamedPosition,
-    /// EC, AEC, and up.
-    EventsTeam,
-    /// MTR, INS, TA, and up.
-    TrainingTeam,
-    /// ATM, DATM (and WM).
-    Admin,
-}
-
-/// Permissions control for accessing things.
-pub fn controller_can_see(controller: &Option<Controller>, team: PermissionsGroup) -> bool {
-    let controller = match controller {
-        Some(c) => c,
-        None => return team == PermissionsGroup::Anon,
-    };
-    let roles: Vec<_> = controller
-        .roles
-        .split(',')
-        .map(StaffPosition::from)
-        .collect();
-    match team {
-        PermissionsGroup::Anon => true,
-        PermissionsGroup::LoggedIn => true,
-        PermissionsGroup::NamedPosition => [
-            StaffPosition::ATM,
-            StaffPosition::DATM,
-            StaffPosition::TA,
-            StaffPosition::FE,
-            StaffPosition::EC,
-            StaffPosition::WM,
-        ]
-        .iter()
-        .any(|r| roles.contains(r)),
-        PermissionsGroup::SomeStaff => [
-            StaffPosition::ATM,
-            StaffPosition::DATM,
-            StaffPosition::TA,
-            StaffPosition::FE,
-            StaffPosition::EC,
-            StaffPosition::WM,
-            StaffPosition::AFE,
-            StaffPosition::AEC,
-            StaffPosition::AWM,
-            StaffPosition::INS,
-            StaffPosition::MTR,
-        ]
-        .iter()
-        .any(|r| roles.contains(r)),
-        PermissionsGroup::EventsTeam => [
-            StaffPosition::EC,
-            StaffPosition::AEC,
-            StaffPosition::ATM,
-            StaffPosition::DATM,
-            StaffPosition::WM,
-        ]
-        .iter()
-        .any(|r| roles.contains(r)),
-        PermissionsGroup::TrainingTeam => [
-            StaffPosition::MTR,
-            StaffPosition::INS,
-            StaffPosition::TA,
-            StaffPosition::ATM,
-            StaffPosition::DATM,
-            StaffPosition::WM,
-        ]
-        .iter()
-        .any(|r| roles.contains(r)),
-        PermissionsGroup::Admin => [StaffPosition::ATM, StaffPosition::DATM, StaffPosition::WM]
-            .iter()
-            .any(|r| roles.contains(r)),
-    }
+/// Request ID for log lines, with a leading space, or empty if not handling a request.
+fn log_request_id() -> String {
+    request_id::current()
+        .map(|id| format!(" {id}"))
+        .unwrap_or_default()
 }
 
 /// Setup logging, load the config, connect to the DB; return config and DB.
@@ -333,6 +285,7 @@ pub async fn general_setup(
     debug_logging: bool,
     binary_name: &str,
     config_path: Option<PathBuf>,
+    environment: Option<String>,
 ) -> (Config, Pool<Sqlite>) {
     let colors_line = ColoredLevelConfig::new()
         .error(Color::Red)
@@ -392,10 +345,11 @@ pub async fn general_setup(
             Dispatch::new()
                 .format(move |out, message, record| {
                     out.finish(format_args!(
-                        "[{} {} {}] {}",
+                        "[{} {} {}{}] {}",
                         humantime::format_rfc3339_seconds(SystemTime::now()),
                         colors_line.color(record.level()),
                         record.target(),
+                        log_request_id(),
                         message,
                     ))
                 })
@@ -405,10 +359,11 @@ pub async fn general_setup(
             Dispatch::new()
                 .format(move |out, message, record| {
                     out.finish(format_args!(
-                        "[{} {} {}] {}",
+                        "[{} {} {}{}] {}",
                         humantime::format_rfc3339_seconds(SystemTime::now()),
                         record.level(),
                         record.target(),
+                        log_request_id(),
                         message,
                     ))
                 })
@@ -425,7 +380,10 @@ pub async fn general_setup(
         None => Path::new(config::DEFAULT_CONFIG_FILE_NAME).to_owned(),
     };
     debug!("Loading from config file");
-    let config = match Config::load_from_disk(&config_location) {
+    if let Some(environment) = &environment {
+        info!("Using the \"{environment}\" config overlay");
+    }
+    let config = match Config::load_from_disk(&config_location, environment.as_deref()) {
         Ok(c) => c,
         Err(e) => {
             error!("Could not load config: {e}");
@@ -515,17 +473,58 @@ pub fn generate_operating_initials_for(
     bail!("Apparently there are no OIs available")
 }
 
+/// Suggest up to `count` unused OIs for the controller to choose from.
+///
+/// Suggestions start with their actual initials, then pair their initials with
+/// the other letters of their names.
+pub fn suggest_operating_initials(
+    in_use: &[String],
+    first_name: &str,
+    last_name: &str,
+    count: usize,
+) -> Vec<String> {
+    let letters = |name: &str| -> Vec<char> {
+        name.chars()
+            .filter(char::is_ascii_alphabetic)
+            .map(|c| c.to_ascii_uppercase())
+            .collect()
+    };
+    let (first, last) = (letters(first_name), letters(last_name));
+    let (Some(&first_first), Some(&last_first)) = (first.first(), last.first()) else {
+        return Vec::new();
+    };
+    let candidates = std::iter::once(format!("{first_first}{last_first}"))
+        .chain(last.iter().skip(1).map(|c| format!("{first_first}{c}")))
+        .chain(first.iter().skip(1).map(|c| format!("{first_first}{c}")))
+        .chain(first.iter().skip(1).map(|c| format!("{c}{last_first}")))
+        .chain(last.iter().skip(1).map(|c| format!("{last_first}{c}")));
+    let mut suggestions: Vec<String> = Vec::new();
+    for candidate in candidates {
+        if suggestions.len() == count {
+            break;
+        }
+        if !in_use.contains(&candidate) && !suggestions.contains(&candidate) {
+            suggestions.push(candidate);
+        }
+    }
+    suggestions
+}
+
 #[cfg(test)]
 pub mod tests {
-    use super::{
-        controller_can_see, determine_staff_positions, position_in_facility_airspace,
-        PermissionsGroup,
-    };
+    use super::{determine_staff_positions, display_name, position_in_facility_airspace};
     use crate::{
         config::Config, generate_operating_initials_for, sql::Controller,
-        vatsim::parse_vatsim_timestamp,
+        suggest_operating_initials, vatsim::parse_vatsim_timestamp,
     };
 
+    #[test]
+    fn test_display_name() {
+        let names = [(1, ("First".to_owned(), "Last".to_owned()))].into();
+        assert_eq!(display_name(&names, 1), "First Last");
+        assert_eq!(display_name(&names, 2), "2");
+    }
+
     #[test]
     fn test_parse_vatsim_timestamp() {
         parse_vatsim_timestamp("2024-03-02T16:20:37.0439318Z").unwrap();
@@ -595,96 +594,6 @@ pub mod tests {
         assert!(determine_staff_positions(&controller).is_empty());
     }
 
-    #[test]
-    fn test_controller_can_see_anon() {
-        assert!(controller_can_see(&None, PermissionsGroup::Anon));
-        let mut controller = Controller::default();
-        assert!(controller_can_see(
-            &Some(controller.clone()),
-            PermissionsGroup::Anon
-        ));
-        controller.roles = "DATM,INS".to_string();
-        assert!(controller_can_see(
-            &Some(controller),
-            PermissionsGroup::Anon
-        ));
-    }
-
-    #[test]
-    fn test_controller_can_see_logged_in() {
-        assert!(!controller_can_see(&None, PermissionsGroup::LoggedIn));
-        let mut controller = Controller::default();
-        assert!(controller_can_see(
-            &Some(controller.clone()),
-            PermissionsGroup::LoggedIn
-        ));
-        controller.roles = "DATM,INS".to_string();
-        assert!(controller_can_see(
-            &Some(controller.clone()),
-            PermissionsGroup::LoggedIn
-        ));
-    }
-
-    #[test]
-    fn test_controller_can_see_teams() {
-        assert!(!controller_can_see(&None, PermissionsGroup::EventsTeam));
-        let mut controller = Controller::default();
-        assert!(!controller_can_see(
-            &Some(controller.clone()),
-            PermissionsGroup::EventsTeam
-        ));
-        controller.roles = "EC".to_string();
-        assert!(controller_can_see(
-            &Some(controller.clone()),
-            PermissionsGroup::EventsTeam
-        ));
-        controller.roles = "AEC".to_string();
-        assert!(controller_can_see(
-            &Some(controller.clone()),
-            PermissionsGroup::EventsTeam
-        ));
-
-        controller.roles = "MTR".to_string();
-        assert!(!controller_can_see(
-            &Some(controller.clone()),
-            PermissionsGroup::EventsTeam
-        ));
-        assert!(controller_can_see(
-            &Some(controller.clone()),
-            PermissionsGroup::TrainingTeam
-        ));
-    }
-
-    #[test]
-    fn test_controller_can_see_admin() {
-        assert!(!controller_can_see(&None, PermissionsGroup::Admin));
-        let mut controller = Controller::default();
-        assert!(!controller_can_see(
-            &Some(controller.clone()),
-            PermissionsGroup::Admin
-        ));
-        controller.roles = "EC".to_string();
-        assert!(!controller_can_see(
-            &Some(controller.clone()),
-            PermissionsGroup::Admin
-        ));
-        controller.roles = "ATM".to_string();
-        assert!(controller_can_see(
-            &Some(controller.clone()),
-            PermissionsGroup::Admin
-        ));
-        controller.roles = "DATM".to_string();
-        assert!(controller_can_see(
-            &Some(controller.clone()),
-            PermissionsGroup::Admin
-        ));
-        controller.roles = "WM".to_string();
-        assert!(controller_can_see(
-            &Some(controller.clone()),
-            PermissionsGroup::Admin
-        ));
-    }
-
     #[test]
     fn test_generate_operating_initials_for() {
         let in_use = &[
@@ -707,4 +616,18 @@ pub mod tests {
         let result = generate_operating_initials_for(in_use, "Ron", "Yo").unwrap();
         assert_eq!(&result, "AB");
     }
+
+    #[test]
+    fn test_suggest_operating_initials() {
+        let in_use = &[String::from("JS"), String::from("JM")];
+        assert_eq!(
+            suggest_operating_initials(in_use, "John", "Smith", 4),
+            vec!["JI", "JT", "JH", "JO"]
+        );
+        assert_eq!(
+            suggest_operating_initials(&[], "Al", "Bo", 10),
+            vec!["AB", "AO", "AL", "LB", "BO"]
+        );
+        assert!(suggest_operating_initials(&[], "", "Smith", 4).is_empty());
+    }
 }
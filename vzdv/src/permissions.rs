@@ -0,0 +1,280 @@
+//! Capability-based permissions.
+//!
+//! Each capability is a string granted to staff roles in the `role_permission`
+//! table. Individual controllers can additionally be granted or denied
+//! capabilities in the `controller_permission` table, which take precedence
+//! over what their roles grant. The defaults are set by the migration that
+//! created the tables, and can be changed by the facility from the site.
+
+use crate::sql::{self, Controller, ControllerPermission, RolePermission};
+use sqlx::SqlitePool;
+use std::collections::HashSet;
+
+/// Baseline staff access: staff notes, assigned feedback, the off-roster list.
+pub const STAFF: &str = "staff";
+/// Review all feedback and see it on controller pages.
+pub const FEEDBACK_MANAGE: &str = "feedback.manage";
+/// Assign pending feedback to staff members to review.
+pub const FEEDBACK_ASSIGN: &str = "feedback.assign";
+/// Create and edit events and their positions.
+pub const EVENTS_MANAGE: &str = "events.manage";
+/// Training records, certifications, and solo certs.
+pub const TRAINING_MANAGE: &str = "training.manage";
+/// Define the training syllabus's lessons and milestones.
+pub const SYLLABUS_MANAGE: &str = "syllabus.manage";
+/// Read students' feedback on their training sessions, summarized by mentor.
+pub const TRAINING_FEEDBACK_VIEW: &str = "training_feedback.view";
+/// Roster changes, operating initials, Discord unlinking, and staff notes.
+pub const ROSTER_MANAGE: &str = "roster.manage";
+/// Assign any staff role, rather than just a role's assistants.
+pub const ROLES_MANAGE: &str = "roles.manage";
+/// Accept and deny visitor applications.
+pub const VISITORS_MANAGE: &str = "visitors.manage";
+/// Upload and delete resources; the facility engineering team has this by default.
+pub const RESOURCES_MANAGE: &str = "resources.manage";
+/// Create, extend, and end leaves of absence.
+pub const LOA_MANAGE: &str = "loa.manage";
+/// Send emails to controllers from the site.
+pub const EMAIL_SEND: &str = "email.send";
+/// Logs, request stats, the cache, banners, the blocklist, and maintenance mode.
+pub const SITE_ADMIN: &str = "site.admin";
+/// Change which roles and controllers have which capabilities.
+pub const PERMISSIONS_MANAGE: &str = "permissions.manage";
+/// View the site as another controller.
+pub const IMPERSONATE: &str = "impersonate";
+
+/// All capabilities, with descriptions for the admin page.
+pub const ALL: &[(&str, &str)] = &[
+    (
+        STAFF,
+        "Staff notes, assigned feedback, and the off-roster list",
+    ),
+    (FEEDBACK_MANAGE, "Review all feedback"),
+    (FEEDBACK_ASSIGN, "Assign feedback to reviewers"),
+    (EVENTS_MANAGE, "Create and edit events"),
+    (TRAINING_MANAGE, "Training records and certifications"),
+    (SYLLABUS_MANAGE, "Training syllabus"),
+    (TRAINING_FEEDBACK_VIEW, "Students' feedback on mentors"),
+    (ROSTER_MANAGE, "Roster changes, OIs, and staff notes"),
+    (ROLES_MANAGE, "Assign any staff role"),
+    (VISITORS_MANAGE, "Visitor applications"),
+    (
+        RESOURCES_MANAGE,
+        "Upload and delete resources, and manage their categories",
+    ),
+    (LOA_MANAGE, "Leaves of absence"),
+    (EMAIL_SEND, "Send emails"),
+    (
+        SITE_ADMIN,
+        "Logs, stats, cache, banners, blocklist, and maintenance",
+    ),
+    (PERMISSIONS_MANAGE, "Change permissions"),
+    (IMPERSONATE, "View the site as another controller"),
+];
+
+/// Staff roles that capabilities can be granted to.
+pub const ROLES: &[&str] = &[
+    "ATM", "DATM", "TA", "FE", "EC", "WM", "AFE", "AEC", "AWM", "INS", "MTR",
+];
+
+/// Get all of the capabilities that the controller has, from their roles and overrides.
+///
+/// Controllers not in the DB have none.
+pub async fn permissions_for(db: &SqlitePool, cid: u32) -> Result<HashSet<String>, sqlx::Error> {
+    let controller: Option<Controller> = sqlx::query_as(sql::GET_CONTROLLER_BY_CID)
+        .bind(cid)
+        .fetch_optional(db)
+        .await?;
+    let Some(controller) = controller else {
+        return Ok(HashSet::new());
+    };
+    let roles: HashSet<&str> = controller
+        .roles
+        .split(',')
+        .map(str::trim)
+        .filter(|role| !role.is_empty())
+        .collect();
+    let grants: Vec<RolePermission> = sqlx::query_as(sql::GET_ROLE_PERMISSIONS)
+        .fetch_all(db)
+        .await?;
+    let mut permissions: HashSet<String> = grants
+        .into_iter()
+        .filter(|grant| roles.contains(grant.role.as_str()))
+        .map(|grant| grant.permission)
+        .collect();
+    let overrides: Vec<ControllerPermission> = sqlx::query_as(sql::GET_CONTROLLER_PERMISSIONS_FOR)
+        .bind(cid)
+        .fetch_all(db)
+        .await?;
+    for o in overrides {
+        if o.granted {
+            permissions.insert(o.permission);
+        } else {
+            permissions.remove(&o.permission);
+        }
+    }
+    Ok(permissions)
+}
+
+/// Return whether the controller has the capability.
+pub async fn has_permission(
+    db: &SqlitePool,
+    cid: u32,
+    permission: &str,
+) -> Result<bool, sqlx::Error> {
+    Ok(permissions_for(db, cid).await?.contains(permission))
+}
+
+/// Get the CIDs of all controllers that have the capability.
+pub async fn cids_with_permission(
+    db: &SqlitePool,
+    permission: &str,
+) -> Result<HashSet<u32>, sqlx::Error> {
+    let grants: Vec<RolePermission> = sqlx::query_as(sql::GET_ROLE_PERMISSIONS)
+        .fetch_all(db)
+        .await?;
+    let granted_roles: HashSet<String> = grants
+        .into_iter()
+        .filter(|grant| grant.permission == permission)
+        .map(|grant| grant.role)
+        .collect();
+    let staff: Vec<(u32, String)> = sqlx::query_as(sql::GET_ALL_CONTROLLER_ROLES)
+        .fetch_all(db)
+        .await?;
+    let mut cids: HashSet<u32> = staff
+        .into_iter()
+        .filter(|(_, roles)| {
+            roles
+                .split(',')
+                .any(|role| granted_roles.contains(role.trim()))
+        })
+        .map(|(cid, _)| cid)
+        .collect();
+    let overrides: Vec<ControllerPermission> = sqlx::query_as(sql::GET_ALL_CONTROLLER_PERMISSIONS)
+        .fetch_all(db)
+        .await?;
+    for o in overrides.into_iter().filter(|o| o.permission == permission) {
+        if o.granted {
+            cids.insert(o.cid);
+        } else {
+            cids.remove(&o.cid);
+        }
+    }
+    Ok(cids)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{cids_with_permission, has_permission, permissions_for, EVENTS_MANAGE, STAFF};
+    use crate::{db::run_migrations, permissions, sql};
+    use sqlx::{sqlite::SqlitePoolOptions, Executor, SqlitePool};
+
+    /// Capabilities that only the ATM, DATM, and WM have by default.
+    const ADMIN_ONLY: &[&str] = &[
+        permissions::FEEDBACK_MANAGE,
+        permissions::ROSTER_MANAGE,
+        permissions::ROLES_MANAGE,
+        permissions::VISITORS_MANAGE,
+        permissions::LOA_MANAGE,
+        permissions::EMAIL_SEND,
+        permissions::SITE_ADMIN,
+        permissions::PERMISSIONS_MANAGE,
+    ];
+
+    async fn db_with(roles: &[(u32, &str)]) -> SqlitePool {
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        db.execute(sql::CREATE_TABLES).await.unwrap();
+        run_migrations(&db).await.unwrap();
+        for (cid, roles) in roles {
+            sqlx::query("INSERT INTO controller (cid, first_name, last_name, rating, roles) VALUES ($1, '', '', 1, $2)")
+                .bind(cid)
+                .bind(roles)
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+        db
+    }
+
+    #[tokio::test]
+    async fn test_default_permissions() {
+        let db = db_with(&[
+            (1, ""),
+            (2, "EC"),
+            (3, "AEC"),
+            (4, "MTR"),
+            (5, "ATM"),
+            (6, "DATM,INS"),
+            (7, "WM"),
+            (8, "FE"),
+            (9, "AFE"),
+        ])
+        .await;
+        assert!(permissions_for(&db, 1).await.unwrap().is_empty());
+        assert!(permissions_for(&db, 999).await.unwrap().is_empty());
+
+        assert!(has_permission(&db, 2, EVENTS_MANAGE).await.unwrap());
+        assert!(has_permission(&db, 3, EVENTS_MANAGE).await.unwrap());
+        assert!(has_permission(&db, 3, STAFF).await.unwrap());
+        assert!(!has_permission(&db, 4, EVENTS_MANAGE).await.unwrap());
+        assert!(has_permission(&db, 4, permissions::TRAINING_MANAGE)
+            .await
+            .unwrap());
+        assert!(has_permission(&db, 8, permissions::RESOURCES_MANAGE)
+            .await
+            .unwrap());
+        assert!(has_permission(&db, 9, permissions::RESOURCES_MANAGE)
+            .await
+            .unwrap());
+        assert!(!has_permission(&db, 9, permissions::EVENTS_MANAGE)
+            .await
+            .unwrap());
+
+        for cid in [2, 3, 4, 8, 9] {
+            let held = permissions_for(&db, cid).await.unwrap();
+            assert!(ADMIN_ONLY.iter().all(|p| !held.contains(*p)));
+        }
+        for cid in [5, 6, 7] {
+            let held = permissions_for(&db, cid).await.unwrap();
+            assert!(ADMIN_ONLY.iter().all(|p| held.contains(*p)));
+        }
+        assert!(has_permission(&db, 5, permissions::IMPERSONATE)
+            .await
+            .unwrap());
+        assert!(!has_permission(&db, 6, permissions::IMPERSONATE)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_permission_overrides() {
+        let db = db_with(&[(1, ""), (2, "ATM")]).await;
+        sqlx::query(sql::SET_CONTROLLER_PERMISSION)
+            .bind(1)
+            .bind(EVENTS_MANAGE)
+            .bind(true)
+            .execute(&db)
+            .await
+            .unwrap();
+        sqlx::query(sql::SET_CONTROLLER_PERMISSION)
+            .bind(2)
+            .bind(EVENTS_MANAGE)
+            .bind(false)
+            .execute(&db)
+            .await
+            .unwrap();
+        assert!(has_permission(&db, 1, EVENTS_MANAGE).await.unwrap());
+        assert!(!has_permission(&db, 2, EVENTS_MANAGE).await.unwrap());
+        let with_events: Vec<u32> = cids_with_permission(&db, EVENTS_MANAGE)
+            .await
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(with_events, vec![1]);
+        assert!(has_permission(&db, 2, STAFF).await.unwrap());
+    }
+}
@@ -0,0 +1,382 @@
+//! Quarterly facility report for VATUSA.
+//!
+//! Covers activity compliance, training, events, and roster changes for a
+//! calendar quarter, assembled from the existing tables and the stats rollups,
+//! and rendered as a plain text document for staff to download and submit.
+
+use crate::{
+    activity::{exemption, Quarter, REQUIRED_MINUTES},
+    sql::{self, Controller, StatsEvent, StatsMonthHours},
+};
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, SqlitePool};
+use std::{collections::HashMap, fmt::Write};
+
+/// On-roster controller's activity in the quarter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControllerCompliance {
+    pub cid: u32,
+    pub name: String,
+    pub minutes: u32,
+    /// Why they don't need to meet the requirement, if so.
+    pub exemption: Option<&'static str>,
+}
+
+impl ControllerCompliance {
+    pub fn met(&self) -> bool {
+        self.minutes >= REQUIRED_MINUTES
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrainingCounts {
+    pub records: u32,
+    pub students: u32,
+    pub instructors: u32,
+    /// Booked sessions that weren't cancelled.
+    pub sessions: u32,
+    /// Certifications set to "certified", by name.
+    pub certified: Vec<(String, u32)>,
+}
+
+/// Controller who left the roster, from the exit survey queued when they did.
+#[derive(Debug, Clone, FromRow)]
+pub struct RosterDeparture {
+    pub cid: u32,
+    pub first_name: String,
+    pub last_name: String,
+    pub departure: String,
+    pub created_date: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub struct QuarterlyReport {
+    pub quarter: Quarter,
+    pub generated: DateTime<Utc>,
+    /// Current roster, by CID.
+    pub compliance: Vec<ControllerCompliance>,
+    /// From the stats rollup, for the quarter's months that have activity.
+    pub hours: Vec<StatsMonthHours>,
+    pub training: TrainingCounts,
+    /// Published events, from the stats rollup.
+    pub events: Vec<StatsEvent>,
+    pub joined: Vec<Controller>,
+    pub departed: Vec<RosterDeparture>,
+}
+
+impl QuarterlyReport {
+    /// Gather the report's data for the quarter.
+    ///
+    /// Compliance is for the current roster, with exemptions as of `now`.
+    pub async fn build(
+        db: &SqlitePool,
+        quarter: &Quarter,
+        now: DateTime<Utc>,
+    ) -> Result<Self, sqlx::Error> {
+        let controllers: Vec<Controller> = sqlx::query_as(sql::GET_ALL_CONTROLLERS_ON_ROSTER)
+            .fetch_all(db)
+            .await?;
+        let totals: HashMap<u32, u32> = sqlx::query_as(sql::GET_ACTIVITY_TOTALS_BETWEEN)
+            .bind(&quarter.months[0])
+            .bind(&quarter.months[2])
+            .fetch_all(db)
+            .await?
+            .into_iter()
+            .collect();
+        let mut compliance: Vec<ControllerCompliance> = controllers
+            .iter()
+            .map(|controller| ControllerCompliance {
+                cid: controller.cid,
+                name: format!("{} {}", controller.first_name, controller.last_name),
+                minutes: totals.get(&controller.cid).copied().unwrap_or_default(),
+                exemption: exemption(controller, quarter, now),
+            })
+            .collect();
+        compliance.sort_by_key(|c| c.cid);
+
+        let hours = sqlx::query_as(sql::GET_STATS_MONTH_HOURS_BETWEEN)
+            .bind(&quarter.months[0])
+            .bind(&quarter.months[2])
+            .fetch_all(db)
+            .await?;
+        let (records, students, instructors): (u32, u32, u32) =
+            sqlx::query_as(sql::GET_TRAINING_RECORD_COUNTS_BETWEEN)
+                .bind(quarter.start)
+                .bind(quarter.end)
+                .fetch_one(db)
+                .await?;
+        let sessions: u32 = sqlx::query_scalar(sql::COUNT_TRAINING_SESSIONS_BETWEEN)
+            .bind(quarter.start)
+            .bind(quarter.end)
+            .fetch_one(db)
+            .await?;
+        let certified = sqlx::query_as(sql::GET_CERTIFICATIONS_GRANTED_BETWEEN)
+            .bind(quarter.start)
+            .bind(quarter.end)
+            .fetch_all(db)
+            .await?;
+        let events = sqlx::query_as(sql::GET_STATS_EVENTS_BETWEEN)
+            .bind(quarter.start)
+            .bind(quarter.end)
+            .fetch_all(db)
+            .await?;
+        let joined = sqlx::query_as(sql::GET_ROSTER_JOINS_BETWEEN)
+            .bind(quarter.start)
+            .bind(quarter.end)
+            .fetch_all(db)
+            .await?;
+        let departed = sqlx::query_as(sql::GET_ROSTER_DEPARTURES_BETWEEN)
+            .bind(quarter.start)
+            .bind(quarter.end)
+            .fetch_all(db)
+            .await?;
+
+        Ok(Self {
+            quarter: quarter.clone(),
+            generated: now,
+            compliance,
+            hours,
+            training: TrainingCounts {
+                records,
+                students,
+                instructors,
+                sessions,
+                certified,
+            },
+            events,
+            joined,
+            departed,
+        })
+    }
+
+    /// File name for the downloaded document.
+    pub fn file_name(&self) -> String {
+        format!("vZDV_{}_report.txt", self.quarter.label)
+    }
+
+    /// The report as a plain text document.
+    pub fn to_document(&self) -> String {
+        let mut doc = String::new();
+        // writing to a String can't fail
+        let _ = self.write_document(&mut doc);
+        doc
+    }
+
+    fn write_document(&self, doc: &mut String) -> std::fmt::Result {
+        writeln!(
+            doc,
+            "vZDV Quarterly Facility Report - {}",
+            self.quarter.label
+        )?;
+        writeln!(
+            doc,
+            "Period: {} to {}",
+            self.quarter.start.format("%Y-%m-%d"),
+            (self.quarter.end - chrono::Duration::days(1)).format("%Y-%m-%d")
+        )?;
+        writeln!(
+            doc,
+            "Generated: {}",
+            self.generated.format("%Y-%m-%d %H:%M UTC")
+        )?;
+
+        let required = self.compliance.iter().filter(|c| c.exemption.is_none());
+        let met = required.clone().filter(|c| c.met()).count();
+        let exempt = self.compliance.len() - required.clone().count();
+        writeln!(doc, "\n== Activity compliance ==")?;
+        writeln!(
+            doc,
+            "Requirement: {} hours per quarter",
+            hours(REQUIRED_MINUTES)
+        )?;
+        writeln!(doc, "Controllers on roster: {}", self.compliance.len())?;
+        writeln!(doc, "Met requirement: {met}")?;
+        writeln!(doc, "Exempt: {exempt}")?;
+        writeln!(doc, "Below requirement: {}", required.clone().count() - met)?;
+        for c in required.filter(|c| !c.met()) {
+            writeln!(doc, "  {} ({}): {} hours", c.name, c.cid, hours(c.minutes))?;
+        }
+        for c in self.compliance.iter().filter(|c| c.exemption.is_some()) {
+            writeln!(
+                doc,
+                "  {} ({}): exempt, {}",
+                c.name,
+                c.cid,
+                c.exemption.unwrap_or_default()
+            )?;
+        }
+
+        writeln!(doc, "\n== Hours ==")?;
+        if self.hours.is_empty() {
+            writeln!(doc, "No activity recorded")?;
+        }
+        for month in &self.hours {
+            writeln!(
+                doc,
+                "{}: {} controlling, {} events, {} instructing; {} active controllers",
+                month.month,
+                hours(month.controlling),
+                hours(month.event),
+                hours(month.training),
+                month.controllers
+            )?;
+        }
+
+        let training = &self.training;
+        writeln!(doc, "\n== Training ==")?;
+        writeln!(doc, "Training records: {}", training.records)?;
+        writeln!(doc, "Students trained: {}", training.students)?;
+        writeln!(doc, "Active instructors: {}", training.instructors)?;
+        writeln!(doc, "Booked sessions held: {}", training.sessions)?;
+        if training.certified.is_empty() {
+            writeln!(doc, "Certifications granted: 0")?;
+        } else {
+            writeln!(doc, "Certifications granted:")?;
+            for (name, count) in &training.certified {
+                writeln!(doc, "  {name}: {count}")?;
+            }
+        }
+
+        writeln!(doc, "\n== Events ==")?;
+        if self.events.is_empty() {
+            writeln!(doc, "No events")?;
+        }
+        for event in &self.events {
+            writeln!(
+                doc,
+                "{} {}: {}/{} positions assigned, {} registrations",
+                event.start.format("%Y-%m-%d"),
+                event.name,
+                event.assigned,
+                event.positions,
+                event.registrations
+            )?;
+        }
+
+        writeln!(doc, "\n== Roster changes ==")?;
+        writeln!(doc, "Joined: {}", self.joined.len())?;
+        for controller in &self.joined {
+            writeln!(
+                doc,
+                "  {} {} ({}), {}",
+                controller.first_name,
+                controller.last_name,
+                controller.cid,
+                controller
+                    .join_date
+                    .map(|date| date.format("%Y-%m-%d").to_string())
+                    .unwrap_or_default()
+            )?;
+        }
+        writeln!(doc, "Left: {}", self.departed.len())?;
+        for departure in &self.departed {
+            writeln!(
+                doc,
+                "  {} {} ({}), {}: {}",
+                departure.first_name,
+                departure.last_name,
+                departure.cid,
+                departure.created_date.format("%Y-%m-%d"),
+                departure.departure
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Minutes as hours with one decimal place.
+fn hours(minutes: u32) -> String {
+    format!("{:.1}", minutes as f32 / 60.0)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::QuarterlyReport;
+    use crate::{activity::Quarter, db::run_migrations, sql, stats::rebuild_rollups};
+    use chrono::{TimeZone, Utc};
+    use sqlx::{sqlite::SqlitePoolOptions, Executor};
+
+    #[tokio::test]
+    async fn test_quarterly_report() {
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        db.execute(sql::CREATE_TABLES).await.unwrap();
+        run_migrations(&db).await.unwrap();
+        let now = Utc.with_ymd_and_hms(2026, 10, 2, 0, 0, 0).unwrap();
+        let long_ago = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let in_quarter = Utc.with_ymd_and_hms(2026, 8, 15, 0, 0, 0).unwrap();
+        // active, inactive, joined during the quarter, and left during it
+        for (cid, name, joined, on_roster) in [
+            (1, "Active", Some(long_ago), true),
+            (2, "Inactive", Some(long_ago), true),
+            (3, "New", Some(in_quarter), true),
+            (4, "Gone", None, false),
+        ] {
+            sqlx::query("INSERT INTO controller (cid, first_name, last_name, rating, is_on_roster, join_date) VALUES ($1, $2, 'Controller', 3, $3, $4)")
+                .bind(cid)
+                .bind(name)
+                .bind(on_roster)
+                .bind(joined)
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+        for (cid, minutes, source) in [(1, 200, "controlling"), (2, 30, "training")] {
+            sqlx::query("INSERT INTO activity (id, cid, month, minutes, source) VALUES (NULL, $1, '2026-08', $2, $3)")
+                .bind(cid)
+                .bind(minutes)
+                .bind(source)
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+        sqlx::query("INSERT INTO training_record (id, cid, instructor_cid, position, date, notes) VALUES (NULL, 3, 1, 'DEN_GND', $1, '')")
+            .bind(in_quarter)
+            .execute(&db)
+            .await
+            .unwrap();
+        sqlx::query(sql::INSERT_CERTIFICATION_HISTORY)
+            .bind(3)
+            .bind("GC T1")
+            .bind("solo")
+            .bind("certified")
+            .bind(1)
+            .bind(in_quarter)
+            .execute(&db)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO event (id, created_by, name, published, start, end) VALUES (NULL, 1, 'FNO', TRUE, $1, $1)")
+            .bind(in_quarter)
+            .execute(&db)
+            .await
+            .unwrap();
+        sqlx::query(sql::QUEUE_EXIT_SURVEY)
+            .bind(4)
+            .bind("Left the VATUSA roster")
+            .bind(in_quarter)
+            .execute(&db)
+            .await
+            .unwrap();
+        rebuild_rollups(&db, now).await.unwrap();
+
+        let quarter = Quarter::previous(now);
+        let report = QuarterlyReport::build(&db, &quarter, now).await.unwrap();
+        assert_eq!(report.compliance.len(), 3);
+        assert_eq!(report.training.records, 1);
+        assert_eq!(report.training.certified, vec![("GC T1".to_owned(), 1)]);
+        assert_eq!(report.file_name(), "vZDV_2026-Q3_report.txt");
+
+        let doc = report.to_document();
+        assert!(doc.contains("Period: 2026-07-01 to 2026-09-30"));
+        assert!(doc.contains("Met requirement: 1\nExempt: 1\nBelow requirement: 1\n"));
+        assert!(doc.contains("  Inactive Controller (2): 0.5 hours"));
+        assert!(doc.contains("  New Controller (3): exempt, joined during the quarter"));
+        assert!(doc.contains("2026-08: 3.3 controlling, 0.0 events, 0.5 instructing; 2 active"));
+        assert!(doc.contains("2026-08-15 FNO: 0/0 positions assigned, 0 registrations"));
+        assert!(doc.contains("Joined: 1\n  New Controller (3), 2026-08-15"));
+        assert!(doc.contains("Left: 1\n  Gone Controller (4), 2026-08-15: Left the VATUSA roster"));
+    }
+}
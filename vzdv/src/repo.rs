@@ -0,0 +1,732 @@
+//! Data access traits over the SQL in `sql`.
+//!
+//! Handlers that go through these traits instead of the pool directly can
+//! be given the in-memory implementation in tests.
+
+use crate::{
+    cert_positions::AUTHORIZING_VALUES,
+    sql::{
+        self, Certification, Controller, Event, EventPosition, Feedback, FeedbackComment,
+        FeedbackForReview,
+    },
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{QueryBuilder, Result, Sqlite, SqlitePool};
+use std::sync::{Arc, Mutex};
+
+/// Controllers per page of the roster.
+pub const ROSTER_PAGE_SIZE: u32 = 50;
+
+/// Column to sort the roster by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RosterSort {
+    #[default]
+    Cid,
+    Name,
+    Rating,
+    OperatingInitials,
+}
+
+impl RosterSort {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "cid" => Some(Self::Cid),
+            "name" => Some(Self::Name),
+            "rating" => Some(Self::Rating),
+            "ois" => Some(Self::OperatingInitials),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Cid => "cid",
+            Self::Name => "name",
+            Self::Rating => "rating",
+            Self::OperatingInitials => "ois",
+        }
+    }
+
+    /// Columns to order by, most significant first.
+    fn columns(&self) -> &'static [&'static str] {
+        match self {
+            Self::Cid => &["cid"],
+            Self::Name => &["last_name COLLATE NOCASE", "first_name COLLATE NOCASE"],
+            Self::Rating => &["rating"],
+            Self::OperatingInitials => &["operating_initials"],
+        }
+    }
+}
+
+/// Filters, sort, and page for the on-roster controllers.
+#[derive(Debug, Clone, Default)]
+pub struct RosterQuery {
+    pub rating: Option<i8>,
+    /// `Some(true)` for home controllers, `Some(false)` for visitors.
+    pub home: Option<bool>,
+    /// Only controllers solo or certified on this certification.
+    pub certification: Option<String>,
+    pub sort: RosterSort,
+    pub descending: bool,
+    /// Starting at 1.
+    pub page: u32,
+}
+
+impl RosterQuery {
+    fn offset(&self) -> u32 {
+        self.page
+            .max(1)
+            .saturating_sub(1)
+            .saturating_mul(ROSTER_PAGE_SIZE)
+    }
+
+    /// Add the query's filters as conditions after a `WHERE`.
+    fn push_filters(&self, builder: &mut QueryBuilder<'_, Sqlite>) {
+        builder.push(" WHERE is_on_roster=TRUE");
+        if let Some(rating) = self.rating {
+            builder.push(" AND rating=").push_bind(rating);
+        }
+        match self.home {
+            Some(true) => {
+                builder.push(" AND home_facility='ZDV'");
+            }
+            Some(false) => {
+                builder.push(" AND COALESCE(home_facility, '')!='ZDV'");
+            }
+            None => {}
+        }
+        if let Some(certification) = &self.certification {
+            builder
+                .push(" AND EXISTS (SELECT 1 FROM certification WHERE certification.cid=controller.cid AND certification.name=")
+                .push_bind(certification.clone())
+                .push(" AND certification.value IN (");
+            let mut values = builder.separated(", ");
+            for value in AUTHORIZING_VALUES {
+                values.push_bind(*value);
+            }
+            builder.push("))");
+        }
+    }
+}
+
+/// One page of the roster.
+#[derive(Debug, Clone, Default)]
+pub struct RosterPage {
+    pub controllers: Vec<Controller>,
+    /// Matching controllers across all pages.
+    pub total: u32,
+}
+
+#[async_trait]
+pub trait ControllerRepo: Send + Sync {
+    async fn get_by_cid(&self, cid: u32) -> Result<Option<Controller>>;
+    async fn get_by_discord_id(&self, discord_id: &str) -> Result<Option<Controller>>;
+    async fn get_all(&self) -> Result<Vec<Controller>>;
+    async fn get_on_roster(&self) -> Result<Vec<Controller>>;
+    async fn get_off_roster(&self) -> Result<Vec<Controller>>;
+    /// On-roster controllers matching the query's filters, sorted and paged.
+    async fn get_roster_page(&self, query: &RosterQuery) -> Result<RosterPage>;
+}
+
+#[async_trait]
+pub trait CertificationRepo: Send + Sync {
+    async fn get_for(&self, cid: u32) -> Result<Vec<Certification>>;
+    async fn get_for_many(&self, cids: &[u32]) -> Result<Vec<Certification>>;
+}
+
+#[async_trait]
+pub trait FeedbackRepo: Send + Sync {
+    async fn get_by_id(&self, id: u32) -> Result<Option<Feedback>>;
+    async fn get_for(&self, cid: u32) -> Result<Vec<Feedback>>;
+    async fn get_for_review(&self) -> Result<Vec<FeedbackForReview>>;
+    async fn get_all_comments(&self) -> Result<Vec<FeedbackComment>>;
+}
+
+#[async_trait]
+pub trait EventRepo: Send + Sync {
+    async fn get(&self, id: u32) -> Result<Option<Event>>;
+    /// Events that haven't ended yet, optionally including unpublished ones.
+    async fn get_upcoming(
+        &self,
+        now: DateTime<Utc>,
+        include_unpublished: bool,
+    ) -> Result<Vec<Event>>;
+    async fn get_positions(&self, event_id: u32) -> Result<Vec<EventPosition>>;
+}
+
+/// All repositories, as stored in app state.
+#[derive(Clone)]
+pub struct Repos {
+    pub controllers: Arc<dyn ControllerRepo>,
+    pub certifications: Arc<dyn CertificationRepo>,
+    pub feedback: Arc<dyn FeedbackRepo>,
+    pub events: Arc<dyn EventRepo>,
+}
+
+impl Repos {
+    /// Repositories backed by the SQLite database.
+    pub fn sqlite(db: &SqlitePool) -> Self {
+        let repo = Arc::new(SqliteRepo { db: db.clone() });
+        Self {
+            controllers: repo.clone(),
+            certifications: repo.clone(),
+            feedback: repo.clone(),
+            events: repo,
+        }
+    }
+
+    /// Repositories backed by the supplied in-memory store.
+    pub fn memory(store: Arc<MemoryRepo>) -> Self {
+        Self {
+            controllers: store.clone(),
+            certifications: store.clone(),
+            feedback: store.clone(),
+            events: store,
+        }
+    }
+}
+
+/// Implementation of the repositories with the SQLite DB.
+pub struct SqliteRepo {
+    db: SqlitePool,
+}
+
+#[async_trait]
+impl ControllerRepo for SqliteRepo {
+    async fn get_by_cid(&self, cid: u32) -> Result<Option<Controller>> {
+        sqlx::query_as(sql::GET_CONTROLLER_BY_CID)
+            .bind(cid)
+            .fetch_optional(&self.db)
+            .await
+    }
+
+    async fn get_by_discord_id(&self, discord_id: &str) -> Result<Option<Controller>> {
+        sqlx::query_as(sql::GET_CONTROLLER_BY_DISCORD_ID)
+            .bind(discord_id)
+            .fetch_optional(&self.db)
+            .await
+    }
+
+    async fn get_all(&self) -> Result<Vec<Controller>> {
+        sqlx::query_as(sql::GET_ALL_CONTROLLERS)
+            .fetch_all(&self.db)
+            .await
+    }
+
+    async fn get_on_roster(&self) -> Result<Vec<Controller>> {
+        sqlx::query_as(sql::GET_ALL_CONTROLLERS_ON_ROSTER)
+            .fetch_all(&self.db)
+            .await
+    }
+
+    async fn get_off_roster(&self) -> Result<Vec<Controller>> {
+        sqlx::query_as(sql::GET_ALL_CONTROLLERS_OFF_ROSTER)
+            .fetch_all(&self.db)
+            .await
+    }
+
+    async fn get_roster_page(&self, query: &RosterQuery) -> Result<RosterPage> {
+        let mut count = QueryBuilder::new("SELECT COUNT(*) FROM controller");
+        query.push_filters(&mut count);
+        let total: u32 = count.build_query_scalar().fetch_one(&self.db).await?;
+
+        let mut select = QueryBuilder::new("SELECT * FROM controller");
+        query.push_filters(&mut select);
+        let direction = if query.descending { " DESC" } else { " ASC" };
+        select.push(" ORDER BY ");
+        for column in query.sort.columns() {
+            select.push(column).push(direction).push(", ");
+        }
+        select
+            .push("cid LIMIT ")
+            .push_bind(ROSTER_PAGE_SIZE)
+            .push(" OFFSET ")
+            .push_bind(query.offset());
+        let controllers = select.build_query_as().fetch_all(&self.db).await?;
+        Ok(RosterPage { controllers, total })
+    }
+}
+
+#[async_trait]
+impl CertificationRepo for SqliteRepo {
+    async fn get_for(&self, cid: u32) -> Result<Vec<Certification>> {
+        sqlx::query_as(sql::GET_ALL_CERTIFICATIONS_FOR)
+            .bind(cid)
+            .fetch_all(&self.db)
+            .await
+    }
+
+    async fn get_for_many(&self, cids: &[u32]) -> Result<Vec<Certification>> {
+        if cids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut builder = QueryBuilder::new("SELECT * FROM certification WHERE cid IN (");
+        let mut separated = builder.separated(", ");
+        for cid in cids {
+            separated.push_bind(*cid);
+        }
+        builder.push(")");
+        builder.build_query_as().fetch_all(&self.db).await
+    }
+}
+
+#[async_trait]
+impl FeedbackRepo for SqliteRepo {
+    async fn get_by_id(&self, id: u32) -> Result<Option<Feedback>> {
+        sqlx::query_as(sql::GET_FEEDBACK_BY_ID)
+            .bind(id)
+            .fetch_optional(&self.db)
+            .await
+    }
+
+    async fn get_for(&self, cid: u32) -> Result<Vec<Feedback>> {
+        sqlx::query_as(sql::GET_ALL_FEEDBACK_FOR)
+            .bind(cid)
+            .fetch_all(&self.db)
+            .await
+    }
+
+    async fn get_for_review(&self) -> Result<Vec<FeedbackForReview>> {
+        sqlx::query_as(sql::GET_PENDING_FEEDBACK_FOR_REVIEW)
+            .fetch_all(&self.db)
+            .await
+    }
+
+    async fn get_all_comments(&self) -> Result<Vec<FeedbackComment>> {
+        sqlx::query_as(sql::GET_ALL_FEEDBACK_COMMENTS)
+            .fetch_all(&self.db)
+            .await
+    }
+}
+
+#[async_trait]
+impl EventRepo for SqliteRepo {
+    async fn get(&self, id: u32) -> Result<Option<Event>> {
+        sqlx::query_as(sql::GET_EVENT)
+            .bind(id)
+            .fetch_optional(&self.db)
+            .await
+    }
+
+    async fn get_upcoming(
+        &self,
+        now: DateTime<Utc>,
+        include_unpublished: bool,
+    ) -> Result<Vec<Event>> {
+        let query = if include_unpublished {
+            sql::GET_ALL_UPCOMING_EVENTS
+        } else {
+            sql::GET_UPCOMING_EVENTS
+        };
+        sqlx::query_as(query).bind(now).fetch_all(&self.db).await
+    }
+
+    async fn get_positions(&self, event_id: u32) -> Result<Vec<EventPosition>> {
+        sqlx::query_as(sql::GET_EVENT_POSITIONS)
+            .bind(event_id)
+            .fetch_all(&self.db)
+            .await
+    }
+}
+
+/// In-memory implementation of the repositories, for tests.
+///
+/// Fill the public fields directly to set up data.
+#[derive(Default)]
+pub struct MemoryRepo {
+    pub controllers: Mutex<Vec<Controller>>,
+    pub certifications: Mutex<Vec<Certification>>,
+    pub feedback: Mutex<Vec<Feedback>>,
+    pub feedback_comments: Mutex<Vec<FeedbackComment>>,
+    pub events: Mutex<Vec<Event>>,
+    pub event_positions: Mutex<Vec<EventPosition>>,
+}
+
+#[async_trait]
+impl ControllerRepo for MemoryRepo {
+    async fn get_by_cid(&self, cid: u32) -> Result<Option<Controller>> {
+        let controllers = self.controllers.lock().unwrap();
+        Ok(controllers.iter().find(|c| c.cid == cid).cloned())
+    }
+
+    async fn get_by_discord_id(&self, discord_id: &str) -> Result<Option<Controller>> {
+        let controllers = self.controllers.lock().unwrap();
+        Ok(controllers
+            .iter()
+            .find(|c| c.discord_id.as_deref() == Some(discord_id))
+            .cloned())
+    }
+
+    async fn get_all(&self) -> Result<Vec<Controller>> {
+        Ok(self.controllers.lock().unwrap().clone())
+    }
+
+    async fn get_on_roster(&self) -> Result<Vec<Controller>> {
+        let controllers = self.controllers.lock().unwrap();
+        Ok(controllers
+            .iter()
+            .filter(|c| c.is_on_roster)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_off_roster(&self) -> Result<Vec<Controller>> {
+        let controllers = self.controllers.lock().unwrap();
+        Ok(controllers
+            .iter()
+            .filter(|c| !c.is_on_roster)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_roster_page(&self, query: &RosterQuery) -> Result<RosterPage> {
+        let controllers = self.controllers.lock().unwrap();
+        let certifications = self.certifications.lock().unwrap();
+        let mut matching: Vec<Controller> = controllers
+            .iter()
+            .filter(|c| c.is_on_roster)
+            .filter(|c| query.rating.is_none_or(|rating| c.rating == rating))
+            .filter(|c| {
+                query
+                    .home
+                    .is_none_or(|home| (c.home_facility == "ZDV") == home)
+            })
+            .filter(|c| {
+                query.certification.as_ref().is_none_or(|name| {
+                    certifications.iter().any(|cert| {
+                        cert.cid == c.cid
+                            && &cert.name == name
+                            && AUTHORIZING_VALUES.contains(&cert.value.as_str())
+                    })
+                })
+            })
+            .cloned()
+            .collect();
+        matching.sort_by(|a, b| {
+            let ordering = match query.sort {
+                RosterSort::Cid => std::cmp::Ordering::Equal,
+                RosterSort::Name => (a.last_name.to_lowercase(), a.first_name.to_lowercase())
+                    .cmp(&(b.last_name.to_lowercase(), b.first_name.to_lowercase())),
+                RosterSort::Rating => a.rating.cmp(&b.rating),
+                RosterSort::OperatingInitials => a.operating_initials.cmp(&b.operating_initials),
+            };
+            let ordering = if query.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            };
+            // ties are always broken by ascending CID, like the SQL, except when sorting by it
+            match (query.sort, query.descending) {
+                (RosterSort::Cid, true) => b.cid.cmp(&a.cid),
+                _ => ordering.then(a.cid.cmp(&b.cid)),
+            }
+        });
+        let total = matching.len() as u32;
+        let controllers = matching
+            .into_iter()
+            .skip(query.offset() as usize)
+            .take(ROSTER_PAGE_SIZE as usize)
+            .collect();
+        Ok(RosterPage { controllers, total })
+    }
+}
+
+#[async_trait]
+impl CertificationRepo for MemoryRepo {
+    async fn get_for(&self, cid: u32) -> Result<Vec<Certification>> {
+        let certifications = self.certifications.lock().unwrap();
+        Ok(certifications
+            .iter()
+            .filter(|c| c.cid == cid)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_for_many(&self, cids: &[u32]) -> Result<Vec<Certification>> {
+        let certifications = self.certifications.lock().unwrap();
+        Ok(certifications
+            .iter()
+            .filter(|c| cids.contains(&c.cid))
+            .cloned()
+            .collect())
+    }
+}
+
+#[async_trait]
+impl FeedbackRepo for MemoryRepo {
+    async fn get_by_id(&self, id: u32) -> Result<Option<Feedback>> {
+        let feedback = self.feedback.lock().unwrap();
+        Ok(feedback.iter().find(|f| f.id == id).cloned())
+    }
+
+    async fn get_for(&self, cid: u32) -> Result<Vec<Feedback>> {
+        let feedback = self.feedback.lock().unwrap();
+        Ok(feedback
+            .iter()
+            .filter(|f| f.controller == cid)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_for_review(&self) -> Result<Vec<FeedbackForReview>> {
+        let feedback = self.feedback.lock().unwrap();
+        let controllers = self.controllers.lock().unwrap();
+        Ok(feedback
+            .iter()
+            .map(|f| {
+                let controller = controllers.iter().find(|c| c.cid == f.controller);
+                FeedbackForReview {
+                    id: f.id,
+                    first_name: controller.map(|c| c.first_name.clone()).unwrap_or_default(),
+                    last_name: controller.map(|c| c.last_name.clone()).unwrap_or_default(),
+                    position: f.position.clone(),
+                    rating: f.rating.clone(),
+                    comments: f.comments.clone(),
+                    created_date: f.created_date,
+                    submitter_cid: f.submitter_cid,
+                    reviewer_action: f.reviewer_action.clone(),
+                    assigned_to_cid: f.assigned_to_cid,
+                }
+            })
+            .collect())
+    }
+
+    async fn get_all_comments(&self) -> Result<Vec<FeedbackComment>> {
+        Ok(self.feedback_comments.lock().unwrap().clone())
+    }
+}
+
+#[async_trait]
+impl EventRepo for MemoryRepo {
+    async fn get(&self, id: u32) -> Result<Option<Event>> {
+        let events = self.events.lock().unwrap();
+        Ok(events.iter().find(|e| e.id == id).cloned())
+    }
+
+    async fn get_upcoming(
+        &self,
+        now: DateTime<Utc>,
+        include_unpublished: bool,
+    ) -> Result<Vec<Event>> {
+        let events = self.events.lock().unwrap();
+        Ok(events
+            .iter()
+            .filter(|e| e.end > now && (include_unpublished || e.published))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_positions(&self, event_id: u32) -> Result<Vec<EventPosition>> {
+        let positions = self.event_positions.lock().unwrap();
+        Ok(positions
+            .iter()
+            .filter(|p| p.event_id == event_id)
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{MemoryRepo, Repos, RosterQuery, RosterSort, ROSTER_PAGE_SIZE};
+    use crate::sql::{self, Certification, Controller};
+    use chrono::Utc;
+    use sqlx::{sqlite::SqlitePoolOptions, Executor};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_sqlite_and_memory_agree() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        pool.execute(sql::CREATE_TABLES).await.unwrap();
+        pool.execute(
+            "INSERT INTO controller (cid, first_name, last_name, rating, status, home_facility, is_on_roster, roles) VALUES
+                (1, 'A', 'B', 5, '', 'ZDV', TRUE, ''),
+                (2, 'C', 'D', 3, '', '', FALSE, '')",
+        )
+        .await
+        .unwrap();
+        let store = Arc::new(MemoryRepo::default());
+        *store.controllers.lock().unwrap() = vec![
+            Controller {
+                cid: 1,
+                is_on_roster: true,
+                ..Default::default()
+            },
+            Controller {
+                cid: 2,
+                ..Default::default()
+            },
+        ];
+
+        for repos in [Repos::sqlite(&pool), Repos::memory(store)] {
+            let on: Vec<_> = repos
+                .controllers
+                .get_on_roster()
+                .await
+                .unwrap()
+                .iter()
+                .map(|c| c.cid)
+                .collect();
+            let off: Vec<_> = repos
+                .controllers
+                .get_off_roster()
+                .await
+                .unwrap()
+                .iter()
+                .map(|c| c.cid)
+                .collect();
+            assert_eq!(on, vec![1]);
+            assert_eq!(off, vec![2]);
+            assert!(repos.controllers.get_by_cid(3).await.unwrap().is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_roster_page() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        pool.execute(sql::CREATE_TABLES).await.unwrap();
+        let store = Arc::new(MemoryRepo::default());
+        // enough controllers for a second page
+        let controllers: Vec<Controller> = (1..=ROSTER_PAGE_SIZE + 5)
+            .map(|cid| Controller {
+                cid,
+                first_name: format!("First{cid}"),
+                last_name: format!("Last{:03}", 1000 - cid),
+                rating: if cid % 2 == 0 { 5 } else { 3 },
+                home_facility: if cid % 3 == 0 { "ZTL" } else { "ZDV" }.to_owned(),
+                is_on_roster: cid != 4,
+                ..Default::default()
+            })
+            .collect();
+        for c in &controllers {
+            sqlx::query("INSERT INTO controller (cid, first_name, last_name, rating, status, home_facility, is_on_roster, roles) VALUES ($1, $2, $3, $4, '', $5, $6, '')")
+                .bind(c.cid)
+                .bind(&c.first_name)
+                .bind(&c.last_name)
+                .bind(c.rating)
+                .bind(&c.home_facility)
+                .bind(c.is_on_roster)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        let certifications: Vec<Certification> = [(2, "certified"), (6, "solo"), (8, "training")]
+            .into_iter()
+            .map(|(cid, value)| Certification {
+                id: cid,
+                cid,
+                name: "GC T1".to_owned(),
+                value: value.to_owned(),
+                changed_on: Utc::now(),
+                set_by: 1,
+            })
+            .collect();
+        for cert in &certifications {
+            sqlx::query(sql::CREATE_CERTIFICATION)
+                .bind(cert.cid)
+                .bind(&cert.name)
+                .bind(&cert.value)
+                .bind(cert.changed_on)
+                .bind(cert.set_by)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        *store.controllers.lock().unwrap() = controllers;
+        *store.certifications.lock().unwrap() = certifications;
+
+        for repos in [Repos::sqlite(&pool), Repos::memory(store)] {
+            let cids = |page: &super::RosterPage| -> Vec<u32> {
+                page.controllers.iter().map(|c| c.cid).collect()
+            };
+
+            let first = repos
+                .controllers
+                .get_roster_page(&RosterQuery::default())
+                .await
+                .unwrap();
+            assert_eq!(first.total, ROSTER_PAGE_SIZE + 4);
+            assert_eq!(first.controllers.len(), ROSTER_PAGE_SIZE as usize);
+            assert_eq!(&cids(&first)[..4], &[1, 2, 3, 5]);
+            let second = repos
+                .controllers
+                .get_roster_page(&RosterQuery {
+                    page: 2,
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            assert_eq!(cids(&second), vec![52, 53, 54, 55]);
+            // a page too far out to compute the offset for is just empty
+            let past_end = repos
+                .controllers
+                .get_roster_page(&RosterQuery {
+                    page: u32::MAX,
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            assert!(past_end.controllers.is_empty());
+
+            let filtered = repos
+                .controllers
+                .get_roster_page(&RosterQuery {
+                    rating: Some(5),
+                    home: Some(true),
+                    certification: Some("GC T1".to_owned()),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            assert_eq!(cids(&filtered), vec![2]);
+            let visitors = repos
+                .controllers
+                .get_roster_page(&RosterQuery {
+                    home: Some(false),
+                    certification: Some("GC T1".to_owned()),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            assert_eq!(cids(&visitors), vec![6]);
+
+            let by_name = repos
+                .controllers
+                .get_roster_page(&RosterQuery {
+                    sort: RosterSort::Name,
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            assert_eq!(&cids(&by_name)[..2], &[55, 54]);
+            let by_rating = repos
+                .controllers
+                .get_roster_page(&RosterQuery {
+                    sort: RosterSort::Rating,
+                    descending: true,
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            assert_eq!(&cids(&by_rating)[..2], &[2, 6]);
+
+            let certs = repos.certifications.get_for_many(&[2, 8, 9]).await.unwrap();
+            assert_eq!(certs.len(), 2);
+            assert!(repos
+                .certifications
+                .get_for_many(&[])
+                .await
+                .unwrap()
+                .is_empty());
+        }
+    }
+}
@@ -0,0 +1,33 @@
+//! ID of the site request currently being handled.
+//!
+//! The site's middleware runs each request within `REQUEST_ID`'s scope, so
+//! anything called while handling it can find the ID: log lines, error
+//! pages, and calls out to VATUSA and VATSIM.
+
+use reqwest::RequestBuilder;
+
+/// Header used both for the site's responses and outbound API calls.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    pub static REQUEST_ID: String;
+}
+
+/// The current request's ID, if running within one.
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Adds the current request's ID to outbound HTTP requests.
+pub trait WithRequestId {
+    fn with_request_id(self) -> Self;
+}
+
+impl WithRequestId for RequestBuilder {
+    fn with_request_id(self) -> Self {
+        match current() {
+            Some(id) => self.header(REQUEST_ID_HEADER, id),
+            None => self,
+        }
+    }
+}
@@ -0,0 +1,21 @@
+//! Who can see the resources in each category.
+
+/// Visibility levels for resource categories, as stored in the DB.
+pub mod visibility {
+    /// Anyone, including visitors who aren't logged in.
+    pub const PUBLIC: &str = "public";
+    /// Controllers on the roster.
+    pub const ROSTER: &str = "roster";
+    /// Staff members.
+    pub const STAFF: &str = "staff";
+    /// Training staff members, for documents like OTS checklists.
+    pub const TRAINING_STAFF: &str = "training_staff";
+
+    /// Every level with its display name, from least to most restricted.
+    pub const ALL: [(&str, &str); 4] = [
+        (PUBLIC, "Public"),
+        (ROSTER, "Roster only"),
+        (STAFF, "Staff only"),
+        (TRAINING_STAFF, "Training staff only"),
+    ];
+}
@@ -6,6 +6,10 @@ use sqlx::{
 
 // Note: SQLite doesn't support u64.
 
+// These queries are bound at runtime, so they're only checked by the tests that
+// run them. The task runner, bot, and importer write their queries inline with the
+// compile-time checked `sqlx::query!` macros instead; see `ops/prepare_queries.sh`.
+
 #[derive(Debug, FromRow, Serialize, Clone, Default)]
 pub struct Controller {
     pub id: u32,
@@ -34,6 +38,19 @@ pub struct Certification {
     pub set_by: u32,
 }
 
+/// A single change to a controller's certification.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct CertificationHistory {
+    pub id: u32,
+    pub cid: u32,
+    pub name: String,
+    /// `None` for the first value recorded.
+    pub old_value: Option<String>,
+    pub new_value: String,
+    pub changed_by: u32,
+    pub changed_on: DateTime<Utc>,
+}
+
 /// Requires joining the `controller` column for the name.
 #[derive(Debug, FromRow, Serialize)]
 pub struct Activity {
@@ -43,9 +60,12 @@ pub struct Activity {
     pub last_name: String,
     pub month: String,
     pub minutes: u32,
+    /// "controlling" for time from VATSIM, "event" for credited event
+    /// hours, or "training" for time spent instructing in training sessions.
+    pub source: String,
 }
 
-#[derive(Debug, FromRow, Serialize)]
+#[derive(Debug, FromRow, Serialize, Clone)]
 pub struct Feedback {
     pub id: u32,
     pub controller: u32,
@@ -57,6 +77,7 @@ pub struct Feedback {
     pub reviewed_by_cid: u32,
     pub reviewer_action: String,
     pub posted_to_discord: bool,
+    pub assigned_to_cid: Option<u32>,
 }
 
 #[derive(Debug, FromRow, Serialize)]
@@ -70,6 +91,19 @@ pub struct FeedbackForReview {
     pub created_date: DateTime<Utc>,
     pub submitter_cid: u32,
     pub reviewer_action: String,
+    pub assigned_to_cid: Option<u32>,
+}
+
+/// Entry in a feedback item's review history.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct FeedbackComment {
+    pub id: u32,
+    pub feedback_id: u32,
+    pub cid: u32,
+    pub comment: String,
+    /// Set if this entry records the feedback being assigned rather than a comment.
+    pub assigned_to_cid: Option<u32>,
+    pub created_date: DateTime<Utc>,
 }
 
 #[derive(Debug, FromRow, Serialize, Default)]
@@ -80,6 +114,23 @@ pub struct Resource {
     pub file_name: Option<String>,
     pub link: Option<String>,
     pub updated: DateTime<Utc>,
+    /// Times the file has been downloaded or viewed, or the link followed, through the site.
+    pub download_count: u32,
+    pub last_downloaded: Option<DateTime<Utc>>,
+}
+
+/// Grouping for resources on the resources page.
+///
+/// Resources refer to their category by name.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct ResourceCategory {
+    pub id: u32,
+    pub name: String,
+    pub sort_order: u32,
+    /// Hidden from the resources page, and not offered for new resources.
+    pub archived: bool,
+    /// One of `vzdv::resources::visibility`.
+    pub visibility: String,
 }
 
 #[derive(Debug, FromRow, Serialize)]
@@ -91,9 +142,13 @@ pub struct VisitorRequest {
     pub home_facility: String,
     pub rating: u8,
     pub date: DateTime<Utc>,
+    /// "pending" or "hold".
+    pub status: String,
+    /// Notes for staff; not shown to the applicant.
+    pub notes: String,
 }
 
-#[derive(Debug, FromRow, Serialize)]
+#[derive(Debug, FromRow, Serialize, Clone)]
 pub struct Event {
     pub id: u32,
     pub published: bool,
@@ -102,9 +157,13 @@ pub struct Event {
     pub end: DateTime<Utc>,
     pub description: Option<String>,
     pub image_url: Option<String>,
+    /// Facility ID, like "ZLC", of the ARTCC the event is held jointly with.
+    pub partner_facility: Option<String>,
+    /// Where controllers sign up for the partner's side of a joint event.
+    pub external_signup_url: Option<String>,
 }
 
-#[derive(Debug, FromRow, Serialize)]
+#[derive(Debug, FromRow, Serialize, Clone)]
 pub struct EventPosition {
     pub id: u32,
     pub event_id: u32,
@@ -113,6 +172,50 @@ pub struct EventPosition {
     pub cid: Option<u32>,
 }
 
+/// Most controllers that can sign up for an event's positions in the category.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct EventCategoryLimit {
+    pub event_id: u32,
+    pub category: String,
+    pub max_signups: u32,
+}
+
+/// Controller waiting for a position in a category of an event's positions.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct EventWaitlistEntry {
+    pub id: u32,
+    pub event_id: u32,
+    pub cid: u32,
+    pub category: String,
+    pub joined_date: DateTime<Utc>,
+    /// Set when they're assigned to a freed position.
+    pub promoted_date: Option<DateTime<Utc>>,
+}
+
+/// Event position a controller is assigned to, with the event's details.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct EventAssignment {
+    pub event_id: u32,
+    pub event_name: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub position: String,
+}
+
+/// Discord channel the bot creates for the duration of an event.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct EventChannel {
+    pub id: u32,
+    pub event_id: u32,
+    pub name: String,
+    /// Voice channel if true, text channel otherwise.
+    pub voice: bool,
+    /// Set once the bot creates the channel.
+    pub channel_id: Option<String>,
+    pub created_date: Option<DateTime<Utc>>,
+    pub deleted_date: Option<DateTime<Utc>>,
+}
+
 #[derive(Debug, FromRow, Serialize)]
 pub struct EventRegistration {
     pub id: u32,
@@ -133,8 +236,357 @@ pub struct StaffNote {
     pub comment: String,
 }
 
+#[derive(Debug, FromRow, Serialize)]
+pub struct TrainingRecord {
+    pub id: u32,
+    pub cid: u32,
+    pub instructor_cid: u32,
+    pub position: String,
+    pub date: DateTime<Utc>,
+    pub notes: String,
+    /// ID of the record on the old site, if imported from it.
+    pub legacy_id: Option<u32>,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct SoloCert {
+    pub id: u32,
+    pub cid: u32,
+    pub issued_by: u32,
+    pub position: String,
+    pub reported: bool,
+    pub created_date: DateTime<Utc>,
+    pub expiration_date: DateTime<Utc>,
+    /// Days-before-expiration of the last reminder sent.
+    pub reminder_days: Option<u32>,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct Loa {
+    pub id: u32,
+    pub cid: u32,
+    pub start_date: DateTime<Utc>,
+    pub end_date: DateTime<Utc>,
+    pub reason: String,
+    pub created_by: u32,
+    pub created_date: DateTime<Utc>,
+    /// Set when staff end the LOA before its original end date.
+    pub ended_by: Option<u32>,
+}
+
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct Banner {
+    pub id: u32,
+    pub message: String,
+    /// "info", "warning", or "emergency".
+    pub level: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub created_by: u32,
+}
+
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct Block {
+    pub id: u32,
+    /// "ip" or "cid".
+    pub kind: String,
+    /// An IP address or CIDR range, or a controller CID.
+    pub value: String,
+    pub reason: String,
+    pub created_by: u32,
+    pub created_date: DateTime<Utc>,
+    /// The block never expires if not set.
+    pub expires: Option<DateTime<Utc>>,
+    pub hits: u32,
+    pub last_hit: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct RememberToken {
+    pub id: u32,
+    pub selector: String,
+    /// SHA-256 of the token's secret half, hex-encoded.
+    pub validator_hash: String,
+    pub cid: u32,
+    pub created_date: DateTime<Utc>,
+    pub expires: DateTime<Utc>,
+    pub last_used: Option<DateTime<Utc>>,
+    /// Hash of the validator replaced by the last rotation.
+    pub previous_validator_hash: Option<String>,
+    pub rotated_date: Option<DateTime<Utc>>,
+}
+
+/// Welcome messages for a controller new to the roster, sent once each.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct WelcomeMessage {
+    pub cid: u32,
+    pub queued_date: DateTime<Utc>,
+    /// Also set if the controller had no email address to send to.
+    pub email_sent_date: Option<DateTime<Utc>>,
+    pub discord_sent_date: Option<DateTime<Utc>>,
+}
+
+/// Lesson plan in a certification's training syllabus.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct TrainingLesson {
+    pub id: u32,
+    /// One of the config's `training.certifications`.
+    pub certification: String,
+    pub sort_order: u32,
+    pub name: String,
+    /// What the student should be able to do after the lesson.
+    pub objectives: String,
+    /// Whether completing the lesson marks a checkpoint, like being ready for an OTS.
+    pub milestone: bool,
+    pub updated_by: u32,
+    pub updated_date: DateTime<Utc>,
+}
+
+/// Training session in which a mentor covered a syllabus lesson with a student.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct TrainingLessonSession {
+    pub id: u32,
+    pub lesson_id: u32,
+    pub cid: u32,
+    pub instructor_cid: u32,
+    pub date: DateTime<Utc>,
+}
+
+/// Training session booked between a student and a mentor.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct TrainingSession {
+    pub id: u32,
+    pub cid: u32,
+    pub instructor_cid: u32,
+    pub position: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub notes: String,
+    /// Incremented on each change so calendar apps replace their copy.
+    pub sequence: u32,
+    pub cancelled: bool,
+    pub created_date: DateTime<Utc>,
+    pub updated_date: DateTime<Utc>,
+}
+
+/// Controller waiting for a training slot in a certification.
+///
+/// Requires joining the `controller` table, and the activity and training
+/// record summaries used for prioritizing.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct WaitlistEntry {
+    pub id: u32,
+    pub cid: u32,
+    pub certification: String,
+    pub joined_date: DateTime<Utc>,
+    pub promoted_date: Option<DateTime<Utc>>,
+    pub first_name: String,
+    pub last_name: String,
+    pub join_date: Option<DateTime<Utc>>,
+    /// Minutes since the activity month passed to the query.
+    pub recent_minutes: u32,
+    /// Date of the controller's latest training record.
+    pub last_session: Option<DateTime<Utc>>,
+}
+
+/// Student's feedback on a training session, requested when the record is filed.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct TrainingFeedback {
+    pub id: u32,
+    pub cid: u32,
+    pub instructor_cid: u32,
+    pub position: String,
+    pub session_date: DateTime<Utc>,
+    pub requested_date: DateTime<Utc>,
+    /// 1 to 5, once submitted.
+    pub rating: Option<u8>,
+    pub comments: Option<String>,
+    pub submitted_date: Option<DateTime<Utc>>,
+}
+
+/// Controller to re-sync from VATUSA, queued by a VATUSA webhook.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct RosterRefresh {
+    pub cid: u32,
+    /// What VATUSA said changed.
+    pub reason: String,
+    pub queued_date: DateTime<Utc>,
+}
+
+/// A user's approval for a facility tool to sign them in.
+///
+/// Starts with a single-use code, which the tool exchanges for an access token.
+/// Only hashes of the code and token are stored.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct OAuthGrant {
+    pub id: u32,
+    pub client_id: String,
+    pub cid: u32,
+    /// Space-separated, like "openid email".
+    pub scope: String,
+    pub nonce: Option<String>,
+    pub redirect_uri: String,
+    /// Cleared once the code is exchanged.
+    pub code_hash: Option<String>,
+    pub code_expires: DateTime<Utc>,
+    pub token_hash: Option<String>,
+    pub token_expires: Option<DateTime<Utc>>,
+    pub created_date: DateTime<Utc>,
+}
+
+/// Survey sent to a controller after they leave the roster.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct ExitSurvey {
+    pub id: u32,
+    pub cid: u32,
+    /// How they left, for staff; not shown to the controller.
+    pub departure: String,
+    pub created_date: DateTime<Utc>,
+    /// Set when the survey is emailed.
+    pub token: Option<String>,
+    /// Also set if the controller had no email address to send to.
+    pub email_sent_date: Option<DateTime<Utc>>,
+    pub submitted_date: Option<DateTime<Utc>>,
+    /// 1 to 5.
+    pub rating: Option<u8>,
+    pub reason: Option<String>,
+    pub comments: Option<String>,
+}
+
+/// A controller's choices for what the site sends them.
+#[derive(Debug, FromRow, Serialize, Clone, Default)]
+pub struct NotificationPreference {
+    pub cid: u32,
+    /// Email approved positive feedback to the controller.
+    pub feedback_email: bool,
+    /// DM approved positive feedback to the controller on Discord.
+    pub feedback_discord: bool,
+}
+
+/// Short-lived code for linking a Discord account through the bot.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct DiscordLinkCode {
+    pub cid: u32,
+    pub code: String,
+    pub expires: DateTime<Utc>,
+}
+
+/// Controller who didn't meet the activity requirement in a quarter, for staff to review.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct PurgeCandidate {
+    pub id: u32,
+    pub cid: u32,
+    /// Like "2026-Q3".
+    pub quarter: String,
+    /// Activity in the quarter, from all sources.
+    pub minutes: u32,
+    pub created_date: DateTime<Utc>,
+    /// Staff chose to keep the controller on the roster.
+    pub excluded: bool,
+    pub removed_date: Option<DateTime<Utc>>,
+    pub removed_by: Option<u32>,
+    /// Why the last removal attempt failed.
+    pub error: Option<String>,
+    /// Staff approved the removal, which hasn't been done yet if `removed_date` is empty.
+    pub approved_date: Option<DateTime<Utc>>,
+    pub approved_by: Option<u32>,
+    /// Why the removal was skipped when it came up, leaving the candidate excluded.
+    pub skip_reason: Option<String>,
+}
+
+/// Controlling session from the VATSIM datafeed.
+///
+/// Only kept until the VATSIM activity sync includes the session.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct ControllerSession {
+    pub id: u32,
+    pub cid: u32,
+    pub callsign: String,
+    pub logon_time: DateTime<Utc>,
+    /// When the session was last in the datafeed.
+    pub last_seen: DateTime<Utc>,
+}
+
+/// A controller's minutes in a month, by source; from `activity_month_summary`.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct ActivityMonthSummary {
+    pub cid: u32,
+    pub month: String,
+    pub controlling: u32,
+    pub event: u32,
+    pub training: u32,
+}
+
+/// Facility hours in a month, by source; from the stats rollup.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct StatsMonthHours {
+    pub month: String,
+    pub controlling: u32,
+    pub event: u32,
+    pub training: u32,
+    /// Controllers with any activity in the month.
+    pub controllers: u32,
+}
+
+/// Feedback submitted in a month with one rating; from the stats rollup.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct StatsMonthFeedback {
+    pub month: String,
+    pub rating: String,
+    pub count: u32,
+}
+
+/// Staffing and signups for a published event; from the stats rollup.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct StatsEvent {
+    pub event_id: u32,
+    pub name: String,
+    pub start: DateTime<Utc>,
+    pub positions: u32,
+    pub assigned: u32,
+    pub registrations: u32,
+}
+
+/// Controller's placing by controlling time in a month; from the stats rollup.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct StatsLeaderboardEntry {
+    pub month: String,
+    pub rank: u32,
+    pub cid: u32,
+    pub minutes: u32,
+    pub first_name: String,
+    pub last_name: String,
+}
+
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct RolePermission {
+    pub role: String,
+    pub permission: String,
+}
+
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct ControllerPermission {
+    pub cid: u32,
+    pub permission: String,
+    /// Whether the capability is granted, or denied regardless of the controller's roles.
+    pub granted: bool,
+}
+
+/// A staff member viewing the site as another controller.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct ImpersonationLogEntry {
+    pub id: u32,
+    pub staff_cid: u32,
+    pub viewed_cid: u32,
+    pub started_date: DateTime<Utc>,
+    /// Not set if the staff member never stopped, like when their session expired.
+    pub ended_date: Option<DateTime<Utc>>,
+}
+
 /// Statements to create tables. Only ran when the DB file does not exist,
-/// so no migration or "IF NOT EXISTS" conditions need to be added.
+/// so no "IF NOT EXISTS" conditions need to be added.
+///
+/// This is the base schema; changes to it go in `MIGRATIONS`.
 pub const CREATE_TABLES: &str = r#"
 CREATE TABLE controller (
     id INTEGER PRIMARY KEY NOT NULL,
@@ -256,6 +708,580 @@ CREATE TABLE staff_note (
 ) STRICT;
 "#;
 
+/// Schema changes to apply on top of `CREATE_TABLES`, in order.
+///
+/// The database's `user_version` pragma stores how many of these have
+/// been applied. Only ever append to this list.
+pub const MIGRATIONS: &[&str] = &[
+    // 1: solo certs
+    "
+CREATE TABLE IF NOT EXISTS solo_cert (
+    id INTEGER PRIMARY KEY NOT NULL,
+    cid INTEGER NOT NULL,
+    issued_by INTEGER NOT NULL,
+    position TEXT NOT NULL,
+    reported INTEGER NOT NULL DEFAULT FALSE,
+    created_date TEXT NOT NULL,
+    expiration_date TEXT NOT NULL,
+
+    FOREIGN KEY (cid) REFERENCES controller(cid),
+    FOREIGN KEY (issued_by) REFERENCES controller(cid)
+) STRICT;
+",
+    // 2: indexes for the roster, activity, and event pages
+    "
+CREATE INDEX IF NOT EXISTS activity_cid_month ON activity (cid, month);
+CREATE INDEX IF NOT EXISTS activity_month ON activity (month);
+CREATE INDEX IF NOT EXISTS feedback_controller ON feedback (controller);
+CREATE INDEX IF NOT EXISTS certification_cid ON certification (cid);
+CREATE INDEX IF NOT EXISTS event_position_event_id ON event_position (event_id);
+CREATE INDEX IF NOT EXISTS controller_discord_id ON controller (discord_id);
+CREATE INDEX IF NOT EXISTS event_end ON event (end);
+",
+    // 3: training records
+    "
+CREATE TABLE IF NOT EXISTS training_record (
+    id INTEGER PRIMARY KEY NOT NULL,
+    cid INTEGER NOT NULL,
+    instructor_cid INTEGER NOT NULL,
+    position TEXT NOT NULL,
+    date TEXT NOT NULL,
+    notes TEXT NOT NULL,
+    legacy_id INTEGER UNIQUE,
+
+    FOREIGN KEY (cid) REFERENCES controller(cid)
+) STRICT;
+CREATE INDEX IF NOT EXISTS training_record_cid ON training_record (cid);
+",
+    // 4: feedback assignment and reviewer comments
+    "
+ALTER TABLE feedback ADD COLUMN assigned_to_cid INTEGER;
+CREATE TABLE IF NOT EXISTS feedback_comment (
+    id INTEGER PRIMARY KEY NOT NULL,
+    feedback_id INTEGER NOT NULL,
+    cid INTEGER NOT NULL,
+    comment TEXT NOT NULL,
+    assigned_to_cid INTEGER,
+    created_date TEXT NOT NULL,
+
+    FOREIGN KEY (feedback_id) REFERENCES feedback(id) ON DELETE CASCADE
+) STRICT;
+CREATE INDEX IF NOT EXISTS feedback_comment_feedback_id ON feedback_comment (feedback_id);
+",
+    // 5: visitor request hold status and staff notes
+    "
+ALTER TABLE visitor_request ADD COLUMN status TEXT NOT NULL DEFAULT 'pending';
+ALTER TABLE visitor_request ADD COLUMN notes TEXT NOT NULL DEFAULT '';
+",
+    // 6: leave of absence history
+    "
+CREATE TABLE IF NOT EXISTS loa (
+    id INTEGER PRIMARY KEY NOT NULL,
+    cid INTEGER NOT NULL,
+    start_date TEXT NOT NULL,
+    end_date TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    created_by INTEGER NOT NULL,
+    created_date TEXT NOT NULL,
+    ended_by INTEGER,
+
+    FOREIGN KEY (cid) REFERENCES controller(cid)
+) STRICT;
+CREATE INDEX IF NOT EXISTS loa_cid ON loa (cid);
+CREATE INDEX IF NOT EXISTS loa_end_date ON loa (end_date);
+",
+    // 7: activity credited from events and training
+    "
+ALTER TABLE activity ADD COLUMN source TEXT NOT NULL DEFAULT 'controlling';
+ALTER TABLE activity ADD COLUMN event_id INTEGER;
+",
+    // 8: site banners
+    "
+CREATE TABLE IF NOT EXISTS banner (
+    id INTEGER PRIMARY KEY NOT NULL,
+    message TEXT NOT NULL,
+    level TEXT NOT NULL,
+    start TEXT NOT NULL,
+    end TEXT NOT NULL,
+    created_by INTEGER NOT NULL
+) STRICT;
+",
+    // 9: site settings changeable at runtime
+    "
+CREATE TABLE IF NOT EXISTS setting (
+    name TEXT PRIMARY KEY NOT NULL,
+    value TEXT NOT NULL
+) STRICT;
+",
+    // 10: blocklist for abusive submitters
+    "
+CREATE TABLE IF NOT EXISTS block (
+    id INTEGER PRIMARY KEY NOT NULL,
+    kind TEXT NOT NULL,
+    value TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    created_by INTEGER NOT NULL,
+    created_date TEXT NOT NULL,
+    expires TEXT,
+    hits INTEGER NOT NULL DEFAULT 0,
+    last_hit TEXT
+) STRICT;
+",
+    // 11: "keep me signed in" tokens
+    "
+CREATE TABLE IF NOT EXISTS remember_token (
+    id INTEGER PRIMARY KEY NOT NULL,
+    selector TEXT NOT NULL UNIQUE,
+    validator_hash TEXT NOT NULL,
+    cid INTEGER NOT NULL,
+    created_date TEXT NOT NULL,
+    expires TEXT NOT NULL,
+    last_used TEXT,
+
+    FOREIGN KEY (cid) REFERENCES controller(cid)
+) STRICT;
+",
+    // 12: capability-based permissions, defaulting to the old fixed groups
+    "
+CREATE TABLE IF NOT EXISTS role_permission (
+    role TEXT NOT NULL,
+    permission TEXT NOT NULL,
+
+    PRIMARY KEY (role, permission)
+) STRICT;
+CREATE TABLE IF NOT EXISTS controller_permission (
+    cid INTEGER NOT NULL,
+    permission TEXT NOT NULL,
+    granted INTEGER NOT NULL,
+
+    PRIMARY KEY (cid, permission),
+    FOREIGN KEY (cid) REFERENCES controller(cid)
+) STRICT;
+INSERT INTO role_permission VALUES ('ATM', 'staff'), ('DATM', 'staff'), ('TA', 'staff'), ('FE', 'staff'), ('EC', 'staff'), ('WM', 'staff'), ('AFE', 'staff'), ('AEC', 'staff'), ('AWM', 'staff'), ('INS', 'staff'), ('MTR', 'staff');
+INSERT INTO role_permission VALUES ('ATM', 'events.manage'), ('DATM', 'events.manage'), ('WM', 'events.manage'), ('EC', 'events.manage'), ('AEC', 'events.manage');
+INSERT INTO role_permission VALUES ('ATM', 'training.manage'), ('DATM', 'training.manage'), ('WM', 'training.manage'), ('TA', 'training.manage'), ('INS', 'training.manage'), ('MTR', 'training.manage');
+INSERT INTO role_permission VALUES ('ATM', 'resources.manage'), ('DATM', 'resources.manage'), ('WM', 'resources.manage'), ('TA', 'resources.manage'), ('FE', 'resources.manage'), ('EC', 'resources.manage');
+INSERT INTO role_permission VALUES ('ATM', 'feedback.manage'), ('DATM', 'feedback.manage'), ('WM', 'feedback.manage');
+INSERT INTO role_permission VALUES ('ATM', 'roster.manage'), ('DATM', 'roster.manage'), ('WM', 'roster.manage');
+INSERT INTO role_permission VALUES ('ATM', 'roles.manage'), ('DATM', 'roles.manage'), ('WM', 'roles.manage');
+INSERT INTO role_permission VALUES ('ATM', 'visitors.manage'), ('DATM', 'visitors.manage'), ('WM', 'visitors.manage');
+INSERT INTO role_permission VALUES ('ATM', 'loa.manage'), ('DATM', 'loa.manage'), ('WM', 'loa.manage');
+INSERT INTO role_permission VALUES ('ATM', 'email.send'), ('DATM', 'email.send'), ('WM', 'email.send');
+INSERT INTO role_permission VALUES ('ATM', 'site.admin'), ('DATM', 'site.admin'), ('WM', 'site.admin');
+INSERT INTO role_permission VALUES ('ATM', 'permissions.manage'), ('DATM', 'permissions.manage'), ('WM', 'permissions.manage');
+INSERT INTO role_permission VALUES ('ATM', 'impersonate'), ('WM', 'impersonate');
+",
+    // 13: the facility engineering team manages resources, so include the AFE
+    "
+INSERT OR IGNORE INTO role_permission VALUES ('AFE', 'resources.manage');
+",
+    // 14: welcome messages for new controllers
+    "
+CREATE TABLE IF NOT EXISTS welcome_message (
+    cid INTEGER PRIMARY KEY NOT NULL,
+    queued_date TEXT NOT NULL,
+    email_sent_date TEXT,
+    discord_sent_date TEXT,
+
+    FOREIGN KEY (cid) REFERENCES controller(cid)
+) STRICT;
+",
+    // 15: exit surveys for controllers leaving the roster
+    "
+CREATE TABLE IF NOT EXISTS exit_survey (
+    id INTEGER PRIMARY KEY NOT NULL,
+    cid INTEGER NOT NULL,
+    departure TEXT NOT NULL,
+    created_date TEXT NOT NULL,
+    token TEXT UNIQUE,
+    email_sent_date TEXT,
+    submitted_date TEXT,
+    rating INTEGER,
+    reason TEXT,
+    comments TEXT,
+
+    FOREIGN KEY (cid) REFERENCES controller(cid)
+) STRICT;
+",
+    // 16: controller notification preferences
+    "
+CREATE TABLE IF NOT EXISTS notification_preference (
+    cid INTEGER PRIMARY KEY NOT NULL,
+    feedback_email INTEGER NOT NULL DEFAULT FALSE,
+    feedback_discord INTEGER NOT NULL DEFAULT FALSE,
+
+    FOREIGN KEY (cid) REFERENCES controller(cid)
+) STRICT;
+",
+    // 17: codes for linking Discord accounts through the bot
+    "
+CREATE TABLE IF NOT EXISTS discord_link_code (
+    cid INTEGER PRIMARY KEY NOT NULL,
+    code TEXT NOT NULL UNIQUE,
+    expires TEXT NOT NULL,
+
+    FOREIGN KEY (cid) REFERENCES controller(cid)
+) STRICT;
+",
+    // 18: temporary Discord channels for events
+    "
+CREATE TABLE IF NOT EXISTS event_channel (
+    id INTEGER PRIMARY KEY NOT NULL,
+    event_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    voice INTEGER NOT NULL DEFAULT FALSE,
+    channel_id TEXT,
+    created_date TEXT,
+    deleted_date TEXT,
+
+    FOREIGN KEY (event_id) REFERENCES event(id)
+) STRICT;
+",
+    // 19: solo cert expiration reminders
+    "
+ALTER TABLE solo_cert ADD COLUMN reminder_days INTEGER;
+",
+    // 20: controllers to review for removal after each quarter
+    "
+CREATE TABLE IF NOT EXISTS purge_candidate (
+    id INTEGER PRIMARY KEY NOT NULL,
+    cid INTEGER NOT NULL,
+    quarter TEXT NOT NULL,
+    minutes INTEGER NOT NULL,
+    created_date TEXT NOT NULL,
+    excluded INTEGER NOT NULL DEFAULT FALSE,
+    removed_date TEXT,
+    removed_by INTEGER,
+    error TEXT,
+
+    UNIQUE (cid, quarter),
+    FOREIGN KEY (cid) REFERENCES controller(cid)
+) STRICT;
+",
+    // 21: controlling sessions seen in the datafeed, for activity between VATSIM syncs
+    "
+CREATE TABLE IF NOT EXISTS controller_session (
+    id INTEGER PRIMARY KEY NOT NULL,
+    cid INTEGER NOT NULL,
+    callsign TEXT NOT NULL,
+    logon_time TEXT NOT NULL,
+    last_seen TEXT NOT NULL,
+
+    UNIQUE (cid, callsign, logon_time)
+) STRICT;
+",
+    // 22: precomputed aggregates for the stats and leaderboard pages
+    "
+CREATE TABLE IF NOT EXISTS stats_month_hours (
+    month TEXT PRIMARY KEY NOT NULL,
+    controlling INTEGER NOT NULL,
+    event INTEGER NOT NULL,
+    training INTEGER NOT NULL,
+    controllers INTEGER NOT NULL
+) STRICT;
+
+CREATE TABLE IF NOT EXISTS stats_month_feedback (
+    month TEXT NOT NULL,
+    rating TEXT NOT NULL,
+    count INTEGER NOT NULL,
+
+    PRIMARY KEY (month, rating)
+) STRICT;
+
+CREATE TABLE IF NOT EXISTS stats_event (
+    event_id INTEGER PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    start TEXT NOT NULL,
+    positions INTEGER NOT NULL,
+    assigned INTEGER NOT NULL,
+    registrations INTEGER NOT NULL
+) STRICT;
+
+CREATE TABLE IF NOT EXISTS stats_leaderboard (
+    month TEXT NOT NULL,
+    rank INTEGER NOT NULL,
+    cid INTEGER NOT NULL,
+    minutes INTEGER NOT NULL,
+
+    PRIMARY KEY (month, rank)
+) STRICT;
+",
+    // 23: controllers to re-sync from VATUSA ahead of the next full roster sync
+    "
+CREATE TABLE IF NOT EXISTS roster_refresh (
+    cid INTEGER PRIMARY KEY NOT NULL,
+    reason TEXT NOT NULL,
+    queued_date TEXT NOT NULL
+) STRICT;
+",
+    // 24: sign ins to facility tools through vzdv's OAuth provider
+    "
+CREATE TABLE IF NOT EXISTS oauth_grant (
+    id INTEGER PRIMARY KEY NOT NULL,
+    client_id TEXT NOT NULL,
+    cid INTEGER NOT NULL,
+    scope TEXT NOT NULL,
+    nonce TEXT,
+    redirect_uri TEXT NOT NULL,
+    code_hash TEXT UNIQUE,
+    code_expires TEXT NOT NULL,
+    token_hash TEXT UNIQUE,
+    token_expires TEXT,
+    created_date TEXT NOT NULL,
+
+    FOREIGN KEY (cid) REFERENCES controller(cid)
+) STRICT;
+",
+    // 25: secret links to controllers' personal calendar feeds
+    "
+CREATE TABLE IF NOT EXISTS calendar_token (
+    cid INTEGER PRIMARY KEY NOT NULL,
+    token TEXT NOT NULL UNIQUE,
+    created_date TEXT NOT NULL,
+
+    FOREIGN KEY (cid) REFERENCES controller(cid)
+) STRICT;
+",
+    // 26: training syllabus lessons, and which ones students have covered
+    "
+CREATE TABLE IF NOT EXISTS training_lesson (
+    id INTEGER PRIMARY KEY NOT NULL,
+    certification TEXT NOT NULL,
+    sort_order INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    objectives TEXT NOT NULL,
+    milestone INTEGER NOT NULL DEFAULT FALSE,
+    updated_by INTEGER NOT NULL,
+    updated_date TEXT NOT NULL
+) STRICT;
+CREATE TABLE IF NOT EXISTS training_lesson_session (
+    id INTEGER PRIMARY KEY NOT NULL,
+    lesson_id INTEGER NOT NULL,
+    cid INTEGER NOT NULL,
+    instructor_cid INTEGER NOT NULL,
+    date TEXT NOT NULL,
+
+    FOREIGN KEY (lesson_id) REFERENCES training_lesson(id) ON DELETE CASCADE,
+    FOREIGN KEY (cid) REFERENCES controller(cid)
+) STRICT;
+CREATE INDEX IF NOT EXISTS training_lesson_session_cid ON training_lesson_session (cid);
+INSERT OR IGNORE INTO role_permission VALUES ('ATM', 'syllabus.manage'), ('DATM', 'syllabus.manage'), ('TA', 'syllabus.manage');
+",
+    // 27: booked training sessions
+    "
+CREATE TABLE IF NOT EXISTS training_session (
+    id INTEGER PRIMARY KEY NOT NULL,
+    cid INTEGER NOT NULL,
+    instructor_cid INTEGER NOT NULL,
+    position TEXT NOT NULL,
+    start TEXT NOT NULL,
+    end TEXT NOT NULL,
+    notes TEXT NOT NULL,
+    sequence INTEGER NOT NULL DEFAULT 0,
+    cancelled INTEGER NOT NULL DEFAULT FALSE,
+    created_date TEXT NOT NULL,
+    updated_date TEXT NOT NULL,
+
+    FOREIGN KEY (cid) REFERENCES controller(cid)
+) STRICT;
+CREATE INDEX IF NOT EXISTS training_session_cid ON training_session (cid);
+CREATE INDEX IF NOT EXISTS training_session_instructor_cid ON training_session (instructor_cid);
+",
+    // 28: waitlists for certifications with limited training slots
+    "
+CREATE TABLE IF NOT EXISTS training_waitlist (
+    id INTEGER PRIMARY KEY NOT NULL,
+    cid INTEGER NOT NULL,
+    certification TEXT NOT NULL,
+    joined_date TEXT NOT NULL,
+    promoted_date TEXT,
+
+    FOREIGN KEY (cid) REFERENCES controller(cid)
+) STRICT;
+CREATE UNIQUE INDEX IF NOT EXISTS training_waitlist_waiting ON training_waitlist (cid, certification) WHERE promoted_date IS NULL;
+",
+    // 29: students' feedback on their training sessions
+    "
+CREATE TABLE IF NOT EXISTS training_feedback (
+    id INTEGER PRIMARY KEY NOT NULL,
+    cid INTEGER NOT NULL,
+    instructor_cid INTEGER NOT NULL,
+    position TEXT NOT NULL,
+    session_date TEXT NOT NULL,
+    requested_date TEXT NOT NULL,
+    rating INTEGER,
+    comments TEXT,
+    submitted_date TEXT,
+
+    FOREIGN KEY (cid) REFERENCES controller(cid)
+) STRICT;
+CREATE INDEX IF NOT EXISTS training_feedback_cid ON training_feedback (cid);
+CREATE INDEX IF NOT EXISTS training_feedback_instructor_cid ON training_feedback (instructor_cid);
+INSERT OR IGNORE INTO role_permission VALUES ('TA', 'training_feedback.view');
+",
+    // 30: every change to a certification, starting from the current values
+    "
+CREATE TABLE IF NOT EXISTS certification_history (
+    id INTEGER PRIMARY KEY NOT NULL,
+    cid INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    old_value TEXT,
+    new_value TEXT NOT NULL,
+    changed_by INTEGER NOT NULL,
+    changed_on TEXT NOT NULL,
+
+    FOREIGN KEY (cid) REFERENCES controller(cid)
+) STRICT;
+CREATE INDEX IF NOT EXISTS certification_history_cid ON certification_history (cid);
+INSERT INTO certification_history (id, cid, name, old_value, new_value, changed_by, changed_on)
+    SELECT NULL, cid, name, NULL, value, set_by, changed_on FROM certification;
+",
+    // 31: resource categories, previously ordered in the config
+    "
+CREATE TABLE IF NOT EXISTS resource_category (
+    id INTEGER PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL UNIQUE,
+    sort_order INTEGER NOT NULL,
+    archived INTEGER NOT NULL DEFAULT FALSE
+) STRICT;
+INSERT OR IGNORE INTO resource_category (id, name, sort_order, archived)
+    SELECT NULL, category, ROW_NUMBER() OVER (ORDER BY category), FALSE
+    FROM (SELECT DISTINCT category FROM resource);
+",
+    // 32: who can see each resource category
+    "
+ALTER TABLE resource_category ADD COLUMN visibility TEXT NOT NULL DEFAULT 'public';
+",
+    // 33: resource download analytics
+    "
+ALTER TABLE resource ADD COLUMN download_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE resource ADD COLUMN last_downloaded TEXT;
+",
+    // 34: caps on how many controllers can sign up for each event position category
+    "
+CREATE TABLE IF NOT EXISTS event_category_limit (
+    event_id INTEGER NOT NULL,
+    category TEXT NOT NULL,
+    max_signups INTEGER NOT NULL,
+
+    PRIMARY KEY (event_id, category),
+    FOREIGN KEY (event_id) REFERENCES event(id)
+) STRICT;
+",
+    // 35: controllers waiting for a position in a category where every position is assigned
+    "
+CREATE TABLE IF NOT EXISTS event_waitlist (
+    id INTEGER PRIMARY KEY NOT NULL,
+    event_id INTEGER NOT NULL,
+    cid INTEGER NOT NULL,
+    category TEXT NOT NULL,
+    joined_date TEXT NOT NULL,
+    promoted_date TEXT,
+
+    FOREIGN KEY (event_id) REFERENCES event(id),
+    FOREIGN KEY (cid) REFERENCES controller(cid)
+) STRICT;
+CREATE UNIQUE INDEX IF NOT EXISTS event_waitlist_waiting ON event_waitlist (event_id, cid, category) WHERE promoted_date IS NULL;
+",
+    // 36: joint events with other facilities
+    "
+ALTER TABLE event ADD COLUMN partner_facility TEXT;
+ALTER TABLE event ADD COLUMN external_signup_url TEXT;
+",
+    // 37: instruction time from training sessions credited as activity
+    "
+ALTER TABLE activity ADD COLUMN training_session_id INTEGER;
+CREATE UNIQUE INDEX IF NOT EXISTS activity_training_session_id ON activity (training_session_id);
+",
+    // 38: per-controller monthly activity totals, kept current by triggers on `activity`
+    "
+CREATE TABLE IF NOT EXISTS activity_month_summary (
+    cid INTEGER NOT NULL,
+    month TEXT NOT NULL,
+    controlling INTEGER NOT NULL DEFAULT 0,
+    event INTEGER NOT NULL DEFAULT 0,
+    training INTEGER NOT NULL DEFAULT 0,
+
+    PRIMARY KEY (cid, month)
+) STRICT;
+
+INSERT OR REPLACE INTO activity_month_summary
+SELECT cid, month,
+    SUM(CASE WHEN source='controlling' THEN minutes ELSE 0 END),
+    SUM(CASE WHEN source='event' THEN minutes ELSE 0 END),
+    SUM(CASE WHEN source='training' THEN minutes ELSE 0 END)
+FROM activity GROUP BY cid, month;
+
+CREATE TRIGGER IF NOT EXISTS activity_summary_insert AFTER INSERT ON activity BEGIN
+    INSERT INTO activity_month_summary (cid, month) VALUES (NEW.cid, NEW.month)
+        ON CONFLICT (cid, month) DO NOTHING;
+    UPDATE activity_month_summary SET
+        controlling = controlling + (CASE WHEN NEW.source='controlling' THEN NEW.minutes ELSE 0 END),
+        event = event + (CASE WHEN NEW.source='event' THEN NEW.minutes ELSE 0 END),
+        training = training + (CASE WHEN NEW.source='training' THEN NEW.minutes ELSE 0 END)
+    WHERE cid=NEW.cid AND month=NEW.month;
+END;
+
+CREATE TRIGGER IF NOT EXISTS activity_summary_delete AFTER DELETE ON activity BEGIN
+    UPDATE activity_month_summary SET
+        controlling = controlling - (CASE WHEN OLD.source='controlling' THEN OLD.minutes ELSE 0 END),
+        event = event - (CASE WHEN OLD.source='event' THEN OLD.minutes ELSE 0 END),
+        training = training - (CASE WHEN OLD.source='training' THEN OLD.minutes ELSE 0 END)
+    WHERE cid=OLD.cid AND month=OLD.month;
+    DELETE FROM activity_month_summary
+        WHERE cid=OLD.cid AND month=OLD.month AND controlling=0 AND event=0 AND training=0;
+END;
+
+CREATE TRIGGER IF NOT EXISTS activity_summary_update AFTER UPDATE ON activity BEGIN
+    UPDATE activity_month_summary SET
+        controlling = controlling - (CASE WHEN OLD.source='controlling' THEN OLD.minutes ELSE 0 END),
+        event = event - (CASE WHEN OLD.source='event' THEN OLD.minutes ELSE 0 END),
+        training = training - (CASE WHEN OLD.source='training' THEN OLD.minutes ELSE 0 END)
+    WHERE cid=OLD.cid AND month=OLD.month;
+    INSERT INTO activity_month_summary (cid, month) VALUES (NEW.cid, NEW.month)
+        ON CONFLICT (cid, month) DO NOTHING;
+    UPDATE activity_month_summary SET
+        controlling = controlling + (CASE WHEN NEW.source='controlling' THEN NEW.minutes ELSE 0 END),
+        event = event + (CASE WHEN NEW.source='event' THEN NEW.minutes ELSE 0 END),
+        training = training + (CASE WHEN NEW.source='training' THEN NEW.minutes ELSE 0 END)
+    WHERE cid=NEW.cid AND month=NEW.month;
+    DELETE FROM activity_month_summary
+        WHERE cid=OLD.cid AND month=OLD.month AND controlling=0 AND event=0 AND training=0;
+END;
+",
+    // 39: approved purges, removed in the background
+    "
+ALTER TABLE purge_candidate ADD COLUMN approved_date TEXT;
+ALTER TABLE purge_candidate ADD COLUMN approved_by INTEGER;
+",
+    // 40: audit trail of staff viewing the site as other controllers
+    "
+CREATE TABLE IF NOT EXISTS impersonation_log (
+    id INTEGER PRIMARY KEY NOT NULL,
+    staff_cid INTEGER NOT NULL,
+    viewed_cid INTEGER NOT NULL,
+    started_date TEXT NOT NULL,
+    ended_date TEXT,
+
+    FOREIGN KEY (staff_cid) REFERENCES controller(cid),
+    FOREIGN KEY (viewed_cid) REFERENCES controller(cid)
+) STRICT;
+",
+    // 41: approved purges skipped because the controller no longer needs removing
+    "
+ALTER TABLE purge_candidate ADD COLUMN skip_reason TEXT;
+",
+    // 42: grace window for a remember-me token's replaced validator
+    "
+ALTER TABLE remember_token ADD COLUMN previous_validator_hash TEXT;
+ALTER TABLE remember_token ADD COLUMN rotated_date TEXT;
+",
+    // 43: assigning feedback to reviewers, which only the ATM could do before
+    "
+INSERT OR IGNORE INTO role_permission VALUES ('ATM', 'feedback.assign');
+",
+];
+
 pub const UPSERT_USER_LOGIN: &str = "
 INSERT INTO controller
     (id, cid, first_name, last_name, email, rating, is_on_roster)
@@ -270,36 +1296,23 @@ WHERE
     cid=excluded.cid
 ";
 
-pub const UPSERT_USER_TASK: &str = "
-INSERT INTO controller
-    (id, cid, first_name, last_name, email, rating, home_facility, is_on_roster, join_date, roles)
-VALUES
-    (NULL, $1, $2, $3, $4, $5, $6, $7, $8, $9)
-ON CONFLICT(cid) DO UPDATE SET
-    first_name=excluded.first_name,
-    last_name=excluded.last_name,
-    email=excluded.email,
-    rating=excluded.rating,
-    home_facility=excluded.home_facility,
-    is_on_roster=excluded.is_on_roster,
-    join_date=excluded.join_date,
-    roles=excluded.roles
-WHERE
-    cid=excluded.cid
-";
-
 pub const GET_ALL_CONTROLLERS: &str = "SELECT * FROM controller";
 pub const GET_ALL_CONTROLLERS_ON_ROSTER: &str = "SELECT * FROM controller WHERE is_on_roster=TRUE";
 pub const GET_ALL_CONTROLLERS_OFF_ROSTER: &str =
     "SELECT * FROM controller WHERE is_on_roster=FALSE";
-pub const GET_ALL_CONTROLLER_CIDS: &str = "SELECT cid FROM controller";
-pub const GET_ALL_ROSTER_CONTROLLER_CIDS: &str =
-    "SELECT cid FROM controller WHERE is_on_roster=TRUE";
 pub const UPDATE_REMOVED_FROM_ROSTER: &str =
     "UPDATE controller SET is_on_roster=0, home_facility='', join_date=NULL, operating_initials=NULL WHERE cid=$1";
+pub const UPDATE_RETURNED_TO_ROSTER: &str =
+    "UPDATE controller SET is_on_roster=1, home_facility=$2, join_date=$3, status='Active' WHERE cid=$1";
+pub const UPDATE_CONTROLLER_STATUS: &str = "UPDATE controller SET status=$2 WHERE cid=$1";
 pub const UPDATE_CONTROLLER_OIS: &str = "UPDATE controller SET operating_initials=$2 WHERE cid=$1";
-pub const GET_ALL_OIS: &str = "SELECT operating_initials FROM controller";
+pub const GET_ALL_OIS: &str =
+    "SELECT operating_initials FROM controller WHERE operating_initials IS NOT NULL";
 pub const GET_CONTROLLER_BY_CID: &str = "SELECT * FROM controller WHERE cid=$1";
+/// $1 is a JSON array of CIDs, so any number of controllers can be fetched at once.
+pub const GET_CONTROLLERS_BY_CIDS: &str =
+    "SELECT * FROM controller WHERE cid IN (SELECT value FROM json_each($1))";
+pub const GET_CONTROLLER_EMAIL: &str = "SELECT email FROM controller WHERE cid=$1";
 pub const GET_CONTROLLER_CIDS_AND_NAMES: &str = "SELECT cid, first_name, last_name from controller";
 pub const GET_ATM_AND_DATM: &str = "SELECT * FROM controller WHERE roles LIKE '%ATM%'";
 pub const GET_CONTROLLER_BY_DISCORD_ID: &str = "SELECT * FROM controller WHERE discord_id=$1";
@@ -313,18 +1326,39 @@ pub const CREATE_CERTIFICATION: &str =
     "INSERT INTO certification VALUES (NULL, $1, $2, $3, $4, $5);";
 pub const UPDATE_CERTIFICATION: &str =
     "UPDATE certification SET value=$2, changed_on=$3, set_by=$4 WHERE id=$1";
+pub const INSERT_CERTIFICATION_HISTORY: &str =
+    "INSERT INTO certification_history VALUES (NULL, $1, $2, $3, $4, $5, $6)";
+pub const GET_CERTIFICATION_HISTORY_FOR: &str =
+    "SELECT * FROM certification_history WHERE cid=$1 ORDER BY changed_on DESC, id DESC";
 
-pub const GET_ALL_ACTIVITY: &str =
-    "SELECT * FROM activity LEFT JOIN controller ON activity.cid = controller.cid";
+pub const GET_ACTIVITY_SUMMARY_SINCE: &str =
+    "SELECT * FROM activity_month_summary WHERE month >= $1";
 pub const GET_ACTIVITY_IN_MONTH: &str =
-    "SELECT activity.*, controller.first_name, controller.last_name FROM activity LEFT JOIN controller ON activity.cid = controller.cid WHERE month=$1 ORDER BY minutes DESC";
-pub const DELETE_ACTIVITY_FOR_CID: &str = "DELETE FROM activity WHERE cid=$1";
-pub const INSERT_INTO_ACTIVITY: &str = "
-INSERT INTO activity
-    (id, cid, month, minutes)
-VALUES
-    (NULL, $1, $2, $3)
-";
+    "SELECT activity.*, controller.first_name, controller.last_name FROM activity INNER JOIN controller ON activity.cid = controller.cid WHERE activity.month=$1 AND activity.source='controlling' ORDER BY activity.minutes DESC";
+pub const GET_ACTIVITY_FOR_SINCE: &str =
+    "SELECT activity.*, controller.first_name, controller.last_name FROM activity INNER JOIN controller ON activity.cid = controller.cid WHERE activity.cid=$1 AND activity.month >= $2";
+pub const GET_EVENT_ACTIVITY_CREDITED: &str =
+    "SELECT EXISTS(SELECT 1 FROM activity WHERE event_id=$1)";
+pub const DELETE_EVENT_ACTIVITY: &str = "DELETE FROM activity WHERE event_id=$1";
+/// Total minutes per controller for months $1 through $2, inclusive.
+pub const GET_ACTIVITY_TOTALS_BETWEEN: &str =
+    "SELECT cid, SUM(minutes) FROM activity WHERE month >= $1 AND month <= $2 GROUP BY cid";
+pub const GET_ACTIVITY_TOTAL_BETWEEN_FOR: &str = "SELECT COALESCE(SUM(minutes), 0) FROM activity
+    WHERE cid=$1 AND month >= $2 AND month <= $3";
+pub const UPSERT_CONTROLLER_SESSION: &str = "INSERT INTO controller_session VALUES (NULL, $1, $2, $3, $4) ON CONFLICT(cid, callsign, logon_time) DO UPDATE SET last_seen=excluded.last_seen";
+/// Sessions still in the datafeed at or after $1.
+pub const GET_CONTROLLER_SESSIONS_SEEN_SINCE: &str =
+    "SELECT * FROM controller_session WHERE last_seen >= $1";
+pub const GET_CONTROLLER_SESSIONS_FOR: &str = "SELECT * FROM controller_session WHERE cid=$1";
+pub const DELETE_CONTROLLER_SESSION: &str = "DELETE FROM controller_session WHERE id=$1";
+pub const INSERT_EVENT_ACTIVITY: &str =
+    "INSERT INTO activity (id, cid, month, minutes, source, event_id) VALUES (NULL, $1, $2, $3, 'event', $4)";
+/// Sessions that ended by $1 and haven't been credited to their instructor.
+pub const GET_UNCREDITED_TRAINING_SESSIONS: &str = "SELECT training_session.* FROM training_session JOIN controller ON training_session.instructor_cid=controller.cid WHERE training_session.cancelled=FALSE AND training_session.end <= $1 AND NOT EXISTS (SELECT 1 FROM activity WHERE activity.training_session_id=training_session.id)";
+pub const INSERT_TRAINING_ACTIVITY: &str =
+    "INSERT INTO activity (id, cid, month, minutes, source, training_session_id) VALUES (NULL, $1, $2, $3, 'training', $4)";
+/// Credited time for sessions that were cancelled afterwards.
+pub const DELETE_CANCELLED_TRAINING_ACTIVITY: &str = "DELETE FROM activity WHERE training_session_id IN (SELECT id FROM training_session WHERE cancelled=TRUE)";
 
 pub const INSERT_FEEDBACK: &str = "
 INSERT INTO feedback
@@ -341,25 +1375,59 @@ pub const UPDATE_FEEDBACK_TAKE_ACTION: &str =
     "UPDATE feedback SET reviewed_by_cid=$1, reviewer_action=$2, posted_to_discord=$3 WHERE id=$4";
 pub const DELETE_FROM_FEEDBACK: &str = "DELETE FROM feedback WHERE id=$1";
 pub const GET_ALL_FEEDBACK_FOR: &str = "SELECT * FROM feedback WHERE controller=$1";
+pub const GET_SHARED_FEEDBACK_FOR: &str =
+    "SELECT * FROM feedback WHERE controller=$1 AND reviewer_action='post' ORDER BY created_date DESC";
+pub const UPDATE_FEEDBACK_ASSIGNED: &str = "UPDATE feedback SET assigned_to_cid=$2 WHERE id=$1";
+pub const GET_ALL_FEEDBACK_COMMENTS: &str =
+    "SELECT * FROM feedback_comment ORDER BY created_date, id";
+pub const INSERT_FEEDBACK_COMMENT: &str =
+    "INSERT INTO feedback_comment VALUES (NULL, $1, $2, $3, $4, $5)";
 
 pub const GET_ALL_RESOURCES: &str = "SELECT * FROM resource";
 pub const GET_RESOURCE_BY_ID: &str = "SELECT * FROM resource WHERE id=$1";
 pub const DELETE_RESOURCE_BY_ID: &str = "DELETE FROM resource WHERE id=$1";
-pub const CREATE_NEW_RESOURCE: &str = "INSERT INTO resource VALUES (NULL, $1, $2, $3, $4, $5)";
+pub const CREATE_NEW_RESOURCE: &str = "INSERT INTO resource (id, category, name, file_name, link, updated) VALUES (NULL, $1, $2, $3, $4, $5)";
+pub const RECORD_RESOURCE_DOWNLOAD: &str =
+    "UPDATE resource SET download_count=download_count+1, last_downloaded=$2 WHERE id=$1";
+pub const RECORD_RESOURCE_FILE_DOWNLOAD: &str =
+    "UPDATE resource SET download_count=download_count+1, last_downloaded=$2 WHERE file_name=$1";
+pub const UPDATE_RESOURCES_CATEGORY: &str = "UPDATE resource SET category=$2 WHERE category=$1";
+
+pub const GET_RESOURCE_CATEGORIES: &str = "SELECT * FROM resource_category ORDER BY sort_order, id";
+pub const GET_RESOURCE_CATEGORY: &str = "SELECT * FROM resource_category WHERE id=$1";
+pub const CREATE_RESOURCE_CATEGORY: &str = "INSERT INTO resource_category VALUES (NULL, $1, (SELECT COALESCE(MAX(sort_order), 0) + 1 FROM resource_category), FALSE, 'public')";
+pub const RENAME_RESOURCE_CATEGORY: &str = "UPDATE resource_category SET name=$2 WHERE id=$1";
+pub const SET_RESOURCE_CATEGORY_ORDER: &str =
+    "UPDATE resource_category SET sort_order=$2 WHERE id=$1";
+pub const SET_RESOURCE_CATEGORY_ARCHIVED: &str =
+    "UPDATE resource_category SET archived=$2 WHERE id=$1";
+pub const SET_RESOURCE_CATEGORY_VISIBILITY: &str =
+    "UPDATE resource_category SET visibility=$2 WHERE id=$1";
+pub const GET_RESOURCE_VISIBILITY_BY_ID: &str = "SELECT resource_category.visibility FROM resource JOIN resource_category ON resource.category=resource_category.name WHERE resource.id=$1";
+pub const GET_RESOURCE_VISIBILITY_BY_FILE_NAME: &str = "SELECT resource_category.visibility FROM resource JOIN resource_category ON resource.category=resource_category.name WHERE resource.file_name=$1";
 
 pub const GET_VISITOR_REQUEST_BY_ID: &str = "SELECT * FROM visitor_request WHERE id=$1";
 pub const GET_ALL_VISITOR_REQUESTS: &str = "SELECT * FROM visitor_request";
 pub const GET_PENDING_VISITOR_REQ_FOR: &str = "SELECT * FROM visitor_request WHERE cid=$1";
-pub const INSERT_INTO_VISITOR_REQ: &str =
-    "INSERT INTO visitor_request VALUES (NULL, $1, $2, $3, $4, $5, $6);";
+pub const INSERT_INTO_VISITOR_REQ: &str = "
+INSERT INTO visitor_request
+    (id, cid, first_name, last_name, home_facility, rating, date)
+VALUES
+    (NULL, $1, $2, $3, $4, $5, $6)
+";
+pub const UPDATE_VISITOR_REQUEST_STATUS: &str = "UPDATE visitor_request SET status=$2 WHERE id=$1";
+pub const UPDATE_VISITOR_REQUEST_NOTES: &str = "UPDATE visitor_request SET notes=$2 WHERE id=$1";
 pub const DELETE_VISITOR_REQUEST: &str = "DELETE FROM visitor_request WHERE id=$1";
 
 pub const GET_UPCOMING_EVENTS: &str = "SELECT * FROM event WHERE end > $1 AND published = TRUE";
 pub const GET_ALL_UPCOMING_EVENTS: &str = "SELECT * FROM event WHERE end > $1";
 pub const GET_EVENT: &str = "SELECT * FROM event WHERE id=$1";
 pub const DELETE_EVENT: &str = "DELETE FROM event WHERE id=$1";
-pub const CREATE_EVENT: &str = "INSERT INTO event VALUES (NULL, $1, FALSE, $2, $3, $4, $5, $6);";
-pub const UPDATE_EVENT: &str = "UPDATE event SET name=$2, published=$3, start=$4, end=$5, description=$6, image_url=$7 where id=$1";
+/// Published upcoming events held jointly with the facility.
+pub const GET_UPCOMING_PARTNER_EVENTS: &str =
+    "SELECT * FROM event WHERE end > $1 AND published = TRUE AND partner_facility=$2 ORDER BY start";
+pub const CREATE_EVENT: &str = "INSERT INTO event (id, created_by, published, name, start, end, description, image_url, partner_facility, external_signup_url) VALUES (NULL, $1, FALSE, $2, $3, $4, $5, $6, $7, $8);";
+pub const UPDATE_EVENT: &str = "UPDATE event SET name=$2, published=$3, start=$4, end=$5, description=$6, image_url=$7, partner_facility=$8, external_signup_url=$9 where id=$1";
 
 pub const GET_EVENT_REGISTRATION_FOR: &str =
     "SELECT * FROM event_registration WHERE event_id=$1 AND cid=$2";
@@ -382,7 +1450,326 @@ pub const INSERT_EVENT_POSITION: &str =
 pub const DELETE_EVENT_POSITION: &str = "DELETE FROM event_position WHERE id=$1";
 pub const UPDATE_EVENT_POSITION_CONTROLLER: &str = "UPDATE event_position SET cid=$2 WHERE id=$1";
 
+pub const GET_EVENT_CATEGORY_LIMITS: &str = "SELECT * FROM event_category_limit WHERE event_id=$1";
+pub const UPSERT_EVENT_CATEGORY_LIMIT: &str =
+    "INSERT INTO event_category_limit VALUES ($1, $2, $3) ON CONFLICT DO UPDATE SET max_signups=$3";
+pub const DELETE_EVENT_CATEGORY_LIMIT: &str =
+    "DELETE FROM event_category_limit WHERE event_id=$1 AND category=$2";
+pub const DELETE_EVENT_CATEGORY_LIMITS_FOR: &str =
+    "DELETE FROM event_category_limit WHERE event_id=$1";
+
+/// Controllers still waiting, in the order they joined.
+pub const GET_EVENT_WAITLIST: &str =
+    "SELECT * FROM event_waitlist WHERE event_id=$1 AND promoted_date IS NULL ORDER BY joined_date, id";
+pub const JOIN_EVENT_WAITLIST: &str =
+    "INSERT OR IGNORE INTO event_waitlist VALUES (NULL, $1, $2, $3, $4, NULL)";
+pub const LEAVE_EVENT_WAITLIST: &str = "DELETE FROM event_waitlist WHERE event_id=$1 AND cid=$2 AND category=$3 AND promoted_date IS NULL";
+pub const SET_EVENT_WAITLIST_ENTRY_PROMOTED: &str =
+    "UPDATE event_waitlist SET promoted_date=$2 WHERE id=$1";
+pub const DELETE_EVENT_WAITLIST_FOR: &str = "DELETE FROM event_waitlist WHERE event_id=$1";
+
+pub const GET_UPCOMING_EVENT_ASSIGNMENTS_FOR: &str = "SELECT event.id AS event_id, event.name AS event_name, event.start, event.end, event_position.name AS position FROM event_position JOIN event ON event_position.event_id=event.id WHERE event_position.cid=$1 AND event.published=TRUE AND event.end > $2 ORDER BY event.start";
+
+pub const GET_EVENT_CHANNELS: &str = "SELECT * FROM event_channel WHERE event_id=$1";
+pub const INSERT_EVENT_CHANNEL: &str =
+    "INSERT INTO event_channel (id, event_id, name, voice) VALUES (NULL, $1, $2, $3)";
+/// Channels can only be removed before the bot has created them.
+pub const DELETE_EVENT_CHANNEL: &str =
+    "DELETE FROM event_channel WHERE id=$1 AND event_id=$2 AND channel_id IS NULL";
+pub const DELETE_EVENT_CHANNELS_FOR: &str = "DELETE FROM event_channel WHERE event_id=$1";
+pub const SET_EVENT_CHANNEL_CREATED: &str =
+    "UPDATE event_channel SET channel_id=$2, created_date=$3 WHERE id=$1";
+
 pub const GET_STAFF_NOTES_FOR: &str = "SELECT * FROM staff_note WHERE cid=$1";
 pub const GET_STAFF_NOTE: &str = "SELECT * FROM staff_note WHERE id=$1";
 pub const DELETE_STAFF_NOTE: &str = "DELETE FROM staff_note WHERE id=$1";
 pub const CREATE_STAFF_NOTE: &str = "INSERT INTO staff_note VALUES (NULL, $1, $2, $3, $4);";
+
+pub const GET_TRAINING_LESSONS: &str =
+    "SELECT * FROM training_lesson ORDER BY certification, sort_order, id";
+pub const GET_TRAINING_LESSON: &str = "SELECT * FROM training_lesson WHERE id=$1";
+pub const CREATE_TRAINING_LESSON: &str = "INSERT INTO training_lesson VALUES (NULL, $1, (SELECT COALESCE(MAX(sort_order), 0) + 1 FROM training_lesson WHERE certification=$1), $2, $3, $4, $5, $6)";
+pub const UPDATE_TRAINING_LESSON: &str = "UPDATE training_lesson SET name=$2, objectives=$3, milestone=$4, updated_by=$5, updated_date=$6 WHERE id=$1";
+pub const SET_TRAINING_LESSON_ORDER: &str = "UPDATE training_lesson SET sort_order=$2 WHERE id=$1";
+pub const DELETE_TRAINING_LESSON: &str = "DELETE FROM training_lesson WHERE id=$1";
+pub const INSERT_TRAINING_LESSON_SESSION: &str =
+    "INSERT INTO training_lesson_session VALUES (NULL, $1, $2, $3, $4)";
+
+pub const GET_TRAINING_LESSON_SESSIONS_FOR: &str =
+    "SELECT * FROM training_lesson_session WHERE cid=$1 ORDER BY date DESC";
+
+pub const GET_TRAINING_SESSION: &str = "SELECT * FROM training_session WHERE id=$1";
+pub const GET_UPCOMING_TRAINING_SESSIONS: &str =
+    "SELECT * FROM training_session WHERE end > $1 ORDER BY start";
+pub const GET_UPCOMING_TRAINING_SESSIONS_FOR: &str =
+    "SELECT * FROM training_session WHERE (cid=$1 OR instructor_cid=$1) AND end > $2 ORDER BY start";
+pub const CREATE_TRAINING_SESSION: &str =
+    "INSERT INTO training_session VALUES (NULL, $1, $2, $3, $4, $5, $6, 0, FALSE, $7, $7)";
+pub const CANCEL_TRAINING_SESSION: &str =
+    "UPDATE training_session SET cancelled=TRUE, sequence=sequence+1, updated_date=$2 WHERE id=$1";
+
+pub const GET_WAITLIST: &str = "
+SELECT
+    training_waitlist.*,
+    controller.first_name,
+    controller.last_name,
+    controller.join_date,
+    (SELECT COALESCE(SUM(minutes), 0) FROM activity WHERE activity.cid=training_waitlist.cid AND activity.month >= $1) AS recent_minutes,
+    (SELECT MAX(date) FROM training_record WHERE training_record.cid=training_waitlist.cid) AS last_session
+FROM training_waitlist
+JOIN controller ON training_waitlist.cid=controller.cid
+WHERE training_waitlist.promoted_date IS NULL
+ORDER BY training_waitlist.joined_date";
+pub const GET_WAITLIST_ENTRY: &str = "SELECT cid FROM training_waitlist WHERE id=$1";
+pub const JOIN_WAITLIST: &str = "INSERT INTO training_waitlist VALUES (NULL, $1, $2, $3, NULL)";
+pub const DELETE_WAITLIST_ENTRY: &str =
+    "DELETE FROM training_waitlist WHERE id=$1 AND promoted_date IS NULL";
+pub const SET_WAITLIST_ENTRY_PROMOTED: &str =
+    "UPDATE training_waitlist SET promoted_date=$2 WHERE id=$1";
+/// On-roster controllers currently in training for the certification, taking up a slot.
+pub const COUNT_IN_TRAINING_FOR_CERTIFICATION: &str = "SELECT COUNT(*) FROM certification JOIN controller ON certification.cid=controller.cid WHERE certification.name=$1 AND certification.value='training' AND controller.is_on_roster=TRUE";
+
+pub const CREATE_TRAINING_FEEDBACK_REQUEST: &str =
+    "INSERT INTO training_feedback (id, cid, instructor_cid, position, session_date, requested_date) VALUES (NULL, $1, $2, $3, $4, $5)";
+pub const GET_TRAINING_FEEDBACK: &str = "SELECT * FROM training_feedback WHERE id=$1";
+pub const GET_PENDING_TRAINING_FEEDBACK_FOR: &str =
+    "SELECT * FROM training_feedback WHERE cid=$1 AND submitted_date IS NULL AND requested_date > $2 ORDER BY session_date DESC";
+pub const GET_SUBMITTED_TRAINING_FEEDBACK: &str =
+    "SELECT * FROM training_feedback WHERE submitted_date IS NOT NULL ORDER BY session_date DESC";
+pub const SUBMIT_TRAINING_FEEDBACK: &str =
+    "UPDATE training_feedback SET rating=$2, comments=$3, submitted_date=$4 WHERE id=$1 AND submitted_date IS NULL";
+
+pub const GET_TRAINING_RECORDS_FOR: &str =
+    "SELECT * FROM training_record WHERE cid=$1 ORDER BY date DESC";
+
+pub const GET_ALL_SOLO_CERTS: &str = "SELECT * FROM solo_cert";
+pub const GET_ALL_SOLO_CERTS_FOR: &str = "SELECT * FROM solo_cert WHERE cid=$1";
+pub const GET_SOLO_CERT_BY_ID: &str = "SELECT * FROM solo_cert WHERE id=$1";
+pub const CREATE_SOLO_CERT: &str = "INSERT INTO solo_cert (id, cid, issued_by, position, reported, created_date, expiration_date) VALUES (NULL, $1, $2, $3, $4, $5, $6);";
+pub const DELETE_SOLO_CERT: &str = "DELETE FROM solo_cert WHERE id=$1";
+
+pub const GET_CURRENT_AND_UPCOMING_LOAS: &str =
+    "SELECT * FROM loa WHERE end_date > $1 ORDER BY start_date";
+pub const GET_ALL_LOAS_FOR: &str = "SELECT * FROM loa WHERE cid=$1 ORDER BY start_date DESC";
+pub const GET_LOA_BY_ID: &str = "SELECT * FROM loa WHERE id=$1";
+pub const CREATE_LOA: &str = "INSERT INTO loa VALUES (NULL, $1, $2, $3, $4, $5, $6, NULL);";
+pub const UPDATE_LOA_END_DATE: &str = "UPDATE loa SET end_date=$2 WHERE id=$1";
+pub const END_LOA: &str = "UPDATE loa SET end_date=$2, ended_by=$3 WHERE id=$1";
+/// Set `controller.loa_until` from the LOAs in effect at `$1`.
+pub const UPDATE_ALL_LOA_UNTIL: &str = "
+UPDATE controller SET loa_until=(
+    SELECT MAX(end_date) FROM loa
+    WHERE loa.cid=controller.cid AND start_date <= $1 AND end_date > $1
+)
+";
+
+pub const GET_ACTIVE_BANNERS: &str =
+    "SELECT * FROM banner WHERE start <= $1 AND end > $1 ORDER BY start";
+pub const GET_ALL_BANNERS: &str = "SELECT * FROM banner ORDER BY start DESC";
+pub const CREATE_BANNER: &str = "INSERT INTO banner VALUES (NULL, $1, $2, $3, $4, $5);";
+pub const DELETE_BANNER: &str = "DELETE FROM banner WHERE id=$1";
+
+pub const GET_ACTIVE_BLOCKS: &str = "SELECT * FROM block WHERE expires IS NULL OR expires > $1";
+pub const GET_ALL_BLOCKS: &str = "SELECT * FROM block ORDER BY created_date DESC";
+pub const CREATE_BLOCK: &str = "INSERT INTO block VALUES (NULL, $1, $2, $3, $4, $5, $6, 0, NULL);";
+pub const DELETE_BLOCK: &str = "DELETE FROM block WHERE id=$1";
+pub const RECORD_BLOCK_HIT: &str = "UPDATE block SET hits=hits+1, last_hit=$2 WHERE id=$1";
+
+pub const GET_REMEMBER_TOKEN: &str = "SELECT * FROM remember_token WHERE selector=$1";
+pub const CREATE_REMEMBER_TOKEN: &str =
+    "INSERT INTO remember_token VALUES (NULL, $1, $2, $3, $4, $5, NULL, NULL, NULL);";
+/// Only rotates if the validator is still the one that was checked (`$5`).
+pub const ROTATE_REMEMBER_TOKEN: &str = "UPDATE remember_token
+    SET previous_validator_hash=validator_hash, validator_hash=$2, expires=$3, last_used=$4, rotated_date=$4
+    WHERE selector=$1 AND validator_hash=$5";
+pub const DELETE_REMEMBER_TOKEN: &str = "DELETE FROM remember_token WHERE selector=$1";
+pub const DELETE_REMEMBER_TOKENS_FOR: &str = "DELETE FROM remember_token WHERE cid=$1";
+pub const DELETE_EXPIRED_REMEMBER_TOKENS: &str = "DELETE FROM remember_token WHERE expires < $1";
+
+pub const GET_ALL_CONTROLLER_ROLES: &str = "SELECT cid, roles FROM controller WHERE roles != ''";
+pub const GET_ROLE_PERMISSIONS: &str = "SELECT * FROM role_permission ORDER BY role, permission";
+pub const GRANT_ROLE_PERMISSION: &str = "INSERT OR IGNORE INTO role_permission VALUES ($1, $2)";
+pub const REVOKE_ROLE_PERMISSION: &str =
+    "DELETE FROM role_permission WHERE role=$1 AND permission=$2";
+pub const GET_CONTROLLER_PERMISSIONS_FOR: &str = "SELECT * FROM controller_permission WHERE cid=$1";
+pub const GET_ALL_CONTROLLER_PERMISSIONS: &str =
+    "SELECT * FROM controller_permission ORDER BY cid, permission";
+pub const SET_CONTROLLER_PERMISSION: &str = "INSERT INTO controller_permission VALUES ($1, $2, $3) ON CONFLICT(cid, permission) DO UPDATE SET granted=excluded.granted";
+pub const DELETE_CONTROLLER_PERMISSION: &str =
+    "DELETE FROM controller_permission WHERE cid=$1 AND permission=$2";
+
+pub const START_IMPERSONATION: &str =
+    "INSERT INTO impersonation_log VALUES (NULL, $1, $2, $3, NULL)";
+/// End the staff member's latest open view-as session of the controller.
+pub const END_IMPERSONATION: &str = "UPDATE impersonation_log SET ended_date=$3 WHERE id=(
+    SELECT MAX(id) FROM impersonation_log WHERE staff_cid=$1 AND viewed_cid=$2 AND ended_date IS NULL
+)";
+pub const GET_RECENT_IMPERSONATIONS: &str =
+    "SELECT * FROM impersonation_log ORDER BY started_date DESC LIMIT $1";
+
+pub const GET_PENDING_WELCOME_EMAILS: &str = "SELECT controller.* FROM welcome_message JOIN controller ON welcome_message.cid=controller.cid WHERE email_sent_date IS NULL";
+pub const SET_WELCOME_EMAIL_SENT: &str =
+    "UPDATE welcome_message SET email_sent_date=$2 WHERE cid=$1";
+
+pub const QUEUE_ROSTER_REFRESH: &str = "INSERT INTO roster_refresh VALUES ($1, $2, $3) ON CONFLICT(cid) DO UPDATE SET reason=excluded.reason, queued_date=excluded.queued_date";
+pub const GET_QUEUED_ROSTER_REFRESHES: &str = "SELECT * FROM roster_refresh ORDER BY queued_date";
+
+pub const CREATE_OAUTH_GRANT: &str =
+    "INSERT INTO oauth_grant VALUES (NULL, $1, $2, $3, $4, $5, $6, $7, NULL, NULL, $8)";
+pub const GET_OAUTH_GRANT_BY_CODE: &str =
+    "SELECT * FROM oauth_grant WHERE code_hash=$1 AND code_expires > $2";
+pub const EXCHANGE_OAUTH_CODE: &str =
+    "UPDATE oauth_grant SET code_hash=NULL, token_hash=$2, token_expires=$3 WHERE id=$1 AND code_hash IS NOT NULL";
+pub const GET_OAUTH_GRANT_BY_TOKEN: &str =
+    "SELECT * FROM oauth_grant WHERE token_hash=$1 AND token_expires > $2";
+pub const DELETE_EXPIRED_OAUTH_GRANTS: &str = "DELETE FROM oauth_grant WHERE (token_expires IS NULL AND code_expires < $1) OR token_expires < $1";
+
+pub const GET_CALENDAR_TOKEN: &str = "SELECT token FROM calendar_token WHERE cid=$1";
+pub const GET_CALENDAR_TOKEN_CID: &str = "SELECT cid FROM calendar_token WHERE token=$1";
+pub const UPSERT_CALENDAR_TOKEN: &str = "INSERT INTO calendar_token VALUES ($1, $2, $3) ON CONFLICT(cid) DO UPDATE SET token=excluded.token, created_date=excluded.created_date";
+
+pub const QUEUE_EXIT_SURVEY: &str =
+    "INSERT INTO exit_survey (id, cid, departure, created_date) VALUES (NULL, $1, $2, $3)";
+pub const GET_UNSENT_EXIT_SURVEYS: &str = "SELECT * FROM exit_survey WHERE email_sent_date IS NULL";
+pub const SET_EXIT_SURVEY_SENT: &str =
+    "UPDATE exit_survey SET token=$2, email_sent_date=$3 WHERE id=$1";
+pub const GET_EXIT_SURVEY_BY_TOKEN: &str = "SELECT * FROM exit_survey WHERE token=$1";
+pub const SUBMIT_EXIT_SURVEY: &str = "UPDATE exit_survey SET submitted_date=$2, rating=$3, reason=$4, comments=$5 WHERE id=$1 AND submitted_date IS NULL";
+pub const GET_SUBMITTED_EXIT_SURVEYS: &str =
+    "SELECT * FROM exit_survey WHERE submitted_date IS NOT NULL ORDER BY submitted_date DESC";
+
+pub const GET_NOTIFICATION_PREFERENCE: &str = "SELECT * FROM notification_preference WHERE cid=$1";
+pub const SET_NOTIFICATION_PREFERENCE: &str = "INSERT INTO notification_preference VALUES ($1, $2, $3) ON CONFLICT(cid) DO UPDATE SET feedback_email=excluded.feedback_email, feedback_discord=excluded.feedback_discord";
+
+pub const SET_DISCORD_LINK_CODE: &str = "INSERT INTO discord_link_code VALUES ($1, $2, $3) ON CONFLICT(cid) DO UPDATE SET code=excluded.code, expires=excluded.expires";
+pub const GET_DISCORD_LINK_CODE_FOR: &str =
+    "SELECT * FROM discord_link_code WHERE cid=$1 AND expires > $2";
+pub const DELETE_EXPIRED_DISCORD_LINK_CODES: &str =
+    "DELETE FROM discord_link_code WHERE expires < $1";
+
+pub const INSERT_PURGE_CANDIDATE: &str = "INSERT OR IGNORE INTO purge_candidate (id, cid, quarter, minutes, created_date) VALUES (NULL, $1, $2, $3, $4)";
+pub const GET_LATEST_PURGE_QUARTER: &str = "SELECT MAX(quarter) FROM purge_candidate";
+pub const GET_PURGE_CANDIDATES: &str =
+    "SELECT * FROM purge_candidate WHERE quarter=$1 ORDER BY cid";
+pub const SET_PURGE_CANDIDATE_EXCLUDED: &str =
+    "UPDATE purge_candidate SET excluded=$2, approved_date=NULL, approved_by=NULL, skip_reason=NULL
+    WHERE id=$1 AND removed_date IS NULL";
+pub const SET_PURGE_CANDIDATE_REMOVED: &str =
+    "UPDATE purge_candidate SET removed_date=$2, removed_by=$3, error=NULL WHERE id=$1";
+/// Queue the quarter's remaining candidates for removal.
+pub const APPROVE_PURGE_CANDIDATES: &str =
+    "UPDATE purge_candidate SET approved_date=$2, approved_by=$3, error=NULL
+    WHERE quarter=$1 AND NOT excluded AND removed_date IS NULL AND approved_date IS NULL";
+pub const GET_APPROVED_PURGE_CANDIDATES: &str = "SELECT * FROM purge_candidate
+    WHERE approved_date IS NOT NULL AND removed_date IS NULL AND NOT excluded ORDER BY id";
+/// Record why the removal failed and take the candidate out of the queue until it's approved again.
+pub const SET_PURGE_CANDIDATE_ERROR: &str =
+    "UPDATE purge_candidate SET error=$2, approved_date=NULL, approved_by=NULL WHERE id=$1";
+/// Take an approved candidate who no longer needs removing out of the purge.
+pub const SET_PURGE_CANDIDATE_SKIPPED: &str = "UPDATE purge_candidate
+    SET excluded=TRUE, skip_reason=$2, approved_date=NULL, approved_by=NULL, error=NULL WHERE id=$1";
+
+/// Events that ended before $1 with no registrations, assigned controllers, or credited activity.
+pub const GET_STALE_EVENTS: &str = "SELECT * FROM event WHERE end < $1
+    AND NOT EXISTS (SELECT 1 FROM event_registration WHERE event_id=event.id)
+    AND NOT EXISTS (SELECT 1 FROM event_position WHERE event_id=event.id AND cid IS NOT NULL)
+    AND NOT EXISTS (SELECT 1 FROM activity WHERE event_id=event.id)";
+pub const DELETE_EVENT_POSITIONS_FOR: &str = "DELETE FROM event_position WHERE event_id=$1";
+pub const DELETE_ORPHANED_EVENT_REGISTRATIONS: &str =
+    "DELETE FROM event_registration WHERE event_id NOT IN (SELECT id FROM event)";
+pub const DELETE_ORPHANED_EVENT_POSITIONS: &str =
+    "DELETE FROM event_position WHERE event_id NOT IN (SELECT id FROM event)";
+pub const DELETE_ORPHANED_EVENT_CHANNELS: &str =
+    "DELETE FROM event_channel WHERE event_id NOT IN (SELECT id FROM event)";
+pub const DELETE_ORPHANED_EVENT_CATEGORY_LIMITS: &str =
+    "DELETE FROM event_category_limit WHERE event_id NOT IN (SELECT id FROM event)";
+pub const DELETE_ORPHANED_EVENT_WAITLIST: &str =
+    "DELETE FROM event_waitlist WHERE event_id NOT IN (SELECT id FROM event)";
+/// The site creates the sessions table on startup, so it may not exist yet.
+pub const SESSIONS_TABLE_EXISTS: &str =
+    "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type='table' AND name='tower_sessions')";
+pub const DELETE_EXPIRED_SESSIONS: &str =
+    "DELETE FROM tower_sessions WHERE expiry_date < datetime('now', 'utc')";
+pub const GET_RESOURCE_FILE_NAMES: &str =
+    "SELECT file_name FROM resource WHERE file_name IS NOT NULL";
+
+/// Rollup rebuilds: each table is cleared and then refilled from the raw tables.
+pub const REBUILD_STATS: [(&str, &str); 4] = [
+    (
+        "DELETE FROM stats_month_hours",
+        "INSERT INTO stats_month_hours
+        SELECT month,
+            SUM(CASE WHEN source='controlling' THEN minutes ELSE 0 END),
+            SUM(CASE WHEN source='event' THEN minutes ELSE 0 END),
+            SUM(CASE WHEN source='training' THEN minutes ELSE 0 END),
+            COUNT(DISTINCT cid)
+        FROM activity GROUP BY month",
+    ),
+    (
+        "DELETE FROM stats_month_feedback",
+        "INSERT INTO stats_month_feedback
+        SELECT substr(created_date, 1, 7), rating, COUNT(*)
+        FROM feedback GROUP BY substr(created_date, 1, 7), rating",
+    ),
+    (
+        "DELETE FROM stats_event",
+        "INSERT INTO stats_event
+        SELECT id, name, start,
+            (SELECT COUNT(*) FROM event_position WHERE event_id=event.id),
+            (SELECT COUNT(*) FROM event_position WHERE event_id=event.id AND cid IS NOT NULL),
+            (SELECT COUNT(*) FROM event_registration WHERE event_id=event.id)
+        FROM event WHERE published=TRUE",
+    ),
+    (
+        "DELETE FROM stats_leaderboard",
+        "INSERT INTO stats_leaderboard
+        SELECT month, rank, cid, minutes FROM (
+            SELECT month, cid, SUM(minutes) AS minutes,
+                ROW_NUMBER() OVER (PARTITION BY month ORDER BY SUM(minutes) DESC, cid) AS rank
+            FROM activity WHERE source='controlling' GROUP BY month, cid
+        ) WHERE rank <= 10",
+    ),
+];
+pub const GET_STATS_MONTH_HOURS_SINCE: &str =
+    "SELECT * FROM stats_month_hours WHERE month >= $1 ORDER BY month DESC";
+pub const GET_STATS_MONTH_FEEDBACK_SINCE: &str =
+    "SELECT * FROM stats_month_feedback WHERE month >= $1 ORDER BY month DESC, rating";
+pub const GET_STATS_EVENTS_SINCE: &str =
+    "SELECT * FROM stats_event WHERE start >= $1 ORDER BY start DESC";
+pub const GET_STATS_LEADERBOARD_MONTHS: &str =
+    "SELECT DISTINCT month FROM stats_leaderboard ORDER BY month DESC";
+pub const GET_STATS_LEADERBOARD: &str = "SELECT stats_leaderboard.*, controller.first_name, controller.last_name FROM stats_leaderboard INNER JOIN controller ON stats_leaderboard.cid = controller.cid WHERE month=$1 ORDER BY rank";
+
+/// Quarterly report queries; months are inclusive, timestamps from $1 up to $2.
+pub const GET_STATS_MONTH_HOURS_BETWEEN: &str =
+    "SELECT * FROM stats_month_hours WHERE month >= $1 AND month <= $2 ORDER BY month";
+pub const GET_STATS_EVENTS_BETWEEN: &str =
+    "SELECT * FROM stats_event WHERE start >= $1 AND start < $2 ORDER BY start";
+/// Training records, distinct students, and distinct instructors.
+pub const GET_TRAINING_RECORD_COUNTS_BETWEEN: &str = "SELECT COUNT(*), COUNT(DISTINCT cid), COUNT(DISTINCT instructor_cid) FROM training_record WHERE date >= $1 AND date < $2";
+pub const COUNT_TRAINING_SESSIONS_BETWEEN: &str =
+    "SELECT COUNT(*) FROM training_session WHERE cancelled=FALSE AND start >= $1 AND start < $2";
+pub const GET_CERTIFICATIONS_GRANTED_BETWEEN: &str = "SELECT name, COUNT(*) FROM certification_history WHERE new_value='certified' AND changed_on >= $1 AND changed_on < $2 GROUP BY name ORDER BY name";
+pub const GET_ROSTER_JOINS_BETWEEN: &str = "SELECT * FROM controller WHERE is_on_roster=TRUE AND join_date >= $1 AND join_date < $2 ORDER BY join_date";
+pub const GET_ROSTER_DEPARTURES_BETWEEN: &str = "SELECT exit_survey.cid, controller.first_name, controller.last_name, exit_survey.departure, exit_survey.created_date FROM exit_survey JOIN controller ON exit_survey.cid=controller.cid WHERE exit_survey.created_date >= $1 AND exit_survey.created_date < $2 ORDER BY exit_survey.created_date";
+
+pub const GET_SETTING: &str = "SELECT value FROM setting WHERE name=$1";
+pub const SET_SETTING: &str =
+    "INSERT INTO setting VALUES ($1, $2) ON CONFLICT(name) DO UPDATE SET value=excluded.value";
+
+/// One row per problem found, up to 100, or a single "ok".
+pub const INTEGRITY_CHECK: &str = "PRAGMA integrity_check(100)";
+/// Returns whether it was blocked, the pages in the WAL, and the pages checkpointed.
+pub const WAL_CHECKPOINT: &str = "PRAGMA wal_checkpoint(TRUNCATE)";
+
+pub const COUNT_PENDING_FEEDBACK: &str =
+    "SELECT COUNT(*) FROM feedback WHERE reviewer_action='pending'";
+pub const COUNT_PENDING_FEEDBACK_ASSIGNED_TO: &str =
+    "SELECT COUNT(*) FROM feedback WHERE reviewer_action='pending' AND assigned_to_cid=$1";
+pub const COUNT_PENDING_VISITOR_REQUESTS: &str =
+    "SELECT COUNT(*) FROM visitor_request WHERE status='pending'";
+/// Candidates from the latest purge still waiting on a decision.
+pub const COUNT_UNDECIDED_PURGE_CANDIDATES: &str = "SELECT COUNT(*) FROM purge_candidate
+    WHERE quarter=(SELECT MAX(quarter) FROM purge_candidate) AND removed_date IS NULL AND NOT excluded";
+pub const COUNT_TRAINING_WAITLIST: &str =
+    "SELECT COUNT(*) FROM training_waitlist WHERE promoted_date IS NULL";
@@ -0,0 +1,135 @@
+//! Exporting the staff roster as contacts, for keeping external contact lists in sync.
+//!
+//! Each position holder is a contact, with the position's facility email alias
+//! when it has one. Vacant positions with an alias are included so the alias
+//! stays in the list.
+
+use crate::ics::{escape, push_line};
+
+/// Organization name on the contacts.
+const ORGANIZATION: &str = "vZDV";
+
+/// Staff position, and who holds it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaffContact {
+    /// Like "ATM".
+    pub position: String,
+    /// Like "Air Traffic Manager".
+    pub title: String,
+    /// CID, first name, and last name; `None` for vacant positions.
+    pub holder: Option<(u32, String, String)>,
+    /// Facility email alias for the position.
+    pub email: Option<String>,
+}
+
+impl StaffContact {
+    fn display_name(&self) -> String {
+        match &self.holder {
+            Some((_, first, last)) => format!("{first} {last}"),
+            None => format!("{ORGANIZATION} {}", self.title),
+        }
+    }
+}
+
+/// Write the contacts as vCards (RFC 6350), one per contact.
+pub fn write_vcards(contacts: &[StaffContact]) -> String {
+    let mut out = String::new();
+    for contact in contacts {
+        push_line(&mut out, "BEGIN:VCARD");
+        push_line(&mut out, "VERSION:4.0");
+        let uid = match &contact.holder {
+            Some((cid, _, _)) => format!("vzdv-staff-{}-{cid}", contact.position),
+            None => format!("vzdv-staff-{}", contact.position),
+        };
+        push_line(&mut out, &format!("UID:{}", escape(&uid)));
+        push_line(&mut out, &format!("FN:{}", escape(&contact.display_name())));
+        if let Some((_, first, last)) = &contact.holder {
+            push_line(
+                &mut out,
+                &format!("N:{};{};;;", escape(last), escape(first)),
+            );
+        }
+        push_line(&mut out, &format!("ORG:{ORGANIZATION}"));
+        push_line(&mut out, &format!("TITLE:{}", escape(&contact.title)));
+        if let Some(email) = &contact.email {
+            push_line(&mut out, &format!("EMAIL;TYPE=work:{}", escape(email)));
+        }
+        push_line(&mut out, "END:VCARD");
+    }
+    out
+}
+
+/// Quote the CSV field if needed.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+/// Write the contacts as CSV with a header row.
+pub fn write_csv(contacts: &[StaffContact]) -> String {
+    let mut out = String::from("Position,Title,CID,First name,Last name,Email\r\n");
+    for contact in contacts {
+        let (cid, first, last) = match &contact.holder {
+            Some((cid, first, last)) => (cid.to_string(), first.as_str(), last.as_str()),
+            None => (String::new(), "", ""),
+        };
+        let fields = [
+            contact.position.as_str(),
+            contact.title.as_str(),
+            &cid,
+            first,
+            last,
+            contact.email.as_deref().unwrap_or_default(),
+        ];
+        out.push_str(&fields.map(csv_field).join(","));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{write_csv, write_vcards, StaffContact};
+
+    fn contacts() -> Vec<StaffContact> {
+        vec![
+            StaffContact {
+                position: "ATM".to_owned(),
+                title: "Air Traffic Manager".to_owned(),
+                holder: Some((1, "First".to_owned(), "Last, Jr".to_owned())),
+                email: Some("atm@example.com".to_owned()),
+            },
+            StaffContact {
+                position: "EC".to_owned(),
+                title: "Events Coordinator".to_owned(),
+                holder: None,
+                email: Some("ec@example.com".to_owned()),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_write_vcards() {
+        let vcards = write_vcards(&contacts());
+        assert_eq!(vcards.matches("BEGIN:VCARD\r\n").count(), 2);
+        assert!(vcards.contains("UID:vzdv-staff-ATM-1\r\n"));
+        assert!(vcards.contains("FN:First Last\\, Jr\r\n"));
+        assert!(vcards.contains("N:Last\\, Jr;First;;;\r\n"));
+        assert!(vcards.contains("EMAIL;TYPE=work:atm@example.com\r\n"));
+        assert!(vcards.contains("FN:vZDV Events Coordinator\r\n"));
+        assert!(vcards.ends_with("END:VCARD\r\n"));
+    }
+
+    #[test]
+    fn test_write_csv() {
+        assert_eq!(
+            write_csv(&contacts()),
+            "Position,Title,CID,First name,Last name,Email\r\n\
+             ATM,Air Traffic Manager,1,First,\"Last, Jr\",atm@example.com\r\n\
+             EC,Events Coordinator,,,,ec@example.com\r\n"
+        );
+    }
+}
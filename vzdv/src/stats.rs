@@ -0,0 +1,92 @@
+//! Precomputed aggregates for the stats and leaderboard pages.
+//!
+//! The rollup tables are rebuilt wholesale by the task runner each night,
+//! so the pages read a handful of rows instead of scanning the raw tables.
+
+use crate::sql;
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+
+/// Setting storing when the rollups were last rebuilt.
+pub const ROLLUP_DATE_SETTING: &str = "stats_rollup_date";
+
+/// Rebuild every rollup table from the raw tables.
+///
+/// Done in a single transaction so the pages never see a partial rebuild.
+pub async fn rebuild_rollups(db: &SqlitePool, now: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+    for (clear, fill) in sql::REBUILD_STATS {
+        sqlx::query(clear).execute(&mut *tx).await?;
+        sqlx::query(fill).execute(&mut *tx).await?;
+    }
+    sqlx::query(sql::SET_SETTING)
+        .bind(ROLLUP_DATE_SETTING)
+        .bind(now.to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::rebuild_rollups;
+    use crate::{
+        db::run_migrations,
+        sql::{self, StatsLeaderboardEntry, StatsMonthHours},
+    };
+    use chrono::Utc;
+    use sqlx::{sqlite::SqlitePoolOptions, Executor};
+
+    #[tokio::test]
+    async fn test_rebuild_rollups() {
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        db.execute(sql::CREATE_TABLES).await.unwrap();
+        run_migrations(&db).await.unwrap();
+        for cid in [1, 2] {
+            sqlx::query("INSERT INTO controller (cid, first_name, last_name, rating, is_on_roster) VALUES ($1, 'First', 'Last', 1, TRUE)")
+                .bind(cid)
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+        for (cid, minutes, source) in [
+            (1, 120, "controlling"),
+            (2, 300, "controlling"),
+            (2, 60, "event"),
+        ] {
+            sqlx::query("INSERT INTO activity (id, cid, month, minutes, source) VALUES (NULL, $1, '2026-09', $2, $3)")
+                .bind(cid)
+                .bind(minutes)
+                .bind(source)
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+
+        // rebuilding twice doesn't duplicate anything
+        rebuild_rollups(&db, Utc::now()).await.unwrap();
+        rebuild_rollups(&db, Utc::now()).await.unwrap();
+
+        let hours: Vec<StatsMonthHours> = sqlx::query_as(sql::GET_STATS_MONTH_HOURS_SINCE)
+            .bind("2026-01")
+            .fetch_all(&db)
+            .await
+            .unwrap();
+        assert_eq!(hours.len(), 1);
+        assert_eq!(hours[0].controlling, 420);
+        assert_eq!(hours[0].event, 60);
+        assert_eq!(hours[0].controllers, 2);
+
+        let leaderboard: Vec<StatsLeaderboardEntry> = sqlx::query_as(sql::GET_STATS_LEADERBOARD)
+            .bind("2026-09")
+            .fetch_all(&db)
+            .await
+            .unwrap();
+        let ranked: Vec<_> = leaderboard.iter().map(|e| (e.rank, e.cid)).collect();
+        assert_eq!(ranked, vec![(1, 2), (2, 1)]);
+    }
+}
@@ -1,13 +1,16 @@
 use std::collections::HashMap;
 
-use crate::{config::Config, get_controller_cids_and_names, position_in_facility_airspace};
+use crate::{
+    config::Config, get_controller_cids_and_names, position_in_facility_airspace,
+    request_id::WithRequestId,
+};
 use anyhow::{bail, Result};
 use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
 use log::error;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use sqlx::SqlitePool;
-use vatsim_utils::live_api::Vatsim;
+use vatsim_utils::{live_api::Vatsim, models::V3ResponseData};
 
 /// Parse a VATSIM timestamp into a `chrono::DateTime`.
 pub fn parse_vatsim_timestamp(stamp: &str) -> Result<DateTime<Utc>> {
@@ -26,14 +29,41 @@ pub struct OnlineController {
     pub cid: u32,
     pub callsign: String,
     pub name: String,
+    pub frequency: String,
+    /// Position type from the callsign's suffix; see [`position_type`].
+    pub position_type: &'static str,
     pub online_for: String,
 }
 
+/// Position types in display order, from highest to lowest.
+pub const POSITION_TYPES: [&str; 5] = ["CTR", "APP", "TWR", "GND", "Other"];
+
+/// Group a callsign into one of the [`POSITION_TYPES`] by its suffix.
+pub fn position_type(callsign: &str) -> &'static str {
+    match callsign.rsplit('_').next().unwrap_or_default() {
+        "CTR" | "FSS" | "TMU" => "CTR",
+        "APP" | "DEP" => "APP",
+        "TWR" => "TWR",
+        "GND" | "DEL" => "GND",
+        _ => "Other",
+    }
+}
+
 /// Get facility controllers currently online.
 pub async fn get_online_facility_controllers(
     db: &SqlitePool,
     config: &Config,
 ) -> Result<Vec<OnlineController>> {
+    let data = Vatsim::new().await?.get_v3_data().await?;
+    Ok(online_facility_controllers(db, config, &data).await)
+}
+
+/// Get facility controllers online in an already-fetched datafeed.
+pub async fn online_facility_controllers(
+    db: &SqlitePool,
+    config: &Config,
+    data: &V3ResponseData,
+) -> Vec<OnlineController> {
     let cid_name_map = match get_controller_cids_and_names(db).await {
         Ok(map) => map,
         Err(e) => {
@@ -43,9 +73,7 @@ pub async fn get_online_facility_controllers(
     };
 
     let now = chrono::Utc::now();
-    let data = Vatsim::new().await?.get_v3_data().await?;
-    let online: Vec<_> = data
-        .controllers
+    data.controllers
         .iter()
         .filter(|controller| position_in_facility_airspace(config, &controller.callsign))
         .map(|controller| {
@@ -59,12 +87,12 @@ pub async fn get_online_facility_controllers(
                     .get(&(controller.cid as u32))
                     .map(|s| format!("{} {}", s.0, s.1))
                     .unwrap_or(String::from("?")),
+                frequency: controller.frequency.clone(),
+                position_type: position_type(&controller.callsign),
                 online_for: format!("{}h{}m", seconds / 3600, (seconds / 60) % 60),
             }
         })
-        .collect();
-
-    Ok(online)
+        .collect()
 }
 
 #[derive(Debug, Deserialize)]
@@ -148,6 +176,7 @@ pub async fn code_to_tokens(code: &str, config: &Config) -> Result<TokenResponse
             "redirect_uri": config.vatsim.oauth_client_callback_url,
             "code": code
         }))
+        .with_request_id()
         .send()
         .await?;
     if !resp.status().is_success() {
@@ -166,6 +195,7 @@ pub async fn get_user_info(access_token: &str, config: &Config) -> Result<UserIn
     let resp = client
         .get(format!("{}api/user", config.vatsim.oauth_url_base))
         .header("Authorization", &format!("Bearer {}", access_token))
+        .with_request_id()
         .send()
         .await?;
     if !resp.status().is_success() {
@@ -177,3 +207,17 @@ pub async fn get_user_info(access_token: &str, config: &Config) -> Result<UserIn
     let data = resp.json().await?;
     Ok(data)
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::position_type;
+
+    #[test]
+    fn test_position_type() {
+        assert_eq!(position_type("DEN_CTR"), "CTR");
+        assert_eq!(position_type("DEN_N_DEP"), "APP");
+        assert_eq!(position_type("DEN_TWR"), "TWR");
+        assert_eq!(position_type("DEN_DEL"), "GND");
+        assert_eq!(position_type("DEN_ATIS"), "Other");
+    }
+}
@@ -1,20 +1,98 @@
-use crate::GENERAL_HTTP_CLIENT;
-use anyhow::{bail, Result};
-use chrono::NaiveDateTime;
-use serde::{Deserialize, Serialize};
+use crate::{request_id::WithRequestId, GENERAL_HTTP_CLIENT};
+use chrono::{NaiveDate, NaiveDateTime};
+use reqwest::StatusCode;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::json;
-use std::collections::HashMap;
-use tokio::task::JoinSet;
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{sync::Semaphore, task::JoinSet};
 
 const BASE_URL: &str = "https://api.vatusa.net/";
 
+/// How long public controller info documents are reused before being re-fetched.
+const CONTROLLER_INFO_TTL: Duration = Duration::from_secs(60 * 15);
+
+/// Max number of in-flight controller info requests from `get_multiple_controller_info`.
+const CONTROLLER_INFO_CONCURRENCY: usize = 5;
+
+/// Cache of public controller info documents, keyed by CID.
+///
+/// Only info retrieved without an API key is stored, so private
+/// information (like email addresses) is never served from here.
+static CONTROLLER_INFO_CACHE: LazyLock<Mutex<HashMap<u32, (Instant, RosterMember)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Errors from calling the VATUSA API.
+///
+/// Each variant that comes from a response carries the name of the API
+/// that was called, since the URL can't be logged (it may have the API key).
+#[derive(Debug, thiserror::Error)]
+pub enum VatusaError {
+    #[error("VATUSA {0} API rejected the API key")]
+    Unauthorized(&'static str),
+    #[error("VATUSA {0} API returned not found")]
+    NotFound(&'static str),
+    #[error("VATUSA {0} API is rate limiting requests")]
+    RateLimited(&'static str),
+    #[error("got status {1} from VATUSA {0} API")]
+    Status(&'static str, u16),
+    #[error("could not parse response from VATUSA {0} API: {1}")]
+    Deserialization(&'static str, reqwest::Error),
+    #[error(transparent)]
+    Http(reqwest::Error),
+}
+
+/// Drops the URL, which may have the API key in its query string.
+impl From<reqwest::Error> for VatusaError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Http(e.without_url())
+    }
+}
+
+impl VatusaError {
+    /// Whether the same call may succeed if tried again later.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::RateLimited(_) | Self::Http(_) => true,
+            Self::Status(_, status) => *status >= 500,
+            _ => false,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, VatusaError>;
+
+/// Convert a non-success response status into the matching error.
+fn check_status(resp: &reqwest::Response, api: &'static str) -> Result<()> {
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    Err(match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => VatusaError::Unauthorized(api),
+        StatusCode::NOT_FOUND => VatusaError::NotFound(api),
+        StatusCode::TOO_MANY_REQUESTS => VatusaError::RateLimited(api),
+        _ => VatusaError::Status(api, status.as_u16()),
+    })
+}
+
+/// Parse the response body, keeping track of which API it came from.
+async fn parse_json<T: DeserializeOwned>(resp: reqwest::Response, api: &'static str) -> Result<T> {
+    resp.json()
+        .await
+        .map_err(|e| VatusaError::Deserialization(api, e.without_url()))
+}
+
 pub enum MembershipType {
     Home,
     Visit,
     Both,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct RosterMemberRole {
     pub id: u32,
     pub cid: u32,
@@ -23,7 +101,7 @@ pub struct RosterMemberRole {
     pub created_at: String,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct RosterMemberVisiting {
     pub id: u32,
     pub cid: u32,
@@ -32,7 +110,7 @@ pub struct RosterMemberVisiting {
     pub updated_at: String,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct RosterMember {
     pub cid: u32,
     #[serde(rename = "fname")]
@@ -88,16 +166,11 @@ pub async fn get_roster(facility: &str, membership: MembershipType) -> Result<Ve
     };
     let resp = GENERAL_HTTP_CLIENT
         .get(format!("{BASE_URL}facility/{facility}/roster/{mem_str}"))
+        .with_request_id()
         .send()
         .await?;
-    if !resp.status().is_success() {
-        bail!(
-            "Got status {} from VATUSA roster API at {}",
-            resp.status().as_u16(),
-            resp.url()
-        );
-    }
-    let data: Wrapper = resp.json().await?;
+    check_status(&resp, "roster")?;
+    let data: Wrapper = parse_json(resp, "roster").await?;
     Ok(data.data)
 }
 
@@ -143,16 +216,11 @@ pub async fn transfer_checklist(api_key: &str, cid: u32) -> Result<TransferCheck
     let resp = GENERAL_HTTP_CLIENT
         .get(format!("{BASE_URL}v2/user/{cid}/transfer/checklist"))
         .query(&[("apikey", api_key)])
+        .with_request_id()
         .send()
         .await?;
-    if !resp.status().is_success() {
-        // not including the URL since it'll have the API key in it
-        bail!(
-            "Got status {} from VATUSA transfer checklist API",
-            resp.status().as_u16()
-        );
-    }
-    let data: Wrapper = resp.json().await?;
+    check_status(&resp, "transfer checklist")?;
+    let data: Wrapper = parse_json(resp, "transfer checklist").await?;
     Ok(data.data)
 }
 
@@ -169,26 +237,60 @@ pub async fn get_controller_info(cid: u32, api_key: Option<&str>) -> Result<Rost
     if let Some(key) = api_key {
         req = req.query(&[("apikey", key)]);
     }
-    let resp = req.send().await?;
-    if !resp.status().is_success() {
-        bail!(
-            // not including the URL since it may have the API key in it
-            "Got status {} from VATUSA controller info API",
-            resp.status().as_u16()
-        );
-    }
-    let data: Wrapper = resp.json().await?;
+    let resp = req.with_request_id().send().await?;
+    check_status(&resp, "controller info")?;
+    let data: Wrapper = parse_json(resp, "controller info").await?;
     Ok(data.data)
 }
 
-/// Get multiple controller info documents at the same time.
+/// Get the controller's public information, using the cache if the
+/// stored document is fresh enough.
+pub async fn get_controller_info_cached(cid: u32) -> Result<RosterMember> {
+    {
+        let cache = CONTROLLER_INFO_CACHE.lock().unwrap();
+        if let Some((fetched, info)) = cache.get(&cid) {
+            if fetched.elapsed() < CONTROLLER_INFO_TTL {
+                return Ok(info.clone());
+            }
+        }
+    }
+    let info = get_controller_info(cid, None).await?;
+    insert_evicting_expired(
+        &mut CONTROLLER_INFO_CACHE.lock().unwrap(),
+        cid,
+        info.clone(),
+        Instant::now(),
+    );
+    Ok(info)
+}
+
+/// Store the value, dropping any expired ones so the cache doesn't keep every CID ever looked up.
+fn insert_evicting_expired<T>(
+    cache: &mut HashMap<u32, (Instant, T)>,
+    cid: u32,
+    value: T,
+    now: Instant,
+) {
+    cache.retain(|_, (fetched, _)| now.duration_since(*fetched) < CONTROLLER_INFO_TTL);
+    cache.insert(cid, (now, value));
+}
+
+/// Get multiple controller public info documents at the same time.
+///
+/// Documents are served from the cache where possible, and at most
+/// `CONTROLLER_INFO_CONCURRENCY` requests are made at once.
 ///
 /// Instead of returning errors, this function simply omits info
 /// from any request that failed.
 pub async fn get_multiple_controller_info(cids: &[u32]) -> Vec<RosterMember> {
+    let semaphore = Arc::new(Semaphore::new(CONTROLLER_INFO_CONCURRENCY));
     let mut set = JoinSet::new();
     for &cid in cids {
-        set.spawn(async move { get_controller_info(cid, None).await });
+        let semaphore = semaphore.clone();
+        set.spawn(async move {
+            let _permit = semaphore.acquire().await;
+            get_controller_info_cached(cid).await
+        });
     }
     let mut info = Vec::new();
     while let Some(res) = set.join_next().await {
@@ -217,17 +319,100 @@ pub async fn add_visiting_controller(cid: u32, api_key: &str) -> Result<()> {
             "{BASE_URL}v2/facility/ZDV/roster/manageVisitor/{cid}"
         ))
         .query(&[("apikey", api_key)])
+        .with_request_id()
         .send()
         .await?;
-    if !resp.status().is_success() {
-        bail!(
-            "Got status {} from VATUSA API to add a visiting controller",
-            resp.status().as_u16()
-        );
-    }
+    check_status(&resp, "visitor add")?;
+    Ok(())
+}
+
+/// Remove a visiting controller from the roster.
+pub async fn remove_visiting_controller(cid: u32, reason: &str, api_key: &str) -> Result<()> {
+    let resp = GENERAL_HTTP_CLIENT
+        .delete(format!(
+            "{BASE_URL}v2/facility/ZDV/roster/manageVisitor/{cid}"
+        ))
+        .query(&[("apikey", api_key)])
+        .form(&[("reason", reason)])
+        .with_request_id()
+        .send()
+        .await?;
+    check_status(&resp, "visitor removal")?;
     Ok(())
 }
 
+/// Remove a home controller from the roster.
+///
+/// VATUSA requires a reason, which is visible to the controller.
+pub async fn remove_home_controller(cid: u32, reason: &str, api_key: &str) -> Result<()> {
+    let resp = GENERAL_HTTP_CLIENT
+        .delete(format!("{BASE_URL}v2/facility/ZDV/roster/{cid}"))
+        .query(&[("apikey", api_key)])
+        .form(&[("reason", reason)])
+        .with_request_id()
+        .send()
+        .await?;
+    check_status(&resp, "home controller removal")?;
+    Ok(())
+}
+
+/// Give the controller a facility role, like "INS" or "MTR".
+pub async fn add_facility_role(cid: u32, role: &str, api_key: &str) -> Result<()> {
+    let resp = GENERAL_HTTP_CLIENT
+        .post(format!("{BASE_URL}v2/user/{cid}/roles/ZDV/{role}"))
+        .query(&[("apikey", api_key)])
+        .with_request_id()
+        .send()
+        .await?;
+    check_status(&resp, "facility role add")?;
+    Ok(())
+}
+
+/// Remove a facility role from the controller.
+pub async fn remove_facility_role(cid: u32, role: &str, api_key: &str) -> Result<()> {
+    let resp = GENERAL_HTTP_CLIENT
+        .delete(format!("{BASE_URL}v2/user/{cid}/roles/ZDV/{role}"))
+        .query(&[("apikey", api_key)])
+        .with_request_id()
+        .send()
+        .await?;
+    check_status(&resp, "facility role removal")?;
+    Ok(())
+}
+
+/// A controller's membership on the facility's VATUSA roster.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum RosterStatus {
+    Home,
+    Visiting,
+    NotOnRoster,
+}
+
+impl RosterStatus {
+    /// Membership on the facility's roster from the controller's VATUSA info.
+    pub fn of(info: &RosterMember) -> Self {
+        if info.facility == "ZDV" {
+            return Self::Home;
+        }
+        let visiting = info
+            .visiting_facilities
+            .as_ref()
+            .is_some_and(|visits| visits.iter().any(|visit| visit.facility == "ZDV"));
+        if visiting {
+            Self::Visiting
+        } else {
+            Self::NotOnRoster
+        }
+    }
+}
+
+/// Get the controller's current membership on the facility's roster
+/// according to VATUSA.
+pub async fn get_roster_status(cid: u32, api_key: &str) -> Result<RosterStatus> {
+    let info = get_controller_info(cid, Some(api_key)).await?;
+    Ok(RosterStatus::of(&info))
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct TrainingRecord {
     pub id: u32,
@@ -250,16 +435,40 @@ pub async fn get_training_records(api_key: &str, cid: u32) -> Result<Vec<Trainin
     let resp = GENERAL_HTTP_CLIENT
         .get(format!("{BASE_URL}v2/user/{cid}/training/records"))
         .query(&[("apikey", api_key)])
+        .with_request_id()
         .send()
         .await?;
-    if !resp.status().is_success() {
-        // not including the URL since it'll have the API key in it
-        bail!(
-            "Got status {} from VATUSA training records API",
-            resp.status().as_u16()
-        );
+    check_status(&resp, "training records")?;
+    let data: Wrapper = parse_json(resp, "training records").await?;
+    Ok(data.data)
+}
+
+/// Result of a VATUSA Academy exam the controller took.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ExamResult {
+    pub id: u32,
+    pub exam_name: String,
+    pub score: u32,
+    pub passed: bool,
+    /// Like "2024-06-01 18:00:00".
+    pub date: String,
+}
+
+/// Get the controller's exam results.
+pub async fn get_exam_results(api_key: &str, cid: u32) -> Result<Vec<ExamResult>> {
+    #[derive(Deserialize)]
+    pub struct Wrapper {
+        pub data: Vec<ExamResult>,
     }
-    let data: Wrapper = resp.json().await?;
+
+    let resp = GENERAL_HTTP_CLIENT
+        .get(format!("{BASE_URL}v2/user/{cid}/exam/history"))
+        .query(&[("apikey", api_key)])
+        .with_request_id()
+        .send()
+        .await?;
+    check_status(&resp, "exam history")?;
+    let data: Wrapper = parse_json(resp, "exam history").await?;
     Ok(data.data)
 }
 
@@ -300,14 +509,114 @@ pub async fn save_training_record(api_key: &str, cid: u32, data: &NewTrainingRec
             "location": data.location,
             "notes": data.notes
         }))
+        .with_request_id()
         .send()
         .await?;
-    if !resp.status().is_success() {
-        // not including the URL since it'll have the API key in it
-        bail!(
-            "Got status {} from VATUSA training record submit API",
-            resp.status().as_u16()
-        );
+    check_status(&resp, "training record submit")?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SoloCert {
+    pub id: u32,
+    pub cid: u32,
+    pub position: String,
+    pub expires: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Get all active solo certifications.
+///
+/// Unlike most endpoints, this returns certs for all facilities.
+pub async fn get_solo_certs(api_key: &str) -> Result<Vec<SoloCert>> {
+    #[derive(Deserialize)]
+    pub struct Wrapper {
+        pub data: Vec<SoloCert>,
     }
+
+    let resp = GENERAL_HTTP_CLIENT
+        .get(format!("{BASE_URL}v2/solo"))
+        .query(&[("apikey", api_key)])
+        .with_request_id()
+        .send()
+        .await?;
+    check_status(&resp, "solo cert list")?;
+    let data: Wrapper = parse_json(resp, "solo cert list").await?;
+    Ok(data.data)
+}
+
+/// Issue a solo certification to the controller for the position.
+///
+/// The position must be a valid callsign, like "DEN_APP".
+pub async fn create_solo_cert(
+    api_key: &str,
+    cid: u32,
+    position: &str,
+    expires: NaiveDate,
+) -> Result<()> {
+    let resp = GENERAL_HTTP_CLIENT
+        .post(format!("{BASE_URL}v2/solo"))
+        .query(&[("apikey", api_key)])
+        .form(&[
+            ("cid", cid.to_string()),
+            ("position", position.to_owned()),
+            ("expDate", expires.format("%Y-%m-%d").to_string()),
+        ])
+        .with_request_id()
+        .send()
+        .await?;
+    check_status(&resp, "solo cert submit")?;
+    Ok(())
+}
+
+/// Remove the controller's solo certification for the position.
+pub async fn delete_solo_cert(api_key: &str, cid: u32, position: &str) -> Result<()> {
+    let resp = GENERAL_HTTP_CLIENT
+        .delete(format!("{BASE_URL}v2/solo"))
+        .query(&[("apikey", api_key)])
+        .form(&[("cid", cid.to_string()), ("position", position.to_owned())])
+        .with_request_id()
+        .send()
+        .await?;
+    check_status(&resp, "solo cert delete")?;
     Ok(())
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::{insert_evicting_expired, VatusaError, CONTROLLER_INFO_TTL};
+    use std::{
+        collections::HashMap,
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn test_insert_evicting_expired() {
+        let start = Instant::now();
+        let mut cache = HashMap::new();
+        insert_evicting_expired(&mut cache, 1, "old", start);
+        insert_evicting_expired(&mut cache, 2, "newer", start + CONTROLLER_INFO_TTL / 2);
+        insert_evicting_expired(
+            &mut cache,
+            3,
+            "new",
+            start + CONTROLLER_INFO_TTL + Duration::from_secs(1),
+        );
+        let mut cids: Vec<_> = cache.keys().copied().collect();
+        cids.sort();
+        assert_eq!(cids, vec![2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_error_hides_api_key() {
+        // nothing listens on port 1, so this fails without leaving the machine
+        let e = reqwest::get("http://127.0.0.1:1/user/1?apikey=secret")
+            .await
+            .unwrap_err();
+        assert!(e.to_string().contains("secret"));
+        let e = VatusaError::from(e);
+        assert!(!e.to_string().contains("secret"));
+        assert!(e.is_retryable());
+    }
+}
@@ -0,0 +1,166 @@
+//! Waitlists for certifications with a limited number of training slots.
+//!
+//! Students join a certification's waitlist and are promoted into training,
+//! in priority order, whenever fewer students than the configured capacity
+//! are in training for it.
+
+use crate::{
+    config::Config,
+    sql::{self, WaitlistEntry},
+};
+use chrono::{DateTime, Datelike, Months, Utc};
+use sqlx::SqlitePool;
+use std::{cmp::Ordering, collections::HashMap};
+
+/// Priority rules, as set in the config.
+pub mod priority {
+    /// Longest on the roster first.
+    pub const ROSTER_TIME: &str = "roster_time";
+    /// Most recent activity first.
+    pub const ACTIVITY: &str = "activity";
+    /// Longest since their last training session first, starting with those who've never had one.
+    pub const LAST_SESSION: &str = "last_session";
+}
+
+/// Months of activity, including the current one, that count for the activity rule.
+pub const ACTIVITY_MONTHS: u32 = 3;
+
+/// First activity month that counts for the activity rule, like "2026-08".
+pub fn activity_since(now: DateTime<Utc>) -> String {
+    let date = now
+        .date_naive()
+        .with_day(1)
+        .unwrap()
+        .checked_sub_months(Months::new(ACTIVITY_MONTHS - 1))
+        .unwrap();
+    date.format("%Y-%m").to_string()
+}
+
+/// Compare two waiting students by a single rule; `Less` goes first.
+fn compare_by(rule: &str, a: &WaitlistEntry, b: &WaitlistEntry) -> Ordering {
+    match rule {
+        // missing dates sort last for roster time, but first for the last session
+        priority::ROSTER_TIME => match (a.join_date, b.join_date) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        },
+        priority::ACTIVITY => b.recent_minutes.cmp(&a.recent_minutes),
+        priority::LAST_SESSION => a.last_session.cmp(&b.last_session),
+        _ => Ordering::Equal,
+    }
+}
+
+/// Sort the waiting students by the priority rules, then by when they joined.
+pub fn sort_by_priority(entries: &mut [WaitlistEntry], rules: &[String]) {
+    entries.sort_by(|a, b| {
+        rules
+            .iter()
+            .map(|rule| compare_by(rule, a, b))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+            .then(a.joined_date.cmp(&b.joined_date))
+    });
+}
+
+/// Everyone still waiting, by certification, in priority order.
+pub async fn get_waitlists(
+    db: &SqlitePool,
+    config: &Config,
+    now: DateTime<Utc>,
+) -> sqlx::Result<HashMap<String, Vec<WaitlistEntry>>> {
+    let entries: Vec<WaitlistEntry> = sqlx::query_as(sql::GET_WAITLIST)
+        .bind(activity_since(now))
+        .fetch_all(db)
+        .await?;
+    let mut waitlists: HashMap<String, Vec<WaitlistEntry>> = HashMap::new();
+    for entry in entries {
+        waitlists
+            .entry(entry.certification.clone())
+            .or_default()
+            .push(entry);
+    }
+    for entries in waitlists.values_mut() {
+        sort_by_priority(entries, &config.training.waitlist.priority);
+    }
+    Ok(waitlists)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{activity_since, get_waitlists, priority};
+    use crate::{config::Config, db::run_migrations, sql};
+    use chrono::{TimeZone, Utc};
+    use sqlx::{sqlite::SqlitePoolOptions, Executor};
+
+    #[test]
+    fn test_activity_since() {
+        let now = Utc.with_ymd_and_hms(2026, 1, 31, 12, 0, 0).unwrap();
+        assert_eq!(activity_since(now), "2025-11");
+    }
+
+    #[tokio::test]
+    async fn test_get_waitlists() {
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        db.execute(sql::CREATE_TABLES).await.unwrap();
+        run_migrations(&db).await.unwrap();
+        let now = Utc.with_ymd_and_hms(2026, 10, 2, 0, 0, 0).unwrap();
+        let long_ago = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let recently = Utc.with_ymd_and_hms(2026, 8, 1, 0, 0, 0).unwrap();
+        for (cid, joined) in [(1, long_ago), (2, recently), (3, recently)] {
+            sqlx::query("INSERT INTO controller (cid, first_name, last_name, rating, is_on_roster, join_date) VALUES ($1, '', '', 3, TRUE, $2)")
+                .bind(cid)
+                .bind(joined)
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+        // 3 is the most active, and 2 hasn't had a session since long ago
+        sqlx::query("INSERT INTO activity (id, cid, month, minutes) VALUES (NULL, 3, '2026-09', 600), (NULL, 3, '2026-01', 6000), (NULL, 1, '2026-10', 60)")
+            .execute(&db)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO training_record VALUES (NULL, 1, 0, 'DEN_GND', $1, '', NULL), (NULL, 2, 0, 'DEN_GND', $2, '', NULL)")
+            .bind(recently)
+            .bind(long_ago)
+            .execute(&db)
+            .await
+            .unwrap();
+        for (cid, minutes_ago) in [(1, 3), (2, 2), (3, 1)] {
+            sqlx::query(sql::JOIN_WAITLIST)
+                .bind(cid)
+                .bind("APP T1")
+                .bind(now - chrono::Duration::minutes(minutes_ago))
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+
+        let order = |config: &Config| {
+            let db = db.clone();
+            let config = config.clone();
+            async move {
+                get_waitlists(&db, &config, now).await.unwrap()["APP T1"]
+                    .iter()
+                    .map(|entry| entry.cid)
+                    .collect::<Vec<_>>()
+            }
+        };
+        let mut config = Config::default();
+        assert_eq!(order(&config).await, vec![1, 2, 3]);
+        config.training.waitlist.priority = vec![priority::ACTIVITY.to_owned()];
+        assert_eq!(order(&config).await, vec![3, 1, 2]);
+        config.training.waitlist.priority = vec![priority::LAST_SESSION.to_owned()];
+        assert_eq!(order(&config).await, vec![3, 2, 1]);
+        config.training.waitlist.priority = vec![
+            priority::ROSTER_TIME.to_owned(),
+            priority::ACTIVITY.to_owned(),
+        ];
+        assert_eq!(order(&config).await, vec![1, 3, 2]);
+    }
+}